@@ -0,0 +1,395 @@
+// Choosing where the private key is kept: a (possibly passphrase-encrypted) key file,
+// the OS keychain, a hardware token, ssh-agent, or the TPM. Each is opened as an idp-core `KeyStore`.
+
+use clap::ValueEnum;
+use idp_core::keystore::{FileKeyStore, KeyStore, PassphraseRequest};
+use idp_core::signer::SigningBackend;
+use idp_core::{crypto, mnemonic, Identity, IdpError, IdpId, KeyStatus, SecretBytes};
+
+/// Environment variable that supplies the passphrase non-interactively (for scripts and CI).
+const PASSPHRASE_ENV: &str = "IDP_PASSPHRASE";
+
+/// Where the private key is kept.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keystore {
+    /// A key file next to the identity, optionally encrypted with a passphrase.
+    File,
+    /// The platform keychain (macOS Keychain, Windows Credential Manager, Secret Service).
+    Os,
+    /// The signature key of a YubiKey or other OpenPGP card; the key never leaves the device.
+    Yubikey,
+    /// An Ed25519 key loaded in a running ssh-agent; idp never sees the private key.
+    SshAgent,
+    /// A P-256 key generated inside this machine's TPM 2.0; it cannot be copied off it.
+    Tpm,
+}
+
+/// Where the key of a new software identity comes from.
+pub enum KeySource<'a> {
+    /// A freshly generated key of the given algorithm (e.g. `crypto::ML_DSA_65`).
+    Generate(&'a str),
+    /// A key derived from a 24-word recovery phrase.
+    RecoveryPhrase(&'a str),
+    /// An existing key of the given algorithm (e.g. PKCS#8 DER or PEM, or a raw seed).
+    Import(&'a [u8], &'a str),
+}
+
+impl Keystore {
+    /// Human-readable location of the key, for status messages.
+    pub fn describe(self, key_file_name: &str) -> String {
+        match self {
+            Keystore::File => key_file_name.to_string(),
+            Keystore::Os => "the OS keychain".to_string(),
+            Keystore::Yubikey => "your hardware token".to_string(),
+            Keystore::SshAgent => "your ssh-agent".to_string(),
+            Keystore::Tpm => "this machine's TPM".to_string(),
+        }
+    }
+
+    /// Fails early if the chosen key store can't be used in this build or on this machine.
+    pub fn check_available(self) -> Result<(), IdpError> {
+        match self {
+            Keystore::File => Ok(()),
+            Keystore::Os => os::available(),
+            Keystore::Yubikey => token::available(),
+            Keystore::SshAgent => agent::available(),
+            Keystore::Tpm => tpm::available(),
+        }
+    }
+
+    /// Opens the key store for an identity.
+    pub fn open(self, idp_id: &IdpId, key_file_name: &str) -> Result<Box<dyn KeyStore>, IdpError> {
+        self.check_available()?;
+        Ok(match self {
+            Keystore::File => Box::new(key_file(key_file_name)),
+            Keystore::Os => os::open(idp_id)?,
+            Keystore::Yubikey | Keystore::SshAgent | Keystore::Tpm => Box::new(DeviceStore(self)),
+        })
+    }
+
+    /// Creates a new identity around the device's (or agent's) key, or around a software key
+    /// from `source`, optionally with a post-quantum half, signed by that key. The TPM key is
+    /// generated on first use.
+    pub fn init(self, name: &str, bio: &str, key_file_name: &str, source: KeySource, hybrid: bool) -> Result<Identity, IdpError> {
+        if matches!(self, Keystore::Yubikey | Keystore::SshAgent | Keystore::Tpm) {
+            let store = DeviceStore(self);
+            if hybrid {
+                return Err(IdpError::Keystore(format!("a post-quantum half cannot be derived from the key in {}", store.describe())));
+            }
+            match source {
+                KeySource::Generate(crypto::ED25519) => {}
+                KeySource::Generate(crypto::P256) if self == Keystore::Tpm => {}
+                KeySource::Generate(algorithm) => {
+                    return Err(IdpError::Keystore(format!("{} cannot hold {} keys", store.describe(), algorithm)));
+                }
+                _ => return Err(store.cannot_store()),
+            }
+            if self == Keystore::Tpm && tpm::connect().is_err() {
+                tpm::generate()?;
+            }
+            let value = store
+                .list()?
+                .into_iter()
+                .next()
+                .ok_or_else(|| IdpError::Keystore(format!("{} holds no usable key", store.describe())))?;
+            let signer = store.load(&value)?;
+            let public_key = idp_core::PublicKey {
+                key_id: "root-key-01".to_string(),
+                algorithm: signer.algorithm().to_string(),
+                value,
+                format: idp_core::KeyFormat::Base64,
+                status: KeyStatus::Active,
+                parent_key_id: None,
+                purpose: idp_core::KeyPurpose::Signing,
+                expires_at: None,
+                unknown_fields: Default::default(),
+            };
+            let mut identity = Identity::from_public_key(name, bio, public_key);
+            identity.sign_document(signer.as_ref())?;
+            return Ok(identity);
+        }
+
+        let (mut identity, private_key) = match source {
+            KeySource::Generate(algorithm) => Identity::new_with_algorithm(name, bio, algorithm)?,
+            KeySource::RecoveryPhrase(phrase) => {
+                Identity::from_existing_key(name, bio, &mnemonic::derive_keypair(phrase)?.private_key_bytes)?
+            }
+            KeySource::Import(key_material, algorithm) => {
+                Identity::from_existing_key_with_algorithm(name, bio, key_material, algorithm)?
+            }
+        };
+        if hybrid {
+            identity.add_post_quantum_key(&private_key)?;
+        }
+        self.open(&identity.identity.id, key_file_name)?.store(&private_key)?;
+        identity.sign_document(&private_key)?;
+        Ok(identity)
+    }
+}
+
+/// A key file, asking for passphrases on the terminal (or reading `IDP_PASSPHRASE`).
+pub fn key_file(path: &str) -> FileKeyStore {
+    FileKeyStore::new(path, prompt_passphrase)
+}
+
+/// The private key behind `key`, for commands that need the key material itself.
+pub fn software_key<'a>(store: &dyn KeyStore, key: &'a dyn SigningBackend) -> Result<&'a [u8], IdpError> {
+    key.software_key()
+        .ok_or_else(|| IdpError::Keystore(format!("the key in {} cannot be exported; this needs a software key", store.describe())))
+}
+
+/// Saves the identity, signed by the successor of `old_key`, together with that successor,
+/// never leaving the two out of step: if the identity cannot be saved, the old key is put back.
+pub fn replace(
+    store: &dyn KeyStore,
+    identity: &mut Identity,
+    id_file_name: &str,
+    old_key: &dyn SigningBackend,
+    new_private_key: &SecretBytes,
+) -> Result<(), IdpError> {
+    store.store(new_private_key)?;
+    if let Err(e) = identity.save_signed_to_file(id_file_name, new_private_key) {
+        if let Some(old_private_key) = old_key.software_key() {
+            let _ = store.store(old_private_key);
+        }
+        return Err(e);
+    }
+    crate::log_change(identity, new_private_key, id_file_name);
+    Ok(())
+}
+
+fn prompt_passphrase(request: PassphraseRequest) -> Result<Option<String>, IdpError> {
+    match request {
+        PassphraseRequest::Unlock(path) => match std::env::var(PASSPHRASE_ENV) {
+            Ok(passphrase) => Ok(Some(passphrase)),
+            Err(_) => Ok(Some(rpassword::prompt_password(format!("Passphrase for '{}': ", path.display()))?)),
+        },
+        PassphraseRequest::New(_) => prompt_new_passphrase(),
+    }
+}
+
+/// Asks for a new passphrase twice. An empty passphrase means "store the key unencrypted".
+fn prompt_new_passphrase() -> Result<Option<String>, IdpError> {
+    if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        return Ok(Some(passphrase).filter(|p| !p.is_empty()));
+    }
+
+    let passphrase = rpassword::prompt_password("Choose a passphrase for your key (leave empty for none): ")?;
+    if passphrase.is_empty() {
+        println!("⚠️  No passphrase chosen: the private key will be stored unencrypted.");
+        return Ok(None);
+    }
+    let confirmation = rpassword::prompt_password("Repeat the passphrase: ")?;
+    if passphrase != confirmation {
+        return Err(IdpError::Crypto("the passphrases do not match".to_string()));
+    }
+    Ok(Some(passphrase))
+}
+
+/// Keys that live on a device or in an agent: idp can sign with them but never store them.
+struct DeviceStore(Keystore);
+
+impl DeviceStore {
+    fn cannot_store(&self) -> IdpError {
+        let advice = match self.0 {
+            Keystore::SshAgent => "create the key with `ssh-keygen -t ed25519` and `ssh-add` it instead",
+            _ => "generate them on the device instead",
+        };
+        IdpError::Keystore(format!("keys cannot be written to {} by idp; {}", self.describe(), advice))
+    }
+}
+
+impl KeyStore for DeviceStore {
+    fn describe(&self) -> String {
+        self.0.describe("")
+    }
+
+    fn list(&self) -> Result<Vec<String>, IdpError> {
+        match self.0 {
+            Keystore::SshAgent => agent::list(),
+            Keystore::Tpm => Ok(vec![tpm::connect()?.public_key_value()?]),
+            _ => Ok(vec![token::connect()?.public_key_value()?]),
+        }
+    }
+
+    fn load(&self, public_key_value: &str) -> Result<Box<dyn SigningBackend>, IdpError> {
+        let signer: Box<dyn SigningBackend> = match self.0 {
+            Keystore::SshAgent => return Ok(Box::new(agent::connect(public_key_value)?)),
+            Keystore::Tpm => Box::new(tpm::connect()?),
+            _ => Box::new(token::connect()?),
+        };
+        if signer.public_key_value()? != public_key_value {
+            return Err(IdpError::Keystore(format!("{} holds a different key", self.describe())));
+        }
+        Ok(signer)
+    }
+
+    fn store(&self, _private_key: &[u8]) -> Result<(), IdpError> {
+        Err(self.cannot_store())
+    }
+
+    fn delete(&self, _public_key_value: &str) -> Result<(), IdpError> {
+        Err(IdpError::Keystore(format!("remove the key from {} with its own tools", self.describe())))
+    }
+}
+
+// The OS keychain, hardware tokens and ssh-agent are optional features; without them,
+// selecting one is a clear error.
+#[cfg(feature = "os-keystore")]
+mod os {
+    use idp_core::keystore::{os::OsKeyStore, KeyStore};
+    use idp_core::{IdpError, IdpId};
+
+    pub fn available() -> Result<(), IdpError> {
+        Ok(())
+    }
+
+    pub fn open(idp_id: &IdpId) -> Result<Box<dyn KeyStore>, IdpError> {
+        Ok(Box::new(OsKeyStore::new(idp_id)))
+    }
+}
+
+#[cfg(not(feature = "os-keystore"))]
+mod os {
+    use idp_core::keystore::KeyStore;
+    use idp_core::{IdpError, IdpId};
+
+    fn unsupported() -> IdpError {
+        IdpError::Keystore("this build of idp has no OS keychain support (rebuild with the `os-keystore` feature)".to_string())
+    }
+
+    pub fn available() -> Result<(), IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn open(_idp_id: &IdpId) -> Result<Box<dyn KeyStore>, IdpError> {
+        Err(unsupported())
+    }
+}
+
+/// Stands in for an external signer in builds without support for it.
+#[cfg(any(not(feature = "yubikey"), not(all(feature = "ssh-agent", unix)), not(feature = "tpm")))]
+enum Unsupported {}
+
+#[cfg(any(not(feature = "yubikey"), not(all(feature = "ssh-agent", unix)), not(feature = "tpm")))]
+impl SigningBackend for Unsupported {
+    fn public_key_value(&self) -> Result<String, IdpError> {
+        match *self {}
+    }
+
+    fn sign(&self, _message: &[u8]) -> Result<idp_core::SignatureComponent, IdpError> {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "yubikey")]
+mod token {
+    use idp_core::signer::yubikey::YubiKeySigner;
+    use idp_core::IdpError;
+
+    pub fn available() -> Result<(), IdpError> {
+        Ok(())
+    }
+
+    pub fn connect() -> Result<YubiKeySigner, IdpError> {
+        YubiKeySigner::connect()
+    }
+}
+
+#[cfg(not(feature = "yubikey"))]
+mod token {
+    use super::Unsupported;
+    use idp_core::IdpError;
+
+    fn unsupported() -> IdpError {
+        IdpError::Keystore("this build of idp has no hardware token support (rebuild with the `yubikey` feature)".to_string())
+    }
+
+    pub fn available() -> Result<(), IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn connect() -> Result<Unsupported, IdpError> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(all(feature = "ssh-agent", unix))]
+mod agent {
+    use idp_core::signer::ssh_agent::SshAgentSigner;
+    use idp_core::IdpError;
+
+    pub fn available() -> Result<(), IdpError> {
+        Ok(())
+    }
+
+    pub fn list() -> Result<Vec<String>, IdpError> {
+        SshAgentSigner::list()
+    }
+
+    pub fn connect(public_key_value: &str) -> Result<SshAgentSigner, IdpError> {
+        SshAgentSigner::connect(public_key_value)
+    }
+}
+
+#[cfg(not(all(feature = "ssh-agent", unix)))]
+mod agent {
+    use super::Unsupported;
+    use idp_core::IdpError;
+
+    fn unsupported() -> IdpError {
+        IdpError::Keystore("this build of idp has no ssh-agent support (rebuild with the `ssh-agent` feature)".to_string())
+    }
+
+    pub fn available() -> Result<(), IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn list() -> Result<Vec<String>, IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn connect(_public_key_value: &str) -> Result<Unsupported, IdpError> {
+        Err(unsupported())
+    }
+}
+
+#[cfg(feature = "tpm")]
+mod tpm {
+    use idp_core::signer::tpm::TpmSigner;
+    use idp_core::IdpError;
+
+    pub fn available() -> Result<(), IdpError> {
+        Ok(())
+    }
+
+    pub fn connect() -> Result<TpmSigner, IdpError> {
+        TpmSigner::connect()
+    }
+
+    pub fn generate() -> Result<TpmSigner, IdpError> {
+        TpmSigner::generate()
+    }
+}
+
+#[cfg(not(feature = "tpm"))]
+mod tpm {
+    use super::Unsupported;
+    use idp_core::IdpError;
+
+    fn unsupported() -> IdpError {
+        IdpError::Keystore("this build of idp has no TPM support (rebuild with the `tpm` feature)".to_string())
+    }
+
+    pub fn available() -> Result<(), IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn connect() -> Result<Unsupported, IdpError> {
+        Err(unsupported())
+    }
+
+    pub fn generate() -> Result<Unsupported, IdpError> {
+        Err(unsupported())
+    }
+}
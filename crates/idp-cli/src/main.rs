@@ -1,23 +1,74 @@
 // 🧬 The command-line interface for the Identity Protocol.
 // This tool allows users to create, manage, and verify their sovereign identity.
 
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Utc};
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
 // We import the full suite of structs needed to construct and load an Identity.
-use idp_core::Identity;
+use idp_core::config::Config;
+use idp_core::{ConsentPurpose, Identity, IdpError, KeyPurpose, KeyStatus, SecretBytes};
+use idp_core::events::EventLog;
+use idp_core::git::GitHistory;
+use idp_core::keystore::KeyStore;
+use idp_core::snapshot;
+use idp_core::lock::{FileLock, LockMode};
+use idp_core::registry::IssuerRegistry;
+use idp_core::signer::SigningBackend;
+use idp_core::templates::{ContractTemplate, CredentialTemplate};
+use idp_core::validate::Severity;
+use idp_core::trust::IdentityResolver;
 
-use std::path::Path; // To handle the file path
+use std::io::Write;
+use std::path::{Path, PathBuf}; // To handle file paths
+use std::sync::atomic::{AtomicBool, Ordering};
+
+mod keystore;
+
+use keystore::{KeySource, Keystore};
 
 /// A sovereign, quantum-resistant identity management tool.
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
+    /// The identity file to work on (or set IDP_FILE); by default, the profile's, or `my.idp` in the current directory.
+    #[arg(short = 'f', long = "file", global = true, value_name = "PATH")]
+    identity_file: Option<String>,
+
+    /// The key file (or set IDP_KEY_FILE); by default, the identity file's name ending in `.key`.
+    #[arg(long, global = true, value_name = "PATH")]
+    key_file: Option<String>,
+
+    /// Work on this profile's identity, with its settings (or set IDP_PROFILE); see `idp profile`.
+    #[arg(long, global = true, value_name = "NAME")]
+    profile: Option<String>,
+
+    /// How to print what commands report: text, or JSON or YAML for scripts (or set IDP_OUTPUT).
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+
+    /// Where the private key is kept (or set IDP_KEYSTORE).
+    #[arg(long, global = true, value_enum, default_value_t = Keystore::File)]
+    keystore: Keystore,
+
+    /// Do not lock the identity file; only for file systems without locks, with no other `idp` running.
+    #[arg(long, global = true)]
+    no_lock: bool,
+
+    /// Commit every change to the identity file in its git repository, signed with your key (or set IDP_GIT=1).
+    #[arg(long, global = true)]
+    git: bool,
+
+    /// Move expired consents to `archived_consent` whenever the identity file is saved (or set IDP_ARCHIVE_CONSENT=1).
+    #[arg(long, global = true)]
+    archive_consent: bool,
+
     #[command(subcommand)]
     command: Commands,
 }
 
 #[derive(Subcommand, Debug)]
 enum Commands {
-    /// Initialize a new identity file in the current directory.
+    /// Initialize a new identity file: the profile's, the one `--file` names, or `my.idp` in the current directory.
     Init {
         /// The full name for the new identity.
         #[arg(short, long)]
@@ -26,9 +77,72 @@ enum Commands {
         /// A short bio for the new identity.
         #[arg(short, long)]
         bio: String,
+
+        /// Derive the key from a new 24-word recovery phrase and display it for backup.
+        #[arg(long, conflicts_with = "import_key")]
+        mnemonic: bool,
+
+        /// Use an existing private key instead of a new one: for Ed25519, PKCS#8 (DER or PEM) or a raw
+        /// seed; for secp256k1, a wallet's private key in hex, or PKCS#8.
+        #[arg(long, value_name = "PATH")]
+        import_key: Option<String>,
+
+        /// The signature algorithm of the new root key.
+        #[arg(long, value_enum, default_value_t = SignatureAlgorithm::Ed25519, conflicts_with = "mnemonic")]
+        algorithm: SignatureAlgorithm,
+
+        /// Also derive an ML-DSA-65 half of the Ed25519 root key, for hybrid signatures that hold
+        /// as long as either algorithm does.
+        #[arg(long, conflicts_with = "algorithm")]
+        hybrid: bool,
+
+        /// The format of the identity file; later commands keep it.
+        #[arg(long, value_enum, default_value_t = DocumentFormat::Yaml)]
+        format: DocumentFormat,
     },
+    /// Rebuild the private key of the identity from its 24-word recovery phrase.
+    Recover,
     /// Show the contents of the identity file.
     Show,
+    /// Check the identity file against the JSON Schema its `schema_url` names.
+    Validate,
+    /// Show what changed between the identity file and another version of it.
+    Diff {
+        /// The other version (e.g. a backup or a synced copy).
+        other: String,
+    },
+    /// Merge another device's version of the identity file into this one.
+    Merge {
+        /// The last version both devices had.
+        base: String,
+        /// The other device's version.
+        theirs: String,
+    },
+    /// Print the whole identity document, e.g. as JSON for tools that cannot read YAML,
+    /// or as CBOR (redirect it to a file).
+    Export {
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = DocumentFormat::Json)]
+        format: DocumentFormat,
+        /// Share only these paths, comma-separated (e.g. `core,credentials[0]`); the identity
+        /// and system blocks are always included so the view can be verified.
+        #[arg(long, value_delimiter = ',')]
+        only: Vec<String>,
+        /// Share only what this grantee may see under the consents you have given them; with --only, refuse any path they may not.
+        #[arg(long = "for", value_name = "GRANTEE")]
+        grantee: Option<String>,
+        /// Write a subject access report of everything the identity holds, as JSON and HTML, to this directory.
+        #[arg(long, value_name = "DIR", num_args = 0..=1, default_missing_value = "subject-access", conflicts_with_all = ["only", "grantee"])]
+        gdpr: Option<String>,
+    },
+    /// Print a value from the identity file.
+    Get {
+        /// The path to the value to read (e.g., "identity.id" or "credentials.*.claim").
+        path: String,
+        /// Print strings without YAML quoting.
+        #[arg(long)]
+        raw: bool,
+    },
     /// Set a value in the identity file.
     Set {
         /// The path to the value to set (e.g., "core.bio").
@@ -36,81 +150,3166 @@ enum Commands {
         /// The new value.
         value: String,
     },
+    /// Apply a JSON Patch (RFC 6902) to the identity file; nothing changes unless every operation applies.
+    Patch {
+        /// The patch file, a JSON array of operations.
+        file: String,
+    },
+    /// Manage the keys of the identity.
+    Key {
+        #[command(subcommand)]
+        action: KeyCommands,
+    },
+    /// Issue credentials to others, and keep the ones issued to you.
+    Credential {
+        #[command(subcommand)]
+        action: CredentialCommands,
+    },
+    /// Keep the status list that revokes credentials you issued, and publish it.
+    StatusList {
+        #[command(subcommand)]
+        action: StatusListCommands,
+    },
+    /// See and revoke the credentials you have issued.
+    Issued {
+        #[command(subcommand)]
+        action: IssuedCommands,
+    },
+    /// Anchor your document's hash, or a proof's, in Bitcoin with OpenTimestamps.
+    Anchor {
+        #[command(subcommand)]
+        action: AnchorCommands,
+    },
+    /// Vouch for a credential someone else issued, with your own signature, for its holder to add.
+    Endorse {
+        /// The credential, by its proof id.
+        proof_id: String,
+        /// The identity file of the holder.
+        #[arg(long)]
+        holder: String,
+        /// Where to write the endorsement; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Ask a holder to present credentials: writes a challenge with a fresh nonce for them to answer.
+    Challenge {
+        /// A claim to ask for, as its text or a claim type (repeatable).
+        #[arg(long = "claim", required = true)]
+        claims: Vec<String>,
+        /// Who you are to the holder, e.g. your id or domain; the presentation must be for it.
+        #[arg(long)]
+        audience: Option<String>,
+        /// How many minutes the holder has to answer, up to a week.
+        #[arg(long, default_value_t = 10, value_parser = clap::value_parser!(i64).range(1..=7 * 24 * 60))]
+        minutes: i64,
+        /// Where to write the challenge; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Answer a verifier's challenge with the credentials it asks for, signed with your key.
+    Present {
+        /// The challenge file written by `idp challenge`.
+        challenge: String,
+        /// The form to write: IDP's own YAML, or a W3C Verifiable Presentation (JSON).
+        #[arg(long, value_enum, default_value_t = PresentationFormat::Yaml)]
+        format: PresentationFormat,
+        /// Where to write the presentation; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Check a presentation against the challenge you sent, the holder's identity file and the issuers'.
+    VerifyPresentation {
+        /// The presentation file written by `idp present`.
+        file: String,
+        /// The challenge it answers.
+        #[arg(long)]
+        challenge: String,
+        /// The identity file of the holder.
+        #[arg(long)]
+        holder: String,
+        /// The identity file of an issuer (repeatable).
+        #[arg(long = "issuer")]
+        issuers: Vec<String>,
+    },
+    /// Sign a file or a message with your key, as a detached signature naming your id and key.
+    Sign {
+        /// The file to sign.
+        #[arg(required_unless_present = "message")]
+        file: Option<String>,
+        /// Sign this text instead of a file.
+        #[arg(long, conflicts_with = "file")]
+        message: Option<String>,
+        /// Where to write the signature; without one, a file's goes beside it as FILE.sig and a message's is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Check a detached signature written by `idp sign` against what it signed and the signer's identity file.
+    VerifySignature {
+        /// The signature file.
+        signature: String,
+        /// The file it signs.
+        #[arg(required_unless_present = "message")]
+        signed: Option<String>,
+        /// The text it signs, instead of a file.
+        #[arg(long, conflicts_with = "signed")]
+        message: Option<String>,
+        /// The identity file of the signer.
+        #[arg(long)]
+        signer: String,
+    },
+    /// Sign contracts with their other parties.
+    Contract {
+        #[command(subcommand)]
+        action: ContractCommands,
+    },
+    /// Grant, revoke and list consent for others to use fields of your identity.
+    Consent {
+        #[command(subcommand)]
+        action: ConsentCommands,
+    },
+    /// Keep several identities apart, each with its own key and settings, and choose which one commands work on.
+    Profile {
+        #[command(subcommand)]
+        action: ProfileCommands,
+    },
+    /// Issue signed reputation events to others, and add the ones issued to you.
+    Reputation {
+        #[command(subcommand)]
+        action: ReputationCommands,
+    },
+    /// Encrypt the identity file (as an age file) so only your key, and any extra recipients, can read it.
+    Encrypt {
+        /// Another age recipient (`age1...`) who may read the file, e.g. a backup key (repeatable).
+        #[arg(long = "recipient")]
+        recipients: Vec<String>,
+    },
+    /// Turn an encrypted identity file back into plain YAML, using your key file.
+    Decrypt,
+    /// Encrypt one section of the identity file to your key-agreement key; the rest stays public.
+    Seal {
+        #[arg(value_parser = idp_core::sealing::SEALABLE_SECTIONS)]
+        section: String,
+    },
+    /// Write a sealed section in the clear again, using your key.
+    Unseal {
+        #[arg(value_parser = idp_core::sealing::SEALABLE_SECTIONS)]
+        section: String,
+    },
+    /// Show the history of changes saved to the identity file, and check that it is intact.
+    Log {
+        /// Also print each change as JSON Patch operations.
+        #[arg(long)]
+        patch: bool,
+    },
+    /// Show the versions of the identity file committed to its git repository.
+    History {
+        /// Print the identity file as it was in this commit instead.
+        #[arg(long, value_name = "COMMIT")]
+        show: Option<String>,
+    },
+    /// Save a timestamped copy of the identity file, to roll back to later.
+    Snapshot {
+        /// List the snapshots taken so far instead.
+        #[arg(long)]
+        list: bool,
+    },
+    /// Restore the identity file from a snapshot, after checking it.
+    Rollback {
+        /// The snapshot file, as printed by `idp snapshot --list`.
+        snapshot: String,
+    },
+    /// Compress the identity file with zstd; it loads as before and stays compressed when saved.
+    Compact {
+        /// Write the file uncompressed again instead.
+        #[arg(long)]
+        expand: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum KeyCommands {
+    /// List the keys of the identity with their purpose and status.
+    List,
+    /// Replace the active key with a new one, endorsed by the old key.
+    Rotate,
+    /// Verify the signed chain of rotations from the key the id was made from to the current one.
+    Chain,
+    /// Revoke a key with a statement signed by your private key.
+    Revoke {
+        /// The id of the key to revoke (e.g., "root-key-01").
+        key_id: String,
+        /// Why the key is being revoked.
+        #[arg(short, long)]
+        reason: Option<String>,
+    },
+    /// Split the private key into Shamir shares for distributed backup.
+    Split {
+        /// How many shares to create.
+        #[arg(long, default_value_t = 5)]
+        shares: u8,
+        /// How many shares are needed to rebuild the key.
+        #[arg(long, default_value_t = 3)]
+        threshold: u8,
+    },
+    /// Rebuild the private key from Shamir shares.
+    Combine,
+    /// Derive a device subkey from your root key, valid only while the root key is active.
+    Derive {
+        /// The derivation path, e.g. "laptop" or "devices/phone".
+        path: String,
+        /// Where to write the subkey's private key.
+        #[arg(short, long)]
+        out: String,
+        /// What the subkey may be used for.
+        #[arg(long, default_value = "signing", value_parser = parse_purpose)]
+        purpose: KeyPurpose,
+        /// The subkey's signature algorithm, e.g. p-256 for WebCrypto; defaults to the root key's.
+        #[arg(long, value_enum)]
+        algorithm: Option<SignatureAlgorithm>,
+    },
+    /// Print public keys in a format other tools understand.
+    Export {
+        /// The key to export; without one, all active keys are exported.
+        key_id: Option<String>,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Jwk)]
+        format: ExportFormat,
+    },
+    /// Record the certifications others made on your OpenPGP certificate as proofs.
+    ImportPgp {
+        /// Your certificate as returned by the signer (e.g. from `gpg --export --armor`).
+        certificate: String,
+        /// The certificate of a PGP key whose certifications to import (repeatable).
+        #[arg(long = "signer", required = true)]
+        signers: Vec<String>,
+    },
+    /// Prove that a blockchain address is yours with a wallet signature, recorded as a proof.
+    /// Without an address, the address of your own secp256k1 key is attested.
+    AttestAddress {
+        /// The Ethereum address (0x...) to attest.
+        address: Option<String>,
+        /// The wallet's `personal_sign` signature (hex) over the statement this command prints.
+        #[arg(long, requires = "address")]
+        signature: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum StatusListCommands {
+    /// Start a status list, to be published at a URL verifiers can fetch.
+    Init {
+        /// Where you will publish the list, e.g. https://example.com/status/1.
+        url: String,
+        /// How many credentials it can hold.
+        #[arg(long, default_value_t = idp_core::status_list::MIN_LENGTH)]
+        length: usize,
+    },
+    /// Revoke the credential with this index in the list.
+    Revoke { index: usize },
+    /// Undo the revocation of the credential with this index.
+    Reinstate { index: usize },
+    /// Print the list as a signed BitstringStatusListCredential, to publish at its URL.
+    Publish {
+        /// Where to write it; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum AnchorCommands {
+    /// Send the hash to OpenTimestamps calendars; it reaches a block within hours.
+    Submit {
+        /// Anchor this proof, by its id, instead of the whole document.
+        #[arg(long)]
+        proof: Option<String>,
+        /// A calendar to use (repeatable); by default, the public pool calendars.
+        #[arg(long = "calendar")]
+        calendars: Vec<String>,
+    },
+    /// Ask the calendars of pending anchors for the blocks they are in by now.
+    Upgrade,
+    /// Check every anchor: that it still covers its target, and the blocks it reaches.
+    Verify {
+        /// The Esplora API to look blocks up in.
+        #[arg(long, default_value = idp_core::anchor::DEFAULT_ESPLORA)]
+        esplora: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum IssuedCommands {
+    /// List every credential you have issued, and where it stands.
+    List,
+    /// Revoke an issued credential by its id, and write the status list that says so.
+    Revoke {
+        /// The credential's id, as `idp issued list` shows it.
+        id: String,
+        /// Where to write the updated list; by default, the list's own path if it is a local file.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ConsentCommands {
+    /// Let another party use some of your fields for a purpose, until a time; granting the same purpose again renews it.
+    Grant {
+        /// Who may use them: an identity id, or any name you know them by.
+        to: String,
+        /// A field they may use, as a path like `core.name` (repeatable).
+        #[arg(long = "field", required = true)]
+        fields: Vec<String>,
+        /// What they may use them for: marketing, analytics, service_delivery, legal_obligation, research, or your own words.
+        #[arg(long)]
+        purpose: ConsentPurpose,
+        /// When the consent ends (e.g. 2026-12-31).
+        #[arg(long, value_parser = parse_time)]
+        expires: DateTime<Utc>,
+        /// Also write a signed receipt of the consent here, for the grantee to keep.
+        #[arg(long)]
+        receipt: Option<String>,
+    },
+    /// Withdraw consent; it stays listed as revoked.
+    Revoke {
+        /// Who holds it.
+        to: String,
+        /// Only the consent for this purpose; by default, every one they hold.
+        #[arg(long)]
+        purpose: Option<ConsentPurpose>,
+    },
+    /// List the consents you have given, and where each stands.
+    List {
+        /// Only the consents that hold now for this purpose.
+        #[arg(long)]
+        purpose: Option<ConsentPurpose>,
+    },
+    /// Show every grant, revocation, expiry and access under consent, and check that the log is whole.
+    Audit,
+    /// Check a consent receipt against the identity file of the party that granted it.
+    Verify {
+        /// The receipt, as written by `idp consent grant --receipt`.
+        receipt: String,
+        /// The identity file of the grantor.
+        #[arg(long)]
+        grantor: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ProfileCommands {
+    /// List the profiles; the current one is marked with `*`.
+    List,
+    /// Start a profile; make its identity with `idp --profile <name> init`.
+    Create {
+        /// Its name, in lower-case letters, digits, `-` and `_`, e.g. `work`.
+        name: String,
+    },
+    /// Make a profile the one commands work on when no --profile or --file is given.
+    Switch {
+        /// The profile to switch to, as listed by `idp profile list`.
+        #[arg(required_unless_present = "none")]
+        name: Option<String>,
+        /// Work on `my.idp` in the current directory again, with no profile.
+        #[arg(long, conflicts_with = "name")]
+        none: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ReputationCommands {
+    /// Sign a reputation event for another identity, to hand to them as a file.
+    Issue {
+        /// The identity file of the subject.
+        #[arg(long)]
+        to: String,
+        /// The score it counts toward, e.g. `seller`.
+        #[arg(long)]
+        score: String,
+        /// What happened, e.g. "sale completed".
+        #[arg(long)]
+        event: String,
+        /// How much it moves the score, up or down.
+        #[arg(long, allow_hyphen_values = true)]
+        change: i64,
+        /// Where to write the event (by default, stdout).
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Add a reputation event issued to you, after checking its issuer's signature.
+    Add {
+        /// The event file written by `idp reputation issue`.
+        file: String,
+        /// The identity file of its issuer.
+        #[arg(long)]
+        issuer: Option<String>,
+        /// Refuse events that no issuer has signed.
+        #[arg(long)]
+        strict: bool,
+    },
+    /// Sign a batch of reputation events for another identity, e.g. to move their history from your platform.
+    Export {
+        /// The identity file of the subject.
+        #[arg(long)]
+        to: String,
+        /// A YAML or JSON list of the events, each with `score_name`, `event`, `change` and optionally `timestamp`.
+        #[arg(long)]
+        events: String,
+        /// Where to write the batch (by default, stdout).
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Take in a batch of reputation events issued to you, after checking it; events you already have are skipped.
+    Import {
+        /// The batch file written by `idp reputation export`.
+        file: String,
+        /// The identity file of its issuer.
+        #[arg(long)]
+        issuer: String,
+    },
+    /// Show your scores, raw and as computed now from their history, and the scores others attest to.
+    Show {
+        /// Events count for half as much after this many days.
+        #[arg(long, value_name = "DAYS", value_parser = parse_half_life)]
+        half_life: Option<chrono::Duration>,
+        /// The most any one issuer can move a score, up or down.
+        #[arg(long)]
+        cap: Option<f64>,
+        /// The weight of an issuer's events as ID=WEIGHT (repeatable); others count in full.
+        #[arg(long = "weight", value_parser = parse_weight)]
+        weights: Vec<(String, f64)>,
+        /// The weight of events no issuer has signed.
+        #[arg(long, default_value_t = 1.0)]
+        unsigned_weight: f64,
+        /// List each score's events too.
+        #[arg(long)]
+        history: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum ContractCommands {
+    /// List the contract templates `idp contract new` can start from, or show one.
+    Templates {
+        /// Print this template as YAML, to copy into your templates directory and change.
+        #[arg(long)]
+        show: Option<String>,
+    },
+    /// Write a draft contract from a template, for its parties to sign with `idp contract sign --contract-file`.
+    New {
+        /// The template, e.g. `service-agreement`.
+        #[arg(long)]
+        template: String,
+        /// A value for one of the template's variables, as NAME=VALUE (repeatable).
+        #[arg(long = "var", value_parser = parse_var)]
+        vars: Vec<(String, String)>,
+        /// The contract's id; by default, the template's name and the time.
+        #[arg(long)]
+        id: Option<String>,
+        /// Where to write it; by default `<id>.yaml`.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Sign a contract in your document, or one another party shared as a file, which is written back with your signature.
+    Sign {
+        /// The contract, by its id; needed without --contract-file.
+        #[arg(required_unless_present = "file")]
+        contract_id: Option<String>,
+        /// A shared contract file to take the terms and other parties' signatures from.
+        #[arg(long = "contract-file", value_name = "PATH")]
+        file: Option<String>,
+    },
+    /// Add a signed note to a contract: a dispute, evidence, or how a dispute was resolved.
+    Annotate {
+        /// The contract, by its id; needed without --contract-file.
+        #[arg(required_unless_present = "file")]
+        contract_id: Option<String>,
+        /// What the note records.
+        #[arg(long, value_enum, default_value_t = AnnotationKind::Note)]
+        kind: AnnotationKind,
+        /// The note itself.
+        #[arg(long)]
+        text: String,
+        /// Where the evidence is, e.g. a URL or a document's hash.
+        #[arg(long)]
+        reference: Option<String>,
+        /// A shared contract file to take in first, and to write back with your note.
+        #[arg(long = "contract-file", value_name = "PATH")]
+        file: Option<String>,
+    },
+    /// Settle an active contract, and fire the hook its consequence names with the signed event.
+    Settle {
+        /// The contract, by its id; needed without --contract-file.
+        #[arg(required_unless_present = "file")]
+        contract_id: Option<String>,
+        /// How it ended.
+        #[arg(long, value_enum)]
+        outcome: Outcome,
+        /// A shared contract file to take in first, and to write back settled.
+        #[arg(long = "contract-file", value_name = "PATH")]
+        file: Option<String>,
+        /// Let the contract's consequence run a local command (`exec:...`); it is skipped otherwise.
+        #[arg(long)]
+        allow_commands: bool,
+        /// Where to write the signed settlement event, as JSON.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// List your contracts: those waiting for your signature or others', and those past their deadline.
+    Status,
+    /// Check a contract: every party's signature against its identity, its obligations, and its status.
+    Verify {
+        /// The contract, by its id; needed without --contract-file.
+        #[arg(required_unless_present = "file")]
+        contract_id: Option<String>,
+        /// A contract file to check instead of one in your document.
+        #[arg(long = "contract-file", value_name = "PATH")]
+        file: Option<String>,
+        /// The identity file of a party (repeatable); yours is always known.
+        #[arg(long = "party")]
+        parties: Vec<String>,
+        /// A directory of identity files to find parties in; by default, the configured `resolvers`.
+        #[arg(long)]
+        dir: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum CredentialCommands {
+    /// List the built-in claim types `idp credential issue --type` takes.
+    Types,
+    /// List the templates `idp credential issue --template` takes: built in, or yours in ~/.config/idp/templates.
+    Templates {
+        /// Print this template as YAML instead, e.g. to copy into the templates directory and change.
+        #[arg(long)]
+        show: Option<String>,
+    },
+    /// Sign a claim about another identity, to hand to them as a credential file.
+    Issue {
+        /// The identity file of the subject.
+        #[arg(long)]
+        to: String,
+        /// What you vouch for, e.g. "member of the chess club".
+        #[arg(long, required_unless_present_any = ["claim_type", "template"])]
+        claim: Option<String>,
+        /// Issue a typed claim instead, by schema name (see `idp credential types`) or type URI.
+        #[arg(long = "type", conflicts_with = "claim", requires = "fields")]
+        claim_type: Option<String>,
+        /// A field of the typed claim as NAME=VALUE, the value read as JSON if it is (repeatable).
+        #[arg(long = "field", value_parser = parse_field, requires = "claim_type")]
+        fields: Vec<(String, serde_json::Value)>,
+        /// Issue from a template instead (see `idp credential templates`).
+        #[arg(long, conflicts_with_all = ["claim", "claim_type"])]
+        template: Option<String>,
+        /// A variable of the template as NAME=VALUE (repeatable).
+        #[arg(long = "var", value_parser = parse_var, requires = "template")]
+        vars: Vec<(String, String)>,
+        /// When the credential expires (e.g. 2026-12-31); without one, it never does, or as the template says.
+        #[arg(long, value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+        /// Give it an entry in your status list, so you can revoke it later.
+        #[arg(long)]
+        revocable: bool,
+        /// Bind the proof to this domain, e.g. the verifier it is meant for; it is signed with it.
+        #[arg(long, conflicts_with = "revocable")]
+        domain: Option<String>,
+        /// Bind the proof to a verifier's challenge (nonce), so it cannot be replayed to another.
+        #[arg(long, conflicts_with = "revocable")]
+        challenge: Option<String>,
+        /// The URL of an RFC 3161 time-stamping authority to timestamp the proof with (or set IDP_TSA).
+        #[arg(long)]
+        tsa: Option<String>,
+        /// Where to write the credential; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Issue an SD-JWT whose fields the holder can disclose one by one, e.g. `age_over_18` without `birthdate`.
+    IssueSdJwt {
+        /// The identity file of the subject.
+        #[arg(long)]
+        to: String,
+        /// A field as NAME=VALUE, the value read as JSON if it is (repeatable).
+        #[arg(long = "field", value_parser = parse_field, required = true)]
+        fields: Vec<(String, serde_json::Value)>,
+        /// When the credential expires (e.g. 2026-12-31); without one, it never does.
+        #[arg(long, value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+        /// Where to write the SD-JWT; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Issue a BBS-signed credential whose fields the holder can prove one by one, without proofs being linkable.
+    IssueBbs {
+        /// The identity file of the subject.
+        #[arg(long)]
+        to: String,
+        /// A field as NAME=VALUE, the value read as JSON if it is (repeatable).
+        #[arg(long = "field", value_parser = parse_field, required = true)]
+        fields: Vec<(String, serde_json::Value)>,
+        /// When the credential expires (e.g. 2026-12-31); without one, it never does.
+        #[arg(long, value_parser = parse_time)]
+        expires: Option<DateTime<Utc>>,
+        /// Where to write the credential; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Add a credential someone issued to you to your identity file.
+    Add {
+        /// The credential file written by `idp credential issue`.
+        file: String,
+    },
+    /// Add an endorsement of one of your credentials, written by `idp endorse`, to your identity file.
+    AddEndorsement {
+        /// The endorsement file.
+        file: String,
+    },
+    /// Verify a W3C Verifiable Credential (JSON, JWT or SD-JWT) issued to you and add it to your identity file.
+    Import {
+        /// The file holding the VC.
+        file: String,
+        /// The issuer's public key as a JWK file; not needed for did:key issuers, except for SD-JWTs.
+        #[arg(long)]
+        issuer_key: Option<String>,
+    },
+    /// Check credentials against the identity files of their issuers.
+    Verify {
+        /// The credential to check, by its proof id; without one, all are checked.
+        proof_id: Option<String>,
+        /// The identity file of an issuer (repeatable).
+        #[arg(long = "issuer")]
+        issuers: Vec<String>,
+        /// The identity file holding the credentials, if not yours.
+        #[arg(long)]
+        holder: Option<String>,
+        /// Require proofs bound to this domain.
+        #[arg(long)]
+        domain: Option<String>,
+        /// Require proofs bound to this challenge.
+        #[arg(long)]
+        challenge: Option<String>,
+    },
+    /// Check that a credential's issuer is vouched for, credential by credential, up to an identity you trust.
+    Chain {
+        /// The credential to check, by its proof id.
+        proof_id: String,
+        /// The id of an identity you trust as a root (repeatable); by default, the configured `trusted_issuers`.
+        #[arg(long = "anchor")]
+        anchors: Vec<String>,
+        /// Only follow issuers' credentials with this claim or claim type, e.g. `notary`.
+        #[arg(long)]
+        require: Option<String>,
+        /// A directory of identity files to find issuers in; by default, the configured `resolvers`.
+        #[arg(long)]
+        dir: Option<String>,
+        /// The identity file of an issuer along the chain (repeatable).
+        #[arg(long = "issuer")]
+        issuers: Vec<String>,
+        /// The identity file holding the credential, if not yours.
+        #[arg(long)]
+        holder: Option<String>,
+    },
+    /// Move expired credentials into the archive, so no one mistakes them for current claims.
+    Gc {
+        /// Only list what would be archived.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Have a time-stamping authority attest that a credential's signature existed by now.
+    Timestamp {
+        /// The credential, by its proof id.
+        proof_id: String,
+        /// The URL of an RFC 3161 time-stamping authority (or set IDP_TSA).
+        #[arg(long)]
+        tsa: Option<String>,
+    },
+    /// Print an SD-JWT you hold with only some of its fields disclosed, to hand to a verifier.
+    Disclose {
+        /// The credential, by its proof id.
+        proof_id: String,
+        /// A field to disclose (repeatable); the others stay hidden.
+        #[arg(long = "field")]
+        fields: Vec<String>,
+    },
+    /// Check an SD-JWT someone disclosed to you against its issuer's identity file, and show what it discloses.
+    VerifyDisclosure {
+        /// The file holding the SD-JWT.
+        file: String,
+        /// The identity file of the issuer.
+        #[arg(long)]
+        issuer: String,
+    },
+    /// Derive a proof from a BBS credential you hold that reveals only some of its fields.
+    Derive {
+        /// The credential, by its proof id.
+        proof_id: String,
+        /// A field to reveal (repeatable); the others stay hidden.
+        #[arg(long = "field")]
+        fields: Vec<String>,
+        /// The identity file of the issuer, for its BBS key.
+        #[arg(long)]
+        issuer: String,
+        /// Where to write the proof; without one, it is printed.
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// Check a proof derived from a BBS credential against its issuer's identity file, and show what it reveals.
+    VerifyDerived {
+        /// The file written by `idp credential derive`.
+        file: String,
+        /// The identity file of the issuer.
+        #[arg(long)]
+        issuer: String,
+    },
+    /// Print credentials you hold in a format verifiers understand.
+    Export {
+        /// The credential to export, by its proof id; without one, all are exported.
+        proof_id: Option<String>,
+        /// The output format.
+        #[arg(long, value_enum, default_value_t = CredentialFormat::Idp)]
+        format: CredentialFormat,
+    },
+}
+
+/// Formats `idp credential export` can write.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum CredentialFormat {
+    /// The credential and its proof, as `idp credential issue` writes them.
+    Idp,
+    /// A W3C Verifiable Credential (VC Data Model 2.0) with an eddsa-jcs-2022 Data Integrity proof.
+    Vc,
+    /// A JWT-VC signed with ES256 or EdDSA, for JWT-based verifiers; one token per line.
+    Jwt,
+}
+
+/// Signature algorithms `idp init` can generate a root key for.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum SignatureAlgorithm {
+    /// Ed25519, small and fast; the default.
+    Ed25519,
+    /// ECDSA P-256, the curve WebCrypto, TPMs and most enterprise PKIs support.
+    #[value(name = "p-256")]
+    P256,
+    /// secp256k1 ECDSA, the curve of Bitcoin and Ethereum wallets; use with --import-key to reuse a wallet key.
+    Secp256k1,
+    /// ML-DSA-65 (FIPS 204), a post-quantum signature scheme with much larger keys and signatures.
+    #[value(name = "ml-dsa-65")]
+    MlDsa65,
+    /// SLH-DSA-SHA2-128s (FIPS 205, SPHINCS+), hash-based: it relies only on SHA-256, but signs slowly.
+    #[value(name = "slh-dsa-sha2-128s")]
+    SlhDsaSha2_128s,
+}
+
+impl SignatureAlgorithm {
+    /// The value written to `PublicKey.algorithm`.
+    fn name(self) -> &'static str {
+        match self {
+            SignatureAlgorithm::Ed25519 => idp_core::crypto::ED25519,
+            SignatureAlgorithm::P256 => idp_core::crypto::P256,
+            SignatureAlgorithm::Secp256k1 => idp_core::crypto::SECP256K1,
+            SignatureAlgorithm::MlDsa65 => idp_core::crypto::ML_DSA_65,
+            SignatureAlgorithm::SlhDsaSha2_128s => "SLH-DSA-SHA2-128s",
+        }
+    }
+}
+
+/// What a note on a contract records.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum AnnotationKind {
+    /// You disagree with how the contract is carried out.
+    Dispute,
+    /// Where to find evidence, given with --reference.
+    Evidence,
+    /// How a dispute was settled.
+    Resolution,
+    /// Anything else the parties should know.
+    Note,
+}
+
+/// How a contract ended.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum Outcome {
+    /// Every party kept its side: the contract is fulfilled.
+    Success,
+    /// A party did not: the contract is breached.
+    Failure,
+}
+
+/// Forms a presentation can be written in.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PresentationFormat {
+    /// IDP's own YAML, which `idp verify-presentation` checks.
+    Yaml,
+    /// A W3C Verifiable Presentation with a Data Integrity proof.
+    Vp,
+}
+
+/// Formats identity documents can be written in.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum DocumentFormat {
+    /// YAML, easy to read and edit by hand.
+    Yaml,
+    /// JSON, for tools that cannot read YAML.
+    Json,
+    /// Deterministic CBOR, a compact binary encoding for devices and the network (export only).
+    Cbor,
+}
+
+impl DocumentFormat {
+    /// The text format to write, or `None` for CBOR, which identity files are never kept in.
+    fn format(self) -> Option<idp_core::Format> {
+        match self {
+            DocumentFormat::Yaml => Some(idp_core::Format::Yaml),
+            DocumentFormat::Json => Some(idp_core::Format::Json),
+            DocumentFormat::Cbor => None,
+        }
+    }
+}
+
+impl Commands {
+    /// How the command locks the identity file: shared to read it, exclusive to change it.
+    fn lock_mode(&self) -> LockMode {
+        match self {
+            Commands::Show
+            | Commands::Validate
+            | Commands::Diff { .. }
+            | Commands::Export { .. }
+            | Commands::Get { .. }
+            | Commands::Log { .. }
+            | Commands::History { .. }
+            | Commands::Snapshot { .. }
+            | Commands::Credential {
+                action:
+                    CredentialCommands::Types
+                    | CredentialCommands::Templates { .. }
+                    | CredentialCommands::Gc { dry_run: true }
+                    | CredentialCommands::Verify { .. }
+                    | CredentialCommands::Chain { .. }
+                    | CredentialCommands::Disclose { .. }
+                    | CredentialCommands::VerifyDisclosure { .. }
+                    | CredentialCommands::Derive { .. }
+                    | CredentialCommands::VerifyDerived { .. }
+                    | CredentialCommands::Export { .. },
+            } => {
+                LockMode::Shared
+            }
+            Commands::StatusList { action: StatusListCommands::Publish { .. } }
+            | Commands::Issued { action: IssuedCommands::List }
+            | Commands::Anchor { action: AnchorCommands::Verify { .. } }
+            | Commands::Endorse { .. }
+            | Commands::Challenge { .. }
+            | Commands::Present { .. }
+            | Commands::VerifyPresentation { .. }
+            | Commands::Sign { .. }
+            | Commands::VerifySignature { .. }
+            | Commands::Contract {
+                action: ContractCommands::Templates { .. } | ContractCommands::New { .. } | ContractCommands::Status | ContractCommands::Verify { .. },
+            }
+            | Commands::Consent { action: ConsentCommands::List { .. } | ConsentCommands::Audit | ConsentCommands::Verify { .. } }
+            | Commands::Reputation { action: ReputationCommands::Issue { .. } | ReputationCommands::Export { .. } | ReputationCommands::Show { .. } }
+            | Commands::Profile { .. } => LockMode::Shared,
+            Commands::Key { action: KeyCommands::List | KeyCommands::Chain | KeyCommands::Split { .. } | KeyCommands::Export { .. } } => {
+                LockMode::Shared
+            }
+            _ => LockMode::Exclusive,
+        }
+    }
+}
+
+/// How commands print what they report. Commands that change the identity file report in text.
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum OutputFormat {
+    /// Lines for people to read.
+    Text,
+    /// JSON, e.g. for jq.
+    Json,
+    /// YAML.
+    Yaml,
+}
+
+/// Prints `value` as JSON or YAML when that output was asked for, and says whether it did; with
+/// text output, the command prints its own lines instead.
+fn print_structured<T: serde::Serialize>(output: OutputFormat, value: &T) -> Result<bool, String> {
+    let text = match output {
+        OutputFormat::Text => return Ok(false),
+        OutputFormat::Json => serde_json::to_string_pretty(value).map_err(|e| fail(e.into()))?,
+        OutputFormat::Yaml => serde_yaml::to_string(value).map_err(|e| fail(e.into()))?,
+    };
+    // A reader like `head` may stop early; that is not a failure of the command.
+    match writeln!(std::io::stdout(), "{}", text.trim_end()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(fail(e.into())),
+        _ => Ok(true),
+    }
+}
+
+/// Formats `idp key export` can write.
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum ExportFormat {
+    /// A JSON Web Key, or a JWK Set when exporting every active key.
+    Jwk,
+    /// OpenSSH public key lines, for `authorized_keys` (signing keys only).
+    Ssh,
+    /// An OpenPGP certificate for your active root key, self-signed with it.
+    Openpgp,
+    /// Multibase (`z6Mk...`) values, as used in did:key and `publicKeyMultibase`.
+    Multibase,
+    /// age recipients (`age1...`) of the key-agreement keys, for `idp encrypt --recipient` or `age -r`.
+    Age,
+}
+
+/// Parses a timestamp as RFC 3339, `YYYY-MM-DD[ HH:MM:SS]` in UTC, or Unix seconds.
+fn parse_time(text: &str) -> Result<DateTime<Utc>, String> {
+    idp_core::timestamp::parse(text).ok_or_else(|| "expected a time like 2026-12-31 or 2026-12-31T12:00:00Z".to_string())
+}
+
+/// The TSA to timestamp with: the one given, or IDP_TSA.
+fn tsa_url(given: &Option<String>) -> Option<String> {
+    given.clone().or_else(|| std::env::var("IDP_TSA").ok().filter(|url| !url.is_empty()))
+}
+
+/// Where fetched status lists are cached: `$XDG_CACHE_HOME/idp/status-lists`, or under `~/.cache`.
+fn status_cache_dir() -> PathBuf {
+    let cache = std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .unwrap_or_else(std::env::temp_dir);
+    cache.join("idp").join("status-lists")
+}
+
+/// The IDP home directory, with `config.toml` and the templates: `$IDP_HOME`, `$XDG_CONFIG_HOME/idp`, or `~/.config/idp`.
+fn idp_home() -> PathBuf {
+    if let Some(home) = std::env::var_os("IDP_HOME").filter(|home| !home.is_empty()) {
+        return PathBuf::from(home);
+    }
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .unwrap_or_else(std::env::temp_dir);
+    config.join("idp")
+}
+
+/// Where the issuer's own credential templates are kept: `templates` in the IDP home directory.
+fn templates_dir() -> PathBuf {
+    idp_home().join("templates")
+}
+
+/// Fills in what the command line leaves to its defaults from IDP_KEYSTORE, IDP_OUTPUT and the
+/// configuration, in that order.
+fn apply_config(cli: &mut Cli, matches: &ArgMatches, config: &Config) -> Result<(), IdpError> {
+    if matches.value_source("output") == Some(ValueSource::DefaultValue)
+        && let Some((name, value)) = std::env::var("IDP_OUTPUT").ok().filter(|value| !value.is_empty()).map(|value| ("IDP_OUTPUT", value)).or_else(|| config.output.clone().map(|value| ("the output", value)))
+    {
+        cli.output = OutputFormat::from_str(&value, true).map_err(|_| IdpError::Config(format!("{} '{}' is not `text`, `json` or `yaml`", name, value)))?;
+    }
+    let parse = |name: &str, value: &str| Keystore::from_str(value, true).map_err(|_| IdpError::Config(format!("{} '{}' is not a keystore idp knows", name, value)));
+    if matches.value_source("keystore") == Some(ValueSource::DefaultValue) {
+        match (std::env::var("IDP_KEYSTORE").ok().filter(|value| !value.is_empty()), &config.keystore) {
+            (Some(value), _) => cli.keystore = parse("IDP_KEYSTORE", &value)?,
+            (None, Some(value)) => cli.keystore = parse("the keystore", value)?,
+            (None, None) => {}
+        }
+    }
+    if let (Some(value), Commands::Init { format, .. } | Commands::Export { format, .. }) = (&config.format, &mut cli.command)
+        && matches.subcommand().is_some_and(|(_, command)| command.value_source("format") == Some(ValueSource::DefaultValue))
+    {
+        *format = match DocumentFormat::from_str(value, true) {
+            Ok(preferred @ (DocumentFormat::Yaml | DocumentFormat::Json)) => preferred,
+            _ => return Err(IdpError::Config(format!("the format '{}' is not `yaml` or `json`", value))),
+        };
+    }
+    Ok(())
+}
+
+/// The directories to find other identities in: the one given with --dir, or else the configured resolvers.
+fn directories(dir: &Option<String>, config: &Config) -> Vec<idp_core::trust::DirectoryResolver> {
+    match dir {
+        Some(dir) => vec![idp_core::trust::DirectoryResolver::new(dir)],
+        None => config.resolvers.iter().map(idp_core::trust::DirectoryResolver::new).collect(),
+    }
+}
+
+/// Reads a contract shared as a YAML file.
+fn read_contract(path: &str) -> Result<idp_core::Contract, IdpError> {
+    Ok(serde_yaml::from_str(&std::fs::read_to_string(path)?)?)
+}
+
+/// Writes a contract as a YAML file, to share with its other parties.
+fn write_contract(path: &str, contract: &idp_core::Contract) -> Result<(), IdpError> {
+    Ok(std::fs::write(path, serde_yaml::to_string(contract)?)?)
+}
+
+/// Parses a template variable given as NAME=VALUE.
+fn parse_var(text: &str) -> Result<(String, String), String> {
+    let (name, value) = text.split_once('=').filter(|(name, _)| !name.is_empty()).ok_or_else(|| "expected NAME=VALUE".to_string())?;
+    Ok((name.to_string(), value.to_string()))
+}
+
+/// Parses a credential field given as NAME=VALUE; values that are not JSON are taken as text.
+fn parse_field(text: &str) -> Result<(String, serde_json::Value), String> {
+    let (name, value) = text.split_once('=').filter(|(name, _)| !name.is_empty()).ok_or_else(|| "expected NAME=VALUE".to_string())?;
+    let value = serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    Ok((name.to_string(), value))
+}
+
+/// Parses an issuer's reputation weight given as ID=WEIGHT.
+fn parse_weight(text: &str) -> Result<(String, f64), String> {
+    let (id, weight) = parse_var(text)?;
+    let weight = weight.parse().map_err(|_| format!("'{}' is not a number", weight))?;
+    Ok((id, weight))
+}
+
+/// Parses a half-life in days, e.g. "30" or "0.5".
+fn parse_half_life(text: &str) -> Result<chrono::Duration, String> {
+    let days = text.parse().map_err(|_| format!("'{}' is not a number", text))?;
+    idp_core::reputation::half_life(days).map_err(|e| match e {
+        IdpError::Reputation(reason) => reason,
+        other => other.to_string(),
+    })
+}
+
+/// Parses a key purpose the way it is written in the identity file (e.g. "authentication").
+fn parse_purpose(text: &str) -> Result<KeyPurpose, String> {
+    serde_yaml::from_str(text)
+        .map_err(|_| "expected one of: signing, key_agreement, authentication, capability_delegation".to_string())
+}
+
+/// Turns a core error into a message that tells the user what to do about it.
+fn explain(error: &IdpError) -> String {
+    match error {
+        IdpError::Io(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            format!("{}\nHint: Have you run `idp init` in this directory?", e)
+        }
+        IdpError::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+            format!("{}\nHint: Check the permissions of your identity and key files.", e)
+        }
+        IdpError::Io(e) => format!("Could not access the file system: {}", e),
+        IdpError::Yaml(e) => {
+            format!("The identity file is not a valid .idp document: {}\nHint: Fix the file by hand or restore it from a backup.", e)
+        }
+        IdpError::Json(e) => {
+            format!("The identity file is not a valid .idp document: {}\nHint: Fix the file by hand or restore it from a backup.", e)
+        }
+        IdpError::Cbor(e) => format!("Could not encode the document as CBOR: {}", e),
+        IdpError::Crypto(e) => {
+            format!("A cryptographic operation failed: {}\nHint: Check your passphrase and make sure your key file is intact and belongs to this identity.", e)
+        }
+        IdpError::Keystore(e) => format!("{}\nHint: Use `--keystore file` to keep the key in a file instead.", e),
+        IdpError::Key(e) => {
+            format!("{}\nHint: Run `idp get system.public_keys` to see your keys and their status.", e)
+        }
+        IdpError::Encrypted => "The identity file is encrypted.\nHint: Run `idp decrypt` to turn it back into plain YAML.".to_string(),
+        IdpError::Schema(e) => format!("{}\nHint: Check the `identity.schema_url` of the document.", e),
+        IdpError::SchemaViolations(violations) => {
+            let lines: Vec<String> = violations.iter().map(|v| format!("  - {}", v)).collect();
+            format!("The identity file does not match its schema:\n{}", lines.join("\n"))
+        }
+        IdpError::Id { id, reason } => {
+            format!("'{}' is not a valid IDP id: {}\nHint: IDP ids look like `idp:key:sha256:<hash>`.", id, reason)
+        }
+        IdpError::Status { kind, value, expected } => {
+            format!("'{}' is not a {}.\nHint: Use one of: {}.", value, kind, expected)
+        }
+        IdpError::Extension { namespace, reason } => {
+            format!("The '{}' extension cannot be used: {}\nHint: Namespaces are lower-case, like `gamehub` or `org.example.app`.", namespace, reason)
+        }
+        IdpError::Path { path, reason } => {
+            format!("Cannot use '{}': {}\nHint: Paths look like `core.bio` or `system.public_keys.0.status`, and values must fit the field there.", path, reason)
+        }
+        IdpError::Patch(reason) => {
+            format!("The patch was not applied: {}\nHint: A patch is a JSON array of RFC 6902 operations, e.g. [{{\"op\": \"replace\", \"path\": \"/core/bio\", \"value\": \"...\"}}].", reason)
+        }
+        IdpError::Credential(_) => format!("{}\nHint: Run `idp get credentials` to see the credentials you hold.", error),
+        IdpError::Trust(_) => format!("{}\nHint: Pass the issuers along the chain with --issuer or --dir, and check which identity ids you --anchor.", error),
+        IdpError::Contract(_) => format!("{}\nHint: Check the contract id, that you are one of its parties, and that every obligation is owed by one.", error),
+        IdpError::Consent(_) => format!("{}\nHint: Run `idp consent list` to see the consents you have given; a receipt is checked against its grantor's current identity file.", error),
+        IdpError::Reputation(_) => format!("{}\nHint: Pass the issuer's identity file with --issuer; an event only counts for the identity it was issued to.", error),
+        IdpError::Template(_) => format!("{}\nHint: Run `idp credential templates` or `idp contract templates` to see the templates and their variables.", error),
+        IdpError::Presentation(_) => format!("{}\nHint: Run `idp get credentials` to see what you can present; a challenge that has lapsed needs a new one from the verifier.", error),
+        IdpError::Anchor(_) => format!("{}\nHint: Anchors reach a block a few hours after `idp anchor submit`; `idp anchor upgrade` fetches it.", error),
+        IdpError::Timestamp(_) => format!("{}\nHint: Check the TSA's URL; IDP asks for SHA-256 timestamps with the TSA's certificate included.", error),
+        IdpError::Jwt(_) => format!("{}\nHint: Pass the token exactly as you received it, as one `header.payload.signature` line.", error),
+        IdpError::Signature(_) => format!("{}\nHint: Check that you passed the file or message that was signed, unchanged, and the identity file of whoever signed it with --signer; the signature block names them on its Idp-Id line.", error),
+        IdpError::Config(_) => format!("{}\nHint: The configuration is `config.toml` in the IDP home directory ($IDP_HOME, or ~/.config/idp); it takes identity, key_file, keystore, format, output, trusted_issuers and resolvers. Run `idp profile list` to see the profiles.", error),
+        IdpError::Snapshot(_) => format!("{}\nHint: Run `idp snapshot --list` to see the snapshots of this identity.", error),
+        IdpError::Log(_) => format!(
+            "{}\nHint: Keep a copy of the identity file's `.log` as evidence; moving it aside starts a fresh log at the next save.",
+            error
+        ),
+        IdpError::Git(_) => format!("{}\nHint: The identity file must be inside a git repository, with `user.name` and `user.email` set.", error),
+        IdpError::Locked(_) => format!(
+            "{}\nHint: Wait for the other `idp` command to finish. If none is running, retry with `--no-lock`.",
+            error
+        ),
+        IdpError::Integrity { .. } => format!(
+            "{}\nHint: The file was corrupted or edited by hand. If the edit was yours, delete its `integrity` block; the next save writes a new one.",
+            error
+        ),
+        IdpError::Verify(e) => format!("Signature verification failed: {}", e),
+    }
+}
+
+/// Saves the identity signed by `key`, and records the change in its event log. A key that can
+/// no longer sign it (e.g. a root key that has just revoked itself) leaves the file unsigned,
+/// with a warning.
+fn save(identity: &mut Identity, key: &dyn SigningBackend, id_file_name: &str) -> Result<(), IdpError> {
+    if ARCHIVE_CONSENT.load(Ordering::Relaxed) {
+        let archived = identity.archive_expired_consent(chrono::Utc::now())?.len();
+        if archived > 0 {
+            println!("🗄️  Archived {} expired consent(s).", archived);
+        }
+    }
+    identity.reseal()?;
+    if let Err(e) = identity.sign_document(key) {
+        identity.signature = None;
+        println!("⚠️  '{}' is saved unsigned: {}", id_file_name, e);
+    }
+    identity.save_to_file(id_file_name)?;
+    log_change(identity, key, id_file_name);
+    Ok(())
+}
+
+/// Decrypts the consent section and its log if they are sealed, so they can be read and added to.
+fn open_consent(identity: &mut Identity, store: &dyn KeyStore, key: &dyn SigningBackend) -> Result<(), IdpError> {
+    if identity.is_sealed("consent") || identity.is_sealed("consent_log") {
+        identity.decrypt_sections(keystore::software_key(store, key)?)?;
+    }
+    Ok(())
+}
+
+/// Set by `--git` or IDP_GIT=1: saved changes are also committed to git.
+static COMMIT_TO_GIT: AtomicBool = AtomicBool::new(false);
+
+/// Set by `--archive-consent` or IDP_ARCHIVE_CONSENT=1: saves move expired consents to the archive.
+static ARCHIVE_CONSENT: AtomicBool = AtomicBool::new(false);
+
+/// Appends the saved change to the event log, and commits it to git if asked to. The save
+/// stands either way; a change that could not be logged is caught up by the next one.
+fn log_change(identity: &Identity, key: &dyn SigningBackend, id_file_name: &str) {
+    let summary = match EventLog::open(id_file_name).and_then(|mut log| Ok(log.record(identity, key)?.map(|event| event.summary()))) {
+        Ok(summary) => summary,
+        Err(e) => {
+            println!("⚠️  The change was saved but not logged: {}", e);
+            None
+        }
+    };
+    if COMMIT_TO_GIT.load(Ordering::Relaxed)
+        && let Err(e) = commit_change(identity, key, id_file_name, summary.as_deref().unwrap_or("updated the document"))
+    {
+        println!("⚠️  The change was saved but not committed: {}", e);
+    }
+}
+
+/// Commits the identity file on its own, with trailers naming the identity and the signing key.
+fn commit_change(identity: &Identity, key: &dyn SigningBackend, id_file_name: &str, summary: &str) -> Result<(), IdpError> {
+    let Some(history) = GitHistory::discover(Path::new(id_file_name))? else {
+        return Err(IdpError::Git(format!("'{}' is not in a git repository", id_file_name)));
+    };
+    let key_id = identity.key_for_private_key(key).map(|public_key| public_key.key_id.clone())?;
+    let message = format!("idp: {}\n\nIdp-Id: {}\nIdp-Key: {}", summary, identity.identity.id, key_id);
+    if let Some(commit) = history.commit(&message, identity, Some(key))? {
+        println!("📌 Committed as {}.", &commit[..12]);
+    }
+    Ok(())
+}
+
+/// The key file that goes with an identity file: `my.key` for `my.idp`, `work/alice.key` for
+/// `work/alice.idp`.
+fn key_file_for(id_file_name: &str) -> String {
+    Path::new(id_file_name).with_extension("key").to_string_lossy().into_owned()
+}
+
+/// Prints an explained error to stderr and returns the short message `main` exits with.
+fn fail(error: IdpError) -> String {
+    eprintln!("\nError: {}", explain(&error));
+    "Command failed.".to_string()
 }
 
 #[tokio::main]
 async fn main() -> Result<(), String> {
-    let cli = Cli::parse();
-    let id_file_name = "my.idp";
-    let key_file_name = "my.key";
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    let home = idp_home();
+    let profile = match cli.profile.clone().or_else(|| std::env::var("IDP_PROFILE").ok().filter(|name| !name.is_empty())) {
+        Some(name) => Some(name),
+        None => idp_core::config::current_profile(&home).map_err(fail)?,
+    };
+    // Managing profiles works whatever the current one is, even if it is gone.
+    let config = match (&profile, &cli.command) {
+        (Some(name), command) if !matches!(command, Commands::Profile { .. }) => Config::load_profile(&home, name),
+        _ => Config::load(&home),
+    }
+    .map_err(fail)?;
+    apply_config(&mut cli, &matches, &config).map_err(fail)?;
+    COMMIT_TO_GIT.store(cli.git || std::env::var("IDP_GIT").is_ok_and(|value| value == "1"), Ordering::Relaxed);
+    ARCHIVE_CONSENT.store(cli.archive_consent || std::env::var("IDP_ARCHIVE_CONSENT").is_ok_and(|value| value == "1"), Ordering::Relaxed);
+    // Flags, then the environment, then the configuration.
+    let configured = |path: &Option<PathBuf>| path.as_ref().map(|path| path.to_string_lossy().into_owned());
+    let id_file = cli.identity_file.clone().or_else(|| std::env::var("IDP_FILE").ok().filter(|path| !path.is_empty())).or_else(|| configured(&config.identity)).unwrap_or_else(|| "my.idp".to_string());
+    let key_file = cli.key_file.clone().or_else(|| std::env::var("IDP_KEY_FILE").ok().filter(|path| !path.is_empty())).or_else(|| {
+        // A configured key file goes with the configured identity, not with one named on the command line.
+        (configured(&config.identity).as_deref() == Some(id_file.as_str())).then(|| configured(&config.key_file)).flatten()
+    });
+    let key_file = key_file.unwrap_or_else(|| key_file_for(&id_file));
+    let (id_file_name, key_file_name) = (id_file.as_str(), key_file.as_str());
+    let _lock = match cli.no_lock {
+        true => None,
+        false => Some(FileLock::acquire(Path::new(id_file_name), cli.command.lock_mode()).map_err(fail)?),
+    };
 
     // Match the subcommand provided by the user and execute the corresponding logic.
     match &cli.command {
-        Commands::Init { name, bio } => {
+        Commands::Init { name, bio, mnemonic, import_key, algorithm, hybrid, format } => {
             println!("Forging a new cryptographic identity for '{}'...", name);
 
             // Safety checks
-            if Path::new(id_file_name).exists() || Path::new(key_file_name).exists() {
+            let key_file_in_use = cli.keystore == Keystore::File && Path::new(key_file_name).exists();
+            if Path::new(id_file_name).exists() || key_file_in_use {
                 eprintln!("Error: '{}' or '{}' already exists.", id_file_name, key_file_name);
                 eprintln!("Please move or rename existing files before initializing.");
                 return Err("Aborted due to existing files.".to_string());
             }
+            cli.keystore.check_available().map_err(fail)?;
+            let Some(format) = format.format() else {
+                eprintln!("Error: identity files are kept as YAML or JSON.");
+                eprintln!("Use `idp export --format cbor` for a CBOR copy.");
+                return Err("Aborted due to an unsupported format.".to_string());
+            };
 
-            // Call our powerful constructor from idp-core
-            match Identity::new(name, bio) {
-                Ok((new_identity, private_key_bytes)) => {
-                    // Save the public identity file
-                    new_identity.save_to_file(id_file_name)?;
+            let recovery_phrase = match mnemonic {
+                true => Some(idp_core::mnemonic::generate().map_err(fail)?),
+                false => None,
+            };
 
-                    // Save the secret private key file
-                    std::fs::write(key_file_name, &private_key_bytes)
-                        .map_err(|e| e.to_string())?;
+            let imported_key = match import_key {
+                Some(path) => Some(SecretBytes::new(std::fs::read(path).map_err(|e| fail(e.into()))?)),
+                None => None,
+            };
+            let source = match (&recovery_phrase, &imported_key) {
+                (Some(phrase), _) => KeySource::RecoveryPhrase(phrase),
+                (_, Some(key_material)) => KeySource::Import(key_material, algorithm.name()),
+                _ => KeySource::Generate(algorithm.name()),
+            };
+
+            // Create the identity; its secret private key is stored first, so the identity is never without it
+            match cli.keystore.init(name, bio, key_file_name, source, *hybrid) {
+                Ok(new_identity) => {
+                    // Save the public identity file
+                    new_identity.save_to_file_with_format(id_file_name, format).map_err(fail)?;
 
                     println!("✅ Success! Your identity has been created.");
                     println!("  - Public identity saved to: {}", id_file_name);
-                    println!("  - Private key saved to:    {}", key_file_name);
-                    println!("\nSECURITY WARNING:");
-                    println!("  The 'my.key' file is your secret. It is your password and your soul.");
-                    println!("  Guard it. Back it up securely. Never share it with anyone.");
+                    println!("  - Private key saved to:    {}", cli.keystore.describe(key_file_name));
+                    if *hybrid {
+                        println!("  - Post-quantum half:       root-key-01/ml-dsa-65 (derived from your key, nothing more to store)");
+                    }
+                    if cli.keystore == Keystore::File {
+                        println!("\nSECURITY WARNING:");
+                        println!("  The '{}' file is your secret. It is your password and your soul.", key_file_name);
+                        println!("  Guard it. Back it up securely. Never share it with anyone.");
+                    }
+                    if let Some(phrase) = &recovery_phrase {
+                        println!("\n🔑 RECOVERY PHRASE — write these 24 words down, in order, and keep them offline:\n");
+                        for (row, words) in phrase.split(' ').collect::<Vec<_>>().chunks(4).enumerate() {
+                            let cells: Vec<String> = words
+                                .iter()
+                                .enumerate()
+                                .map(|(column, word)| format!("{:>2}. {:<10}", row * 4 + column + 1, word))
+                                .collect();
+                            println!("  {}", cells.join(" "));
+                        }
+                        println!("\n  Anyone with these words can rebuild your key. `idp recover` restores it.");
+                    }
                 }
                 Err(e) => {
-                    eprintln!("Error creating new identity: {}", e);
-                    return Err(e);
+                    eprintln!("Error creating new identity: {}", explain(&e));
+                    return Err("Failed to create identity.".to_string());
                 }
             }
         }
-        Commands::Show => {
-            println!("🔎 Reading identity from '{}'...", id_file_name);
+        Commands::Key { action: KeyCommands::Split { shares, threshold } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let private_key = keystore::software_key(store.as_ref(), key.as_ref()).map_err(fail)?;
 
-            // Use our powerful core library function to load the identity from disk.
-            match Identity::load_from_file(id_file_name) {
-                Ok(identity) => {
-                    // If loading succeeds, print a beautifully formatted summary.
-                    println!("\n--- 🧬 Sovereign Identity ---");
-                    println!("  ID:        {}", identity.identity.id);
-                    println!("  Name:      {}", identity.core.name);
-                    println!("  Bio:       {}", identity.core.bio);
+            let pieces = idp_core::crypto::split_secret(private_key, *shares, *threshold).map_err(fail)?;
+            println!("🧩 Your key has been split into {} shares; any {} of them rebuild it.", shares, threshold);
+            println!("  Give each share to a different trusted person or place. Never store them together.\n");
+            for piece in pieces {
+                println!("  Share {}: {}", piece.index, piece);
+            }
+            println!("\n  Use `idp key combine` to rebuild the key.");
+        }
+        Commands::Key { action: KeyCommands::Combine } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            cli.keystore.check_available().map_err(fail)?;
+            if cli.keystore == Keystore::File && Path::new(key_file_name).exists() {
+                eprintln!("Error: '{}' already exists.", key_file_name);
+                eprintln!("Please move or rename it before combining shares.");
+                return Err("Aborted due to existing files.".to_string());
+            }
+
+            // The first share tells us how many more are needed.
+            let mut pieces: Vec<idp_core::crypto::SecretShare> = Vec::new();
+            loop {
+                let prompt = match pieces.first() {
+                    Some(first) => format!("Share {} of {}: ", pieces.len() + 1, first.threshold),
+                    None => "Share 1: ".to_string(),
+                };
+                let text = rpassword::prompt_password(prompt).map_err(|e| fail(e.into()))?;
+                pieces.push(text.parse().map_err(fail)?);
+                if pieces.len() >= pieces[0].threshold as usize {
+                    break;
+                }
+            }
+            let private_key = idp_core::crypto::combine_secret(&pieces).map_err(fail)?;
+
+            // Only store the result if it really is one of this identity's keys.
+            let key_id = identity
+                .system
+                .public_keys
+                .iter()
+                .find(|k| idp_core::crypto::public_key_value(&private_key).is_ok_and(|value| k.has_value(&value)))
+                .map(|k| k.key_id.clone())
+                .ok_or_else(|| fail(IdpError::Key("the shares do not rebuild a key of this identity".to_string())))?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            store.store(&private_key).map_err(fail)?;
+            println!("✅ Rebuilt key '{}' into {}.", key_id, cli.keystore.describe(key_file_name));
+        }
+        Commands::Key { action: KeyCommands::List } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+
+            // The status shown is the effective one, so lapsed keys show as expired.
+            let now = chrono::Utc::now();
+            let statuses = identity.system.public_keys.iter().map(|key| identity.key_status_at(&key.key_id, now)).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+            let keys: Vec<serde_json::Value> = identity
+                .system
+                .public_keys
+                .iter()
+                .zip(&statuses)
+                .map(|(key, status)| {
+                    serde_json::json!({
+                        "key_id": key.key_id,
+                        "algorithm": key.algorithm,
+                        "purpose": key.purpose.to_string(),
+                        "status": status.to_string(),
+                        "parent_key_id": key.parent_key_id,
+                        "expires_at": key.expires_at,
+                    })
+                })
+                .collect();
+            if print_structured(cli.output, &keys)? {
+                return Ok(());
+            }
+            println!("{:<24} {:<10} {:<22} {:<11} {:<12} EXPIRES", "KEY ID", "ALGORITHM", "PURPOSE", "STATUS", "PARENT");
+            for key in &identity.system.public_keys {
+                let status = identity.key_status_at(&key.key_id, now).map_err(fail)?;
+                println!(
+                    "{:<24} {:<10} {:<22} {:<11} {:<12} {}",
+                    key.key_id,
+                    key.algorithm,
+                    key.purpose.to_string(),
+                    status.to_string(),
+                    key.parent_key_id.as_deref().unwrap_or("-"),
+                    key.expires_at.map(|e| e.format("%Y-%m-%d").to_string()).unwrap_or_else(|| "never".to_string())
+                );
+            }
+        }
+        Commands::Key { action: KeyCommands::Chain } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let chain = identity.verify_key_chain().map_err(fail)?;
+            let listed: Vec<serde_json::Value> = chain
+                .iter()
+                .map(|key| serde_json::json!({ "key_id": key.key_id, "took_over_at": identity.system.rotations.iter().find(|r| r.new_key_id == key.key_id).map(|rotation| rotation.rotated_at) }))
+                .collect();
+            if print_structured(cli.output, &listed)? {
+                return Ok(());
+            }
+
+            println!("🔗 Key chain of {}:", identity.identity.id);
+            for (n, key) in chain.iter().enumerate() {
+                let rotated_at = identity.system.rotations.iter().find(|r| r.new_key_id == key.key_id);
+                match rotated_at {
+                    Some(rotation) => println!("  {}. {} (took over on {})", n + 1, key.key_id, rotation.rotated_at.format("%Y-%m-%d")),
+                    None => println!("  {}. {} (anchors the id)", n + 1, key.key_id),
+                }
+            }
+            println!("✅ Every hand-over is signed by the key before it.");
+        }
+        Commands::Key { action: KeyCommands::Derive { path, out, purpose, algorithm } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            if Path::new(out).exists() {
+                eprintln!("Error: '{}' already exists.", out);
+                return Err("Aborted due to existing files.".to_string());
+            }
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let root_key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let root_private_key = keystore::software_key(store.as_ref(), root_key.as_ref()).map_err(fail)?;
+
+            let subkey = match algorithm {
+                Some(algorithm) => identity.derive_subkey_as(root_private_key, path, *purpose, algorithm.name()),
+                None => identity.derive_subkey(root_private_key, path, *purpose),
+            }
+            .map_err(fail)?;
+            keystore::key_file(out).store(&subkey).map_err(fail)?;
+            save(&mut identity, root_key.as_ref(), id_file_name).map_err(fail)?;
+
+            let key_id = &identity.system.public_keys.last().expect("a subkey was added").key_id;
+            println!("🌱 Derived subkey '{}'.", key_id);
+            println!("  - Private key saved to: {}", out);
+            println!("  Move it to its device; if the device is lost, run `idp key revoke {}`.", key_id);
+        }
+        Commands::Key { action: KeyCommands::Export { key_id, format } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+
+            let keys: Vec<&idp_core::PublicKey> = match key_id {
+                Some(key_id) => vec![identity
+                    .find_key(key_id)
+                    .ok_or_else(|| fail(IdpError::Key(format!("unknown key '{}'", key_id))))?],
+                // Agreement keys have no SSH form and only agreement keys have an age form.
+                None => identity
+                    .system
+                    .public_keys
+                    .iter()
+                    .filter(|k| identity.check_key_active(&k.key_id).is_ok())
+                    .filter(|k| match format {
+                        ExportFormat::Ssh => k.purpose != KeyPurpose::KeyAgreement,
+                        ExportFormat::Age => k.purpose == KeyPurpose::KeyAgreement,
+                        _ => true,
+                    })
+                    .collect(),
+            };
+
+            match format {
+                ExportFormat::Jwk => {
+                    let jwks = keys.iter().map(|k| k.to_jwk()).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+                    // A single requested key is printed bare; everything else is a JWK Set. A JWK is
+                    // JSON already, so text output prints it as JSON too.
+                    let output = match cli.output {
+                        OutputFormat::Text => OutputFormat::Json,
+                        output => output,
+                    };
+                    match key_id {
+                        Some(_) => print_structured(output, &jwks[0])?,
+                        None => print_structured(output, &idp_core::jwk::JwkSet { keys: jwks })?,
+                    };
+                }
+                ExportFormat::Openpgp => {
+                    // The certificate is self-signed, so it can only be made for the key you hold.
+                    let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                    let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+                    let held = identity.key_for_private_key(key.as_ref()).map_err(fail)?;
+                    if key_id.as_ref().is_some_and(|id| *id != held.key_id) {
+                        let message = format!("only your active key '{}' can be exported as an OpenPGP certificate", held.key_id);
+                        return Err(fail(IdpError::Key(message)));
+                    }
+                    let certificate = identity.to_openpgp(key.as_ref()).map_err(fail)?;
+                    let exported = serde_json::json!({ "key_id": held.key_id, "certificate": certificate });
+                    if !print_structured(cli.output, &exported)? {
+                        print!("{}", certificate);
+                    }
+                }
+                ExportFormat::Ssh | ExportFormat::Multibase | ExportFormat::Age => {
+                    let exported = keys
+                        .iter()
+                        .map(|key| {
+                            let value = match format {
+                                ExportFormat::Ssh => key.to_openssh(&format!("{}#{}", identity.identity.id, key.key_id)),
+                                ExportFormat::Multibase => key.to_multibase(),
+                                _ => key.to_age_recipient(),
+                            };
+                            Ok((key.key_id.as_str(), value?))
+                        })
+                        .collect::<Result<Vec<_>, IdpError>>()
+                        .map_err(fail)?;
+                    let listed: Vec<serde_json::Value> = exported.iter().map(|(key_id, value)| serde_json::json!({ "key_id": key_id, "value": value })).collect();
+                    if !print_structured(cli.output, &listed)? {
+                        for (key_id, value) in exported {
+                            match format {
+                                // An OpenSSH line carries its own comment.
+                                ExportFormat::Ssh => println!("{}", value),
+                                _ => println!("{:<24} {}", key_id, value),
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Commands::Key { action: KeyCommands::ImportPgp { certificate, signers } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let certificate = std::fs::read_to_string(certificate).map_err(|e| fail(e.into()))?;
+            let signers = signers
+                .iter()
+                .map(std::fs::read_to_string)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| fail(e.into()))?;
+
+            let signers: Vec<&str> = signers.iter().map(String::as_str).collect();
+            let imported = identity.import_openpgp_certifications(&certificate, &signers).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🔏 Imported {} OpenPGP certification(s) as proofs.", imported);
+        }
+        Commands::Key { action: KeyCommands::AttestAddress { address, signature } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let (address, key) = match (address, signature) {
+                (Some(address), Some(signature)) => {
+                    let address = idp_core::address::parse_address(address).map_err(fail)?;
+                    identity.attest_address(&address, signature).map_err(fail)?;
+                    (address, None)
+                }
+                (Some(address), None) => {
+                    let address = idp_core::address::parse_address(address).map_err(fail)?;
+                    println!("✍️  Sign this message with the wallet of {} (personal_sign):\n", address);
+                    println!("  {}\n", idp_core::address::ownership_statement(&identity.identity.id, &address));
+                    println!("  Then run `idp key attest-address {} --signature <signature>`.", address);
+                    return Ok(());
+                }
+                _ => {
+                    // The identity's own key signs, so it must be a secp256k1 key held in software.
+                    let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                    let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+                    let private_key = keystore::software_key(store.as_ref(), key.as_ref()).map_err(fail)?;
+                    (identity.attest_own_address(private_key).map_err(fail)?, Some(key))
+                }
+            };
+            // Attesting the identity's own address has loaded the key already.
+            let key = match key {
+                Some(key) => key,
+                None => {
+                    let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                    identity.signer_from(store.as_ref()).map_err(fail)?
+                }
+            };
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🔗 Recorded proof that {} belongs to this identity.", address);
+        }
+        Commands::Recover => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            cli.keystore.check_available().map_err(fail)?;
+            if cli.keystore == Keystore::File && Path::new(key_file_name).exists() {
+                eprintln!("Error: '{}' already exists.", key_file_name);
+                eprintln!("Please move or rename it before recovering.");
+                return Err("Aborted due to existing files.".to_string());
+            }
+
+            let phrase = rpassword::prompt_password("Enter your 24-word recovery phrase: ").map_err(|e| fail(e.into()))?;
+            let key_pair = idp_core::mnemonic::derive_keypair(&phrase).map_err(fail)?;
+
+            // The phrase must belong to this identity, or we'd store a useless key.
+            let key = identity
+                .system
+                .public_keys
+                .iter()
+                .find(|k| k.has_value(&key_pair.public_key.value))
+                .ok_or_else(|| fail(IdpError::Key("the recovery phrase does not match any key of this identity".to_string())))?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            store.store(&key_pair.private_key_bytes).map_err(fail)?;
+
+            println!("✅ Recovered key '{}' to {}.", key.key_id, cli.keystore.describe(key_file_name));
+            if key.status != KeyStatus::Active {
+                println!("⚠️  This key is '{}', so it can no longer sign for the identity.", key.status);
+            }
+        }
+        Commands::Show if cli.output != OutputFormat::Text => {
+            print_structured(cli.output, &Identity::load_from_file(id_file_name).map_err(fail)?)?;
+        }
+        Commands::Show => {
+            println!("🔎 Reading identity from '{}'...", id_file_name);
+
+            // Use our powerful core library function to load the identity from disk.
+            match Identity::load_from_file(id_file_name) {
+                Ok(identity) => {
+                    // If loading succeeds, print a beautifully formatted summary.
+                    println!("\n--- 🧬 Sovereign Identity ---");
+                    println!("  ID:        {}", identity.identity.id);
+                    println!("  Name:      {}", identity.core.name);
+                    println!("  Bio:       {}", identity.core.bio);
                     println!("----------------------------");
                     println!("  Keys:      {} (Spec v{})", identity.system.public_keys.len(), identity.identity.version);
                     println!("  Created:   {}", identity.identity.created_at);
+                    match identity.verify_self() {
+                        Ok(()) => println!("  Signature: ✅ unchanged since its last signed save"),
+                        Err(e) => println!("  Signature: ⚠️  {}", e),
+                    }
                     println!("----------------------------");
                 }
                 Err(e) => {
                     // If loading fails, print a helpful error message to standard error.
                     eprintln!("\nError: Failed to load identity file.");
-                    eprintln!("  Reason: {}", e);
-                    eprintln!("\nHint: Have you run `idp init` in this directory?");
+                    eprintln!("  Reason: {}", explain(&e));
                     return Err("Failed to load identity.".to_string());
                 }
             }
         }
+        Commands::Validate => {
+            // Checked in generic form, so files that no longer load still get a full report.
+            let contents = std::fs::read(id_file_name).map_err(|e| fail(e.into()))?;
+            let contents = idp_core::compress::decompress(contents).map_err(fail)?;
+            if idp_core::encryption::is_encrypted_document(&contents) {
+                return Err(fail(IdpError::Encrypted));
+            }
+            let document: serde_json::Value = match idp_core::Format::detect(&contents) {
+                idp_core::Format::Yaml => serde_yaml::from_slice(&contents).map_err(|e| fail(e.into()))?,
+                idp_core::Format::Json => serde_json::from_slice(&contents).map_err(|e| fail(e.into()))?,
+            };
+            idp_core::schema::validate_document(&document).map_err(fail)?;
+
+            // Then the checks that need the whole document at once.
+            let findings = Identity::load_from_file(id_file_name).map_err(fail)?.validate();
+            if !print_structured(cli.output, &findings)? {
+                println!("✅ '{}' matches its schema.", id_file_name);
+                for finding in &findings {
+                    let icon = match finding.severity {
+                        Severity::Error => "❌",
+                        Severity::Warning => "⚠️ ",
+                    };
+                    println!("{} {}", icon, finding);
+                }
+                if findings.is_empty() {
+                    println!("✅ '{}' is consistent.", id_file_name);
+                }
+            }
+            let errors = findings.iter().filter(|f| f.severity == Severity::Error).count();
+            if errors > 0 {
+                return Err(format!("'{}' contradicts itself in {} place(s).", id_file_name, errors));
+            }
+        }
+        Commands::Diff { other } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let changes = identity.diff(&Identity::load_from_file(other).map_err(fail)?).map_err(fail)?;
+            if print_structured(cli.output, &changes)? {
+                return Ok(());
+            }
+            if changes.is_empty() {
+                println!("✅ '{}' and '{}' are the same.", id_file_name, other);
+            }
+            for change in changes {
+                println!("{}", change);
+            }
+        }
+        Commands::Merge { base, theirs } => {
+            let ours = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let base_identity = Identity::load_from_file(base).map_err(fail)?;
+            let merge = Identity::merge(&base_identity, &ours, &Identity::load_from_file(theirs).map_err(fail)?).map_err(fail)?;
+
+            // Nothing is written until every conflict is resolved by hand.
+            if !merge.is_clean() {
+                for conflict in &merge.conflicts {
+                    println!("⚔️  {}", conflict);
+                }
+                return Err(format!("'{}' and '{}' conflict in {} place(s); nothing was merged.", id_file_name, theirs, merge.conflicts.len()));
+            }
+            let mut merged = merge.merged;
+            merged.touch();
+            let store = cli.keystore.open(&merged.identity.id, key_file_name).map_err(fail)?;
+            let key = merged.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut merged, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("✅ Merged '{}' into '{}'.", theirs, id_file_name);
+        }
+        Commands::Export { gdpr: Some(dir), .. } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            if !identity.sealed.is_empty() {
+                let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+                identity.decrypt_sections(keystore::software_key(store.as_ref(), key.as_ref()).map_err(fail)?).map_err(fail)?;
+            }
+            let log = EventLog::open(id_file_name).map_err(fail)?;
+            let report = identity.subject_access_report(log.events(), chrono::Utc::now()).map_err(fail)?;
+
+            let dir = Path::new(dir);
+            std::fs::create_dir_all(dir).map_err(|e| fail(e.into()))?;
+            std::fs::write(dir.join("subject-access.json"), report.to_json().map_err(fail)?).map_err(|e| fail(e.into()))?;
+            std::fs::write(dir.join("subject-access.html"), report.to_html().map_err(fail)?).map_err(|e| fail(e.into()))?;
+            println!("📦 Wrote the subject access report to {}: subject-access.json and subject-access.html.", dir.display());
+            println!(
+                "  {} consent(s), {} credential(s), {} contract(s) and {} audit entries.",
+                report.shared_with.len(),
+                report.credentials.len(),
+                report.contracts.len(),
+                report.audit_trail.len()
+            );
+        }
+        Commands::Export { format, only, grantee, gdpr: None } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let only: Vec<&str> = only.iter().map(String::as_str).collect();
+            let view = match (grantee, only.is_empty()) {
+                (Some(grantee), _) => Some(identity.export_for_fields(grantee, &only).map_err(fail)?),
+                (None, true) => None,
+                (None, false) => Some(identity.export_view(&only).map_err(fail)?),
+            };
+            match (format.format(), view) {
+                (Some(format), None) => print!("{}", identity.to_string_with_format(format).map_err(fail)?),
+                (Some(format), Some(view)) => print!("{}", format.write(&view).map_err(fail)?),
+                (None, view) => {
+                    let cbor = match view {
+                        Some(view) => idp_core::cbor::to_deterministic_cbor(&view),
+                        None => identity.to_cbor(),
+                    };
+                    std::io::stdout().write_all(&cbor.map_err(fail)?).map_err(|e| fail(e.into()))?;
+                }
+            }
+        }
+        Commands::Encrypt { recipients } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let recipients: Vec<&str> = recipients.iter().map(String::as_str).collect();
+            identity.save_encrypted_to_file(id_file_name, &recipients).map_err(fail)?;
+
+            println!("🔒 Encrypted '{}' to your key-agreement key.", id_file_name);
+            if !recipients.is_empty() {
+                println!("  - Also readable by {} other recipient(s).", recipients.len());
+            }
+            println!("  Other commands need the plain file; run `idp decrypt` to get it back.");
+        }
+        Commands::Decrypt => {
+            // The keychain entry is named by the IDP id, which is inside the encrypted file.
+            if cli.keystore != Keystore::File {
+                return Err(fail(IdpError::Keystore("an encrypted identity can only be opened with a key file".to_string())));
+            }
+            let store = keystore::key_file(key_file_name);
+            let value = store
+                .list()
+                .map_err(fail)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| fail(IdpError::Keystore(format!("'{}' holds no key", key_file_name))))?;
+            let key = store.load(&value).map_err(fail)?;
+            let private_key = keystore::software_key(&store, key.as_ref()).map_err(fail)?;
+
+            let mut identity = Identity::load_from_file_with_key(id_file_name, private_key).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🔓 Decrypted '{}'; it is plain YAML again.", id_file_name);
+        }
+        Commands::Seal { section } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            if identity.is_sealed(section) {
+                println!("🔒 '{}' is already sealed.", section);
+                return Ok(());
+            }
+            identity.seal_section(section).map_err(fail)?;
+
+            identity.touch();
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🔒 Sealed '{}'; only your key can read it now.", section);
+        }
+        Commands::Unseal { section } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let private_key = keystore::software_key(store.as_ref(), key.as_ref()).map_err(fail)?;
+            identity.unseal_section(section, private_key).map_err(fail)?;
+
+            identity.touch();
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🔓 '{}' is readable again.", section);
+        }
+        Commands::Log { patch } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let log = EventLog::open(id_file_name).map_err(fail)?;
+            if print_structured(cli.output, &log.events())? {
+                return log.verify(&identity).map_err(fail);
+            }
+            if log.events().is_empty() {
+                println!("No changes have been logged for '{}' yet.", id_file_name);
+                return Ok(());
+            }
+
+            for event in log.events() {
+                println!("#{:<4} {}  {:<20} {}", event.seq, event.at.format("%Y-%m-%d %H:%M:%S"), event.signed_by.key_id, event.summary());
+                if *patch {
+                    let operations = serde_json::to_string_pretty(&event.changes).map_err(|e| fail(e.into()))?;
+                    println!("{}", operations.lines().map(|line| format!("      {}", line)).collect::<Vec<_>>().join("\n"));
+                }
+            }
+            log.verify(&identity).map_err(fail)?;
+            println!("\n✅ {} change(s), all signed; replaying them gives '{}' as it is.", log.events().len(), id_file_name);
+        }
+        Commands::History { show } => {
+            let Some(history) = GitHistory::discover(Path::new(id_file_name)).map_err(fail)? else {
+                return Err(fail(IdpError::Git(format!("'{}' is not in a git repository", id_file_name))));
+            };
+            if let Some(commit) = show {
+                let identity = history.document_at(commit).map_err(fail)?;
+                if !print_structured(cli.output, &identity)? {
+                    print!("{}", identity.to_string_with_format(idp_core::Format::Yaml).map_err(fail)?);
+                }
+                return Ok(());
+            }
+
+            let versions = history.versions().map_err(fail)?;
+            if print_structured(cli.output, &versions)? {
+                return Ok(());
+            }
+            if versions.is_empty() {
+                println!("'{}' has not been committed yet. Save a change with `--git` to start its history.", id_file_name);
+            }
+            for version in versions {
+                println!("{}  {}  {}", &version.commit[..12], version.at.format("%Y-%m-%d %H:%M:%S"), version.subject);
+            }
+        }
+        Commands::Snapshot { list } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let dir = snapshot::snapshot_dir(Path::new(id_file_name));
+            if *list {
+                let snapshots = snapshot::list_snapshots(&dir).map_err(fail)?;
+                if print_structured(cli.output, &snapshots)? {
+                    return Ok(());
+                }
+                if snapshots.is_empty() {
+                    println!("No snapshots of '{}' yet.", id_file_name);
+                }
+                for path in snapshots {
+                    println!("{}", path.display());
+                }
+                return Ok(());
+            }
+            let path = identity.snapshot_to(&dir).map_err(fail)?;
+            println!("📸 Saved a snapshot of '{}' to {}.", id_file_name, path.display());
+        }
+        Commands::Rollback { snapshot } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let current = identity.clone();
+            identity.restore_from(snapshot).map_err(fail)?;
+
+            // The current state is kept too, so the rollback itself can be undone.
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let kept = current.snapshot_to(snapshot::snapshot_dir(Path::new(id_file_name))).map_err(fail)?;
+            identity.touch();
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("⏪ Rolled '{}' back to {}.", id_file_name, snapshot);
+            println!("  The state before the rollback is in {}.", kept.display());
+        }
+        Commands::Compact { expand } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let size = |path: &str| std::fs::metadata(path).map(|m| m.len()).map_err(|e| fail(e.into()));
+            let before = size(id_file_name)?;
+            identity.save_to_file_compressed(id_file_name, !expand).map_err(fail)?;
+
+            let after = size(id_file_name)?;
+            match expand {
+                true => println!("📄 '{}' is uncompressed again ({} -> {} bytes).", id_file_name, before, after),
+                false => println!("🗜️  Compressed '{}' ({} -> {} bytes).", id_file_name, before, after),
+            }
+        }
+        Commands::Get { path, raw } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let values = identity.get_path(path).map_err(fail)?;
+            if print_structured(cli.output, &values)? {
+                return Ok(());
+            }
+
+            // Each match goes on its own line so wildcard results are easy to script against.
+            for value in values {
+                match value {
+                    serde_yaml::Value::String(text) if *raw => println!("{}", text),
+                    other => {
+                        let yaml = serde_yaml::to_string(&other).map_err(|e| fail(e.into()))?;
+                        println!("{}", yaml.trim_end());
+                    }
+                }
+            }
+        }
         Commands::Set { path, value } => {
-            println!("Setting a value...");
-            println!("  Path: {}", path);
-            println!("  Value: {}", value);
-            // TODO: Implement logic to load, modify, and save the file.
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+
+            // Values are read as YAML so numbers and booleans keep their type,
+            // but anything that doesn't fit the field is retried as plain text.
+            let parsed: serde_yaml::Value = serde_yaml::from_str(value)
+                .unwrap_or_else(|_| serde_yaml::Value::String(value.clone()));
+            let result = match identity.set_path(path, parsed.clone()) {
+                Err(IdpError::Yaml(_)) if !parsed.is_string() => {
+                    identity.set_path(path, serde_yaml::Value::String(value.clone()))
+                }
+                other => other,
+            };
+            result.map_err(fail)?;
+
+            identity.touch();
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("✅ Set '{}' to '{}'.", path, value);
+        }
+        Commands::Patch { file } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let patch = std::fs::read_to_string(file).map_err(|e| fail(e.into()))?;
+            identity.apply_patch_json(&patch).map_err(fail)?;
+
+            identity.touch();
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("✅ Applied '{}'.", file);
+        }
+        Commands::Key { action: KeyCommands::Rotate } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let old_key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let new_private_key = identity.rotate_key(old_key.as_ref()).map_err(fail)?;
+            keystore::replace(store.as_ref(), &mut identity, id_file_name, old_key.as_ref(), &new_private_key)
+                .map_err(fail)?;
+
+            let new_key = identity.key_for_private_key(&new_private_key).map_err(fail)?;
+            println!("🔄 Key rotated.");
+            println!("  - New active key: {}", new_key.key_id);
+            println!("  - New private key saved to: {}", cli.keystore.describe(key_file_name));
+        }
+        Commands::Key { action: KeyCommands::Revoke { key_id, reason } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            identity.revoke_key(key_id, key.as_ref(), reason.as_deref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("⛔ Key '{}' revoked.", key_id);
+        }
+        Commands::Credential { action: CredentialCommands::Templates { show } } => {
+            let dir = templates_dir();
+            if let Some(name) = show {
+                let template = CredentialTemplate::load(name, Some(&dir)).map_err(fail)?;
+                if !print_structured(cli.output, &template)? {
+                    print!("{}", serde_yaml::to_string(&template).map_err(|e| fail(e.into()))?);
+                }
+                return Ok(());
+            }
+            let templates = CredentialTemplate::all(Some(&dir)).map_err(fail)?;
+            if print_structured(cli.output, &templates)? {
+                return Ok(());
+            }
+            for template in templates {
+                let vars: Vec<String> = template.variables.iter().map(|variable| if variable.default.is_none() { variable.name.clone() } else { format!("[{}]", variable.name) }).collect();
+                println!("{:<16} {:<28} {}", template.name, vars.join(" "), template.description);
+            }
+            println!("  Your own templates go in {}, as TOML or YAML.", dir.display());
+        }
+        Commands::Credential { action: CredentialCommands::Types } => {
+            for schema in idp_core::claims::BUILT_IN {
+                let fields: Vec<String> = schema.fields.iter().map(|(name, required)| if *required { name.to_string() } else { format!("[{}]", name) }).collect();
+                println!("{:<14} {:<44} {}", schema.name, schema.type_uri, fields.join(" "));
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Issue { to, claim, claim_type, fields, template, vars, expires, revocable, domain, challenge, tsa, out } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let subject = Identity::load_from_file(to).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            // A typed claim is checked against its schema, and signed as canonical JSON; a
+            // template's claim is one or the other, and it may say when the credential expires.
+            let template = template.as_ref().map(|name| CredentialTemplate::load(name, Some(&templates_dir()))).transpose().map_err(fail)?;
+            let expires = &match (expires, &template) {
+                (Some(expires), _) => Some(*expires),
+                (None, Some(template)) => template.expires_at(chrono::Utc::now()).map_err(fail)?,
+                (None, None) => None,
+            };
+            let claim = match (claim, claim_type, &template) {
+                (_, _, Some(template)) => template.render(&vars.iter().cloned().collect()).map_err(fail)?,
+                (Some(claim), _, None) => claim.clone(),
+                (None, Some(claim_type), None) => {
+                    let schema = idp_core::claims::schema(claim_type)
+                        .ok_or_else(|| fail(IdpError::Credential(format!("unknown claim type '{}'; see `idp credential types`", claim_type))))?;
+                    schema.to_claim(serde_json::Value::Object(fields.iter().cloned().collect())).map_err(fail)?
+                }
+                (None, None, None) => unreachable!("clap requires --claim, --type or --template"),
+            };
+            let claim = &claim;
+
+            // Revocable credentials take an index in the status list, which is saved with it.
+            let mut issued = match revocable {
+                true => {
+                    let issued = identity.issue_revocable_credential(&subject, claim, *expires, key.as_ref()).map_err(fail)?;
+                    save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+                    issued
+                }
+                false => {
+                    let context = idp_core::context::ProofContext::new(domain.as_deref(), challenge.as_deref());
+                    identity.issue_bound_credential(&subject, claim, *expires, &context, key.as_ref()).map_err(fail)?
+                }
+            };
+            if let Some(tsa) = tsa_url(tsa) {
+                let token = idp_core::tsa::stamped_data(&issued.proof).and_then(|data| idp_core::tsa::request_timestamp(&tsa, &data)).map_err(fail)?;
+                let timestamp = idp_core::tsa::add_timestamp(&mut issued.proof, &token).map_err(fail)?;
+                println!("🕰️  Timestamped by {} at {}.", timestamp.tsa, timestamp.time.to_rfc3339());
+            }
+            let mut registry = IssuerRegistry::open(id_file_name).map_err(fail)?;
+            registry.record(subject.identity.id.as_str(), &issued);
+            registry.save().map_err(fail)?;
+            let yaml = serde_yaml::to_string(&issued).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, yaml).map_err(|e| fail(e.into()))?;
+                    println!("🎖️  Issued '{}' to {} ({}).", claim, subject.core.name, subject.identity.id);
+                    if issued.credential.status.is_some() {
+                        println!("  Revoke it with `idp issued revoke {}`.", issued.proof.proof_id);
+                    }
+                    println!("  Send them {}; they add it with `idp credential add {}`.", out, out);
+                }
+                None => print!("{}", yaml),
+            }
+        }
+        Commands::StatusList { action } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            match action {
+                StatusListCommands::Init { url, length } => {
+                    identity.init_status_list(url, *length).map_err(fail)?;
+                    println!("📋 Started a status list of {} entries, to publish at {}.", length, url);
+                    println!("  Issue with `idp credential issue --revocable`, and publish with `idp status-list publish`.");
+                }
+                StatusListCommands::Revoke { index } | StatusListCommands::Reinstate { index } => {
+                    let revoked = matches!(action, StatusListCommands::Revoke { .. });
+                    identity.set_status(*index, revoked).map_err(fail)?;
+                    println!("{} entry {} of the status list; publish the list again for verifiers to see it.", if revoked { "🚫 Revoked" } else { "♻️  Reinstated" }, index);
+                }
+                StatusListCommands::Publish { out } => {
+                    let list = serde_json::to_string_pretty(&identity.publish_status_list(key.as_ref()).map_err(fail)?).map_err(|e| fail(e.into()))?;
+                    match out {
+                        Some(out) => {
+                            std::fs::write(out, list).map_err(|e| fail(e.into()))?;
+                            let url = identity.status_list().map_err(fail)?.map(|list| list.url).unwrap_or_default();
+                            println!("📋 Wrote the signed status list to {}; publish it at {}.", out, url);
+                        }
+                        None => println!("{}", list),
+                    }
+                    return Ok(());
+                }
+            }
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+        }
+        Commands::Anchor { action: AnchorCommands::Submit { proof, calendars } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let target = proof.as_deref().unwrap_or(idp_core::anchor::DOCUMENT);
+            let calendars = match calendars.is_empty() {
+                true => idp_core::anchor::DEFAULT_CALENDARS.iter().map(|calendar| calendar.to_string()).collect(),
+                false => calendars.clone(),
+            };
+            let digest = identity.anchor_digest(target).map_err(fail)?;
+            let timestamp = idp_core::anchor::stamp(&digest, &calendars, &mut idp_core::anchor::submit).map_err(fail)?;
+            let anchor = identity.add_anchor(target, &timestamp).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("⚓ Sent the hash of the {} ({}) to {} calendar(s).", if proof.is_some() { "proof" } else { "document" }, anchor.digest, calendars.len());
+            println!("  It takes a few hours to reach a block; then run `idp anchor upgrade`.");
+        }
+        Commands::Anchor { action: AnchorCommands::Upgrade } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let upgraded = identity.upgrade_anchors(&mut idp_core::anchor::fetch_upgrade).map_err(fail)?;
+            if upgraded > 0 {
+                save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            }
+            let anchors = identity.anchors().map_err(fail)?;
+            let pending = anchors.iter().filter(|anchor| anchor.bitcoin_height.is_none()).count();
+            println!("⚓ Upgraded {} anchor(s); {} of {} still pending.", upgraded, pending, anchors.len());
+        }
+        Commands::Anchor { action: AnchorCommands::Verify { esplora } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let anchors = identity.anchors().map_err(fail)?;
+            let text = cli.output == OutputFormat::Text;
+            let say = |line: String| {
+                if text {
+                    println!("{}", line);
+                }
+            };
+            if anchors.is_empty() {
+                say("No anchors; make one with `idp anchor submit`.".to_string());
+            }
+            let mut failed = 0;
+            let mut checked = Vec::new();
+            for anchor in &anchors {
+                let (matches, attestations) = identity.check_anchor(anchor).map_err(fail)?;
+                say(format!("⚓ {} ({}), anchored {}", anchor.target, anchor.digest, anchor.anchored_at.format("%Y-%m-%d %H:%M")));
+                match (matches, anchor.target.as_str()) {
+                    (true, _) => {}
+                    // The document keeps changing; its anchor covers the version it was made for.
+                    (false, idp_core::anchor::DOCUMENT) => say("  ℹ️  The document has changed since; this anchor covers an earlier version.".to_string()),
+                    (false, _) => {
+                        failed += 1;
+                        say("  ❌ The proof no longer has the hash that was anchored.".to_string());
+                    }
+                }
+                let mut results = Vec::new();
+                for (message, attestation) in attestations {
+                    let result = match attestation {
+                        idp_core::anchor::Attestation::Pending(calendar) => {
+                            say(format!("  ⏳ Pending at {}", calendar));
+                            serde_json::json!({ "status": "pending", "calendar": calendar })
+                        }
+                        idp_core::anchor::Attestation::Bitcoin(height) => match idp_core::anchor::block_merkle_root(esplora, height) {
+                            Ok(root) if root == message => {
+                                say(format!("  ✅ In Bitcoin block {}", height));
+                                serde_json::json!({ "status": "confirmed", "block": height })
+                            }
+                            Ok(_) => {
+                                failed += 1;
+                                say(format!("  ❌ Not in Bitcoin block {}, as it claims", height));
+                                serde_json::json!({ "status": "mismatch", "block": height })
+                            }
+                            Err(e) => {
+                                say(format!("  ⚠️  Claims Bitcoin block {}, which could not be checked: {}", height, e));
+                                serde_json::json!({ "status": "unchecked", "block": height, "error": e.to_string() })
+                            }
+                        },
+                        idp_core::anchor::Attestation::Unknown(..) => {
+                            say("  ❔ An attestation this version does not know".to_string());
+                            serde_json::json!({ "status": "unknown" })
+                        }
+                    };
+                    results.push(result);
+                }
+                checked.push(serde_json::json!({ "target": anchor.target, "digest": anchor.digest, "anchored_at": anchor.anchored_at, "matches": matches, "attestations": results }));
+            }
+            print_structured(cli.output, &checked)?;
+            if failed > 0 {
+                return Err(format!("{} anchor check(s) failed.", failed));
+            }
+        }
+        Commands::Endorse { proof_id, holder, out } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let holder = Identity::load_from_file(holder).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let endorsement = identity.endorse(&holder, proof_id, key.as_ref()).map_err(fail)?;
+            let yaml = serde_yaml::to_string(&endorsement).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, yaml).map_err(|e| fail(e.into()))?;
+                    println!("🤝 Wrote your endorsement to {}; the holder adds it with `idp credential add-endorsement`.", out);
+                }
+                None => print!("{}", yaml),
+            }
+        }
+        Commands::Challenge { claims, audience, minutes, out } => {
+            let challenge = idp_core::presentation::Challenge::new(claims, audience.as_deref(), chrono::Duration::minutes(*minutes));
+            let yaml = serde_yaml::to_string(&challenge).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, yaml).map_err(|e| fail(e.into()))?;
+                    println!("🎯 Wrote a challenge for {} to {}; it stands until {}.", claims.join(", "), out, challenge.expires_at.format("%H:%M"));
+                }
+                None => print!("{}", yaml),
+            }
+        }
+        Commands::Present { challenge, format, out } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let challenge = serde_yaml::from_str(&std::fs::read_to_string(challenge).map_err(|e| fail(e.into()))?).map_err(|e| fail(e.into()))?;
+
+            let presentation = identity.present(&challenge, key.as_ref()).map_err(fail)?;
+            let text = match format {
+                PresentationFormat::Yaml => serde_yaml::to_string(&presentation).map_err(|e| fail(e.into()))?,
+                PresentationFormat::Vp => {
+                    let vp = presentation.to_verifiable_presentation().map_err(fail)?;
+                    format!("{}\n", serde_json::to_string_pretty(&vp).map_err(|e| fail(e.into()))?)
+                }
+            };
+            match out {
+                Some(out) => {
+                    std::fs::write(out, text).map_err(|e| fail(e.into()))?;
+                    println!("📨 Wrote a presentation of {} credential(s) to {}; hand it to the verifier.", presentation.credentials.len(), out);
+                }
+                None => print!("{}", text),
+            }
+        }
+        Commands::VerifyPresentation { file, challenge, holder, issuers } => {
+            let read = |path: &String| std::fs::read_to_string(path).map_err(|e| fail(e.into()));
+            let presentation: idp_core::presentation::Presentation = serde_yaml::from_str(&read(file)?).map_err(|e| fail(e.into()))?;
+            let challenge = serde_yaml::from_str(&read(challenge)?).map_err(|e| fail(e.into()))?;
+            let holder = Identity::load_from_file(holder).map_err(fail)?;
+            let issuers = issuers.iter().map(Identity::load_from_file).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+
+            let report = presentation.verify(&challenge, &holder, &issuers).map_err(fail)?;
+            if print_structured(cli.output, &report)? {
+                return match report.is_valid() {
+                    true => Ok(()),
+                    false => Err("The presentation did not verify.".to_string()),
+                };
+            }
+            if !report.is_valid() {
+                println!("❌ The presentation from {} does not hold:", report.holder);
+                for problem in &report.problems {
+                    println!("  - {}", problem);
+                }
+                return Err("The presentation did not verify.".to_string());
+            }
+            println!("✅ {} presented, for your challenge:", report.holder);
+            for claim in &report.claims {
+                println!("  🎖️  '{}'", claim);
+            }
+        }
+        Commands::Sign { file, message, out } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let content = match (file, message) {
+                (Some(file), _) => std::fs::read(file).map_err(|e| fail(e.into()))?,
+                (None, message) => message.clone().unwrap_or_default().into_bytes(),
+            };
+            let name = file.as_deref().map(|file| Path::new(file).file_name().map_or(file.into(), |name| name.to_string_lossy()));
+
+            let signature = identity.sign_detached(&content, name.as_deref(), key.as_ref()).map_err(fail)?;
+            match out.clone().or_else(|| file.as_ref().map(|file| format!("{}.sig", file))) {
+                Some(out) => {
+                    std::fs::write(&out, signature.to_armor()).map_err(|e| fail(e.into()))?;
+                    println!("✍️  Signed {} as {} with {}; the signature is in {}.", name.as_deref().unwrap_or("the message"), identity.identity.id, signature.signed_by.key_id, out);
+                }
+                None => print!("{}", signature.to_armor()),
+            }
+        }
+        Commands::VerifySignature { signature, signed, message, signer } => {
+            let text = std::fs::read_to_string(signature).map_err(|e| fail(e.into()))?;
+            let signature = idp_core::detached::DetachedSignature::from_armor(&text).map_err(fail)?;
+            let content = match (signed, message) {
+                (Some(file), _) => std::fs::read(file).map_err(|e| fail(e.into()))?,
+                (None, message) => message.clone().unwrap_or_default().into_bytes(),
+            };
+            let signer = Identity::load_from_file(signer).map_err(fail)?;
+
+            signature.verify(&content, &signer).map_err(fail)?;
+            if print_structured(cli.output, &signature)? {
+                return Ok(());
+            }
+            println!("✅ Signed by {} with {} at {}.", signature.signed_by.idp_id, signature.signed_by.key_id, signature.signed_at.format("%Y-%m-%d %H:%M UTC"));
+            if let Some(name) = &signature.name {
+                println!("   As '{}'.", name);
+            }
+        }
+        Commands::Contract { action: ContractCommands::Templates { show } } => {
+            let dir = templates_dir().join("contracts");
+            if let Some(name) = show {
+                let template = ContractTemplate::load(name, Some(&dir)).map_err(fail)?;
+                if !print_structured(cli.output, &template)? {
+                    print!("{}", serde_yaml::to_string(&template).map_err(|e| fail(e.into()))?);
+                }
+                return Ok(());
+            }
+            let templates = ContractTemplate::all(Some(&dir)).map_err(fail)?;
+            if print_structured(cli.output, &templates)? {
+                return Ok(());
+            }
+            for template in templates {
+                let vars: Vec<String> = template.variables.iter().map(|variable| if variable.default.is_none() { variable.name.clone() } else { format!("[{}]", variable.name) }).collect();
+                println!("{:<18} {:<44} {}", template.name, vars.join(" "), template.description);
+            }
+            println!("  Your own contract templates go in {}, as TOML or YAML.", dir.display());
+        }
+        Commands::Contract { action: ContractCommands::New { template, vars, id, out } } => {
+            let template = ContractTemplate::load(template, Some(&templates_dir().join("contracts"))).map_err(fail)?;
+            let now = chrono::Utc::now();
+            let id = id.clone().unwrap_or_else(|| format!("{}-{}", template.name, now.format("%Y%m%d%H%M%S")));
+            let contract = template.instantiate(&id, &vars.iter().cloned().collect(), now).map_err(fail)?;
+
+            let path = out.clone().unwrap_or_else(|| format!("{}.yaml", id));
+            write_contract(&path, &contract).map_err(fail)?;
+            println!("📜 Wrote the draft contract '{}' to {}, between {} parties.", contract.contract_id, path, contract.parties.len());
+            println!("  Each party signs it with `idp contract sign --contract-file {}`.", path);
+        }
+        Commands::Contract { action: ContractCommands::Sign { contract_id, file } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            // 1. A shared contract joins the document, with the signatures it carries.
+            let contract_id = match file {
+                Some(path) => {
+                    let shared = read_contract(path).map_err(fail)?;
+                    if contract_id.as_ref().is_some_and(|id| *id != shared.contract_id) {
+                        return Err(format!("{} holds the contract '{}', not the one you named.", path, shared.contract_id));
+                    }
+                    identity.merge_contract(shared).map_err(fail)?.contract_id.clone()
+                }
+                None => contract_id.clone().ok_or("Name a contract, or give its --contract-file.")?,
+            };
+
+            // 2. Your signature, unless it is there already, and the signed contract back to the file.
+            let signed = identity.contracts.iter().any(|contract| contract.contract_id == contract_id && contract.signature_of(identity.identity.id.as_str()).is_some());
+            let contract = match signed {
+                true => identity.contracts.iter().find(|contract| contract.contract_id == contract_id).cloned().ok_or("The contract is gone.")?,
+                false => identity.sign_contract(&contract_id, key.as_ref()).map_err(fail)?.clone(),
+            };
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            if let Some(path) = file {
+                write_contract(path, &contract).map_err(fail)?;
+            }
+            match signed {
+                true => println!("ℹ️  You had already signed '{}'.", contract.contract_id),
+                false => println!("✍️  Signed '{}'.", contract.contract_id),
+            }
+            match contract.unsigned_parties().as_slice() {
+                [] => println!("🤝 Every party has signed; the contract is {}.", contract.status),
+                waiting => {
+                    println!("⏳ Waiting for {} of {} parties to sign:", waiting.len(), contract.parties.len());
+                    for party in waiting {
+                        println!("  - {}", party);
+                    }
+                    if let Some(path) = file {
+                        println!("  Send them {} to sign with `idp contract sign --contract-file`.", path);
+                    }
+                }
+            }
+        }
+        Commands::Contract { action: ContractCommands::Annotate { contract_id, kind, text, reference, file } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            // 1. A shared contract joins the document first, with the notes it carries.
+            let contract_id = match file {
+                Some(path) => identity.merge_contract(read_contract(path).map_err(fail)?).map_err(fail)?.contract_id.clone(),
+                None => contract_id.clone().ok_or("Name a contract, or give its --contract-file.")?,
+            };
+
+            // 2. The signed note, kept in the document and written back to the file.
+            let kind = match kind {
+                AnnotationKind::Dispute => idp_core::AnnotationKind::Dispute,
+                AnnotationKind::Evidence => idp_core::AnnotationKind::Evidence,
+                AnnotationKind::Resolution => idp_core::AnnotationKind::Resolution,
+                AnnotationKind::Note => idp_core::AnnotationKind::Note,
+            };
+            identity.annotate_contract(&contract_id, kind, text, reference.as_deref(), key.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            let contract = identity.contracts.iter().find(|contract| contract.contract_id == contract_id).ok_or("The contract is gone.")?;
+            if let Some(path) = file {
+                write_contract(path, contract).map_err(fail)?;
+            }
+            println!("📝 Added a signed {} to '{}'.", kind, contract_id);
+            if let Some(dispute) = contract.open_dispute() {
+                println!("  ⚖️  The dispute {} raised on {} is open until a party notes its resolution.", dispute.signed_by.idp_id, dispute.signed_at.format("%Y-%m-%d"));
+            }
+            if file.is_none() {
+                println!("  The other parties see it once you share the contract with `idp contract annotate --contract-file` or `idp contract sign --contract-file`.");
+            }
+        }
+        Commands::Contract { action: ContractCommands::Settle { contract_id, outcome, file, allow_commands, out } } => {
+            use idp_core::consequence::{self, Hook};
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            // 1. The settlement, kept in the document and written back to the file.
+            let contract_id = match file {
+                Some(path) => identity.merge_contract(read_contract(path).map_err(fail)?).map_err(fail)?.contract_id.clone(),
+                None => contract_id.clone().ok_or("Name a contract, or give its --contract-file.")?,
+            };
+            let outcome = match outcome {
+                Outcome::Success => consequence::Outcome::Success,
+                Outcome::Failure => consequence::Outcome::Failure,
+            };
+            let event = identity.settle_contract(&contract_id, outcome, key.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            if let (Some(path), Some(contract)) = (file, identity.contracts.iter().find(|contract| contract.contract_id == contract_id)) {
+                write_contract(path, contract).map_err(fail)?;
+            }
+            if let Some(path) = out {
+                std::fs::write(path, serde_json::to_string_pretty(&event).map_err(|e| fail(e.into()))?).map_err(|e| fail(e.into()))?;
+            }
+            println!("🏁 Settled '{}' with {}: it is {}.", contract_id, outcome, event.status);
+
+            // 2. The consequence: a hook to fire, or words for the parties to act on.
+            match Hook::parse(&event.consequence) {
+                None => println!("  ➡️  {}", event.consequence),
+                Some(Hook::Command(command)) if !allow_commands => {
+                    println!("  ⚠️  The contract would run `{}`; it was not run. Pass --allow-commands to let it.", command);
+                }
+                Some(hook) => {
+                    consequence::fire(&hook, &event, *allow_commands).map_err(fail)?;
+                    match hook {
+                        Hook::Webhook(url) => println!("  📡 Sent the signed settlement to {}.", url),
+                        Hook::Command(command) => println!("  ⚙️  Ran `{}` with the signed settlement.", command),
+                    }
+                }
+            }
+        }
+        Commands::Contract { action: ContractCommands::Status } => {
+            use idp_core::contract::ContractAlert;
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let now = chrono::Utc::now();
+            let statuses: Vec<serde_json::Value> = identity
+                .contracts
+                .iter()
+                .map(|contract| {
+                    let overdue = identity.overdue_contracts(now).iter().any(|overdue| overdue.contract_id == contract.contract_id);
+                    serde_json::json!({
+                        "contract_id": contract.contract_id,
+                        "status": contract.status,
+                        "parties": contract.parties,
+                        "deadline": contract.deadline,
+                        "obligations": contract.obligations,
+                        "alerts": contract.evaluate(identity.identity.id.as_str(), now),
+                        "overdue": overdue,
+                    })
+                })
+                .collect();
+            if print_structured(cli.output, &statuses)? {
+                return Ok(());
+            }
+            if identity.contracts.is_empty() {
+                println!("You have no contracts; sign one another party shares with `idp contract sign --contract-file`.");
+            }
+            for contract in &identity.contracts {
+                let deadline = contract.deadline.map(|deadline| format!(", due {}", deadline.format("%Y-%m-%d %H:%M"))).unwrap_or_default();
+                println!("📜 {} ({}, {} parties{})", contract.contract_id, contract.status, contract.parties.len(), deadline);
+                for obligation in &contract.obligations {
+                    let party = if obligation.party == identity.identity.id.as_str() { "You" } else { obligation.party.as_str() };
+                    let amount = obligation.amount.as_ref().map(|amount| format!(", {}", amount)).unwrap_or_default();
+                    let due = obligation.due.map(|due| format!(", by {}", due.format("%Y-%m-%d"))).unwrap_or_default();
+                    println!("  📌 {}: {}{}{}", party, obligation.action, amount, due);
+                }
+                for alert in contract.evaluate(identity.identity.id.as_str(), now) {
+                    match alert {
+                        ContractAlert::AwaitingYourSignature => println!("  ✍️  Waiting for your signature: `idp contract sign {}`.", contract.contract_id),
+                        ContractAlert::AwaitingOthers(parties) => println!("  ⏳ Waiting for {} other party(s): {}", parties.len(), parties.join(", ")),
+                        ContractAlert::Overdue(deadline) => println!("  ⏰ Overdue: the deadline passed on {}.", deadline.format("%Y-%m-%d %H:%M")),
+                        ContractAlert::ReviewDue(review_at) => println!("  🔎 Due for review since {}.", review_at.format("%Y-%m-%d")),
+                        ContractAlert::Disputed(party) => println!("  ⚖️  Disputed by {}, with no resolution yet.", party),
+                    }
+                }
+            }
+            let overdue = identity.overdue_contracts(now).len();
+            if overdue > 0 {
+                println!("⚠️  {} active contract(s) past their deadline.", overdue);
+            }
+        }
+        Commands::Contract { action: ContractCommands::Verify { contract_id, file, parties, dir } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let contract = match file {
+                Some(path) => read_contract(path).map_err(fail)?,
+                None => {
+                    let contract_id = contract_id.as_deref().unwrap_or_default();
+                    identity
+                        .contracts
+                        .iter()
+                        .find(|contract| contract.contract_id == contract_id)
+                        .cloned()
+                        .ok_or_else(|| fail(IdpError::Contract(format!("there is no contract '{}'", contract_id))))?
+                }
+            };
+
+            // Parties given by file come first, with your own identity, then the directory's.
+            let mut known = parties.iter().map(Identity::load_from_file).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+            known.push(identity);
+            let directories = directories(dir, &config);
+            let resolver = |id: &str| match known.resolve(id)? {
+                Some(identity) => Ok(Some(identity)),
+                None => directories.iter().find_map(|directory| directory.resolve(id).transpose()).transpose(),
+            };
+
+            let report = contract.verify(&resolver).map_err(fail)?;
+            if print_structured(cli.output, &report)? {
+                return match report.problems().is_empty() {
+                    true => Ok(()),
+                    false => Err("The contract did not verify.".to_string()),
+                };
+            }
+            println!("📜 '{}' ({})", report.contract_id, report.status);
+            for check in &report.parties {
+                match (&check.signed, &check.error) {
+                    (Some((key_id, signed_at)), None) => println!("  ✅ {} signed with '{}' on {}", check.party, key_id, signed_at.format("%Y-%m-%d %H:%M")),
+                    (Some((key_id, _)), Some(_)) => println!("  ❌ {} signed with '{}', but the signature does not hold", check.party, key_id),
+                    (None, _) => println!("  ⏳ {} has not signed", check.party),
+                }
+            }
+            for annotation in &contract.annotations {
+                let reference = annotation.reference.as_ref().map(|reference| format!(" ({})", reference)).unwrap_or_default();
+                println!("  📝 {} by {} on {}: {}{}", annotation.kind, annotation.signed_by.idp_id, annotation.signed_at.format("%Y-%m-%d"), annotation.text, reference);
+            }
+            let problems = report.problems();
+            if !problems.is_empty() {
+                println!("❌ The contract does not hold:");
+                for problem in &problems {
+                    println!("  - {}", problem);
+                }
+                return Err("The contract did not verify.".to_string());
+            }
+            match report.is_fully_signed() {
+                true => println!("✅ Every party has signed, and every signature holds."),
+                false => println!("✅ Every signature so far holds; the contract is still a draft."),
+            }
+        }
+        Commands::Consent { action: ConsentCommands::Grant { to, fields, purpose, expires, receipt } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            open_consent(&mut identity, store.as_ref(), key.as_ref()).map_err(fail)?;
+            identity.grant_consent(to, fields, purpose, *expires).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🤝 {} may use {} for '{}' until {}.", to, fields.join(", "), purpose, expires.format("%Y-%m-%d %H:%M"));
+            if let Some(path) = receipt {
+                let receipt = identity.consent_receipt(to, purpose, key.as_ref()).map_err(fail)?;
+                std::fs::write(path, serde_json::to_string_pretty(&receipt).map_err(|e| fail(e.into()))?).map_err(|e| fail(e.into()))?;
+                println!("🧾 Wrote the signed receipt to {}; {} can check it with `idp consent verify`.", path, to);
+            }
+        }
+        Commands::Consent { action: ConsentCommands::Revoke { to, purpose } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            open_consent(&mut identity, store.as_ref(), key.as_ref()).map_err(fail)?;
+            let revoked = identity.revoke_consent(to, purpose.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🚫 Revoked {} consent(s) given to {}; they stay on record as revoked.", revoked, to);
+        }
+        Commands::Consent { action: ConsentCommands::List { purpose } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            if identity.is_sealed("consent") || identity.is_sealed("consent_log") {
+                let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+                open_consent(&mut identity, store.as_ref(), key.as_ref()).map_err(fail)?;
+            }
+            let now = chrono::Utc::now();
+            let consents = match purpose {
+                Some(purpose) => identity.consents_for_purpose(purpose, now),
+                None => identity.consent.iter().collect(),
+            };
+            if print_structured(cli.output, &consents)? {
+                return Ok(());
+            }
+            if identity.consent.is_empty() {
+                println!("You have not given anyone consent; grant it with `idp consent grant`.");
+            }
+            for consent in consents {
+                let state = match (consent.revoked_at, consent.is_expired(now)) {
+                    (Some(revoked_at), _) => format!("revoked {}", revoked_at.format("%Y-%m-%d")),
+                    (None, true) => format!("expired {}", consent.expires_at.format("%Y-%m-%d")),
+                    (None, false) => format!("until {}", consent.expires_at.format("%Y-%m-%d")),
+                };
+                println!("🤝 {} for '{}' ({}): {}", consent.granted_to, consent.purpose, state, consent.fields.join(", "));
+            }
+            if !identity.archived_consent.is_empty() {
+                println!("🗄️  {} expired consent(s) archived in `archived_consent`.", identity.archived_consent.len());
+            }
+        }
+        Commands::Consent { action: ConsentCommands::Audit } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            if identity.is_sealed("consent") || identity.is_sealed("consent_log") {
+                let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+                let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+                open_consent(&mut identity, store.as_ref(), key.as_ref()).map_err(fail)?;
+            }
+            if print_structured(cli.output, &identity.consent_log)? {
+                return identity.verify_consent_log().map_err(fail);
+            }
+            if identity.consent_log.is_empty() {
+                println!("Nothing has happened under consent yet.");
+                return Ok(());
+            }
+            for entry in &identity.consent_log {
+                let fields = if entry.fields.is_empty() { String::new() } else { format!(": {}", entry.fields.join(", ")) };
+                println!("{:>4}  {}  {:<8} {} for '{}'{}", entry.seq, entry.at.format("%Y-%m-%d %H:%M"), entry.action.to_string(), entry.granted_to, entry.purpose, fields);
+            }
+            identity.verify_consent_log().map_err(fail)?;
+            println!("✅ The log's {} entries are whole and in order.", identity.consent_log.len());
+        }
+        Commands::Consent { action: ConsentCommands::Verify { receipt, grantor } } => {
+            let grantor = Identity::load_from_file(grantor).map_err(fail)?;
+            let contents = std::fs::read(receipt).map_err(|e| fail(e.into()))?;
+            let receipt: idp_core::consent::ConsentReceipt = serde_json::from_slice(&contents).map_err(|e| fail(e.into()))?;
+            receipt.verify(&grantor, chrono::Utc::now()).map_err(fail)?;
+            if print_structured(cli.output, &receipt)? {
+                return Ok(());
+            }
+            println!("✅ {} granted {} the use of {} for '{}' on {}, until {}.", receipt.grantor, receipt.granted_to, receipt.fields.join(", "), receipt.purpose, receipt.granted_at.format("%Y-%m-%d"), receipt.expires_at.format("%Y-%m-%d"));
+        }
+        Commands::Profile { action: ProfileCommands::List } => {
+            let names = idp_core::config::profiles(&home).map_err(fail)?;
+            let current = idp_core::config::current_profile(&home).map_err(fail)?;
+            let identities: Vec<Option<Identity>> = names.iter().map(|name| Config::load_profile(&home, name).ok().and_then(|config| config.identity).and_then(|path| Identity::load_from_file(&path).ok())).collect();
+            let listed: Vec<serde_json::Value> = names
+                .iter()
+                .zip(&identities)
+                .map(|(name, identity)| serde_json::json!({ "name": name, "current": current.as_ref() == Some(name), "id": identity.as_ref().map(|identity| identity.identity.id.to_string()) }))
+                .collect();
+            if print_structured(cli.output, &listed)? {
+                return Ok(());
+            }
+            if names.is_empty() {
+                println!("There are no profiles; start one with `idp profile create <name>`.");
+            }
+            for (name, identity) in names.iter().zip(identities) {
+                let marker = if current.as_ref() == Some(name) { "*" } else { " " };
+                match identity {
+                    Some(identity) => println!("{} {:<16} {} ({})", marker, name, identity.core.name, identity.identity.id),
+                    None => println!("{} {:<16} (no identity yet; make it with `idp --profile {} init`)", marker, name, name),
+                }
+            }
+        }
+        Commands::Profile { action: ProfileCommands::Create { name } } => {
+            let dir = idp_core::config::create_profile(&home, name).map_err(fail)?;
+            println!("📁 Started the profile '{}' in {}.", name, dir.display());
+            println!("  Make its identity with `idp --profile {} init`, and put its settings in its {}.", name, idp_core::config::CONFIG_FILE);
+        }
+        Commands::Profile { action: ProfileCommands::Switch { name, .. } } => {
+            idp_core::config::switch_profile(&home, name.as_deref()).map_err(fail)?;
+            match name {
+                Some(name) => println!("🔀 Commands now work on the profile '{}'.", name),
+                None => println!("🔀 Commands now work on `my.idp` in the current directory."),
+            }
+        }
+        Commands::Reputation { action: ReputationCommands::Issue { to, score, event, change, out } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let subject = Identity::load_from_file(to).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let issued = identity.issue_reputation_event(&subject.identity.id, score, event, *change, key.as_ref()).map_err(fail)?;
+            let json = serde_json::to_string_pretty(&issued).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, json).map_err(|e| fail(e.into()))?;
+                    println!("⭐ Signed '{}' ({:+}) toward {}'s '{}' score; they add it with `idp reputation add {} --issuer <your identity file>`.", event, change, subject.core.name, score, out);
+                }
+                None => println!("{}", json),
+            }
+        }
+        Commands::Reputation { action: ReputationCommands::Add { file, issuer, strict } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let contents = std::fs::read(file).map_err(|e| fail(e.into()))?;
+            let issued: idp_core::reputation::IssuedReputationEvent = serde_json::from_slice(&contents).map_err(|e| fail(e.into()))?;
+            let issuers = issuer.iter().map(Identity::load_from_file).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+            let event = issued.event.event.clone();
+            let score = identity.add_reputation_event(issued, &issuers, *strict).map_err(fail)?.clone();
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("⭐ Added '{}' to your '{}' score, now {}.", event, score.score_name, score.value);
+        }
+        Commands::Reputation { action: ReputationCommands::Export { to, events, out } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let subject = Identity::load_from_file(to).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let contents = std::fs::read_to_string(events).map_err(|e| fail(e.into()))?;
+            let entries: Vec<idp_core::reputation::ReputationEntry> = serde_yaml::from_str(&contents).map_err(|e| fail(e.into()))?;
+            let batch = identity.export_reputation(&subject.identity.id, &entries, key.as_ref()).map_err(fail)?;
+            let json = serde_json::to_string_pretty(&batch).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, json).map_err(|e| fail(e.into()))?;
+                    println!("📦 Signed {} reputation event(s) for {} ({}).", batch.events.len(), subject.core.name, subject.identity.id);
+                    println!("  Send them {}; they take it in with `idp reputation import {} --issuer <your identity file>`.", out, out);
+                }
+                None => println!("{}", json),
+            }
+        }
+        Commands::Reputation { action: ReputationCommands::Import { file, issuer } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let contents = std::fs::read(file).map_err(|e| fail(e.into()))?;
+            let batch: idp_core::reputation::ReputationBatch = serde_json::from_slice(&contents).map_err(|e| fail(e.into()))?;
+            let issuer = Identity::load_from_file(issuer).map_err(fail)?;
+            let (added, skipped) = identity.import_reputation(batch, &vec![issuer]).map_err(fail)?;
+            if added > 0 {
+                save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            }
+            println!("⭐ Added {} reputation event(s); {} were already in your history.", added, skipped);
+        }
+        Commands::Reputation { action: ReputationCommands::Show { half_life, cap, weights, unsigned_weight, history } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let now = chrono::Utc::now();
+            let attested = identity.attested_reputation(now);
+            let policy = idp_core::reputation::ReputationPolicy {
+                weights: weights.iter().cloned().collect(),
+                unsigned_weight: *unsigned_weight,
+                half_life: *half_life,
+                issuer_cap: *cap,
+                ..Default::default()
+            };
+            let report = serde_json::json!({
+                "scores": identity.reputation.iter().map(|score| serde_json::json!({
+                    "score_name": score.score_name,
+                    "value": score.value,
+                    "computed": score.compute(&policy, now),
+                    "history": if *history { serde_json::to_value(&score.history).unwrap_or_default() } else { serde_json::Value::Null },
+                })).collect::<Vec<_>>(),
+                "attested": attested.iter().map(|(credential, attestation)| serde_json::json!({ "issued_by": credential.issued_by, "attestation": attestation })).collect::<Vec<_>>(),
+            });
+            if print_structured(cli.output, &report)? {
+                return Ok(());
+            }
+            if identity.reputation.is_empty() && attested.is_empty() {
+                println!("You have no reputation yet; others issue events to you with `idp reputation issue`, or attest to a score with `idp credential issue --type reputation`.");
+                return Ok(());
+            }
+            if !identity.reputation.is_empty() {
+                println!("{:<20} {:>8} {:>10}", "SCORE", "RAW", "COMPUTED");
+            }
+            for score in &identity.reputation {
+                println!("{:<20} {:>8} {:>10.2}", score.score_name, score.value, score.compute(&policy, now));
+                if *history {
+                    for event in &score.history {
+                        let issuer = event.issued_by.as_ref().map(|issued_by| issued_by.idp_id.to_string()).unwrap_or_else(|| "(unsigned)".to_string());
+                        println!("  {}  {:>+5}  {:>8.2}  '{}' from {}", event.timestamp.format("%Y-%m-%d"), event.change, policy.weigh(event, now), event.event, issuer);
+                    }
+                }
+            }
+            for (credential, attestation) in &attested {
+                let scale = attestation.scale.map(|scale| format!(" of {}", scale)).unwrap_or_default();
+                let platform = attestation.platform.as_ref().map(|platform| format!(" on {}", platform)).unwrap_or_default();
+                println!("🏅 {} {}{}{} as of {}, attested by {}", attestation.score_name, attestation.value, scale, platform, attestation.as_of, credential.issued_by);
+            }
+        }
+        Commands::Issued { action: IssuedCommands::List } => {
+            let registry = IssuerRegistry::open(id_file_name).map_err(fail)?;
+            let now = chrono::Utc::now();
+            let listed = registry
+                .entries()
+                .iter()
+                .map(|entry| {
+                    let mut listed = serde_json::to_value(entry)?;
+                    listed["state"] = entry.state(now).to_string().into();
+                    Ok(listed)
+                })
+                .collect::<Result<Vec<_>, serde_json::Error>>()
+                .map_err(|e| fail(e.into()))?;
+            if print_structured(cli.output, &listed)? {
+                return Ok(());
+            }
+            if registry.entries().is_empty() {
+                println!("You have not issued any credentials.");
+            }
+            for entry in registry.entries() {
+                let revocable = if entry.status.is_some() { "" } else { " (not revocable)" };
+                println!("{:<26} {:<8} '{}' to {}, {}{}", entry.id, entry.state(now).to_string(), entry.claim, entry.subject, entry.issued_at.format("%Y-%m-%d"), revocable);
+            }
+        }
+        Commands::Issued { action: IssuedCommands::Revoke { id, out } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let mut registry = IssuerRegistry::open(id_file_name).map_err(fail)?;
+
+            // 1. The bit in the document's list, then the registry.
+            let entry = registry.revoke(id, &mut identity).map_err(fail)?.clone();
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            registry.save().map_err(fail)?;
+            println!("🚫 Revoked '{}' ({}), issued to {}.", entry.claim, entry.id, entry.subject);
+
+            // 2. The signed list, where verifiers read it from when that is a local file.
+            let url = entry.status.map(|status| status.list).unwrap_or_default();
+            let target = out.clone().or_else(|| match url.strip_prefix("file://") {
+                Some(path) => Some(path.to_string()),
+                None => (!url.contains("://")).then(|| url.clone()),
+            });
+            match target {
+                Some(target) => {
+                    let list = serde_json::to_string_pretty(&identity.publish_status_list(key.as_ref()).map_err(fail)?).map_err(|e| fail(e.into()))?;
+                    std::fs::write(&target, list).map_err(|e| fail(e.into()))?;
+                    println!("📋 Wrote the updated status list to {}.", target);
+                }
+                None => println!("  Publish the list at {} again for verifiers to see it: `idp status-list publish -o <file>`.", url),
+            }
+        }
+        Commands::Credential { action: CredentialCommands::IssueSdJwt { to, fields, expires, out } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let subject = Identity::load_from_file(to).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let claims = fields.iter().cloned().collect();
+            let sd_jwt = identity.issue_sd_jwt(&subject, &claims, *expires, key.as_ref()).map_err(fail)?;
+            let mut registry = IssuerRegistry::open(id_file_name).map_err(fail)?;
+            registry.record_sd_jwt(&sd_jwt).map_err(fail)?;
+            registry.save().map_err(fail)?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, format!("{}\n", sd_jwt)).map_err(|e| fail(e.into()))?;
+                    println!("🎖️  Issued an SD-JWT with {} field(s) to {} ({}).", claims.len(), subject.core.name, subject.identity.id);
+                    let key_id = &identity.key_for_private_key(key.as_ref()).map_err(fail)?.key_id;
+                    println!("  Send them {} and the output of `idp key export {}`, and they add it with `idp credential import {} --issuer-key <that file>`.", out, key_id, out);
+                }
+                None => println!("{}", sd_jwt),
+            }
+        }
+        Commands::Credential { action: CredentialCommands::IssueBbs { to, fields, expires, out } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let subject = Identity::load_from_file(to).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            // 1. The BBS key is derived from the root key the first time it is needed.
+            let root_key_id = identity.key_for_private_key(key.as_ref()).map_err(fail)?.key_id.clone();
+            if identity.find_key(&format!("{}/bbs", root_key_id)).is_none() {
+                let root = key.software_key().ok_or_else(|| fail(IdpError::Key("BBS keys are derived from the root key, which must be a software key".to_string())))?;
+                identity.add_bbs_key(root).map_err(fail)?;
+                println!("🔑 Added the BBS key '{}/bbs'; holders need your updated identity file to derive proofs.", root_key_id);
+                save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            }
+
+            // 2. Issue, as `idp credential issue` does.
+            let claims = fields.iter().cloned().collect();
+            let issued = identity.issue_bbs_credential(&subject, &claims, *expires, key.as_ref()).map_err(fail)?;
+            let mut registry = IssuerRegistry::open(id_file_name).map_err(fail)?;
+            registry.record(subject.identity.id.as_str(), &issued);
+            registry.save().map_err(fail)?;
+            let yaml = serde_yaml::to_string(&issued).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, yaml).map_err(|e| fail(e.into()))?;
+                    println!("🎖️  Issued a BBS credential with {} field(s) to {} ({}).", claims.len(), subject.core.name, subject.identity.id);
+                    println!("  Send them {}; they add it with `idp credential add {}`.", out, out);
+                }
+                None => print!("{}", yaml),
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Add { file } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let contents = std::fs::read_to_string(file).map_err(|e| fail(e.into()))?;
+            let issued: idp_core::credential::IssuedCredential = serde_yaml::from_str(&contents).map_err(|e| fail(e.into()))?;
+            let claim = issued.credential.claim.clone();
+            let issuer = issued.credential.issued_by.clone();
+
+            identity.add_credential(issued).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🎖️  Added '{}', issued by {}.", claim, issuer);
+        }
+        Commands::Credential { action: CredentialCommands::AddEndorsement { file } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let contents = std::fs::read_to_string(file).map_err(|e| fail(e.into()))?;
+            let endorsement: idp_core::Proof = serde_yaml::from_str(&contents).map_err(|e| fail(e.into()))?;
+            let endorser = endorsement.signed_by.idp_id.to_string();
+
+            identity.add_endorsement(endorsement).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🤝 Added an endorsement by {}.", endorser);
+        }
+        Commands::Credential { action: CredentialCommands::Import { file, issuer_key } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let vc = std::fs::read_to_string(file).map_err(|e| fail(e.into()))?;
+            let issuer_key = match issuer_key {
+                Some(path) => {
+                    let jwk = serde_json::from_str(&std::fs::read_to_string(path).map_err(|e| fail(e.into()))?).map_err(|e| fail(e.into()))?;
+                    Some(idp_core::PublicKey::from_jwk(&jwk).map_err(fail)?)
+                }
+                None => None,
+            };
+
+            // SD-JWTs carry their disclosures after a `~`; other VCs never contain one.
+            let credential = match vc.contains('~') {
+                true => {
+                    let sd_jwt = idp_core::sd_jwt::SdJwt::parse(&vc).map_err(fail)?;
+                    let issuer_key = issuer_key.ok_or_else(|| fail(IdpError::Credential("an SD-JWT can only be checked with its issuer's key (--issuer-key)".to_string())))?;
+                    identity.add_sd_jwt(&sd_jwt, &issuer_key).map_err(fail)?
+                }
+                false => identity.import_verifiable_credential(&vc, issuer_key.as_ref()).map_err(fail)?,
+            };
+            println!("🎖️  Imported '{}', issued by {}.", credential.claim, credential.issued_by);
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+        }
+        Commands::Credential { action: CredentialCommands::Verify { proof_id, issuers, holder, domain, challenge } } => {
+            let holder_file = holder.as_deref().unwrap_or(id_file_name);
+            let mut holder = Identity::load_from_file(holder_file).map_err(fail)?;
+            let issuers = issuers.iter().map(Identity::load_from_file).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+            // Sealed credentials are opened with your key; another holder's stay out of reach.
+            if holder.is_locked("credentials") {
+                if holder_file != id_file_name {
+                    return Err(fail(IdpError::Credential(format!("the credentials of {} are sealed; only its holder can verify them", holder.identity.id))));
+                }
+                let store = cli.keystore.open(&holder.identity.id, key_file_name).map_err(fail)?;
+                let key = holder.signer_from(store.as_ref()).map_err(fail)?;
+                holder.decrypt_sections(keystore::software_key(store.as_ref(), key.as_ref()).map_err(fail)?).map_err(fail)?;
+            }
+            let credentials: Vec<_> = holder
+                .credentials
+                .iter()
+                .filter(|credential| proof_id.as_ref().is_none_or(|proof_id| credential.proof == *proof_id))
+                .collect();
+            if let (Some(proof_id), true) = (proof_id, credentials.is_empty()) {
+                return Err(fail(IdpError::Credential(format!("no credential has the proof '{}'", proof_id))));
+            }
+            if credentials.is_empty() {
+                if !print_structured(cli.output, &Vec::<serde_json::Value>::new())? {
+                    println!("No credentials to check.");
+                }
+                return Ok(());
+            }
+
+            let cache = idp_core::status_list::StatusListCache::new(status_cache_dir());
+            let expected = idp_core::context::ProofContext::new(domain.as_deref(), challenge.as_deref());
+            let mut failed = 0;
+            let mut results = Vec::new();
+            for credential in credentials {
+                let problems: Vec<String> = match (holder.credential_proof(credential), issuers.iter().find(|issuer| issuer.identity.id.to_string() == credential.issued_by)) {
+                    (None, _) => vec![format!("its proof '{}' is missing", credential.proof)],
+                    // Imported VCs are checked with the issuer's key when its identity file is given, or by their did:key.
+                    (Some(proof), issuer) if [idp_core::vc::IMPORTED_VC_PROOF, idp_core::sd_jwt::SD_JWT_PROOF].contains(&proof.proof_type.as_str()) => {
+                        let key = issuer.and_then(|issuer| issuer.find_key(proof.signed_by.key_id.rsplit('#').next().unwrap_or_default()));
+                        let timestamp = idp_core::tsa::proof_timestamp(proof).and_then(Result::err).map(|e| format!("its timestamp does not verify: {}", e));
+                        holder.verify_imported_credential(proof, key).err().map(|e| e.to_string()).into_iter().chain(timestamp).chain(expected.mismatch(proof)).collect()
+                    }
+                    (_, None) => vec![format!("the identity file of {} was not given (--issuer)", credential.issued_by)],
+                    (Some(proof), Some(issuer)) => {
+                        let mut problems = holder.verify_credential_in(credential, proof, issuer, &expected).problems();
+                        if let Some(status) = &credential.status {
+                            let checked = cache
+                                .get(&status.list)
+                                .and_then(|list| idp_core::status_list::check_status(status, &credential.issued_by, &list, &issuer.system.public_keys));
+                            match checked {
+                                Ok(false) => {}
+                                Ok(true) => problems.push(format!("the issuer has revoked it (entry {} of {})", status.index, status.list)),
+                                Err(e) => problems.push(format!("its status could not be checked: {}", e)),
+                            }
+                        }
+                        problems
+                    }
+                };
+                if !problems.is_empty() {
+                    failed += 1;
+                }
+                if cli.output != OutputFormat::Text {
+                    results.push(serde_json::json!({
+                        "claim": credential.claim,
+                        "issued_by": credential.issued_by,
+                        "proof": credential.proof,
+                        "valid": problems.is_empty(),
+                        "problems": problems,
+                    }));
+                    continue;
+                }
+                match problems.is_empty() {
+                    true => {
+                        println!("✅ '{}' from {}", credential.claim, credential.issued_by);
+                        if let Some(Ok(timestamp)) = holder.credential_proof(credential).and_then(idp_core::tsa::proof_timestamp) {
+                            println!("  🕰️  signed by {}, according to {}", timestamp.time.to_rfc3339(), timestamp.tsa);
+                        }
+                        for endorsement in holder.endorsements(credential) {
+                            let endorser = &endorsement.signed_by.idp_id;
+                            match issuers.iter().find(|issuer| issuer.identity.id == *endorser) {
+                                Some(issuer) => match holder.verify_endorsement(endorsement, issuer) {
+                                    Ok(()) => println!("  🤝 endorsed by {}", endorser),
+                                    Err(e) => println!("  ❌ endorsed by {}, but it does not verify: {}", endorser, e),
+                                },
+                                None => println!("  ❔ endorsed by {}, not checked (--issuer)", endorser),
+                            }
+                        }
+                    }
+                    false => {
+                        println!("❌ '{}' from {}", credential.claim, credential.issued_by);
+                        for problem in problems {
+                            println!("  - {}", problem);
+                        }
+                    }
+                }
+            }
+            print_structured(cli.output, &results)?;
+            if failed > 0 {
+                return Err(format!("{} credential(s) did not verify.", failed));
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Chain { proof_id, anchors, require, dir, issuers, holder } } => {
+            let holder = Identity::load_from_file(holder.as_deref().unwrap_or(id_file_name)).map_err(fail)?;
+            let issuers = issuers.iter().map(Identity::load_from_file).collect::<Result<Vec<_>, _>>().map_err(fail)?;
+            let credential = holder
+                .credentials
+                .iter()
+                .find(|credential| credential.proof == *proof_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("no credential has the proof '{}'", proof_id))))?;
+
+            // Issuers given by file come first, then the directories'.
+            let directories = directories(dir, &config);
+            let resolver = |id: &str| match issuers.resolve(id)? {
+                Some(identity) => Ok(Some(identity)),
+                None => directories.iter().find_map(|directory| directory.resolve(id).transpose()).transpose(),
+            };
+            let anchors = if anchors.is_empty() { &config.trusted_issuers } else { anchors };
+            if anchors.is_empty() {
+                return Err(fail(IdpError::Trust("no identity is trusted as a root; pass --anchor, or list `trusted_issuers` in the configuration".to_string())));
+            }
+            let mut chain = idp_core::trust::TrustChain::new(&resolver);
+            for anchor in anchors {
+                chain = chain.anchor(anchor);
+            }
+            if let Some(claim) = require {
+                chain = chain.require_claim(claim);
+            }
+
+            let links = chain.validate(&holder, credential).map_err(fail)?;
+            println!("✅ '{}' leads to a trust anchor in {} link(s):", credential.claim, links.len());
+            for link in links {
+                println!("  🔗 {} vouched for {} with '{}'", link.issuer, link.subject, link.claim);
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Gc { dry_run: true } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let expired = identity.expired_credentials(chrono::Utc::now());
+            if expired.is_empty() {
+                println!("No expired credentials.");
+            }
+            for credential in expired {
+                println!("🗄️  Would archive '{}' from {}, expired {}", credential.claim, credential.issued_by, credential.expires_at.map(|at| at.to_rfc3339()).unwrap_or_default());
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Gc { dry_run: false } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+
+            let archived: Vec<String> = identity.prune_expired().iter().map(|credential| format!("'{}' from {}", credential.claim, credential.issued_by)).collect();
+            if archived.is_empty() {
+                println!("No expired credentials.");
+                return Ok(());
+            }
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            for credential in &archived {
+                println!("🗄️  Archived {}", credential);
+            }
+            println!("  They stay in `archived_credentials`, with their proofs.");
+        }
+        Commands::Credential { action: CredentialCommands::Timestamp { proof_id, tsa } } => {
+            let mut identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let store = cli.keystore.open(&identity.identity.id, key_file_name).map_err(fail)?;
+            let key = identity.signer_from(store.as_ref()).map_err(fail)?;
+            let tsa = tsa_url(tsa).ok_or_else(|| fail(IdpError::Timestamp("no TSA was given (--tsa or IDP_TSA)".to_string())))?;
+
+            let proof = identity.proofs.iter().find(|proof| proof.proof_id == *proof_id).ok_or_else(|| fail(IdpError::Timestamp(format!("no proof '{}' in the document", proof_id))))?;
+            let token = idp_core::tsa::stamped_data(proof).and_then(|data| idp_core::tsa::request_timestamp(&tsa, &data)).map_err(fail)?;
+            let timestamp = identity.timestamp_proof(proof_id, &token).map_err(fail)?;
+            save(&mut identity, key.as_ref(), id_file_name).map_err(fail)?;
+            println!("🕰️  '{}' is timestamped by {} at {}.", proof_id, timestamp.tsa, timestamp.time.to_rfc3339());
+        }
+        Commands::Credential { action: CredentialCommands::Disclose { proof_id, fields } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let credential = identity
+                .credentials
+                .iter()
+                .find(|credential| credential.proof == *proof_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("no credential has the proof '{}'", proof_id))))?;
+            let sd_jwt = identity
+                .held_sd_jwt(credential)
+                .ok_or_else(|| fail(IdpError::Credential(format!("'{}' is not an SD-JWT, so its fields cannot be disclosed one by one", proof_id))))?;
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            println!("{}", sd_jwt.disclose(&fields).map_err(fail)?);
+        }
+        Commands::Credential { action: CredentialCommands::VerifyDisclosure { file, issuer } } => {
+            let sd_jwt = idp_core::sd_jwt::SdJwt::parse(&std::fs::read_to_string(file).map_err(|e| fail(e.into()))?).map_err(fail)?;
+            let issuer = Identity::load_from_file(issuer).map_err(fail)?;
+
+            // The JWT names the key that signed it as `<issuer id>#<key id>`.
+            let header = idp_core::jwt::Jws::decode(&sd_jwt.jwt).map_err(fail)?.header;
+            let key_id = header["kid"].as_str().and_then(|kid| kid.rsplit_once('#')).map(|(_, key_id)| key_id).unwrap_or_default();
+            let key = issuer
+                .find_key(key_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("{} has no key '{}' to check the SD-JWT with", issuer.identity.id, key_id))))?;
+            let disclosed = sd_jwt.verify(key).map_err(fail)?;
+            if disclosed.issuer != issuer.identity.id.as_str() {
+                return Err(fail(IdpError::Credential(format!("the SD-JWT was issued by {}, not {}", disclosed.issuer, issuer.identity.id))));
+            }
+            if print_structured(cli.output, &disclosed)? {
+                return Ok(());
+            }
+            println!("✅ Issued by {} to {}", disclosed.issuer, disclosed.subject.as_deref().unwrap_or("(no subject)"));
+            for (name, value) in &disclosed.claims {
+                println!("  {}: {}", name, value);
+            }
+            if disclosed.claims.is_empty() {
+                println!("  (no fields disclosed)");
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Derive { proof_id, fields, issuer, out } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let issuer = Identity::load_from_file(issuer).map_err(fail)?;
+            let credential = identity
+                .credentials
+                .iter()
+                .find(|credential| credential.proof == *proof_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("no credential has the proof '{}'", proof_id))))?;
+            let key_id = identity.credential_proof(credential).map(|proof| proof.signed_by.key_id.as_str()).unwrap_or_default();
+            let key = issuer
+                .find_key(key_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("{} has no key '{}' to derive a proof with", issuer.identity.id, key_id))))?;
+
+            let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+            let derived = identity.derive_credential(credential, &fields, key).map_err(fail)?;
+            let yaml = serde_yaml::to_string(&derived).map_err(|e| fail(e.into()))?;
+            match out {
+                Some(out) => {
+                    std::fs::write(out, yaml).map_err(|e| fail(e.into()))?;
+                    println!("🕶️  Wrote a proof revealing {} of {} field(s) to {}.", fields.len(), derived.fields.len(), out);
+                }
+                None => print!("{}", yaml),
+            }
+        }
+        Commands::Credential { action: CredentialCommands::VerifyDerived { file, issuer } } => {
+            let contents = std::fs::read_to_string(file).map_err(|e| fail(e.into()))?;
+            let derived: idp_core::bbs::DerivedCredential = serde_yaml::from_str(&contents).map_err(|e| fail(e.into()))?;
+            let issuer = Identity::load_from_file(issuer).map_err(fail)?;
+            if derived.issued_by != issuer.identity.id.as_str() {
+                return Err(fail(IdpError::Credential(format!("the credential was issued by {}, not {}", derived.issued_by, issuer.identity.id))));
+            }
+            let key = issuer
+                .find_key(&derived.proof.signed_by.key_id)
+                .ok_or_else(|| fail(IdpError::Credential(format!("{} has no key '{}' to check the proof with", issuer.identity.id, derived.proof.signed_by.key_id))))?;
+
+            derived.verify(key).map_err(fail)?;
+            let revealed = serde_json::json!({ "issued_by": derived.issued_by, "fields": derived.fields.len(), "claims": derived.claims() });
+            if print_structured(cli.output, &revealed)? {
+                return Ok(());
+            }
+            println!("✅ Issued by {}; {} of {} field(s) revealed", derived.issued_by, derived.claims().len(), derived.fields.len());
+            for (name, value) in derived.claims() {
+                println!("  {}: {}", name, value);
+            }
+        }
+        Commands::Credential { action: CredentialCommands::Export { proof_id, format } } => {
+            let identity = Identity::load_from_file(id_file_name).map_err(fail)?;
+            let mut exported = Vec::new();
+            for credential in identity.credentials.iter().filter(|credential| proof_id.as_ref().is_none_or(|proof_id| credential.proof == *proof_id)) {
+                let proof = identity
+                    .credential_proof(credential)
+                    .ok_or_else(|| fail(IdpError::Credential(format!("the proof '{}' of '{}' is missing", credential.proof, credential.claim))))?;
+                exported.push(match format {
+                    CredentialFormat::Idp => {
+                        let issued = idp_core::credential::IssuedCredential { credential: credential.clone(), proof: proof.clone() };
+                        serde_json::to_value(issued).map_err(|e| fail(e.into()))?
+                    }
+                    CredentialFormat::Vc => idp_core::vc::to_verifiable_credential(&identity.identity.id, credential, proof).map_err(fail)?,
+                    CredentialFormat::Jwt => idp_core::vc::to_jwt(&identity.identity.id, credential, proof).map_err(fail)?.into(),
+                });
+            }
+
+            // One credential is written on its own, several as a list.
+            let output = match exported.len() {
+                0 => return Err(fail(IdpError::Credential("no credential to export".to_string()))),
+                1 => exported.remove(0),
+                _ => serde_json::Value::Array(exported),
+            };
+            match format {
+                CredentialFormat::Idp => print!("{}", serde_yaml::to_string(&output).map_err(|e| fail(e.into()))?),
+                CredentialFormat::Vc => println!("{}", serde_json::to_string_pretty(&output).map_err(|e| fail(e.into()))?),
+                CredentialFormat::Jwt => {
+                    for token in output.as_array().cloned().unwrap_or_else(|| vec![output]) {
+                        println!("{}", token.as_str().unwrap_or_default());
+                    }
+                }
+            }
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_cli() {
+        Cli::command().debug_assert();
+    }
+}
@@ -0,0 +1,44 @@
+// crates/idp-cli/tests/cli.rs
+
+// End-to-end checks of the `idp` binary, each in a fresh directory with its own IDP home.
+
+use std::path::Path;
+use std::process::{Command, Output};
+
+// Runs `idp` in `dir` with `args`, an empty passphrase, and `dir` as the IDP home.
+fn idp(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_idp-cli"))
+        .args(args)
+        .current_dir(dir)
+        .env("IDP_HOME", dir)
+        .env("IDP_PASSPHRASE", "")
+        .env_remove("IDP_FILE")
+        .env_remove("IDP_KEY_FILE")
+        .env_remove("IDP_PROFILE")
+        .env_remove("IDP_OUTPUT")
+        .output()
+        .expect("the idp binary runs")
+}
+
+#[test]
+fn it_validates_a_compacted_file() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(idp(dir.path(), &["init", "--name", "Alice", "--bio", "Compacts her files."]).status.success());
+    assert!(idp(dir.path(), &["compact"]).status.success());
+
+    let validated = idp(dir.path(), &["validate"]);
+    assert!(validated.status.success(), "{}", String::from_utf8_lossy(&validated.stderr));
+    assert!(String::from_utf8_lossy(&validated.stdout).contains("is consistent"));
+}
+
+#[test]
+fn it_prints_the_event_log_as_json() {
+    let dir = tempfile::tempdir().unwrap();
+    assert!(idp(dir.path(), &["init", "--name", "Alice", "--bio", "Reads her log with jq."]).status.success());
+    assert!(idp(dir.path(), &["set", "core.bio", "Keeps a log."]).status.success());
+
+    let log = idp(dir.path(), &["--output", "json", "log"]);
+    assert!(log.status.success(), "{}", String::from_utf8_lossy(&log.stderr));
+    let events: serde_json::Value = serde_json::from_slice(&log.stdout).expect("the log is JSON");
+    assert!(events.as_array().is_some_and(|events| !events.is_empty()));
+}
@@ -0,0 +1,241 @@
+// crates/idp-core/src/address.rs
+
+// Blockchain addresses as proofs. A wallet shows it controls an Ethereum-style address by
+// signing a statement naming the identity with `personal_sign` (EIP-191); the signature is kept
+// as a proof, and anyone can recover the signing key from it and compare addresses.
+
+use data_encoding::{BASE64, HEXLOWER, HEXLOWER_PERMISSIVE};
+use k256::ecdsa::{RecoveryId, VerifyingKey};
+use ring::digest;
+use sha3::{Digest, Keccak256};
+
+use crate::crypto::{self, VerifyError};
+use crate::{id, Identity, IdpError, IdpId, Proof, PublicKey, SecretBytes, SignatureComponent, Signer};
+
+/// Proof type for a wallet's signature showing that it controls an address.
+pub const ADDRESS_OWNERSHIP_PROOF: &str = "Secp256k1AddressOwnership";
+
+/// Signature algorithm of address proofs: recoverable secp256k1 ECDSA (r || s || v) over an
+/// EIP-191 `personal_sign` message.
+pub const EIP191: &str = "EIP-191";
+
+/// The Ethereum address of a secp256k1 public key, in EIP-55 checksum case.
+pub fn ethereum_address(public_key: &VerifyingKey) -> String {
+    // The last 20 bytes of the Keccak-256 hash of the uncompressed point, without its 0x04 tag.
+    let point = public_key.to_encoded_point(false);
+    let hash = Keccak256::digest(&point.as_bytes()[1..]);
+    checksummed(&HEXLOWER.encode(&hash[12..]))
+}
+
+/// Checks the shape of an address and returns it in EIP-55 checksum case. Mixed-case input
+/// already carries a checksum, which must then be right.
+pub fn parse_address(address: &str) -> Result<String, IdpError> {
+    let invalid = || IdpError::Key(format!("'{}' is not an Ethereum address", address));
+    let hex = address.strip_prefix("0x").ok_or_else(invalid)?;
+    if hex.len() != 40 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(invalid());
+    }
+
+    let normalized = checksummed(&hex.to_ascii_lowercase());
+    let mixed_case = hex.bytes().any(|b| b.is_ascii_lowercase()) && hex.bytes().any(|b| b.is_ascii_uppercase());
+    if mixed_case && normalized[2..] != *hex {
+        return Err(IdpError::Key(format!("'{}' has an invalid EIP-55 checksum", address)));
+    }
+    Ok(normalized)
+}
+
+// EIP-55: a letter is upper case when the matching nibble of the Keccak-256 hash of the
+// lowercase hex address is 8 or more.
+fn checksummed(lower_hex: &str) -> String {
+    let hash = Keccak256::digest(lower_hex.as_bytes());
+    let digits: String = lower_hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = if i % 2 == 0 { hash[i / 2] >> 4 } else { hash[i / 2] & 0x0f };
+            if nibble >= 8 { c.to_ascii_uppercase() } else { c }
+        })
+        .collect();
+    format!("0x{}", digits)
+}
+
+/// The statement a wallet signs to show that its address belongs to an identity.
+pub fn ownership_statement(idp_id: &IdpId, address: &str) -> String {
+    format!("I control {} and link it to the IDP identity {}", address, idp_id)
+}
+
+// EIP-191 version 0x45, what `personal_sign` hashes: the prefix keeps a signed message from
+// ever being a valid transaction.
+fn personal_message_hash(message: &[u8]) -> [u8; 32] {
+    let mut hasher = Keccak256::new();
+    hasher.update(format!("\x19Ethereum Signed Message:\n{}", message.len()));
+    hasher.update(message);
+    hasher.finalize().into()
+}
+
+/// Signs a message the way a wallet's `personal_sign` does, with a secp256k1 private key in
+/// PKCS#8 form (as written to `my.key`). Returns the 65-byte r || s || v signature.
+pub fn personal_sign(private_key_bytes: &[u8], message: &[u8]) -> Result<Vec<u8>, IdpError> {
+    if crypto::private_key_algorithm(private_key_bytes)? != crypto::SECP256K1 {
+        return Err(IdpError::Key("only secp256k1 keys can sign for an address".to_string()));
+    }
+    let signing_key = crypto::secp256k1_signing_key(&*crypto::private_key_seed(private_key_bytes)?)?;
+    let (signature, recovery_id) = signing_key
+        .sign_prehash_recoverable(&personal_message_hash(message))
+        .map_err(|e| IdpError::Crypto(e.to_string()))?;
+
+    let mut bytes = signature.to_bytes().to_vec();
+    bytes.push(27 + recovery_id.to_byte());
+    Ok(bytes)
+}
+
+/// Recovers the address that made a `personal_sign` signature over a message.
+pub fn recover_address(message: &[u8], signature: &[u8]) -> Result<String, VerifyError> {
+    let [rs @ .., v] = signature else {
+        return Err(VerifyError::InvalidSignature);
+    };
+    // Wallets write v as 27 or 28; some hardware wallets as 0 or 1.
+    let recovery_id = RecoveryId::from_byte(v.checked_sub(27).unwrap_or(*v)).ok_or(VerifyError::InvalidSignature)?;
+    let signature = k256::ecdsa::Signature::from_slice(rs).map_err(|_| VerifyError::InvalidSignature)?;
+    let public_key = VerifyingKey::recover_from_prehash(&personal_message_hash(message), &signature, recovery_id)
+        .map_err(|_| VerifyError::InvalidSignature)?;
+    Ok(ethereum_address(&public_key))
+}
+
+impl PublicKey {
+    /// The Ethereum address of a secp256k1 key.
+    pub fn ethereum_address(&self) -> Result<String, IdpError> {
+        if self.algorithm != crypto::SECP256K1 {
+            return Err(IdpError::Key(format!("'{}' is a {} key, not a secp256k1 key", self.key_id, self.algorithm)));
+        }
+        let public_key = VerifyingKey::from_sec1_bytes(&self.raw_value()?)
+            .map_err(|_| IdpError::Key(format!("the value of '{}' is not a secp256k1 point", self.key_id)))?;
+        Ok(ethereum_address(&public_key))
+    }
+}
+
+impl Identity {
+    /// Records a wallet's `personal_sign` signature over `ownership_statement` (hex, as wallets
+    /// print it) as proof that `address` belongs to this identity. Replaces an earlier proof
+    /// for the same address.
+    pub fn attest_address(&mut self, address: &str, signature_hex: &str) -> Result<(), IdpError> {
+        // 1. The signature must recover to the address it is for.
+        let address = parse_address(address)?;
+        let signature_hex = signature_hex.trim();
+        let signature = HEXLOWER_PERMISSIVE
+            .decode(signature_hex.strip_prefix("0x").unwrap_or(signature_hex).as_bytes())
+            .map_err(|_| IdpError::Verify(VerifyError::MalformedEncoding("signature".to_string())))?;
+        let statement = ownership_statement(&self.identity.id, &address);
+        if recover_address(statement.as_bytes(), &signature)? != address {
+            return Err(IdpError::Verify(VerifyError::InvalidSignature));
+        }
+
+        // 2. Keep it as a proof signed by the address itself.
+        let proof_id = format!("address-{}", address);
+        self.proofs.retain(|p| p.proof_id != proof_id);
+        self.proofs.push(Proof {
+            proof_id,
+            proof_type: ADDRESS_OWNERSHIP_PROOF.to_string(),
+            claim_hash: BASE64.encode(digest::digest(&digest::SHA256, statement.as_bytes()).as_ref()),
+            signed_by: Signer {
+                idp_id: IdpId::parse(&format!("{}:{}", id::ETHEREUM_SCHEME, address))?,
+                key_id: address,
+            },
+            signature: vec![SignatureComponent {
+                algorithm: EIP191.to_string(),
+                value: BASE64.encode(&signature),
+            }],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        });
+        self.touch();
+        Ok(())
+    }
+
+    /// Attests the address of a secp256k1 key the identity holds itself, signing with it.
+    /// Returns the address.
+    pub fn attest_own_address(&mut self, private_key_bytes: &[u8]) -> Result<String, IdpError> {
+        let key = self.key_for_private_key(&SecretBytes::from(private_key_bytes))?;
+        let address = key.ethereum_address()?;
+        let signature = personal_sign(private_key_bytes, ownership_statement(&self.identity.id, &address).as_bytes())?;
+        self.attest_address(&address, &HEXLOWER.encode(&signature))?;
+        Ok(address)
+    }
+
+    /// The addresses with an ownership proof in the document, valid or not.
+    pub fn attested_addresses(&self) -> Vec<&str> {
+        self.proofs
+            .iter()
+            .filter(|p| p.proof_type == ADDRESS_OWNERSHIP_PROOF)
+            .map(|p| p.signed_by.key_id.as_str())
+            .collect()
+    }
+
+    /// Checks every address proof: each signature must recover to the address it names,
+    /// over the statement for this identity.
+    pub fn verify_address_attestations(&self) -> Result<(), VerifyError> {
+        for proof in self.proofs.iter().filter(|p| p.proof_type == ADDRESS_OWNERSHIP_PROOF) {
+            let address = &proof.signed_by.key_id;
+            let signature = proof
+                .signature
+                .iter()
+                .find(|s| s.algorithm == EIP191)
+                .ok_or_else(|| VerifyError::MissingComponent(EIP191.to_string()))?;
+            let signature = BASE64
+                .decode(signature.value.as_bytes())
+                .map_err(|_| VerifyError::MalformedEncoding("signature".to_string()))?;
+            let statement = ownership_statement(&self.identity.id, address);
+            if recover_address(statement.as_bytes(), &signature)? != *address {
+                return Err(VerifyError::InvalidSignature);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_wallet_addresses_and_signatures() {
+        // The example account of the web3.js documentation.
+        let wallet_secret = "0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+        let key_pair = crypto::import_secp256k1_private_key(wallet_secret.as_bytes()).unwrap();
+        assert_eq!(key_pair.public_key.ethereum_address().unwrap(), "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23");
+
+        let signature = personal_sign(&key_pair.private_key_bytes, b"Some data").unwrap();
+        assert_eq!(
+            HEXLOWER.encode(&signature),
+            "b91467e570a6466aa9e9876cbcd013baba02900b8979d43fe208a4a4f339f5fd6007e74cd82e037b800186422fc2da167c747ef045e5d18a5f5d4300f8e1a0291c"
+        );
+        assert_eq!(recover_address(b"Some data", &signature).unwrap(), "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23");
+
+        assert_eq!(parse_address("0x2c7536e3605d9c16a7a3d7b1898e529396a65c23").unwrap(), "0x2c7536E3605D9C16a7a3D7b1898e529396a65c23");
+        assert!(parse_address("0x2c7536e3605D9C16a7a3D7b1898e529396a65c23").is_err());
+        assert!(parse_address("2c7536e3605d9c16a7a3d7b1898e529396a65c23").is_err());
+    }
+
+    #[test]
+    fn it_attests_addresses_with_wallet_signatures() {
+        let (mut identity, private_key) = Identity::new_with_algorithm("Wallet User", "Holds coins.", crypto::SECP256K1).unwrap();
+        let address = identity.attest_own_address(&private_key).unwrap();
+        assert_eq!(identity.attested_addresses(), vec![address.as_str()]);
+        assert_eq!(identity.verify_address_attestations(), Ok(()));
+
+        // A wallet that is not the identity's own key signs the statement for its address.
+        let wallet = crypto::generate_keypair(crypto::SECP256K1).unwrap();
+        let wallet_address = wallet.public_key.ethereum_address().unwrap();
+        let statement = ownership_statement(&identity.identity.id, &wallet_address);
+        let signature = HEXLOWER.encode(&personal_sign(&wallet.private_key_bytes, statement.as_bytes()).unwrap());
+        assert!(identity.attest_address(&address, &signature).is_err());
+        identity.attest_address(&wallet_address, &format!("0x{}", signature)).unwrap();
+        assert_eq!(identity.attested_addresses().len(), 2);
+
+        // A proof moved to another identity no longer verifies.
+        let (mut other, _) = Identity::new("Someone Else", "Wants the coins.").unwrap();
+        other.proofs = identity.proofs.clone();
+        assert_eq!(other.verify_address_attestations(), Err(VerifyError::InvalidSignature));
+    }
+}
@@ -0,0 +1,533 @@
+// crates/idp-core/src/anchor.rs
+
+// Anchoring in Bitcoin with OpenTimestamps. The hash of the document, or of one proof, is sent to
+// public calendars, which aggregate it into a Bitcoin transaction; their answer is a tree of hash
+// operations from our hash to a pending attestation, upgraded later to a block the commitment is
+// in. The tree is kept in the document as a standard `.ots` file, so `ots verify` can check it too.
+
+use chrono::{DateTime, Utc};
+use data_encoding::{BASE64, HEXLOWER};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+use crate::{canonical, tsa, Identity, IdpError};
+
+/// The calendars asked when none are given.
+pub const DEFAULT_CALENDARS: [&str; 2] = ["https://a.pool.opentimestamps.org", "https://b.pool.opentimestamps.org"];
+
+/// The Esplora API blocks are looked up in when none is given.
+pub const DEFAULT_ESPLORA: &str = "https://blockstream.info/api";
+
+/// The target that anchors the whole document, rather than one proof.
+pub const DOCUMENT: &str = "document";
+
+// The extension namespace anchors are kept in.
+const NAMESPACE: &str = "anchors";
+
+// The start of every detached `.ots` file, then the format version.
+const MAGIC: &[u8] = b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+const VERSION: u8 = 1;
+
+const BITCOIN: [u8; 8] = [0x05, 0x88, 0x96, 0x0d, 0x73, 0xd7, 0x19, 0x01];
+const PENDING: [u8; 8] = [0x83, 0xdf, 0xe3, 0x0d, 0x2e, 0xf9, 0x0c, 0x8e];
+
+/// Asks a calendar, given its URL and a message it has, for an upgraded timestamp of it.
+pub type FetchUpgrade<'a> = dyn FnMut(&str, &[u8]) -> Result<Option<Timestamp>, IdpError> + 'a;
+
+/// Sends a calendar, given its URL, a commitment to timestamp.
+pub type Submit<'a> = dyn FnMut(&str, &[u8]) -> Result<Timestamp, IdpError> + 'a;
+
+/// A message, and what is attested about it.
+pub type Attested = (Vec<u8>, Attestation);
+
+/// One step from a message to the next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Op {
+    Sha256,
+    Sha1,
+    Keccak256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+    Reverse,
+    Hexlify,
+    /// An operation this version cannot compute, e.g. RIPEMD-160.
+    Unsupported(u8),
+}
+
+impl Op {
+    fn tag(&self) -> u8 {
+        match self {
+            Op::Sha1 => 0x02,
+            Op::Sha256 => 0x08,
+            Op::Keccak256 => 0x67,
+            Op::Append(_) => 0xf0,
+            Op::Prepend(_) => 0xf1,
+            Op::Reverse => 0xf2,
+            Op::Hexlify => 0xf3,
+            Op::Unsupported(tag) => *tag,
+        }
+    }
+
+    pub fn apply(&self, message: &[u8]) -> Result<Vec<u8>, IdpError> {
+        Ok(match self {
+            Op::Sha256 => digest::digest(&digest::SHA256, message).as_ref().to_vec(),
+            Op::Sha1 => digest::digest(&digest::SHA1_FOR_LEGACY_USE_ONLY, message).as_ref().to_vec(),
+            Op::Keccak256 => Keccak256::digest(message).to_vec(),
+            Op::Append(suffix) => [message, suffix].concat(),
+            Op::Prepend(prefix) => [prefix, message].concat(),
+            Op::Reverse => message.iter().rev().copied().collect(),
+            Op::Hexlify => HEXLOWER.encode(message).into_bytes(),
+            Op::Unsupported(tag) => return Err(IdpError::Anchor(format!("the timestamp uses operation 0x{:02x}, which this version cannot compute", tag))),
+        })
+    }
+}
+
+/// What a calendar or chain says about the message it is attached to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attestation {
+    /// The calendar at this URL has the message, and will have a block for it later.
+    Pending(String),
+    /// The message is the Merkle root of the Bitcoin block at this height.
+    Bitcoin(u64),
+    Unknown([u8; 8], Vec<u8>),
+}
+
+/// A tree of operations from a message, with the attestations made along the way.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Timestamp {
+    pub attestations: Vec<Attestation>,
+    pub ops: Vec<(Op, Timestamp)>,
+}
+
+impl Timestamp {
+    /// Reads a timestamp as calendars send it, with no file header.
+    pub fn parse(bytes: &[u8]) -> Result<Timestamp, IdpError> {
+        let mut reader = Reader(bytes);
+        let timestamp = reader.timestamp(0)?;
+        match reader.0.is_empty() {
+            true => Ok(timestamp),
+            false => Err(IdpError::Anchor("trailing bytes after the timestamp".to_string())),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes);
+        bytes
+    }
+
+    /// Every attestation in the tree, with the message it attests, starting from `message`.
+    pub fn attestations(&self, message: &[u8]) -> Result<Vec<Attested>, IdpError> {
+        let mut found: Vec<Attested> = self.attestations.iter().map(|attestation| (message.to_vec(), attestation.clone())).collect();
+        for (op, child) in &self.ops {
+            found.extend(child.attestations(&op.apply(message)?)?);
+        }
+        Ok(found)
+    }
+
+    /// The lowest Bitcoin block the tree reaches, if it reaches one.
+    pub fn bitcoin_height(&self) -> Option<u64> {
+        let own = self.attestations.iter().filter_map(|attestation| match attestation {
+            Attestation::Bitcoin(height) => Some(*height),
+            _ => None,
+        });
+        own.chain(self.ops.iter().filter_map(|(_, child)| child.bitcoin_height())).min()
+    }
+
+    /// Adds another tree for the same message to this one.
+    pub fn merge(&mut self, other: Timestamp) {
+        for attestation in other.attestations {
+            if !self.attestations.contains(&attestation) {
+                self.attestations.push(attestation);
+            }
+        }
+        for (op, child) in other.ops {
+            match self.ops.iter_mut().find(|(existing, _)| *existing == op) {
+                Some((_, existing)) => existing.merge(child),
+                None => self.ops.push((op, child)),
+            }
+        }
+    }
+
+    /// Asks the calendars of pending attestations for what they have since, with `fetch(calendar,
+    /// message)`; an answer replaces the pending attestation. Returns whether anything changed.
+    pub fn upgrade(&mut self, message: &[u8], fetch: &mut FetchUpgrade) -> Result<bool, IdpError> {
+        let mut changed = false;
+        let pending: Vec<String> = self
+            .attestations
+            .iter()
+            .filter_map(|attestation| match attestation {
+                Attestation::Pending(calendar) => Some(calendar.clone()),
+                _ => None,
+            })
+            .collect();
+        for calendar in pending {
+            if let Some(upgrade) = fetch(&calendar, message)? {
+                self.attestations.retain(|attestation| *attestation != Attestation::Pending(calendar.clone()));
+                self.merge(upgrade);
+                changed = true;
+            }
+        }
+        for (op, child) in &mut self.ops {
+            changed |= child.upgrade(&op.apply(message)?, fetch)?;
+        }
+        Ok(changed)
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        let count = self.attestations.len() + self.ops.len();
+        let mut written = 0;
+        let mut separate = |bytes: &mut Vec<u8>| {
+            written += 1;
+            if written < count {
+                bytes.push(0xff);
+            }
+        };
+        for attestation in &self.attestations {
+            separate(bytes);
+            bytes.push(0x00);
+            let (tag, payload) = match attestation {
+                Attestation::Pending(calendar) => (PENDING, varbytes(calendar.as_bytes())),
+                Attestation::Bitcoin(height) => (BITCOIN, varuint(*height)),
+                Attestation::Unknown(tag, payload) => (*tag, payload.clone()),
+            };
+            bytes.extend(tag);
+            bytes.extend(varbytes(&payload));
+        }
+        for (op, child) in &self.ops {
+            separate(bytes);
+            bytes.push(op.tag());
+            if let Op::Append(argument) | Op::Prepend(argument) = op {
+                bytes.extend(varbytes(argument));
+            }
+            child.write(bytes);
+        }
+    }
+}
+
+/// A detached `.ots` file: the SHA-256 hash timestamped, and its timestamp.
+pub fn detached(digest: &[u8], timestamp: &Timestamp) -> Vec<u8> {
+    [MAGIC, &[VERSION, Op::Sha256.tag()], digest, &timestamp.to_bytes()].concat()
+}
+
+/// Reads a detached `.ots` file of a SHA-256 hash.
+pub fn parse_detached(bytes: &[u8]) -> Result<(Vec<u8>, Timestamp), IdpError> {
+    let rest = bytes.strip_prefix(MAGIC).ok_or_else(|| IdpError::Anchor("not an OpenTimestamps proof".to_string()))?;
+    let [VERSION, 0x08, rest @ ..] = rest else {
+        return Err(IdpError::Anchor("only version 1 proofs of SHA-256 hashes are read".to_string()));
+    };
+    let digest = rest.get(..32).ok_or_else(|| IdpError::Anchor("the proof ends early".to_string()))?;
+    Ok((digest.to_vec(), Timestamp::parse(&rest[32..])?))
+}
+
+/// Timestamps `digest` with `submit(calendar, commitment)` at each calendar. A random nonce is
+/// hashed in first, so calendars do not learn the digest. At least one calendar must answer.
+pub fn stamp(digest: &[u8], calendars: &[String], submit: &mut Submit) -> Result<Timestamp, IdpError> {
+    let nonce: [u8; 16] = rand::random();
+    let commitment = digest::digest(&digest::SHA256, &[digest, &nonce].concat());
+    let mut answers = Timestamp::default();
+    let mut errors = Vec::new();
+    for calendar in calendars {
+        match submit(calendar, commitment.as_ref()) {
+            Ok(answer) => answers.merge(answer),
+            Err(IdpError::Anchor(e)) => errors.push(e),
+            Err(e) => errors.push(format!("{}: {}", calendar, e)),
+        }
+    }
+    if answers == Timestamp::default() {
+        return Err(IdpError::Anchor(format!("no calendar accepted the hash ({})", errors.join("; "))));
+    }
+    let hashed = Timestamp { attestations: Vec::new(), ops: vec![(Op::Sha256, answers)] };
+    Ok(Timestamp { attestations: Vec::new(), ops: vec![(Op::Append(nonce.to_vec()), hashed)] })
+}
+
+/// Sends a commitment to a calendar, over HTTP(S) with the `opentimestamps` feature.
+#[cfg(feature = "opentimestamps")]
+pub fn submit(calendar: &str, commitment: &[u8]) -> Result<Timestamp, IdpError> {
+    let response = ureq::post(&format!("{}/digest", calendar.trim_end_matches('/')))
+        .set("Accept", "application/vnd.opentimestamps.v1")
+        .send_bytes(commitment)
+        .map_err(|e| IdpError::Anchor(format!("{} did not take the hash: {}", calendar, e)))?;
+    Timestamp::parse(&read_body(response)?)
+}
+
+/// Asks a calendar whether a commitment it has is in a block yet; `None` while it is pending.
+#[cfg(feature = "opentimestamps")]
+pub fn fetch_upgrade(calendar: &str, commitment: &[u8]) -> Result<Option<Timestamp>, IdpError> {
+    match ureq::get(&format!("{}/timestamp/{}", calendar.trim_end_matches('/'), HEXLOWER.encode(commitment))).set("Accept", "application/vnd.opentimestamps.v1").call() {
+        Ok(response) => Ok(Some(Timestamp::parse(&read_body(response)?)?)),
+        Err(ureq::Error::Status(404, _)) => Ok(None),
+        Err(e) => Err(IdpError::Anchor(format!("could not ask {}: {}", calendar, e))),
+    }
+}
+
+/// The Merkle root of the Bitcoin block at `height`, in the byte order attestations use, from an
+/// Esplora API such as `DEFAULT_ESPLORA`.
+#[cfg(feature = "opentimestamps")]
+pub fn block_merkle_root(esplora: &str, height: u64) -> Result<Vec<u8>, IdpError> {
+    let esplora = esplora.trim_end_matches('/');
+    let failed = |e: ureq::Error| IdpError::Anchor(format!("could not look up block {}: {}", height, e));
+    let hash = ureq::get(&format!("{}/block-height/{}", esplora, height)).call().map_err(failed)?.into_string()?;
+    let block: serde_json::Value = ureq::get(&format!("{}/block/{}", esplora, hash.trim())).call().map_err(failed)?.into_json()?;
+    let root = HEXLOWER.decode(block["merkle_root"].as_str().unwrap_or_default().as_bytes()).map_err(|_| IdpError::Anchor(format!("block {} has no Merkle root", height)))?;
+    Ok(root.into_iter().rev().collect())
+}
+
+#[cfg(feature = "opentimestamps")]
+fn read_body(response: ureq::Response) -> Result<Vec<u8>, IdpError> {
+    let mut body = Vec::new();
+    std::io::Read::read_to_end(&mut response.into_reader(), &mut body)?;
+    Ok(body)
+}
+
+#[cfg(not(feature = "opentimestamps"))]
+pub fn submit(calendar: &str, _commitment: &[u8]) -> Result<Timestamp, IdpError> {
+    Err(offline(calendar))
+}
+
+#[cfg(not(feature = "opentimestamps"))]
+pub fn fetch_upgrade(calendar: &str, _commitment: &[u8]) -> Result<Option<Timestamp>, IdpError> {
+    Err(offline(calendar))
+}
+
+#[cfg(not(feature = "opentimestamps"))]
+pub fn block_merkle_root(esplora: &str, _height: u64) -> Result<Vec<u8>, IdpError> {
+    Err(offline(esplora))
+}
+
+#[cfg(not(feature = "opentimestamps"))]
+fn offline(url: &str) -> IdpError {
+    IdpError::Anchor(format!("this build cannot reach '{}'; rebuild with the `opentimestamps` feature", url))
+}
+
+/// A hash of the document or of a proof, as anchored.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Anchor {
+    /// `document`, or the id of the proof anchored.
+    pub target: String,
+    /// The SHA-256 hash anchored, in hex.
+    pub digest: String,
+    pub anchored_at: DateTime<Utc>,
+    /// The detached `.ots` proof, Base64.
+    pub ots: String,
+    /// The lowest block the proof reaches, once a calendar has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bitcoin_height: Option<u64>,
+}
+
+impl Anchor {
+    /// The hash anchored and its timestamp.
+    pub fn timestamp(&self) -> Result<(Vec<u8>, Timestamp), IdpError> {
+        parse_detached(&BASE64.decode(self.ots.as_bytes()).map_err(|_| IdpError::Anchor("the proof is not valid Base64".to_string()))?)
+    }
+
+    fn set_timestamp(&mut self, digest: &[u8], timestamp: &Timestamp) {
+        self.ots = BASE64.encode(&detached(digest, timestamp));
+        self.bitcoin_height = timestamp.bitcoin_height();
+    }
+}
+
+impl Identity {
+    /// The hash anchoring `target` commits to. For the document, that is its canonical JSON
+    /// without signatures, its anchors or `updated_at`, so adding an anchor does not change it.
+    pub fn anchor_digest(&self, target: &str) -> Result<Vec<u8>, IdpError> {
+        let bytes = match target {
+            DOCUMENT => {
+                let mut document = self.unsigned_value()?;
+                document["identity"].as_object_mut().map(|identity| identity.remove("updated_at"));
+                let map = document.as_object_mut().expect("documents serialize to objects");
+                if let Some(extensions) = map.get_mut("extensions").and_then(|extensions| extensions.as_object_mut()) {
+                    extensions.remove(NAMESPACE);
+                    if extensions.is_empty() {
+                        map.remove("extensions");
+                    }
+                }
+                canonical::canonicalize(&document)
+            }
+            proof_id => {
+                let proof = self.proofs.iter().find(|proof| proof.proof_id == proof_id).ok_or_else(|| IdpError::Anchor(format!("no proof '{}' in the document", proof_id)))?;
+                tsa::stamped_data(proof)?
+            }
+        };
+        Ok(digest::digest(&digest::SHA256, &bytes).as_ref().to_vec())
+    }
+
+    pub fn anchors(&self) -> Result<Vec<Anchor>, IdpError> {
+        Ok(self.extension(NAMESPACE)?.unwrap_or_default())
+    }
+
+    /// Keeps `timestamp` of `target`'s hash as it is now.
+    pub fn add_anchor(&mut self, target: &str, timestamp: &Timestamp) -> Result<Anchor, IdpError> {
+        let digest = self.anchor_digest(target)?;
+        let mut anchor = Anchor { target: target.to_string(), digest: HEXLOWER.encode(&digest), anchored_at: Utc::now(), ots: String::new(), bitcoin_height: None };
+        anchor.set_timestamp(&digest, timestamp);
+        let mut anchors = self.anchors()?;
+        anchors.push(anchor.clone());
+        self.set_extension(NAMESPACE, &anchors)?;
+        self.touch();
+        Ok(anchor)
+    }
+
+    /// Upgrades the pending anchors with `fetch`, as `Timestamp::upgrade` does. Returns how many changed.
+    pub fn upgrade_anchors(&mut self, fetch: &mut FetchUpgrade) -> Result<usize, IdpError> {
+        let mut anchors = self.anchors()?;
+        let mut changed = 0;
+        for anchor in anchors.iter_mut().filter(|anchor| anchor.bitcoin_height.is_none()) {
+            let (digest, mut timestamp) = anchor.timestamp()?;
+            if timestamp.upgrade(&digest, fetch)? {
+                anchor.set_timestamp(&digest, &timestamp);
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.set_extension(NAMESPACE, &anchors)?;
+            self.touch();
+        }
+        Ok(changed)
+    }
+
+    /// Whether `anchor` still commits to its target as it is now, and the attestations it has.
+    pub fn check_anchor(&self, anchor: &Anchor) -> Result<(bool, Vec<Attested>), IdpError> {
+        let (digest, timestamp) = anchor.timestamp()?;
+        let current = self.anchor_digest(&anchor.target).ok();
+        Ok((current.as_deref() == Some(digest.as_slice()) && HEXLOWER.encode(&digest) == anchor.digest, timestamp.attestations(&digest)?))
+    }
+}
+
+// Reads the binary encoding, which is LEB128 integers and length-prefixed bytes.
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn byte(&mut self) -> Result<u8, IdpError> {
+        let (first, rest) = self.0.split_first().ok_or_else(|| IdpError::Anchor("the timestamp ends early".to_string()))?;
+        self.0 = rest;
+        Ok(*first)
+    }
+
+    fn bytes(&mut self, count: usize) -> Result<Vec<u8>, IdpError> {
+        let bytes = self.0.get(..count).ok_or_else(|| IdpError::Anchor("the timestamp ends early".to_string()))?.to_vec();
+        self.0 = &self.0[count..];
+        Ok(bytes)
+    }
+
+    fn varuint(&mut self) -> Result<u64, IdpError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(IdpError::Anchor("an integer in the timestamp is too large".to_string()))
+    }
+
+    fn varbytes(&mut self) -> Result<Vec<u8>, IdpError> {
+        let length = self.varuint()?;
+        self.bytes(usize::try_from(length).map_err(|_| IdpError::Anchor("a length in the timestamp is too large".to_string()))?)
+    }
+
+    fn timestamp(&mut self, depth: usize) -> Result<Timestamp, IdpError> {
+        if depth > 256 {
+            return Err(IdpError::Anchor("the timestamp is nested too deeply".to_string()));
+        }
+        let mut timestamp = Timestamp::default();
+        loop {
+            let mut tag = self.byte()?;
+            let more = tag == 0xff;
+            if more {
+                tag = self.byte()?;
+            }
+            match tag {
+                0x00 => timestamp.attestations.push(self.attestation()?),
+                tag => {
+                    let op = match tag {
+                        0x02 => Op::Sha1,
+                        0x08 => Op::Sha256,
+                        0x67 => Op::Keccak256,
+                        0xf0 => Op::Append(self.varbytes()?),
+                        0xf1 => Op::Prepend(self.varbytes()?),
+                        0xf2 => Op::Reverse,
+                        0xf3 => Op::Hexlify,
+                        0x03 => Op::Unsupported(tag),
+                        tag => return Err(IdpError::Anchor(format!("unknown operation 0x{:02x} in the timestamp", tag))),
+                    };
+                    timestamp.ops.push((op, self.timestamp(depth + 1)?));
+                }
+            }
+            if !more {
+                return Ok(timestamp);
+            }
+        }
+    }
+
+    fn attestation(&mut self) -> Result<Attestation, IdpError> {
+        let tag: [u8; 8] = self.bytes(8)?.try_into().expect("eight bytes");
+        let bytes = self.varbytes()?;
+        let mut payload = Reader(&bytes);
+        Ok(match tag {
+            BITCOIN => Attestation::Bitcoin(payload.varuint()?),
+            PENDING => Attestation::Pending(String::from_utf8(payload.varbytes()?).map_err(|_| IdpError::Anchor("a calendar URL is not UTF-8".to_string()))?),
+            tag => Attestation::Unknown(tag, bytes),
+        })
+    }
+}
+
+fn varuint(mut value: u64) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        match value {
+            0 => {
+                bytes.push(byte);
+                return bytes;
+            }
+            _ => bytes.push(byte | 0x80),
+        }
+    }
+}
+
+fn varbytes(bytes: &[u8]) -> Vec<u8> {
+    [varuint(bytes.len() as u64), bytes.to_vec()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_anchors_a_document_through_a_calendar() {
+        let (mut identity, _) = Identity::new("Anchored", "Existed by then.").unwrap();
+        let digest = identity.anchor_digest(DOCUMENT).unwrap();
+
+        // A calendar first answers with a pending attestation...
+        let calendar = "https://calendar.example".to_string();
+        let mut submitted = Vec::new();
+        let timestamp = stamp(&digest, std::slice::from_ref(&calendar), &mut |calendar, commitment| {
+            submitted = commitment.to_vec();
+            Ok(Timestamp { attestations: vec![Attestation::Pending(calendar.to_string())], ops: Vec::new() })
+        })
+        .unwrap();
+        let anchor = identity.add_anchor(DOCUMENT, &timestamp).unwrap();
+        assert_eq!(parse_detached(&detached(&digest, &timestamp)).unwrap(), (digest.clone(), timestamp));
+        assert_eq!(anchor.bitcoin_height, None);
+
+        // ...and later with the path to a block's Merkle root.
+        let block = Timestamp { attestations: vec![Attestation::Bitcoin(840_000)], ops: Vec::new() };
+        let upgrade = Timestamp { attestations: Vec::new(), ops: vec![(Op::Prepend(b"left".to_vec()), Timestamp { attestations: Vec::new(), ops: vec![(Op::Sha256, block)] })] };
+        let mut fetch = |_: &str, commitment: &[u8]| Ok((commitment == submitted.as_slice()).then(|| upgrade.clone()));
+        assert_eq!(identity.upgrade_anchors(&mut fetch).unwrap(), 1);
+        let anchor = &identity.anchors().unwrap()[0];
+        assert_eq!(anchor.bitcoin_height, Some(840_000));
+
+        // The anchor holds for the document as it was, and reaches the block's root.
+        let (matches, attestations) = identity.check_anchor(anchor).unwrap();
+        let root = digest::digest(&digest::SHA256, &[b"left".as_slice(), &submitted].concat());
+        assert!(matches);
+        assert_eq!(attestations, vec![(root.as_ref().to_vec(), Attestation::Bitcoin(840_000))]);
+        identity.core.bio = "Edited since.".to_string();
+        assert!(!identity.check_anchor(anchor).unwrap().0);
+    }
+}
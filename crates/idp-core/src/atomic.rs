@@ -0,0 +1,56 @@
+// crates/idp-core/src/atomic.rs
+
+// Crash-safe file writes. Identity and key files are written to a temporary file next to the
+// target, flushed to disk and renamed over it, so a crash leaves either the old or the new file.
+
+use std::io::Write;
+use std::path::Path;
+
+use crate::IdpError;
+
+/// Replaces the file at `path` with `contents` in one step. A replaced file keeps its
+/// permissions; a new one is readable by its owner only.
+pub fn write(path: &Path, contents: &[u8]) -> Result<(), IdpError> {
+    // 1. The temporary file must be in the same directory, or the rename is a copy.
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    temp.write_all(contents)?;
+    if let Ok(metadata) = std::fs::metadata(path) {
+        temp.as_file().set_permissions(metadata.permissions())?;
+    }
+    temp.as_file().sync_all()?;
+
+    // 2. Swap it in, then make the rename itself durable.
+    temp.persist(path).map_err(|e| e.error)?;
+    #[cfg(unix)]
+    std::fs::File::open(dir)?.sync_all()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_replaces_files_in_one_step() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        write(&path, b"first").unwrap();
+        write(&path, b"second").unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"second");
+
+        // Nothing is left behind next to it.
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o640)).unwrap();
+            write(&path, b"third").unwrap();
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o640);
+        }
+    }
+}
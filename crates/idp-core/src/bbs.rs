@@ -0,0 +1,554 @@
+// crates/idp-core/src/bbs.rs
+
+// BBS signatures (draft-irtf-cfrg-bbs-signatures, BLS12-381 with SHA-256). The issuer signs a
+// list of messages at once, one per claim field and one for the holder's id; from that signature
+// the holder derives zero-knowledge proofs that reveal any subset of the fields. Every proof is
+// freshly randomized and never reveals the holder's id, so two presentations cannot be linked.
+
+use bls12_381::hash_to_curve::{ExpandMessageState, ExpandMsgXmd, HashToCurve, HashToField, InitExpandMessage};
+use bls12_381::{multi_miller_loop, G1Affine, G1Projective, G2Affine, G2Prepared, G2Projective, Gt, Scalar};
+use chrono::{DateTime, Utc};
+use data_encoding::{BASE64, HEXLOWER};
+use ring::digest;
+use ring::rand::{self, SecureRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::Sha256;
+
+use crate::credential::IssuedCredential;
+use crate::crypto::{self, VerifyError};
+use crate::signer::SigningBackend;
+use crate::{canonical, timestamp, Credential, Identity, IdpError, IdpId, Proof, PublicKey, SecretBytes, SignatureComponent, Signer};
+
+/// The `Proof.proof_type` of a credential signed with BBS, as the holder keeps it.
+pub const BBS_PROOF: &str = "BbsSignature";
+
+/// The `Proof.proof_type` of a proof derived from one, revealing only some fields.
+pub const BBS_DERIVED_PROOF: &str = "BbsDerivedProof";
+
+// Every domain separation tag starts with the ciphersuite and how messages become scalars.
+const CIPHERSUITE_ID: &[u8] = b"BBS_BLS12381G1_XMD:SHA-256_SSWU_RO_";
+const API_ID: &[u8] = b"BBS_BLS12381G1_XMD:SHA-256_SSWU_RO_H2G_HM2S_";
+
+const POINT_LEN: usize = 48;
+const SCALAR_LEN: usize = 32;
+
+/// A BBS secret key.
+pub struct SecretKey(Scalar);
+
+impl SecretKey {
+    /// Turns 32 bytes of key material into a secret key.
+    pub fn from_seed(seed: &[u8; 32]) -> Result<SecretKey, IdpError> {
+        let secret = hash_to_scalar(seed, &dst(API_ID, "KEYGEN_DST_"));
+        match secret == Scalar::zero() {
+            true => Err(IdpError::Crypto("the seed gives no usable BBS key".to_string())),
+            false => Ok(SecretKey(secret)),
+        }
+    }
+
+    /// The public key: the secret times the G2 generator, compressed.
+    pub fn public_key(&self) -> [u8; 96] {
+        G2Affine::from(G2Projective::generator() * self.0).to_compressed()
+    }
+}
+
+/// Signs `messages` together with `header`, which every proof reveals.
+pub fn sign(secret_key: &SecretKey, header: &[u8], messages: &[&[u8]]) -> Result<Vec<u8>, IdpError> {
+    let public_key = G2Projective::generator() * secret_key.0;
+    let generators = message_generators(messages.len());
+    let scalars = message_scalars(messages);
+    let domain = domain(&public_key, &generators, header);
+
+    // 1. e is derived from everything signed, so signing is deterministic.
+    let mut input = scalar_bytes(&secret_key.0).to_vec();
+    for scalar in &scalars {
+        input.extend(scalar_bytes(scalar));
+    }
+    input.extend(scalar_bytes(&domain));
+    let e = hash_to_scalar(&input, &dst(API_ID, "H2S_"));
+
+    // 2. A = B / (sk + e), with B committing to the messages.
+    let b = commitment(&generators, &domain, scalars.iter().enumerate());
+    let inverse = Option::<Scalar>::from((secret_key.0 + e).invert()).ok_or_else(|| IdpError::Crypto("BBS signing failed; try again".to_string()))?;
+    let mut signature = G1Affine::from(b * inverse).to_compressed().to_vec();
+    signature.extend(scalar_bytes(&e));
+    Ok(signature)
+}
+
+/// Checks a signature over `messages` and `header` with a compressed G2 public key.
+pub fn verify(public_key: &[u8], signature: &[u8], header: &[u8], messages: &[&[u8]]) -> Result<(), VerifyError> {
+    let public_key = parse_public_key(public_key)?;
+    let (a, e) = match signature.split_at_checked(POINT_LEN) {
+        Some((a, e)) if e.len() == SCALAR_LEN => (parse_point(a)?, parse_scalar(e)?),
+        _ => return Err(VerifyError::InvalidSignature),
+    };
+    let generators = message_generators(messages.len());
+    let domain = domain(&public_key, &generators, header);
+    let b = commitment(&generators, &domain, message_scalars(messages).iter().enumerate());
+
+    // e(A, W + e*P2) = e(B, P2), checked as one product equal to the identity.
+    let w = G2Affine::from(public_key + G2Projective::generator() * e);
+    match pairing_product(&[(G1Affine::from(a), w), (G1Affine::from(b), -G2Affine::generator())]) {
+        true => Ok(()),
+        false => Err(VerifyError::InvalidSignature),
+    }
+}
+
+/// Derives a proof of knowledge of a signature that reveals only the messages at `disclosed`
+/// (their positions in `messages`). `presentation_header` is bound into the proof, e.g. a nonce.
+pub fn derive_proof(
+    public_key: &[u8],
+    signature: &[u8],
+    header: &[u8],
+    presentation_header: &[u8],
+    messages: &[&[u8]],
+    disclosed: &[usize],
+) -> Result<Vec<u8>, IdpError> {
+    verify(public_key, signature, header, messages)?;
+    let public_key = parse_public_key(public_key)?;
+    let a = parse_point(&signature[..POINT_LEN])?;
+    let e = parse_scalar(&signature[POINT_LEN..])?;
+    let mut disclosed = disclosed.to_vec();
+    disclosed.sort();
+    disclosed.dedup();
+    if disclosed.last().is_some_and(|last| *last >= messages.len()) {
+        return Err(IdpError::Crypto("a disclosed message is out of range".to_string()));
+    }
+    let generators = message_generators(messages.len());
+    let scalars = message_scalars(messages);
+    let domain = domain(&public_key, &generators, header);
+    let undisclosed: Vec<usize> = (0..messages.len()).filter(|i| !disclosed.contains(i)).collect();
+
+    // 1. Randomize the signature, and commit to random blinds for everything kept secret.
+    let [r1, r2, e_blind, r1_blind, r3_blind] = [(); 5].map(|_| random_scalar());
+    let (r1, r2, e_blind, r1_blind, r3_blind) = (r1?, r2?, e_blind?, r1_blind?, r3_blind?);
+    let message_blinds = undisclosed.iter().map(|_| random_scalar()).collect::<Result<Vec<_>, _>>()?;
+    let b = commitment(&generators, &domain, scalars.iter().enumerate());
+    let d = b * r2;
+    let a_bar = a * (r1 * r2);
+    let b_bar = d * r1 - a_bar * e;
+    let t1 = a_bar * e_blind + d * r1_blind;
+    let t2 = undisclosed.iter().zip(&message_blinds).fold(d * r3_blind, |t2, (i, blind)| t2 + generators.h[*i] * blind);
+
+    // 2. The challenge binds the commitments to what is revealed.
+    let revealed: Vec<(usize, Scalar)> = disclosed.iter().map(|i| (*i, scalars[*i])).collect();
+    let challenge = challenge(&[a_bar, b_bar, d, t1, t2], &revealed, &domain, presentation_header);
+
+    // 3. Answer it for every secret: e, r1, 1/r2 and the undisclosed messages.
+    let r3 = Option::<Scalar>::from(r2.invert()).ok_or_else(|| IdpError::Crypto("BBS proof failed; try again".to_string()))?;
+    let mut proof = Vec::new();
+    for point in [a_bar, b_bar, d] {
+        proof.extend(G1Affine::from(point).to_compressed());
+    }
+    for scalar in [e_blind + e * challenge, r1_blind - r1 * challenge, r3_blind - r3 * challenge] {
+        proof.extend(scalar_bytes(&scalar));
+    }
+    for (i, blind) in undisclosed.iter().zip(&message_blinds) {
+        proof.extend(scalar_bytes(&(blind + scalars[*i] * challenge)));
+    }
+    proof.extend(scalar_bytes(&challenge));
+    Ok(proof)
+}
+
+/// Checks a proof from `derive_proof` against the messages it reveals, each with its position
+/// among the `message_count` messages signed.
+pub fn verify_proof(
+    public_key: &[u8],
+    proof: &[u8],
+    header: &[u8],
+    presentation_header: &[u8],
+    message_count: usize,
+    disclosed: &[(usize, &[u8])],
+) -> Result<(), VerifyError> {
+    // 1. The proof holds three points, then e, r1 and r3, one scalar per hidden message, and the challenge.
+    let public_key = parse_public_key(public_key)?;
+    let mut disclosed = disclosed.to_vec();
+    disclosed.sort();
+    if disclosed.windows(2).any(|pair| pair[0].0 == pair[1].0) || disclosed.last().is_some_and(|(i, _)| *i >= message_count) {
+        return Err(VerifyError::InvalidSignature);
+    }
+    let undisclosed: Vec<usize> = (0..message_count).filter(|i| !disclosed.iter().any(|(j, _)| j == i)).collect();
+    if proof.len() != 3 * POINT_LEN + (4 + undisclosed.len()) * SCALAR_LEN {
+        return Err(VerifyError::InvalidSignature);
+    }
+    let (points, scalars) = proof.split_at(3 * POINT_LEN);
+    let [a_bar, b_bar, d] = [0, 1, 2].map(|i| parse_point(&points[i * POINT_LEN..(i + 1) * POINT_LEN]));
+    let (a_bar, b_bar, d) = (a_bar?, b_bar?, d?);
+    let scalars = scalars.chunks(SCALAR_LEN).map(parse_scalar).collect::<Result<Vec<_>, _>>()?;
+    let (e_hat, r1_hat, r3_hat, challenge) = (scalars[0], scalars[1], scalars[2], scalars[scalars.len() - 1]);
+    let message_hats = &scalars[3..scalars.len() - 1];
+    if bool::from(a_bar.is_identity()) {
+        return Err(VerifyError::InvalidSignature);
+    }
+
+    // 2. Rebuild the commitments from the answers, and with them the challenge.
+    let generators = message_generators(message_count);
+    let domain = domain(&public_key, &generators, header);
+    let revealed: Vec<(usize, Scalar)> = disclosed.iter().map(|(i, message)| (*i, message_scalar(message))).collect();
+    let t1 = b_bar * challenge + a_bar * e_hat + d * r1_hat;
+    let b_revealed = commitment(&generators, &domain, revealed.iter().map(|(i, scalar)| (*i, scalar)));
+    let t2 = undisclosed.iter().zip(message_hats).fold(b_revealed * challenge + d * r3_hat, |t2, (i, hat)| t2 + generators.h[*i] * hat);
+    if challenge != self::challenge(&[a_bar, b_bar, d, t1, t2], &revealed, &domain, presentation_header) {
+        return Err(VerifyError::InvalidSignature);
+    }
+
+    // 3. The randomized signature must still be one made with the issuer's key: e(Ā, W) = e(B̄, P2).
+    match pairing_product(&[(G1Affine::from(a_bar), G2Affine::from(public_key)), (G1Affine::from(b_bar), -G2Affine::generator())]) {
+        true => Ok(()),
+        false => Err(VerifyError::InvalidSignature),
+    }
+}
+
+/// A proof derived from a BBS credential, as handed to a verifier: what every proof reveals,
+/// the fields chosen, and the proof itself in a `BbsDerivedProof` proof entry.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DerivedCredential {
+    pub issued_by: String,
+
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub issued_at: DateTime<Utc>,
+
+    #[serde(default, deserialize_with = "timestamp::deserialize_option", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    /// Every field of the credential in order, `None` where it is withheld.
+    pub fields: Vec<Option<DisclosedField>>,
+
+    pub proof: Proof,
+}
+
+/// A field a derived proof reveals.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DisclosedField {
+    pub name: String,
+    pub value: Value,
+}
+
+impl DerivedCredential {
+    /// The fields revealed, by name.
+    pub fn claims(&self) -> Map<String, Value> {
+        self.fields.iter().flatten().map(|field| (field.name.clone(), field.value.clone())).collect()
+    }
+
+    /// Checks the proof with the issuer's BBS key, and that the credential has not expired.
+    pub fn verify(&self, issuer_key: &PublicKey) -> Result<(), IdpError> {
+        // 1. The proof must be one of these, by the key given.
+        let header = header(&self.issued_by, &self.issued_at, &self.expires_at);
+        if self.proof.proof_type != BBS_DERIVED_PROOF || self.proof.claim_hash != canonical::hash(&header) {
+            return Err(IdpError::Credential("the proof is not a BBS proof of this credential".to_string()));
+        }
+        if self.proof.signed_by.key_id != issuer_key.key_id || issuer_key.algorithm != crypto::BBS_BLS12_381 {
+            return Err(VerifyError::UnknownKey(self.proof.signed_by.key_id.clone()).into());
+        }
+        let proof = self.proof.signature.first().ok_or(VerifyError::InvalidSignature)?;
+        let proof = BASE64.decode(proof.value.as_bytes()).map_err(|_| VerifyError::InvalidSignature)?;
+
+        // 2. The holder's id is message 0 and never revealed; the fields follow it.
+        let messages: Vec<(usize, Vec<u8>)> = self
+            .fields
+            .iter()
+            .enumerate()
+            .filter_map(|(i, field)| field.as_ref().map(|field| (i + 1, field_message(&field.name, &field.value))))
+            .collect();
+        let disclosed: Vec<(usize, &[u8])> = messages.iter().map(|(i, message)| (*i, message.as_slice())).collect();
+        verify_proof(&issuer_key.raw_value()?, &proof, &header, &[], self.fields.len() + 1, &disclosed)?;
+        if let Some(expires_at) = self.expires_at.filter(|expires_at| *expires_at <= Utc::now()) {
+            return Err(IdpError::Credential(format!("the credential expired at {}", expires_at.to_rfc3339())));
+        }
+        Ok(())
+    }
+}
+
+/// The statement a BBS credential's proof hashes: the header and every message, as canonical JSON.
+/// Fails if the credential's claim is not a JSON object of fields.
+pub fn credential_statement(subject: &IdpId, credential: &Credential) -> Result<Vec<u8>, IdpError> {
+    let (header, messages) = signed_parts(subject, credential)?;
+    let text = |bytes: Vec<u8>| String::from_utf8(bytes).expect("canonical JSON is UTF-8");
+    Ok(canonical::canonicalize(&json!([text(header), messages.into_iter().map(text).collect::<Vec<_>>()])))
+}
+
+/// Checks the issuer's BBS signature on a credential held by `subject`.
+pub fn verify_credential_signature(subject: &IdpId, credential: &Credential, proof: &Proof, issuer_key: &PublicKey) -> Result<(), VerifyError> {
+    let (header, messages) = signed_parts(subject, credential).map_err(|_| VerifyError::InvalidSignature)?;
+    let signature = proof.signature.first().ok_or(VerifyError::InvalidSignature)?;
+    if issuer_key.algorithm != crypto::BBS_BLS12_381 || signature.algorithm != crypto::BBS_BLS12_381 {
+        return Err(VerifyError::AlgorithmMismatch { key: issuer_key.algorithm.clone(), signature: signature.algorithm.clone() });
+    }
+    let signature = BASE64.decode(signature.value.as_bytes()).map_err(|_| VerifyError::InvalidSignature)?;
+    let public_key = issuer_key.raw_value().map_err(|_| VerifyError::InvalidSignature)?;
+    verify(&public_key, &signature, &header, &messages.iter().map(Vec::as_slice).collect::<Vec<_>>())
+}
+
+impl Identity {
+    /// Issues a credential whose `claims` fields the subject can later prove one by one, signed
+    /// with the BBS key of `signer`'s root key (see `add_bbs_key`). The root key must be in software,
+    /// since the BBS key is derived from it.
+    pub fn issue_bbs_credential(
+        &self,
+        subject: &Identity,
+        claims: &Map<String, Value>,
+        expires_at: Option<DateTime<Utc>>,
+        signer: &dyn SigningBackend,
+    ) -> Result<IssuedCredential, IdpError> {
+        let root = signer.software_key().ok_or_else(|| IdpError::Key("BBS keys are derived from the root key, which must be a software key".to_string()))?;
+        let root_key = self.key_for_private_key(&SecretBytes::from(root))?;
+        let key = self.check_key_active(&format!("{}/bbs", root_key.key_id)).map_err(|_| {
+            IdpError::Key(format!("'{}' has no BBS key yet; add one to issue BBS credentials", root_key.key_id))
+        })?;
+        let issued_at = Utc::now();
+        if expires_at.is_some_and(|expires_at| expires_at <= issued_at) {
+            return Err(IdpError::Credential("the credential would expire before it is issued".to_string()));
+        }
+
+        // 1. The claim is the fields as canonical JSON; the holder's id and each field are signed apart.
+        let mut credential = Credential {
+            claim: String::from_utf8(canonical::canonicalize(&Value::Object(claims.clone()))).expect("canonical JSON is UTF-8"),
+            issued_by: self.identity.id.to_string(),
+            issued_at,
+            expires_at,
+            proof: String::new(),
+            provenance: None,
+            status: None,
+            unknown_fields: Default::default(),
+        };
+        let (header, messages) = signed_parts(&subject.identity.id, &credential)?;
+        let signature = sign(&crypto::derive_bbs_secret_key(root)?, &header, &messages.iter().map(Vec::as_slice).collect::<Vec<_>>())?;
+
+        // 2. The proof records the statement's hash and the BBS signature.
+        let statement = credential_statement(&subject.identity.id, &credential)?;
+        let proof_id = format!("bbs-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, &statement).as_ref()[..8]));
+        credential.proof = proof_id.clone();
+        let proof = Proof {
+            proof_id,
+            proof_type: BBS_PROOF.to_string(),
+            claim_hash: canonical::multihash(&statement),
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: key.key_id.clone() },
+            signature: vec![SignatureComponent { algorithm: crypto::BBS_BLS12_381.to_string(), value: BASE64.encode(&signature) }],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        };
+        Ok(IssuedCredential { credential, proof })
+    }
+
+    /// Derives a proof from a BBS credential held by this identity that reveals only the fields
+    /// in `names`. Proofs need the issuer's BBS key, and each one is unlinkable to the others.
+    pub fn derive_credential(&self, credential: &Credential, names: &[&str], issuer_key: &PublicKey) -> Result<DerivedCredential, IdpError> {
+        let proof = self
+            .credential_proof(credential)
+            .filter(|proof| proof.proof_type == BBS_PROOF)
+            .ok_or_else(|| IdpError::Credential(format!("'{}' is not a BBS credential", credential.proof)))?;
+        let fields = claim_fields(credential)?;
+        if let Some(name) = names.iter().find(|name| !fields.contains_key(**name)) {
+            return Err(IdpError::Credential(format!("the credential has no field '{}' to disclose", name)));
+        }
+
+        let (header, messages) = signed_parts(&self.identity.id, credential)?;
+        let signature = BASE64.decode(proof.signature.first().ok_or(VerifyError::InvalidSignature)?.value.as_bytes()).map_err(|_| VerifyError::InvalidSignature)?;
+        let disclosed: Vec<usize> = fields.keys().enumerate().filter(|(_, name)| names.contains(&name.as_str())).map(|(i, _)| i + 1).collect();
+        let derived = derive_proof(&issuer_key.raw_value()?, &signature, &header, &[], &messages.iter().map(Vec::as_slice).collect::<Vec<_>>(), &disclosed)?;
+
+        Ok(DerivedCredential {
+            issued_by: credential.issued_by.clone(),
+            issued_at: credential.issued_at,
+            expires_at: credential.expires_at,
+            fields: fields
+                .iter()
+                .map(|(name, value)| names.contains(&name.as_str()).then(|| DisclosedField { name: name.clone(), value: value.clone() }))
+                .collect(),
+            proof: Proof {
+                proof_id: format!("bbs-derived-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, &derived).as_ref()[..8])),
+                proof_type: BBS_DERIVED_PROOF.to_string(),
+                claim_hash: canonical::hash(&header),
+                signed_by: proof.signed_by.clone(),
+                signature: vec![SignatureComponent { algorithm: crypto::BBS_BLS12_381.to_string(), value: BASE64.encode(&derived) }],
+                domain: None,
+                challenge: None,
+                unknown_fields: Default::default(),
+            },
+        })
+    }
+}
+
+// What every proof of a credential reveals: who issued it, and when it is valid.
+fn header(issued_by: &str, issued_at: &DateTime<Utc>, expires_at: &Option<DateTime<Utc>>) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-bbs-credential",
+        "issuer": issued_by,
+        "issued_at": issued_at.to_rfc3339(),
+        "expires_at": expires_at.map(|expires_at| expires_at.to_rfc3339()),
+    }))
+}
+
+// The header and the messages signed for a credential: the holder's id, then each field by name.
+fn signed_parts(subject: &IdpId, credential: &Credential) -> Result<(Vec<u8>, Vec<Vec<u8>>), IdpError> {
+    let mut messages = vec![canonical::canonicalize(&json!(subject))];
+    messages.extend(claim_fields(credential)?.iter().map(|(name, value)| field_message(name, value)));
+    Ok((header(&credential.issued_by, &credential.issued_at, &credential.expires_at), messages))
+}
+
+fn claim_fields(credential: &Credential) -> Result<Map<String, Value>, IdpError> {
+    match serde_json::from_str(&credential.claim) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        _ => Err(IdpError::Credential(format!("the claim of '{}' is not a set of fields", credential.proof))),
+    }
+}
+
+fn field_message(name: &str, value: &Value) -> Vec<u8> {
+    canonical::canonicalize(&json!([name, value]))
+}
+
+// The generators messages are committed with: Q1, then one H per message.
+struct Generators {
+    p1: G1Projective,
+    q1: G1Projective,
+    h: Vec<G1Projective>,
+}
+
+fn message_generators(count: usize) -> Generators {
+    let mut message_generators = create_generators(&[API_ID, b"MESSAGE_GENERATOR_SEED"].concat(), count + 1);
+    let p1 = create_generators(&[CIPHERSUITE_ID, b"H2G_HM2S_BP_MESSAGE_GENERATOR_SEED"].concat(), 1)[0];
+    let q1 = message_generators.remove(0);
+    Generators { p1, q1, h: message_generators }
+}
+
+// Points nobody knows a discrete log of, each hashed to the curve from a chained seed.
+fn create_generators(seed: &[u8], count: usize) -> Vec<G1Projective> {
+    let seed_dst = dst(API_ID, "SIG_GENERATOR_SEED_");
+    let generator_dst = dst(API_ID, "SIG_GENERATOR_DST_");
+    let mut v = expand_message(seed, &seed_dst);
+    (1..=count as u64)
+        .map(|i| {
+            v = expand_message(&[v.as_slice(), &i.to_be_bytes()].concat(), &seed_dst);
+            <G1Projective as HashToCurve<ExpandMsgXmd<Sha256>>>::hash_to_curve(&v, &generator_dst)
+        })
+        .collect()
+}
+
+// B = P1 + Q1 * domain + the sum of H_i * m_i over the messages given.
+fn commitment<'a>(generators: &Generators, domain: &Scalar, messages: impl Iterator<Item = (usize, &'a Scalar)>) -> G1Projective {
+    messages.fold(generators.p1 + generators.q1 * domain, |b, (i, scalar)| b + generators.h[i] * scalar)
+}
+
+// Binds signatures to the key, the generators and the header.
+fn domain(public_key: &G2Projective, generators: &Generators, header: &[u8]) -> Scalar {
+    let mut input = G2Affine::from(public_key).to_compressed().to_vec();
+    input.extend((generators.h.len() as u64).to_be_bytes());
+    for point in std::iter::once(&generators.q1).chain(&generators.h) {
+        input.extend(G1Affine::from(point).to_compressed());
+    }
+    for bytes in [API_ID, header] {
+        input.extend((bytes.len() as u64).to_be_bytes());
+        input.extend(bytes);
+    }
+    hash_to_scalar(&input, &dst(API_ID, "H2S_"))
+}
+
+fn challenge(points: &[G1Projective; 5], revealed: &[(usize, Scalar)], domain: &Scalar, presentation_header: &[u8]) -> Scalar {
+    let mut input = (revealed.len() as u64).to_be_bytes().to_vec();
+    for (i, scalar) in revealed {
+        input.extend((*i as u64).to_be_bytes());
+        input.extend(scalar_bytes(scalar));
+    }
+    for point in points {
+        input.extend(G1Affine::from(point).to_compressed());
+    }
+    input.extend(scalar_bytes(domain));
+    input.extend((presentation_header.len() as u64).to_be_bytes());
+    input.extend(presentation_header);
+    hash_to_scalar(&input, &dst(API_ID, "H2S_"))
+}
+
+fn message_scalars(messages: &[&[u8]]) -> Vec<Scalar> {
+    messages.iter().map(|message| message_scalar(message)).collect()
+}
+
+fn message_scalar(message: &[u8]) -> Scalar {
+    hash_to_scalar(message, &dst(API_ID, "MAP_MSG_TO_SCALAR_AS_HASH_"))
+}
+
+fn dst(prefix: &[u8], suffix: &str) -> Vec<u8> {
+    [prefix, suffix.as_bytes()].concat()
+}
+
+fn expand_message(message: &[u8], dst: &[u8]) -> Vec<u8> {
+    <ExpandMsgXmd<Sha256> as InitExpandMessage>::init_expand(message, dst, POINT_LEN).into_vec()
+}
+
+fn hash_to_scalar(message: &[u8], dst: &[u8]) -> Scalar {
+    let mut output = [Scalar::zero()];
+    Scalar::hash_to_field::<ExpandMsgXmd<Sha256>>(message, dst, &mut output);
+    output[0]
+}
+
+fn random_scalar() -> Result<Scalar, IdpError> {
+    let mut bytes = [0u8; 64];
+    rand::SystemRandom::new().fill(&mut bytes).map_err(|e| IdpError::Crypto(e.to_string()))?;
+    Ok(Scalar::from_bytes_wide(&bytes))
+}
+
+fn pairing_product(terms: &[(G1Affine, G2Affine)]) -> bool {
+    let prepared: Vec<(G1Affine, G2Prepared)> = terms.iter().map(|(p, q)| (*p, G2Prepared::from(*q))).collect();
+    let terms: Vec<(&G1Affine, &G2Prepared)> = prepared.iter().map(|(p, q)| (p, q)).collect();
+    multi_miller_loop(&terms).final_exponentiation() == Gt::identity()
+}
+
+// Scalars are written big-endian, as the draft does; the library keeps them little-endian.
+fn scalar_bytes(scalar: &Scalar) -> [u8; SCALAR_LEN] {
+    let mut bytes = scalar.to_bytes();
+    bytes.reverse();
+    bytes
+}
+
+fn parse_scalar(bytes: &[u8]) -> Result<Scalar, VerifyError> {
+    let mut bytes: [u8; SCALAR_LEN] = bytes.try_into().map_err(|_| VerifyError::InvalidSignature)?;
+    bytes.reverse();
+    Option::from(Scalar::from_bytes(&bytes)).ok_or(VerifyError::InvalidSignature)
+}
+
+fn parse_point(bytes: &[u8]) -> Result<G1Projective, VerifyError> {
+    let bytes: [u8; POINT_LEN] = bytes.try_into().map_err(|_| VerifyError::InvalidSignature)?;
+    Option::<G1Affine>::from(G1Affine::from_compressed(&bytes)).map(G1Projective::from).ok_or(VerifyError::InvalidSignature)
+}
+
+fn parse_public_key(bytes: &[u8]) -> Result<G2Projective, VerifyError> {
+    let bytes: [u8; 96] = bytes.try_into().map_err(|_| VerifyError::InvalidSignature)?;
+    match Option::<G2Affine>::from(G2Affine::from_compressed(&bytes)) {
+        Some(point) if !bool::from(point.is_identity()) => Ok(G2Projective::from(point)),
+        _ => Err(VerifyError::InvalidSignature),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_proves_chosen_fields_without_linking_proofs() {
+        let (mut issuer, issuer_key) = Identity::new("Registry", "Knows birthdates.").unwrap();
+        let (mut holder, _) = Identity::new("Holder", "Is over 18.").unwrap();
+        issuer.add_bbs_key(&issuer_key).unwrap();
+        let bbs_key = issuer.find_key("root-key-01/bbs").unwrap().clone();
+        let claims = json!({ "age_over_18": true, "birthdate": "2000-01-01", "name": "Holder" }).as_object().unwrap().clone();
+        let issued = issuer.issue_bbs_credential(&holder, &claims, None, &issuer_key).unwrap();
+        holder.add_credential(issued.clone()).unwrap();
+        assert!(holder.verify_credential(&holder.credentials[0], &issued.proof, &issuer).is_valid());
+
+        // Proofs reveal only the chosen fields, and two proofs of the same fields share nothing.
+        let derived = holder.derive_credential(&holder.credentials[0], &["age_over_18"], &bbs_key).unwrap();
+        derived.verify(&bbs_key).unwrap();
+        assert_eq!(Value::Object(derived.claims()), json!({ "age_over_18": true }));
+        assert_eq!(derived.fields.iter().filter(|field| field.is_none()).count(), 2);
+        let again = holder.derive_credential(&holder.credentials[0], &["age_over_18"], &bbs_key).unwrap();
+        assert_ne!(derived.proof.signature, again.proof.signature);
+
+        // A changed field, or a field revealed that was not proven, fails.
+        let mut forged = derived.clone();
+        forged.fields[0] = Some(DisclosedField { name: "age_over_18".to_string(), value: json!(false) });
+        assert!(forged.verify(&bbs_key).is_err());
+        let mut overshared = derived.clone();
+        overshared.fields[1] = Some(DisclosedField { name: "birthdate".to_string(), value: json!("2000-01-01") });
+        assert!(overshared.verify(&bbs_key).is_err());
+        assert!(holder.derive_credential(&holder.credentials[0], &["height"], &bbs_key).is_err());
+    }
+}
@@ -0,0 +1,184 @@
+// crates/idp-core/src/builder.rs
+
+// Building identities in code, for tests and issuers. Every block starts from the same defaults
+// `Identity::new` uses, and `build` checks the result against the schema before handing it out.
+
+use chrono::{DateTime, Utc};
+
+use crate::{crypto, Consent, Contract, Credential, Identity, IdpError, Proof, PublicKey, Reputation, SecretBytes};
+
+// Where the root key comes from.
+enum RootKey {
+    Generate(String),
+    Import(SecretBytes, String),
+    Public(PublicKey),
+}
+
+/// A fluent way to make an `Identity`; start with `Identity::builder()`.
+pub struct IdentityBuilder {
+    name: String,
+    bio: String,
+    root_key: RootKey,
+    created_at: Option<DateTime<Utc>>,
+    credentials: Vec<Credential>,
+    proofs: Vec<Proof>,
+    contracts: Vec<Contract>,
+    reputation: Vec<Reputation>,
+    consent: Vec<Consent>,
+}
+
+impl Default for IdentityBuilder {
+    fn default() -> Self {
+        IdentityBuilder {
+            name: String::new(),
+            bio: String::new(),
+            root_key: RootKey::Generate(crypto::ED25519.to_string()),
+            created_at: None,
+            credentials: vec![],
+            proofs: vec![],
+            contracts: vec![],
+            reputation: vec![],
+            consent: vec![],
+        }
+    }
+}
+
+impl IdentityBuilder {
+    /// The display name; required.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn bio(mut self, bio: &str) -> Self {
+        self.bio = bio.to_string();
+        self
+    }
+
+    /// Generates the root key with this algorithm instead of Ed25519.
+    pub fn algorithm(mut self, algorithm: &str) -> Self {
+        self.root_key = RootKey::Generate(algorithm.to_string());
+        self
+    }
+
+    /// Uses an existing private key as the root key, in any form `crypto::import_private_key` reads.
+    pub fn private_key(mut self, key_material: &[u8], algorithm: &str) -> Self {
+        self.root_key = RootKey::Import(SecretBytes::from(key_material), algorithm.to_string());
+        self
+    }
+
+    /// Uses a root key whose private half lives elsewhere; `build` then returns no private key.
+    pub fn public_key(mut self, public_key: PublicKey) -> Self {
+        self.root_key = RootKey::Public(public_key);
+        self
+    }
+
+    /// Backdates the document, e.g. for fixtures; defaults to now.
+    pub fn created_at(mut self, created_at: DateTime<Utc>) -> Self {
+        self.created_at = Some(created_at);
+        self
+    }
+
+    pub fn credential(mut self, credential: Credential) -> Self {
+        self.credentials.push(credential);
+        self
+    }
+
+    pub fn proof(mut self, proof: Proof) -> Self {
+        self.proofs.push(proof);
+        self
+    }
+
+    pub fn contract(mut self, contract: Contract) -> Self {
+        self.contracts.push(contract);
+        self
+    }
+
+    pub fn reputation(mut self, reputation: Reputation) -> Self {
+        self.reputation.push(reputation);
+        self
+    }
+
+    pub fn consent(mut self, consent: Consent) -> Self {
+        self.consent.push(consent);
+        self
+    }
+
+    /// Makes the identity and checks it against the schema. Returns the private root key too,
+    /// unless the builder was given only a public key.
+    pub fn build(self) -> Result<(Identity, Option<SecretBytes>), IdpError> {
+        // 1. Set up the root key the same way `Identity::new` and friends do.
+        let (mut identity, private_key) = match self.root_key {
+            RootKey::Generate(algorithm) => Identity::new_with_algorithm(&self.name, &self.bio, &algorithm).map(|(i, k)| (i, Some(k)))?,
+            RootKey::Import(material, algorithm) => {
+                Identity::from_existing_key_with_algorithm(&self.name, &self.bio, &material, &algorithm).map(|(i, k)| (i, Some(k)))?
+            }
+            RootKey::Public(public_key) => (Identity::from_public_key(&self.name, &self.bio, public_key), None),
+        };
+
+        // 2. Fill in the remaining blocks.
+        if let Some(created_at) = self.created_at {
+            identity.identity.created_at = created_at;
+            identity.identity.updated_at = created_at;
+        }
+        identity.credentials = self.credentials;
+        identity.proofs = self.proofs;
+        identity.contracts = self.contracts;
+        identity.reputation = self.reputation;
+        identity.consent = self.consent;
+
+        // 3. Hand out only documents that would pass `idp validate`.
+        identity.validate_schema()?;
+        Ok((identity, private_key))
+    }
+}
+
+impl Identity {
+    /// Starts building an identity with defaults for every block.
+    pub fn builder() -> IdentityBuilder {
+        IdentityBuilder::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Consequence, ContractStatus};
+
+    #[test]
+    fn it_builds_valid_identities() {
+        let contract = Contract {
+            contract_id: "c-1".to_string(),
+            status: ContractStatus::Draft,
+            parties: vec![],
+            terms: "Deliver on time.".to_string(),
+            obligations: vec![],
+            consequence: Consequence {
+                on_success: "pay".to_string(),
+                on_failure: "refund".to_string(),
+                unknown_fields: Default::default(),
+            },
+            deadline: None,
+            review_at: None,
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+        let created_at = "2024-07-06T10:00:00Z".parse().unwrap();
+        let (identity, private_key) = Identity::builder().name("Built User").bio("Made in code.").created_at(created_at).contract(contract).build().unwrap();
+        assert_eq!((identity.core.name.as_str(), identity.identity.created_at), ("Built User", created_at));
+        assert_eq!(identity.contracts.len(), 1);
+        assert!(private_key.is_some());
+
+        // An injected key becomes the root key, and its id follows from it.
+        let existing = crypto::generate_ed25519_keypair().unwrap();
+        let seed = crypto::ed25519_seed(&existing.private_key_bytes).unwrap();
+        let (imported, _) = Identity::builder().name("Imported").private_key(seed.as_slice(), crypto::ED25519).build().unwrap();
+        assert_eq!(imported.system.public_keys[0].value, existing.public_key.value);
+        let (public_only, private_key) = Identity::builder().name("Token").public_key(existing.public_key).build().unwrap();
+        assert_eq!((public_only.identity.id, private_key.is_none()), (imported.identity.id, true));
+
+        // Documents that break the schema are not built.
+        assert!(matches!(Identity::builder().bio("No name.").build(), Err(IdpError::SchemaViolations(_))));
+    }
+}
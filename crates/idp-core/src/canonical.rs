@@ -0,0 +1,187 @@
+// crates/idp-core/src/canonical.rs
+
+// The JSON Canonicalization Scheme (JCS, RFC 8785). YAML text changes with every editor's
+// whitespace and key order, so everything that is hashed or signed is first written as
+// canonical JSON: sorted keys, no whitespace, one spelling per string and number.
+
+use data_encoding::BASE64;
+use ring::digest;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{Identity, IdpError};
+
+/// The canonical JSON bytes of any serializable value: a whole document, one of its blocks,
+/// or a statement.
+pub fn to_canonical_json<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, IdpError> {
+    Ok(canonicalize(&serde_json::to_value(value)?))
+}
+
+/// The canonical JSON bytes of a JSON value.
+pub fn canonicalize(value: &Value) -> Vec<u8> {
+    let mut out = String::new();
+    write_value(value, &mut out);
+    out.into_bytes()
+}
+
+/// The Base64 SHA-256 hash of a value's canonical JSON, as recorded in `Proof.claim_hash`.
+pub fn claim_hash<T: Serialize + ?Sized>(value: &T) -> Result<String, IdpError> {
+    Ok(hash(&to_canonical_json(value)?))
+}
+
+/// The Base64 SHA-256 hash of bytes that are already canonical.
+pub fn hash(canonical: &[u8]) -> String {
+    BASE64.encode(digest::digest(&digest::SHA256, canonical).as_ref())
+}
+
+// The multihash code of SHA-256, followed by its digest length.
+const SHA2_256: [u8; 2] = [0x12, 0x20];
+
+/// The SHA-256 multihash of bytes that are already canonical, as multibase base58btc (`zQm...`).
+/// Credential claim hashes take this form: it names its hash function, so others can follow.
+pub fn multihash(canonical: &[u8]) -> String {
+    let mut bytes = SHA2_256.to_vec();
+    bytes.extend(digest::digest(&digest::SHA256, canonical).as_ref());
+    format!("z{}", bs58::encode(bytes).into_string())
+}
+
+fn write_value(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&number_to_string(n.as_f64().expect("JSON numbers are finite"))),
+        // serde_json already escapes exactly as JCS asks: `"`, `\` and control characters
+        // only, with the short forms where they exist and lowercase `\u00xx` otherwise.
+        Value::String(s) => out.push_str(&serde_json::to_string(s).expect("strings always serialize")),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_value(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            // Keys are ordered by their UTF-16 code units, not by their UTF-8 bytes.
+            let mut entries: Vec<(&String, &Value)> = map.iter().collect();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            out.push('{');
+            for (i, (key, item)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&serde_json::to_string(key).expect("strings always serialize"));
+                out.push(':');
+                write_value(item, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+// ECMAScript's Number.prototype.toString, which JCS adopts: the shortest digits that
+// round-trip, written out in full between 1e-7 and 1e21 and in exponent form outside.
+fn number_to_string(number: f64) -> String {
+    if number == 0.0 {
+        return "0".to_string();
+    }
+
+    // 1. Rust's `{:e}` gives the shortest round-tripping digits `d.ddd` and the exponent.
+    let scientific = format!("{:e}", number.abs());
+    let (mantissa, exponent) = scientific.split_once('e').expect("`{:e}` always has an exponent");
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().expect("the exponent is an integer") + 1;
+
+    // 2. Place the decimal point the way ECMAScript does.
+    let magnitude = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let sign = if n > 0 { "+" } else { "-" };
+        match digits.split_at(1) {
+            (first, "") => format!("{}e{}{}", first, sign, (n - 1).abs()),
+            (first, rest) => format!("{}.{}e{}{}", first, rest, sign, (n - 1).abs()),
+        }
+    };
+    match number < 0.0 {
+        true => format!("-{}", magnitude),
+        false => magnitude,
+    }
+}
+
+impl Identity {
+    /// The canonical JSON bytes of the whole document.
+    pub fn to_canonical_json(&self) -> Result<Vec<u8>, IdpError> {
+        to_canonical_json(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_matches_the_rfc_8785_examples() {
+        // Section 3.2.2: whitespace, numbers, string escapes and literals.
+        let input = r#"{
+            "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+            "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+            "literals": [null, true, false]
+        }"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+        assert_eq!(
+            String::from_utf8(canonicalize(&value)).unwrap(),
+            r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+        );
+
+        // Section 3.2.3: keys sort by UTF-16 code units, so the emoji comes before U+FB33.
+        let input = r#"{"\u20ac": "Euro Sign", "\r": "Carriage Return", "\ufb33": "Hebrew Letter Dalet With Dagesh",
+            "1": "One", "\ud83d\ude00": "Emoji: Grinning Face", "\u0080": "Control", "\u00f6": "Latin Small Letter O With Diaeresis"}"#;
+        let value: Value = serde_json::from_str(input).unwrap();
+        assert_eq!(
+            String::from_utf8(canonicalize(&value)).unwrap(),
+            concat!(
+                "{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",",
+                "\"\u{f6}\":\"Latin Small Letter O With Diaeresis\",\"\u{20ac}\":\"Euro Sign\",",
+                "\"\u{1f600}\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}"
+            )
+        );
+    }
+
+    #[test]
+    fn it_writes_numbers_like_ecmascript() {
+        // Appendix B.
+        let cases = [
+            (0x0000000000000000, "0"),
+            (0x8000000000000000, "0"),
+            (0x0000000000000001, "5e-324"),
+            (0x7fefffffffffffff, "1.7976931348623157e+308"),
+            (0x4340000000000000, "9007199254740992"),
+            (0x444b1ae4d6e2ef50, "1e+21"),
+            (0x444b1ae4d6e2ef4f, "999999999999999900000"),
+            (0x3eb0c6f7a0b5ed8d, "0.000001"),
+            (0x3eb0c6f7a0b5ed8c, "9.999999999999997e-7"),
+            (0xc3e0000000000000, "-9223372036854776000"),
+        ];
+        for (bits, expected) in cases {
+            assert_eq!(number_to_string(f64::from_bits(bits)), expected);
+        }
+    }
+
+    #[test]
+    fn it_ignores_how_a_document_was_written() {
+        let (identity, _) = Identity::new("Canonical User", "Same bytes every time.").unwrap();
+        let yaml = serde_yaml::to_string(&identity).unwrap();
+        let json = serde_json::to_string_pretty(&identity).unwrap();
+        let reformatted: Identity = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_yaml::from_str::<Identity>(&yaml).unwrap().to_canonical_json().unwrap(), identity.to_canonical_json().unwrap());
+        assert_eq!(identity.to_canonical_json().unwrap(), reformatted.to_canonical_json().unwrap());
+        assert!(!identity.to_canonical_json().unwrap().contains(&b'\n'));
+    }
+}
@@ -0,0 +1,80 @@
+// crates/idp-core/src/cbor.rs
+
+// A compact binary form of IDP documents for embedded devices and the network. Encoding is
+// deterministic (RFC 8949, section 4.2.1): shortest-form numbers, definite lengths and map keys
+// sorted by their encoded bytes, so the same document always gives the same bytes to hash or sign.
+
+use ciborium::Value;
+use serde::Serialize;
+
+use crate::{Identity, IdpError};
+
+/// Encodes any serializable value (a document, or a view of one) as deterministic CBOR.
+pub fn to_deterministic_cbor<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, IdpError> {
+    // 1. Go through a generic CBOR tree, whose maps can be put in order.
+    let mut document = Value::serialized(value).map_err(|e| IdpError::Cbor(e.to_string()))?;
+    sort_maps(&mut document)?;
+
+    // 2. ciborium already writes numbers and lengths in their shortest form.
+    let mut bytes = Vec::new();
+    ciborium::into_writer(&document, &mut bytes).map_err(|e| IdpError::Cbor(e.to_string()))?;
+    Ok(bytes)
+}
+
+impl Identity {
+    /// Encodes the document as deterministic CBOR.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, IdpError> {
+        to_deterministic_cbor(self)
+    }
+
+    /// Decodes a document written by `to_cbor` (or any other CBOR encoder).
+    pub fn from_cbor(bytes: &[u8]) -> Result<Self, IdpError> {
+        ciborium::from_reader(bytes).map_err(|e| IdpError::Cbor(e.to_string()))
+    }
+}
+
+// Orders the entries of every map by the bytes of their encoded keys, which for text keys means
+// shorter keys first and keys of the same length in byte order.
+fn sort_maps(value: &mut Value) -> Result<(), IdpError> {
+    match value {
+        Value::Array(items) => items.iter_mut().try_for_each(sort_maps),
+        Value::Tag(_, inner) => sort_maps(inner),
+        Value::Map(entries) => {
+            let mut keyed = Vec::with_capacity(entries.len());
+            for (key, mut item) in entries.drain(..) {
+                sort_maps(&mut item)?;
+                let mut encoded = Vec::new();
+                ciborium::into_writer(&key, &mut encoded).map_err(|e| IdpError::Cbor(e.to_string()))?;
+                keyed.push((encoded, key, item));
+            }
+            keyed.sort_by(|a, b| a.0.cmp(&b.0));
+            entries.extend(keyed.into_iter().map(|(_, key, item)| (key, item)));
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encodes_documents_deterministically() {
+        let (identity, _) = Identity::new("Binary User", "Small and stable.").unwrap();
+        let bytes = identity.to_cbor().unwrap();
+        assert_eq!(Identity::from_cbor(&bytes).unwrap(), identity);
+
+        // The same document read back from another format encodes to the same bytes.
+        let from_json = Identity::parse(identity.to_string_with_format(crate::Format::Json).unwrap().as_bytes()).unwrap();
+        assert_eq!(from_json.to_cbor().unwrap(), bytes);
+        assert!(bytes.len() < identity.to_string_with_format(crate::Format::Json).unwrap().len());
+
+        // Keys are in deterministic order: the top-level map starts with the shortest key.
+        let Value::Map(entries) = ciborium::from_reader::<Value, _>(bytes.as_slice()).unwrap() else {
+            panic!("a document is a map");
+        };
+        let keys: Vec<&str> = entries.iter().map(|(k, _)| k.as_text().unwrap()).collect();
+        assert_eq!(keys, ["core", "proofs", "system", "identity"]);
+    }
+}
@@ -0,0 +1,240 @@
+// crates/idp-core/src/claims.rs
+
+// Typed claims. A credential's claim is free text unless it is a typed claim: the canonical JSON
+// of `{"type": <claim type URI>, "payload": {...}}`, which verifiers can parse. The claim string
+// is what the issuer signs, so the type is covered by the signature like the rest. A registry of
+// built-in schemas covers the common claims; applications add their own with `ClaimType`.
+
+use chrono::NaiveDate;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::{canonical, Credential, IdpError};
+
+/// A claim with a type URI and a structured payload.
+pub trait ClaimType: Serialize + DeserializeOwned {
+    /// The URI claims of this type carry in `type`.
+    const TYPE: &'static str;
+
+    /// Checks what the payload's shape cannot, e.g. that a date is not in the future.
+    fn check(&self) -> Result<(), IdpError> {
+        Ok(())
+    }
+
+    /// The claim string a credential carries for this claim.
+    fn to_claim(&self) -> Result<String, IdpError> {
+        self.check()?;
+        encode(Self::TYPE, serde_json::to_value(self)?)
+    }
+}
+
+/// A person's legal name.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Name {
+    pub given_name: String,
+    pub family_name: String,
+}
+
+impl ClaimType for Name {
+    const TYPE: &'static str = "https://idp.org/claims/v1/name";
+}
+
+/// A person's date of birth.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Birthdate {
+    pub birthdate: NaiveDate,
+}
+
+impl ClaimType for Birthdate {
+    const TYPE: &'static str = "https://idp.org/claims/v1/birthdate";
+
+    fn check(&self) -> Result<(), IdpError> {
+        match self.birthdate > chrono::Utc::now().date_naive() {
+            true => Err(IdpError::Credential("a birthdate cannot be in the future".to_string())),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Membership of an organization, optionally with a role and since when.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Membership {
+    pub organization: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub since: Option<NaiveDate>,
+}
+
+impl ClaimType for Membership {
+    const TYPE: &'static str = "https://idp.org/claims/v1/membership";
+}
+
+/// A degree, certificate or licence awarded to a person.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct Qualification {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub field: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub awarded_on: Option<NaiveDate>,
+}
+
+impl ClaimType for Qualification {
+    const TYPE: &'static str = "https://idp.org/claims/v1/qualification";
+}
+
+/// A reputation score another platform holds for its subject, e.g. a seller rating of 4.8 out of 5.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ReputationAttestation {
+    pub score_name: String,
+    pub value: f64,
+    /// The best score there is, if the score has one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// Where the score was earned, if not the issuer itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<String>,
+    pub as_of: NaiveDate,
+}
+
+impl ClaimType for ReputationAttestation {
+    const TYPE: &'static str = "https://idp.org/claims/v1/reputation";
+
+    fn check(&self) -> Result<(), IdpError> {
+        if self.as_of > chrono::Utc::now().date_naive() {
+            return Err(IdpError::Credential("a score cannot be attested as of a future date".to_string()));
+        }
+        match self.scale {
+            Some(scale) if self.value > scale => Err(IdpError::Credential(format!("a score of {} is off its scale of {}", self.value, scale))),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// A claim schema in the registry, for building and checking claims by name.
+#[derive(Debug, Clone, Copy)]
+pub struct ClaimSchema {
+    /// A short name, e.g. `membership`.
+    pub name: &'static str,
+    pub type_uri: &'static str,
+    /// The payload's fields, and whether each is required.
+    pub fields: &'static [(&'static str, bool)],
+    check: fn(Value) -> Result<(), IdpError>,
+}
+
+impl ClaimSchema {
+    /// Checks that `payload` is a claim of this type.
+    pub fn validate(&self, payload: &Value) -> Result<(), IdpError> {
+        (self.check)(payload.clone())
+    }
+
+    /// The claim string for `payload`, once it validates.
+    pub fn to_claim(&self, payload: Value) -> Result<String, IdpError> {
+        self.validate(&payload)?;
+        encode(self.type_uri, payload)
+    }
+}
+
+// Checks a payload by reading it as `T`.
+fn check_as<T: ClaimType>(payload: Value) -> Result<(), IdpError> {
+    serde_json::from_value::<T>(payload).map_err(|e| IdpError::Credential(format!("not a valid {} claim: {}", T::TYPE, e)))?.check()
+}
+
+/// The built-in claim schemas.
+pub const BUILT_IN: [ClaimSchema; 5] = [
+    ClaimSchema { name: "name", type_uri: Name::TYPE, fields: &[("given_name", true), ("family_name", true)], check: check_as::<Name> },
+    ClaimSchema { name: "birthdate", type_uri: Birthdate::TYPE, fields: &[("birthdate", true)], check: check_as::<Birthdate> },
+    ClaimSchema {
+        name: "membership",
+        type_uri: Membership::TYPE,
+        fields: &[("organization", true), ("role", false), ("since", false)],
+        check: check_as::<Membership>,
+    },
+    ClaimSchema {
+        name: "qualification",
+        type_uri: Qualification::TYPE,
+        fields: &[("title", true), ("field", false), ("awarded_on", false)],
+        check: check_as::<Qualification>,
+    },
+    ClaimSchema {
+        name: "reputation",
+        type_uri: ReputationAttestation::TYPE,
+        fields: &[("score_name", true), ("value", true), ("scale", false), ("platform", false), ("as_of", true)],
+        check: check_as::<ReputationAttestation>,
+    },
+];
+
+/// The built-in schema with this name or type URI.
+pub fn schema(name_or_uri: &str) -> Option<&'static ClaimSchema> {
+    BUILT_IN.iter().find(|schema| schema.name == name_or_uri || schema.type_uri == name_or_uri)
+}
+
+/// The claim string for a payload of `claim_type`: canonical JSON, so it reads back the same.
+pub fn encode(claim_type: &str, payload: Value) -> Result<String, IdpError> {
+    if !payload.is_object() {
+        return Err(IdpError::Credential("a typed claim's payload must be a JSON object".to_string()));
+    }
+    let claim = canonical::canonicalize(&json!({ "type": claim_type, "payload": payload }));
+    Ok(String::from_utf8(claim).expect("canonical JSON is UTF-8"))
+}
+
+/// The type and payload of a typed claim string; `None` for free text.
+pub fn decode(claim: &str) -> Option<(String, Value)> {
+    let mut claim = serde_json::from_str::<Value>(claim).ok()?;
+    let object = claim.as_object_mut().filter(|object| object.len() == 2)?;
+    let payload = object.remove("payload").filter(Value::is_object)?;
+    Some((object.remove("type")?.as_str()?.to_string(), payload))
+}
+
+impl Credential {
+    /// The claim type URI, if the claim is typed.
+    pub fn claim_type(&self) -> Option<String> {
+        decode(&self.claim).map(|(claim_type, _)| claim_type)
+    }
+
+    /// Reads the claim as `T`; it must be typed, and of `T`'s type.
+    pub fn parse_claim<T: ClaimType>(&self) -> Result<T, IdpError> {
+        let (claim_type, payload) = decode(&self.claim).ok_or_else(|| IdpError::Credential("the claim is free text, not a typed claim".to_string()))?;
+        if claim_type != T::TYPE {
+            return Err(IdpError::Credential(format!("the claim is a {}, not a {}", claim_type, T::TYPE)));
+        }
+        let claim: T = serde_json::from_value(payload).map_err(|e| IdpError::Credential(format!("the claim does not match {}: {}", T::TYPE, e)))?;
+        claim.check()?;
+        Ok(claim)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_issues_and_parses_typed_claims() {
+        let (issuer, issuer_key) = Identity::new("Chess Club", "Keeps a roster.").unwrap();
+        let (mut member, _) = Identity::new("Member", "Plays chess.").unwrap();
+        let membership = Membership { organization: "Chess Club".to_string(), role: Some("captain".to_string()), since: NaiveDate::from_ymd_opt(2024, 1, 1) };
+        let issued = issuer.issue_credential(&member, &membership.to_claim().unwrap(), None, &issuer_key).unwrap();
+        member.add_credential(issued).unwrap();
+
+        // The holder's copy reads back as the type it was issued as, and only as that type.
+        let credential = &member.credentials[0];
+        assert_eq!(credential.claim_type().as_deref(), Some(Membership::TYPE));
+        assert_eq!(credential.parse_claim::<Membership>().unwrap(), membership);
+        assert!(credential.parse_claim::<Qualification>().is_err());
+
+        // Schemas are found by name, and check payloads built at run time.
+        let schema = schema("birthdate").unwrap();
+        assert!(schema.to_claim(json!({ "birthdate": "2000-02-29" })).is_ok());
+        assert!(schema.validate(&json!({ "birthdate": "2999-01-01" })).is_err(), "in the future");
+        assert!(schema.validate(&json!({ "born": "2000-02-29" })).is_err(), "unknown field");
+        assert_eq!(decode("member of the chess club"), None);
+    }
+}
@@ -0,0 +1,71 @@
+// crates/idp-core/src/compress.rs
+
+// zstd-compressed identity files (`.idp.zst`). A compressed file is a short magic header followed
+// by one zstd frame holding the YAML or JSON document; loading decompresses it transparently.
+
+use std::path::Path;
+
+use crate::IdpError;
+
+/// The start of every compressed document: "IDPZ" and the container version.
+pub const MAGIC: &[u8; 5] = b"IDPZ\x01";
+
+// zstd's default level: most of the gain of higher levels, for documents that are mostly text.
+const LEVEL: i32 = 3;
+
+/// Returns true if `contents` is a compressed document.
+pub fn is_compressed(contents: &[u8]) -> bool {
+    contents.starts_with(MAGIC)
+}
+
+/// Returns true if a new file at `path` should be written compressed (its name ends in `.zst`).
+pub fn wants_compression<P: AsRef<Path>>(path: P) -> bool {
+    path.as_ref().extension().is_some_and(|extension| extension == "zst")
+}
+
+/// Wraps a serialized document in the compressed container.
+pub fn compress(contents: &[u8]) -> Result<Vec<u8>, IdpError> {
+    let mut compressed = MAGIC.to_vec();
+    compressed.extend(zstd::encode_all(contents, LEVEL)?);
+    Ok(compressed)
+}
+
+/// The document inside a compressed container; anything else is returned as it is.
+pub fn decompress(contents: Vec<u8>) -> Result<Vec<u8>, IdpError> {
+    match contents.strip_prefix(MAGIC) {
+        Some(frame) => Ok(zstd::decode_all(frame)?),
+        None => Ok(contents),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_round_trips_compressed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut identity, _) = Identity::new("Compact User", &"A long biography. ".repeat(200)).unwrap();
+
+        // A `.zst` name is written compressed, and loads like any other file.
+        let file_path = dir.path().join("compact.idp.zst");
+        identity.save_to_file(&file_path).unwrap();
+        let contents = std::fs::read(&file_path).unwrap();
+        assert!(is_compressed(&contents));
+        assert!(contents.len() < identity.to_string_with_format(crate::Format::Yaml).unwrap().len() / 4);
+        identity.integrity = Some(identity.compute_integrity().unwrap());
+        assert_eq!(Identity::load_from_file(&file_path).unwrap(), identity);
+        assert_eq!(Identity::load_sections(&file_path, &["core"]).unwrap().core, Some(identity.core.clone()));
+
+        // A compressed file stays compressed when saved again, whatever its name.
+        let plain_path = dir.path().join("compact.idp");
+        identity.save_to_file_compressed(&plain_path, true).unwrap();
+        identity.save_to_file(&plain_path).unwrap();
+        assert!(is_compressed(&std::fs::read(&plain_path).unwrap()));
+        identity.save_to_file_compressed(&plain_path, false).unwrap();
+        assert!(std::fs::read_to_string(&plain_path).unwrap().starts_with("identity:"));
+
+        assert!(matches!(decompress(b"IDPZ\x01garbage".to_vec()), Err(IdpError::Io(_))));
+    }
+}
@@ -0,0 +1,219 @@
+// crates/idp-core/src/config.rs
+
+// User configuration: `config.toml` in the IDP home directory. It holds defaults for what is
+// otherwise given on every command line: which identity file to work on, where its key is kept,
+// the preferred formats, the issuers trusted as roots, and where to look for other identities.
+// Flags and environment variables override it; a missing file is an empty configuration.
+//
+// Profiles keep several identities apart, e.g. a personal and a work one. Each profile is a
+// directory under `profiles/` in the home directory, with its own identity file, key and
+// `config.toml`, whose settings win over the home directory's. One profile can be made current.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::IdpError;
+
+/// The configuration file's name in the IDP home directory.
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// The directory of profiles in the IDP home directory.
+pub const PROFILES_DIR: &str = "profiles";
+
+/// The file in the IDP home directory naming the current profile.
+pub const CURRENT_PROFILE: &str = "profile";
+
+/// The identity file of a profile, unless its configuration names another.
+pub const PROFILE_IDENTITY: &str = "my.idp";
+
+/// Defaults for the command line.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// The identity file to work on.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub identity: Option<PathBuf>,
+    /// The key file, if not the identity file's name ending in `.key`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_file: Option<PathBuf>,
+    /// Where the private key is kept, e.g. `file` or `os`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub keystore: Option<String>,
+    /// The format for new identity files and exports, `yaml` or `json`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// How commands print what they report: `text`, `json` or `yaml`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    /// The ids of identities trusted as roots when checking who vouches for an issuer.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub trusted_issuers: Vec<String>,
+    /// Directories of identity files to find other identities in.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub resolvers: Vec<PathBuf>,
+}
+
+impl Config {
+    /// The configuration in `home`, or the empty one if there is none. Relative paths in it are
+    /// taken from `home`.
+    pub fn load(home: &Path) -> Result<Config, IdpError> {
+        let path = home.join(CONFIG_FILE);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Config::default()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut config: Config = toml::from_str(&text).map_err(|e| IdpError::Config(format!("{}: {}", path.display(), e)))?;
+        for path in config.identity.iter_mut().chain(config.key_file.iter_mut()).chain(config.resolvers.iter_mut()) {
+            *path = home.join(expand_home(path));
+        }
+        Ok(config)
+    }
+
+    /// The configuration of profile `name`: its own settings, then the home directory's. Its
+    /// identity file is the profile's `my.idp` unless it names another, and only a key file it
+    /// names itself applies.
+    pub fn load_profile(home: &Path, name: &str) -> Result<Config, IdpError> {
+        let dir = profile_dir(home, name)?;
+        if !dir.is_dir() {
+            return Err(IdpError::Config(format!("there is no profile '{}'", name)));
+        }
+        let base = Config::load(home)?;
+        let own = Config::load(&dir)?;
+        Ok(Config {
+            identity: Some(own.identity.unwrap_or_else(|| dir.join(PROFILE_IDENTITY))),
+            key_file: own.key_file,
+            keystore: own.keystore.or(base.keystore),
+            format: own.format.or(base.format),
+            output: own.output.or(base.output),
+            trusted_issuers: if own.trusted_issuers.is_empty() { base.trusted_issuers } else { own.trusted_issuers },
+            resolvers: if own.resolvers.is_empty() { base.resolvers } else { own.resolvers },
+        })
+    }
+}
+
+/// The directory of profile `name`. Names are lower-case letters, digits, `-` and `_`.
+pub fn profile_dir(home: &Path, name: &str) -> Result<PathBuf, IdpError> {
+    let valid = !name.is_empty() && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-' || c == '_');
+    match valid {
+        true => Ok(home.join(PROFILES_DIR).join(name)),
+        false => Err(IdpError::Config(format!("'{}' is not a profile name; use lower-case letters, digits, `-` and `_`", name))),
+    }
+}
+
+/// The profiles in `home`, by name.
+pub fn profiles(home: &Path) -> Result<Vec<String>, IdpError> {
+    let mut names = Vec::new();
+    match std::fs::read_dir(home.join(PROFILES_DIR)) {
+        Ok(entries) => {
+            for entry in entries {
+                let entry = entry?;
+                if let Some(name) = entry.file_name().to_str()
+                    && entry.file_type()?.is_dir()
+                    && profile_dir(home, name).is_ok()
+                {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    names.sort();
+    Ok(names)
+}
+
+/// Starts profile `name`, with an empty configuration; its identity is made in it with `init`.
+pub fn create_profile(home: &Path, name: &str) -> Result<PathBuf, IdpError> {
+    let dir = profile_dir(home, name)?;
+    if dir.exists() {
+        return Err(IdpError::Config(format!("the profile '{}' already exists", name)));
+    }
+    std::fs::create_dir_all(&dir)?;
+    std::fs::write(dir.join(CONFIG_FILE), "# Settings for this profile, over those of the IDP home directory.\n")?;
+    Ok(dir)
+}
+
+/// The current profile, if one is set.
+pub fn current_profile(home: &Path) -> Result<Option<String>, IdpError> {
+    match std::fs::read_to_string(home.join(CURRENT_PROFILE)) {
+        Ok(name) => Ok(Some(name.trim().to_string()).filter(|name| !name.is_empty())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Makes profile `name` current, or none with `None`.
+pub fn switch_profile(home: &Path, name: Option<&str>) -> Result<(), IdpError> {
+    match name {
+        Some(name) => {
+            if !profile_dir(home, name)?.is_dir() {
+                return Err(IdpError::Config(format!("there is no profile '{}'", name)));
+            }
+            crate::atomic::write(&home.join(CURRENT_PROFILE), format!("{}\n", name).as_bytes())
+        }
+        None => match std::fs::remove_file(home.join(CURRENT_PROFILE)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        },
+    }
+}
+
+// `~/ids/alice.idp` names a file in the user's home directory.
+fn expand_home(path: &Path) -> PathBuf {
+    match (path.strip_prefix("~"), std::env::var_os("HOME")) {
+        (Ok(rest), Some(home)) => PathBuf::from(home).join(rest),
+        _ => path.to_path_buf(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_loads_the_configuration_from_the_home_directory() {
+        let home = tempfile::tempdir().unwrap();
+        assert_eq!(Config::load(home.path()).unwrap(), Config::default());
+
+        std::fs::write(
+            home.path().join(CONFIG_FILE),
+            "identity = \"ids/alice.idp\"\nkeystore = \"os\"\ntrusted_issuers = [\"idp:key:sha256:root\"]\nresolvers = [\"/srv/identities\"]\n",
+        )
+        .unwrap();
+        let config = Config::load(home.path()).unwrap();
+        assert_eq!(config.identity, Some(home.path().join("ids/alice.idp")));
+        assert_eq!((config.keystore.as_deref(), config.format), (Some("os"), None));
+        assert_eq!(config.resolvers, [PathBuf::from("/srv/identities")]);
+
+        // Settings it does not know are mistakes, not ignored.
+        std::fs::write(home.path().join(CONFIG_FILE), "identiy = \"alice.idp\"\n").unwrap();
+        assert!(matches!(Config::load(home.path()), Err(IdpError::Config(_))));
+    }
+
+    #[test]
+    fn it_keeps_profiles_apart() {
+        let home = tempfile::tempdir().unwrap();
+        std::fs::write(home.path().join(CONFIG_FILE), "keystore = \"os\"\nformat = \"json\"\n").unwrap();
+        let work = create_profile(home.path(), "work").unwrap();
+        create_profile(home.path(), "personal").unwrap();
+        std::fs::write(work.join(CONFIG_FILE), "keystore = \"file\"\n").unwrap();
+        assert!(create_profile(home.path(), "work").is_err());
+        assert!(create_profile(home.path(), "../work").is_err());
+        assert_eq!(profiles(home.path()).unwrap(), ["personal", "work"]);
+
+        // A profile's settings win over the home directory's, and its identity is its own.
+        let config = Config::load_profile(home.path(), "work").unwrap();
+        assert_eq!(config.identity, Some(work.join(PROFILE_IDENTITY)));
+        assert_eq!((config.keystore.as_deref(), config.format.as_deref()), (Some("file"), Some("json")));
+        assert!(Config::load_profile(home.path(), "school").is_err());
+
+        assert_eq!(current_profile(home.path()).unwrap(), None);
+        switch_profile(home.path(), Some("work")).unwrap();
+        assert_eq!(current_profile(home.path()).unwrap().as_deref(), Some("work"));
+        assert!(switch_profile(home.path(), Some("school")).is_err());
+        switch_profile(home.path(), None).unwrap();
+        assert_eq!(current_profile(home.path()).unwrap(), None);
+    }
+}
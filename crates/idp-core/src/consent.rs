@@ -0,0 +1,531 @@
+// crates/idp-core/src/consent.rs
+
+// Managing the consent block: which fields of the document another party may use, for what, and
+// until when. There is one consent per grantee and purpose; granting again renews it. Revoking
+// does not delete it but records when it was withdrawn, so the document keeps a record of what
+// was shared and when sharing stopped. Consents that have expired can be moved to
+// `archived_consent`, out of the way of anyone reading what is granted now.
+//
+// A consent's purpose is one of a few standard ones (`ConsentPurpose`), so policies can act on
+// it, or free text.
+//
+// Every grant, revocation and expiry, and every access a grantee makes under a consent, is
+// entered in `consent_log`. Each entry carries the hash of the one before, so an entry removed
+// or rewritten breaks the chain from there on.
+//
+// Exporting for a grantee enforces the consents: the view holds only what they may see. Services
+// embedding this crate ask `is_access_allowed` before each use, and get the consent that allows
+// it, or why none does.
+//
+// A grantee who needs to show they were given consent holds a receipt: the consent as granted,
+// signed by the grantor, which anyone with the grantor's document can check.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use serde_yaml::Value as YamlValue;
+
+use crate::contract::check_signed;
+use crate::signer::SigningBackend;
+use crate::{canonical, path, Consent, ConsentAction, ConsentLogEntry, ConsentPurpose, Identity, IdpError, SignatureComponent, Signer};
+
+/// The `type` of a consent receipt.
+pub const CONSENT_RECEIPT: &str = "idp-consent-receipt";
+
+/// A grantor's signed statement that they gave a consent, for the grantee to keep and present.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsentReceipt {
+    #[serde(rename = "type")]
+    pub receipt_type: String,
+    /// The identity that granted the consent.
+    pub grantor: String,
+    pub granted_to: String,
+    pub fields: Vec<String>,
+    pub purpose: ConsentPurpose,
+    pub expires_at: DateTime<Utc>,
+    pub granted_at: DateTime<Utc>,
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signature: Vec<SignatureComponent>,
+}
+
+impl ConsentReceipt {
+    /// The canonical receipt without its signature, which the grantor signs.
+    pub fn statement(&self) -> Vec<u8> {
+        let mut receipt = serde_json::to_value(self).unwrap_or(Value::Null);
+        if let Value::Object(fields) = &mut receipt {
+            fields.remove("signature");
+        }
+        canonical::canonicalize(&receipt)
+    }
+
+    /// Checks the receipt against the grantor's document at `now`: it is theirs and signed
+    /// with a key they held, the consent has not expired, and the document does not show it
+    /// revoked since.
+    pub fn verify(&self, grantor: &Identity, now: DateTime<Utc>) -> Result<(), IdpError> {
+        // 1. A receipt, from this grantor.
+        if self.receipt_type != CONSENT_RECEIPT {
+            return Err(IdpError::Consent(format!("'{}' is not a consent receipt", self.receipt_type)));
+        }
+        if self.grantor != grantor.identity.id.as_str() || self.signed_by.idp_id != grantor.identity.id {
+            return Err(IdpError::Consent(format!("the receipt is from {}, not {}", self.grantor, grantor.identity.id)));
+        }
+
+        // 2. Their signature.
+        check_signed(grantor, &self.signed_by, self.signed_at, &self.statement(), &self.signature)?;
+
+        // 3. A consent that still holds.
+        if now >= self.expires_at {
+            return Err(IdpError::Consent(format!("the consent expired on {}", self.expires_at.to_rfc3339())));
+        }
+        let revoked = grantor.consent.iter().find(|c| c.granted_to == self.granted_to && c.purpose == self.purpose).and_then(|c| c.revoked_at);
+        match revoked {
+            Some(revoked_at) if revoked_at >= self.granted_at => Err(IdpError::Consent(format!("{} revoked the consent on {}", grantor.identity.id, revoked_at.to_rfc3339()))),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Consent {
+    pub fn is_revoked(&self) -> bool {
+        self.revoked_at.is_some()
+    }
+
+    /// Whether the consent reaches `field`: it names the field, or a section holding it
+    /// (`core` covers `core.name`).
+    pub fn covers(&self, field: &str) -> bool {
+        self.fields.iter().any(|granted| field == granted || field.strip_prefix(granted.as_str()).is_some_and(|rest| rest.starts_with('.')))
+    }
+}
+
+/// Whether a requester may use a field for a purpose, as the consents decide.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Decision<'a> {
+    /// The consent that allows it.
+    Allow(&'a Consent),
+    /// Why it is not allowed, with the requester's consent that came closest, if they hold one.
+    Deny(DenyReason, Option<&'a Consent>),
+}
+
+impl Decision<'_> {
+    pub fn is_allowed(&self) -> bool {
+        matches!(self, Decision::Allow(_))
+    }
+}
+
+/// Why access is denied, from the least to the most nearly allowed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DenyReason {
+    /// The requester holds no consent at all.
+    NoConsent,
+    /// Their consents are for other purposes.
+    OtherPurpose,
+    /// Their consent for this purpose does not cover the field.
+    FieldNotCovered,
+    /// The consent that covers it starts later.
+    NotYetGranted,
+    Expired,
+    Revoked,
+}
+
+impl std::fmt::Display for DenyReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            DenyReason::NoConsent => "no consent was given",
+            DenyReason::OtherPurpose => "consent was given for other purposes",
+            DenyReason::FieldNotCovered => "the consent does not cover the field",
+            DenyReason::NotYetGranted => "the consent was not yet granted",
+            DenyReason::Expired => "the consent has expired",
+            DenyReason::Revoked => "the consent was revoked",
+        })
+    }
+}
+
+impl Identity {
+    /// Decides whether `requester` may use the field at `field_path` for `purpose` at `now`:
+    /// allowed if one of their consents is for that purpose, covers the field, and holds then.
+    pub fn is_access_allowed(&self, requester: &str, field_path: &str, purpose: &ConsentPurpose, now: DateTime<Utc>) -> Decision<'_> {
+        let field = field_path.replace('[', ".").replace(']', "");
+        let mut closest: Option<(DenyReason, &Consent)> = None;
+        for consent in self.consent.iter().filter(|consent| consent.granted_to == requester) {
+            let reason = if consent.purpose != *purpose {
+                DenyReason::OtherPurpose
+            } else if !consent.covers(&field) {
+                DenyReason::FieldNotCovered
+            } else if consent.is_revoked() {
+                DenyReason::Revoked
+            } else if consent.is_expired(now) {
+                DenyReason::Expired
+            } else if consent.granted_at.is_some_and(|granted_at| granted_at > now) {
+                DenyReason::NotYetGranted
+            } else {
+                return Decision::Allow(consent);
+            };
+            if closest.is_none_or(|(closest, _)| reason > closest) {
+                closest = Some((reason, consent));
+            }
+        }
+        match closest {
+            Some((reason, consent)) => Decision::Deny(reason, Some(consent)),
+            None => Decision::Deny(DenyReason::NoConsent, None),
+        }
+    }
+
+    /// Grants `granted_to` the use of `fields` (dot-paths, e.g. `core.name`) for `purpose`
+    /// until `expires_at`. A consent already given for the same purpose is renewed in place,
+    /// revoked or not.
+    pub fn grant_consent(&mut self, granted_to: &str, fields: &[String], purpose: &ConsentPurpose, expires_at: DateTime<Utc>) -> Result<&Consent, IdpError> {
+        // 1. Someone to grant it to, for something, over fields that are paths.
+        let now = Utc::now();
+        if granted_to.trim().is_empty() || purpose.to_string().trim().is_empty() {
+            return Err(IdpError::Consent("consent needs a grantee and a purpose".to_string()));
+        }
+        if fields.is_empty() {
+            return Err(IdpError::Consent(format!("consent to {} grants no fields", granted_to)));
+        }
+        for field in fields {
+            path::parse(field).map_err(|e| IdpError::Consent(format!("'{}' is not a field: {}", field, e)))?;
+        }
+        if expires_at <= now {
+            return Err(IdpError::Consent(format!("consent to {} would expire before it is granted ({})", granted_to, expires_at.to_rfc3339())));
+        }
+
+        // 2. A new grant, or the renewal of one for the same purpose.
+        let consent = Consent {
+            granted_to: granted_to.to_string(),
+            fields: fields.to_vec(),
+            expires_at,
+            purpose: purpose.clone(),
+            granted_at: Some(now),
+            revoked_at: None,
+            unknown_fields: Default::default(),
+        };
+        self.log_consent(ConsentAction::Granted, &consent, fields, now)?;
+        let index = match self.consent.iter().position(|c| c.granted_to == granted_to && c.purpose == *purpose) {
+            Some(index) => {
+                let unknown_fields = std::mem::take(&mut self.consent[index].unknown_fields);
+                self.consent[index] = Consent { unknown_fields, ..consent };
+                index
+            }
+            None => {
+                self.consent.push(consent);
+                self.consent.len() - 1
+            }
+        };
+        Ok(&self.consent[index])
+    }
+
+    /// Revokes the consents given to `granted_to`, or only the one for `purpose`, and returns
+    /// how many were revoked. Consents already revoked keep their first revocation.
+    pub fn revoke_consent(&mut self, granted_to: &str, purpose: Option<&ConsentPurpose>) -> Result<usize, IdpError> {
+        let now = Utc::now();
+        let mut revoked = Vec::new();
+        for consent in self.consent.iter_mut().filter(|c| c.granted_to == granted_to && purpose.is_none_or(|p| c.purpose == *p) && !c.is_revoked()) {
+            consent.revoked_at = Some(now);
+            revoked.push(consent.clone());
+        }
+        for consent in &revoked {
+            self.log_consent(ConsentAction::Revoked, consent, &[], now)?;
+        }
+        match (revoked.len(), purpose) {
+            (0, Some(purpose)) => Err(IdpError::Consent(format!("{} holds no consent for '{}' to revoke", granted_to, purpose))),
+            (0, None) => Err(IdpError::Consent(format!("{} holds no consent to revoke", granted_to))),
+            (count, _) => Ok(count),
+        }
+    }
+
+    /// Signs a receipt for the active consent given to `granted_to` for `purpose`.
+    pub fn consent_receipt(&self, granted_to: &str, purpose: &ConsentPurpose, signer: &dyn SigningBackend) -> Result<ConsentReceipt, IdpError> {
+        let now = Utc::now();
+        let consent = self
+            .consent
+            .iter()
+            .find(|c| c.granted_to == granted_to && c.purpose == *purpose && c.is_active(now))
+            .ok_or_else(|| IdpError::Consent(format!("{} holds no active consent for '{}'", granted_to, purpose)))?;
+        let granted_at = consent.granted_at.ok_or_else(|| IdpError::Consent(format!("the consent to {} has no grant time; grant it again", granted_to)))?;
+        let mut receipt = ConsentReceipt {
+            receipt_type: CONSENT_RECEIPT.to_string(),
+            grantor: self.identity.id.to_string(),
+            granted_to: consent.granted_to.clone(),
+            fields: consent.fields.clone(),
+            purpose: consent.purpose.clone(),
+            expires_at: consent.expires_at,
+            granted_at,
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: self.issuing_key(signer)?.key_id.clone() },
+            signed_at: now,
+            signature: vec![],
+        };
+        receipt.signature.push(signer.sign(&receipt.statement())?);
+        Ok(receipt)
+    }
+
+    /// A view of the document (see `export_view`) holding every field `grantee` may see under
+    /// the consents that hold now.
+    pub fn export_for(&self, grantee: &str) -> Result<YamlValue, IdpError> {
+        self.export_for_fields(grantee, &[])
+    }
+
+    /// A view holding `fields` for `grantee`, refused if any of them is not covered by a
+    /// consent that holds now. Without fields, it holds everything they may see.
+    pub fn export_for_fields(&self, grantee: &str, fields: &[&str]) -> Result<YamlValue, IdpError> {
+        // 1. The consents they hold now.
+        let consents: Vec<&Consent> = self.active_consents(Utc::now()).into_iter().filter(|consent| consent.granted_to == grantee).collect();
+        if consents.is_empty() {
+            return Err(IdpError::Consent(format!("{} holds no active consent", grantee)));
+        }
+
+        // 2. The fields asked for, each covered by one of them; or every field granted that the document has.
+        let fields: Vec<String> = match fields.is_empty() {
+            true => {
+                let document = serde_yaml::to_value(self)?;
+                let mut granted: Vec<String> = consents.iter().flat_map(|consent| consent.fields.iter().cloned()).collect();
+                granted.sort();
+                granted.dedup();
+                granted.retain(|field| path::query(&document, field).is_ok_and(|found| !found.is_empty()));
+                granted
+            }
+            false => fields.iter().map(|field| field.replace('[', ".").replace(']', "")).collect(),
+        };
+        if let Some(field) = fields.iter().find(|field| !consents.iter().any(|consent| consent.covers(field))) {
+            return Err(IdpError::Consent(format!("no consent lets {} see {}", grantee, field)));
+        }
+        if fields.is_empty() {
+            return Err(IdpError::Consent(format!("nothing {} may see is in the document", grantee)));
+        }
+        self.export_view(&fields.iter().map(String::as_str).collect::<Vec<_>>())
+    }
+
+    /// The consents that hold at `now`.
+    pub fn active_consents(&self, now: DateTime<Utc>) -> Vec<&Consent> {
+        self.consent.iter().filter(|consent| consent.is_active(now)).collect()
+    }
+
+    /// The consents that hold at `now` for `purpose`.
+    pub fn consents_for_purpose(&self, purpose: &ConsentPurpose, now: DateTime<Utc>) -> Vec<&Consent> {
+        self.active_consents(now).into_iter().filter(|consent| consent.purpose == *purpose).collect()
+    }
+
+    /// The consents that have expired by `now`, revoked or not.
+    pub fn expired_consents(&self, now: DateTime<Utc>) -> Vec<&Consent> {
+        self.consent.iter().filter(|consent| consent.is_expired(now)).collect()
+    }
+
+    /// Moves the consents that have expired by `now` into `archived_consent`, and returns them.
+    /// A sealed consent section or log is left alone, since the archive is written in the clear.
+    pub fn archive_expired_consent(&mut self, now: DateTime<Utc>) -> Result<&[Consent], IdpError> {
+        let archived = self.archived_consent.len();
+        if self.is_sealed("consent") || self.is_sealed("consent_log") {
+            return Ok(&[]);
+        }
+        let (expired, current): (Vec<Consent>, Vec<Consent>) = std::mem::take(&mut self.consent).into_iter().partition(|consent| consent.is_expired(now));
+        self.consent = current;
+        for consent in &expired {
+            self.log_consent(ConsentAction::Expired, consent, &[], now)?;
+        }
+        if !expired.is_empty() {
+            self.archived_consent.extend(expired);
+            self.touch();
+        }
+        Ok(&self.archived_consent[archived..])
+    }
+
+    /// Records that `granted_to` used `fields` for `purpose` at `at`. The access must be
+    /// covered by a consent that held then.
+    pub fn record_consent_access(&mut self, granted_to: &str, purpose: &ConsentPurpose, fields: &[String], at: DateTime<Utc>) -> Result<(), IdpError> {
+        let consent = self
+            .consent
+            .iter()
+            .find(|c| c.granted_to == granted_to && c.purpose == *purpose && c.is_active(at) && fields.iter().all(|field| c.covers(field)))
+            .cloned()
+            .ok_or_else(|| IdpError::Consent(format!("no consent lets {} use {} for '{}'", granted_to, fields.join(", "), purpose)))?;
+        self.log_consent(ConsentAction::Accessed, &consent, fields, at)
+    }
+
+    /// Checks that the consent log is whole: numbered from 1 without gaps, each entry
+    /// chained to the one before.
+    pub fn verify_consent_log(&self) -> Result<(), IdpError> {
+        let mut prev = String::new();
+        for (i, entry) in self.consent_log.iter().enumerate() {
+            if entry.seq != i as u64 + 1 {
+                return Err(IdpError::Consent(format!("the consent log has entry {} where {} belongs", entry.seq, i + 1)));
+            }
+            if entry.prev != prev {
+                return Err(IdpError::Consent(format!("entry {} of the consent log does not follow the one before; it was changed or removed", entry.seq)));
+            }
+            prev = canonical::claim_hash(entry)?;
+        }
+        Ok(())
+    }
+
+    // Appends an entry for `consent` to the consent log.
+    fn log_consent(&mut self, action: ConsentAction, consent: &Consent, fields: &[String], at: DateTime<Utc>) -> Result<(), IdpError> {
+        let prev = match self.consent_log.last() {
+            Some(last) => canonical::claim_hash(last)?,
+            None => String::new(),
+        };
+        self.consent_log.push(ConsentLogEntry {
+            seq: self.consent_log.len() as u64 + 1,
+            at,
+            action,
+            granted_to: consent.granted_to.clone(),
+            purpose: consent.purpose.clone(),
+            fields: fields.to_vec(),
+            prev,
+            unknown_fields: Default::default(),
+        });
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    #[test]
+    fn it_grants_renews_and_revokes_consent() {
+        let (mut identity, _) = Identity::new("Alice", "Shops online.").unwrap();
+        let fields = vec!["core.name".to_string()];
+        let next_year = Utc::now() + Duration::days(365);
+        identity.grant_consent("shop", &fields, &ConsentPurpose::ServiceDelivery, next_year).unwrap();
+        identity.grant_consent("shop", &fields, &ConsentPurpose::Marketing, next_year).unwrap();
+        assert!(identity.grant_consent("shop", &fields, &ConsentPurpose::ServiceDelivery, Utc::now() - Duration::days(1)).is_err());
+        assert!(identity.grant_consent("shop", &["core..name".to_string()], &ConsentPurpose::ServiceDelivery, next_year).is_err());
+
+        // Revoking keeps the record; granting the same purpose again renews it.
+        assert_eq!(identity.revoke_consent("shop", Some(&ConsentPurpose::Marketing)).unwrap(), 1);
+        assert!(identity.revoke_consent("shop", Some(&ConsentPurpose::Marketing)).is_err());
+        assert_eq!(identity.consent.len(), 2);
+        assert!(identity.consent[1].is_revoked() && !identity.consent[0].is_revoked());
+        let fields = vec!["core.name".to_string(), "core.address".to_string()];
+        assert!(!identity.grant_consent("shop", &fields, &ConsentPurpose::Marketing, next_year).unwrap().is_revoked());
+        assert_eq!((identity.consent.len(), identity.consent[1].fields.len()), (2, 2));
+        assert_eq!(identity.revoke_consent("shop", None).unwrap(), 2);
+    }
+
+    #[test]
+    fn it_reads_standard_and_custom_purposes() {
+        let (mut identity, _) = Identity::new("Alice", "Shops online.").unwrap();
+        let fields = vec!["core.name".to_string()];
+        let next_year = Utc::now() + Duration::days(365);
+        assert_eq!("Service Delivery".parse(), Ok(ConsentPurpose::ServiceDelivery));
+        identity.grant_consent("shop", &fields, &"service-delivery".parse().unwrap(), next_year).unwrap();
+        identity.grant_consent("shop", &fields, &"loyalty scheme".parse().unwrap(), next_year).unwrap();
+        identity.grant_consent("lab", &fields, &ConsentPurpose::Research, next_year).unwrap();
+
+        // Written by name, and read back as the same purposes.
+        let yaml = identity.to_string_with_format(crate::Format::Yaml).unwrap();
+        assert!(yaml.contains("purpose: service_delivery") && yaml.contains("purpose: loyalty scheme"));
+        let loaded = Identity::parse(yaml.as_bytes()).unwrap();
+        assert_eq!(loaded.consent[1].purpose, ConsentPurpose::Custom("loyalty scheme".to_string()));
+        let for_research = loaded.consents_for_purpose(&ConsentPurpose::Research, Utc::now());
+        assert_eq!(for_research.iter().map(|consent| consent.granted_to.as_str()).collect::<Vec<_>>(), ["lab"]);
+    }
+
+    #[test]
+    fn it_archives_expired_consent() {
+        let (mut identity, _) = Identity::new("Alice", "Shops online.").unwrap();
+        let fields = vec!["core.name".to_string()];
+        let now = Utc::now();
+        identity.grant_consent("shop", &fields, &ConsentPurpose::ServiceDelivery, now + Duration::days(30)).unwrap();
+        identity.grant_consent("lab", &fields, &ConsentPurpose::Research, now + Duration::days(365)).unwrap();
+        identity.revoke_consent("lab", None).unwrap();
+        assert_eq!(identity.active_consents(Utc::now()).len(), 1);
+
+        // A month on, the shop's consent has lapsed and moves to the archive; the revoked one stays.
+        let later = now + Duration::days(31);
+        assert!(identity.active_consents(later).is_empty());
+        let archived = identity.archive_expired_consent(later).unwrap().to_vec();
+        assert_eq!(archived.iter().map(|consent| consent.granted_to.as_str()).collect::<Vec<_>>(), ["shop"]);
+        assert_eq!((identity.consent.len(), identity.archived_consent.len()), (1, 1));
+        assert!(identity.archive_expired_consent(later).unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_keeps_a_chained_log_of_consent() {
+        let (mut identity, _) = Identity::new("Alice", "Shops online.").unwrap();
+        let now = Utc::now();
+        identity.grant_consent("shop", &["core".to_string()], &ConsentPurpose::ServiceDelivery, now + Duration::days(30)).unwrap();
+        identity.record_consent_access("shop", &ConsentPurpose::ServiceDelivery, &["core.name".to_string()], Utc::now()).unwrap();
+        assert!(identity.record_consent_access("shop", &ConsentPurpose::Marketing, &["core.name".to_string()], Utc::now()).is_err());
+        assert!(identity.record_consent_access("shop", &ConsentPurpose::ServiceDelivery, &["credentials".to_string()], Utc::now()).is_err());
+        identity.revoke_consent("shop", None).unwrap();
+        identity.archive_expired_consent(now + Duration::days(31)).unwrap();
+        let actions: Vec<ConsentAction> = identity.consent_log.iter().map(|entry| entry.action).collect();
+        assert_eq!(actions, [ConsentAction::Granted, ConsentAction::Accessed, ConsentAction::Revoked, ConsentAction::Expired]);
+        identity.verify_consent_log().unwrap();
+
+        // Dropping or rewriting an entry breaks the chain.
+        let mut rewritten = identity.clone();
+        rewritten.consent_log[1].fields = vec!["core.bio".to_string()];
+        assert!(rewritten.verify_consent_log().is_err());
+        identity.consent_log.remove(2);
+        assert!(identity.verify_consent_log().is_err());
+    }
+
+    #[test]
+    fn it_exports_only_what_consent_covers() {
+        let (mut identity, _) = Identity::new("Alice", "Private bio.").unwrap();
+        let next_year = Utc::now() + Duration::days(365);
+        identity.grant_consent("shop", &["core.name".to_string(), "core.address".to_string()], &ConsentPurpose::ServiceDelivery, next_year).unwrap();
+        identity.grant_consent("lab", &["core".to_string()], &ConsentPurpose::Research, next_year).unwrap();
+
+        // The shop sees the name only: the document has no address.
+        let view = identity.export_for("shop").unwrap();
+        assert_eq!(view["core"].as_mapping().unwrap().len(), 1);
+        assert_eq!(view["core"]["name"], YamlValue::from("Alice"));
+        assert!(view.get("consent").is_none());
+        assert!(identity.export_for_fields("shop", &["core.bio"]).is_err());
+        assert!(identity.export_for_fields("lab", &["core.bio"]).is_ok());
+
+        // Nothing for anyone else, or once revoked.
+        assert!(identity.export_for("bank").is_err());
+        identity.revoke_consent("shop", None).unwrap();
+        assert!(identity.export_for("shop").is_err());
+    }
+
+    #[test]
+    fn it_decides_access_by_consent() {
+        let (mut identity, _) = Identity::new("Alice", "Shops online.").unwrap();
+        let now = Utc::now();
+        identity.grant_consent("shop", &["core".to_string()], &ConsentPurpose::ServiceDelivery, now + Duration::days(30)).unwrap();
+        identity.grant_consent("shop", &["core.name".to_string()], &ConsentPurpose::Marketing, now + Duration::days(30)).unwrap();
+        identity.revoke_consent("shop", Some(&ConsentPurpose::Marketing)).unwrap();
+        let later = Utc::now();
+
+        let allowed = identity.is_access_allowed("shop", "core.name", &ConsentPurpose::ServiceDelivery, later);
+        assert_eq!(allowed, Decision::Allow(&identity.consent[0]));
+        let deny = |requester: &str, field: &str, purpose: ConsentPurpose, at: DateTime<Utc>| match identity.is_access_allowed(requester, field, &purpose, at) {
+            Decision::Deny(reason, _) => reason,
+            Decision::Allow(_) => panic!("{} may use {}", requester, field),
+        };
+        assert_eq!(deny("shop", "core.name", ConsentPurpose::Marketing, later), DenyReason::Revoked);
+        assert_eq!(deny("shop", "core.name", ConsentPurpose::ServiceDelivery, now + Duration::days(31)), DenyReason::Expired);
+        assert_eq!(deny("shop", "credentials[0]", ConsentPurpose::ServiceDelivery, later), DenyReason::FieldNotCovered);
+        assert_eq!(deny("shop", "core.name", ConsentPurpose::Research, later), DenyReason::OtherPurpose);
+        assert_eq!(deny("bank", "core.name", ConsentPurpose::ServiceDelivery, later), DenyReason::NoConsent);
+    }
+
+    #[test]
+    fn it_signs_consent_receipts() {
+        let (mut alice, alice_key) = Identity::new("Alice", "Shops online.").unwrap();
+        let (bob, _) = Identity::new("Bob", "Runs a shop.").unwrap();
+        let shop = bob.identity.id.to_string();
+        alice.grant_consent(&shop, &["core.name".to_string()], &ConsentPurpose::ServiceDelivery, Utc::now() + Duration::days(30)).unwrap();
+        let receipt = alice.consent_receipt(&shop, &ConsentPurpose::ServiceDelivery, &alice_key).unwrap();
+        assert!(alice.consent_receipt(&shop, &ConsentPurpose::Marketing, &alice_key).is_err());
+
+        // It holds against Alice's document until it expires, and not against Bob's.
+        receipt.verify(&alice, Utc::now()).unwrap();
+        assert!(receipt.verify(&bob, Utc::now()).is_err());
+        assert!(receipt.verify(&alice, Utc::now() + Duration::days(31)).is_err());
+        let mut widened = receipt.clone();
+        widened.fields.push("core.address".to_string());
+        assert!(widened.verify(&alice, Utc::now()).is_err());
+
+        // Once she revokes it, her document says so.
+        alice.revoke_consent(&shop, None).unwrap();
+        assert!(receipt.verify(&alice, Utc::now()).is_err());
+    }
+}
@@ -0,0 +1,213 @@
+// crates/idp-core/src/consequence.rs
+
+// Consequences that act. A contract's `on_success` and `on_failure` usually say in words what
+// follows from its outcome; either may instead name a hook: a webhook (`https://...`) that is
+// sent the signed settlement event as JSON, or a local command (`exec:...`) that reads it on
+// stdin. A command comes from a contract another party may have written, so it only runs when
+// the party settling the contract allows it.
+
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::contract::{check_signed, contract_statement};
+use crate::signer::SigningBackend;
+use crate::{canonical, AnnotationKind, ContractStatus, Identity, IdpError, SignatureComponent, Signer};
+
+/// The `type` of a settlement event.
+pub const SETTLEMENT_EVENT: &str = "idp-contract-settled";
+
+/// How a contract ended.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Outcome::Success => "success",
+            Outcome::Failure => "failure",
+        })
+    }
+}
+
+/// What a consequence does when it is fired.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hook {
+    /// POST the event, as JSON, to this URL.
+    Webhook(String),
+    /// Run this command with `sh -c`, the event as JSON on its stdin.
+    Command(String),
+}
+
+impl Hook {
+    /// The hook a consequence names, if it names one rather than describing the outcome in words.
+    pub fn parse(consequence: &str) -> Option<Hook> {
+        let consequence = consequence.trim();
+        if let Some(command) = consequence.strip_prefix("exec:") {
+            return Some(Hook::Command(command.trim().to_string()));
+        }
+        (consequence.starts_with("https://") || consequence.starts_with("http://")).then(|| Hook::Webhook(consequence.to_string()))
+    }
+}
+
+/// The signed record of a contract's settlement, which its consequence's hook is sent.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SettlementEvent {
+    #[serde(rename = "type")]
+    pub event_type: String,
+    pub contract_id: String,
+    /// The multihash of the contract's canonical terms.
+    pub terms: String,
+    pub outcome: Outcome,
+    pub status: ContractStatus,
+    /// The consequence that follows, as the contract gives it.
+    pub consequence: String,
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signature: Vec<SignatureComponent>,
+}
+
+impl SettlementEvent {
+    /// The canonical event without its signature, which the settling party signs.
+    pub fn statement(&self) -> Vec<u8> {
+        let mut event = serde_json::to_value(self).unwrap_or(Value::Null);
+        if let Value::Object(fields) = &mut event {
+            fields.remove("signature");
+        }
+        canonical::canonicalize(&event)
+    }
+
+    /// Checks the event against the identity of the party that settled the contract.
+    pub fn verify(&self, party: &Identity) -> Result<(), IdpError> {
+        if self.event_type != SETTLEMENT_EVENT {
+            return Err(IdpError::Contract(format!("'{}' is not a settlement event", self.event_type)));
+        }
+        check_signed(party, &self.signed_by, self.signed_at, &self.statement(), &self.signature)
+    }
+}
+
+impl Identity {
+    /// Settles the active contract `contract_id` as one of its parties: it becomes fulfilled on
+    /// success or breached on failure, with a signed settlement note, and the signed event for
+    /// its consequence's hook is returned.
+    pub fn settle_contract(&mut self, contract_id: &str, outcome: Outcome, signer: &dyn SigningBackend) -> Result<SettlementEvent, IdpError> {
+        let key_id = self.issuing_key(signer)?.key_id.clone();
+        let note = self.annotate_contract(contract_id, AnnotationKind::Settlement, &outcome.to_string(), None, signer)?.clone();
+        let contract = self.contracts.iter().find(|contract| contract.contract_id == contract_id).ok_or_else(|| IdpError::Contract(format!("there is no contract '{}'", contract_id)))?;
+
+        let mut event = SettlementEvent {
+            event_type: SETTLEMENT_EVENT.to_string(),
+            contract_id: contract_id.to_string(),
+            terms: canonical::multihash(&contract_statement(contract)),
+            outcome,
+            status: contract.status.clone(),
+            consequence: match outcome {
+                Outcome::Success => contract.consequence.on_success.clone(),
+                Outcome::Failure => contract.consequence.on_failure.clone(),
+            },
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id },
+            signed_at: note.signed_at,
+            signature: vec![],
+        };
+        event.signature.push(signer.sign(&event.statement())?);
+        Ok(event)
+    }
+}
+
+/// Fires `hook` with `event`. A command only runs if `allow_commands` is set.
+pub fn fire(hook: &Hook, event: &SettlementEvent, allow_commands: bool) -> Result<(), IdpError> {
+    let body = serde_json::to_vec(event)?;
+    match hook {
+        Hook::Webhook(url) => post(url, &body),
+        Hook::Command(command) if !allow_commands => Err(IdpError::Contract(format!("the command `{}` was not run; commands from a contract only run when allowed", command))),
+        Hook::Command(command) => {
+            let mut child = Command::new("sh")
+                .args(["-c", command])
+                .env("IDP_CONTRACT_ID", &event.contract_id)
+                .env("IDP_OUTCOME", event.outcome.to_string())
+                .stdin(Stdio::piped())
+                .spawn()
+                .map_err(|e| IdpError::Contract(format!("cannot run `{}`: {}", command, e)))?;
+            child.stdin.take().expect("stdin is piped").write_all(&body)?;
+            match child.wait()? {
+                status if status.success() => Ok(()),
+                status => Err(IdpError::Contract(format!("`{}` failed ({})", command, status))),
+            }
+        }
+    }
+}
+
+/// POSTs an event to a webhook, over HTTP(S) with the `webhooks` feature.
+#[cfg(feature = "webhooks")]
+fn post(url: &str, body: &[u8]) -> Result<(), IdpError> {
+    ureq::post(url).set("Content-Type", "application/json").send_bytes(body).map_err(|e| IdpError::Contract(format!("the webhook {} failed: {}", url, e)))?;
+    Ok(())
+}
+
+#[cfg(not(feature = "webhooks"))]
+fn post(url: &str, _body: &[u8]) -> Result<(), IdpError> {
+    Err(IdpError::Contract(format!("cannot reach {}: this build has no webhook support (enable the `webhooks` feature)", url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Consequence, Contract};
+    use serde_json::json;
+
+    #[test]
+    fn it_settles_contracts_and_fires_their_hooks() {
+        let (mut alice, alice_key) = Identity::new("Alice", "Hires a painter.").unwrap();
+        let (mut bob, bob_key) = Identity::new("Bob", "Paints.").unwrap();
+        let out = tempfile::tempdir().unwrap();
+        let event_file = out.path().join("event.json");
+        let contract = Contract {
+            contract_id: "paint".to_string(),
+            status: ContractStatus::Draft,
+            parties: vec![alice.identity.id.to_string(), bob.identity.id.to_string()],
+            terms: "Bob paints the fence.".to_string(),
+            obligations: vec![],
+            consequence: Consequence {
+                on_success: format!("exec: cat > {}", event_file.display()),
+                on_failure: "https://example.com/refund".to_string(),
+                unknown_fields: Default::default(),
+            },
+            deadline: None,
+            review_at: None,
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+        alice.merge_contract(contract).unwrap();
+        let shared = alice.sign_contract("paint", &alice_key).unwrap().clone();
+        bob.merge_contract(shared).unwrap();
+        bob.sign_contract("paint", &bob_key).unwrap();
+
+        // Bob settles it as a success; Alice takes in his settlement, and the event checks out.
+        let event = bob.settle_contract("paint", Outcome::Success, &bob_key).unwrap();
+        assert_eq!((event.status.clone(), bob.contracts[0].status.clone()), (ContractStatus::Fulfilled, ContractStatus::Fulfilled));
+        alice.merge_contract(bob.contracts[0].clone()).unwrap();
+        assert_eq!(alice.contracts[0].status, ContractStatus::Fulfilled);
+        assert!(alice.contracts[0].verify(&vec![alice.clone(), bob.clone()]).unwrap().is_valid());
+        event.verify(&bob).unwrap();
+        assert!(event.verify(&alice).is_err());
+        assert!(bob.settle_contract("paint", Outcome::Failure, &bob_key).is_err(), "already settled");
+
+        // The command runs only when allowed, and reads the event.
+        let hook = Hook::parse(&event.consequence).unwrap();
+        assert!(fire(&hook, &event, false).is_err());
+        fire(&hook, &event, true).unwrap();
+        let fired: Value = serde_json::from_slice(&std::fs::read(&event_file).unwrap()).unwrap();
+        assert_eq!(fired["outcome"], json!("success"));
+        assert_eq!(Hook::parse("https://example.com/refund"), Some(Hook::Webhook("https://example.com/refund".to_string())));
+        assert_eq!(Hook::parse("the fee is refunded"), None);
+    }
+}
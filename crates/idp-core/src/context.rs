@@ -0,0 +1,94 @@
+// crates/idp-core/src/context.rs
+
+// Binding proofs to where they are used. A proof may carry a `domain` (who it is meant for) and
+// a `challenge` (a verifier's nonce); both are signed along with its statement, so a proof copied
+// to another domain, or replayed against another challenge, fails once the verifier says what it
+// expects. Proofs without them sign their statement alone, as before.
+
+use serde_json::{json, Value};
+
+use crate::{canonical, Proof};
+
+/// The domain and challenge a proof is bound to, or that a verifier expects.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ProofContext {
+    pub domain: Option<String>,
+    pub challenge: Option<String>,
+}
+
+impl ProofContext {
+    pub fn new(domain: Option<&str>, challenge: Option<&str>) -> ProofContext {
+        ProofContext { domain: domain.map(str::to_string), challenge: challenge.map(str::to_string) }
+    }
+
+    /// The context `proof` is bound to.
+    pub fn of(proof: &Proof) -> ProofContext {
+        ProofContext { domain: proof.domain.clone(), challenge: proof.challenge.clone() }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.domain.is_none() && self.challenge.is_none()
+    }
+
+    /// Records the context in `proof`.
+    pub fn apply(&self, proof: &mut Proof) {
+        proof.domain = self.domain.clone();
+        proof.challenge = self.challenge.clone();
+    }
+
+    /// The bytes signed for a canonical JSON `statement` under this context: the statement
+    /// itself without one, or else the statement with `domain` and `challenge` added.
+    pub fn bind(&self, statement: &[u8]) -> Vec<u8> {
+        let mut bound = match serde_json::from_slice::<Value>(statement) {
+            Ok(Value::Object(bound)) if !self.is_empty() => bound,
+            _ => return statement.to_vec(),
+        };
+        for (name, value) in [("domain", &self.domain), ("challenge", &self.challenge)] {
+            if let Some(value) = value {
+                bound.insert(name.to_string(), json!(value));
+            }
+        }
+        canonical::canonicalize(&Value::Object(bound))
+    }
+
+    /// Why `proof` is not bound to what this context expects, if it is not. Only the fields
+    /// given are required: an empty context accepts any proof.
+    pub fn mismatch(&self, proof: &Proof) -> Option<String> {
+        let differs = |expected: &Option<String>, actual: &Option<String>| expected.is_some() && expected != actual;
+        match (differs(&self.domain, &proof.domain), differs(&self.challenge, &proof.challenge)) {
+            (true, _) => Some(format!("the proof is for the domain {}, not {}", proof.domain.as_deref().unwrap_or("(none)"), self.domain.as_deref().unwrap_or_default())),
+            (_, true) => Some("the proof answers another challenge, or none".to_string()),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_binds_proofs_to_a_domain_and_challenge() {
+        let (issuer, issuer_key) = Identity::new("Venue", "Checks tickets.").unwrap();
+        let (mut holder, _) = Identity::new("Guest", "Has a ticket.").unwrap();
+        let context = ProofContext::new(Some("venue.example"), Some("c-1"));
+        holder.add_credential(issuer.issue_bound_credential(&holder, "ticket", None, &context, &issuer_key).unwrap()).unwrap();
+        let (credential, proof) = (&holder.credentials[0], holder.credential_proof(&holder.credentials[0]).unwrap());
+        assert_eq!(ProofContext::of(proof), context);
+        assert_eq!(proof.claim_hash, credential.compute_hash(&holder.identity.id));
+
+        // It holds for its own context, or when none is asked for, but not for another challenge.
+        let keys = &issuer;
+        assert!(holder.verify_credential(credential, proof, keys).is_valid());
+        assert!(holder.verify_credential_in(credential, proof, keys, &ProofContext::new(None, Some("c-1"))).is_valid());
+        assert!(!holder.verify_credential_in(credential, proof, keys, &ProofContext::new(None, Some("c-2"))).is_valid());
+        assert!(!holder.verify_credential_in(credential, proof, keys, &ProofContext::new(Some("elsewhere.example"), None)).is_valid());
+
+        // The context is signed: rewriting it breaks the issuer's signature.
+        let mut replayed = proof.clone();
+        ProofContext::new(Some("venue.example"), Some("c-2")).apply(&mut replayed);
+        let report = holder.verify_credential_in(credential, &replayed, keys, &ProofContext::new(None, Some("c-2")));
+        assert!(report.signature_error.is_some());
+    }
+}
@@ -0,0 +1,594 @@
+// crates/idp-core/src/contract.rs
+
+// Signing contracts. Every party signs the same canonical terms (the contract without its status
+// or signatures) with one of its signing keys, and keeps the signature in the contract's
+// `signatures`. Parties pass the partly signed contract around as a file; once all of them have
+// signed, a draft becomes active. Next to the terms in words, a contract may list what each party
+// owes as obligations. An active contract past its deadline is overdue, a sign that a party has
+// not kept its side of it. Parties may also sign notes on the contract, such as a dispute and its
+// resolution, without touching the terms. A signature holds if the key it names was active when
+// it was made, even if the party has rotated that key since.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::crypto::{self, VerifyError};
+use crate::keys::EffectiveStatus;
+use crate::signer::SigningBackend;
+use crate::trust::IdentityResolver;
+use crate::{canonical, AnnotationKind, Contract, ContractAnnotation, ContractSignature, ContractStatus, Identity, IdpError, KeyPurpose, SignatureComponent, Signer};
+
+// The fields of a contract that change as it is signed and carried out, and so are not signed.
+const UNSIGNED_FIELDS: [&str; 3] = ["status", "signatures", "annotations"];
+
+/// The canonical terms of `contract` that every party signs.
+pub fn contract_statement(contract: &Contract) -> Vec<u8> {
+    let mut terms = match serde_json::to_value(contract) {
+        Ok(Value::Object(terms)) => terms,
+        _ => unreachable!("a contract serializes to an object"),
+    };
+    for field in UNSIGNED_FIELDS {
+        terms.remove(field);
+    }
+    terms.insert("type".to_string(), json!("idp-contract"));
+    canonical::canonicalize(&Value::Object(terms))
+}
+
+/// Builds the statement `signed_by` signs for `contract` at `signed_at`.
+pub fn signature_statement(contract: &Contract, signed_by: &Signer, signed_at: &DateTime<Utc>) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-contract-signature",
+        "terms": canonical::multihash(&contract_statement(contract)),
+        "party": signed_by.idp_id,
+        "key_id": signed_by.key_id,
+        "signed_at": signed_at,
+    }))
+}
+
+/// Builds the statement a party signs for a note on `contract`.
+pub fn annotation_statement(contract: &Contract, annotation: &ContractAnnotation) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-contract-annotation",
+        "terms": canonical::multihash(&contract_statement(contract)),
+        "kind": annotation.kind,
+        "text": annotation.text,
+        "reference": annotation.reference,
+        "party": annotation.signed_by.idp_id,
+        "key_id": annotation.signed_by.key_id,
+        "signed_at": annotation.signed_at,
+    }))
+}
+
+// The status of `party`'s key `key_id` when it signed at `signed_at`: a key rotated out since
+// was still active before its rotation.
+pub(crate) fn status_when_signed(party: &Identity, key_id: &str, signed_at: DateTime<Utc>) -> Result<EffectiveStatus, IdpError> {
+    let rotated_at = party.system.rotations.iter().find(|rotation| rotation.old_key_id == key_id).map(|rotation| rotation.rotated_at);
+    Ok(match party.key_status_at(key_id, signed_at)? {
+        EffectiveStatus::Superseded if rotated_at.is_some_and(|rotated_at| signed_at < rotated_at) => EffectiveStatus::Active,
+        status => status,
+    })
+}
+
+// Checks a signature `party` made at `signed_at` over `statement`: by one of its signing keys,
+// active then, even if it has been rotated out since.
+pub(crate) fn check_signed(party: &Identity, signed_by: &Signer, signed_at: DateTime<Utc>, statement: &[u8], signature: &[SignatureComponent]) -> Result<(), IdpError> {
+    let key_id = &signed_by.key_id;
+    if signed_by.idp_id != party.identity.id {
+        return Err(IdpError::Contract(format!("signed by {}, not {}", signed_by.idp_id, party.identity.id)));
+    }
+    if signed_at > Utc::now() {
+        return Err(IdpError::Contract(format!("the signature of {} is dated in the future", party.identity.id)));
+    }
+
+    // 1. The key, as it stood when it signed.
+    let key = party.find_key(key_id).ok_or_else(|| VerifyError::UnknownKey(key_id.clone()))?;
+    let status = status_when_signed(party, key_id, signed_at)?;
+    if status != EffectiveStatus::Active {
+        return Err(VerifyError::KeyNotActive { key_id: key_id.clone(), status: status.to_string() }.into());
+    }
+    if key.purpose != KeyPurpose::Signing {
+        return Err(VerifyError::WrongPurpose { key_id: key_id.clone(), purpose: key.purpose, required: KeyPurpose::Signing.to_string() }.into());
+    }
+
+    // 2. The signature over the statement.
+    let component = signature.first().ok_or_else(|| IdpError::Contract(format!("the signature of {} is empty", party.identity.id)))?;
+    Ok(crypto::verify(key, statement, component)?)
+}
+
+/// Something about a contract a party should act on.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(tag = "alert", content = "detail", rename_all = "snake_case")]
+pub enum ContractAlert {
+    /// The draft waits for this party's signature.
+    AwaitingYourSignature,
+    /// This party has signed the draft; these others have not.
+    AwaitingOthers(Vec<String>),
+    /// The contract is active and its deadline has passed.
+    Overdue(DateTime<Utc>),
+    /// The parties agreed to look at the contract again by now.
+    ReviewDue(DateTime<Utc>),
+    /// A party has raised a dispute that no resolution has followed yet.
+    Disputed(String),
+}
+
+/// One party's signature, as `Contract::verify` found it.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PartyCheck {
+    pub party: String,
+    /// The key it signed with, and when; `None` if it has not signed.
+    pub signed: Option<(String, DateTime<Utc>)>,
+    /// Why its signature does not hold, if it does not.
+    pub error: Option<String>,
+}
+
+/// The outcome of `Contract::verify`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ContractReport {
+    pub contract_id: String,
+    pub status: ContractStatus,
+    pub parties: Vec<PartyCheck>,
+    /// Identities that signed without being parties.
+    pub strangers: Vec<String>,
+    pub obligation_problems: Vec<String>,
+    /// Why the status does not fit the signatures, if it does not.
+    pub status_error: Option<String>,
+    /// Why each note that does not hold does not.
+    pub annotation_errors: Vec<String>,
+}
+
+impl ContractReport {
+    /// Whether every party has signed, and every signature holds.
+    pub fn is_fully_signed(&self) -> bool {
+        self.parties.iter().all(|check| check.signed.is_some() && check.error.is_none())
+    }
+
+    /// Everything that does not hold; unsigned parties of a draft are not a problem.
+    pub fn problems(&self) -> Vec<String> {
+        let signatures = self.parties.iter().filter_map(|check| check.error.as_ref().map(|error| format!("the signature of {}: {}", check.party, error)));
+        let strangers = self.strangers.iter().map(|stranger| format!("{} signed, but is not a party", stranger));
+        let rest = self.obligation_problems.iter().cloned().chain(self.status_error.clone()).chain(self.annotation_errors.iter().cloned());
+        signatures.chain(strangers).chain(rest).collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.problems().is_empty()
+    }
+}
+
+impl Contract {
+    /// The signature of `party`, if it has signed.
+    pub fn signature_of(&self, party: &str) -> Option<&ContractSignature> {
+        self.signatures.iter().find(|signature| signature.signed_by.idp_id.as_str() == party)
+    }
+
+    /// The parties that have yet to sign.
+    pub fn unsigned_parties(&self) -> Vec<&str> {
+        self.parties.iter().map(String::as_str).filter(|party| self.signature_of(party).is_none()).collect()
+    }
+
+    /// Whether every party has signed.
+    pub fn is_fully_signed(&self) -> bool {
+        !self.parties.is_empty() && self.unsigned_parties().is_empty()
+    }
+
+    /// What is wrong with the obligations, by index: each must name a listed party, an amount
+    /// must be a decimal number, and nothing may fall due after the contract's deadline.
+    pub fn obligation_problems(&self) -> Vec<(usize, String)> {
+        let mut problems = Vec::new();
+        for (i, obligation) in self.obligations.iter().enumerate() {
+            if !self.parties.contains(&obligation.party) {
+                problems.push((i, format!("'{}' is owed by {}, who is not a party", obligation.action, obligation.party)));
+            }
+            if let Some(amount) = obligation.amount.as_ref().filter(|amount| !is_decimal(&amount.value)) {
+                problems.push((i, format!("the amount of '{}' is not a decimal number: {}", obligation.action, amount.value)));
+            }
+            if let (Some(due), Some(deadline)) = (obligation.due, self.deadline)
+                && due > deadline
+            {
+                problems.push((i, format!("'{}' falls due after the contract's deadline", obligation.action)));
+            }
+        }
+        problems
+    }
+
+    // The terms as they can be signed: without problems in the obligations.
+    fn check_terms(&self) -> Result<(), IdpError> {
+        match self.obligation_problems().into_iter().map(|(_, problem)| problem).collect::<Vec<_>>() {
+            problems if problems.is_empty() => Ok(()),
+            problems => Err(IdpError::Contract(format!("the terms of '{}' do not hold: {}", self.contract_id, problems.join("; ")))),
+        }
+    }
+
+    /// Whether the contract is active and past its deadline at `now`.
+    pub fn is_overdue(&self, now: DateTime<Utc>) -> bool {
+        self.status == ContractStatus::Active && self.deadline.is_some_and(|deadline| deadline < now)
+    }
+
+    /// The latest dispute, if no resolution has been noted since.
+    pub fn open_dispute(&self) -> Option<&ContractAnnotation> {
+        let latest = |kind| self.annotations.iter().filter(|annotation| annotation.kind == kind).max_by_key(|annotation| annotation.signed_at);
+        match (latest(AnnotationKind::Dispute), latest(AnnotationKind::Resolution)) {
+            (Some(dispute), Some(resolution)) if resolution.signed_at >= dispute.signed_at => None,
+            (dispute, _) => dispute,
+        }
+    }
+
+    /// What `party` should act on in this contract at `now`; nothing once it is settled.
+    pub fn evaluate(&self, party: &str, now: DateTime<Utc>) -> Vec<ContractAlert> {
+        let mut alerts = Vec::new();
+        match self.status {
+            ContractStatus::Draft if self.signature_of(party).is_none() => alerts.push(ContractAlert::AwaitingYourSignature),
+            ContractStatus::Draft => alerts.push(ContractAlert::AwaitingOthers(self.unsigned_parties().into_iter().map(str::to_string).collect())),
+            ContractStatus::Active => {}
+            _ => return alerts,
+        }
+        if let Some(deadline) = self.deadline.filter(|_| self.is_overdue(now)) {
+            alerts.push(ContractAlert::Overdue(deadline));
+        }
+        if let Some(review_at) = self.review_at.filter(|review_at| *review_at <= now) {
+            alerts.push(ContractAlert::ReviewDue(review_at));
+        }
+        if let Some(dispute) = self.open_dispute() {
+            alerts.push(ContractAlert::Disputed(dispute.signed_by.idp_id.to_string()));
+        }
+        alerts
+    }
+
+    /// Checks `party`'s signature on these terms against its identity: it must be made by one of
+    /// its signing keys, which was active when it signed.
+    pub fn verify_signature(&self, party: &Identity) -> Result<(), IdpError> {
+        let signature = self.signature_of(party.identity.id.as_str()).ok_or_else(|| IdpError::Contract(format!("{} has not signed '{}'", party.identity.id, self.contract_id)))?;
+        let statement = signature_statement(self, &signature.signed_by, &signature.signed_at);
+        check_signed(party, &signature.signed_by, signature.signed_at, &statement, &signature.signature)
+    }
+
+    /// Checks a note on this contract against the identity of the party that signed it.
+    pub fn verify_annotation(&self, annotation: &ContractAnnotation, party: &Identity) -> Result<(), IdpError> {
+        if !self.parties.iter().any(|id| id == party.identity.id.as_str()) {
+            return Err(IdpError::Contract(format!("{} is not a party to '{}'", party.identity.id, self.contract_id)));
+        }
+        check_signed(party, &annotation.signed_by, annotation.signed_at, &annotation_statement(self, annotation), &annotation.signature)
+    }
+
+    /// Checks the whole contract: every party's signature against its identity from `parties`,
+    /// the obligations, and that the status fits the signatures.
+    pub fn verify(&self, parties: &dyn IdentityResolver) -> Result<ContractReport, IdpError> {
+        // 1. Each party's signature, if it has signed.
+        let mut checks = Vec::new();
+        for party in &self.parties {
+            let signed = self.signature_of(party).map(|signature| (signature.signed_by.key_id.clone(), signature.signed_at));
+            let error = match (&signed, parties.resolve(party)?) {
+                (None, _) => None,
+                (Some(_), None) => Some("the identity of the party could not be found".to_string()),
+                (Some(_), Some(identity)) => self.verify_signature(&identity).err().map(|e| e.to_string()),
+            };
+            checks.push(PartyCheck { party: party.clone(), signed, error });
+        }
+        let strangers = self.signatures.iter().map(|signature| signature.signed_by.idp_id.to_string()).filter(|signer| !self.parties.contains(signer)).collect();
+
+        // 2. Each note, by the party that signed it.
+        let mut annotation_errors = Vec::new();
+        for annotation in &self.annotations {
+            let error = match parties.resolve(annotation.signed_by.idp_id.as_str())? {
+                Some(party) => self.verify_annotation(annotation, &party).err().map(|e| e.to_string()),
+                None => Some("the identity of the party could not be found".to_string()),
+            };
+            if let Some(error) = error {
+                annotation_errors.push(format!("the {} by {}: {}", annotation.kind, annotation.signed_by.idp_id, error));
+            }
+        }
+
+        // 3. The status the signatures allow: in force only once every party has signed.
+        let mut report = ContractReport {
+            contract_id: self.contract_id.clone(),
+            status: self.status.clone(),
+            parties: checks,
+            strangers,
+            obligation_problems: self.obligation_problems().into_iter().map(|(_, problem)| problem).collect(),
+            status_error: None,
+            annotation_errors,
+        };
+        report.status_error = match &self.status {
+            ContractStatus::Draft if report.is_fully_signed() => Some("every party has signed, but it is still a draft".to_string()),
+            ContractStatus::Active | ContractStatus::Fulfilled | ContractStatus::Breached if !report.is_fully_signed() => {
+                Some(format!("it is {}, but not every party's signature holds", self.status))
+            }
+            ContractStatus::Fulfilled | ContractStatus::Breached if self.settled_status().as_ref() != Some(&self.status) => {
+                Some(format!("it is {}, but no party has signed a settlement saying so", self.status))
+            }
+            ContractStatus::Other(status) => Some(format!("the status '{}' is unknown", status)),
+            _ => None,
+        };
+        Ok(report)
+    }
+
+    // The status the latest signed settlement gives the contract, if a party has settled it.
+    fn settled_status(&self) -> Option<ContractStatus> {
+        let settlement = self.annotations.iter().filter(|annotation| annotation.kind == AnnotationKind::Settlement).max_by_key(|annotation| annotation.signed_at)?;
+        match settlement.text.as_str() {
+            "success" => Some(ContractStatus::Fulfilled),
+            "failure" => Some(ContractStatus::Breached),
+            _ => None,
+        }
+    }
+
+    // A draft comes into force once every party has signed it, and an active contract is
+    // settled once a party has signed its settlement.
+    fn update_status(&mut self) {
+        if self.status == ContractStatus::Draft && self.is_fully_signed() {
+            self.status = ContractStatus::Active;
+        }
+        if let Some(status) = self.settled_status().filter(|_| self.status == ContractStatus::Active) {
+            self.status = status;
+        }
+    }
+}
+
+// Whether `value` is a plain decimal number, like `100` or `100.50`.
+fn is_decimal(value: &str) -> bool {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, "0"));
+    [whole, fraction].iter().all(|digits| !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
+impl Identity {
+    /// Signs the draft contract `contract_id` in this document as one of its parties, with
+    /// `signer`'s key of this identity. The contract becomes active if this was the last signature.
+    pub fn sign_contract(&mut self, contract_id: &str, signer: &dyn SigningBackend) -> Result<&Contract, IdpError> {
+        let key_id = self.issuing_key(signer)?.key_id.clone();
+        let id = self.identity.id.clone();
+        let index = self
+            .contracts
+            .iter()
+            .position(|contract| contract.contract_id == contract_id)
+            .ok_or_else(|| IdpError::Contract(format!("there is no contract '{}'", contract_id)))?;
+
+        // 1. Only a party signs, once, and only while the contract is a draft.
+        let contract = &self.contracts[index];
+        if !contract.parties.iter().any(|party| party == id.as_str()) {
+            return Err(IdpError::Contract(format!("{} is not a party to '{}'", id, contract_id)));
+        }
+        if contract.signature_of(id.as_str()).is_some() {
+            return Err(IdpError::Contract(format!("you have already signed '{}'", contract_id)));
+        }
+        if contract.status != ContractStatus::Draft {
+            return Err(IdpError::Contract(format!("'{}' is {}, not a draft", contract_id, contract.status)));
+        }
+        contract.check_terms()?;
+
+        // 2. The signature over the terms, and the contract in force once it is the last one.
+        let signed_by = Signer { idp_id: id, key_id };
+        let signed_at = Utc::now();
+        let signature = signer.sign(&signature_statement(contract, &signed_by, &signed_at))?;
+        let contract = &mut self.contracts[index];
+        contract.signatures.push(ContractSignature { signed_by, signed_at, signature: vec![signature], unknown_fields: Default::default() });
+        contract.update_status();
+        self.touch();
+        Ok(&self.contracts[index])
+    }
+
+    /// Adds a contract shared by another party, or the signatures it carries to the same contract
+    /// in this document. This identity must be a party, and the terms must be the same.
+    pub fn merge_contract(&mut self, shared: Contract) -> Result<&Contract, IdpError> {
+        if !shared.parties.iter().any(|party| party == self.identity.id.as_str()) {
+            return Err(IdpError::Contract(format!("{} is not a party to '{}'", self.identity.id, shared.contract_id)));
+        }
+        let signers = shared.signatures.iter().map(|signature| &signature.signed_by).chain(shared.annotations.iter().map(|annotation| &annotation.signed_by));
+        if let Some(stranger) = signers.into_iter().find(|signer| !shared.parties.iter().any(|party| party == signer.idp_id.as_str())) {
+            return Err(IdpError::Contract(format!("'{}' is signed by {}, who is not a party", shared.contract_id, stranger.idp_id)));
+        }
+        shared.check_terms()?;
+        let index = match self.contracts.iter().position(|contract| contract.contract_id == shared.contract_id) {
+            Some(index) => {
+                let contract = &mut self.contracts[index];
+                if contract_statement(contract) != contract_statement(&shared) {
+                    return Err(IdpError::Contract(format!("the terms of '{}' differ from the ones in this document", shared.contract_id)));
+                }
+                for signature in shared.signatures {
+                    if contract.signature_of(signature.signed_by.idp_id.as_str()).is_none() {
+                        contract.signatures.push(signature);
+                    }
+                }
+                for annotation in shared.annotations {
+                    if !contract.annotations.contains(&annotation) {
+                        contract.annotations.push(annotation);
+                    }
+                }
+                contract.annotations.sort_by_key(|annotation| annotation.signed_at);
+                index
+            }
+            None => {
+                self.contracts.push(shared);
+                self.contracts.len() - 1
+            }
+        };
+        self.contracts[index].update_status();
+        self.touch();
+        Ok(&self.contracts[index])
+    }
+
+    /// Adds a signed note to the contract `contract_id` as one of its parties, e.g. to raise a
+    /// dispute, point to evidence, or record how a dispute was resolved. A settlement note, with
+    /// the text `success` or `failure`, settles an active contract.
+    pub fn annotate_contract(&mut self, contract_id: &str, kind: AnnotationKind, text: &str, reference: Option<&str>, signer: &dyn SigningBackend) -> Result<&ContractAnnotation, IdpError> {
+        let key_id = self.issuing_key(signer)?.key_id.clone();
+        let id = self.identity.id.clone();
+        let index = self
+            .contracts
+            .iter()
+            .position(|contract| contract.contract_id == contract_id)
+            .ok_or_else(|| IdpError::Contract(format!("there is no contract '{}'", contract_id)))?;
+        let contract = &mut self.contracts[index];
+        if !contract.parties.iter().any(|party| party == id.as_str()) {
+            return Err(IdpError::Contract(format!("{} is not a party to '{}'", id, contract_id)));
+        }
+        if kind == AnnotationKind::Settlement && (contract.status != ContractStatus::Active || !["success", "failure"].contains(&text)) {
+            return Err(IdpError::Contract(format!("only an active contract is settled, with success or failure; '{}' is {}", contract_id, contract.status)));
+        }
+
+        let mut annotation = ContractAnnotation {
+            kind,
+            text: text.to_string(),
+            reference: reference.map(str::to_string),
+            signed_by: Signer { idp_id: id, key_id },
+            signed_at: Utc::now(),
+            signature: vec![],
+            unknown_fields: Default::default(),
+        };
+        annotation.signature.push(signer.sign(&annotation_statement(contract, &annotation))?);
+        contract.annotations.push(annotation);
+        contract.update_status();
+        self.touch();
+        Ok(&self.contracts[index].annotations[self.contracts[index].annotations.len() - 1])
+    }
+
+    /// The contracts in this document that are active and past their deadline at `now`.
+    pub fn overdue_contracts(&self, now: DateTime<Utc>) -> Vec<&Contract> {
+        self.contracts.iter().filter(|contract| contract.is_overdue(now)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Amount, Consequence, Obligation};
+
+    #[test]
+    fn it_collects_every_partys_signature() {
+        let (mut alice, alice_key) = Identity::new("Alice", "Sells a bike.").unwrap();
+        let (mut bob, bob_key) = Identity::new("Bob", "Buys a bike.").unwrap();
+        let contract = Contract {
+            contract_id: "bike".to_string(),
+            status: ContractStatus::Draft,
+            parties: vec![alice.identity.id.to_string(), bob.identity.id.to_string()],
+            terms: "Alice sells Bob her bike for 100 EUR.".to_string(),
+            obligations: vec![
+                Obligation { party: alice.identity.id.to_string(), action: "hand over the bike".to_string(), due: None, amount: None, unknown_fields: Default::default() },
+                Obligation {
+                    party: bob.identity.id.to_string(),
+                    action: "pay".to_string(),
+                    due: None,
+                    amount: Some(Amount { value: "100.00".to_string(), currency: "EUR".to_string() }),
+                    unknown_fields: Default::default(),
+                },
+            ],
+            consequence: Consequence { on_success: "bike handed over".to_string(), on_failure: "refund".to_string(), unknown_fields: Default::default() },
+            deadline: None,
+            review_at: None,
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+
+        // Alice signs first; the contract stays a draft until Bob has signed his copy too.
+        alice.merge_contract(contract.clone()).unwrap();
+        let shared = alice.sign_contract("bike", &alice_key).unwrap().clone();
+        assert_eq!((shared.status.clone(), shared.unsigned_parties()), (ContractStatus::Draft, vec![bob.identity.id.as_str()]));
+        assert!(alice.sign_contract("bike", &alice_key).is_err(), "already signed");
+        let signed = bob.merge_contract(shared).unwrap().clone();
+        assert_eq!(signed.status, ContractStatus::Draft);
+        let signed = bob.sign_contract("bike", &bob_key).unwrap().clone();
+        assert!(signed.is_fully_signed());
+        assert_eq!(signed.status, ContractStatus::Active);
+        assert_eq!(alice.merge_contract(signed.clone()).unwrap().status, ContractStatus::Active);
+        signed.verify_signature(&alice).unwrap();
+        signed.verify_signature(&bob).unwrap();
+
+        // Changed terms neither merge nor keep the signatures valid, and strangers cannot sign.
+        let mut changed = signed.clone();
+        changed.terms = "Alice gives Bob her bike.".to_string();
+        assert!(alice.merge_contract(changed.clone()).is_err());
+        assert!(changed.verify_signature(&alice).is_err());
+        let (mut carol, _) = Identity::new("Carol", "Not involved.").unwrap();
+        assert!(carol.merge_contract(contract.clone()).is_err());
+
+        // Every obligation is owed by a party, and its amount is a number.
+        let mut unfair = contract;
+        unfair.contract_id = "unfair".to_string();
+        unfair.obligations[0].party = carol.identity.id.to_string();
+        unfair.obligations[1].amount.as_mut().unwrap().value = "a hundred".to_string();
+        assert_eq!(unfair.obligation_problems().iter().map(|(i, _)| *i).collect::<Vec<_>>(), [0, 1]);
+        assert!(matches!(alice.merge_contract(unfair), Err(IdpError::Contract(_))));
+    }
+
+    #[test]
+    fn it_verifies_signatures_as_of_when_they_were_made() {
+        let (mut alice, alice_key) = Identity::new("Alice", "Lends money.").unwrap();
+        let (mut bob, bob_key) = Identity::new("Bob", "Borrows money.").unwrap();
+        let contract = Contract {
+            contract_id: "loan".to_string(),
+            status: ContractStatus::Draft,
+            parties: vec![alice.identity.id.to_string(), bob.identity.id.to_string()],
+            terms: "Alice lends Bob 50 EUR.".to_string(),
+            obligations: vec![],
+            consequence: Consequence { on_success: "repaid".to_string(), on_failure: "interest".to_string(), unknown_fields: Default::default() },
+            deadline: None,
+            review_at: None,
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+        alice.merge_contract(contract).unwrap();
+        let shared = alice.sign_contract("loan", &alice_key).unwrap().clone();
+        bob.merge_contract(shared).unwrap();
+        let signed = bob.sign_contract("loan", &bob_key).unwrap().clone();
+
+        // Both signatures hold, and still do after Alice rotates the key she signed with.
+        let alice_key = alice.rotate_key(&alice_key).unwrap();
+        let report = signed.verify(&vec![alice.clone(), bob.clone()]).unwrap();
+        assert!(report.is_valid() && report.is_fully_signed(), "{:?}", report.problems());
+
+        // An unknown party, or an active contract missing a signature, does not hold.
+        let report = signed.verify(&vec![alice.clone()]).unwrap();
+        assert_eq!(report.problems().len(), 2, "{:?}", report.problems());
+        let mut half_signed = signed.clone();
+        half_signed.signatures.pop();
+        assert!(half_signed.verify(&vec![alice.clone(), bob.clone()]).unwrap().status_error.is_some());
+
+        // Bob disputes the loan; the note is signed apart from the terms, which still hold.
+        let dispute = bob.annotate_contract("loan", AnnotationKind::Dispute, "The money never arrived.", None, &bob_key).unwrap().clone();
+        let disputed = bob.contracts[0].clone();
+        assert_eq!(disputed.open_dispute(), Some(&dispute));
+        assert!(disputed.verify(&vec![alice.clone(), bob.clone()]).unwrap().is_valid());
+        assert!(disputed.verify_annotation(&dispute, &alice).is_err(), "signed by Bob");
+        let mut edited = disputed.clone();
+        edited.annotations[0].text = "All is well.".to_string();
+        assert_eq!(edited.verify(&vec![alice.clone(), bob.clone()]).unwrap().annotation_errors.len(), 1);
+
+        // Alice takes Bob's note in, and resolves the dispute with her own.
+        alice.merge_contract(disputed).unwrap();
+        alice.annotate_contract("loan", AnnotationKind::Resolution, "Paid again.", Some("bank transfer 1234"), &alice_key).unwrap();
+        assert_eq!(alice.contracts[0].open_dispute(), None);
+    }
+
+    #[test]
+    fn it_flags_overdue_contracts() {
+        let now = Utc::now();
+        let (alice, bob) = ("idp:alice".to_string(), "idp:bob".to_string());
+        let mut contract = Contract {
+            contract_id: "repair".to_string(),
+            status: ContractStatus::Draft,
+            parties: vec![alice.clone(), bob.clone()],
+            terms: "Bob repairs the roof.".to_string(),
+            obligations: vec![],
+            consequence: Consequence { on_success: "pay".to_string(), on_failure: "refund".to_string(), unknown_fields: Default::default() },
+            deadline: Some(now - chrono::Duration::days(1)),
+            review_at: Some(now + chrono::Duration::days(1)),
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+
+        // A draft is never overdue; it waits for signatures.
+        assert_eq!(contract.evaluate(&alice, now), [ContractAlert::AwaitingYourSignature]);
+        assert!(!contract.is_overdue(now));
+
+        // Once active, the passed deadline counts, and so does the review date when it comes.
+        contract.status = ContractStatus::Active;
+        assert_eq!(contract.evaluate(&bob, now), [ContractAlert::Overdue(contract.deadline.unwrap())]);
+        let later = now + chrono::Duration::days(2);
+        assert_eq!(contract.evaluate(&bob, later).len(), 2);
+        contract.status = ContractStatus::Fulfilled;
+        assert!(contract.evaluate(&bob, later).is_empty());
+    }
+}
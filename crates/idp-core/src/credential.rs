@@ -0,0 +1,416 @@
+// crates/idp-core/src/credential.rs
+
+// Credentials one identity issues to another. The issuer signs a statement binding the claim to
+// the subject's id and the validity period; the credential and its proof are handed to the
+// subject, who keeps them in their own document. Anyone with the issuer's keys can check them.
+
+use chrono::{DateTime, Utc};
+use data_encoding::HEXLOWER;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::context::ProofContext;
+use crate::contract::status_when_signed;
+use crate::crypto::{self, VerifyError};
+use crate::keys::EffectiveStatus;
+use crate::signer::SigningBackend;
+use crate::tsa::{self, Timestamp};
+use crate::{bbs, canonical, jwt, vc, Credential, CredentialStatus, Identity, IdpError, IdpId, KeyPurpose, Proof, PublicKey, SignatureComponent, Signer};
+
+/// The `Proof.proof_type` of a credential issued by an identity.
+pub const CREDENTIAL_PROOF: &str = "CredentialIssuance";
+
+/// A credential together with the issuer's proof, as handed to the subject.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IssuedCredential {
+    pub credential: Credential,
+    pub proof: Proof,
+}
+
+/// What checking a credential against its issuer's keys found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CredentialReport {
+    pub claim: String,
+    pub issued_by: String,
+    pub key_id: String,
+    /// The proof is this credential's, and its hash matches the statement rebuilt for this holder.
+    pub proof_matches: bool,
+    /// The issuer's key as it stood when the credential was issued; `None` if it is not among the keys given.
+    pub key_status: Option<EffectiveStatus>,
+    /// Why the issuer's signature does not verify, if it does not.
+    pub signature_error: Option<VerifyError>,
+    /// Why the proof is not bound to the domain or challenge the verifier expects, if it is not.
+    pub context_error: Option<String>,
+    pub expired: bool,
+    /// The proof's RFC 3161 timestamp, if it has one, or why it does not check out.
+    pub timestamp: Option<Result<Timestamp, String>>,
+}
+
+impl CredentialReport {
+    /// Whether the credential holds: its proof matches, the issuer's key was usable and signed
+    /// it, and it has not expired. A key superseded since still vouches for what it signed.
+    pub fn is_valid(&self) -> bool {
+        self.problems().is_empty()
+    }
+
+    /// Every reason the credential does not hold, in words.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !self.proof_matches {
+            problems.push("the proof is for another credential or holder, or the credential was changed".to_string());
+        }
+        match self.key_status {
+            None => problems.push(format!("the issuer's key '{}' is unknown", self.key_id)),
+            Some(EffectiveStatus::Revoked) => problems.push(format!("the issuer's key '{}' had been revoked when it signed", self.key_id)),
+            Some(EffectiveStatus::Expired) => problems.push(format!("the issuer's key '{}' had expired when it signed", self.key_id)),
+            Some(_) => {}
+        }
+        if let Some(e) = &self.signature_error {
+            problems.push(format!("the issuer's signature does not verify: {}", e));
+        }
+        if let Some(e) = &self.context_error {
+            problems.push(e.clone());
+        }
+        if self.expired {
+            problems.push("the credential has expired".to_string());
+        }
+        if let Some(Err(e)) = &self.timestamp {
+            problems.push(format!("its timestamp does not verify: {}", e));
+        }
+        problems
+    }
+}
+
+// Whether `claim_hash` is the hash of `statement`: a multihash, or the bare Base64 SHA-256 that
+// credentials issued before claim hashes were multihashes carry.
+fn hash_matches(claim_hash: &str, statement: &[u8]) -> bool {
+    claim_hash == canonical::multihash(statement) || claim_hash == canonical::hash(statement)
+}
+
+impl Credential {
+    /// The claim hash of this credential held by `subject`, as its proof records it: the SHA-256
+    /// multihash (`canonical::multihash`) of the canonical JSON of the credential without its
+    /// proof, provenance or unknown fields, together with the subject's id. See
+    /// `credential_statement` for the exact fields.
+    pub fn compute_hash(&self, subject: &IdpId) -> String {
+        canonical::multihash(&credential_statement(subject, self))
+    }
+}
+
+/// Builds the statement an issuer signs for a credential. Verifiers rebuild it from the
+/// credential and the id of the document that holds it, so a copied credential does not verify.
+pub fn credential_statement(subject: &IdpId, credential: &Credential) -> Vec<u8> {
+    let mut statement = json!({
+        "type": "idp-credential",
+        "issuer": credential.issued_by,
+        "subject": subject,
+        "claim": credential.claim,
+        "issued_at": credential.issued_at.to_rfc3339(),
+        "expires_at": credential.expires_at.map(|expires_at| expires_at.to_rfc3339()),
+    });
+    // Only revocable credentials have a status, and it is signed like the rest.
+    if let Some(status) = &credential.status {
+        statement["status"] = json!(status);
+    }
+    canonical::canonicalize(&statement)
+}
+
+impl Identity {
+    /// Issues a credential for `claim` to `subject`, signed with `signer`'s key of this identity,
+    /// which must be an active signing key. The subject adds the result with `add_credential`.
+    pub fn issue_credential(
+        &self,
+        subject: &Identity,
+        claim: &str,
+        expires_at: Option<DateTime<Utc>>,
+        signer: &dyn SigningBackend,
+    ) -> Result<IssuedCredential, IdpError> {
+        self.issue(subject, claim, expires_at, signer, None, &ProofContext::default())
+    }
+
+    /// Issues a credential as `issue_credential` does, with its proof bound to `context`: the
+    /// domain it is meant for and the verifier's challenge are signed with it.
+    pub fn issue_bound_credential(
+        &self,
+        subject: &Identity,
+        claim: &str,
+        expires_at: Option<DateTime<Utc>>,
+        context: &ProofContext,
+        signer: &dyn SigningBackend,
+    ) -> Result<IssuedCredential, IdpError> {
+        self.issue(subject, claim, expires_at, signer, None, context)
+    }
+
+    /// Issues a credential as `issue_credential` does, with an entry in this identity's status
+    /// list so it can be revoked later (see `set_status`).
+    pub fn issue_revocable_credential(
+        &mut self,
+        subject: &Identity,
+        claim: &str,
+        expires_at: Option<DateTime<Utc>>,
+        signer: &dyn SigningBackend,
+    ) -> Result<IssuedCredential, IdpError> {
+        let status = self.allocate_status()?;
+        self.issue(subject, claim, expires_at, signer, Some(status), &ProofContext::default())
+    }
+
+    fn issue(
+        &self,
+        subject: &Identity,
+        claim: &str,
+        expires_at: Option<DateTime<Utc>>,
+        signer: &dyn SigningBackend,
+        status: Option<CredentialStatus>,
+        context: &ProofContext,
+    ) -> Result<IssuedCredential, IdpError> {
+        let key = self.issuing_key(signer)?;
+        let issued_at = Utc::now();
+        if expires_at.is_some_and(|expires_at| expires_at <= issued_at) {
+            return Err(IdpError::Credential("the credential would expire before it is issued".to_string()));
+        }
+
+        // 1. The statement names both identities, so the proof only holds in the subject's document.
+        let mut credential = Credential {
+            claim: claim.to_string(),
+            issued_by: self.identity.id.to_string(),
+            issued_at,
+            expires_at,
+            proof: String::new(),
+            provenance: None,
+            status,
+            unknown_fields: Default::default(),
+        };
+        let statement = context.bind(&credential_statement(&subject.identity.id, &credential));
+        let proof_id = format!("credential-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, &statement).as_ref()[..8]));
+        credential.proof = proof_id.clone();
+
+        // 2. The proof records the credential's hash and the issuer's signature over the statement.
+        let mut proof = Proof {
+            proof_id,
+            proof_type: CREDENTIAL_PROOF.to_string(),
+            claim_hash: credential.compute_hash(&subject.identity.id),
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: key.key_id.clone() },
+            signature: vec![signer.sign(&statement)?],
+            domain: context.domain.clone(),
+            challenge: context.challenge.clone(),
+            unknown_fields: Default::default(),
+        };
+
+        // 3. The key also signs the VC forms it can, so the holder can export them.
+        if key.algorithm == crypto::ED25519 {
+            let payload = vc::data_integrity_payload(&vc::unsecured_credential(&subject.identity.id, &credential), &vc::proof_options(&credential, &proof));
+            let signature = signer.sign(&payload)?;
+            proof.signature.push(SignatureComponent { algorithm: vc::EDDSA_JCS_2022.to_string(), value: signature.value });
+        }
+        if let Some((header, payload)) = vc::jwt_parts(&subject.identity.id, &credential, &proof) {
+            let signature = signer.sign(jwt::signing_input(&header, &payload).as_bytes())?;
+            proof.signature.push(SignatureComponent { algorithm: vc::JWT_FORMAT.to_string(), value: signature.value });
+        }
+        Ok(IssuedCredential { credential, proof })
+    }
+
+    // The key of this identity `signer` holds, which must be a signing key to issue credentials.
+    pub(crate) fn issuing_key(&self, signer: &dyn SigningBackend) -> Result<&PublicKey, IdpError> {
+        let key = self.key_for_private_key(signer)?;
+        if key.purpose != KeyPurpose::Signing {
+            return Err(VerifyError::WrongPurpose {
+                key_id: key.key_id.clone(),
+                purpose: key.purpose,
+                required: KeyPurpose::Signing.to_string(),
+            }
+            .into());
+        }
+        Ok(key)
+    }
+
+    /// Adds a credential issued to this identity, with its proof. The proof must be for this
+    /// credential and this identity; checking the issuer's signature needs the issuer's keys.
+    pub fn add_credential(&mut self, issued: IssuedCredential) -> Result<(), IdpError> {
+        let IssuedCredential { credential, proof } = issued;
+        let statement = match proof.proof_type.as_str() {
+            CREDENTIAL_PROOF => Some(credential_statement(&self.identity.id, &credential)),
+            bbs::BBS_PROOF => bbs::credential_statement(&self.identity.id, &credential).ok(),
+            _ => None,
+        };
+        let Some(statement) = statement.filter(|_| credential.proof == proof.proof_id && credential.issued_by == proof.signed_by.idp_id.to_string()) else {
+            return Err(IdpError::Credential("the proof does not belong to this credential".to_string()));
+        };
+        if !hash_matches(&proof.claim_hash, &statement) {
+            return Err(IdpError::Credential(format!("the credential was issued to another identity, or changed since ('{}')", credential.claim)));
+        }
+        if self.proofs.iter().any(|existing| existing.proof_id == proof.proof_id) {
+            return Err(IdpError::Credential(format!("the credential '{}' is already in the document", proof.proof_id)));
+        }
+        self.credentials.push(credential);
+        self.proofs.push(proof);
+        self.touch();
+        Ok(())
+    }
+
+    /// The credentials held that have expired by `now`.
+    pub fn expired_credentials(&self, now: DateTime<Utc>) -> Vec<&Credential> {
+        self.credentials.iter().filter(|credential| credential.is_expired(now)).collect()
+    }
+
+    /// Moves the credentials that have expired into `archived_credentials`, out of the way of
+    /// verifiers looking for claims. Their proofs stay, so they can still be checked. Returns
+    /// the credentials moved.
+    pub fn prune_expired(&mut self) -> &[Credential] {
+        let now = Utc::now();
+        let (expired, current): (Vec<Credential>, Vec<Credential>) = std::mem::take(&mut self.credentials).into_iter().partition(|credential| credential.is_expired(now));
+        self.credentials = current;
+        let archived = self.archived_credentials.len();
+        if !expired.is_empty() {
+            self.archived_credentials.extend(expired);
+            self.touch();
+        }
+        &self.archived_credentials[archived..]
+    }
+
+    /// The proof a credential in this document points to.
+    pub fn credential_proof(&self, credential: &Credential) -> Option<&Proof> {
+        self.proofs.iter().find(|proof| proof.proof_id == credential.proof)
+    }
+
+    /// Checks a credential held by this identity against the identity of its `issuer`: the claim
+    /// hash is recomputed, and the signature checked with the issuer's key, which must not have
+    /// been revoked since or expired when the credential was issued.
+    pub fn verify_credential(&self, credential: &Credential, proof: &Proof, issuer: &Identity) -> CredentialReport {
+        self.verify_credential_in(credential, proof, issuer, &ProofContext::default())
+    }
+
+    /// Checks a credential as `verify_credential` does, and that its proof is bound to the
+    /// domain and challenge `expected` gives.
+    pub fn verify_credential_in(&self, credential: &Credential, proof: &Proof, issuer: &Identity, expected: &ProofContext) -> CredentialReport {
+        // 1. The proof must be this credential's, for this holder.
+        let statement = match proof.proof_type.as_str() {
+            bbs::BBS_PROOF => bbs::credential_statement(&self.identity.id, credential).unwrap_or_default(),
+            _ => credential_statement(&self.identity.id, credential),
+        };
+        let proof_matches = [CREDENTIAL_PROOF, bbs::BBS_PROOF].contains(&proof.proof_type.as_str())
+            && credential.proof == proof.proof_id
+            && credential.issued_by == proof.signed_by.idp_id.to_string()
+            && hash_matches(&proof.claim_hash, &statement);
+
+        // 2. The issuer's key as it stood when it signed, and its signature over the rebuilt statement.
+        let key = Some(issuer).filter(|issuer| issuer.identity.id == proof.signed_by.idp_id).and_then(|issuer| issuer.find_key(&proof.signed_by.key_id));
+        let key_status = key.and_then(|key| status_when_signed(issuer, &key.key_id, credential.issued_at).ok());
+        let signature_error = match (key, proof.signature.first()) {
+            (Some(key), Some(_)) if proof.proof_type == bbs::BBS_PROOF => bbs::verify_credential_signature(&self.identity.id, credential, proof, key).err(),
+            (Some(key), Some(signature)) => crypto::verify(key, &ProofContext::of(proof).bind(&statement), signature).err(),
+            (Some(_), None) => Some(VerifyError::InvalidSignature),
+            (None, _) => None,
+        };
+
+        CredentialReport {
+            claim: credential.claim.clone(),
+            issued_by: credential.issued_by.clone(),
+            key_id: proof.signed_by.key_id.clone(),
+            proof_matches,
+            key_status,
+            signature_error,
+            context_error: expected.mismatch(proof),
+            expired: credential.is_expired(Utc::now()),
+            timestamp: tsa::proof_timestamp(proof).map(|timestamp| timestamp.map_err(|e| e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_issues_credentials_to_a_subject() {
+        let (issuer, issuer_key) = Identity::new("Issuer", "Vouches for people.").unwrap();
+        let (mut subject, _) = Identity::new("Subject", "Vouched for.").unwrap();
+        let (mut stranger, _) = Identity::new("Stranger", "Would like to be vouched for.").unwrap();
+        let expires_at = Utc::now() + chrono::Duration::days(365);
+        let issued = issuer.issue_credential(&subject, "member of the chess club", Some(expires_at), &issuer_key).unwrap();
+
+        // The issuer's signature covers the statement the proof hashes.
+        let statement = credential_statement(&subject.identity.id, &issued.credential);
+        assert_eq!(issued.proof.claim_hash, canonical::multihash(&statement));
+        assert_eq!(issued.proof.claim_hash, issued.credential.compute_hash(&subject.identity.id));
+        assert!(issued.proof.claim_hash.starts_with("zQm"));
+        crypto::verify(&issuer.system.public_keys[0], &statement, &issued.proof.signature[0]).unwrap();
+
+        // Only the subject can take it, and only once.
+        assert!(matches!(stranger.add_credential(issued.clone()), Err(IdpError::Credential(_))));
+        subject.add_credential(issued.clone()).unwrap();
+        assert_eq!(subject.credentials[0].claim, "member of the chess club");
+        let report = subject.verify_credential(&subject.credentials[0], &issued.proof, &issuer);
+        assert!(report.is_valid(), "{:?}", report.problems());
+        assert_eq!(subject.credential_proof(&subject.credentials[0]), Some(&issued.proof));
+        assert!(matches!(subject.add_credential(issued.clone()), Err(IdpError::Credential(_))));
+
+        // Proofs from before multihash claim hashes, with the bare Base64 hash, still verify.
+        let mut legacy = issued.proof.clone();
+        legacy.claim_hash = canonical::hash(&statement);
+        assert!(subject.verify_credential(&subject.credentials[0], &legacy, &issuer).is_valid());
+
+        // A changed claim no longer matches its proof.
+        let mut forged = issued.clone();
+        forged.credential.claim = "president of the chess club".to_string();
+        forged.proof.proof_id = "credential-forged".to_string();
+        forged.credential.proof = "credential-forged".to_string();
+        assert!(matches!(subject.add_credential(forged.clone()), Err(IdpError::Credential(_))));
+        assert!(!subject.verify_credential(&forged.credential, &forged.proof, &issuer).proof_matches);
+
+        // The issuer's key must be known and not revoked, and the credential current.
+        assert_eq!(subject.verify_credential(&subject.credentials[0], &issued.proof, &stranger).key_status, None);
+        let mut revoked = issuer.clone();
+        revoked.system.public_keys[0].status = crate::KeyStatus::Revoked;
+        assert!(!subject.verify_credential(&subject.credentials[0], &issued.proof, &revoked).is_valid());
+        let mut lapsed = subject.credentials[0].clone();
+        lapsed.expires_at = Some(Utc::now() - chrono::Duration::days(1));
+        let report = subject.verify_credential(&lapsed, &issued.proof, &issuer);
+        assert!(report.expired && !report.proof_matches);
+        assert!(issuer.issue_credential(&subject, "too late", Some(Utc::now()), &issuer_key).is_err());
+    }
+
+    #[test]
+    fn it_keeps_credentials_signed_before_their_key_was_revoked() {
+        let (mut issuer, issuer_key) = Identity::new("Issuer", "Retires a key.").unwrap();
+        let (mut subject, _) = Identity::new("Subject", "Vouched for.").unwrap();
+        let issued = issuer.issue_credential(&subject, "member of the chess club", None, &issuer_key).unwrap();
+        subject.add_credential(issued.clone()).unwrap();
+
+        // Revoked after it signed, the key still vouches for the credential.
+        issuer.revoke_key("root-key-01", &issuer_key, Some("retired")).unwrap();
+        let report = subject.verify_credential(&subject.credentials[0], &issued.proof, &issuer);
+        assert!(report.is_valid(), "{:?}", report.problems());
+
+        // A signed revocation dated before the credential was issued takes it down.
+        let revocation = &mut issuer.system.revocations[0];
+        revocation.revoked_at = issued.credential.issued_at - chrono::Duration::days(1);
+        let statement = crate::keys::revocation_statement(&issuer.identity.id, "root-key-01", &revocation.revoked_at, Some("retired"));
+        revocation.signature = vec![issuer_key.sign(&statement).unwrap()];
+        let report = subject.verify_credential(&subject.credentials[0], &issued.proof, &issuer);
+        assert_eq!(report.key_status, Some(EffectiveStatus::Revoked));
+        assert_eq!(report.problems(), ["the issuer's key 'root-key-01' had been revoked when it signed"]);
+    }
+
+    #[test]
+    fn it_archives_expired_credentials() {
+        let (issuer, issuer_key) = Identity::new("Issuer", "Vouches for a while.").unwrap();
+        let (mut subject, _) = Identity::new("Subject", "Vouched for.").unwrap();
+        for claim in ["day pass", "member"] {
+            let issued = issuer.issue_credential(&subject, claim, Some(Utc::now() + chrono::Duration::days(1)), &issuer_key).unwrap();
+            subject.add_credential(issued).unwrap();
+        }
+        let later = Utc::now() + chrono::Duration::days(2);
+        subject.credentials[1].expires_at = None;
+        assert_eq!(subject.expired_credentials(later).len(), 1);
+
+        // Once lapsed, it moves to the archive with its proof left in place.
+        subject.credentials[0].expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        let proofs = subject.proofs.len();
+        let archived = subject.prune_expired().to_vec();
+        assert_eq!(archived.iter().map(|credential| credential.claim.as_str()).collect::<Vec<_>>(), ["day pass"]);
+        assert_eq!((subject.credentials.len(), subject.archived_credentials.len(), subject.proofs.len()), (1, 1, proofs));
+        assert!(subject.prune_expired().is_empty());
+        subject.validate_schema().unwrap();
+    }
+}
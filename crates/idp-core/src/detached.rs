@@ -0,0 +1,149 @@
+// crates/idp-core/src/detached.rs
+
+// Detached signatures, for signing anything with an identity: a file, or a message typed on the
+// command line. The signature is kept apart from what it signs, in an ASCII-armored block whose
+// headers say who signed, with which key, when, and the SHA-256 multihash of the signed bytes.
+// The key signs those headers as canonical JSON, so none of them can be changed; a verifier
+// needs only the signed bytes, the block and the signer's identity file.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::contract::check_signed;
+use crate::signer::SigningBackend;
+use crate::{canonical, Identity, IdpError, IdpId, SignatureComponent, Signer};
+
+/// The `type` of the statement a detached signature signs.
+pub const DETACHED_SIGNATURE: &str = "idp-detached-signature";
+
+/// The label of the armored block.
+const BLOCK: &str = "IDP SIGNATURE";
+
+/// A signature over some bytes, kept apart from them.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DetachedSignature {
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    /// The multihash of the signed bytes.
+    pub digest: String,
+    /// What was signed, for people to read: a file name, or none for a message.
+    pub name: Option<String>,
+    pub signature: SignatureComponent,
+}
+
+impl DetachedSignature {
+    /// The canonical statement the key signs: everything but the signature.
+    pub fn statement(&self) -> Vec<u8> {
+        canonical::canonicalize(&json!({
+            "type": DETACHED_SIGNATURE,
+            "signed_by": self.signed_by,
+            "signed_at": self.signed_at,
+            "digest": self.digest,
+            "name": self.name,
+        }))
+    }
+
+    /// Checks that the signature is `signer`'s, over `content`.
+    pub fn verify(&self, content: &[u8], signer: &Identity) -> Result<(), IdpError> {
+        if self.signed_by.idp_id != signer.identity.id {
+            return Err(IdpError::Signature(format!("signed by {}, not {}", self.signed_by.idp_id, signer.identity.id)));
+        }
+        let digest = canonical::multihash(content);
+        if digest != self.digest {
+            return Err(IdpError::Signature(format!("it signs other content (digest {}, not {})", self.digest, digest)));
+        }
+        check_signed(signer, &self.signed_by, self.signed_at, &self.statement(), std::slice::from_ref(&self.signature))
+    }
+
+    /// The signature as an ASCII-armored block.
+    pub fn to_armor(&self) -> String {
+        let mut text = format!("-----BEGIN {}-----\n", BLOCK);
+        text.push_str(&format!("Idp-Id: {}\n", self.signed_by.idp_id));
+        text.push_str(&format!("Key-Id: {}\n", self.signed_by.key_id));
+        text.push_str(&format!("Signed-At: {}\n", self.signed_at.to_rfc3339_opts(SecondsFormat::AutoSi, true)));
+        text.push_str(&format!("Digest: {}\n", self.digest));
+        if let Some(name) = &self.name {
+            text.push_str(&format!("Name: {}\n", name));
+        }
+        text.push_str(&format!("Algorithm: {}\n\n", self.signature.algorithm));
+        for line in self.signature.value.as_bytes().chunks(64) {
+            text.push_str(std::str::from_utf8(line).expect("signature values are Base64"));
+            text.push('\n');
+        }
+        text.push_str(&format!("-----END {}-----\n", BLOCK));
+        text
+    }
+
+    /// Reads a signature from its armored block.
+    pub fn from_armor(text: &str) -> Result<DetachedSignature, IdpError> {
+        let malformed = |reason: &str| IdpError::Signature(format!("not an IDP signature block: {}", reason));
+        let mut lines = text.lines().map(str::trim_end).skip_while(|line| *line != format!("-----BEGIN {}-----", BLOCK));
+        lines.next().ok_or_else(|| malformed("no BEGIN line"))?;
+
+        // 1. The headers, up to the first blank line.
+        let mut header = std::collections::BTreeMap::new();
+        for line in lines.by_ref().take_while(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(": ").ok_or_else(|| malformed(&format!("'{}' is not a header", line)))?;
+            header.insert(name.to_string(), value.to_string());
+        }
+        let mut take = |name: &str| header.remove(name).ok_or_else(|| malformed(&format!("no {} header", name)));
+        let idp_id = IdpId::parse(&take("Idp-Id")?)?;
+        let key_id = take("Key-Id")?;
+        let signed_at = DateTime::parse_from_rfc3339(&take("Signed-At")?).map_err(|_| malformed("Signed-At is not an RFC 3339 time"))?.with_timezone(&Utc);
+        let digest = take("Digest")?;
+        let algorithm = take("Algorithm")?;
+        let name = header.remove("Name");
+
+        // 2. The signature, up to the END line.
+        let mut value = String::new();
+        for line in lines {
+            if line == format!("-----END {}-----", BLOCK) {
+                return Ok(DetachedSignature { signed_by: Signer { idp_id, key_id }, signed_at, digest, name, signature: SignatureComponent { algorithm, value } });
+            }
+            value.push_str(line.trim());
+        }
+        Err(malformed("no END line"))
+    }
+}
+
+impl Identity {
+    /// Signs `content` with `signer`'s key of this identity, naming it `name` if it has one.
+    pub fn sign_detached(&self, content: &[u8], name: Option<&str>, signer: &dyn SigningBackend) -> Result<DetachedSignature, IdpError> {
+        let mut signature = DetachedSignature {
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: self.issuing_key(signer)?.key_id.clone() },
+            signed_at: Utc::now(),
+            digest: canonical::multihash(content),
+            name: name.map(str::to_string),
+            signature: SignatureComponent { algorithm: String::new(), value: String::new() },
+        };
+        signature.signature = signer.sign(&signature.statement())?;
+        Ok(signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_signs_and_verifies_detached() {
+        let (alice, alice_key) = Identity::new("Alice", "Signs things.").unwrap();
+        let (bob, _) = Identity::new("Bob", "Checks things.").unwrap();
+        let signature = alice.sign_detached(b"the quarterly report", Some("report.pdf"), &alice_key).unwrap();
+
+        // The armored block carries everything a verifier needs besides the bytes.
+        let armored = signature.to_armor();
+        assert!(armored.starts_with("-----BEGIN IDP SIGNATURE-----\nIdp-Id: "));
+        let read = DetachedSignature::from_armor(&format!("Signed by Alice:\n\n{}", armored)).unwrap();
+        assert_eq!(read, signature);
+        read.verify(b"the quarterly report", &alice).unwrap();
+
+        // Other bytes, another signer, or a changed header do not verify.
+        assert!(matches!(read.verify(b"the quarterly report!", &alice), Err(IdpError::Signature(_))));
+        assert!(matches!(read.verify(b"the quarterly report", &bob), Err(IdpError::Signature(_))));
+        let renamed = DetachedSignature::from_armor(&armored.replace("Name: report.pdf", "Name: invoice.pdf")).unwrap();
+        assert!(renamed.verify(b"the quarterly report", &alice).is_err());
+        assert!(DetachedSignature::from_armor("-----BEGIN IDP SIGNATURE-----\nKey-Id: root-key-01\n").is_err());
+    }
+}
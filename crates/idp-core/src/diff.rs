@@ -0,0 +1,132 @@
+// crates/idp-core/src/diff.rs
+
+// Structured differences between two versions of a document, for sync tools and audits.
+// Every change is addressed by the same dot-paths `idp get` and `idp set` use; lists are
+// compared position by position, so an entry added in the middle shows as changes after it.
+
+use std::fmt;
+
+use serde::Serialize;
+use serde_yaml::Value;
+
+use crate::{Identity, IdpError};
+
+/// One difference between two documents.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum Change {
+    /// A value only the newer document has.
+    Added { path: String, value: Value },
+    /// A value only the older document has.
+    Removed { path: String, value: Value },
+    /// A value both have, with different contents.
+    Modified { path: String, old: Value, new: Value },
+}
+
+impl Change {
+    /// Where in the document the change is.
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } | Change::Removed { path, .. } | Change::Modified { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, value } => write!(f, "+ {}: {}", path, inline(value)),
+            Change::Removed { path, value } => write!(f, "- {}: {}", path, inline(value)),
+            Change::Modified { path, old, new } => write!(f, "~ {}: {} -> {}", path, inline(old), inline(new)),
+        }
+    }
+}
+
+// A value on one line: nested blocks are written as JSON.
+fn inline(value: &Value) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value))
+}
+
+/// The changes that turn `old` into `new`, in document order.
+pub fn diff_values(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(&mut Vec::new(), old, new, &mut changes);
+    changes
+}
+
+fn diff_at(path: &mut Vec<String>, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Mapping(old_map), Value::Mapping(new_map)) => {
+            // 1. Keys of the older document first, in its order, then keys only the newer one has.
+            for (key, old_value) in old_map {
+                path.push(segment(key));
+                match new_map.get(key) {
+                    Some(new_value) => diff_at(path, old_value, new_value, changes),
+                    None => changes.push(Change::Removed { path: path.join("."), value: old_value.clone() }),
+                }
+                path.pop();
+            }
+            for (key, new_value) in new_map.iter().filter(|(key, _)| !old_map.contains_key(*key)) {
+                path.push(segment(key));
+                changes.push(Change::Added { path: path.join("."), value: new_value.clone() });
+                path.pop();
+            }
+        }
+        (Value::Sequence(old_items), Value::Sequence(new_items)) => {
+            // 2. Lists position by position, with the longer one's tail added or removed.
+            for i in 0..old_items.len().max(new_items.len()) {
+                path.push(i.to_string());
+                match (old_items.get(i), new_items.get(i)) {
+                    (Some(old_item), Some(new_item)) => diff_at(path, old_item, new_item, changes),
+                    (Some(old_item), None) => changes.push(Change::Removed { path: path.join("."), value: old_item.clone() }),
+                    (None, Some(new_item)) => changes.push(Change::Added { path: path.join("."), value: new_item.clone() }),
+                    (None, None) => unreachable!("i is below the longer length"),
+                }
+                path.pop();
+            }
+        }
+        _ if old != new => changes.push(Change::Modified { path: path.join("."), old: old.clone(), new: new.clone() }),
+        _ => {}
+    }
+}
+
+fn segment(key: &Value) -> String {
+    match key {
+        Value::String(text) => text.clone(),
+        other => inline(other),
+    }
+}
+
+impl Identity {
+    /// The changes that turn this document into `other`, addressed by dot-path.
+    pub fn diff(&self, other: &Identity) -> Result<Vec<Change>, IdpError> {
+        Ok(diff_values(&serde_yaml::to_value(self)?, &serde_yaml::to_value(other)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_lists_changes_by_path() {
+        let (old, _) = Identity::new("Diff User", "Before.").unwrap();
+        assert_eq!(old.diff(&old).unwrap(), vec![]);
+
+        let mut new = old.clone();
+        new.core.bio = "After.".to_string();
+        new.system.public_keys.pop();
+        new.proofs.clear();
+        let changes = new.diff(&old).unwrap();
+        let summary: Vec<(&str, &str)> = changes
+            .iter()
+            .map(|change| match change {
+                Change::Added { path, .. } => ("added", path.as_str()),
+                Change::Removed { path, .. } => ("removed", path.as_str()),
+                Change::Modified { path, .. } => ("modified", path.as_str()),
+            })
+            .collect();
+        assert_eq!(summary, [("added", "system.public_keys.1"), ("modified", "core.bio"), ("added", "proofs")]);
+        assert_eq!(changes[1].to_string(), "~ core.bio: \"After.\" -> \"Before.\"");
+    }
+}
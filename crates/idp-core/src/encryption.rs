@@ -0,0 +1,292 @@
+// crates/idp-core/src/encryption.rs
+
+// Encrypting data to an identity, HPKE-style (base mode, X25519 + HKDF-SHA256 + ChaCha20-Poly1305):
+// the sender makes a one-time X25519 key, agrees a secret with the recipient's agreement key,
+// and derives a single-use AEAD key from it. Only the recipient's root key can open the result.
+//
+// Whole `.idp` documents are encrypted as age files instead (age-encryption.org/v1, ASCII-armored),
+// with the same agreement keys as X25519 recipients, so the `age` and `rage` tools can open them too.
+
+use std::io::Write;
+use std::path::Path;
+
+use age::armor::{ArmoredWriter, Format};
+use data_encoding::BASE64;
+use ring::{
+    aead, hkdf,
+    rand::{self, SecureRandom},
+};
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use crate::{atomic, compress, crypto, Identity, IdpError, IdpId, PublicKey};
+
+/// Domain separator for the key schedule; bump it if the construction ever changes.
+const ENCRYPTION_INFO: &[u8] = b"idp-encrypt-v1";
+
+/// A message encrypted to one key-agreement key of an identity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct EncryptedMessage {
+    /// The recipient's IDP id.
+    pub recipient: IdpId,
+    /// The recipient's agreement key the message was encrypted to.
+    pub key_id: String,
+    /// The sender's one-time X25519 public key (Base64).
+    pub ephemeral_key: String,
+    /// The ChaCha20-Poly1305 ciphertext and tag (Base64).
+    pub ciphertext: String,
+}
+
+impl Identity {
+    /// Encrypts bytes so that only `recipient` can read them.
+    pub fn encrypt_for(recipient: &Identity, plaintext: &[u8]) -> Result<EncryptedMessage, IdpError> {
+        let agreement_key = recipient.agreement_key()?;
+        let recipient_public = x25519_public_key(&agreement_key.canonical_value())?;
+
+        // 1. One-time key pair and shared secret.
+        let mut ephemeral_bytes = [0u8; 32];
+        rand::SystemRandom::new()
+            .fill(&mut ephemeral_bytes)
+            .map_err(|_| IdpError::Crypto("failed to generate an ephemeral key".to_string()))?;
+        let ephemeral_secret = x25519_dalek::StaticSecret::from(ephemeral_bytes);
+        let ephemeral_public = x25519_dalek::PublicKey::from(&ephemeral_secret);
+        let shared_secret = ephemeral_secret.diffie_hellman(&recipient_public);
+
+        // 2. Seal under a key bound to both public keys and the recipient's id.
+        let key = message_key(shared_secret.as_bytes(), &ephemeral_public, &recipient_public)?;
+        let mut in_out = plaintext.to_vec();
+        key.seal_in_place_append_tag(
+            aead::Nonce::assume_unique_for_key([0; aead::NONCE_LEN]),
+            aead::Aad::from(recipient.identity.id.as_bytes()),
+            &mut in_out,
+        )
+        .map_err(|e| IdpError::Crypto(e.to_string()))?;
+
+        Ok(EncryptedMessage {
+            recipient: recipient.identity.id.clone(),
+            key_id: agreement_key.key_id.clone(),
+            ephemeral_key: BASE64.encode(ephemeral_public.as_bytes()),
+            ciphertext: BASE64.encode(&in_out),
+        })
+    }
+
+    /// Decrypts a message sent to this identity, using the root private key its
+    /// agreement key was derived from.
+    pub fn decrypt(&self, message: &EncryptedMessage, root_private_key: &[u8]) -> Result<Vec<u8>, IdpError> {
+        if message.recipient != self.identity.id {
+            return Err(IdpError::Crypto(format!("message is addressed to '{}'", message.recipient)));
+        }
+
+        // 1. Rebuild our agreement secret and check it is the key the message was sent to.
+        let agreement_key = self
+            .find_key(&message.key_id)
+            .ok_or_else(|| IdpError::Key(format!("unknown agreement key '{}'", message.key_id)))?;
+        let secret = crypto::derive_x25519_secret(root_private_key)?;
+        let our_public = x25519_dalek::PublicKey::from(&secret);
+        if !agreement_key.has_value(&BASE64.encode(our_public.as_bytes())) {
+            return Err(IdpError::Key(format!("private key does not belong to '{}'", message.key_id)));
+        }
+
+        // 2. Agree on the same secret and open the ciphertext.
+        let ephemeral_public = x25519_public_key(&message.ephemeral_key)?;
+        let shared_secret = secret.diffie_hellman(&ephemeral_public);
+        let key = message_key(shared_secret.as_bytes(), &ephemeral_public, &our_public)?;
+        let mut in_out = BASE64
+            .decode(message.ciphertext.as_bytes())
+            .map_err(|_| IdpError::Crypto("malformed ciphertext".to_string()))?;
+        let plaintext = key
+            .open_in_place(
+                aead::Nonce::assume_unique_for_key([0; aead::NONCE_LEN]),
+                aead::Aad::from(self.identity.id.as_bytes()),
+                &mut in_out,
+            )
+            .map_err(|_| IdpError::Crypto("message could not be decrypted".to_string()))?;
+        Ok(plaintext.to_vec())
+    }
+}
+
+/// The first line of an encrypted `.idp` document.
+const AGE_ARMOR_BEGIN: &[u8] = b"-----BEGIN AGE ENCRYPTED FILE-----";
+/// The first line of a binary age file, as written by `age` without `--armor`.
+const AGE_BINARY_BEGIN: &[u8] = b"age-encryption.org/v1";
+
+/// Whether file contents are an age-encrypted document rather than YAML.
+pub fn is_encrypted_document(contents: &[u8]) -> bool {
+    contents.trim_ascii_start().starts_with(AGE_ARMOR_BEGIN) || contents.starts_with(AGE_BINARY_BEGIN)
+}
+
+/// The age identity (`AGE-SECRET-KEY-1...`) of the agreement key derived from a root key,
+/// for opening encrypted documents with the `age` tool.
+pub fn age_identity(root_private_key: &[u8]) -> Result<Zeroizing<String>, IdpError> {
+    let secret = crypto::derive_x25519_secret(root_private_key)?;
+    let encoded = Zeroizing::new(bech32_encode("age-secret-key-", secret.as_bytes())?);
+    Ok(Zeroizing::new(encoded.to_uppercase()))
+}
+
+fn bech32_encode(hrp: &str, data: &[u8]) -> Result<String, IdpError> {
+    let hrp = bech32::Hrp::parse(hrp).map_err(|e| IdpError::Crypto(e.to_string()))?;
+    bech32::encode::<bech32::Bech32>(hrp, data).map_err(|e| IdpError::Crypto(e.to_string()))
+}
+
+impl PublicKey {
+    /// The key as an age recipient (`age1...`). Only X25519 agreement keys have one.
+    pub fn to_age_recipient(&self) -> Result<String, IdpError> {
+        if self.algorithm != "X25519" {
+            return Err(IdpError::Key(format!("'{}' is a {} key; only X25519 keys can be age recipients", self.key_id, self.algorithm)));
+        }
+        bech32_encode("age", &self.raw_value()?)
+    }
+}
+
+impl Identity {
+    /// Serializes the document and encrypts it as an ASCII-armored age file, to the identity's
+    /// own agreement key and to any further age recipients (`age1...`, e.g. a backup key).
+    pub fn to_encrypted(&self, extra_recipients: &[&str]) -> Result<String, IdpError> {
+        // 1. The identity can always read its own document.
+        let own_recipient = self.agreement_key()?.to_age_recipient()?;
+        let recipients = std::iter::once(own_recipient.as_str())
+            .chain(extra_recipients.iter().copied())
+            .map(|recipient| {
+                recipient
+                    .parse::<age::x25519::Recipient>()
+                    .map_err(|e| IdpError::Crypto(format!("invalid age recipient '{}': {}", recipient, e)))
+            })
+            .collect::<Result<Vec<_>, IdpError>>()?;
+
+        // 2. Encrypt the YAML under a file key wrapped for each of them.
+        let yaml = Zeroizing::new(serde_yaml::to_string(self)?);
+        let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|r| r as &dyn age::Recipient))
+            .map_err(|e| IdpError::Crypto(e.to_string()))?;
+        let mut armored = Vec::new();
+        let mut writer = encryptor.wrap_output(ArmoredWriter::wrap_output(&mut armored, Format::AsciiArmor)?)?;
+        writer.write_all(yaml.as_bytes())?;
+        writer.finish()?.finish()?;
+        Ok(String::from_utf8(armored).expect("age armor is ASCII"))
+    }
+
+    /// Like `save_to_file`, but writes the document encrypted (see `to_encrypted`).
+    pub fn save_encrypted_to_file<P: AsRef<Path>>(&self, path: P, extra_recipients: &[&str]) -> Result<(), IdpError> {
+        atomic::write(path.as_ref(), self.to_encrypted(extra_recipients)?.as_bytes())
+    }
+
+    /// Reads a document, first decrypting it with the agreement secret of `root_private_key`
+    /// if it is encrypted. Plain YAML or JSON is read as is. Sealed sections are decrypted too.
+    pub fn from_encrypted(contents: &[u8], root_private_key: &[u8]) -> Result<Self, IdpError> {
+        let mut identity = Self::open_encrypted(contents, root_private_key)?;
+        identity.decrypt_sections(root_private_key)?;
+        Ok(identity)
+    }
+
+    fn open_encrypted(contents: &[u8], root_private_key: &[u8]) -> Result<Self, IdpError> {
+        if !is_encrypted_document(contents) {
+            return Identity::parse(contents);
+        }
+        let identity: age::x25519::Identity = age_identity(root_private_key)?
+            .parse()
+            .map_err(|e: &str| IdpError::Crypto(e.to_string()))?;
+        let yaml = Zeroizing::new(
+            age::decrypt(&identity, contents)
+                .map_err(|e| IdpError::Crypto(format!("the document could not be decrypted with this key: {}", e)))?,
+        );
+        Identity::parse(&yaml)
+    }
+
+    /// Like `load_from_file`, but also opens documents written by `save_encrypted_to_file`.
+    pub fn load_from_file_with_key<P: AsRef<Path>>(path: P, root_private_key: &[u8]) -> Result<Self, IdpError> {
+        Self::from_encrypted(&compress::decompress(std::fs::read(path)?)?, root_private_key)
+    }
+}
+
+// Every message uses a fresh ephemeral key, so each AEAD key is used exactly once
+// and a fixed nonce is safe.
+fn message_key(
+    shared_secret: &[u8],
+    ephemeral_public: &x25519_dalek::PublicKey,
+    recipient_public: &x25519_dalek::PublicKey,
+) -> Result<aead::LessSafeKey, IdpError> {
+    let mut context = ephemeral_public.as_bytes().to_vec();
+    context.extend_from_slice(recipient_public.as_bytes());
+
+    let mut key_bytes = [0u8; 32];
+    hkdf::Salt::new(hkdf::HKDF_SHA256, &context)
+        .extract(shared_secret)
+        .expand(&[ENCRYPTION_INFO], hkdf::HKDF_SHA256)
+        .and_then(|okm| okm.fill(&mut key_bytes))
+        .map_err(|e| IdpError::Crypto(e.to_string()))?;
+    let key = aead::UnboundKey::new(&aead::CHACHA20_POLY1305, &key_bytes).map_err(|e| IdpError::Crypto(e.to_string()))?;
+    Ok(aead::LessSafeKey::new(key))
+}
+
+fn x25519_public_key(value: &str) -> Result<x25519_dalek::PublicKey, IdpError> {
+    let bytes: [u8; 32] = BASE64
+        .decode(value.as_bytes())
+        .ok()
+        .and_then(|b| b.try_into().ok())
+        .ok_or_else(|| IdpError::Crypto("malformed X25519 public key".to_string()))?;
+    Ok(x25519_dalek::PublicKey::from(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_encrypts_to_an_identity() {
+        let (bob, bob_private_key) = Identity::new("Bob", "Receives secrets.").unwrap();
+        let message = Identity::encrypt_for(&bob, b"meet at noon").unwrap();
+
+        assert_eq!(message.key_id, "root-key-01/x25519");
+        assert_eq!(bob.decrypt(&message, &bob_private_key).unwrap(), b"meet at noon");
+    }
+
+    #[test]
+    fn it_cannot_be_read_by_anyone_else() {
+        let (bob, _) = Identity::new("Bob", "Receives secrets.").unwrap();
+        let (_, mallory_private_key) = Identity::new("Mallory", "Snoops.").unwrap();
+        let message = Identity::encrypt_for(&bob, b"meet at noon").unwrap();
+
+        assert!(matches!(bob.decrypt(&message, &mallory_private_key), Err(IdpError::Key(_))));
+    }
+
+    #[test]
+    fn it_encrypts_whole_documents() {
+        let (alice, alice_private_key) = Identity::new("Alice", "Keeps her file private.").unwrap();
+        let (backup, backup_private_key) = Identity::new("Backup", "Holds a spare copy.").unwrap();
+        let (_, mallory_private_key) = Identity::new("Mallory", "Snoops.").unwrap();
+
+        let backup_recipient = backup.agreement_key().unwrap().to_age_recipient().unwrap();
+        let encrypted = alice.to_encrypted(&[&backup_recipient]).unwrap();
+        assert!(is_encrypted_document(encrypted.as_bytes()));
+        assert!(!encrypted.contains("Alice"));
+
+        // Both recipients can read it, nobody else can; plain documents pass through.
+        assert_eq!(Identity::from_encrypted(encrypted.as_bytes(), &alice_private_key).unwrap(), alice);
+        assert_eq!(Identity::from_encrypted(encrypted.as_bytes(), &backup_private_key).unwrap(), alice);
+        assert!(matches!(Identity::from_encrypted(encrypted.as_bytes(), &mallory_private_key), Err(IdpError::Crypto(_))));
+        let yaml = serde_yaml::to_string(&alice).unwrap();
+        assert_eq!(Identity::from_encrypted(yaml.as_bytes(), &mallory_private_key).unwrap(), alice);
+
+        // The exported age identity is the one that opens it.
+        let age_identity: age::x25519::Identity = age_identity(&alice_private_key).unwrap().parse().unwrap();
+        assert!(age::decrypt(&age_identity, encrypted.as_bytes()).is_ok());
+        assert!(alice.to_encrypted(&["age1notarecipient"]).is_err());
+
+        // On disk, only the key-aware loader opens it.
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        alice.save_encrypted_to_file(&path, &[]).unwrap();
+        assert!(matches!(Identity::load_from_file(&path), Err(IdpError::Encrypted)));
+        assert_eq!(Identity::load_from_file_with_key(&path, &alice_private_key).unwrap(), alice);
+    }
+
+    #[test]
+    fn it_follows_key_rotation() {
+        let (mut bob, old_private_key) = Identity::new("Bob", "Receives secrets.").unwrap();
+        let new_private_key = bob.rotate_key(&old_private_key).unwrap();
+
+        // New messages go to the new root's agreement key.
+        let message = Identity::encrypt_for(&bob, b"after rotation").unwrap();
+        assert_eq!(message.key_id, "root-key-02/x25519");
+        assert_eq!(bob.decrypt(&message, &new_private_key).unwrap(), b"after rotation");
+    }
+}
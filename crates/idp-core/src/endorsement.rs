@@ -0,0 +1,121 @@
+// crates/idp-core/src/endorsement.rs
+
+// Endorsements: a third party's counter-signature on a credential someone else issued ("I also
+// vouch for this claim"). The endorser signs the credential's claim hash for its holder; the
+// holder keeps the result as another proof with the same claim hash, next to the issuer's.
+// Anyone with the endorser's keys can check it, as they would the credential.
+
+use data_encoding::HEXLOWER;
+use ring::digest;
+use serde_json::json;
+
+use crate::signer::SigningBackend;
+use crate::{canonical, Credential, Identity, IdpError, IdpId, Proof, Signer};
+
+/// The `Proof.proof_type` of an endorsement.
+pub const ENDORSEMENT_PROOF: &str = "Endorsement";
+
+/// Builds the statement an endorser signs for the credential with `claim_hash` held by `subject`.
+pub fn endorsement_statement(subject: &IdpId, claim_hash: &str, endorser: &IdpId) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-endorsement",
+        "endorser": endorser,
+        "subject": subject,
+        "claim_hash": claim_hash,
+    }))
+}
+
+impl Identity {
+    /// Endorses the credential `proof_id` held by `holder`, signed with `signer`'s key of this
+    /// identity. The holder adds the result with `add_endorsement`.
+    pub fn endorse(&self, holder: &Identity, proof_id: &str, signer: &dyn SigningBackend) -> Result<Proof, IdpError> {
+        let key = self.issuing_key(signer)?;
+        let credential = holder
+            .credentials
+            .iter()
+            .find(|credential| credential.proof == proof_id)
+            .ok_or_else(|| IdpError::Credential(format!("{} holds no credential '{}'", holder.identity.id, proof_id)))?;
+        if credential.issued_by == self.identity.id.as_str() || holder.identity.id == self.identity.id {
+            return Err(IdpError::Credential("only a third party can endorse a credential, not its issuer or holder".to_string()));
+        }
+        let claim_hash = &holder.credential_proof(credential).ok_or_else(|| IdpError::Credential(format!("the proof of '{}' is missing", credential.claim)))?.claim_hash;
+
+        let statement = endorsement_statement(&holder.identity.id, claim_hash, &self.identity.id);
+        Ok(Proof {
+            proof_id: format!("endorsement-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, &statement).as_ref()[..8])),
+            proof_type: ENDORSEMENT_PROOF.to_string(),
+            claim_hash: claim_hash.clone(),
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: key.key_id.clone() },
+            signature: vec![signer.sign(&statement)?],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        })
+    }
+
+    /// Adds an endorsement of one of this identity's credentials. Checking the endorser's
+    /// signature needs the endorser's keys.
+    pub fn add_endorsement(&mut self, endorsement: Proof) -> Result<(), IdpError> {
+        if endorsement.proof_type != ENDORSEMENT_PROOF {
+            return Err(IdpError::Credential(format!("'{}' is not an endorsement", endorsement.proof_id)));
+        }
+        if !self.credentials.iter().filter_map(|credential| self.credential_proof(credential)).any(|proof| proof.claim_hash == endorsement.claim_hash) {
+            return Err(IdpError::Credential("the endorsement is of a credential this identity does not hold".to_string()));
+        }
+        if self.proofs.iter().any(|existing| existing.proof_id == endorsement.proof_id) {
+            return Err(IdpError::Credential(format!("the endorsement '{}' is already in the document", endorsement.proof_id)));
+        }
+        self.proofs.push(endorsement);
+        self.touch();
+        Ok(())
+    }
+
+    /// The endorsements of a credential in this document.
+    pub fn endorsements(&self, credential: &Credential) -> Vec<&Proof> {
+        let Some(claim_hash) = self.credential_proof(credential).map(|proof| &proof.claim_hash) else {
+            return Vec::new();
+        };
+        self.proofs.iter().filter(|proof| proof.proof_type == ENDORSEMENT_PROOF && proof.claim_hash == *claim_hash).collect()
+    }
+
+    /// Checks an endorsement in this document against the endorser's identity: it must be for
+    /// this holder and signed by an active signing key of the endorser.
+    pub fn verify_endorsement(&self, endorsement: &Proof, endorser: &Identity) -> Result<(), IdpError> {
+        if endorsement.proof_type != ENDORSEMENT_PROOF || endorsement.signed_by.idp_id != endorser.identity.id {
+            return Err(IdpError::Credential(format!("'{}' is not an endorsement by {}", endorsement.proof_id, endorser.identity.id)));
+        }
+        let statement = endorsement_statement(&self.identity.id, &endorsement.claim_hash, &endorser.identity.id);
+        let signature = endorsement.signature.first().ok_or_else(|| IdpError::Credential(format!("the endorsement '{}' is not signed", endorsement.proof_id)))?;
+        endorser.verify_signed_by(&endorsement.signed_by.key_id, &statement, signature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_adds_and_checks_endorsements() {
+        let (issuer, issuer_key) = Identity::new("University", "Awards degrees.").unwrap();
+        let (endorser, endorser_key) = Identity::new("Employer", "Hires graduates.").unwrap();
+        let (mut holder, holder_key) = Identity::new("Graduate", "Has a degree.").unwrap();
+        holder.add_credential(issuer.issue_credential(&holder, "BSc Physics", None, &issuer_key).unwrap()).unwrap();
+        let proof_id = holder.credentials[0].proof.clone();
+
+        // A third party's endorsement carries the credential's claim hash, and checks out.
+        let endorsement = endorser.endorse(&holder, &proof_id, &endorser_key).unwrap();
+        assert_eq!(endorsement.claim_hash, holder.credential_proof(&holder.credentials[0]).unwrap().claim_hash);
+        holder.add_endorsement(endorsement.clone()).unwrap();
+        assert_eq!(holder.endorsements(&holder.credentials[0]), [&endorsement]);
+        holder.verify_endorsement(&endorsement, &endorser).unwrap();
+        assert!(holder.add_endorsement(endorsement.clone()).is_err(), "already added");
+
+        // Not by another identity, nor moved to another holder, nor by the issuer or holder.
+        assert!(holder.verify_endorsement(&endorsement, &issuer).is_err());
+        let (mut other, _) = Identity::new("Other", "Would like a degree.").unwrap();
+        assert!(other.add_endorsement(endorsement.clone()).is_err());
+        assert!(other.verify_endorsement(&endorsement, &endorser).is_err());
+        assert!(issuer.endorse(&holder, &proof_id, &issuer_key).is_err());
+        assert!(holder.endorse(&holder, &proof_id, &holder_key).is_err());
+    }
+}
@@ -0,0 +1,105 @@
+// crates/idp-core/src/envelope.rs
+
+// The envelope signature: the active root key signs the canonical JSON of the whole document
+// (minus the signature itself) every time it is saved, so any edit made to the file since
+// then shows up in `Identity::verify_self`.
+
+use crate::crypto::{self, VerifyError};
+use crate::signer::SigningBackend;
+use crate::{canonical, DocumentSignature, Identity, IdpError, Signer};
+use std::path::Path;
+
+impl Identity {
+    /// The bytes the envelope signature covers: the canonical JSON of the document
+    /// without its `signature` and `integrity` blocks.
+    pub fn envelope_bytes(&self) -> Result<Vec<u8>, IdpError> {
+        Ok(canonical::canonicalize(&self.unsigned_value()?))
+    }
+
+    // The document without the blocks every save renews.
+    pub(crate) fn unsigned_value(&self) -> Result<serde_json::Value, IdpError> {
+        let mut document = serde_json::to_value(self)?;
+        let map = document.as_object_mut().expect("documents serialize to objects");
+        map.remove("signature");
+        map.remove("integrity");
+        Ok(document)
+    }
+
+    /// Signs the document as it is now with the active root key held by `signer`,
+    /// replacing any earlier envelope signature.
+    pub fn sign_document(&mut self, signer: &dyn SigningBackend) -> Result<(), IdpError> {
+        let key = self.key_for_private_key(signer)?;
+        if key.parent_key_id.is_some() {
+            return Err(IdpError::Key(format!(
+                "'{}' is a subkey; documents are signed with the active root key",
+                key.key_id
+            )));
+        }
+        let signed_by = Signer {
+            idp_id: self.identity.id.clone(),
+            key_id: key.key_id.clone(),
+        };
+        let signature = signer.sign(&self.envelope_bytes()?)?;
+        self.signature = Some(DocumentSignature {
+            signed_by,
+            signature: vec![signature],
+            unknown_fields: Default::default(),
+        });
+        Ok(())
+    }
+
+    /// Renews the envelope signature and saves the document.
+    pub fn save_signed_to_file<P: AsRef<Path>>(&mut self, path: P, signer: &dyn SigningBackend) -> Result<(), IdpError> {
+        self.sign_document(signer)?;
+        self.save_to_file(path)
+    }
+
+    /// Checks that the document is exactly as its active root key last signed it.
+    /// Fails if it is unsigned, was signed by any other key, or was edited since.
+    pub fn verify_self(&self) -> Result<(), IdpError> {
+        // 1. The signer must be the newest key of the verified rotation chain, still active.
+        let envelope = self.signature.as_ref().ok_or(VerifyError::UnsignedDocument)?;
+        let chain = self.verify_key_chain()?;
+        let root = *chain.last().expect("the chain starts with the anchor");
+        if envelope.signed_by.key_id != root.key_id || envelope.signed_by.idp_id != self.identity.id {
+            return Err(VerifyError::NotRootKey(envelope.signed_by.key_id.clone()).into());
+        }
+        self.check_key_active(&root.key_id)?;
+
+        // 2. The signature must cover the document as it is now.
+        let signature = envelope.signature.first().ok_or(VerifyError::InvalidSignature)?;
+        crypto::verify(root, &self.envelope_bytes()?, signature)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_detects_edits_made_after_signing() {
+        let (mut identity, private_key) = Identity::new("Sealed User", "Signed on save.").unwrap();
+        assert!(matches!(identity.verify_self(), Err(IdpError::Verify(VerifyError::UnsignedDocument))));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        identity.save_signed_to_file(&path, &private_key).unwrap();
+        let loaded = Identity::load_from_file(&path).unwrap();
+        loaded.verify_self().unwrap();
+
+        // Any change to the file, even to a single field, breaks the seal.
+        let mut tampered = loaded.clone();
+        tampered.core.bio = "Edited by hand.".to_string();
+        assert!(matches!(tampered.verify_self(), Err(IdpError::Verify(VerifyError::InvalidSignature))));
+
+        // After a rotation only the new root key can seal the document.
+        let new_private_key = identity.rotate_key(&private_key).unwrap();
+        identity.sign_document(&private_key).unwrap_err();
+        let mut stale = identity.clone();
+        stale.signature = loaded.signature.clone();
+        assert!(matches!(stale.verify_self(), Err(IdpError::Verify(VerifyError::NotRootKey(_)))));
+        identity.sign_document(&new_private_key).unwrap();
+        identity.verify_self().unwrap();
+    }
+}
@@ -0,0 +1,143 @@
+// crates/idp-core/src/error.rs
+
+use thiserror::Error;
+
+use crate::crypto::VerifyError;
+use crate::schema::SchemaViolation;
+
+/// The single error type returned by every fallible operation in idp-core.
+/// Each variant maps to a distinct failure cause so callers can react to it.
+#[derive(Debug, Error)]
+pub enum IdpError {
+    /// Reading or writing a file failed (missing file, permissions, full disk...).
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// The document could not be parsed from, or serialized to, YAML.
+    #[error("invalid IDP document: {0}")]
+    Yaml(#[from] serde_yaml::Error),
+
+    /// The document could not be parsed from, or serialized to, JSON.
+    #[error("invalid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// The document could not be encoded to, or decoded from, CBOR.
+    #[error("invalid CBOR document: {0}")]
+    Cbor(String),
+
+    /// A cryptographic primitive failed, e.g. key generation or a malformed private key.
+    #[error("cryptographic failure: {0}")]
+    Crypto(String),
+
+    /// A key operation is not allowed in the key's current state, e.g. revoking it twice.
+    #[error("key error: {0}")]
+    Key(String),
+
+    /// A key store backend (such as the OS keychain) failed or is unavailable.
+    #[error("key store error: {0}")]
+    Keystore(String),
+
+    /// The document is encrypted, so it can only be read with a key that can decrypt it.
+    #[error("the document is encrypted and can only be read with its key")]
+    Encrypted,
+
+    /// The schema a document names could not be found, fetched or compiled.
+    #[error("schema error: {0}")]
+    Schema(String),
+
+    /// The document does not match its schema; every violation is listed.
+    #[error("the document breaks its schema in {} place(s)", .0.len())]
+    SchemaViolations(Vec<SchemaViolation>),
+
+    /// An identity id is malformed, e.g. a document's `identity.id` or a proof's signer.
+    #[error("invalid id '{id}': {reason}")]
+    Id { id: String, reason: String },
+
+    /// A key or contract status is not one this version knows, e.g. a typo like "actve".
+    #[error("unknown {kind} '{value}', expected one of: {expected}")]
+    Status { kind: String, value: String, expected: String },
+
+    /// An extension namespace is malformed, or its payload does not have the expected shape.
+    #[error("invalid extension '{namespace}': {reason}")]
+    Extension { namespace: String, reason: String },
+
+    /// A dot-path (as used by `idp set`) could not be resolved against the document.
+    #[error("invalid path '{path}': {reason}")]
+    Path { path: String, reason: String },
+
+    /// A JSON Patch is malformed, or one of its operations could not be applied.
+    #[error("invalid patch: {0}")]
+    Patch(String),
+
+    /// A credential cannot be issued or added, e.g. because its proof is for another identity.
+    #[error("credential error: {0}")]
+    Credential(String),
+
+    /// A JSON Web Token is malformed or uses an algorithm IDP keys cannot verify.
+    #[error("invalid JWT: {0}")]
+    Jwt(String),
+
+    /// A trusted timestamp could not be obtained, or its token does not check out.
+    #[error("timestamp error: {0}")]
+    Timestamp(String),
+
+    /// An OpenTimestamps anchor could not be made, upgraded or read.
+    #[error("anchor error: {0}")]
+    Anchor(String),
+
+    /// No chain of verified credentials leads from an issuer to a trust anchor.
+    #[error("untrusted: {0}")]
+    Trust(String),
+
+    /// A challenge cannot be answered, or a presentation is not what was asked for.
+    #[error("presentation error: {0}")]
+    Presentation(String),
+
+    /// A credential template is unreadable, unknown, or missing a variable.
+    #[error("template error: {0}")]
+    Template(String),
+
+    /// A contract cannot be signed or merged: it is unknown, or its terms differ.
+    #[error("contract error: {0}")]
+    Contract(String),
+
+    /// Consent cannot be granted or revoked: it is malformed, or there is none to revoke.
+    #[error("consent error: {0}")]
+    Consent(String),
+
+    /// A reputation event is unsigned where a signature is required, or its signature does not hold.
+    #[error("reputation error: {0}")]
+    Reputation(String),
+
+    /// The configuration file cannot be read, or holds a setting that is unknown or invalid.
+    #[error("configuration error: {0}")]
+    Config(String),
+
+    /// A detached signature that cannot be read, or was made by another identity.
+    #[error("signature error: {0}")]
+    Signature(String),
+
+    /// A snapshot cannot be restored: it is damaged, or of another identity.
+    #[error("the snapshot cannot be restored: {0}")]
+    Snapshot(String),
+
+    /// An event log is unreadable, broken, or out of step with its document.
+    #[error("event log error: {0}")]
+    Log(String),
+
+    /// A git command failed, or the document is not in a git repository.
+    #[error("git error: {0}")]
+    Git(String),
+
+    /// Another process holds the lock on the document, e.g. a second `idp` command changing it.
+    #[error("'{0}' is locked by another process")]
+    Locked(String),
+
+    /// The document does not match its integrity digest: it was corrupted or edited by hand.
+    #[error("the document does not match its integrity digest (recorded {expected}, computed {actual})")]
+    Integrity { expected: String, actual: String },
+
+    /// A signature did not verify.
+    #[error("verification failed: {0}")]
+    Verify(#[from] VerifyError),
+}
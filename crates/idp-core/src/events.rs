@@ -0,0 +1,225 @@
+// crates/idp-core/src/events.rs
+
+// The event log: an append-only record of every change saved to a document, kept next to it
+// (`my.idp.log`, one JSON event per line). Each event holds the JSON Patch from the state the
+// log ends in to the document as saved, is signed by the saving key and chained to the event
+// before it, so replaying the log rebuilds the document and any gap or rewrite shows.
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use json_patch::{Patch, PatchOperation};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::crypto;
+use crate::signer::SigningBackend;
+use crate::{canonical, Identity, IdpError, SignatureComponent, Signer};
+
+/// One saved change.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Event {
+    /// Position in the log, from 1.
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    /// The hash of the previous event; empty for the first.
+    pub prev: String,
+    /// What changed, without the signature and integrity blocks every save renews.
+    pub changes: Patch,
+    pub signed_by: Signer,
+    pub signature: SignatureComponent,
+}
+
+impl Event {
+    /// The bytes the event's signature covers: its canonical JSON without the signature.
+    pub fn signed_bytes(&self) -> Result<Vec<u8>, IdpError> {
+        let mut event = serde_json::to_value(self)?;
+        event.as_object_mut().expect("events serialize to objects").remove("signature");
+        Ok(canonical::canonicalize(&event))
+    }
+
+    /// The hash the next event records as `prev`.
+    pub fn hash(&self) -> Result<String, IdpError> {
+        canonical::claim_hash(self)
+    }
+
+    /// A one-line description of the changes, e.g. `rotated a key, replace core.bio`.
+    pub fn summary(&self) -> String {
+        let mut parts: Vec<String> = Vec::new();
+        for operation in self.changes.iter() {
+            let path = operation.path().to_string().trim_start_matches('/').replace('/', ".");
+            let part = match (operation, path.as_str()) {
+                (_, "") => "recorded the whole document".to_string(),
+                (_, "identity.updated_at") => continue,
+                (PatchOperation::Add(_), path) => match LABELS.iter().find(|(list, _)| path == *list || path.starts_with(&format!("{}.", list))) {
+                    Some((_, label)) => label.to_string(),
+                    None => format!("add {}", path),
+                },
+                (PatchOperation::Remove(_), path) => format!("remove {}", path),
+                (_, path) => format!("replace {}", path),
+            };
+            if !parts.contains(&part) {
+                parts.push(part);
+            }
+        }
+        match parts.is_empty() {
+            true => "touched the document".to_string(),
+            false => parts.join(", "),
+        }
+    }
+}
+
+// What adding to a list means, for summaries.
+const LABELS: [(&str, &str); 7] = [
+    ("system.rotations", "rotated a key"),
+    ("system.revocations", "revoked a key"),
+    ("system.public_keys", "added a key"),
+    ("credentials", "added a credential"),
+    ("proofs", "added a proof"),
+    ("contracts", "added a contract"),
+    ("consent", "granted consent"),
+];
+
+/// The event log of one document.
+#[derive(Debug, Clone)]
+pub struct EventLog {
+    path: PathBuf,
+    events: Vec<Event>,
+}
+
+impl EventLog {
+    /// The log kept next to the document at `document_path` (`my.idp.log` for `my.idp`).
+    pub fn path_for(document_path: &Path) -> PathBuf {
+        let mut path = document_path.as_os_str().to_owned();
+        path.push(".log");
+        PathBuf::from(path)
+    }
+
+    /// Reads the log of the document at `document_path`; a document without one has an empty log.
+    pub fn open<P: AsRef<Path>>(document_path: P) -> Result<EventLog, IdpError> {
+        let path = Self::path_for(document_path.as_ref());
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(e) => return Err(e.into()),
+        };
+        let events = contents
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(i, line)| serde_json::from_str(line).map_err(|e| IdpError::Log(format!("line {}: {}", i + 1, e))))
+            .collect::<Result<_, _>>()?;
+        Ok(EventLog { path, events })
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    /// Appends the changes between the state the log ends in and `identity`, signed with
+    /// `signer`'s key. Returns the new event, or `None` if nothing changed.
+    pub fn record(&mut self, identity: &Identity, signer: &dyn SigningBackend) -> Result<Option<&Event>, IdpError> {
+        // 1. Diff against the replayed log, not the old file, so a change that was saved but
+        //    never logged is caught up by the next event.
+        let changes = json_patch::diff(&self.replay(self.events.len())?, &identity.unsigned_value()?);
+        if changes.is_empty() {
+            return Ok(None);
+        }
+
+        // 2. Chain, sign, and append one line.
+        let key = identity.key_for_private_key(signer)?;
+        let mut event = Event {
+            seq: self.events.len() as u64 + 1,
+            at: Utc::now(),
+            prev: self.events.last().map(Event::hash).transpose()?.unwrap_or_default(),
+            changes,
+            signed_by: Signer { idp_id: identity.identity.id.clone(), key_id: key.key_id.clone() },
+            signature: SignatureComponent { algorithm: String::new(), value: String::new() },
+        };
+        event.signature = signer.sign(&event.signed_bytes()?)?;
+        let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+        file.write_all(format!("{}\n", serde_json::to_string(&event)?).as_bytes())?;
+        file.sync_all()?;
+
+        self.events.push(event);
+        Ok(self.events.last())
+    }
+
+    /// The document as it was right after event `seq`, without its signature.
+    pub fn state_at(&self, seq: u64) -> Result<Identity, IdpError> {
+        if seq == 0 || seq > self.events.len() as u64 {
+            return Err(IdpError::Log(format!("there is no event #{} (the log has {})", seq, self.events.len())));
+        }
+        Ok(serde_json::from_value(self.replay(seq as usize)?)?)
+    }
+
+    /// Checks that the events are numbered and chained in order, that each is signed by a key
+    /// of `identity`, and that replaying them gives `identity` as it is now.
+    pub fn verify(&self, identity: &Identity) -> Result<(), IdpError> {
+        let mut prev = String::new();
+        for (i, event) in self.events.iter().enumerate() {
+            if event.seq != i as u64 + 1 || event.prev != prev {
+                return Err(IdpError::Log(format!("event #{} is out of order; events were removed or rewritten", i + 1)));
+            }
+            let key = match identity.find_key(&event.signed_by.key_id) {
+                Some(key) if event.signed_by.idp_id == identity.identity.id => key,
+                _ => return Err(IdpError::Log(format!("event #{} is signed by a key this identity does not list", event.seq))),
+            };
+            crypto::verify(key, &event.signed_bytes()?, &event.signature)?;
+            prev = event.hash()?;
+        }
+        if self.replay(self.events.len())? != identity.unsigned_value()? {
+            return Err(IdpError::Log("the document has changes the log does not record".to_string()));
+        }
+        Ok(())
+    }
+
+    // The state after the first `count` events; `null` before any.
+    fn replay(&self, count: usize) -> Result<Value, IdpError> {
+        let mut state = Value::Null;
+        for event in &self.events[..count] {
+            json_patch::patch(&mut state, &event.changes)
+                .map_err(|e| IdpError::Log(format!("event #{} does not apply: {}", event.seq, e)))?;
+        }
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_records_and_replays_changes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        let (mut identity, private_key) = Identity::new("Logged User", "First bio.").unwrap();
+        let mut log = EventLog::open(&path).unwrap();
+        log.record(&identity, &private_key).unwrap();
+
+        identity.core.bio = "Second bio.".to_string();
+        identity.touch();
+        assert_eq!(log.record(&identity, &private_key).unwrap().unwrap().summary(), "replace core.bio");
+        assert!(log.record(&identity, &private_key).unwrap().is_none());
+        let new_private_key = identity.rotate_key(&private_key).unwrap();
+        log.record(&identity, &new_private_key).unwrap();
+
+        // The log on disk replays to every saved state, and to the document as it is.
+        let log = EventLog::open(&path).unwrap();
+        assert_eq!(log.events().len(), 3);
+        assert_eq!(log.state_at(1).unwrap().core.bio, "First bio.");
+        assert_eq!(log.state_at(2).unwrap().core.bio, "Second bio.");
+        assert!(log.events()[2].summary().contains("rotated a key"));
+        log.verify(&identity).unwrap();
+
+        // Unlogged changes and rewritten history both show.
+        identity.core.name = "Someone Else".to_string();
+        assert!(matches!(log.verify(&identity), Err(IdpError::Log(_))));
+        identity.core.name = "Logged User".to_string();
+        let mut rewritten = log.clone();
+        rewritten.events.remove(1);
+        assert!(matches!(rewritten.verify(&identity), Err(IdpError::Log(_))));
+    }
+}
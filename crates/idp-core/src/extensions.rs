@@ -0,0 +1,83 @@
+// crates/idp-core/src/extensions.rs
+
+// Application data in the `extensions` section, kept apart from the spec blocks. Each application
+// owns a namespace (e.g. `gamehub` or `org.example.app`) and stores any payload it can
+// serialize; other tools carry the payload along untouched.
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::{Identity, IdpError};
+
+/// Checks that a namespace is lower-case letters, digits, `_`, `-` and `.`, starting with a
+/// letter or digit.
+pub fn validate_namespace(namespace: &str) -> Result<(), IdpError> {
+    let mut chars = namespace.chars();
+    let valid = chars.next().is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.'));
+    match valid {
+        true => Ok(()),
+        false => Err(invalid(namespace, "namespaces are lower-case letters, digits, '_', '-' and '.'".to_string())),
+    }
+}
+
+impl Identity {
+    /// Reads the payload of a namespace as `T`, or `None` if the namespace is not there.
+    pub fn extension<T: DeserializeOwned>(&self, namespace: &str) -> Result<Option<T>, IdpError> {
+        self.extensions
+            .get(namespace)
+            .map(|payload| serde_yaml::from_value(payload.clone()).map_err(|e| invalid(namespace, e.to_string())))
+            .transpose()
+    }
+
+    /// Stores a payload under a namespace, replacing what was there.
+    pub fn set_extension<T: Serialize>(&mut self, namespace: &str, payload: &T) -> Result<(), IdpError> {
+        validate_namespace(namespace)?;
+        let payload = serde_yaml::to_value(payload).map_err(|e| invalid(namespace, e.to_string()))?;
+        self.extensions.insert(namespace.to_string(), payload);
+        Ok(())
+    }
+
+    /// Removes a namespace and returns its raw payload.
+    pub fn remove_extension(&mut self, namespace: &str) -> Option<serde_yaml::Value> {
+        self.extensions.remove(namespace)
+    }
+}
+
+fn invalid(namespace: &str, reason: String) -> IdpError {
+    IdpError::Extension {
+        namespace: namespace.to_string(),
+        reason,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct PlayerStats {
+        level: u32,
+        titles: Vec<String>,
+    }
+
+    #[test]
+    fn it_stores_typed_extension_payloads() {
+        let (mut identity, _) = Identity::new("Gamer", "Plays a lot.").unwrap();
+        let stats = PlayerStats { level: 42, titles: vec!["Champion".to_string()] };
+        identity.set_extension("gamehub", &stats).unwrap();
+        identity.validate_schema().unwrap();
+
+        // The payload survives a save and load, and reads back as its type.
+        let loaded = Identity::parse(identity.to_string_with_format(crate::Format::Json).unwrap().as_bytes()).unwrap();
+        assert_eq!(loaded.extension::<PlayerStats>("gamehub").unwrap(), Some(stats));
+        assert_eq!(loaded.get_path("extensions.gamehub.level").unwrap(), vec![serde_yaml::Value::from(42)]);
+        assert_eq!(loaded.extension::<PlayerStats>("other").unwrap(), None);
+
+        assert!(matches!(loaded.extension::<String>("gamehub"), Err(IdpError::Extension { .. })));
+        assert!(matches!(identity.set_extension("GameHub", &1), Err(IdpError::Extension { .. })));
+        assert!(identity.remove_extension("gamehub").is_some());
+        assert!(identity.extensions.is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+// crates/idp-core/src/git.rs
+
+// History for documents kept in a git repository. Each saved change can be committed on its
+// own, touching only the document and leaving whatever else is staged alone, with the commit
+// signed by the identity key as an SSH signature; past versions are read back out of git.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use chrono::{DateTime, FixedOffset};
+use serde::Serialize;
+
+use crate::signer::SigningBackend;
+use crate::{compress, ssh, Identity, IdpError};
+
+/// The git repository a document lives in.
+#[derive(Debug, Clone)]
+pub struct GitHistory {
+    root: PathBuf,
+    // The document's path inside the repository, with `/` separators.
+    path: String,
+}
+
+/// One commit that changed the document.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Version {
+    pub commit: String,
+    pub at: DateTime<FixedOffset>,
+    pub subject: String,
+}
+
+impl GitHistory {
+    /// Finds the repository the document at `document_path` is in, or `None` if it is not in one.
+    pub fn discover(document_path: &Path) -> Result<Option<GitHistory>, IdpError> {
+        let document_path = document_path.canonicalize()?;
+        let dir = document_path.parent().expect("a file has a parent directory");
+        let output = Command::new("git")
+            .current_dir(dir)
+            .args(["rev-parse", "--show-toplevel"])
+            .output()
+            .map_err(|e| IdpError::Git(format!("cannot run git (is it installed?): {}", e)))?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let root = PathBuf::from(String::from_utf8_lossy(&output.stdout).trim()).canonicalize()?;
+        let path = document_path
+            .strip_prefix(&root)
+            .map_err(|_| IdpError::Git(format!("'{}' is outside the repository at '{}'", document_path.display(), root.display())))?
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        Ok(Some(GitHistory { root, path }))
+    }
+
+    /// Commits the document as it is on disk onto the current branch, with `message`, signed
+    /// by `signer`'s key of `identity` if there is a signer. Returns the new commit, or `None`
+    /// if the committed document is already the same.
+    pub fn commit(&self, message: &str, identity: &Identity, signer: Option<&dyn SigningBackend>) -> Result<Option<String>, IdpError> {
+        // 1. Build the tree in a scratch index, so only the document goes into the commit.
+        let scratch = tempfile::tempdir()?;
+        let index = scratch.path().join("index");
+        let parent = self.git(&["rev-parse", "--verify", "-q", "HEAD"], None, None).ok();
+        if parent.is_some() {
+            self.git(&["read-tree", "HEAD"], None, Some(&index))?;
+        }
+        self.git(&["add", "--", &self.path], None, Some(&index))?;
+        let tree = self.git(&["write-tree"], None, Some(&index))?;
+        if parent.is_some() && self.git(&["rev-parse", "HEAD^{tree}"], None, None)? == tree {
+            return Ok(None);
+        }
+
+        // 2. Write the commit object, with the signature in a `gpgsig` header as `git commit -S` does.
+        let mut headers = format!("tree {}\n", tree);
+        if let Some(parent) = &parent {
+            headers.push_str(&format!("parent {}\n", parent));
+        }
+        headers.push_str(&format!("author {}\n", self.git(&["var", "GIT_AUTHOR_IDENT"], None, None)?));
+        headers.push_str(&format!("committer {}\n", self.git(&["var", "GIT_COMMITTER_IDENT"], None, None)?));
+        let body = format!("\n{}\n", message.trim_end());
+        if let Some(signer) = signer {
+            let key = identity.key_for_private_key(signer)?;
+            let signature = ssh::sshsig(signer, key, "git", format!("{}{}", headers, body).as_bytes())?;
+            headers.push_str(&format!("gpgsig {}\n", signature.trim_end().replace('\n', "\n ")));
+        }
+        let commit = self.git(&["hash-object", "-t", "commit", "-w", "--stdin"], Some(format!("{}{}", headers, body).as_bytes()), None)?;
+
+        // 3. Move the branch, refusing if it moved meanwhile, and bring the real index in line.
+        let mut update = vec!["update-ref", "-m", "idp: commit", "HEAD", &commit];
+        if let Some(parent) = &parent {
+            update.push(parent);
+        }
+        self.git(&update, None, None)?;
+        self.git(&["reset", "-q", "--", &self.path], None, None)?;
+        Ok(Some(commit))
+    }
+
+    /// The commits that changed the document, newest first.
+    pub fn versions(&self) -> Result<Vec<Version>, IdpError> {
+        let log = self.git(&["log", "--format=%H%x09%aI%x09%s", "--", &self.path], None, None)?;
+        log.lines()
+            .map(|line| {
+                let mut fields = line.splitn(3, '\t');
+                let (Some(commit), Some(at), subject) = (fields.next(), fields.next(), fields.next()) else {
+                    return Err(IdpError::Git(format!("unexpected git log line '{}'", line)));
+                };
+                Ok(Version {
+                    commit: commit.to_string(),
+                    at: DateTime::parse_from_rfc3339(at).map_err(|e| IdpError::Git(format!("bad commit date '{}': {}", at, e)))?,
+                    subject: subject.unwrap_or_default().to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// The document as committed in `revision` (a commit id, `HEAD~2`, a tag...).
+    pub fn document_at(&self, revision: &str) -> Result<Identity, IdpError> {
+        let output = self.command(&["show", &format!("{}:{}", revision, self.path)], None).output()?;
+        if !output.status.success() {
+            return Err(IdpError::Git(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Identity::parse(&compress::decompress(output.stdout)?)
+    }
+
+    // Runs a git command in the repository and returns its trimmed output.
+    fn git(&self, args: &[&str], stdin: Option<&[u8]>, index: Option<&Path>) -> Result<String, IdpError> {
+        let mut command = self.command(args, index);
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| IdpError::Git(format!("cannot run git (is it installed?): {}", e)))?;
+        let mut input = child.stdin.take().expect("stdin is piped");
+        input.write_all(stdin.unwrap_or_default())?;
+        drop(input);
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(IdpError::Git(format!("git {} failed: {}", args[0], stderr.trim())));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    fn command(&self, args: &[&str], index: Option<&Path>) -> Command {
+        let mut command = Command::new("git");
+        command.current_dir(&self.root).args(args);
+        if let Some(index) = index {
+            command.env("GIT_INDEX_FILE", index);
+        }
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_commits_signed_versions() {
+        let dir = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| Command::new("git").current_dir(dir.path()).args(args).output().unwrap();
+        git(&["init", "-q"]);
+        git(&["config", "user.name", "Versioned User"]);
+        git(&["config", "user.email", "versioned@example.com"]);
+        let path = dir.path().join("my.idp");
+
+        let (mut identity, private_key) = Identity::new("Versioned User", "First bio.").unwrap();
+        identity.save_to_file(&path).unwrap();
+        let history = GitHistory::discover(&path).unwrap().unwrap();
+        let first = history.commit("idp: created", &identity, Some(&private_key)).unwrap().unwrap();
+        assert!(history.commit("idp: nothing", &identity, Some(&private_key)).unwrap().is_none());
+
+        identity.core.bio = "Second bio.".to_string();
+        identity.save_to_file(&path).unwrap();
+        history.commit("idp: replace core.bio", &identity, Some(&private_key)).unwrap().unwrap();
+
+        // Both versions are in the log and readable, and the commits carry SSH signatures.
+        let versions = history.versions().unwrap();
+        assert_eq!(versions.iter().map(|version| version.subject.as_str()).collect::<Vec<_>>(), ["idp: replace core.bio", "idp: created"]);
+        assert_eq!(history.document_at(&first).unwrap().core.bio, "First bio.");
+        assert_eq!(history.document_at("HEAD").unwrap().core.bio, "Second bio.");
+        let object = String::from_utf8(git(&["cat-file", "commit", "HEAD"]).stdout).unwrap();
+        assert!(object.contains("gpgsig -----BEGIN SSH SIGNATURE-----"));
+    }
+}
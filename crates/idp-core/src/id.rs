@@ -0,0 +1,195 @@
+// crates/idp-core/src/id.rs
+
+// Identity ids. An IDP id names the root key it was made from: `idp:key:sha256:` and the Base64
+// SHA-256 of that key. Proofs signed outside IDP name their signer in its own scheme
+// (`ethereum:0x...`, `openpgp:<fingerprint>`, `did:key:z6Mk...`), and those parse as ids too.
+
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+
+use data_encoding::BASE64;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::{address, IdpError, PublicKey};
+
+/// The method of ids anchored to a key, the only IDP method so far.
+pub const KEY_METHOD: &str = "key";
+
+/// The scheme of ids naming an Ethereum account, the signer of address proofs.
+pub const ETHEREUM_SCHEME: &str = "ethereum";
+
+/// The scheme of ids naming an OpenPGP key by its fingerprint, the signer of certifications.
+pub const OPENPGP_SCHEME: &str = "openpgp";
+
+/// The scheme of W3C decentralized identifiers, the issuers of imported Verifiable Credentials.
+pub const DID_SCHEME: &str = "did";
+
+/// The DID methods accepted: those whose keys can be found from the DID alone or over HTTPS.
+pub const DID_METHODS: [&str; 3] = ["key", "jwk", "web"];
+
+/// A validated identity id. It can only be made by parsing or deriving, so a document with
+/// a malformed id fails to load.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct IdpId(String);
+
+impl IdpId {
+    /// The id anchored to a root key: `idp:key:sha256:` and the hash of its Base64 value.
+    pub fn derive_from_public_key(public_key: &PublicKey) -> IdpId {
+        let public_key_hash = digest::digest(&digest::SHA256, public_key.canonical_value().as_bytes());
+        IdpId(format!("idp:{}:sha256:{}", KEY_METHOD, BASE64.encode(public_key_hash.as_ref())))
+    }
+
+    /// Parses an id, checking every segment: the method and hash of IDP ids, or the account
+    /// of an external signer.
+    pub fn parse(text: &str) -> Result<IdpId, IdpError> {
+        let invalid = |reason: String| IdpError::Id {
+            id: text.to_string(),
+            reason,
+        };
+        match text.split_once(':') {
+            Some(("idp", rest)) => {
+                // 1. `idp:<method>:<hash algorithm>:<digest>`, with the only method and hash known.
+                let mut segments = rest.splitn(3, ':');
+                let (method, algorithm, hash) = match (segments.next(), segments.next(), segments.next()) {
+                    (Some(method), Some(algorithm), Some(hash)) => (method, algorithm, hash),
+                    _ => return Err(invalid("expected idp:key:sha256:<hash>".to_string())),
+                };
+                if method != KEY_METHOD {
+                    return Err(invalid(format!("unknown method '{}'", method)));
+                }
+                if algorithm != "sha256" {
+                    return Err(invalid(format!("unknown hash algorithm '{}'", algorithm)));
+                }
+                if !BASE64.decode(hash.as_bytes()).is_ok_and(|digest| digest.len() == 32) {
+                    return Err(invalid("the hash is not a Base64 SHA-256 digest".to_string()));
+                }
+            }
+            // 2. External signers, in the form their proofs write them.
+            Some((ETHEREUM_SCHEME, account)) => {
+                if address::parse_address(account).ok().as_deref() != Some(account) {
+                    return Err(invalid("expected an Ethereum address in EIP-55 case".to_string()));
+                }
+            }
+            Some((OPENPGP_SCHEME, fingerprint)) => {
+                let hex = fingerprint.bytes().all(|b| b.is_ascii_digit() || (b'A'..=b'F').contains(&b));
+                if !hex || !matches!(fingerprint.len(), 40 | 64) {
+                    return Err(invalid("expected an upper-case hex OpenPGP fingerprint".to_string()));
+                }
+            }
+            Some((DID_SCHEME, rest)) => match rest.split_once(':') {
+                Some((method, id)) if DID_METHODS.contains(&method) && !id.is_empty() => {}
+                _ => return Err(invalid(format!("expected a DID of one of the methods {}", DID_METHODS.join(", ")))),
+            },
+            _ => return Err(invalid("expected an id like idp:key:sha256:<hash>".to_string())),
+        }
+        Ok(IdpId(text.to_string()))
+    }
+
+    /// The id as text.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The method of an IDP id (`key`), or the scheme of an external signer (e.g. `ethereum`).
+    pub fn method(&self) -> &str {
+        match self.0.strip_prefix("idp:") {
+            Some(rest) => rest.split(':').next().expect("parsed ids have a method"),
+            None => self.0.split(':').next().expect("parsed ids have a scheme"),
+        }
+    }
+
+    /// Whether the id names a signer outside IDP rather than an IDP identity.
+    pub fn is_external(&self) -> bool {
+        !self.0.starts_with("idp:")
+    }
+}
+
+impl Deref for IdpId {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for IdpId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl FromStr for IdpId {
+    type Err = IdpError;
+
+    fn from_str(text: &str) -> Result<Self, IdpError> {
+        IdpId::parse(text)
+    }
+}
+
+impl TryFrom<String> for IdpId {
+    type Error = IdpError;
+
+    fn try_from(text: String) -> Result<Self, IdpError> {
+        IdpId::parse(&text)
+    }
+}
+
+impl From<IdpId> for String {
+    fn from(id: IdpId) -> String {
+        id.0
+    }
+}
+
+impl PartialEq<str> for IdpId {
+    fn eq(&self, other: &str) -> bool {
+        self.0 == other
+    }
+}
+
+impl PartialEq<&str> for IdpId {
+    fn eq(&self, other: &&str) -> bool {
+        self.0 == *other
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto, Identity};
+
+    #[test]
+    fn it_parses_and_rejects_ids() {
+        let key = crypto::generate_ed25519_keypair().unwrap().public_key;
+        let id = IdpId::derive_from_public_key(&key);
+        assert_eq!(IdpId::parse(&id.to_string()).unwrap(), id);
+        assert_eq!((id.method(), id.is_external()), ("key", false));
+
+        let external = IdpId::parse("ethereum:0x2c7536E3605D9C16a7a3D7b1898e529396a65c23").unwrap();
+        assert_eq!((external.method(), external.is_external()), ("ethereum", true));
+        IdpId::parse("openpgp:D8F2A0C4E6B81357D8F2A0C4E6B81357D8F2A0C4").unwrap();
+        assert_eq!(IdpId::parse("did:key:z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK").unwrap().method(), "did");
+
+        for malformed in [
+            "idp:key:clein_001",
+            "idp:did:sha256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            "idp:key:md5:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=",
+            "idp:key:sha256:c2hvcnQ=",
+            "ethereum:0x2c7536e3605d9c16a7a3d7b1898e529396a65c23",
+            "did:example:123",
+        ] {
+            assert!(matches!(IdpId::parse(malformed), Err(IdpError::Id { .. })), "{}", malformed);
+        }
+    }
+
+    #[test]
+    fn it_rejects_documents_with_malformed_ids() {
+        let (identity, _) = Identity::new("Id User", "Well named.").unwrap();
+        let yaml = serde_yaml::to_string(&identity).unwrap();
+        let broken = yaml.replace(identity.identity.id.as_str(), "idp:key:sha256:not-a-hash");
+        let error = serde_yaml::from_str::<Identity>(&broken).unwrap_err();
+        assert!(error.to_string().contains("not a Base64 SHA-256 digest"));
+    }
+}
@@ -0,0 +1,98 @@
+// crates/idp-core/src/integrity.rs
+
+// A digest of the whole document, written on every save and checked on every load. It catches
+// a file corrupted on disk or edited by hand before anything reads it, even when it is unsigned.
+
+use data_encoding::BASE64;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::{canonical, Identity, IdpError, UnknownFields};
+
+/// The hash functions an integrity block can use.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum DigestAlgorithm {
+    #[default]
+    Sha256,
+    Blake3,
+}
+
+/// The `integrity` block: a digest of the canonical JSON of everything else in the document.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Integrity {
+    pub algorithm: DigestAlgorithm,
+    /// Base64.
+    pub digest: String,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+impl DigestAlgorithm {
+    /// The Base64 digest of `bytes`.
+    pub fn digest(self, bytes: &[u8]) -> String {
+        match self {
+            DigestAlgorithm::Sha256 => BASE64.encode(digest::digest(&digest::SHA256, bytes).as_ref()),
+            DigestAlgorithm::Blake3 => BASE64.encode(blake3::hash(bytes).as_bytes()),
+        }
+    }
+}
+
+impl Identity {
+    /// The bytes the integrity digest covers: the canonical JSON of the document without
+    /// its `integrity` block. Unlike the envelope signature, it covers the signature too.
+    pub fn integrity_bytes(&self) -> Result<Vec<u8>, IdpError> {
+        let mut document = serde_json::to_value(self)?;
+        document.as_object_mut().expect("documents serialize to objects").remove("integrity");
+        Ok(canonical::canonicalize(&document))
+    }
+
+    /// The integrity block for the document as it is now, using the algorithm of the
+    /// current block (SHA-256 if there is none).
+    pub fn compute_integrity(&self) -> Result<Integrity, IdpError> {
+        let algorithm = self.integrity.as_ref().map(|integrity| integrity.algorithm).unwrap_or_default();
+        Ok(Integrity {
+            algorithm,
+            digest: algorithm.digest(&self.integrity_bytes()?),
+            unknown_fields: Default::default(),
+        })
+    }
+
+    /// Checks the document against its integrity block. Documents without one pass.
+    pub fn check_integrity(&self) -> Result<(), IdpError> {
+        let Some(recorded) = &self.integrity else {
+            return Ok(());
+        };
+        let actual = recorded.algorithm.digest(&self.integrity_bytes()?);
+        if actual != recorded.digest {
+            return Err(IdpError::Integrity { expected: recorded.digest.clone(), actual });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_detects_changes_made_behind_its_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        let (mut identity, _) = Identity::new("Careful User", "Checked on load.").unwrap();
+
+        // Every save writes a fresh digest, in the algorithm the document already uses.
+        identity.integrity = Some(Integrity { algorithm: DigestAlgorithm::Blake3, digest: String::new(), unknown_fields: Default::default() });
+        identity.save_to_file(&path).unwrap();
+        let loaded = Identity::load_from_file(&path).unwrap();
+        assert_eq!(loaded.integrity.as_ref().unwrap().algorithm, DigestAlgorithm::Blake3);
+
+        // A hand edit is caught on load; removing the block accepts it.
+        let edited = std::fs::read_to_string(&path).unwrap().replace("Checked on load.", "Edited by hand.");
+        std::fs::write(&path, &edited).unwrap();
+        assert!(matches!(Identity::load_from_file(&path), Err(IdpError::Integrity { .. })));
+        let stripped: String = edited.lines().take_while(|line| !line.starts_with("integrity:")).map(|line| format!("{}\n", line)).collect();
+        assert_eq!(Identity::parse(stripped.as_bytes()).unwrap().core.bio, "Edited by hand.");
+    }
+}
@@ -0,0 +1,226 @@
+// crates/idp-core/src/jwk.rs
+
+// JSON Web Key (RFC 7517 / RFC 8037 / RFC 8812) form of IDP public keys, so they can be published
+// in a JWKS endpoint or an OIDC configuration and read back from one.
+
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use k256::elliptic_curve::sec1::ToEncodedPoint;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::{crypto, IdpError, KeyFormat, KeyPurpose, KeyStatus, PublicKey};
+
+/// A public key in JWK form: an octet key pair (`"kty": "OKP"`) for the Edwards and Montgomery
+/// curves, or an elliptic-curve key (`"kty": "EC"`) for the ECDSA curves.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Jwk {
+    pub kty: String,
+    pub crv: String,
+    /// The raw public key (for EC keys, its x coordinate), Base64url without padding.
+    pub x: String,
+
+    /// The y coordinate of an EC key, Base64url without padding.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kid: Option<String>,
+
+    #[serde(rename = "use", default, skip_serializing_if = "Option::is_none")]
+    pub key_use: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub alg: Option<String>,
+}
+
+/// A JWK Set, the document served from a `jwks_uri`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct JwkSet {
+    pub keys: Vec<Jwk>,
+}
+
+impl Jwk {
+    /// The RFC 7638 thumbprint: a stable identifier computed from the key material alone.
+    pub fn thumbprint(&self) -> String {
+        // The required members must appear in lexicographic order with no whitespace.
+        let canonical = match &self.y {
+            Some(y) => format!(r#"{{"crv":"{}","kty":"{}","x":"{}","y":"{}"}}"#, self.crv, self.kty, self.x, y),
+            None => format!(r#"{{"crv":"{}","kty":"{}","x":"{}"}}"#, self.crv, self.kty, self.x),
+        };
+        BASE64URL_NOPAD.encode(digest::digest(&digest::SHA256, canonical.as_bytes()).as_ref())
+    }
+}
+
+impl PublicKey {
+    /// Converts the key to a JWK, using its key id as the `kid`.
+    pub fn to_jwk(&self) -> Result<Jwk, IdpError> {
+        let (alg, key_use) = match self.algorithm.as_str() {
+            "Ed25519" => ("EdDSA", "sig"),
+            "X25519" => ("ECDH-ES", "enc"),
+            crypto::P256 => ("ES256", "sig"),
+            crypto::SECP256K1 => ("ES256K", "sig"),
+            other => return Err(IdpError::Key(format!("'{}' keys cannot be expressed as a JWK", other))),
+        };
+        let raw = self.raw_value()?;
+
+        // EC keys are split into their coordinates; the uncompressed point is 0x04 || x || y.
+        let (kty, x, y) = match self.algorithm.as_str() {
+            crypto::P256 | crypto::SECP256K1 => {
+                let point = uncompressed_point(&self.algorithm, &raw)
+                    .ok_or_else(|| IdpError::Key(format!("the value of '{}' is not a {} point", self.key_id, self.algorithm)))?;
+                ("EC", BASE64URL_NOPAD.encode(&point[1..33]), Some(BASE64URL_NOPAD.encode(&point[33..])))
+            }
+            _ => ("OKP", BASE64URL_NOPAD.encode(&raw), None),
+        };
+
+        Ok(Jwk {
+            kty: kty.to_string(),
+            crv: self.algorithm.clone(),
+            x,
+            y,
+            kid: Some(self.key_id.clone()),
+            key_use: Some(key_use.to_string()),
+            alg: Some(alg.to_string()),
+        })
+    }
+
+    /// Reads a key from a JWK. Keys without a `kid` are named by their thumbprint.
+    pub fn from_jwk(jwk: &Jwk) -> Result<Self, IdpError> {
+        // 1. Only the curves IDP can verify or encrypt to are accepted.
+        let purpose = match (jwk.kty.as_str(), jwk.crv.as_str()) {
+            ("OKP", "Ed25519") | ("EC", crypto::P256 | crypto::SECP256K1) => KeyPurpose::Signing,
+            ("OKP", "X25519") => KeyPurpose::KeyAgreement,
+            ("OKP" | "EC", other) => return Err(IdpError::Key(format!("unsupported JWK curve '{}'", other))),
+            (other, _) => return Err(IdpError::Key(format!("unsupported JWK key type '{}'", other))),
+        };
+
+        // 2. Each coordinate (or the whole OKP key) must be exactly 32 bytes.
+        let coordinate = |member: &str, value: Option<&String>| {
+            value
+                .and_then(|value| BASE64URL_NOPAD.decode(value.as_bytes()).ok())
+                .filter(|raw| raw.len() == 32)
+                .ok_or_else(|| IdpError::Key(format!("the JWK '{}' member is not a 32-byte Base64url value", member)))
+        };
+        let x = coordinate("x", Some(&jwk.x))?;
+        let raw = match jwk.kty.as_str() {
+            "EC" => {
+                let mut point = vec![0x04];
+                point.extend(x);
+                point.extend(coordinate("y", jwk.y.as_ref())?);
+                stored_point(&jwk.crv, &point).ok_or_else(|| IdpError::Key(format!("the JWK is not a point on {}", jwk.crv)))?
+            }
+            _ => x,
+        };
+
+        Ok(PublicKey {
+            key_id: jwk.kid.clone().unwrap_or_else(|| jwk.thumbprint()),
+            algorithm: jwk.crv.clone(),
+            value: BASE64.encode(&raw),
+            format: KeyFormat::Base64,
+            status: KeyStatus::Active,
+            parent_key_id: None,
+            purpose,
+            expires_at: None,
+            unknown_fields: Default::default(),
+        })
+    }
+}
+
+// The uncompressed SEC1 form of an ECDSA public key, checking it is on the curve.
+fn uncompressed_point(algorithm: &str, sec1: &[u8]) -> Option<Vec<u8>> {
+    match algorithm {
+        crypto::P256 => Some(p256::PublicKey::from_sec1_bytes(sec1).ok()?.to_encoded_point(false).as_bytes().to_vec()),
+        crypto::SECP256K1 => Some(k256::PublicKey::from_sec1_bytes(sec1).ok()?.to_encoded_point(false).as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+// An ECDSA public key in the form `PublicKey.value` holds: uncompressed for P-256, as WebCrypto
+// and TPMs export it, and compressed for secp256k1, as wallets do.
+fn stored_point(algorithm: &str, sec1: &[u8]) -> Option<Vec<u8>> {
+    match algorithm {
+        crypto::P256 => uncompressed_point(algorithm, sec1),
+        crypto::SECP256K1 => Some(k256::PublicKey::from_sec1_bytes(sec1).ok()?.to_encoded_point(true).as_bytes().to_vec()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_round_trips_keys_through_jwk() {
+        let (identity, _) = Identity::new("JWK User", "Publishes keys.").unwrap();
+
+        for key in &identity.system.public_keys {
+            let jwk = key.to_jwk().unwrap();
+            let back = PublicKey::from_jwk(&jwk).unwrap();
+            assert_eq!(back.key_id, key.key_id);
+            assert_eq!(back.value, key.value);
+            assert_eq!(back.purpose, key.purpose);
+        }
+    }
+
+    #[test]
+    fn it_writes_ecdsa_keys_as_ec_jwks() {
+        // The P-256 key of RFC 7515, appendix A.3.
+        let jwk = Jwk {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            x: "f83OJ3D2xF1Bg8vub9tLe1gHMzV76e8Tus9uPHvRVEU".to_string(),
+            y: Some("x_FEzRu9m36HLN_tue659LNpXW6pCyStikYjKIWI5a0".to_string()),
+            kid: Some("webcrypto".to_string()),
+            key_use: None,
+            alg: None,
+        };
+        let key = PublicKey::from_jwk(&jwk).unwrap();
+        assert_eq!(key.algorithm, crypto::P256);
+        assert_eq!(key.raw_value().unwrap().len(), 65);
+        let back = key.to_jwk().unwrap();
+        assert_eq!((back.x.as_str(), back.y.as_ref()), (jwk.x.as_str(), jwk.y.as_ref()));
+        assert_eq!(back.alg.as_deref(), Some("ES256"));
+
+        // secp256k1 keys are stored compressed, but published with both coordinates.
+        let wallet = crypto::generate_keypair(crypto::SECP256K1).unwrap().public_key;
+        let jwk = wallet.to_jwk().unwrap();
+        assert_eq!((jwk.kty.as_str(), jwk.alg.as_deref()), ("EC", Some("ES256K")));
+        assert_eq!(PublicKey::from_jwk(&jwk).unwrap().value, wallet.value);
+
+        let mut off_curve = jwk.clone();
+        off_curve.y = Some(off_curve.x.clone());
+        assert!(PublicKey::from_jwk(&off_curve).is_err());
+    }
+
+    #[test]
+    fn it_computes_the_rfc8037_thumbprint() {
+        let jwk = Jwk {
+            kty: "OKP".to_string(),
+            crv: "Ed25519".to_string(),
+            x: "11qYAYKxCrfVS_7TyWQHOg7hcvPapiMlrwIaaPcHURo".to_string(),
+            y: None,
+            kid: None,
+            key_use: None,
+            alg: None,
+        };
+
+        assert_eq!(jwk.thumbprint(), "kPrK_qmxVWaYVA9wwBF6Iuo3vVzz7TxHCTwXBygrS4k");
+        assert_eq!(PublicKey::from_jwk(&jwk).unwrap().key_id, jwk.thumbprint());
+    }
+
+    #[test]
+    fn it_rejects_unsupported_jwks() {
+        let jwk = Jwk {
+            kty: "EC".to_string(),
+            crv: "P-256".to_string(),
+            x: "AAAA".to_string(),
+            y: None,
+            kid: None,
+            key_use: None,
+            alg: None,
+        };
+
+        assert!(matches!(PublicKey::from_jwk(&jwk), Err(IdpError::Key(_))));
+    }
+}
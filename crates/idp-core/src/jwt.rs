@@ -0,0 +1,113 @@
+// crates/idp-core/src/jwt.rs
+
+// Compact JSON Web Signatures (RFC 7515), the form JWTs are exchanged in. Only the algorithms IDP
+// keys can sign and verify are used: EdDSA (Ed25519), ES256 (P-256) and ES256K (secp256k1).
+
+use data_encoding::{BASE64, BASE64URL_NOPAD};
+use serde_json::Value;
+
+use crate::crypto::{self, VerifyError};
+use crate::{IdpError, PublicKey, SignatureComponent};
+
+/// The IDP key algorithm a JWS `alg` stands for.
+pub fn key_algorithm(alg: &str) -> Option<&'static str> {
+    match alg {
+        "EdDSA" | "Ed25519" => Some(crypto::ED25519),
+        "ES256" => Some(crypto::P256),
+        "ES256K" => Some(crypto::SECP256K1),
+        _ => None,
+    }
+}
+
+/// The JWS `alg` for keys of an IDP key algorithm.
+pub fn jws_alg(key_algorithm: &str) -> Option<&'static str> {
+    match key_algorithm {
+        crypto::ED25519 => Some("EdDSA"),
+        crypto::P256 => Some("ES256"),
+        crypto::SECP256K1 => Some("ES256K"),
+        _ => None,
+    }
+}
+
+/// The part of a JWS its signature covers: the encoded header and payload, joined by a dot.
+pub fn signing_input(header: &Value, payload: &Value) -> String {
+    let encode = |value: &Value| BASE64URL_NOPAD.encode(value.to_string().as_bytes());
+    format!("{}.{}", encode(header), encode(payload))
+}
+
+/// Completes a JWS from its signing input and the signature over it, as a signer returns it.
+pub fn assemble(signing_input: &str, signature: &SignatureComponent) -> Result<String, IdpError> {
+    let signature = BASE64.decode(signature.value.as_bytes()).map_err(|_| IdpError::Jwt("the signature is not valid Base64".to_string()))?;
+    Ok(format!("{}.{}", signing_input, BASE64URL_NOPAD.encode(&signature)))
+}
+
+/// A decoded compact JWS; its signature is not checked until `verify`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Jws {
+    pub header: Value,
+    pub payload: Value,
+    signing_input: String,
+    signature: Vec<u8>,
+}
+
+impl Jws {
+    /// Decodes `header.payload.signature`, each part Base64url without padding.
+    pub fn decode(token: &str) -> Result<Jws, IdpError> {
+        let parts: Vec<&str> = token.trim().split('.').collect();
+        let [header, payload, signature] = parts[..] else {
+            return Err(IdpError::Jwt("expected three parts separated by dots".to_string()));
+        };
+        let part = |name: &str, text: &str| {
+            BASE64URL_NOPAD.decode(text.as_bytes()).map_err(|_| IdpError::Jwt(format!("the {} is not Base64url", name)))
+        };
+        let json = |name: &str, bytes: Vec<u8>| {
+            serde_json::from_slice::<Value>(&bytes)
+                .ok()
+                .filter(Value::is_object)
+                .ok_or_else(|| IdpError::Jwt(format!("the {} is not a JSON object", name)))
+        };
+        Ok(Jws {
+            header: json("header", part("header", header)?)?,
+            payload: json("payload", part("payload", payload)?)?,
+            signing_input: format!("{}.{}", header, payload),
+            signature: part("signature", signature)?,
+        })
+    }
+
+    /// The `alg` of the header.
+    pub fn alg(&self) -> &str {
+        self.header["alg"].as_str().unwrap_or_default()
+    }
+
+    /// Checks the signature with `public_key`, whose algorithm must be the one `alg` names.
+    pub fn verify(&self, public_key: &PublicKey) -> Result<(), IdpError> {
+        let algorithm = key_algorithm(self.alg()).ok_or_else(|| IdpError::Jwt(format!("unsupported alg '{}'", self.alg())))?;
+        if algorithm != public_key.algorithm {
+            return Err(VerifyError::AlgorithmMismatch { key: public_key.algorithm.clone(), signature: algorithm.to_string() }.into());
+        }
+        let signature = SignatureComponent { algorithm: algorithm.to_string(), value: BASE64.encode(&self.signature) };
+        Ok(crypto::verify(public_key, self.signing_input.as_bytes(), &signature)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::signer::SigningBackend;
+    use crate::Identity;
+
+    #[test]
+    fn it_decodes_and_verifies_compact_jws() {
+        let (identity, private_key) = Identity::new("Token User", "Signs tokens.").unwrap();
+        let signing_input = signing_input(&serde_json::json!({ "alg": "EdDSA" }), &serde_json::json!({ "sub": "me" }));
+        let token = assemble(&signing_input, &private_key.sign(signing_input.as_bytes()).unwrap()).unwrap();
+
+        let jws = Jws::decode(&token).unwrap();
+        assert_eq!(jws.payload["sub"], "me");
+        jws.verify(&identity.system.public_keys[0]).unwrap();
+
+        let (other, _) = Identity::new("Other", "Did not sign.").unwrap();
+        assert!(matches!(jws.verify(&other.system.public_keys[0]), Err(IdpError::Verify(VerifyError::InvalidSignature))));
+        assert!(matches!(Jws::decode("not.a-token"), Err(IdpError::Jwt(_))));
+    }
+}
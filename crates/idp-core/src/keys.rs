@@ -0,0 +1,904 @@
+// crates/idp-core/src/keys.rs
+
+// Key management for an identity: finding the signing key, rotating, revoking and
+// deriving subkeys, and checking that a key may sign for the identity.
+
+use chrono::{DateTime, Utc};
+use serde_json::json;
+use std::fmt;
+
+use crate::crypto::{SignaturePolicy, VerifyError};
+use crate::keystore::KeyStore;
+use crate::signer::SigningBackend;
+use crate::{
+    canonical, crypto, Identity, IdpError, IdpId, KeyPurpose, KeyStatus, Proof, PublicKey, Revocation, Rotation, SecretBytes, SignatureComponent, Signer,
+};
+
+/// Derivation path of the ML-DSA-65 half of a hybrid root key (`root-key-01/ml-dsa-65`).
+const POST_QUANTUM_DERIVATION_PATH: &str = "ml-dsa-65";
+
+/// Builds the statement an outgoing key signs to endorse its successor, as canonical JSON.
+/// Verifiers rebuild it from the document to check a rotation record.
+pub fn rotation_statement(idp_id: &IdpId, old_key: &PublicKey, new_key: &PublicKey, rotated_at: &DateTime<Utc>) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-key-rotation",
+        "idp_id": idp_id,
+        "old_key_id": old_key.key_id,
+        "new_key": statement_key(new_key),
+        "rotated_at": rotated_at.to_rfc3339(),
+    }))
+}
+
+/// The `Proof.proof_type` recorded when a parent key delegates to a derived subkey.
+pub const SUBKEY_DELEGATION_PROOF: &str = "SubkeyDelegation";
+
+/// Builds the statement a parent key signs to vouch for a subkey derived from it.
+pub fn delegation_statement(idp_id: &IdpId, parent: &PublicKey, subkey: &PublicKey) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-subkey-delegation",
+        "idp_id": idp_id,
+        "parent_key_id": parent.key_id,
+        "subkey": statement_key(subkey),
+    }))
+}
+
+/// Builds the statement a key signs to revoke a key (possibly itself).
+pub fn revocation_statement(idp_id: &IdpId, key_id: &str, revoked_at: &DateTime<Utc>, reason: Option<&str>) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": "idp-key-revocation",
+        "idp_id": idp_id,
+        "key_id": key_id,
+        "revoked_at": revoked_at.to_rfc3339(),
+        "reason": reason,
+    }))
+}
+
+// A key as statements name it: its value in one encoding, whichever the document uses.
+fn statement_key(key: &PublicKey) -> serde_json::Value {
+    json!({
+        "key_id": key.key_id,
+        "algorithm": key.algorithm,
+        "value": key.canonical_value(),
+    })
+}
+
+/// What a key may do at a given moment, taking its recorded status, its expiry
+/// and any signed revocation into account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EffectiveStatus {
+    Active,
+    Superseded,
+    Expired,
+    Revoked,
+}
+
+impl fmt::Display for EffectiveStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            EffectiveStatus::Active => "active",
+            EffectiveStatus::Superseded => "superseded",
+            EffectiveStatus::Expired => "expired",
+            EffectiveStatus::Revoked => "revoked",
+        })
+    }
+}
+
+impl Identity {
+    /// Returns the public key with the given id.
+    pub fn find_key(&self, key_id: &str) -> Option<&PublicKey> {
+        self.system.public_keys.iter().find(|k| k.key_id == key_id)
+    }
+
+    /// Finds the active public key that matches the given private key.
+    /// An expired key is refused: anything it signed now would not verify.
+    pub fn key_for_private_key(&self, signer: &dyn SigningBackend) -> Result<&PublicKey, IdpError> {
+        let value = signer.public_key_value()?;
+        let key = self
+            .system
+            .public_keys
+            .iter()
+            .find(|k| k.has_value(&value) && k.status == KeyStatus::Active)
+            .ok_or_else(|| IdpError::Key("private key does not match any active key of this identity".to_string()))?;
+        if let Some(expires_at) = key.expires_at.filter(|e| *e <= Utc::now()) {
+            return Err(IdpError::Key(format!(
+                "key '{}' expired at {}; extend its `expires_at` to keep using it",
+                key.key_id,
+                expires_at.to_rfc3339()
+            )));
+        }
+        Ok(key)
+    }
+
+    /// Opens the key in `store` that is an active key of this identity, ready to sign.
+    pub fn signer_from(&self, store: &dyn KeyStore) -> Result<Box<dyn SigningBackend>, IdpError> {
+        let held = store.list()?;
+        let value = held
+            .iter()
+            .find(|value| self.system.public_keys.iter().any(|k| k.has_value(value) && k.status == KeyStatus::Active))
+            .ok_or_else(|| IdpError::Key(format!("{} holds no active key of this identity", store.describe())))?;
+        store.load(value)
+    }
+
+    /// Finds the active key matching a signer and checks it may sign key-management statements.
+    fn key_manager_for(&self, signer: &dyn SigningBackend) -> Result<&PublicKey, IdpError> {
+        let key = self.key_for_private_key(signer)?;
+        if !key.purpose.can_manage_keys() {
+            return Err(VerifyError::WrongPurpose {
+                key_id: key.key_id.clone(),
+                purpose: key.purpose,
+                required: "key management".to_string(),
+            }
+            .into());
+        }
+        Ok(key)
+    }
+
+    /// Replaces the active key with a freshly generated one.
+    ///
+    /// The old key is marked `superseded` and signs a `Rotation` record endorsing
+    /// the new key, which takes over its purpose. Returns the new private key bytes,
+    /// which the caller must store.
+    pub fn rotate_key(&mut self, current_key: &dyn SigningBackend) -> Result<SecretBytes, IdpError> {
+        // 1. The caller must prove control of the current key, which must be a root key.
+        let old_key = self.key_manager_for(current_key)?.clone();
+        if old_key.parent_key_id.is_some() {
+            return Err(IdpError::Key(format!(
+                "'{}' is a subkey; only root keys can be rotated",
+                old_key.key_id
+            )));
+        }
+
+        // 2. Generate the successor with the next free key id, keeping the algorithm where
+        //    it can be generated in software (a TPM key is succeeded by an Ed25519 one).
+        let algorithm = match crypto::can_generate(&old_key.algorithm) {
+            true => old_key.algorithm.as_str(),
+            false => crypto::ED25519,
+        };
+        let mut key_pair = crypto::generate_keypair(algorithm)?;
+        key_pair.public_key.key_id = self.next_key_id();
+        key_pair.public_key.purpose = old_key.purpose;
+        let new_key = key_pair.public_key;
+
+        // 3. The old key endorses the new one.
+        let rotated_at = Utc::now();
+        let statement = rotation_statement(&self.identity.id, &old_key, &new_key, &rotated_at);
+        let rotation = Rotation {
+            old_key_id: old_key.key_id.clone(),
+            new_key_id: new_key.key_id.clone(),
+            rotated_at,
+            signature: vec![current_key.sign(&statement)?],
+            unknown_fields: Default::default(),
+        };
+
+        // 4. Apply the rotation to the document.
+        for key in self.system.public_keys.iter_mut().filter(|k| k.key_id == old_key.key_id) {
+            key.status = KeyStatus::Superseded;
+        }
+        self.system.public_keys.push(new_key);
+        self.system.rotations.push(rotation);
+        self.touch();
+
+        // 5. Encryption, hybrid signing and BBS follow the root key: the new root gets its own
+        //    agreement key, post-quantum half and BBS key.
+        let had_agreement_key = self.system.public_keys.iter().any(|k| {
+            k.purpose == KeyPurpose::KeyAgreement && k.parent_key_id.as_deref() == Some(old_key.key_id.as_str())
+        });
+        if had_agreement_key {
+            self.add_agreement_key(&key_pair.private_key_bytes)?;
+        }
+        let post_quantum_id = format!("{}/{}", old_key.key_id, POST_QUANTUM_DERIVATION_PATH);
+        if self.find_key(&post_quantum_id).is_some() {
+            self.add_post_quantum_key(&key_pair.private_key_bytes)?;
+        }
+        if self.find_key(&format!("{}/bbs", old_key.key_id)).is_some() {
+            self.add_bbs_key(&key_pair.private_key_bytes)?;
+        }
+
+        Ok(key_pair.private_key_bytes)
+    }
+
+    /// Derives a subkey (e.g. for one device) from an active root key and a derivation path.
+    ///
+    /// The subkey is recorded with `parent_key_id` and a `SubkeyDelegation` proof signed by
+    /// the parent; it can sign for the identity only while its parent stays active.
+    /// Returns the subkey's private key bytes, which the caller must store.
+    pub fn derive_subkey(&mut self, root_private_key: &[u8], path: &str, purpose: KeyPurpose) -> Result<SecretBytes, IdpError> {
+        let algorithm = crypto::private_key_algorithm(root_private_key)?;
+        self.derive_subkey_as(root_private_key, path, purpose, algorithm)
+    }
+
+    /// Like `derive_subkey`, for a subkey of another algorithm than the root, so one identity
+    /// can carry e.g. an Ed25519 root and a P-256 key for WebCrypto.
+    pub fn derive_subkey_as(
+        &mut self,
+        root_private_key: &[u8],
+        path: &str,
+        purpose: KeyPurpose,
+        algorithm: &str,
+    ) -> Result<SecretBytes, IdpError> {
+        // 1. Only a root key of this identity can derive subkeys.
+        let parent = self.key_manager_for(&SecretBytes::from(root_private_key))?.clone();
+        if parent.parent_key_id.is_some() {
+            return Err(IdpError::Key("subkeys cannot derive further subkeys".to_string()));
+        }
+        let key_id = format!("{}/{}", parent.key_id, path);
+        if self.find_key(&key_id).is_some() {
+            return Err(IdpError::Key(format!("subkey '{}' already exists", key_id)));
+        }
+
+        // 2. Derive the subkey deterministically from the root seed.
+        let mut key_pair = crypto::derive_subkey_as(root_private_key, path, algorithm)?;
+        key_pair.public_key.key_id = key_id;
+        key_pair.public_key.parent_key_id = Some(parent.key_id.clone());
+        key_pair.public_key.purpose = purpose;
+        let subkey = key_pair.public_key;
+
+        // 3. The parent vouches for the subkey.
+        self.add_delegated_key(&parent, subkey, root_private_key)?;
+        Ok(key_pair.private_key_bytes)
+    }
+
+    /// Adds the X25519 key-agreement key that belongs to an active root key, so others can
+    /// encrypt to this identity. Its secret is derived from the root key, never stored.
+    pub fn add_agreement_key(&mut self, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let parent = self.key_manager_for(&SecretBytes::from(root_private_key))?.clone();
+        if parent.parent_key_id.is_some() {
+            return Err(IdpError::Key("agreement keys belong to root keys, not subkeys".to_string()));
+        }
+
+        let mut agreement_key = crypto::derive_x25519_public_key(root_private_key)?;
+        agreement_key.key_id = format!("{}/{}", parent.key_id, agreement_key.key_id);
+        agreement_key.parent_key_id = Some(parent.key_id.clone());
+        if self.find_key(&agreement_key.key_id).is_some() {
+            return Err(IdpError::Key(format!("'{}' already exists", agreement_key.key_id)));
+        }
+        self.add_delegated_key(&parent, agreement_key, root_private_key)
+    }
+
+    /// Returns the key others should encrypt to: the active agreement key of an active root.
+    pub fn agreement_key(&self) -> Result<&PublicKey, IdpError> {
+        self.system
+            .public_keys
+            .iter()
+            .filter(|k| k.purpose == KeyPurpose::KeyAgreement)
+            .find(|k| self.check_key_active(&k.key_id).is_ok())
+            .ok_or_else(|| IdpError::Key("this identity has no active key-agreement key".to_string()))
+    }
+
+    /// Adds the ML-DSA-65 half of a hybrid root key. Like the agreement key, it is derived from
+    /// the root seed on demand, so it never has to be stored; the root delegates to it.
+    pub fn add_post_quantum_key(&mut self, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let parent = self.key_manager_for(&SecretBytes::from(root_private_key))?.clone();
+        if parent.parent_key_id.is_some() || parent.algorithm != crypto::ED25519 {
+            return Err(IdpError::Key("only Ed25519 root keys can have a post-quantum half".to_string()));
+        }
+
+        let mut key_pair = crypto::derive_subkey_as(root_private_key, POST_QUANTUM_DERIVATION_PATH, crypto::ML_DSA_65)?;
+        key_pair.public_key.key_id = format!("{}/{}", parent.key_id, POST_QUANTUM_DERIVATION_PATH);
+        key_pair.public_key.parent_key_id = Some(parent.key_id.clone());
+        if self.find_key(&key_pair.public_key.key_id).is_some() {
+            return Err(IdpError::Key(format!("'{}' already exists", key_pair.public_key.key_id)));
+        }
+        self.add_delegated_key(&parent, key_pair.public_key, root_private_key)
+    }
+
+    /// Signs a message with a hybrid root key: an Ed25519 and an ML-DSA-65 component over the
+    /// same bytes, so the signature holds as long as either algorithm does.
+    pub fn sign_hybrid(&self, root_private_key: &[u8], message: &[u8]) -> Result<Vec<SignatureComponent>, IdpError> {
+        let root = self.key_for_private_key(&SecretBytes::from(root_private_key))?;
+        let post_quantum_id = format!("{}/{}", root.key_id, POST_QUANTUM_DERIVATION_PATH);
+        if self.find_key(&post_quantum_id).is_none() {
+            return Err(IdpError::Key(format!("'{}' has no post-quantum half to sign with", root.key_id)));
+        }
+
+        let post_quantum = crypto::derive_subkey_as(root_private_key, POST_QUANTUM_DERIVATION_PATH, crypto::ML_DSA_65)?;
+        Ok(vec![
+            crypto::sign(root_private_key, message)?,
+            crypto::sign(&post_quantum.private_key_bytes, message)?,
+        ])
+    }
+
+    /// Verifies a hybrid signature by an active signing root key and its post-quantum half.
+    /// Under `RequireAny` a root without a post-quantum half is checked on its own.
+    pub fn verify_hybrid(
+        &self,
+        key_id: &str,
+        message: &[u8],
+        signatures: &[SignatureComponent],
+        policy: SignaturePolicy,
+    ) -> Result<(), IdpError> {
+        let root = self.check_key_active(key_id)?;
+        if root.purpose != KeyPurpose::Signing {
+            return Err(VerifyError::WrongPurpose {
+                key_id: key_id.to_string(),
+                purpose: root.purpose,
+                required: KeyPurpose::Signing.to_string(),
+            }
+            .into());
+        }
+
+        let mut keys = vec![root];
+        let post_quantum_id = format!("{}/{}", key_id, POST_QUANTUM_DERIVATION_PATH);
+        match self.find_key(&post_quantum_id) {
+            Some(_) => keys.push(self.check_key_active(&post_quantum_id)?),
+            None if policy == SignaturePolicy::RequireAll => {
+                return Err(VerifyError::MissingComponent(crypto::ML_DSA_65.to_string()).into());
+            }
+            None => {}
+        }
+        Ok(crypto::verify_components(&keys, message, signatures, policy)?)
+    }
+
+    /// Adds the BBS key that belongs to an active root key, for issuing credentials whose fields
+    /// can be proven one by one. Like the agreement key, it is derived from the root, never stored.
+    pub fn add_bbs_key(&mut self, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let parent = self.key_manager_for(&SecretBytes::from(root_private_key))?.clone();
+        if parent.parent_key_id.is_some() {
+            return Err(IdpError::Key("BBS keys belong to root keys, not subkeys".to_string()));
+        }
+
+        let mut bbs_key = crypto::derive_bbs_public_key(root_private_key)?;
+        bbs_key.key_id = format!("{}/{}", parent.key_id, bbs_key.key_id);
+        bbs_key.parent_key_id = Some(parent.key_id.clone());
+        if self.find_key(&bbs_key.key_id).is_some() {
+            return Err(IdpError::Key(format!("'{}' already exists", bbs_key.key_id)));
+        }
+        self.add_delegated_key(&parent, bbs_key, root_private_key)
+    }
+
+    /// Records a subkey together with the parent's signed delegation to it.
+    fn add_delegated_key(&mut self, parent: &PublicKey, subkey: PublicKey, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let statement = delegation_statement(&self.identity.id, parent, &subkey);
+        self.proofs.push(Proof {
+            proof_id: format!("delegation-{}", subkey.key_id),
+            proof_type: SUBKEY_DELEGATION_PROOF.to_string(),
+            claim_hash: canonical::hash(&statement),
+            signed_by: Signer {
+                idp_id: self.identity.id.clone(),
+                key_id: parent.key_id.clone(),
+            },
+            signature: vec![crypto::sign(root_private_key, &statement)?],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        });
+        self.system.public_keys.push(subkey);
+        self.touch();
+        Ok(())
+    }
+
+    /// Computes the status of a key at a moment in time. Revocations (signed ones count even
+    /// if the `status` field was edited back) outrank expiry, which outranks supersession.
+    /// A signed revocation counts from when it was made; a key marked revoked without one
+    /// counts as revoked at any time. An otherwise active subkey takes on the status of its parent.
+    pub fn key_status_at(&self, key_id: &str, at: DateTime<Utc>) -> Result<EffectiveStatus, IdpError> {
+        let key = self.find_key(key_id).ok_or_else(|| VerifyError::UnknownKey(key_id.to_string()))?;
+        let revoked = match self.key_revoked_at(key_id) {
+            Some(revoked_at) => at >= revoked_at,
+            None => key.status == KeyStatus::Revoked,
+        };
+        // A key revoked after `at` was, at `at`, active, or superseded if it had been rotated out.
+        let superseded = match key.status {
+            KeyStatus::Active => false,
+            KeyStatus::Revoked => self.system.rotations.iter().any(|rotation| rotation.old_key_id == key_id),
+            _ => true,
+        };
+        if revoked {
+            Ok(EffectiveStatus::Revoked)
+        } else if key.expires_at.is_some_and(|expires_at| at >= expires_at) {
+            Ok(EffectiveStatus::Expired)
+        } else if superseded {
+            Ok(EffectiveStatus::Superseded)
+        } else {
+            // Only root keys can be parents, which also keeps hand-edited cycles from looping.
+            match key.parent_key_id.as_deref().and_then(|id| self.find_key(id)) {
+                Some(parent) if parent.parent_key_id.is_none() => self.key_status_at(&parent.key_id, at),
+                _ => Ok(EffectiveStatus::Active),
+            }
+        }
+    }
+
+    /// Checks that a key may currently sign for this identity (see `check_key_active_at`).
+    pub fn check_key_active(&self, key_id: &str) -> Result<&PublicKey, IdpError> {
+        self.check_key_active_at(key_id, Utc::now())
+    }
+
+    /// Checks that a key could sign for this identity at `at`: it is active, unexpired and not
+    /// revoked, and if it is a subkey, its parent is too and has delegated to it.
+    pub fn check_key_active_at(&self, key_id: &str, at: DateTime<Utc>) -> Result<&PublicKey, IdpError> {
+        let key = self.find_key(key_id).ok_or_else(|| VerifyError::UnknownKey(key_id.to_string()))?;
+        let status = self.key_status_at(key_id, at)?;
+        if status != EffectiveStatus::Active {
+            return Err(VerifyError::KeyNotActive {
+                key_id: key_id.to_string(),
+                status: status.to_string(),
+            }
+            .into());
+        }
+
+        if let Some(parent_id) = &key.parent_key_id {
+            let parent = self.find_key(parent_id).ok_or_else(|| VerifyError::UnknownKey(parent_id.clone()))?;
+            if parent.parent_key_id.is_some() {
+                return Err(VerifyError::MissingDelegation(key_id.to_string()).into());
+            }
+            self.check_key_active_at(parent_id, at)?;
+
+            let statement = delegation_statement(&self.identity.id, parent, key);
+            let delegated = self.proofs.iter().any(|p| {
+                p.proof_type == SUBKEY_DELEGATION_PROOF
+                    && p.signed_by.key_id == *parent_id
+                    && p.signature.first().is_some_and(|sig| crypto::verify(parent, &statement, sig).is_ok())
+            });
+            if !delegated {
+                return Err(VerifyError::MissingDelegation(key_id.to_string()).into());
+            }
+        }
+        Ok(key)
+    }
+
+    /// Verifies a general-purpose signature made by one of this identity's signing keys,
+    /// which must currently be allowed to sign (see `check_key_active`).
+    pub fn verify_signed_by(&self, key_id: &str, message: &[u8], signature: &SignatureComponent) -> Result<(), IdpError> {
+        self.verify_for_purpose(key_id, KeyPurpose::Signing, message, signature)
+    }
+
+    /// Verifies a signature made for a specific purpose (e.g. authentication) by a key that
+    /// was issued for exactly that purpose and is currently active.
+    pub fn verify_for_purpose(
+        &self,
+        key_id: &str,
+        purpose: KeyPurpose,
+        message: &[u8],
+        signature: &SignatureComponent,
+    ) -> Result<(), IdpError> {
+        self.verify_for_purpose_at(key_id, purpose, message, signature, Utc::now())
+    }
+
+    /// Like `verify_for_purpose`, for a signature known to have been made at `signed_at`
+    /// (e.g. a credential's issuance date): the key must have been active at that moment.
+    pub fn verify_for_purpose_at(
+        &self,
+        key_id: &str,
+        purpose: KeyPurpose,
+        message: &[u8],
+        signature: &SignatureComponent,
+        signed_at: DateTime<Utc>,
+    ) -> Result<(), IdpError> {
+        let key = self.check_key_active_at(key_id, signed_at)?;
+        if key.purpose != purpose {
+            return Err(VerifyError::WrongPurpose {
+                key_id: key_id.to_string(),
+                purpose: key.purpose,
+                required: purpose.to_string(),
+            }
+            .into());
+        }
+        crypto::verify(key, message, signature)?;
+        Ok(())
+    }
+
+    /// Revokes a key with a signed revocation statement.
+    ///
+    /// The statement is signed by `signing_key`, which must belong to another
+    /// active key of this identity or to the revoked key itself.
+    pub fn revoke_key(&mut self, key_id: &str, signing_key: &dyn SigningBackend, reason: Option<&str>) -> Result<(), IdpError> {
+        // 1. The key must exist and not be revoked already.
+        let target = self.find_key(key_id).ok_or_else(|| VerifyError::UnknownKey(key_id.to_string()))?;
+        if target.status == KeyStatus::Revoked {
+            return Err(IdpError::Key(format!("key '{}' is already revoked", key_id)));
+        }
+
+        // 2. Find who is signing and check they are allowed to.
+        let signer_value = signing_key.public_key_value()?;
+        let signer = self
+            .system
+            .public_keys
+            .iter()
+            .find(|k| {
+                k.has_value(&signer_value)
+                    && ((k.status == KeyStatus::Active && k.purpose.can_manage_keys()) || k.key_id == key_id)
+            })
+            .ok_or_else(|| {
+                IdpError::Key("revocations must be signed by an active key-management key or the revoked key itself".to_string())
+            })?;
+
+        // 3. Sign the revocation statement.
+        let revoked_at = Utc::now();
+        let statement = revocation_statement(&self.identity.id, key_id, &revoked_at, reason);
+        let revocation = Revocation {
+            key_id: key_id.to_string(),
+            revoked_at,
+            reason: reason.map(str::to_string),
+            signed_by: Signer {
+                idp_id: self.identity.id.clone(),
+                key_id: signer.key_id.clone(),
+            },
+            signature: vec![signing_key.sign(&statement)?],
+            unknown_fields: Default::default(),
+        };
+
+        // 4. Apply it to the document.
+        for key in self.system.public_keys.iter_mut().filter(|k| k.key_id == key_id) {
+            key.status = KeyStatus::Revoked;
+        }
+        self.system.revocations.push(revocation);
+        self.touch();
+        Ok(())
+    }
+
+    /// Checks the signature of a single revocation record against this identity's keys.
+    pub fn verify_revocation(&self, revocation: &Revocation) -> Result<(), IdpError> {
+        let signer = self
+            .find_key(&revocation.signed_by.key_id)
+            .ok_or_else(|| VerifyError::UnknownKey(revocation.signed_by.key_id.clone()))?;
+        let signature = revocation.signature.first().ok_or(VerifyError::InvalidSignature)?;
+        // Expiry is checked directly: the full key status depends on revocations itself.
+        if signer.expires_at.is_some_and(|expires_at| revocation.revoked_at >= expires_at) {
+            return Err(VerifyError::KeyNotActive {
+                key_id: signer.key_id.clone(),
+                status: EffectiveStatus::Expired.to_string(),
+            }
+            .into());
+        }
+        let statement = revocation_statement(
+            &self.identity.id,
+            &revocation.key_id,
+            &revocation.revoked_at,
+            revocation.reason.as_deref(),
+        );
+        crypto::verify(signer, &statement, signature)?;
+        Ok(())
+    }
+
+    /// Returns true if the key is revoked by a validly signed revocation.
+    /// Unlike the `status` field, this cannot be faked by editing the file.
+    pub fn is_key_revoked(&self, key_id: &str) -> bool {
+        self.key_revoked_at(key_id).is_some()
+    }
+
+    /// When the key was revoked, by the earliest validly signed revocation of it.
+    pub fn key_revoked_at(&self, key_id: &str) -> Option<DateTime<Utc>> {
+        self.system
+            .revocations
+            .iter()
+            .filter(|r| r.key_id == key_id && self.verify_revocation(r).is_ok())
+            .map(|r| r.revoked_at)
+            .min()
+    }
+
+    /// Checks that key statuses and signed revocations agree:
+    /// every revoked key has a valid revocation, and every valid revocation is applied.
+    pub fn verify_revocations(&self) -> Result<(), IdpError> {
+        for revocation in &self.system.revocations {
+            self.verify_revocation(revocation)?;
+        }
+        for key in &self.system.public_keys {
+            match (key.status == KeyStatus::Revoked, self.is_key_revoked(&key.key_id)) {
+                (true, false) => return Err(VerifyError::UnsignedRevocation(key.key_id.clone()).into()),
+                (false, true) => return Err(VerifyError::RevocationNotApplied(key.key_id.clone()).into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the root key the IDP id was derived from.
+    pub fn anchor_key(&self) -> Result<&PublicKey, IdpError> {
+        self.system
+            .public_keys
+            .iter()
+            .find(|k| k.parent_key_id.is_none() && IdpId::derive_from_public_key(k) == self.identity.id)
+            .ok_or_else(|| VerifyError::BrokenKeyChain("no root key matches the identity's id".to_string()).into())
+    }
+
+    /// Follows the rotation log from the id-anchored root key to the current one, checking
+    /// each hand-over was signed by the outgoing key while it could still sign. Every root
+    /// key must lie on the chain, so keys added by editing the file are caught.
+    /// Returns the keys in order, oldest first.
+    pub fn verify_key_chain(&self) -> Result<Vec<&PublicKey>, IdpError> {
+        let broken = |reason: String| -> IdpError { VerifyError::BrokenKeyChain(reason).into() };
+
+        // 1. Walk the log from the anchor, one signed hand-over at a time.
+        let mut chain = vec![self.anchor_key()?];
+        let mut handed_over_at: Option<DateTime<Utc>> = None;
+        loop {
+            let current = *chain.last().expect("the chain starts with the anchor");
+            let mut successors = self.system.rotations.iter().filter(|r| r.old_key_id == current.key_id);
+            let Some(rotation) = successors.next() else { break };
+            if successors.next().is_some() {
+                return Err(broken(format!("'{}' was rotated more than once", current.key_id)));
+            }
+
+            // 2. Each step must be signed by the outgoing key, in order, before it expired.
+            let next = self
+                .find_key(&rotation.new_key_id)
+                .filter(|k| k.parent_key_id.is_none())
+                .ok_or_else(|| broken(format!("'{}' is not a root key of this identity", rotation.new_key_id)))?;
+            if chain.iter().any(|k| k.key_id == next.key_id) {
+                return Err(broken(format!("'{}' appears twice in the chain", next.key_id)));
+            }
+            if handed_over_at.is_some_and(|previous| rotation.rotated_at < previous) {
+                return Err(broken(format!("the rotation to '{}' predates the one before it", next.key_id)));
+            }
+            if current.expires_at.is_some_and(|expires_at| rotation.rotated_at >= expires_at) {
+                return Err(broken(format!("'{}' had expired when it endorsed '{}'", current.key_id, next.key_id)));
+            }
+            let statement = rotation_statement(&self.identity.id, current, next, &rotation.rotated_at);
+            let signature = rotation.signature.first().ok_or(VerifyError::InvalidSignature)?;
+            crypto::verify(current, &statement, signature)?;
+
+            handed_over_at = Some(rotation.rotated_at);
+            chain.push(next);
+        }
+
+        // 3. Only the newest key may still claim to be active, and no root key may be left out.
+        if let Some(stale) = chain[..chain.len() - 1].iter().find(|k| k.status == KeyStatus::Active) {
+            return Err(broken(format!("'{}' was rotated away but is still marked active", stale.key_id)));
+        }
+        let outside = self
+            .system
+            .public_keys
+            .iter()
+            .find(|k| k.parent_key_id.is_none() && !chain.iter().any(|c| c.key_id == k.key_id));
+        if let Some(outside) = outside {
+            return Err(broken(format!("'{}' is not linked to the chain by a signed rotation", outside.key_id)));
+        }
+        Ok(chain)
+    }
+
+    /// Picks a key id that isn't used yet, following the `root-key-NN` convention.
+    fn next_key_id(&self) -> String {
+        let root_keys = self.system.public_keys.iter().filter(|k| k.parent_key_id.is_none()).count();
+        (root_keys + 1..)
+            .map(|n| format!("root-key-{:02}", n))
+            .find(|id| self.find_key(id).is_none())
+            .expect("an unused key id always exists")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_can_rotate_the_active_key() {
+        let (mut identity, old_private_key) = Identity::new("Rotating User", "Rotating keys.").unwrap();
+        let original_id = identity.identity.id.clone();
+
+        let new_private_key = identity.rotate_key(&old_private_key).unwrap();
+
+        // The old key is retired and the new one is active, with its own agreement key.
+        assert_eq!(identity.system.public_keys.len(), 4);
+        assert_eq!(identity.system.public_keys[0].status, KeyStatus::Superseded);
+        assert!(identity.check_key_active("root-key-01/x25519").is_err());
+        assert_eq!(identity.agreement_key().unwrap().key_id, "root-key-02/x25519");
+        assert_eq!(identity.key_for_private_key(&new_private_key).unwrap().key_id, "root-key-02");
+        assert!(identity.key_for_private_key(&old_private_key).is_err());
+        assert_eq!(identity.identity.id, original_id);
+
+        // The rotation record is a valid signature by the old key over the statement.
+        let rotation = &identity.system.rotations[0];
+        assert_eq!((rotation.old_key_id.as_str(), rotation.new_key_id.as_str()), ("root-key-01", "root-key-02"));
+        let new_key = identity.find_key("root-key-02").unwrap();
+        let statement = rotation_statement(&original_id, &identity.system.public_keys[0], new_key, &rotation.rotated_at);
+        crypto::verify(&identity.system.public_keys[0], &statement, &rotation.signature[0]).unwrap();
+    }
+
+    #[test]
+    fn it_rotates_post_quantum_keys_to_post_quantum_keys() {
+        let (mut identity, old_private_key) = Identity::new_with_algorithm("PQ User", "Quantum safe.", crypto::ML_DSA_65).unwrap();
+        assert_eq!(identity.system.public_keys[0].algorithm, crypto::ML_DSA_65);
+        assert!(identity.agreement_key().is_ok());
+
+        let new_private_key = identity.rotate_key(&old_private_key).unwrap();
+        assert_eq!(identity.key_for_private_key(&new_private_key).unwrap().algorithm, crypto::ML_DSA_65);
+        assert_eq!(identity.verify_key_chain().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn it_signs_with_both_halves_of_a_hybrid_key() {
+        let (mut identity, private_key) = Identity::new("Hybrid User", "Belt and braces.").unwrap();
+        identity.add_post_quantum_key(&private_key).unwrap();
+        assert_eq!(identity.find_key("root-key-01/ml-dsa-65").unwrap().algorithm, crypto::ML_DSA_65);
+
+        let signatures = identity.sign_hybrid(&private_key, b"hybrid").unwrap();
+        let algorithms: Vec<&str> = signatures.iter().map(|s| s.algorithm.as_str()).collect();
+        assert_eq!(algorithms, [crypto::ED25519, crypto::ML_DSA_65]);
+        identity.verify_hybrid("root-key-01", b"hybrid", &signatures, SignaturePolicy::RequireAll).unwrap();
+
+        // Dropping the post-quantum component fails RequireAll but passes RequireAny.
+        assert!(matches!(
+            identity.verify_hybrid("root-key-01", b"hybrid", &signatures[..1], SignaturePolicy::RequireAll),
+            Err(IdpError::Verify(VerifyError::MissingComponent(_)))
+        ));
+        identity.verify_hybrid("root-key-01", b"hybrid", &signatures[..1], SignaturePolicy::RequireAny).unwrap();
+        assert!(identity.verify_hybrid("root-key-01", b"hybryd", &signatures, SignaturePolicy::RequireAny).is_err());
+
+        // A rotated root keeps signing hybrid.
+        let new_private_key = identity.rotate_key(&private_key).unwrap();
+        let signatures = identity.sign_hybrid(&new_private_key, b"hybrid").unwrap();
+        identity.verify_hybrid("root-key-02", b"hybrid", &signatures, SignaturePolicy::RequireAll).unwrap();
+        assert!(identity.verify_hybrid("root-key-01", b"hybrid", &signatures, SignaturePolicy::RequireAny).is_err());
+    }
+
+    #[test]
+    fn it_verifies_the_key_chain_back_to_the_anchor() {
+        let (mut identity, first_private_key) = Identity::new("Chained User", "Rotating often.").unwrap();
+        let second_private_key = identity.rotate_key(&first_private_key).unwrap();
+        identity.rotate_key(&second_private_key).unwrap();
+
+        let chain = identity.verify_key_chain().unwrap();
+        let ids: Vec<&str> = chain.iter().map(|k| k.key_id.as_str()).collect();
+        assert_eq!(ids, ["root-key-01", "root-key-02", "root-key-03"]);
+
+        // A root key slipped in by hand has no signed hand-over.
+        let mut forged = identity.clone();
+        let mut intruder = crypto::generate_ed25519_keypair().unwrap().public_key;
+        intruder.key_id = "root-key-04".to_string();
+        forged.system.public_keys.push(intruder);
+        assert!(matches!(forged.verify_key_chain(), Err(IdpError::Verify(VerifyError::BrokenKeyChain(_)))));
+
+        // Redirecting a hand-over to another key breaks its signature.
+        let mut forged = identity.clone();
+        forged.system.rotations[1].new_key_id = "root-key-02".to_string();
+        assert!(forged.verify_key_chain().is_err());
+
+        // Reviving an old key is caught too.
+        let mut forged = identity.clone();
+        forged.system.public_keys[0].status = KeyStatus::Active;
+        assert!(forged.verify_key_chain().is_err());
+    }
+
+    #[test]
+    fn it_can_revoke_a_key_with_a_signed_statement() {
+        let (mut identity, old_private_key) = Identity::new("Revoking User", "Revoking keys.").unwrap();
+        let new_private_key = identity.rotate_key(&old_private_key).unwrap();
+
+        // The new active key revokes the superseded one.
+        identity.revoke_key("root-key-01", &new_private_key, Some("laptop stolen")).unwrap();
+        assert_eq!(identity.system.public_keys[0].status, KeyStatus::Revoked);
+        assert!(identity.is_key_revoked("root-key-01"));
+        assert!(!identity.is_key_revoked("root-key-02"));
+        identity.verify_revocations().unwrap();
+    }
+
+    #[test]
+    fn it_detects_tampered_revocations() {
+        let (mut identity, private_key) = Identity::new("Revoking User", "Revoking keys.").unwrap();
+
+        // A status edited by hand has no signed revocation behind it.
+        let mut forged = identity.clone();
+        forged.system.public_keys[0].status = KeyStatus::Revoked;
+        assert!(matches!(
+            forged.verify_revocations(),
+            Err(IdpError::Verify(VerifyError::UnsignedRevocation(_)))
+        ));
+
+        // Un-revoking a key by hand is caught too.
+        identity.revoke_key("root-key-01", &private_key, None).unwrap();
+        let mut restored = identity.clone();
+        restored.system.public_keys[0].status = KeyStatus::Active;
+        assert!(matches!(
+            restored.verify_revocations(),
+            Err(IdpError::Verify(VerifyError::RevocationNotApplied(_)))
+        ));
+
+        // Altering the signed reason breaks the signature.
+        let mut altered = identity.clone();
+        altered.system.revocations[0].reason = Some("made up".to_string());
+        assert!(!altered.is_key_revoked("root-key-01"));
+    }
+
+    #[test]
+    fn it_accepts_subkey_signatures_while_the_parent_is_active() {
+        let (mut identity, root_private_key) = Identity::new("Subkey User", "Many devices.").unwrap();
+        let laptop_private_key = identity.derive_subkey(&root_private_key, "laptop", KeyPurpose::Signing).unwrap();
+
+        let laptop = identity.find_key("root-key-01/laptop").unwrap();
+        assert_eq!(laptop.parent_key_id.as_deref(), Some("root-key-01"));
+        let signature = crypto::sign(&laptop_private_key, b"from my laptop").unwrap();
+        identity.verify_signed_by("root-key-01/laptop", b"from my laptop", &signature).unwrap();
+
+        // Revoking the laptop key doesn't burn the identity...
+        let mut lost_laptop = identity.clone();
+        lost_laptop.revoke_key("root-key-01/laptop", &root_private_key, Some("stolen")).unwrap();
+        assert!(lost_laptop.verify_signed_by("root-key-01/laptop", b"from my laptop", &signature).is_err());
+        lost_laptop.check_key_active("root-key-01").unwrap();
+
+        // ...but once the parent is rotated away, its subkeys stop being valid.
+        identity.rotate_key(&root_private_key).unwrap();
+        assert!(matches!(
+            identity.verify_signed_by("root-key-01/laptop", b"from my laptop", &signature),
+            Err(IdpError::Verify(VerifyError::KeyNotActive { .. }))
+        ));
+    }
+
+    #[test]
+    fn it_enforces_key_purposes() {
+        let (mut identity, root_private_key) = Identity::new("Purpose User", "Typed keys.").unwrap();
+        let login_private_key = identity.derive_subkey(&root_private_key, "login", KeyPurpose::Authentication).unwrap();
+        let signature = crypto::sign(&login_private_key, b"challenge").unwrap();
+
+        // An authentication key proves logins, but can't sign general statements...
+        identity
+            .verify_for_purpose("root-key-01/login", KeyPurpose::Authentication, b"challenge", &signature)
+            .unwrap();
+        assert!(matches!(
+            identity.verify_signed_by("root-key-01/login", b"challenge", &signature),
+            Err(IdpError::Verify(VerifyError::WrongPurpose { .. }))
+        ));
+
+        // ...nor manage the identity's keys.
+        assert!(matches!(
+            identity.rotate_key(&login_private_key),
+            Err(IdpError::Verify(VerifyError::WrongPurpose { .. }))
+        ));
+        assert!(identity.revoke_key("root-key-01", &login_private_key, None).is_err());
+    }
+
+    #[test]
+    fn it_carries_keys_of_several_algorithms() {
+        let (mut identity, root_private_key) = Identity::new("WebCrypto User", "Signs in the browser.").unwrap();
+        let browser_private_key = identity
+            .derive_subkey_as(&root_private_key, "browser", KeyPurpose::Signing, crypto::P256)
+            .unwrap();
+
+        let browser = identity.find_key("root-key-01/browser").unwrap();
+        assert_eq!((browser.algorithm.as_str(), identity.system.public_keys[0].algorithm.as_str()), (crypto::P256, crypto::ED25519));
+        let signature = crypto::sign(&browser_private_key, b"from the browser").unwrap();
+        identity.verify_signed_by("root-key-01/browser", b"from the browser", &signature).unwrap();
+    }
+
+    #[test]
+    fn it_rejects_subkeys_without_a_delegation() {
+        let (mut identity, root_private_key) = Identity::new("Subkey User", "Many devices.").unwrap();
+        identity.derive_subkey(&root_private_key, "laptop", KeyPurpose::Signing).unwrap();
+        identity.proofs.clear();
+
+        assert!(matches!(
+            identity.check_key_active("root-key-01/laptop"),
+            Err(IdpError::Verify(VerifyError::MissingDelegation(_)))
+        ));
+    }
+
+    #[test]
+    fn it_expires_keys() {
+        let (mut identity, private_key) = Identity::new("Expiring User", "Keys with a shelf life.").unwrap();
+        let expiry = Utc::now() + chrono::Duration::days(30);
+        identity.system.public_keys[0].expires_at = Some(expiry);
+        let message = b"signed in time";
+        let signature = crypto::sign(&private_key, message).unwrap();
+
+        // Before expiry the key, and the subkeys under it, are active; afterwards they are not.
+        let before = expiry - chrono::Duration::days(1);
+        let after = expiry + chrono::Duration::days(1);
+        assert_eq!(identity.key_status_at("root-key-01", before).unwrap(), EffectiveStatus::Active);
+        assert_eq!(identity.key_status_at("root-key-01", after).unwrap(), EffectiveStatus::Expired);
+        assert!(identity.check_key_active_at("root-key-01/x25519", before).is_ok());
+        assert_eq!(identity.key_status_at("root-key-01/x25519", after).unwrap(), EffectiveStatus::Expired);
+        assert!(matches!(
+            identity.check_key_active_at("root-key-01/x25519", after),
+            Err(IdpError::Verify(VerifyError::KeyNotActive { .. }))
+        ));
+        identity
+            .verify_for_purpose_at("root-key-01", KeyPurpose::Signing, message, &signature, before)
+            .unwrap();
+        assert!(identity
+            .verify_for_purpose_at("root-key-01", KeyPurpose::Signing, message, &signature, after)
+            .is_err());
+
+        // Once it has lapsed, the key can no longer sign anything new.
+        identity.system.public_keys[0].expires_at = Some(Utc::now() - chrono::Duration::seconds(1));
+        assert!(identity.verify_signed_by("root-key-01", message, &signature).is_err());
+        assert!(matches!(identity.rotate_key(&private_key), Err(IdpError::Key(_))));
+    }
+
+    #[test]
+    fn it_refuses_to_rotate_with_a_foreign_key() {
+        let (mut identity, _) = Identity::new("Rotating User", "Rotating keys.").unwrap();
+        let stranger = crypto::generate_ed25519_keypair().unwrap();
+
+        assert!(matches!(identity.rotate_key(&stranger.private_key_bytes), Err(IdpError::Key(_))));
+        assert_eq!(identity.system.public_keys.len(), 2);
+    }
+}
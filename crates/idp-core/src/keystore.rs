@@ -0,0 +1,262 @@
+// crates/idp-core/src/keystore.rs
+
+// Where private keys live. Every storage scheme (a key file, the OS keychain, a hardware
+// token, an agent) implements `KeyStore`, so signing code never needs to know which one it has.
+
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+use zeroize::Zeroizing;
+
+use crate::signer::SigningBackend;
+use crate::{atomic, crypto, IdpError, SecretBytes, SignatureComponent};
+
+/// A place private keys are kept. Keys are addressed by their Base64 public key value,
+/// which is how `PublicKey.value` in the identity refers to them.
+pub trait KeyStore {
+    /// Where the keys are, for messages (e.g. "my.key" or "the OS keychain").
+    fn describe(&self) -> String;
+
+    /// The public key values of the keys this store holds.
+    fn list(&self) -> Result<Vec<String>, IdpError>;
+
+    /// Opens a held key for signing.
+    fn load(&self, public_key_value: &str) -> Result<Box<dyn SigningBackend>, IdpError>;
+
+    /// Signs a message with a held key.
+    fn sign(&self, public_key_value: &str, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+        self.load(public_key_value)?.sign(message)
+    }
+
+    /// Stores a software private key. Stores that hold a single key replace it.
+    fn store(&self, private_key: &[u8]) -> Result<(), IdpError>;
+
+    /// Removes a held key.
+    fn delete(&self, public_key_value: &str) -> Result<(), IdpError>;
+}
+
+/// What a `FileKeyStore` needs a passphrase for.
+pub enum PassphraseRequest<'a> {
+    /// Unlocking an existing encrypted key file.
+    Unlock(&'a Path),
+    /// Protecting a new key file; answering `None` stores it unencrypted.
+    New(&'a Path),
+}
+
+/// Supplies the passphrases a `FileKeyStore` asks for.
+pub type PassphraseSource = Box<dyn Fn(PassphraseRequest) -> Result<Option<String>, IdpError>>;
+
+/// The default key store: one private key in a file, optionally encrypted with a passphrase.
+pub struct FileKeyStore {
+    path: PathBuf,
+    passphrases: PassphraseSource,
+    // Filled on first use: the decrypted key and the passphrase that protected it,
+    // so a replacement key is protected the same way without asking again.
+    unlocked: RefCell<Option<(SecretBytes, Option<Zeroizing<String>>)>>,
+}
+
+impl FileKeyStore {
+    /// A key file whose passphrases (if any) are supplied by `passphrases`.
+    pub fn new(
+        path: impl Into<PathBuf>,
+        passphrases: impl Fn(PassphraseRequest) -> Result<Option<String>, IdpError> + 'static,
+    ) -> Self {
+        FileKeyStore {
+            path: path.into(),
+            passphrases: Box::new(passphrases),
+            unlocked: RefCell::new(None),
+        }
+    }
+
+    /// A key file that is never encrypted.
+    pub fn unencrypted(path: impl Into<PathBuf>) -> Self {
+        Self::new(path, |_| Ok(None))
+    }
+
+    /// Reads and, if needed, decrypts the key file.
+    fn unlock(&self) -> Result<SecretBytes, IdpError> {
+        if let Some((private_key, _)) = self.unlocked.borrow().as_ref() {
+            return Ok(private_key.clone());
+        }
+
+        let contents = std::fs::read(&self.path)?;
+        let (private_key, passphrase) = match crypto::is_encrypted_private_key(&contents) {
+            false => (SecretBytes::new(contents), None),
+            true => {
+                let passphrase = (self.passphrases)(PassphraseRequest::Unlock(&self.path))?
+                    .map(Zeroizing::new)
+                    .ok_or_else(|| IdpError::Crypto("a passphrase is required to unlock the key file".to_string()))?;
+                (crypto::decrypt_private_key(&contents, &passphrase)?, Some(passphrase))
+            }
+        };
+        *self.unlocked.borrow_mut() = Some((private_key.clone(), passphrase));
+        Ok(private_key)
+    }
+
+    /// Unlocks the key file and checks it holds the requested key.
+    fn unlock_matching(&self, public_key_value: &str) -> Result<SecretBytes, IdpError> {
+        let private_key = self.unlock()?;
+        if crypto::public_key_value(&private_key)? != public_key_value {
+            return Err(IdpError::Keystore(format!("{} holds a different key", self.path.display())));
+        }
+        Ok(private_key)
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn describe(&self) -> String {
+        self.path.display().to_string()
+    }
+
+    fn list(&self) -> Result<Vec<String>, IdpError> {
+        if !self.path.exists() {
+            return Ok(vec![]);
+        }
+        Ok(vec![crypto::public_key_value(&self.unlock()?)?])
+    }
+
+    fn load(&self, public_key_value: &str) -> Result<Box<dyn SigningBackend>, IdpError> {
+        Ok(Box::new(self.unlock_matching(public_key_value)?))
+    }
+
+    fn store(&self, private_key: &[u8]) -> Result<(), IdpError> {
+        // 1. Keep the protection of the key being replaced, or ask for a new passphrase.
+        let known = self.unlocked.borrow().as_ref().map(|(_, passphrase)| passphrase.clone());
+        let passphrase = match known {
+            Some(passphrase) => passphrase,
+            None => (self.passphrases)(PassphraseRequest::New(&self.path))?.map(Zeroizing::new),
+        };
+        let contents = Zeroizing::new(match &passphrase {
+            Some(passphrase) => crypto::encrypt_private_key(private_key, passphrase)?,
+            None => private_key.to_vec(),
+        });
+
+        // 2. Write next to the old file and swap it in, so a crash never leaves half a key.
+        atomic::write(&self.path, &contents)?;
+
+        *self.unlocked.borrow_mut() = Some((SecretBytes::from(private_key), passphrase));
+        Ok(())
+    }
+
+    fn delete(&self, public_key_value: &str) -> Result<(), IdpError> {
+        self.unlock_matching(public_key_value)?;
+        std::fs::remove_file(&self.path)?;
+        *self.unlocked.borrow_mut() = None;
+        Ok(())
+    }
+}
+
+/// Private keys stored in the platform keychain: macOS Keychain, Windows Credential
+/// Manager, or the Secret Service on Linux. Each identity gets one entry, keyed by its IDP id,
+/// so the entry survives key rotation.
+#[cfg(feature = "os-keystore")]
+pub mod os {
+    use super::KeyStore;
+    use crate::signer::SigningBackend;
+    use crate::{crypto, IdpError, IdpId, SecretBytes};
+
+    /// The service name all IDP entries are filed under in the keychain.
+    const SERVICE: &str = "idp";
+
+    /// The keychain entry of one identity.
+    pub struct OsKeyStore {
+        idp_id: IdpId,
+    }
+
+    impl OsKeyStore {
+        pub fn new(idp_id: &IdpId) -> Self {
+            OsKeyStore { idp_id: idp_id.clone() }
+        }
+
+        fn entry(&self) -> Result<keyring::Entry, IdpError> {
+            keyring::Entry::new(SERVICE, &self.idp_id).map_err(|e| IdpError::Keystore(e.to_string()))
+        }
+
+        fn secret(&self) -> Result<Option<SecretBytes>, IdpError> {
+            match self.entry()?.get_secret() {
+                Ok(secret) => Ok(Some(SecretBytes::new(secret))),
+                Err(keyring::Error::NoEntry) => Ok(None),
+                Err(other) => Err(IdpError::Keystore(other.to_string())),
+            }
+        }
+    }
+
+    impl KeyStore for OsKeyStore {
+        fn describe(&self) -> String {
+            "the OS keychain".to_string()
+        }
+
+        fn list(&self) -> Result<Vec<String>, IdpError> {
+            self.secret()?.map(|secret| crypto::public_key_value(&secret)).into_iter().collect()
+        }
+
+        fn load(&self, public_key_value: &str) -> Result<Box<dyn SigningBackend>, IdpError> {
+            match self.secret()? {
+                Some(secret) if crypto::public_key_value(&secret)? == public_key_value => Ok(Box::new(secret)),
+                _ => Err(IdpError::Keystore(format!("no such key stored in the OS keychain for '{}'", self.idp_id))),
+            }
+        }
+
+        fn store(&self, private_key: &[u8]) -> Result<(), IdpError> {
+            self.entry()?
+                .set_secret(private_key)
+                .map_err(|e| IdpError::Keystore(e.to_string()))
+        }
+
+        fn delete(&self, public_key_value: &str) -> Result<(), IdpError> {
+            self.load(public_key_value)?;
+            self.entry()?
+                .delete_credential()
+                .map_err(|e| IdpError::Keystore(e.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_a_key_in_a_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileKeyStore::unencrypted(dir.path().join("my.key"));
+        assert!(store.list().unwrap().is_empty());
+
+        let key_pair = crypto::generate_ed25519_keypair().unwrap();
+        store.store(&key_pair.private_key_bytes).unwrap();
+        assert_eq!(store.list().unwrap(), vec![key_pair.public_key.value.clone()]);
+
+        let signature = store.sign(&key_pair.public_key.value, b"through the store").unwrap();
+        crypto::verify(&key_pair.public_key, b"through the store", &signature).unwrap();
+        assert!(store.load("some other key").is_err());
+
+        store.delete(&key_pair.public_key.value).unwrap();
+        assert!(store.list().unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_keeps_the_passphrase_of_the_key_it_replaces() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.key");
+        let old_key = crypto::generate_ed25519_keypair().unwrap();
+        std::fs::write(&path, crypto::encrypt_private_key(&old_key.private_key_bytes, "hunter2").unwrap()).unwrap();
+
+        // The passphrase is asked for once, to unlock; the replacement is sealed with it too.
+        let asked = std::rc::Rc::new(std::cell::Cell::new(0));
+        let counter = asked.clone();
+        let store = FileKeyStore::new(&path, move |request| match request {
+            PassphraseRequest::Unlock(_) => {
+                counter.set(counter.get() + 1);
+                Ok(Some("hunter2".to_string()))
+            }
+            PassphraseRequest::New(_) => panic!("no new passphrase should be needed"),
+        });
+        store.load(&old_key.public_key.value).unwrap();
+        let new_key = crypto::generate_ed25519_keypair().unwrap();
+        store.store(&new_key.private_key_bytes).unwrap();
+
+        assert_eq!(asked.get(), 1);
+        let contents = std::fs::read(&path).unwrap();
+        assert_eq!(crypto::decrypt_private_key(&contents, "hunter2").unwrap(), new_key.private_key_bytes);
+    }
+}
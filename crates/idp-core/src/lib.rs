@@ -4,76 +4,324 @@
 // Specification: v0.2.1
 
 use chrono::{DateTime, Utc};
-use data_encoding::BASE64;
-use ring::digest;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs::File;
-use std::io::{Read, Write};
+use std::io::Read;
 use std::path::Path;
 
+pub mod address;
+pub mod anchor;
+pub mod atomic;
+pub mod bbs;
+pub mod builder;
+pub mod canonical;
+pub mod claims;
+pub mod cbor;
+pub mod compress;
+pub mod config;
+pub mod consent;
+pub mod consequence;
+pub mod context;
+pub mod contract;
+pub mod credential;
 pub mod crypto;
+pub mod detached;
+pub mod diff;
+pub mod encryption;
+pub mod endorsement;
+pub mod envelope;
+pub mod error;
+pub mod events;
+pub mod extensions;
+pub mod git;
+pub mod id;
+pub mod integrity;
+pub mod jwk;
+pub mod jwt;
+pub mod keys;
+pub mod keystore;
+pub mod lock;
+pub mod merge;
+pub mod mnemonic;
+pub mod multibase;
+pub mod openpgp;
+pub mod patch;
+pub mod presentation;
+pub mod registry;
+pub mod reputation;
+pub mod path;
+pub mod schema;
+pub mod sealing;
+pub mod sd_jwt;
+pub mod secret;
+pub mod signer;
+pub mod snapshot;
+pub mod ssh;
+pub mod status_list;
+pub mod status;
+pub mod stream;
+pub mod subject_access;
+pub mod templates;
+pub mod timestamp;
+pub mod trust;
+pub mod tsa;
+pub mod validate;
+pub mod vc;
+pub mod view;
 
-// The top-level struct that represents an entire IDP document.
+pub use builder::IdentityBuilder;
+pub use error::IdpError;
+pub use id::IdpId;
+pub use secret::SecretBytes;
+pub use status::{ContractStatus, KeyStatus};
+
+/// Fields written by a newer version that this one does not know. Every block keeps them,
+/// so loading and saving a document never drops data.
+pub type UnknownFields = BTreeMap<String, serde_yaml::Value>;
+
+// The top-level struct that represents an entire IDP document. It is (de)serialized through
+// `sealing`, which writes sealed sections in place of their contents.
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(remote = "Self")]
 pub struct Identity {
     pub identity: IdentityBlock,
     pub system: SystemBlock,
     pub core: CoreBlock,
     
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, deserialize_with = "sealing::credentials", skip_serializing_if = "Vec::is_empty")]
     pub credentials: Vec<Credential>,
+
+    // Expired credentials moved out of `credentials`, kept with their proofs; see `Identity::prune_expired`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived_credentials: Vec<Credential>,
     
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub proofs: Vec<Proof>,
     
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, deserialize_with = "sealing::contracts", skip_serializing_if = "Vec::is_empty")]
     pub contracts: Vec<Contract>,
 
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, deserialize_with = "sealing::reputation", skip_serializing_if = "Vec::is_empty")]
     pub reputation: Vec<Reputation>,
 
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[serde(default, deserialize_with = "sealing::consent", skip_serializing_if = "Vec::is_empty")]
     pub consent: Vec<Consent>,
+
+    // Expired consents moved out of `consent`, kept as a record; see `Identity::archive_expired_consent`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub archived_consent: Vec<Consent>,
+
+    // Every grant, revocation, expiry and access under consent, hash-chained; see `Identity::verify_consent_log`.
+    #[serde(default, deserialize_with = "sealing::consent_log", skip_serializing_if = "Vec::is_empty")]
+    pub consent_log: Vec<ConsentLogEntry>,
+
+    // Application data by namespace (e.g. `gamehub`); see `Identity::extension`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extensions: BTreeMap<String, serde_yaml::Value>,
+
+    // Sections stored encrypted, by name; see `Identity::seal_section`.
+    #[serde(skip)]
+    pub sealed: BTreeMap<String, sealing::SealedSection>,
+
+    // The envelope signature over everything above; see `Identity::verify_self`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<DocumentSignature>,
+
+    // A digest of everything above, renewed on every save; see `Identity::check_integrity`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub integrity: Option<integrity::Integrity>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct IdentityBlock {
-    pub id: String,
+    pub id: IdpId,
     pub version: String,
     pub schema_url: String,
     pub created_at: DateTime<Utc>, // Changed from String
     pub updated_at: DateTime<Utc>, // Changed from String
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct SystemBlock {
     pub public_keys: Vec<PublicKey>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub revocations: Vec<Revocation>,
+
+    // The rotation log: each root key's signed hand-over to its successor.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rotations: Vec<Rotation>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct PublicKey {
     pub key_id: String,
     pub algorithm: String,
-    pub value: String, // Base64 encoded public key
-    pub status: String, // "active" or "revoked"
+    pub value: String, // Base64 encoded public key, or multibase if `format` says so
+    pub status: KeyStatus,
+
+    // How `value` is encoded; documents written before multibase support are all Base64.
+    #[serde(default, skip_serializing_if = "KeyFormat::is_base64")]
+    pub format: KeyFormat,
+
+    // Set on subkeys: the key they were derived from and are only valid alongside.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent_key_id: Option<String>,
+
+    // Keys written before purposes existed were all signing keys.
+    #[serde(default)]
+    pub purpose: KeyPurpose,
+
+    // After this moment the key no longer counts as active; see `Identity::key_status_at`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// What a key may be used for. A key only ever serves one purpose.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyPurpose {
+    /// Signing statements, credentials and documents.
+    #[default]
+    Signing,
+    /// Agreeing on shared secrets for encryption; never signs.
+    KeyAgreement,
+    /// Proving control of the identity in login and challenge-response flows.
+    Authentication,
+    /// Managing the identity's keys: rotation, revocation and delegation.
+    CapabilityDelegation,
+}
+
+impl KeyPurpose {
+    /// Key management (rotation, revocation, delegation) may be signed by general signing
+    /// keys or by dedicated capability-delegation keys.
+    pub fn can_manage_keys(self) -> bool {
+        matches!(self, KeyPurpose::Signing | KeyPurpose::CapabilityDelegation)
+    }
+}
+
+impl std::fmt::Display for KeyPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            KeyPurpose::Signing => "signing",
+            KeyPurpose::KeyAgreement => "key_agreement",
+            KeyPurpose::Authentication => "authentication",
+            KeyPurpose::CapabilityDelegation => "capability_delegation",
+        };
+        f.write_str(name)
+    }
+}
+
+// How a public key's `value` is written down.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyFormat {
+    /// Standard Base64 of the raw key.
+    #[default]
+    Base64,
+    /// Multibase base58btc with a multicodec prefix (`z6Mk...`), as in did:key.
+    Multibase,
+}
+
+impl KeyFormat {
+    pub fn is_base64(&self) -> bool {
+        *self == KeyFormat::Base64
+    }
+}
+
+// A signed statement that a key must no longer be trusted.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Revocation {
+    pub key_id: String,
+    pub revoked_at: DateTime<Utc>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+
+    pub signed_by: Signer,
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// A statement, signed by an outgoing root key, that its successor now speaks for the identity.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Rotation {
+    pub old_key_id: String,
+    pub new_key_id: String,
+    pub rotated_at: DateTime<Utc>,
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct CoreBlock {
     pub name: String,
     pub bio: String,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Credential {
     pub claim: String,
     pub issued_by: String,
-    pub issued_at: String,
-    
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub expires_at: Option<String>,
+
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub issued_at: DateTime<Utc>,
+
+    #[serde(default, deserialize_with = "timestamp::deserialize_option", skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
     
     pub proof: String,
+
+    // Set on credentials imported from outside IDP.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+
+    // Where the issuer publishes whether the credential was revoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<CredentialStatus>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// A credential's entry in its issuer's status list (see `status_list`).
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CredentialStatus {
+    /// What a set bit means, e.g. `revocation`.
+    pub purpose: String,
+    /// The URL the issuer publishes the list at.
+    pub list: String,
+    pub index: usize,
+}
+
+// Where an imported credential came from.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Provenance {
+    /// The form it was issued in, e.g. `vc-jwt`.
+    pub format: String,
+    /// The issuer's key, as the issuer named it.
+    pub verification_method: String,
+    pub imported_at: DateTime<Utc>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -84,11 +332,20 @@ pub struct Proof {
     pub claim_hash: String,
     pub signed_by: Signer,
     pub signature: Vec<SignatureComponent>,
+    /// Who the proof is meant for; signed with it (see `context::ProofContext`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub domain: Option<String>,
+    /// The verifier's nonce the proof answers; signed with it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub challenge: Option<String>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Signer {
-    pub idp_id: String,
+    pub idp_id: IdpId,
     pub key_id: String,
 }
 
@@ -98,19 +355,132 @@ pub struct SignatureComponent {
     pub value: String,
 }
 
+// A signature by the active root key over the rest of the document, renewed on every save.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct DocumentSignature {
+    pub signed_by: Signer,
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Contract {
     pub contract_id: String,
-    pub status: String,
+    pub status: ContractStatus,
     pub parties: Vec<String>,
+    /// The terms in words, for people; `obligations` says the same for programs.
     pub terms: String,
+    /// What each party has agreed to do.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obligations: Vec<Obligation>,
     pub consequence: Consequence,
+
+    /// When the parties must have done what they agreed; an active contract past it is overdue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline: Option<DateTime<Utc>>,
+    /// When the parties agreed to look at the contract again.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub review_at: Option<DateTime<Utc>>,
+
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signatures: Vec<ContractSignature>,
+    /// Notes the parties have signed since: disputes, evidence and resolutions.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub annotations: Vec<ContractAnnotation>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// One thing a party to a contract has agreed to do: who, what, by when, and for how much.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Obligation {
+    pub party: String,
+    pub action: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<Amount>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// A sum of money, as a decimal string so it is never rounded, e.g. `100.50` `EUR`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Amount {
+    pub value: String,
+    pub currency: String,
+}
+
+impl std::fmt::Display for Amount {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.value, self.currency)
+    }
+}
+
+// What a note on a contract records.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnotationKind {
+    Dispute,
+    Evidence,
+    Resolution,
+    Note,
+    /// A party settled the contract; the text is the outcome, `success` or `failure`.
+    Settlement,
+}
+
+impl std::fmt::Display for AnnotationKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            AnnotationKind::Dispute => "dispute",
+            AnnotationKind::Evidence => "evidence",
+            AnnotationKind::Resolution => "resolution",
+            AnnotationKind::Note => "note",
+            AnnotationKind::Settlement => "settlement",
+        })
+    }
+}
+
+// A note a party has signed on a contract, e.g. raising a dispute or pointing to evidence.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContractAnnotation {
+    pub kind: AnnotationKind,
+    pub text: String,
+    /// Where the evidence is, e.g. a URL or a document's hash.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+// One party's signature on a contract's terms, made with one of its signing keys.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ContractSignature {
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Consequence {
     pub on_success: String,
     pub on_failure: String,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -118,83 +488,435 @@ pub struct Reputation {
     pub score_name: String,
     pub value: i64,
     pub history: Vec<ReputationEvent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct ReputationEvent {
     pub event: String,
     pub change: i64,
-    pub timestamp: String,
+
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub timestamp: DateTime<Utc>,
+
+    /// Who vouches for the event; events recorded before they were signed have no issuer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issued_by: Option<Signer>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signature: Vec<SignatureComponent>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub struct Consent {
     pub granted_to: String,
     pub fields: Vec<String>,
-    pub expires_at: String,
-    pub purpose: String,
+
+    #[serde(deserialize_with = "timestamp::deserialize")]
+    pub expires_at: DateTime<Utc>,
+
+    pub purpose: ConsentPurpose,
+
+    /// When it was granted; consents recorded before this was kept have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub granted_at: Option<DateTime<Utc>>,
+    /// When it was withdrawn; a revoked consent stays listed, so the revocation is on record.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+/// What happened to a consent, as its log records it.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ConsentAction {
+    Granted,
+    Revoked,
+    Expired,
+    /// The grantee used fields under the consent.
+    Accessed,
+}
+
+impl std::fmt::Display for ConsentAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ConsentAction::Granted => "granted",
+            ConsentAction::Revoked => "revoked",
+            ConsentAction::Expired => "expired",
+            ConsentAction::Accessed => "accessed",
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConsentLogEntry {
+    /// Position in the log, from 1.
+    pub seq: u64,
+    pub at: DateTime<Utc>,
+    pub action: ConsentAction,
+    pub granted_to: String,
+    pub purpose: ConsentPurpose,
+    /// The fields granted, or accessed.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<String>,
+    /// The hash of the previous entry; empty for the first.
+    pub prev: String,
+
+    #[serde(flatten)]
+    pub unknown_fields: UnknownFields,
+}
+
+/// What a consent is for: one of the standard purposes policies can act on, or any other,
+/// in words. Written as `service_delivery`, `research`, ... or the words themselves, so
+/// documents whose purposes were free text still load.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
+pub enum ConsentPurpose {
+    Marketing,
+    Analytics,
+    ServiceDelivery,
+    LegalObligation,
+    Research,
+    Custom(String),
+}
+
+impl ConsentPurpose {
+    /// The standard purposes, by the names they are written with.
+    pub const STANDARD: [(&str, ConsentPurpose); 5] = [
+        ("marketing", ConsentPurpose::Marketing),
+        ("analytics", ConsentPurpose::Analytics),
+        ("service_delivery", ConsentPurpose::ServiceDelivery),
+        ("legal_obligation", ConsentPurpose::LegalObligation),
+        ("research", ConsentPurpose::Research),
+    ];
+
+    pub fn is_standard(&self) -> bool {
+        !matches!(self, ConsentPurpose::Custom(_))
+    }
+}
+
+impl std::fmt::Display for ConsentPurpose {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConsentPurpose::Custom(text) => f.write_str(text),
+            standard => f.write_str(ConsentPurpose::STANDARD.iter().find(|(_, purpose)| purpose == standard).map_or("", |(name, _)| name)),
+        }
+    }
+}
+
+impl std::str::FromStr for ConsentPurpose {
+    type Err = std::convert::Infallible;
+
+    /// Reads a standard purpose by its name, in any case and with `-` or spaces for `_`;
+    /// anything else is a custom purpose.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let name = text.trim().to_lowercase().replace(['-', ' '], "_");
+        Ok(ConsentPurpose::STANDARD.into_iter().find(|(standard, _)| *standard == name).map_or_else(|| ConsentPurpose::Custom(text.to_string()), |(_, purpose)| purpose))
+    }
+}
+
+impl From<String> for ConsentPurpose {
+    fn from(text: String) -> Self {
+        let Ok(purpose) = text.parse();
+        purpose
+    }
+}
+
+impl From<ConsentPurpose> for String {
+    fn from(purpose: ConsentPurpose) -> Self {
+        purpose.to_string()
+    }
+}
+
+impl Credential {
+    /// Whether the credential has lapsed by `now`; credentials without an expiry never do.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+}
+
+impl Consent {
+    /// Whether the consent has lapsed by `now`.
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        now >= self.expires_at
+    }
+
+    /// Whether the consent holds at `now`: granted by then, and neither expired nor revoked.
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && !self.is_expired(now) && self.granted_at.is_none_or(|granted_at| granted_at <= now)
+    }
+}
+
+/// The text formats an IDP document can be written in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// YAML, the default: easy to read and edit by hand.
+    #[default]
+    Yaml,
+    /// JSON, for tooling that cannot read YAML.
+    Json,
+}
+
+impl Format {
+    /// Tells a document's format from its contents: a JSON document is an object, so it
+    /// starts with `{`, which a YAML document never does.
+    pub fn detect(contents: &[u8]) -> Format {
+        match contents.iter().find(|b| !b.is_ascii_whitespace()) {
+            Some(b'{') => Format::Json,
+            _ => Format::Yaml,
+        }
+    }
+
+    /// Writes any document-shaped value (a whole document or a view of one) in this format.
+    pub fn write<T: Serialize + ?Sized>(self, value: &T) -> Result<String, IdpError> {
+        match self {
+            Format::Yaml => Ok(serde_yaml::to_string(value)?),
+            Format::Json => Ok(serde_json::to_string_pretty(value)? + "\n"),
+        }
+    }
 }
 
 // Implementation block for the Identity struct.
 impl Identity {
     /// Creates a new Identity instance, generating a new cryptographic key pair.
     /// Returns the new Identity and the secret private key bytes.
-    pub fn new(name: &str, bio: &str) -> Result<(Self, Vec<u8>), String> {
+    pub fn new(name: &str, bio: &str) -> Result<(Self, SecretBytes), IdpError> {
+        Self::new_with_algorithm(name, bio, crypto::ED25519)
+    }
+
+    /// Like `new`, with a root key of the given signature algorithm (e.g. `crypto::ML_DSA_65`).
+    pub fn new_with_algorithm(name: &str, bio: &str, algorithm: &str) -> Result<(Self, SecretBytes), IdpError> {
         // 1. Generate the cryptographic foundation.
-        let key_pair = crypto::generate_ed25519_keypair()?;
-        let public_key = key_pair.public_key;
-        let private_key_bytes = key_pair.private_key_bytes;
+        let key_pair = crypto::generate_keypair(algorithm)?;
 
-        // 2. Create the unique ID by hashing the public key.
-        let public_key_hash = digest::digest(&digest::SHA256, public_key.value.as_bytes());
-        let id = format!("idp:key:sha256:{}", BASE64.encode(public_key_hash.as_ref()));
+        // 2. Build the identity around it.
+        Self::from_key_pair(name, bio, key_pair)
+    }
+
+    /// Creates a new Identity around an Ed25519 private key the user already has:
+    /// PKCS#8 (DER or PEM) or a raw 32-byte seed.
+    /// Returns the new Identity and the private key in the form `my.key` stores.
+    pub fn from_existing_key(name: &str, bio: &str, key_material: &[u8]) -> Result<(Self, SecretBytes), IdpError> {
+        Self::from_key_pair(name, bio, crypto::import_ed25519_private_key(key_material)?)
+    }
 
-        // 3. Get a real timestamp.
+    /// Like `from_existing_key`, for a key of another algorithm that can be imported
+    /// (e.g. a wallet's `crypto::SECP256K1` secret).
+    pub fn from_existing_key_with_algorithm(name: &str, bio: &str, key_material: &[u8], algorithm: &str) -> Result<(Self, SecretBytes), IdpError> {
+        Self::from_key_pair(name, bio, crypto::import_private_key(key_material, algorithm)?)
+    }
+
+    fn from_key_pair(name: &str, bio: &str, key_pair: crypto::GeneratedKeyPair) -> Result<(Self, SecretBytes), IdpError> {
+        // 1. Build the identity around the public half, able to receive encrypted messages.
+        let mut new_identity = Self::from_public_key(name, bio, key_pair.public_key);
+        new_identity.add_agreement_key(&key_pair.private_key_bytes)?;
+
+        // 2. Return both the public identity and the secret private key.
+        Ok((new_identity, key_pair.private_key_bytes))
+    }
+
+    /// Creates a new Identity around a root public key whose private half lives elsewhere
+    /// (e.g. on a hardware token).
+    pub fn from_public_key(name: &str, bio: &str, public_key: PublicKey) -> Self {
+        // 1. Create the unique ID by hashing the public key.
+        let id = IdpId::derive_from_public_key(&public_key);
+
+        // 2. Get a real timestamp.
         let now: DateTime<Utc> = Utc::now();
 
-        // 4. Construct the full Identity struct.
-        let new_identity = Identity {
+        // 3. Construct the full Identity struct.
+        Identity {
             identity: IdentityBlock {
                 id,
                 version: "0.2.1".to_string(),
-                schema_url: "https://idp.org/schemas/v0.2.1".to_string(),
+                schema_url: schema::SCHEMA_URL.to_string(),
                 created_at: now,
                 updated_at: now,
+                unknown_fields: Default::default(),
             },
             system: SystemBlock {
                 public_keys: vec![public_key],
+                revocations: vec![],
+                rotations: vec![],
+                unknown_fields: Default::default(),
             },
             core: CoreBlock {
                 name: name.to_string(),
                 bio: bio.to_string(),
+                unknown_fields: Default::default(),
             },
             credentials: vec![],
+            archived_credentials: vec![],
             proofs: vec![],
             contracts: vec![],
             reputation: vec![],
             consent: vec![],
-        };
+            archived_consent: vec![],
+            consent_log: vec![],
+            extensions: BTreeMap::new(),
+            sealed: BTreeMap::new(),
+            signature: None,
+            integrity: None,
+            unknown_fields: Default::default(),
+        }
+    }
+
+    /// Loads an Identity from a YAML or JSON file path, compressed or not. Encrypted
+    /// documents need `load_from_file_with_key` instead.
+    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, IdpError> {
+        Self::parse(&Self::read_plain(path)?)
+    }
+
+    /// Like `load_from_file`, but keeps statuses this version does not know as `Other`.
+    pub fn load_from_file_lenient<P: AsRef<Path>>(path: P) -> Result<Self, IdpError> {
+        Self::parse_lenient(&Self::read_plain(path)?)
+    }
 
-        // 5. Return both the public identity and the secret private key.
-        Ok((new_identity, private_key_bytes))
+    fn read_plain<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, IdpError> {
+        let mut file = File::open(path)?;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        let contents = compress::decompress(contents)?;
+        if encryption::is_encrypted_document(&contents) {
+            return Err(IdpError::Encrypted);
+        }
+        Ok(contents)
     }
 
-    /// Loads an Identity from a YAML file path.
-    pub fn load_from_file<P: AsRef<Path>>(path: P) -> Result<Self, String> {
-        let mut file = File::open(path).map_err(|e| e.to_string())?;
-        let mut contents = String::new();
-        file.read_to_string(&mut contents).map_err(|e| e.to_string())?;
-        let identity: Self = serde_yaml::from_str(&contents).map_err(|e| e.to_string())?;
+    /// Parses a plain (unencrypted) document in either format, checking its integrity block.
+    pub fn parse(contents: &[u8]) -> Result<Self, IdpError> {
+        let identity: Identity = match Format::detect(contents) {
+            Format::Yaml => serde_yaml::from_slice(contents)?,
+            Format::Json => serde_json::from_slice(contents)?,
+        };
+        identity.check_integrity()?;
         Ok(identity)
     }
 
-    /// Serializes the Identity struct to YAML and saves it to a file.
-    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), String> {
-        let yaml_string = serde_yaml::to_string(self).map_err(|e| e.to_string())?;
-        let mut file = File::create(path).map_err(|e| e.to_string())?;
-        file.write_all(yaml_string.as_bytes()).map_err(|e| e.to_string())?;
+    /// Parses a document written by a newer version, keeping statuses it does not know as
+    /// `Other` instead of rejecting them.
+    pub fn parse_lenient(contents: &[u8]) -> Result<Self, IdpError> {
+        status::leniently(|| Self::parse(contents))
+    }
+
+    /// Serializes the document in the given format.
+    pub fn to_string_with_format(&self, format: Format) -> Result<String, IdpError> {
+        format.write(self)
+    }
+
+    /// Saves the Identity to a file, as it is (`save_signed_to_file` renews the envelope
+    /// signature first), in the format of the file it replaces: YAML for a new file.
+    /// Compressed files stay compressed, and new `.zst` files are written compressed.
+    pub fn save_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), IdpError> {
+        let (format, compressed) = Self::file_layout(path.as_ref())?;
+        self.write_file(path.as_ref(), format, compressed)
+    }
+
+    /// Saves the Identity in the format of the file it replaces, compressing it or not.
+    pub fn save_to_file_compressed<P: AsRef<Path>>(&self, path: P, compressed: bool) -> Result<(), IdpError> {
+        let (format, _) = Self::file_layout(path.as_ref())?;
+        self.write_file(path.as_ref(), format, compressed)
+    }
+
+    /// Serializes the Identity in the given format and saves it to a file.
+    pub fn save_to_file_with_format<P: AsRef<Path>>(&self, path: P, format: Format) -> Result<(), IdpError> {
+        self.write_file(path.as_ref(), format, compress::wants_compression(&path))
+    }
+
+    // The format of the file at `path`, and whether it is compressed.
+    fn file_layout(path: &Path) -> Result<(Format, bool), IdpError> {
+        match std::fs::read(path) {
+            Ok(contents) => {
+                let compressed = compress::is_compressed(&contents);
+                Ok((Format::detect(&compress::decompress(contents)?), compressed))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok((Format::Yaml, compress::wants_compression(path))),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn write_file(&self, path: &Path, format: Format, compressed: bool) -> Result<(), IdpError> {
+        let mut document = self.clone();
+        document.integrity = Some(self.compute_integrity()?);
+        let contents = document.to_string_with_format(format)?.into_bytes();
+        let contents = if compressed { compress::compress(&contents)? } else { contents };
+        atomic::write(path, &contents)
+    }
+
+    /// Marks the document as modified right now.
+    pub fn touch(&mut self) {
+        self.identity.updated_at = Utc::now();
+    }
+
+    /// Reads the values addressed by a dot-path (e.g. `credentials.0.claim`).
+    /// Paths may contain `*` wildcards (e.g. `credentials.*.claim`), so several values can match.
+    pub fn get_path(&self, path: &str) -> Result<Vec<serde_yaml::Value>, IdpError> {
+        let document = serde_yaml::to_value(self)?;
+        let values: Vec<serde_yaml::Value> = path::query(&document, path)?.into_iter().cloned().collect();
+
+        // A wildcard may legitimately match nothing; a plain path must exist.
+        if values.is_empty() && !path::is_wildcard(path) {
+            return Err(IdpError::Path {
+                path: path.to_string(),
+                reason: "no value at this path".to_string(),
+            });
+        }
+        Ok(values)
+    }
+
+    /// Sets the field addressed by a dot-path (e.g. `system.public_keys.0.status`).
+    /// The change is only applied if the result is still a valid IDP document.
+    pub fn set_path(&mut self, path: &str, value: serde_yaml::Value) -> Result<(), IdpError> {
+        // 1. Work on a generic YAML tree so any field can be addressed.
+        let mut document = serde_yaml::to_value(&*self)?;
+        path::set(&mut document, path, value)?;
+
+        // 2. Convert back, which type-checks the new value against the data model. The error
+        //    is about the value at `path`, not about the file, and names what would fit.
+        let updated: Self = serde_yaml::from_value(document).map_err(|e| IdpError::Path {
+            path: path.to_string(),
+            reason: format!("the value does not fit: {}", e),
+        })?;
+
+        // 3. Make sure the value landed in a known field: a new key would otherwise be kept
+        //    as an unknown field, and a typo like `core.nickame` would go unnoticed. The value
+        //    may read back normalized (a time in another offset comes back in UTC), so only the
+        //    path is looked for.
+        let landed = path::get(&serde_yaml::to_value(&updated)?, path)?.is_some();
+        if !landed || updated.unknown_field_count() > self.unknown_field_count() {
+            return Err(IdpError::Path {
+                path: path.to_string(),
+                reason: "no such field in the IDP document".to_string(),
+            });
+        }
+
+        *self = updated;
         Ok(())
     }
+
+    // How many unknown fields the document carries across all of its blocks.
+    fn unknown_field_count(&self) -> usize {
+        let system = &self.system;
+        [self.unknown_fields.len(), self.identity.unknown_fields.len(), system.unknown_fields.len(), self.core.unknown_fields.len()]
+            .into_iter()
+            .chain(system.public_keys.iter().map(|k| k.unknown_fields.len()))
+            .chain(system.revocations.iter().map(|r| r.unknown_fields.len()))
+            .chain(system.rotations.iter().map(|r| r.unknown_fields.len()))
+            .chain(self.credentials.iter().chain(&self.archived_credentials).map(|c| c.unknown_fields.len()))
+            .chain(self.proofs.iter().map(|p| p.unknown_fields.len()))
+            .chain(self.contracts.iter().map(|c| c.unknown_fields.len() + c.consequence.unknown_fields.len() + c.obligations.iter().map(|o| o.unknown_fields.len()).sum::<usize>() + c.signatures.iter().map(|s| s.unknown_fields.len()).sum::<usize>() + c.annotations.iter().map(|a| a.unknown_fields.len()).sum::<usize>()))
+            .chain(self.reputation.iter().map(|r| r.unknown_fields.len() + r.history.iter().map(|e| e.unknown_fields.len()).sum::<usize>()))
+            .chain(self.consent.iter().chain(&self.archived_consent).map(|c| c.unknown_fields.len()))
+            .chain(self.consent_log.iter().map(|e| e.unknown_fields.len()))
+            .chain(self.signature.iter().map(|s| s.unknown_fields.len()))
+            .sum()
+    }
 }
 
 // This module contains all tests for the idp-core library.
@@ -206,34 +928,63 @@ mod tests {
     fn it_can_be_created() {
         let identity = Identity {
             identity: IdentityBlock {
-                id: "idp:key:123".to_string(),
+                id: IdpId::parse("idp:key:sha256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=").unwrap(),
                 version: "0.2.1".to_string(),
                 schema_url: "https://idp.org/schemas/v0.2.1".to_string(),
                 created_at: Utc::now(), // Updated to use chrono
                 updated_at: Utc::now(), // Updated to use chrono
+                unknown_fields: Default::default(),
             },
             system: SystemBlock {
                 public_keys: vec![PublicKey {
                     key_id: "root-key-01".to_string(),
                     algorithm: "Ed25519".to_string(),
                     value: "BASE64_KEY_HERE".to_string(),
-                    status: "active".to_string(),
+                    format: KeyFormat::Base64,
+                    status: KeyStatus::Active,
+                    parent_key_id: None,
+                    purpose: KeyPurpose::Signing,
+                    expires_at: None,
+                    unknown_fields: Default::default(),
                 }],
+                revocations: vec![],
+                rotations: vec![],
+                unknown_fields: Default::default(),
             },
             core: CoreBlock {
                 name: "Clein Pius".to_string(),
                 bio: "Founder of IDP.".to_string(),
+                unknown_fields: Default::default(),
             },
             credentials: vec![],
+            archived_credentials: vec![],
             proofs: vec![],
             contracts: vec![],
             reputation: vec![],
             consent: vec![],
+            archived_consent: vec![],
+            consent_log: vec![],
+            extensions: BTreeMap::new(),
+            sealed: BTreeMap::new(),
+            signature: None,
+            integrity: None,
+            unknown_fields: Default::default(),
         };
         assert_eq!(identity.core.name, "Clein Pius");
         println!("✅ Smoke test passed: Identity struct created successfully.");
     }
 
+    #[test]
+    fn it_can_be_created_from_an_existing_key() {
+        let existing = crypto::generate_ed25519_keypair().unwrap();
+        let seed = crypto::ed25519_seed(&existing.private_key_bytes).unwrap();
+
+        let (identity, private_key) = Identity::from_existing_key("Imported User", "Brought my own key.", seed.as_slice()).unwrap();
+        assert_eq!(identity.system.public_keys[0].value, existing.public_key.value);
+        assert_eq!(private_key, existing.private_key_bytes);
+        println!("✅ Test passed: Identity created from an existing key.");
+    }
+
     #[test]
     fn it_can_load_from_a_file() {
         let dir = tempfile::tempdir().unwrap();
@@ -241,7 +992,7 @@ mod tests {
         // Using a real timestamp string format that serde_yaml + chrono can parse.
         let sample_idp_content = r#"
 identity:
-  id: "idp:key:clein_001"
+  id: "idp:key:sha256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU="
   version: "0.2.1"
   schema_url: "https://idp.org/schemas/v0.2.1"
   created_at: "2024-07-06T10:00:00Z" 
@@ -254,7 +1005,7 @@ core:
 "#;
         std::fs::write(&file_path, sample_idp_content).unwrap();
         let loaded_identity = Identity::load_from_file(&file_path).unwrap();
-        assert_eq!(loaded_identity.identity.id, "idp:key:clein_001");
+        assert_eq!(loaded_identity.identity.id, "idp:key:sha256:47DEQpj8HBSa+/TImW+5JCeuQeRkm5NMpJWZG3hSuFU=");
         assert_eq!(loaded_identity.core.name, "Clein Pius");
         println!("✅ Test passed: Identity loaded successfully from file.");
     }
@@ -262,7 +1013,7 @@ core:
     #[test]
     fn it_can_perform_a_save_and_load_round_trip() {
         // 1. SETUP
-        let original_identity = Identity::new("Round Trip User", "Testing the save/load cycle.").unwrap().0;
+        let mut original_identity = Identity::new("Round Trip User", "Testing the save/load cycle.").unwrap().0;
         
         let dir = tempfile::tempdir().unwrap();
         let file_path = dir.path().join("round_trip.idp");
@@ -273,8 +1024,98 @@ core:
         // 3. ACTION 2: Load
         let loaded_identity = Identity::load_from_file(&file_path).unwrap();
         
-        // 4. VERIFICATION
+        // 4. VERIFICATION: the file also carries the digest of what was saved.
+        original_identity.integrity = Some(original_identity.compute_integrity().unwrap());
         assert_eq!(original_identity, loaded_identity);
         println!("✅ Test passed: Save/load round-trip completed successfully.");
     }
+
+    #[test]
+    fn it_keeps_fields_it_does_not_know() {
+        let (identity, _) = Identity::new("Future User", "Written by a newer tool.").unwrap();
+        let yaml = serde_yaml::to_string(&identity)
+            .unwrap()
+            .replacen("core:\n", "core:\n  pronouns: they/them\n", 1)
+            .replacen("status: active\n", "status: active\n    attested_by: [hsm]\n", 1)
+            + "badges:\n- early-adopter\n";
+
+        let loaded = Identity::parse(yaml.as_bytes()).unwrap();
+        assert_eq!(loaded.core.unknown_fields["pronouns"], serde_yaml::Value::from("they/them"));
+        assert!(loaded.system.public_keys[0].unknown_fields.contains_key("attested_by"));
+
+        // Every format writes them back unchanged.
+        for format in [Format::Yaml, Format::Json] {
+            let saved = Identity::parse(loaded.to_string_with_format(format).unwrap().as_bytes()).unwrap();
+            assert_eq!(saved, loaded);
+        }
+        assert_eq!(Identity::from_cbor(&loaded.to_cbor().unwrap()).unwrap(), loaded);
+        assert!(serde_yaml::to_string(&loaded).unwrap().contains("badges:\n- early-adopter"));
+    }
+
+    #[test]
+    fn it_reads_and_writes_json() {
+        let (mut identity, _) = Identity::new("JSON User", "No YAML here.").unwrap();
+        let dir = tempfile::tempdir().unwrap();
+        let file_path = dir.path().join("json.idp");
+
+        // The format is detected on load, and kept by later saves.
+        identity.save_to_file_with_format(&file_path, Format::Json).unwrap();
+        assert!(std::fs::read_to_string(&file_path).unwrap().starts_with("{\n  \"identity\": {"));
+        identity.integrity = Some(identity.compute_integrity().unwrap());
+        assert_eq!(Identity::load_from_file(&file_path).unwrap(), identity);
+        identity.save_to_file(&file_path).unwrap();
+        assert_eq!(Format::detect(&std::fs::read(&file_path).unwrap()), Format::Json);
+
+        let garbage = Identity::parse(b"{\"identity\": []}").unwrap_err();
+        assert!(matches!(garbage, IdpError::Json(_)));
+    }
+
+    #[test]
+    fn it_reports_structured_errors_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+
+        // A missing file is an I/O error we can match on.
+        let missing = Identity::load_from_file(dir.path().join("missing.idp")).unwrap_err();
+        assert!(matches!(missing, IdpError::Io(ref e) if e.kind() == std::io::ErrorKind::NotFound));
+
+        // A file that isn't an IDP document is a YAML error.
+        let garbage_path = dir.path().join("garbage.idp");
+        std::fs::write(&garbage_path, "identity: [not, a, block]").unwrap();
+        let garbage = Identity::load_from_file(&garbage_path).unwrap_err();
+        assert!(matches!(garbage, IdpError::Yaml(_)));
+        println!("✅ Test passed: Load failures are reported as structured errors.");
+    }
+
+    #[test]
+    fn it_can_set_a_field_by_path() {
+        let mut identity = Identity::new("Path User", "Old bio.").unwrap().0;
+
+        identity.set_path("core.bio", "New bio.".into()).unwrap();
+        identity.set_path("system.public_keys.0.status", "revoked".into()).unwrap();
+        assert_eq!(identity.core.bio, "New bio.");
+        assert_eq!(identity.system.public_keys[0].status, KeyStatus::Revoked);
+
+        // Values the data model normalizes are taken as they read back.
+        identity.set_path("system.public_keys.0.expires_at", "2030-01-01T02:00:00+02:00".into()).unwrap();
+        assert_eq!(identity.system.public_keys[0].expires_at, Some("2030-01-01T00:00:00Z".parse().unwrap()));
+
+        // Fields outside the data model and values of the wrong type are rejected untouched.
+        assert!(matches!(identity.set_path("core.nickname", "x".into()), Err(IdpError::Path { .. })));
+        assert!(matches!(identity.set_path("core.bio", vec!["a", "b"].into()), Err(IdpError::Path { .. })));
+        let misspelled = identity.set_path("system.public_keys.0.status", "actve".into()).unwrap_err().to_string();
+        assert!(misspelled.contains("active, superseded, revoked"), "{}", misspelled);
+        assert_eq!(identity.core.bio, "New bio.");
+        println!("✅ Test passed: Fields can be set by dot-path.");
+    }
+
+    #[test]
+    fn it_can_get_fields_by_path() {
+        let identity = Identity::new("Path User", "A bio.").unwrap().0;
+
+        assert_eq!(identity.get_path("core.name").unwrap(), vec![serde_yaml::Value::from("Path User")]);
+        assert_eq!(identity.get_path("system.public_keys.*.status").unwrap(), vec![serde_yaml::Value::from("active"); 2]);
+        assert!(identity.get_path("credentials.*.claim").unwrap().is_empty());
+        assert!(matches!(identity.get_path("core.nickname"), Err(IdpError::Path { .. })));
+        println!("✅ Test passed: Fields can be read by dot-path.");
+    }
 }
@@ -0,0 +1,91 @@
+// crates/idp-core/src/lock.rs
+
+// Advisory locks that keep two processes from changing an identity file at the same time.
+// The lock is taken on a `.lock` file next to the document, since saves replace the document
+// itself. Readers share the lock; a writer needs it alone. Locks are released on drop.
+
+use std::fs::{File, OpenOptions, TryLockError};
+use std::path::{Path, PathBuf};
+
+use crate::{Identity, IdpError};
+
+/// How a lock is shared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockMode {
+    /// For reading: any number of readers, but no writer.
+    Shared,
+    /// For a load-modify-save cycle: no one else.
+    Exclusive,
+}
+
+/// A held lock on an identity file.
+#[derive(Debug)]
+pub struct FileLock {
+    _file: File,
+}
+
+impl FileLock {
+    /// Takes the lock on the document at `path`, failing at once if another process holds it.
+    pub fn acquire(path: &Path, mode: LockMode) -> Result<FileLock, IdpError> {
+        let file = OpenOptions::new().create(true).truncate(false).write(true).open(lock_path(path))?;
+        let taken = match mode {
+            LockMode::Shared => file.try_lock_shared(),
+            LockMode::Exclusive => file.try_lock(),
+        };
+        match taken {
+            Ok(()) => Ok(FileLock { _file: file }),
+            Err(TryLockError::WouldBlock) => Err(IdpError::Locked(path.display().to_string())),
+            Err(TryLockError::Error(e)) => Err(e.into()),
+        }
+    }
+}
+
+/// The file the lock of the document at `path` is taken on (`my.idp.lock` for `my.idp`).
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut lock_path = path.as_os_str().to_owned();
+    lock_path.push(".lock");
+    PathBuf::from(lock_path)
+}
+
+impl Identity {
+    /// Loads the document at `path`, lets `change` edit it and saves it, holding the
+    /// exclusive lock throughout. Nothing is saved if `change` fails.
+    pub fn update_file<P: AsRef<Path>, T>(path: P, change: impl FnOnce(&mut Identity) -> Result<T, IdpError>) -> Result<T, IdpError> {
+        let _lock = FileLock::acquire(path.as_ref(), LockMode::Exclusive)?;
+        let mut identity = Identity::load_from_file(path.as_ref())?;
+        let result = change(&mut identity)?;
+        identity.save_to_file(path.as_ref())?;
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_keeps_writers_apart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.idp");
+        let (identity, _) = Identity::new("Locked User", "One at a time.").unwrap();
+        identity.save_to_file(&path).unwrap();
+
+        // Readers share the lock, and keep writers out while they hold it.
+        let reader = FileLock::acquire(&path, LockMode::Shared).unwrap();
+        let other_reader = FileLock::acquire(&path, LockMode::Shared).unwrap();
+        assert!(matches!(FileLock::acquire(&path, LockMode::Exclusive), Err(IdpError::Locked(_))));
+        let update = Identity::update_file(&path, |identity| {
+            identity.core.bio = "Changed.".to_string();
+            Ok(())
+        });
+        assert!(matches!(update, Err(IdpError::Locked(_))));
+        drop((reader, other_reader));
+
+        Identity::update_file(&path, |identity| {
+            identity.core.bio = "Changed.".to_string();
+            Ok(())
+        })
+        .unwrap();
+        assert_eq!(Identity::load_from_file(&path).unwrap().core.bio, "Changed.");
+    }
+}
@@ -0,0 +1,223 @@
+// crates/idp-core/src/merge.rs
+
+// Three-way merges of a document edited on two devices. Each value takes whichever side changed
+// it from the common base; lists of records (keys, proofs, credentials...) are merged record by
+// record, so both sides can add entries. Anything both sides changed differently is a conflict.
+
+use std::fmt;
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{Identity, IdpError};
+
+/// A value both sides changed, in different ways. A side is `None` where the value is absent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    pub path: String,
+    pub base: Option<Value>,
+    pub ours: Option<Value>,
+    pub theirs: Option<Value>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let side = |value: &Option<Value>| match value {
+            Some(value) => serde_json::to_string(value).unwrap_or_else(|_| format!("{:?}", value)),
+            None => "(absent)".to_string(),
+        };
+        write!(f, "{}: ours {}, theirs {}", self.path, side(&self.ours), side(&self.theirs))
+    }
+}
+
+/// The result of `Identity::merge`. Where there are conflicts, `merged` keeps our side of them.
+#[derive(Debug, Clone)]
+pub struct Merge {
+    pub merged: Identity,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+// The lists merged record by record, and the fields that tell their records apart. Credentials
+// have no id, so a credential is only the same record if it is identical; so is an archived
+// consent, since the same grant may have lapsed more than once.
+const KEYED_LISTS: [(&str, &[&str]); 10] = [
+    ("system.public_keys", &["key_id"]),
+    ("system.revocations", &["key_id"]),
+    ("system.rotations", &["old_key_id"]),
+    ("credentials", &[]),
+    ("archived_credentials", &[]),
+    ("proofs", &["proof_id"]),
+    ("contracts", &["contract_id"]),
+    ("consent", &["granted_to", "purpose"]),
+    ("archived_consent", &[]),
+    ("consent_log", &["seq"]),
+];
+
+impl Identity {
+    /// Merges two versions of a document that both started from `base`. The merged document
+    /// is unsigned, since neither side's signature covers it, and `updated_at` is the later one.
+    /// Its integrity digest is renewed when it is saved.
+    pub fn merge(base: &Identity, ours: &Identity, theirs: &Identity) -> Result<Merge, IdpError> {
+        // 1. Merge the generic trees.
+        let mut conflicts = Vec::new();
+        let merged = merge_values(
+            &mut Vec::new(),
+            Some(&serde_yaml::to_value(base)?),
+            Some(&serde_yaml::to_value(ours)?),
+            Some(&serde_yaml::to_value(theirs)?),
+            &mut conflicts,
+        );
+        let mut merged: Identity = serde_yaml::from_value(merged.unwrap_or(Value::Null))?;
+
+        // 2. Every save moves `updated_at` and renews the signature and digest, so those never conflict.
+        merged.identity.updated_at = ours.identity.updated_at.max(theirs.identity.updated_at);
+        merged.signature = None;
+        let renewed = |path: &str| ["signature", "integrity"].iter().any(|block| path == *block || path.starts_with(&format!("{}.", block)));
+        conflicts.retain(|c| c.path != "identity.updated_at" && !renewed(&c.path));
+        Ok(Merge { merged, conflicts })
+    }
+}
+
+fn merge_values(path: &mut Vec<String>, base: Option<&Value>, ours: Option<&Value>, theirs: Option<&Value>, conflicts: &mut Vec<Conflict>) -> Option<Value> {
+    // 1. Whichever side left the value as it was takes the other side's version.
+    if ours == theirs || theirs == base {
+        return ours.cloned();
+    }
+    if ours == base {
+        return theirs.cloned();
+    }
+
+    // 2. Both changed it: look inside, or give up. Empty record lists are left out of documents.
+    if let (Some(fields), Some(ours_items), Some(theirs_items)) = (record_fields(path), records(ours), records(theirs)) {
+        let base_items = base.and_then(Value::as_sequence).map(Vec::as_slice).unwrap_or_default();
+        return Some(Value::Sequence(merge_records(path, fields, base_items, ours_items, theirs_items, conflicts)));
+    }
+    match (ours, theirs) {
+        (Some(Value::Mapping(ours_map)), Some(Value::Mapping(theirs_map))) => {
+            let base_map = base.and_then(Value::as_mapping);
+            let mut merged = Mapping::new();
+            for key in ours_map.keys().chain(theirs_map.keys().filter(|key| !ours_map.contains_key(*key))) {
+                path.push(key.as_str().unwrap_or_default().to_string());
+                if let Some(value) = merge_values(path, base_map.and_then(|m| m.get(key)), ours_map.get(key), theirs_map.get(key), conflicts) {
+                    merged.insert(key.clone(), value);
+                }
+                path.pop();
+            }
+            Some(Value::Mapping(merged))
+        }
+        _ => {
+            conflicts.push(Conflict { path: path.join("."), base: base.cloned(), ours: ours.cloned(), theirs: theirs.cloned() });
+            ours.cloned()
+        }
+    }
+}
+
+// Merges lists of records matched up by their identifying fields: our records in our order,
+// then records only they have.
+fn merge_records(path: &mut Vec<String>, fields: &[&str], base: &[Value], ours: &[Value], theirs: &[Value], conflicts: &mut Vec<Conflict>) -> Vec<Value> {
+    let identity_of = |record: &Value| -> Value {
+        match fields.is_empty() {
+            true => record.clone(),
+            false => Value::Sequence(fields.iter().map(|field| record.get(*field).cloned().unwrap_or(Value::Null)).collect()),
+        }
+    };
+    let find = |records: &[Value], id: &Value| records.iter().find(|record| identity_of(record) == *id).cloned();
+
+    let mut ids: Vec<Value> = ours.iter().map(identity_of).collect();
+    ids.extend(theirs.iter().map(identity_of).filter(|id| find(ours, id).is_none()));
+    ids.extend(base.iter().map(identity_of).filter(|id| find(ours, id).is_none() && find(theirs, id).is_none()));
+
+    let mut merged = Vec::new();
+    for id in ids {
+        path.push(merged.len().to_string());
+        let record = merge_values(path, find(base, &id).as_ref(), find(ours, &id).as_ref(), find(theirs, &id).as_ref(), conflicts);
+        merged.extend(record);
+        path.pop();
+    }
+    merged
+}
+
+// A side of a record list: absent counts as empty, anything but a list as no list at all.
+fn records(side: Option<&Value>) -> Option<&[Value]> {
+    match side {
+        None => Some(&[]),
+        Some(value) => value.as_sequence().map(Vec::as_slice),
+    }
+}
+
+fn record_fields(path: &[String]) -> Option<&'static [&'static str]> {
+    let joined = path.join(".");
+    KEYED_LISTS.iter().find(|(list, _)| *list == joined).map(|(_, fields)| *fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Consent, ConsentPurpose, Credential};
+
+    fn credential(claim: &str) -> Credential {
+        Credential {
+            claim: claim.to_string(),
+            issued_by: "issuer".to_string(),
+            issued_at: "2024-07-06T10:00:00Z".parse().unwrap(),
+            expires_at: None,
+            proof: String::new(),
+            provenance: None,
+            status: None,
+            unknown_fields: Default::default(),
+        }
+    }
+
+    #[test]
+    fn it_merges_edits_from_two_devices() {
+        let (base, _) = Identity::new("Merge User", "Base bio.").unwrap();
+
+        // Each side adds a credential; one edits the bio, the other the name.
+        let mut ours = base.clone();
+        ours.core.bio = "Our bio.".to_string();
+        ours.credentials.push(credential("ours"));
+        let mut theirs = base.clone();
+        theirs.core.name = "Their Name".to_string();
+        theirs.credentials.push(credential("theirs"));
+        theirs.identity.updated_at = base.identity.updated_at + chrono::Duration::seconds(5);
+
+        let merge = Identity::merge(&base, &ours, &theirs).unwrap();
+        assert!(merge.is_clean(), "{:?}", merge.conflicts);
+        assert_eq!((merge.merged.core.name.as_str(), merge.merged.core.bio.as_str()), ("Their Name", "Our bio."));
+        assert_eq!(merge.merged.credentials, [credential("ours"), credential("theirs")]);
+        assert_eq!(merge.merged.identity.updated_at, theirs.identity.updated_at);
+    }
+
+    #[test]
+    fn it_reports_conflicting_edits() {
+        let (mut base, _) = Identity::new("Merge User", "Base bio.").unwrap();
+        let consent = Consent {
+            granted_to: "shop".to_string(),
+            fields: vec!["core.name".to_string()],
+            expires_at: "2030-01-01T00:00:00Z".parse().unwrap(),
+            purpose: ConsentPurpose::ServiceDelivery,
+            granted_at: None,
+            revoked_at: None,
+            unknown_fields: Default::default(),
+        };
+        base.consent.push(consent.clone());
+
+        let mut ours = base.clone();
+        ours.core.bio = "Our bio.".to_string();
+        ours.consent[0].expires_at = "2031-01-01T00:00:00Z".parse().unwrap();
+        let mut theirs = base.clone();
+        theirs.core.bio = "Their bio.".to_string();
+        theirs.consent.clear();
+
+        let merge = Identity::merge(&base, &ours, &theirs).unwrap();
+        let paths: Vec<&str> = merge.conflicts.iter().map(|c| c.path.as_str()).collect();
+        assert_eq!(paths, ["core.bio", "consent.0"]);
+        assert_eq!(merge.merged.core.bio, "Our bio.");
+        assert_eq!(merge.conflicts[1].theirs, None);
+    }
+}
@@ -0,0 +1,157 @@
+// crates/idp-core/src/multibase.rs
+
+// Multibase / multicodec form of public keys (`z6Mk...`), the encoding did:key, DID documents
+// and IPLD expect. Documents keep Base64 by default; `PublicKey.format` records which one a
+// key's `value` is written in, and everything that reads the key goes through `raw_value`.
+
+use data_encoding::BASE64;
+
+use crate::{IdpError, KeyFormat, PublicKey};
+
+/// The multibase prefix for base58btc, the only base IDP writes.
+const BASE58BTC: char = 'z';
+
+// Multicodec codes for the key types IDP holds, as unsigned varints.
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+const X25519_PUB: [u8; 2] = [0xec, 0x01];
+const SECP256K1_PUB: [u8; 2] = [0xe7, 0x01];
+
+/// Encodes a raw public key as multibase base58btc with its multicodec prefix.
+pub fn encode(algorithm: &str, raw_public_key: &[u8]) -> Result<String, IdpError> {
+    let codec = match algorithm {
+        "Ed25519" => ED25519_PUB,
+        "X25519" => X25519_PUB,
+        "secp256k1" => SECP256K1_PUB,
+        other => return Err(IdpError::Key(format!("no multicodec is known for '{}' keys", other))),
+    };
+    let mut bytes = codec.to_vec();
+    bytes.extend(raw_public_key);
+    Ok(format!("{}{}", BASE58BTC, bs58::encode(bytes).into_string()))
+}
+
+/// Decodes a multibase key into its algorithm and raw bytes.
+pub fn decode(value: &str) -> Result<(&'static str, Vec<u8>), IdpError> {
+    // 1. Only base58btc is accepted; it is what every DID method uses for keys.
+    let encoded = value
+        .strip_prefix(BASE58BTC)
+        .ok_or_else(|| IdpError::Key(format!("'{}' is not a base58btc multibase value", value)))?;
+    let bytes = bs58::decode(encoded)
+        .into_vec()
+        .map_err(|_| IdpError::Key(format!("'{}' is not valid base58btc", value)))?;
+
+    // 2. The multicodec prefix names the key type.
+    let (algorithm, raw) = match bytes.split_at_checked(2) {
+        Some((codec, raw)) if codec == ED25519_PUB => ("Ed25519", raw),
+        Some((codec, raw)) if codec == X25519_PUB => ("X25519", raw),
+        Some((codec, raw)) if codec == SECP256K1_PUB => ("secp256k1", raw),
+        _ => return Err(IdpError::Key(format!("'{}' does not carry a supported multicodec key", value))),
+    };
+    // secp256k1 keys are compressed SEC1 points: a parity byte and the x coordinate.
+    let key_len = if algorithm == "secp256k1" { 33 } else { 32 };
+    if raw.len() != key_len {
+        return Err(IdpError::Key(format!("'{}' does not hold a {}-byte {} key", value, key_len, algorithm)));
+    }
+    Ok((algorithm, raw.to_vec()))
+}
+
+impl PublicKey {
+    /// The raw public key, decoded according to the key's `format`.
+    pub fn raw_value(&self) -> Result<Vec<u8>, IdpError> {
+        match self.format {
+            KeyFormat::Base64 => BASE64
+                .decode(self.value.as_bytes())
+                .map_err(|_| IdpError::Key(format!("the value of '{}' is not valid Base64", self.key_id))),
+            KeyFormat::Multibase => {
+                let (algorithm, raw) = decode(&self.value)?;
+                if algorithm != self.algorithm {
+                    return Err(IdpError::Key(format!("the value of '{}' is a {} key", self.key_id, algorithm)));
+                }
+                Ok(raw)
+            }
+        }
+    }
+
+    /// The Base64 form of the key, whatever its `format`. Signed statements and the IDP id
+    /// are built from it, so re-encoding a key never invalidates them. A value that cannot be
+    /// decoded is returned as written.
+    pub fn canonical_value(&self) -> String {
+        match (self.format, self.raw_value()) {
+            (KeyFormat::Multibase, Ok(raw)) => BASE64.encode(&raw),
+            _ => self.value.clone(),
+        }
+    }
+
+    /// Whether this key is the one with the given Base64 value (as reported by signers and
+    /// key stores).
+    pub fn has_value(&self, base64_value: &str) -> bool {
+        self.canonical_value() == base64_value
+    }
+
+    /// The key as a multibase string, as used in did:key and `publicKeyMultibase`.
+    pub fn to_multibase(&self) -> Result<String, IdpError> {
+        encode(&self.algorithm, &self.raw_value()?)
+    }
+
+    /// Rewrites `value` in another format.
+    pub fn set_format(&mut self, format: KeyFormat) -> Result<(), IdpError> {
+        let raw = self.raw_value()?;
+        self.value = match format {
+            KeyFormat::Base64 => BASE64.encode(&raw),
+            KeyFormat::Multibase => encode(&self.algorithm, &raw)?,
+        };
+        self.format = format;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{crypto, Identity};
+
+    #[test]
+    fn it_encodes_keys_as_multibase() {
+        // An Ed25519 key from the did:key test vectors.
+        let raw = BASE64.decode(b"Lm/M42cB3HkUiODQsXRcweM6TByfzEHGO9ND274JcOY=").unwrap();
+        let value = encode("Ed25519", &raw).unwrap();
+        assert_eq!(value, "z6MkhaXgBZDvotDkL5257faiztiGiC2QtKLGpbnnEGta2doK");
+        assert_eq!(decode(&value).unwrap(), ("Ed25519", raw));
+
+        // Compressed secp256k1 keys get the `zQ3s` prefix of did:key.
+        let secp256k1 = encode("secp256k1", &[2; 33]).unwrap();
+        assert!(secp256k1.starts_with("zQ3s"));
+        assert_eq!(decode(&secp256k1).unwrap(), ("secp256k1", vec![2; 33]));
+
+        assert!(decode("mAAAA").is_err());
+        assert!(encode("P-256", &[0; 33]).is_err());
+    }
+
+    #[test]
+    fn it_keeps_keys_usable_in_either_format() {
+        let (mut identity, private_key) = Identity::new("Multibase User", "Uses did:key.").unwrap();
+        let id = identity.identity.id.clone();
+        let base64_value = identity.system.public_keys[0].value.clone();
+
+        for key in &mut identity.system.public_keys {
+            key.set_format(KeyFormat::Multibase).unwrap();
+        }
+        let key = &identity.system.public_keys[0];
+        assert!(key.value.starts_with("z6Mk"));
+        assert!(identity.agreement_key().unwrap().value.starts_with("z6LS"));
+        assert!(key.has_value(&base64_value));
+        assert_eq!(key.canonical_value(), base64_value);
+
+        // Signing still finds the key, and the format survives a save.
+        let signature = crypto::sign(&private_key, b"multibase").unwrap();
+        crypto::verify(key, b"multibase", &signature).unwrap();
+        assert_eq!(identity.key_for_private_key(&private_key).unwrap().key_id, key.key_id);
+        let yaml = serde_yaml::to_string(&identity).unwrap();
+        let reloaded: Identity = serde_yaml::from_str(&yaml).unwrap();
+        assert_eq!(reloaded.system.public_keys[0].format, KeyFormat::Multibase);
+        assert_eq!(reloaded.identity.id, id);
+
+        let mut key = reloaded.system.public_keys[0].clone();
+        key.set_format(KeyFormat::Base64).unwrap();
+        assert_eq!(key.value, base64_value);
+    }
+}
@@ -0,0 +1,592 @@
+// crates/idp-core/src/openpgp.rs
+
+// OpenPGP interoperability (RFC 4880 with the EdDSA extension from RFC 4880bis).
+// An identity's root key can be exported as an OpenPGP certificate, and certifications
+// other people make on that certificate with their PGP keys can be imported as IDP proofs,
+// so an existing web of trust carries over. Only the packets needed for this are supported.
+
+use data_encoding::{BASE64, HEXUPPER};
+use ring::digest;
+
+use crate::crypto::{self, VerifyError};
+use crate::signer::SigningBackend;
+use crate::{id, Identity, IdpError, IdpId, KeyFormat, KeyPurpose, KeyStatus, Proof, PublicKey, SignatureComponent, Signer};
+
+/// Proof type for a third-party OpenPGP certification of the identity's key.
+pub const OPENPGP_CERTIFICATION_PROOF: &str = "OpenPgpCertification";
+
+// Packet tags.
+const TAG_SIGNATURE: u8 = 2;
+const TAG_PUBLIC_KEY: u8 = 6;
+const TAG_USER_ID: u8 = 13;
+
+// Algorithm ids and the Ed25519 curve OID (1.3.6.1.4.1.11591.15.1).
+const EDDSA: u8 = 22;
+const HASH_SHA256: u8 = 8;
+const ED25519_OID: &[u8] = &[0x2B, 0x06, 0x01, 0x04, 0x01, 0xDA, 0x47, 0x0F, 0x01];
+
+// Signature types and subpackets.
+const POSITIVE_CERTIFICATION: u8 = 0x13;
+const CERTIFICATIONS: std::ops::RangeInclusive<u8> = 0x10..=0x13;
+const SUBPACKET_CREATION_TIME: u8 = 2;
+const SUBPACKET_ISSUER: u8 = 16;
+const SUBPACKET_KEY_FLAGS: u8 = 27;
+const SUBPACKET_ISSUER_FINGERPRINT: u8 = 33;
+
+const PUBLIC_KEY_BLOCK: &str = "PGP PUBLIC KEY BLOCK";
+
+impl Identity {
+    /// Exports the active root key as an ASCII-armored OpenPGP certificate, with the
+    /// name and bio as its user id, self-signed by `signer`.
+    pub fn to_openpgp(&self, signer: &dyn SigningBackend) -> Result<String, IdpError> {
+        let key = self.key_for_private_key(signer)?;
+        let key_body = self.openpgp_key_body(key)?;
+        let user_id = self.openpgp_user_id();
+
+        let mut certificate = packet(TAG_PUBLIC_KEY, &key_body);
+        certificate.extend(packet(TAG_USER_ID, user_id.as_bytes()));
+        certificate.extend(packet(TAG_SIGNATURE, &certify(&key_body, &user_id, &key_body, signer)?));
+        Ok(armor(PUBLIC_KEY_BLOCK, &certificate))
+    }
+
+    /// The OpenPGP v4 fingerprint `to_openpgp` gives a key, as uppercase hex.
+    pub fn openpgp_fingerprint(&self, key_id: &str) -> Result<String, IdpError> {
+        let key = self.find_key(key_id).ok_or_else(|| IdpError::Key(format!("unknown key '{}'", key_id)))?;
+        Ok(HEXUPPER.encode(&fingerprint(&self.openpgp_key_body(key)?)))
+    }
+
+    /// Imports the certifications found on a copy of our OpenPGP certificate (as returned
+    /// by someone who signed it with `gpg --sign-key`). Each certification made by one of
+    /// `signer_certificates` is verified and recorded as a proof; returns how many were added.
+    pub fn import_openpgp_certifications(
+        &mut self,
+        certificate: &str,
+        signer_certificates: &[&str],
+    ) -> Result<usize, IdpError> {
+        // 1. The certificate must be for one of our keys.
+        let packets = parse_packets(&dearmor(certificate)?)?;
+        let key_body = match packets.first() {
+            Some((TAG_PUBLIC_KEY, body)) => body.clone(),
+            _ => return Err(malformed("the certificate does not start with a public key")),
+        };
+        let key_value = BASE64.encode(&eddsa_public_key(&key_body)?);
+        if !self.system.public_keys.iter().any(|k| k.has_value(&key_value)) {
+            return Err(IdpError::Key("the certificate is not for a key of this identity".to_string()));
+        }
+        let own_fingerprint = fingerprint(&key_body);
+
+        // 2. The keys we can check certifications against.
+        let signers = signer_certificates
+            .iter()
+            .map(|armored| {
+                let packets = parse_packets(&dearmor(armored)?)?;
+                match packets.into_iter().next() {
+                    Some((TAG_PUBLIC_KEY, body)) => Ok((fingerprint(&body), eddsa_public_key(&body)?)),
+                    _ => Err(malformed("a signer certificate does not start with a public key")),
+                }
+            })
+            .collect::<Result<Vec<_>, IdpError>>()?;
+
+        // 3. Walk the user ids and their signatures, keeping verified third-party certifications.
+        let mut imported = 0;
+        let mut user_id: Option<&[u8]> = None;
+        for (tag, body) in &packets[1..] {
+            match *tag {
+                TAG_USER_ID => user_id = Some(body),
+                TAG_SIGNATURE => {
+                    let Some(user_id) = user_id else { continue };
+                    let signature = SignaturePacket::parse(body)?;
+                    if !CERTIFICATIONS.contains(&signature.sig_type) || signature.is_issued_by(&own_fingerprint) {
+                        continue;
+                    }
+                    let Some((signer_fingerprint, signer_key)) = signers.iter().find(|(f, _)| signature.is_issued_by(f)) else {
+                        continue;
+                    };
+
+                    let claim = signature.verify(&key_body, user_id, signer_key)?;
+                    let signer_hex = HEXUPPER.encode(signer_fingerprint);
+                    let proof_id = format!("openpgp-{}", signer_hex);
+                    if self.proofs.iter().any(|p| p.proof_id == proof_id) {
+                        continue;
+                    }
+                    self.proofs.push(Proof {
+                        proof_id,
+                        proof_type: OPENPGP_CERTIFICATION_PROOF.to_string(),
+                        claim_hash: BASE64.encode(&claim),
+                        signed_by: Signer {
+                            idp_id: IdpId::parse(&format!("{}:{}", id::OPENPGP_SCHEME, signer_hex))?,
+                            key_id: signer_hex,
+                        },
+                        signature: vec![SignatureComponent {
+                            algorithm: "OpenPGP".to_string(),
+                            value: BASE64.encode(&packet(TAG_SIGNATURE, body)),
+                        }],
+                        domain: None,
+                        challenge: None,
+                        unknown_fields: Default::default(),
+                    });
+                    imported += 1;
+                }
+                _ => {}
+            }
+        }
+        if imported > 0 {
+            self.touch();
+        }
+        Ok(imported)
+    }
+
+    fn openpgp_user_id(&self) -> String {
+        match self.core.bio.as_str() {
+            "" => self.core.name.clone(),
+            bio => format!("{} ({})", self.core.name, bio),
+        }
+    }
+
+    // Keys carry no creation date of their own, so the identity's is used; it keeps the
+    // fingerprint stable across exports.
+    fn openpgp_key_body(&self, key: &PublicKey) -> Result<Vec<u8>, IdpError> {
+        if key.algorithm != "Ed25519" || key.purpose == KeyPurpose::KeyAgreement {
+            return Err(IdpError::Key(format!("'{}' cannot be used as an OpenPGP key", key.key_id)));
+        }
+        let raw = key.raw_value()?;
+
+        let mut body = vec![4];
+        body.extend((self.identity.created_at.timestamp() as u32).to_be_bytes());
+        body.push(EDDSA);
+        body.push(ED25519_OID.len() as u8);
+        body.extend(ED25519_OID);
+        // The point is an MPI with the 0x40 "native point" prefix: 263 bits.
+        body.extend(263u16.to_be_bytes());
+        body.push(0x40);
+        body.extend(raw);
+        Ok(body)
+    }
+}
+
+/// A parsed v4 signature packet.
+struct SignaturePacket {
+    sig_type: u8,
+    hash_algorithm: u8,
+    // Version through hashed subpackets: the part of the packet the signature covers.
+    hashed: Vec<u8>,
+    issuer_fingerprint: Option<Vec<u8>>,
+    issuer_key_id: Option<Vec<u8>>,
+    r: Vec<u8>,
+    s: Vec<u8>,
+}
+
+impl SignaturePacket {
+    fn parse(body: &[u8]) -> Result<Self, IdpError> {
+        let mut reader = Reader(body);
+        if reader.take(1)? != [4] {
+            return Err(malformed("only version 4 signatures are supported"));
+        }
+        let sig_type = reader.take(1)?[0];
+        let public_key_algorithm = reader.take(1)?[0];
+        let hash_algorithm = reader.take(1)?[0];
+        let hashed_len = reader.u16()? as usize;
+        let hashed_subpackets = reader.take(hashed_len)?;
+        let hashed = body[..6 + hashed_len].to_vec();
+        let unhashed_len = reader.u16()? as usize;
+        let unhashed_subpackets = reader.take(unhashed_len)?;
+        let _hash_prefix = reader.take(2)?;
+        if public_key_algorithm != EDDSA {
+            return Err(IdpError::Verify(VerifyError::UnsupportedAlgorithm(format!(
+                "OpenPGP public key algorithm {}",
+                public_key_algorithm
+            ))));
+        }
+        let r = reader.mpi()?.to_vec();
+        let s = reader.mpi()?.to_vec();
+
+        let mut packet = SignaturePacket {
+            sig_type,
+            hash_algorithm,
+            hashed,
+            issuer_fingerprint: None,
+            issuer_key_id: None,
+            r,
+            s,
+        };
+        for (kind, data) in subpackets(hashed_subpackets)?.into_iter().chain(subpackets(unhashed_subpackets)?) {
+            match kind {
+                SUBPACKET_ISSUER_FINGERPRINT if data.len() == 21 => packet.issuer_fingerprint = Some(data[1..].to_vec()),
+                SUBPACKET_ISSUER if data.len() == 8 => packet.issuer_key_id = Some(data.to_vec()),
+                _ => {}
+            }
+        }
+        Ok(packet)
+    }
+
+    /// Whether the signature names the key with this fingerprint (or its key id) as issuer.
+    fn is_issued_by(&self, fingerprint: &[u8]) -> bool {
+        match (&self.issuer_fingerprint, &self.issuer_key_id) {
+            (Some(issuer), _) => issuer == fingerprint,
+            (None, Some(key_id)) => fingerprint.ends_with(key_id),
+            (None, None) => false,
+        }
+    }
+
+    /// Verifies the certification of `user_id` on a key and returns the digest it signs.
+    fn verify(&self, key_body: &[u8], user_id: &[u8], signer_key: &[u8]) -> Result<Vec<u8>, IdpError> {
+        let digest = certification_digest(self.hash_algorithm, key_body, user_id, &self.hashed)?;
+
+        let mut signature = left_pad(&self.r)?;
+        signature.extend(left_pad(&self.s)?);
+        let signer = PublicKey {
+            key_id: "openpgp".to_string(),
+            algorithm: "Ed25519".to_string(),
+            value: BASE64.encode(signer_key),
+            format: KeyFormat::Base64,
+            status: KeyStatus::Active,
+            parent_key_id: None,
+            purpose: KeyPurpose::Signing,
+            expires_at: None,
+            unknown_fields: Default::default(),
+        };
+        let signature = SignatureComponent {
+            algorithm: "Ed25519".to_string(),
+            value: BASE64.encode(&signature),
+        };
+        crypto::verify(&signer, &digest, &signature)?;
+        Ok(digest)
+    }
+}
+
+/// Makes a positive certification of `user_id` on the key in `key_body`, signed by `signer`
+/// (whose own key packet body is `signer_key_body`).
+fn certify(key_body: &[u8], user_id: &str, signer_key_body: &[u8], signer: &dyn SigningBackend) -> Result<Vec<u8>, IdpError> {
+    let signer_fingerprint = fingerprint(signer_key_body);
+
+    // 1. The hashed part: header plus creation time, issuer and key flags (certify + sign).
+    let mut subpackets = Vec::new();
+    let now = chrono::Utc::now().timestamp() as u32;
+    subpacket(&mut subpackets, SUBPACKET_CREATION_TIME, &now.to_be_bytes());
+    let mut issuer = vec![4];
+    issuer.extend(&signer_fingerprint);
+    subpacket(&mut subpackets, SUBPACKET_ISSUER_FINGERPRINT, &issuer);
+    if key_body == signer_key_body {
+        subpacket(&mut subpackets, SUBPACKET_KEY_FLAGS, &[0x03]);
+    }
+    let mut hashed = vec![4, POSITIVE_CERTIFICATION, EDDSA, HASH_SHA256];
+    hashed.extend((subpackets.len() as u16).to_be_bytes());
+    hashed.extend(subpackets);
+
+    // 2. EdDSA in OpenPGP signs the digest rather than the data.
+    let digest = certification_digest(HASH_SHA256, key_body, user_id.as_bytes(), &hashed)?;
+    let signature = signer.sign(&digest)?;
+    let signature = BASE64
+        .decode(signature.value.as_bytes())
+        .map_err(|_| IdpError::Verify(VerifyError::MalformedEncoding("signature".to_string())))?;
+
+    // 3. Assemble: hashed part, issuer key id (unhashed), hash prefix, then r and s.
+    let mut body = hashed;
+    let mut unhashed = Vec::new();
+    subpacket(&mut unhashed, SUBPACKET_ISSUER, &signer_fingerprint[12..]);
+    body.extend((unhashed.len() as u16).to_be_bytes());
+    body.extend(unhashed);
+    body.extend(&digest[..2]);
+    body.extend(mpi(&signature[..32]));
+    body.extend(mpi(&signature[32..]));
+    Ok(body)
+}
+
+/// The digest a certification signs: key, user id, hashed signature part and trailer.
+fn certification_digest(hash_algorithm: u8, key_body: &[u8], user_id: &[u8], hashed: &[u8]) -> Result<Vec<u8>, IdpError> {
+    let algorithm = match hash_algorithm {
+        8 => &digest::SHA256,
+        9 => &digest::SHA384,
+        10 => &digest::SHA512,
+        other => {
+            return Err(IdpError::Verify(VerifyError::UnsupportedAlgorithm(format!("OpenPGP hash algorithm {}", other))));
+        }
+    };
+    let mut context = digest::Context::new(algorithm);
+    context.update(&[0x99]);
+    context.update(&(key_body.len() as u16).to_be_bytes());
+    context.update(key_body);
+    context.update(&[0xB4]);
+    context.update(&(user_id.len() as u32).to_be_bytes());
+    context.update(user_id);
+    context.update(hashed);
+    context.update(&[4, 0xFF]);
+    context.update(&(hashed.len() as u32).to_be_bytes());
+    Ok(context.finish().as_ref().to_vec())
+}
+
+/// The v4 fingerprint: SHA-1 over the framed public key packet.
+fn fingerprint(key_body: &[u8]) -> Vec<u8> {
+    let mut context = digest::Context::new(&digest::SHA1_FOR_LEGACY_USE_ONLY);
+    context.update(&[0x99]);
+    context.update(&(key_body.len() as u16).to_be_bytes());
+    context.update(key_body);
+    context.finish().as_ref().to_vec()
+}
+
+/// Extracts the Ed25519 point from an EdDSA public key packet body.
+fn eddsa_public_key(body: &[u8]) -> Result<Vec<u8>, IdpError> {
+    let mut reader = Reader(body);
+    if reader.take(1)? != [4] {
+        return Err(malformed("only version 4 keys are supported"));
+    }
+    let _created = reader.take(4)?;
+    let algorithm = reader.take(1)?[0];
+    let oid_len = reader.take(1)?[0] as usize;
+    if algorithm != EDDSA || reader.take(oid_len)? != ED25519_OID {
+        return Err(IdpError::Verify(VerifyError::UnsupportedAlgorithm("non-Ed25519 OpenPGP key".to_string())));
+    }
+    match reader.mpi()? {
+        [0x40, point @ ..] if point.len() == 32 => Ok(point.to_vec()),
+        _ => Err(malformed("the Ed25519 point is not in native form")),
+    }
+}
+
+fn packet(tag: u8, body: &[u8]) -> Vec<u8> {
+    // New-format header with a five-octet length, which fits every body.
+    let mut packet = vec![0xC0 | tag, 0xFF];
+    packet.extend((body.len() as u32).to_be_bytes());
+    packet.extend(body);
+    packet
+}
+
+fn subpacket(buffer: &mut Vec<u8>, kind: u8, data: &[u8]) {
+    buffer.push(data.len() as u8 + 1);
+    buffer.push(kind);
+    buffer.extend(data);
+}
+
+fn mpi(bytes: &[u8]) -> Vec<u8> {
+    let bytes = &bytes[bytes.iter().take_while(|&&b| b == 0).count()..];
+    let bits = match bytes.first() {
+        Some(first) => bytes.len() * 8 - first.leading_zeros() as usize,
+        None => 0,
+    };
+    let mut encoded = (bits as u16).to_be_bytes().to_vec();
+    encoded.extend(bytes);
+    encoded
+}
+
+fn left_pad(bytes: &[u8]) -> Result<Vec<u8>, IdpError> {
+    if bytes.len() > 32 {
+        return Err(malformed("EdDSA signature value is too long"));
+    }
+    let mut padded = vec![0u8; 32 - bytes.len()];
+    padded.extend(bytes);
+    Ok(padded)
+}
+
+/// Splits a binary OpenPGP message into (tag, body) packets, in old or new format.
+fn parse_packets(mut bytes: &[u8]) -> Result<Vec<(u8, Vec<u8>)>, IdpError> {
+    let mut packets = Vec::new();
+    while let Some((&header, rest)) = bytes.split_first() {
+        if header & 0x80 == 0 {
+            return Err(malformed("invalid packet header"));
+        }
+        let mut reader = Reader(rest);
+        let (tag, len) = if header & 0x40 != 0 {
+            let first = reader.take(1)?[0] as usize;
+            let len = match first {
+                0..=191 => first,
+                192..=223 => ((first - 192) << 8) + reader.take(1)?[0] as usize + 192,
+                255 => u32::from_be_bytes(reader.take(4)?.try_into().expect("four bytes")) as usize,
+                _ => return Err(malformed("partial-length packets are not supported")),
+            };
+            (header & 0x3F, len)
+        } else {
+            let len = match header & 0x03 {
+                0 => reader.take(1)?[0] as usize,
+                1 => reader.u16()? as usize,
+                2 => u32::from_be_bytes(reader.take(4)?.try_into().expect("four bytes")) as usize,
+                _ => return Err(malformed("indeterminate-length packets are not supported")),
+            };
+            ((header >> 2) & 0x0F, len)
+        };
+        packets.push((tag, reader.take(len)?.to_vec()));
+        bytes = reader.0;
+    }
+    Ok(packets)
+}
+
+fn subpackets(mut bytes: &[u8]) -> Result<Vec<(u8, &[u8])>, IdpError> {
+    let mut found = Vec::new();
+    while !bytes.is_empty() {
+        let mut reader = Reader(bytes);
+        let first = reader.take(1)?[0];
+        let len = match first {
+            0..=191 => first as usize,
+            192..=254 => ((first as usize - 192) << 8) + reader.take(1)?[0] as usize + 192,
+            255 => u32::from_be_bytes(reader.take(4)?.try_into().expect("four bytes")) as usize,
+        };
+        let body = reader.take(len)?;
+        let (&kind, data) = body.split_first().ok_or_else(|| malformed("empty signature subpacket"))?;
+        // The top bit only marks the subpacket as critical.
+        found.push((kind & 0x7F, data));
+        bytes = reader.0;
+    }
+    Ok(found)
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], IdpError> {
+        let bytes = self.0.get(..len).ok_or_else(|| malformed("truncated packet"))?;
+        self.0 = &self.0[len..];
+        Ok(bytes)
+    }
+
+    fn u16(&mut self) -> Result<u16, IdpError> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().expect("two bytes")))
+    }
+
+    fn mpi(&mut self) -> Result<&'a [u8], IdpError> {
+        let bits = self.u16()? as usize;
+        self.take(bits.div_ceil(8))
+    }
+}
+
+/// Wraps binary data in ASCII armor with its CRC-24 checksum.
+fn armor(kind: &str, bytes: &[u8]) -> String {
+    let mut text = format!("-----BEGIN {}-----\n\n", kind);
+    for line in BASE64.encode(bytes).as_bytes().chunks(64) {
+        text.push_str(std::str::from_utf8(line).expect("Base64 is ASCII"));
+        text.push('\n');
+    }
+    text.push('=');
+    text.push_str(&BASE64.encode(&crc24(bytes).to_be_bytes()[1..]));
+    text.push_str(&format!("\n-----END {}-----\n", kind));
+    text
+}
+
+/// Removes ASCII armor, checking the CRC-24 checksum when one is present.
+fn dearmor(text: &str) -> Result<Vec<u8>, IdpError> {
+    let mut lines = text.lines().map(str::trim).skip_while(|line| !line.starts_with("-----BEGIN PGP"));
+    lines.next().ok_or_else(|| malformed("no ASCII-armored OpenPGP block found"))?;
+
+    // Armor headers (e.g. "Comment: ...") end at the first blank line.
+    let mut body = String::new();
+    let mut checksum = None;
+    for line in lines.skip_while(|line| line.contains(": ")).filter(|line| !line.is_empty()) {
+        if line.starts_with("-----END") {
+            break;
+        }
+        match line.strip_prefix('=') {
+            Some(crc) => checksum = Some(crc.to_string()),
+            None => body.push_str(line),
+        }
+    }
+
+    let bytes = BASE64.decode(body.as_bytes()).map_err(|_| malformed("invalid Base64 in ASCII armor"))?;
+    if checksum.is_some_and(|crc| crc != BASE64.encode(&crc24(&bytes).to_be_bytes()[1..])) {
+        return Err(malformed("ASCII armor checksum mismatch"));
+    }
+    Ok(bytes)
+}
+
+fn crc24(bytes: &[u8]) -> u32 {
+    let mut crc: u32 = 0xB704CE;
+    for &byte in bytes {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864CFB;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+fn malformed(reason: &str) -> IdpError {
+    IdpError::Crypto(format!("malformed OpenPGP data: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SecretBytes;
+
+    // A certificate for `identity`, with an extra certification by `certifier` appended,
+    // the way `gpg --sign-key` would hand it back.
+    fn certified_by(identity: &Identity, key: &SecretBytes, certifier: &Identity, certifier_key: &SecretBytes) -> String {
+        let mut bytes = dearmor(&identity.to_openpgp(key).unwrap()).unwrap();
+        let key_body = identity.openpgp_key_body(identity.key_for_private_key(key).unwrap()).unwrap();
+        let certifier_body = certifier
+            .openpgp_key_body(certifier.key_for_private_key(certifier_key).unwrap())
+            .unwrap();
+        let certification = certify(&key_body, &identity.openpgp_user_id(), &certifier_body, certifier_key).unwrap();
+        bytes.extend(packet(TAG_SIGNATURE, &certification));
+        armor(PUBLIC_KEY_BLOCK, &bytes)
+    }
+
+    #[test]
+    fn it_exports_a_self_signed_certificate() {
+        let (identity, private_key) = Identity::new("PGP User", "Bridges trust.").unwrap();
+        let armored = identity.to_openpgp(&private_key).unwrap();
+        assert!(armored.starts_with("-----BEGIN PGP PUBLIC KEY BLOCK-----"));
+
+        let packets = parse_packets(&dearmor(&armored).unwrap()).unwrap();
+        let tags: Vec<u8> = packets.iter().map(|(tag, _)| *tag).collect();
+        assert_eq!(tags, vec![TAG_PUBLIC_KEY, TAG_USER_ID, TAG_SIGNATURE]);
+        assert_eq!(packets[1].1, b"PGP User (Bridges trust.)");
+
+        // The self-signature verifies against the exported key itself.
+        let key = eddsa_public_key(&packets[0].1).unwrap();
+        let signature = SignaturePacket::parse(&packets[2].1).unwrap();
+        assert!(signature.is_issued_by(&fingerprint(&packets[0].1)));
+        signature.verify(&packets[0].1, &packets[1].1, &key).unwrap();
+        assert_eq!(
+            identity.openpgp_fingerprint("root-key-01").unwrap(),
+            HEXUPPER.encode(&fingerprint(&packets[0].1))
+        );
+    }
+
+    #[test]
+    fn it_imports_certifications_from_pgp_keys() {
+        let (mut bob, bob_key) = Identity::new("Bob", "Gets vouched for.").unwrap();
+        let (carol, carol_key) = Identity::new("Carol", "Has a PGP key.").unwrap();
+        let carol_certificate = carol.to_openpgp(&carol_key).unwrap();
+        let certificate = certified_by(&bob, &bob_key, &carol, &carol_key);
+
+        // Bob's own self-signature is skipped; Carol's certification becomes a proof, once.
+        assert_eq!(bob.import_openpgp_certifications(&certificate, &[&carol_certificate]).unwrap(), 1);
+        assert_eq!(bob.import_openpgp_certifications(&certificate, &[&carol_certificate]).unwrap(), 0);
+        let proof = bob.proofs.iter().find(|p| p.proof_type == OPENPGP_CERTIFICATION_PROOF).unwrap();
+        assert_eq!(proof.signed_by.key_id, carol.openpgp_fingerprint("root-key-01").unwrap());
+
+        // Certifications by keys we were not given are ignored.
+        let (mut dave, _) = Identity::new("Dave", "No friends here.").unwrap();
+        assert!(matches!(
+            dave.import_openpgp_certifications(&certificate, &[&carol_certificate]),
+            Err(IdpError::Key(_))
+        ));
+    }
+
+    #[test]
+    fn it_rejects_forged_certifications() {
+        let (mut bob, bob_key) = Identity::new("Bob", "Gets vouched for.").unwrap();
+        let (carol, carol_key) = Identity::new("Carol", "Has a PGP key.").unwrap();
+        let carol_certificate = carol.to_openpgp(&carol_key).unwrap();
+
+        // Carol's certification was made for a different user id than the one shown.
+        let certificate = certified_by(&bob, &bob_key, &carol, &carol_key);
+        bob.core.name = "Mallory".to_string();
+        let mut bytes = dearmor(&certificate).unwrap();
+        let mut packets = parse_packets(&bytes).unwrap();
+        packets[1].1 = bob.openpgp_user_id().into_bytes();
+        bytes = packets.iter().flat_map(|(tag, body)| packet(*tag, body)).collect();
+
+        assert!(matches!(
+            bob.import_openpgp_certifications(&armor(PUBLIC_KEY_BLOCK, &bytes), &[&carol_certificate]),
+            Err(IdpError::Verify(VerifyError::InvalidSignature))
+        ));
+    }
+
+    #[test]
+    fn it_checks_the_armor_checksum() {
+        let armored = armor(PUBLIC_KEY_BLOCK, b"hello");
+        assert_eq!(dearmor(&armored).unwrap(), b"hello");
+        assert!(dearmor(&armored.replace("aGVsbG8", "aGVsbG9")).is_err());
+    }
+}
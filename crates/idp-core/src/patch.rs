@@ -0,0 +1,61 @@
+// crates/idp-core/src/patch.rs
+
+// Changing documents with JSON Patch (RFC 6902), the standard way for integrators and sync
+// protocols to describe edits. A patch applies as a whole or not at all, and only if the
+// result is still a document that matches its schema.
+
+pub use json_patch::{Patch, PatchOperation};
+
+use crate::{schema, Identity, IdpError};
+
+impl Identity {
+    /// Applies an RFC 6902 patch. On any failure (a failed `test` operation, a missing path,
+    /// or a result that breaks the schema) the document is left as it was.
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), IdpError> {
+        // 1. Apply every operation to a generic copy.
+        let mut document = serde_json::to_value(&*self)?;
+        json_patch::patch(&mut document, &patch.0).map_err(|e| IdpError::Patch(e.to_string()))?;
+
+        // 2. Check it against the schema first, since serde would silently drop unknown fields.
+        schema::validate_document(&document)?;
+        *self = serde_json::from_value(document)?;
+        Ok(())
+    }
+
+    /// Like `apply_patch`, for a patch given as JSON text: an array of operations.
+    pub fn apply_patch_json(&mut self, patch: &str) -> Result<(), IdpError> {
+        let patch: Patch = serde_json::from_str(patch).map_err(|e| IdpError::Patch(e.to_string()))?;
+        self.apply_patch(&patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_applies_patches_all_or_nothing() {
+        let (mut identity, _) = Identity::new("Patch User", "Before.").unwrap();
+        identity
+            .apply_patch_json(r#"[
+                { "op": "test", "path": "/core/bio", "value": "Before." },
+                { "op": "replace", "path": "/core/bio", "value": "After." },
+                { "op": "copy", "from": "/core/name", "path": "/core/bio" }
+            ]"#)
+            .unwrap();
+        assert_eq!(identity.core.bio, "Patch User");
+
+        // A failed test, an unknown field or a wrongly typed value changes nothing.
+        let before = identity.clone();
+        for patch in [
+            r#"[{ "op": "replace", "path": "/core/name", "value": "X" }, { "op": "test", "path": "/core/bio", "value": "nope" }]"#,
+            r#"[{ "op": "add", "path": "/core/nickname", "value": "Pat" }]"#,
+            r#"[{ "op": "replace", "path": "/system/public_keys/0/status", "value": "actve" }]"#,
+            r#"[{ "op": "remove", "path": "/core/missing" }]"#,
+        ] {
+            assert!(identity.apply_patch_json(patch).is_err(), "{}", patch);
+            assert_eq!(identity, before);
+        }
+        assert!(matches!(identity.apply_patch_json("{}"), Err(IdpError::Patch(_))));
+    }
+}
@@ -0,0 +1,177 @@
+// crates/idp-core/src/path.rs
+
+// The path-resolution engine behind `idp set` and `idp get`.
+// A path is a dot-separated list of segments, e.g. `system.public_keys.0.status`.
+// Segments address mapping keys, or sequence indices when the current node is a list.
+// When reading, a `*` segment matches every child (e.g. `credentials.*.claim`).
+
+use serde_yaml::Value;
+
+use crate::IdpError;
+
+/// Splits a dot-path into its segments, rejecting empty ones (`core..bio`, `.core`).
+pub fn parse(path: &str) -> Result<Vec<&str>, IdpError> {
+    let segments: Vec<&str> = path.split('.').collect();
+    if segments.iter().any(|s| s.is_empty()) {
+        return Err(invalid(path, "empty path segment"));
+    }
+    Ok(segments)
+}
+
+/// Resolves a path to a single node, if it exists.
+pub fn get<'a>(root: &'a Value, path: &str) -> Result<Option<&'a Value>, IdpError> {
+    let mut node = root;
+    for segment in parse(path)? {
+        let next = match node {
+            Value::Mapping(map) => map.get(segment),
+            Value::Sequence(seq) => segment.parse::<usize>().ok().and_then(|i| seq.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(value) => node = value,
+            None => return Ok(None),
+        }
+    }
+    Ok(Some(node))
+}
+
+/// Resolves a path that may contain `*` wildcards to every matching node, in document order.
+pub fn query<'a>(root: &'a Value, path: &str) -> Result<Vec<&'a Value>, IdpError> {
+    let mut nodes = vec![root];
+    for segment in parse(path)? {
+        nodes = nodes
+            .into_iter()
+            .flat_map(|node| children(node, segment))
+            .collect();
+    }
+    Ok(nodes)
+}
+
+/// Returns true if the path contains a wildcard segment.
+pub fn is_wildcard(path: &str) -> bool {
+    path.split('.').any(|segment| segment == WILDCARD)
+}
+
+const WILDCARD: &str = "*";
+
+fn children<'a>(node: &'a Value, segment: &str) -> Vec<&'a Value> {
+    match (node, segment) {
+        (Value::Mapping(map), WILDCARD) => map.values().collect(),
+        (Value::Sequence(seq), WILDCARD) => seq.iter().collect(),
+        (Value::Mapping(map), _) => map.get(segment).into_iter().collect(),
+        (Value::Sequence(seq), _) => segment.parse::<usize>().ok().and_then(|i| seq.get(i)).into_iter().collect(),
+        _ => vec![],
+    }
+}
+
+/// Replaces the node at `path` with `value`.
+/// Every parent must already exist; the last segment may add a new key to a mapping.
+pub fn set(root: &mut Value, path: &str, value: Value) -> Result<(), IdpError> {
+    let segments = parse(path)?;
+    let (last, parents) = segments.split_last().expect("parse never returns an empty path");
+
+    let mut node = root;
+    for segment in parents {
+        node = child_mut(node, segment).ok_or_else(|| invalid(path, &format!("'{}' does not exist", segment)))?;
+    }
+
+    match node {
+        Value::Mapping(map) => {
+            map.insert(Value::String(last.to_string()), value);
+            Ok(())
+        }
+        Value::Sequence(seq) => {
+            let index = parse_index(path, last)?;
+            let len = seq.len();
+            let slot = seq
+                .get_mut(index)
+                .ok_or_else(|| invalid(path, &format!("index {} is out of bounds (length {})", index, len)))?;
+            *slot = value;
+            Ok(())
+        }
+        _ => Err(invalid(path, &format!("cannot set '{}' on a scalar value", last))),
+    }
+}
+
+fn child_mut<'a>(node: &'a mut Value, segment: &str) -> Option<&'a mut Value> {
+    match node {
+        Value::Mapping(map) => map.get_mut(segment),
+        Value::Sequence(seq) => segment.parse::<usize>().ok().and_then(move |i| seq.get_mut(i)),
+        _ => None,
+    }
+}
+
+fn parse_index(path: &str, segment: &str) -> Result<usize, IdpError> {
+    segment
+        .parse::<usize>()
+        .map_err(|_| invalid(path, &format!("'{}' is not a list index", segment)))
+}
+
+fn invalid(path: &str, reason: &str) -> IdpError {
+    IdpError::Path {
+        path: path.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Value {
+        serde_yaml::from_str(
+            r#"
+core:
+  name: Clein Pius
+system:
+  public_keys:
+    - key_id: root-key-01
+      status: active
+"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn it_resolves_nested_fields_and_indices() {
+        let doc = sample();
+        assert_eq!(get(&doc, "core.name").unwrap(), Some(&Value::from("Clein Pius")));
+        assert_eq!(get(&doc, "system.public_keys.0.status").unwrap(), Some(&Value::from("active")));
+        assert_eq!(get(&doc, "system.public_keys.1.status").unwrap(), None);
+    }
+
+    #[test]
+    fn it_expands_wildcards() {
+        let doc: Value = serde_yaml::from_str(
+            r#"
+credentials:
+  - claim: first
+  - claim: second
+  - issued_by: nobody
+"#,
+        )
+        .unwrap();
+        let claims = query(&doc, "credentials.*.claim").unwrap();
+        assert_eq!(claims, vec![&Value::from("first"), &Value::from("second")]);
+        assert!(is_wildcard("credentials.*.claim"));
+        assert!(!is_wildcard("credentials.0.claim"));
+    }
+
+    #[test]
+    fn it_sets_nested_fields_and_indices() {
+        let mut doc = sample();
+        set(&mut doc, "core.name", Value::from("Someone Else")).unwrap();
+        set(&mut doc, "system.public_keys.0.status", Value::from("revoked")).unwrap();
+        assert_eq!(get(&doc, "core.name").unwrap(), Some(&Value::from("Someone Else")));
+        assert_eq!(get(&doc, "system.public_keys.0.status").unwrap(), Some(&Value::from("revoked")));
+    }
+
+    #[test]
+    fn it_rejects_bad_paths() {
+        let mut doc = sample();
+        assert!(matches!(parse("core..name"), Err(IdpError::Path { .. })));
+        assert!(matches!(set(&mut doc, "missing.name", Value::from("x")), Err(IdpError::Path { .. })));
+        assert!(matches!(set(&mut doc, "system.public_keys.5", Value::from("x")), Err(IdpError::Path { .. })));
+        assert!(matches!(set(&mut doc, "core.name.first", Value::from("x")), Err(IdpError::Path { .. })));
+    }
+}
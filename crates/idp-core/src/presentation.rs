@@ -0,0 +1,387 @@
+// crates/idp-core/src/presentation.rs
+
+// Challenge-response presentations. A verifier who wants proof of some claims sends a challenge:
+// a fresh nonce, the claims it asks for and how long it stands. The holder answers with the
+// matching credentials and their proofs, signed together with the nonce, so the answer cannot be
+// replayed to another challenge. The verifier checks the holder's signature, the nonce and each
+// credential against its issuer. Presentations are built with `Identity::presentation()` and
+// kept as YAML, or written as W3C Verifiable Presentations for verifiers in the VC ecosystem.
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use data_encoding::{BASE64, BASE64URL_NOPAD, HEXLOWER};
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::credential::IssuedCredential;
+use crate::signer::SigningBackend;
+use crate::trust::IdentityResolver;
+use crate::{canonical, crypto, vc, Credential, Identity, IdpError, IdpId, Proof, SignatureComponent, Signer};
+
+/// The `Proof.proof_type` of a presentation's holder signature.
+pub const PRESENTATION_PROOF: &str = "Presentation";
+
+/// What a verifier asks a holder to present.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Challenge {
+    pub nonce: String,
+    /// The claims asked for, as claim text or typed claim URIs.
+    pub claims: Vec<String>,
+    /// Who asks, e.g. the verifier's id or domain; presentations for it must name it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Challenge {
+    /// A challenge for `claims` with a random nonce, answerable for `valid_for`.
+    pub fn new(claims: &[String], audience: Option<&str>, valid_for: Duration) -> Challenge {
+        let issued_at = Utc::now();
+        let nonce: [u8; 16] = rand::random();
+        Challenge {
+            nonce: BASE64URL_NOPAD.encode(&nonce),
+            claims: claims.to_vec(),
+            audience: audience.map(str::to_string),
+            issued_at,
+            expires_at: issued_at + valid_for,
+        }
+    }
+
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires_at <= now
+    }
+}
+
+// Whether `credential` is what a challenge asks for with `claim`.
+fn answers(credential: &Credential, claim: &str) -> bool {
+    credential.claim == claim || credential.claim_type().as_deref() == Some(claim)
+}
+
+/// A holder's credentials bundled for one verifier, signed together with its nonce.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Presentation {
+    pub holder: IdpId,
+    pub nonce: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audience: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub credentials: Vec<IssuedCredential>,
+    /// The holder's signature over the statement `statement` builds and, for Ed25519 keys,
+    /// over the W3C form too.
+    pub proof: Proof,
+}
+
+/// A presentation being put together; start with `Identity::presentation()`.
+pub struct PresentationBuilder<'a> {
+    holder: &'a Identity,
+    credentials: Vec<String>,
+    nonce: Option<String>,
+    audience: Option<String>,
+}
+
+impl PresentationBuilder<'_> {
+    /// Includes the credential with this proof id.
+    pub fn credential(mut self, proof_id: &str) -> Self {
+        self.credentials.push(proof_id.to_string());
+        self
+    }
+
+    /// The verifier's nonce; required.
+    pub fn nonce(mut self, nonce: &str) -> Self {
+        self.nonce = Some(nonce.to_string());
+        self
+    }
+
+    /// Who the presentation is for.
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    /// Signs the presentation with `signer`'s key of the holder.
+    pub fn sign(self, signer: &dyn SigningBackend) -> Result<Presentation, IdpError> {
+        let holder = self.holder;
+        let key = holder.issuing_key(signer)?;
+        let nonce = self.nonce.ok_or_else(|| IdpError::Presentation("a presentation needs the verifier's nonce".to_string()))?;
+        let created_at = Utc::now();
+        if self.credentials.is_empty() {
+            return Err(IdpError::Presentation("a presentation needs at least one credential".to_string()));
+        }
+
+        // 1. The credentials, current and with their proofs.
+        let mut credentials = Vec::new();
+        for proof_id in &self.credentials {
+            let credential = holder
+                .credentials
+                .iter()
+                .find(|credential| credential.proof == *proof_id)
+                .ok_or_else(|| IdpError::Presentation(format!("no credential has the proof '{}'", proof_id)))?;
+            if credential.is_expired(created_at) {
+                return Err(IdpError::Presentation(format!("'{}' has expired", credential.claim)));
+            }
+            let proof = holder.credential_proof(credential).ok_or_else(|| IdpError::Presentation(format!("the proof of '{}' is missing", credential.claim)))?;
+            credentials.push(IssuedCredential { credential: credential.clone(), proof: proof.clone() });
+        }
+
+        // 2. The holder signs them together with the nonce and audience.
+        let mut presentation = Presentation {
+            holder: holder.identity.id.clone(),
+            nonce,
+            audience: self.audience,
+            created_at,
+            credentials,
+            proof: Proof {
+                proof_id: String::new(),
+                proof_type: PRESENTATION_PROOF.to_string(),
+                claim_hash: String::new(),
+                signed_by: Signer { idp_id: holder.identity.id.clone(), key_id: key.key_id.clone() },
+                signature: Vec::new(),
+                domain: None,
+                challenge: None,
+                unknown_fields: Default::default(),
+            },
+        };
+        let statement = presentation.statement();
+        presentation.proof.proof_id = format!("presentation-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, &statement).as_ref()[..8]));
+        presentation.proof.claim_hash = canonical::hash(&statement);
+        presentation.proof.signature.push(signer.sign(&statement)?);
+
+        // 3. The key also signs the W3C form, when it and every credential can take that form.
+        if key.algorithm == crypto::ED25519
+            && let Ok(unsecured) = presentation.unsecured()
+        {
+            let signature = signer.sign(&vc::data_integrity_payload(&unsecured, &presentation.proof_options()))?;
+            presentation.proof.signature.push(SignatureComponent { algorithm: vc::EDDSA_JCS_2022.to_string(), value: signature.value });
+        }
+        Ok(presentation)
+    }
+}
+
+/// What checking a presentation found.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct PresentationReport {
+    pub holder: String,
+    /// The claims of the credentials presented.
+    pub claims: Vec<String>,
+    pub problems: Vec<String>,
+}
+
+impl PresentationReport {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl Identity {
+    /// Starts a presentation of this identity's credentials.
+    pub fn presentation(&self) -> PresentationBuilder<'_> {
+        PresentationBuilder { holder: self, credentials: Vec::new(), nonce: None, audience: None }
+    }
+
+    /// Answers `challenge` with a current credential for each claim it asks for, signed with
+    /// `signer`'s key of this identity.
+    pub fn present(&self, challenge: &Challenge, signer: &dyn SigningBackend) -> Result<Presentation, IdpError> {
+        let now = Utc::now();
+        if challenge.is_expired(now) {
+            return Err(IdpError::Presentation(format!("the challenge expired at {}", challenge.expires_at.to_rfc3339())));
+        }
+        let mut presentation = self.presentation().nonce(&challenge.nonce);
+        if let Some(audience) = &challenge.audience {
+            presentation = presentation.audience(audience);
+        }
+        for claim in &challenge.claims {
+            let credential = self
+                .credentials
+                .iter()
+                .find(|credential| answers(credential, claim) && !credential.is_expired(now))
+                .ok_or_else(|| IdpError::Presentation(format!("you hold no current credential for '{}'", claim)))?;
+            presentation = presentation.credential(&credential.proof);
+        }
+        presentation.sign(signer)
+    }
+}
+
+impl Presentation {
+    /// The statement the holder signs: nonce, audience, and each credential by its proof's hash.
+    pub fn statement(&self) -> Vec<u8> {
+        let hashes: Vec<&str> = self.credentials.iter().map(|issued| issued.proof.claim_hash.as_str()).collect();
+        let mut statement = json!({
+            "type": "idp-presentation",
+            "holder": self.holder,
+            "nonce": self.nonce,
+            "created_at": self.created_at.to_rfc3339(),
+            "credentials": hashes,
+        });
+        if let Some(audience) = &self.audience {
+            statement["audience"] = json!(audience);
+        }
+        canonical::canonicalize(&statement)
+    }
+
+    // The W3C form without its proof; every credential must have its VC form.
+    fn unsecured(&self) -> Result<Value, IdpError> {
+        let credentials = self
+            .credentials
+            .iter()
+            .map(|issued| vc::to_verifiable_credential(&self.holder, &issued.credential, &issued.proof))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(json!({
+            "@context": [vc::VC_CONTEXT],
+            "type": ["VerifiablePresentation"],
+            "holder": self.holder,
+            "verifiableCredential": credentials,
+        }))
+    }
+
+    // The proof options of the W3C form: the nonce is its `challenge`, the audience its `domain`.
+    fn proof_options(&self) -> Value {
+        let mut options = json!({
+            "type": "DataIntegrityProof",
+            "cryptosuite": vc::EDDSA_JCS_2022,
+            "created": self.created_at.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+            "verificationMethod": format!("{}#{}", self.holder, self.proof.signed_by.key_id),
+            "proofPurpose": "authentication",
+            "challenge": self.nonce,
+        });
+        if let Some(audience) = &self.audience {
+            options["domain"] = json!(audience);
+        }
+        options
+    }
+
+    /// Writes the presentation as a W3C Verifiable Presentation with a Data Integrity proof. The
+    /// holder's key must be Ed25519, and every credential exportable as a VC.
+    pub fn to_verifiable_presentation(&self) -> Result<Value, IdpError> {
+        let signature = self
+            .proof
+            .signature
+            .iter()
+            .find(|signature| signature.algorithm == vc::EDDSA_JCS_2022)
+            .ok_or_else(|| IdpError::Presentation("it was not signed as a W3C presentation: the holder's key is not Ed25519, or a credential has no VC form".to_string()))?;
+        let signature = BASE64.decode(signature.value.as_bytes()).map_err(|_| IdpError::Presentation("the VP signature is not valid Base64".to_string()))?;
+
+        let mut document = self.unsecured()?;
+        let mut proof = self.proof_options();
+        proof["proofValue"] = json!(format!("z{}", bs58::encode(signature).into_string()));
+        document["proof"] = proof;
+        Ok(document)
+    }
+
+    /// Checks the presentation as an answer to `challenge`, from the holder whose document is
+    /// `holder`, with the credentials' issuers found through `issuers`.
+    pub fn verify(&self, challenge: &Challenge, holder: &Identity, issuers: &dyn IdentityResolver) -> Result<PresentationReport, IdpError> {
+        let mut problems = Vec::new();
+
+        // 1. Fresh: the answer to this challenge, made while it stood.
+        if self.nonce != challenge.nonce {
+            problems.push("it answers another challenge".to_string());
+        }
+        if challenge.audience.is_some() && self.audience != challenge.audience {
+            problems.push(format!("it is meant for {}", self.audience.as_deref().unwrap_or("no one in particular")));
+        }
+        if challenge.is_expired(Utc::now()) || self.created_at < challenge.issued_at || self.created_at >= challenge.expires_at {
+            problems.push(format!("it was not made while the challenge stood ({} to {})", challenge.issued_at.to_rfc3339(), challenge.expires_at.to_rfc3339()));
+        }
+
+        // 2. Signed by the holder, over exactly these credentials.
+        let statement = self.statement();
+        if holder.identity.id != self.holder || self.proof.signed_by.idp_id != self.holder {
+            problems.push(format!("it was made by {}, not by the holder given", self.holder));
+        } else if self.proof.proof_type != PRESENTATION_PROOF || self.proof.claim_hash != canonical::hash(&statement) {
+            problems.push("its proof is for other credentials, or they were changed".to_string());
+        } else {
+            let verified = match self.proof.signature.first() {
+                Some(signature) => holder.verify_signed_by(&self.proof.signed_by.key_id, &statement, signature),
+                None => Err(IdpError::Presentation("it is not signed".to_string())),
+            };
+            if let Err(e) = verified {
+                problems.push(format!("the holder's signature does not verify: {}", e));
+            }
+        }
+
+        // 3. Every claim asked for, each from a credential that holds.
+        for claim in &challenge.claims {
+            if !self.credentials.iter().any(|issued| answers(&issued.credential, claim)) {
+                problems.push(format!("no credential for '{}' was presented", claim));
+            }
+        }
+        for IssuedCredential { credential, proof } in &self.credentials {
+            let credential_problems = match issuers.resolve(&credential.issued_by)? {
+                Some(issuer) => holder.verify_credential(credential, proof, &issuer).problems(),
+                None => vec![format!("the identity of {} could not be found", credential.issued_by)],
+            };
+            problems.extend(credential_problems.into_iter().map(|problem| format!("'{}': {}", credential.claim, problem)));
+        }
+
+        Ok(PresentationReport {
+            holder: self.holder.to_string(),
+            claims: self.credentials.iter().map(|issued| issued.credential.claim.clone()).collect(),
+            problems,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_answers_a_challenge_only_once() {
+        let (issuer, issuer_key) = Identity::new("Club", "Keeps a roster.").unwrap();
+        let (mut holder, holder_key) = Identity::new("Holder", "A member.").unwrap();
+        holder.add_credential(issuer.issue_credential(&holder, "member", None, &issuer_key).unwrap()).unwrap();
+        holder.add_credential(issuer.issue_credential(&holder, "treasurer", None, &issuer_key).unwrap()).unwrap();
+        let issuers = vec![issuer.clone()];
+
+        // Only what was asked for is presented, and it verifies for this challenge.
+        let challenge = Challenge::new(&["member".to_string()], None, Duration::minutes(5));
+        let presentation = holder.present(&challenge, &holder_key).unwrap();
+        assert_eq!(presentation.credentials.len(), 1);
+        let report = presentation.verify(&challenge, &holder, &issuers).unwrap();
+        assert!(report.is_valid(), "{:?}", report.problems);
+        assert_eq!(report.claims, ["member"]);
+
+        // Not for another challenge, nor with its credentials changed, nor without their issuer.
+        let other = Challenge::new(&["member".to_string()], None, Duration::minutes(5));
+        assert!(!presentation.verify(&other, &holder, &issuers).unwrap().is_valid());
+        let mut changed = presentation.clone();
+        changed.credentials.clear();
+        assert!(!changed.verify(&challenge, &holder, &issuers).unwrap().is_valid());
+        assert!(!presentation.verify(&challenge, &holder, &Vec::new()).unwrap().is_valid());
+
+        // A claim the holder cannot back, or a lapsed challenge, gets no presentation.
+        assert!(holder.present(&Challenge::new(&["judge".to_string()], None, Duration::minutes(5)), &holder_key).is_err());
+        assert!(holder.present(&Challenge::new(&["member".to_string()], None, Duration::zero()), &holder_key).is_err());
+    }
+
+    #[test]
+    fn it_builds_presentations_in_both_forms() {
+        let (issuer, issuer_key) = Identity::new("Club", "Keeps a roster.").unwrap();
+        let (mut holder, holder_key) = Identity::new("Holder", "A member.").unwrap();
+        holder.add_credential(issuer.issue_credential(&holder, "member", None, &issuer_key).unwrap()).unwrap();
+        let proof_id = holder.credentials[0].proof.clone();
+        let presentation = holder.presentation().credential(&proof_id).nonce("n-0S6_WzA2Mj").audience("https://verifier.example").sign(&holder_key).unwrap();
+
+        // The native form reads back as it was, and holds only for its audience.
+        let yaml = serde_yaml::to_string(&presentation).unwrap();
+        assert_eq!(serde_yaml::from_str::<Presentation>(&yaml).unwrap(), presentation);
+        let mut challenge = Challenge::new(&["member".to_string()], Some("https://verifier.example"), Duration::minutes(5));
+        challenge.nonce = presentation.nonce.clone();
+        challenge.issued_at = presentation.created_at;
+        assert!(presentation.verify(&challenge, &holder, &vec![issuer.clone()]).unwrap().is_valid());
+        challenge.audience = Some("https://elsewhere.example".to_string());
+        assert!(!presentation.verify(&challenge, &holder, &vec![issuer.clone()]).unwrap().is_valid());
+
+        // The W3C form carries the nonce and audience in its proof, which the holder's key verifies.
+        let vp = presentation.to_verifiable_presentation().unwrap();
+        assert_eq!(vp["type"][0], "VerifiablePresentation");
+        assert_eq!((vp["proof"]["challenge"].as_str(), vp["proof"]["domain"].as_str()), (Some("n-0S6_WzA2Mj"), Some("https://verifier.example")));
+        vc::verify_data_integrity(&vp, &holder.system.public_keys[0]).unwrap();
+        vc::verify_data_integrity(&vp["verifiableCredential"][0], &issuer.system.public_keys[0]).unwrap();
+
+        // A nonce and a credential are required.
+        assert!(holder.presentation().credential(&proof_id).sign(&holder_key).is_err());
+        assert!(holder.presentation().nonce("n").sign(&holder_key).is_err());
+    }
+}
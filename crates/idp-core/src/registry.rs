@@ -0,0 +1,188 @@
+// crates/idp-core/src/registry.rs
+
+// The issuer registry: a record of every credential an identity has issued, kept next to its
+// document (`my.idp.issued`, YAML). The credentials themselves live in their subjects' documents;
+// the registry remembers who got what, the hash of the statement signed, and where each one
+// stands, so a revocable credential can be revoked by its id without looking up its list index.
+
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, TimeZone, Utc};
+use data_encoding::HEXLOWER;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+
+use crate::credential::IssuedCredential;
+use crate::jwt::Jws;
+use crate::sd_jwt::SdJwt;
+use crate::{atomic, canonical, CredentialStatus, Identity, IdpError};
+
+/// One issued credential, as the issuer remembers it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IssuedEntry {
+    /// The credential's proof id, which the subject's document also carries.
+    pub id: String,
+    pub subject: String,
+    pub claim: String,
+    pub claim_hash: String,
+    pub issued_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Its entry in the issuer's status list; without one it cannot be revoked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<CredentialStatus>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// Where an issued credential stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IssuedState {
+    Active,
+    Expired,
+    Revoked,
+}
+
+impl IssuedEntry {
+    pub fn state(&self, now: DateTime<Utc>) -> IssuedState {
+        match (self.revoked_at, self.expires_at) {
+            (Some(_), _) => IssuedState::Revoked,
+            (None, Some(expires_at)) if expires_at <= now => IssuedState::Expired,
+            _ => IssuedState::Active,
+        }
+    }
+}
+
+impl std::fmt::Display for IssuedState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            IssuedState::Active => "active",
+            IssuedState::Expired => "expired",
+            IssuedState::Revoked => "revoked",
+        })
+    }
+}
+
+/// The registry of one issuer's document.
+#[derive(Debug, Clone)]
+pub struct IssuerRegistry {
+    path: PathBuf,
+    entries: Vec<IssuedEntry>,
+}
+
+impl IssuerRegistry {
+    /// The registry kept next to the document at `document_path` (`my.idp.issued` for `my.idp`).
+    pub fn path_for(document_path: &Path) -> PathBuf {
+        let mut path = document_path.as_os_str().to_owned();
+        path.push(".issued");
+        PathBuf::from(path)
+    }
+
+    /// Reads the registry of the document at `document_path`; an issuer without one has issued nothing.
+    pub fn open<P: AsRef<Path>>(document_path: P) -> Result<IssuerRegistry, IdpError> {
+        let path = Self::path_for(document_path.as_ref());
+        let entries = match std::fs::read_to_string(&path) {
+            Ok(contents) if contents.trim().is_empty() => Vec::new(),
+            Ok(contents) => serde_yaml::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(IssuerRegistry { path, entries })
+    }
+
+    pub fn entries(&self) -> &[IssuedEntry] {
+        &self.entries
+    }
+
+    pub fn find(&self, id: &str) -> Option<&IssuedEntry> {
+        self.entries.iter().find(|entry| entry.id == id)
+    }
+
+    /// Records a credential issued to `subject` with `issue_credential` or its variants.
+    pub fn record(&mut self, subject: &str, issued: &IssuedCredential) -> &IssuedEntry {
+        self.push(IssuedEntry {
+            id: issued.proof.proof_id.clone(),
+            subject: subject.to_string(),
+            claim: issued.credential.claim.clone(),
+            claim_hash: issued.proof.claim_hash.clone(),
+            issued_at: issued.credential.issued_at,
+            expires_at: issued.credential.expires_at,
+            status: issued.credential.status.clone(),
+            revoked_at: None,
+        })
+    }
+
+    /// Records an SD-JWT, under the id its holder keeps it by; its claim lists the fields' names.
+    pub fn record_sd_jwt(&mut self, sd_jwt: &SdJwt) -> Result<&IssuedEntry, IdpError> {
+        let payload = Jws::decode(&sd_jwt.jwt)?.payload;
+        let time = |name: &str| payload[name].as_i64().and_then(|seconds| Utc.timestamp_opt(seconds, 0).single());
+        let names: Vec<&str> = sd_jwt.disclosures.iter().map(|disclosure| disclosure.name.as_str()).collect();
+        Ok(self.push(IssuedEntry {
+            id: format!("sd-jwt-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, sd_jwt.jwt.as_bytes()).as_ref()[..8])),
+            subject: payload["sub"].as_str().unwrap_or_default().to_string(),
+            claim: names.join(", "),
+            claim_hash: canonical::hash(sd_jwt.jwt.as_bytes()),
+            issued_at: time("iat").unwrap_or_else(Utc::now),
+            expires_at: time("exp"),
+            status: None,
+            revoked_at: None,
+        }))
+    }
+
+    fn push(&mut self, entry: IssuedEntry) -> &IssuedEntry {
+        self.entries.retain(|existing| existing.id != entry.id);
+        self.entries.push(entry);
+        self.entries.last().expect("just added")
+    }
+
+    /// Revokes the credential `id` in `issuer`'s status list and marks it revoked here. The list
+    /// still has to be published again, and `issuer` saved, for verifiers to see it.
+    pub fn revoke(&mut self, id: &str, issuer: &mut Identity) -> Result<&IssuedEntry, IdpError> {
+        let entry = self.entries.iter_mut().find(|entry| entry.id == id).ok_or_else(|| IdpError::Credential(format!("no credential '{}' was issued", id)))?;
+        if entry.revoked_at.is_some() {
+            return Err(IdpError::Credential(format!("the credential '{}' is already revoked", id)));
+        }
+        let status = entry.status.as_ref().ok_or_else(|| IdpError::Credential(format!("the credential '{}' was issued without a status entry, so it cannot be revoked", id)))?;
+        issuer.set_status(status.index, true)?;
+        entry.revoked_at = Some(Utc::now());
+        Ok(entry)
+    }
+
+    /// Writes the registry back next to its document.
+    pub fn save(&self) -> Result<(), IdpError> {
+        atomic::write(&self.path, serde_yaml::to_string(&self.entries)?.as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::status_list::MIN_LENGTH;
+
+    #[test]
+    fn it_records_and_revokes_issued_credentials() {
+        let dir = tempfile::tempdir().unwrap();
+        let document = dir.path().join("my.idp");
+        let (mut issuer, issuer_key) = Identity::new("Issuer", "Keeps a registry.").unwrap();
+        let (holder, _) = Identity::new("Holder", "Holds things.").unwrap();
+        issuer.init_status_list("file:///status.json", MIN_LENGTH).unwrap();
+
+        let mut registry = IssuerRegistry::open(&document).unwrap();
+        let revocable = issuer.issue_revocable_credential(&holder, "member", None, &issuer_key).unwrap();
+        let plain = issuer.issue_credential(&holder, "visitor", None, &issuer_key).unwrap();
+        registry.record(holder.identity.id.as_str(), &revocable);
+        registry.record(holder.identity.id.as_str(), &plain);
+        registry.save().unwrap();
+
+        // Reopened, revoking by id sets the credential's bit in the issuer's list.
+        let mut registry = IssuerRegistry::open(&document).unwrap();
+        assert_eq!(registry.entries().len(), 2);
+        assert_eq!(registry.find(&plain.proof.proof_id).unwrap().claim_hash, plain.proof.claim_hash);
+        let entry = registry.revoke(&revocable.proof.proof_id, &mut issuer).unwrap();
+        assert_eq!(entry.state(Utc::now()), IssuedState::Revoked);
+        assert_eq!(issuer.status_list().unwrap().unwrap().revoked, vec![0]);
+        assert!(registry.revoke(&revocable.proof.proof_id, &mut issuer).is_err(), "already revoked");
+        assert!(registry.revoke(&plain.proof.proof_id, &mut issuer).is_err(), "no status entry");
+        assert!(registry.revoke("credential-unknown", &mut issuer).is_err());
+    }
+}
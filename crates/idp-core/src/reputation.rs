@@ -0,0 +1,453 @@
+// crates/idp-core/src/reputation.rs
+
+// Signed reputation. A reputation event is worth only as much as whoever vouches for it, so an
+// issuer signs each event for its subject: the score it counts toward, what happened, the
+// change and when. The subject adds the event to its document once the signature checks out
+// against the issuer's identity. Events written before they were signed still load; strict
+// callers refuse to add them.
+//
+// A score's stored value is the plain sum of its history. A policy computes a current score
+// from the same history instead: each event weighted by who issued it, fading with age, and
+// with what any one issuer can add or take away capped.
+//
+// A score kept elsewhere travels as a credential: an issuer attests to it with a typed
+// `reputation` claim, which its subject holds and anyone can verify like any other credential.
+// A whole history travels as a batch: the issuer signs each event and the batch as a whole, and
+// the subject takes in the events it does not have yet.
+
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::claims::{ClaimType, ReputationAttestation};
+use crate::contract::check_signed;
+use crate::credential::IssuedCredential;
+use crate::signer::SigningBackend;
+use crate::trust::IdentityResolver;
+use crate::{canonical, Credential, Identity, IdpError, IdpId, Reputation, ReputationEvent, SignatureComponent, Signer};
+
+/// The `type` of a signed reputation statement.
+pub const REPUTATION_EVENT: &str = "idp-reputation-event";
+
+/// The `type` of a reputation batch.
+pub const REPUTATION_BATCH: &str = "idp-reputation-batch";
+
+/// A reputation event as an issuer hands it to its subject.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IssuedReputationEvent {
+    pub subject: IdpId,
+    pub score_name: String,
+    pub event: ReputationEvent,
+}
+
+/// An event as an issuer keeps it, to export in a batch.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReputationEntry {
+    pub score_name: String,
+    pub event: String,
+    pub change: i64,
+    /// When it happened; by default, when it is exported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+/// A signed batch of reputation events from one issuer for one subject.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ReputationBatch {
+    #[serde(rename = "type")]
+    pub batch_type: String,
+    pub subject: IdpId,
+    pub events: Vec<IssuedReputationEvent>,
+    pub signed_by: Signer,
+    pub signed_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub signature: Vec<SignatureComponent>,
+}
+
+impl ReputationBatch {
+    /// The canonical batch without its signature, which the issuer signs.
+    pub fn statement(&self) -> Vec<u8> {
+        let mut batch = serde_json::to_value(self).unwrap_or(serde_json::Value::Null);
+        if let serde_json::Value::Object(fields) = &mut batch {
+            fields.remove("signature");
+        }
+        canonical::canonicalize(&batch)
+    }
+
+    /// Checks the batch, and every event in it, against the identity of its issuer.
+    pub fn verify(&self, issuer: &Identity) -> Result<(), IdpError> {
+        if self.batch_type != REPUTATION_BATCH {
+            return Err(IdpError::Reputation(format!("'{}' is not a reputation batch", self.batch_type)));
+        }
+        if self.signed_by.idp_id != issuer.identity.id {
+            return Err(IdpError::Reputation(format!("the batch was signed by {}, not {}", self.signed_by.idp_id, issuer.identity.id)));
+        }
+        check_signed(issuer, &self.signed_by, self.signed_at, &self.statement(), &self.signature)?;
+        for issued in &self.events {
+            if issued.subject != self.subject {
+                return Err(IdpError::Reputation(format!("'{}' is for {}, not the batch's subject {}", issued.event.event, issued.subject, self.subject)));
+            }
+            issued.event.verify(&issued.subject, &issued.score_name, issuer)?;
+        }
+        Ok(())
+    }
+}
+
+/// The bytes an issuer signs for `event`, counting toward `score_name` of `subject`.
+pub fn reputation_statement(subject: &IdpId, score_name: &str, event: &ReputationEvent) -> Vec<u8> {
+    canonical::canonicalize(&json!({
+        "type": REPUTATION_EVENT,
+        "subject": subject,
+        "score_name": score_name,
+        "event": event.event,
+        "change": event.change,
+        "timestamp": event.timestamp,
+    }))
+}
+
+/// How to compute a current score from a reputation history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ReputationPolicy {
+    /// The weight of each issuer's events, by identity id.
+    pub weights: BTreeMap<String, f64>,
+    /// The weight of events from issuers not in `weights`.
+    pub default_weight: f64,
+    /// The weight of events no issuer has signed.
+    pub unsigned_weight: f64,
+    /// How long an event takes to count for half as much; events never fade without one.
+    #[serde(with = "half_life_days")]
+    pub half_life: Option<Duration>,
+    /// The most any one issuer can move the score, up or down.
+    pub issuer_cap: Option<f64>,
+}
+
+impl Default for ReputationPolicy {
+    /// Every event counts in full, forever: the computed score is the raw sum.
+    fn default() -> Self {
+        ReputationPolicy { weights: BTreeMap::new(), default_weight: 1.0, unsigned_weight: 1.0, half_life: None, issuer_cap: None }
+    }
+}
+
+impl ReputationPolicy {
+    /// How much `event` counts at `now`, before any cap.
+    pub fn weigh(&self, event: &ReputationEvent, now: DateTime<Utc>) -> f64 {
+        let weight = match &event.issued_by {
+            Some(issued_by) => self.weights.get(issued_by.idp_id.as_str()).copied().unwrap_or(self.default_weight),
+            None => self.unsigned_weight,
+        };
+        let decay = match self.half_life {
+            Some(half_life) if half_life > Duration::zero() => {
+                let age = (now - event.timestamp).max(Duration::zero());
+                0.5_f64.powf(age.num_seconds() as f64 / half_life.num_seconds() as f64)
+            }
+            _ => 1.0,
+        };
+        event.change as f64 * weight * decay
+    }
+}
+
+/// A half-life of `days` days, which may be fractional: it must be positive, at least a second,
+/// and short enough to be a duration.
+pub fn half_life(days: f64) -> Result<Duration, IdpError> {
+    let invalid = |reason: &str| IdpError::Reputation(format!("a half-life of {:?} days {}", days, reason));
+    if !days.is_finite() || days <= 0.0 {
+        return Err(invalid("is not a positive number"));
+    }
+    let seconds = (days * 86_400.0).round();
+    if seconds < 1.0 {
+        return Err(invalid("is under a second"));
+    }
+    // Past what an i64 holds the cast saturates, and `try_seconds` refuses it.
+    Duration::try_seconds(seconds as i64).filter(|_| seconds < i64::MAX as f64).ok_or_else(|| invalid("is too long"))
+}
+
+// The half-life is written in days, as a number.
+mod half_life_days {
+    use chrono::Duration;
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(half_life: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+        match half_life {
+            Some(half_life) => serializer.serialize_some(&(half_life.num_seconds() as f64 / 86_400.0)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+        Option::<f64>::deserialize(deserializer)?.map(super::half_life).transpose().map_err(D::Error::custom)
+    }
+}
+
+impl Reputation {
+    /// Whether the history already holds `event`: the same event, at the same time, from the
+    /// same issuer.
+    pub fn contains(&self, event: &ReputationEvent) -> bool {
+        self.history.iter().any(|recorded| recorded.event == event.event && recorded.timestamp == event.timestamp && recorded.issued_by == event.issued_by)
+    }
+
+    /// The score at `now` under `policy`: the weighted, decayed sum of the history, with each
+    /// issuer's share capped.
+    pub fn compute(&self, policy: &ReputationPolicy, now: DateTime<Utc>) -> f64 {
+        let mut by_issuer: BTreeMap<Option<&str>, f64> = BTreeMap::new();
+        for event in &self.history {
+            *by_issuer.entry(event.issued_by.as_ref().map(|issued_by| issued_by.idp_id.as_str())).or_default() += policy.weigh(event, now);
+        }
+        by_issuer
+            .into_values()
+            .map(|share| match policy.issuer_cap {
+                Some(cap) => share.clamp(-cap.abs(), cap.abs()),
+                None => share,
+            })
+            .sum()
+    }
+}
+
+impl ReputationEvent {
+    pub fn is_signed(&self) -> bool {
+        self.issued_by.is_some() && !self.signature.is_empty()
+    }
+
+    /// Checks the issuer's signature on the event, for `score_name` of `subject`.
+    pub fn verify(&self, subject: &IdpId, score_name: &str, issuer: &Identity) -> Result<(), IdpError> {
+        let issued_by = self.issued_by.as_ref().ok_or_else(|| IdpError::Reputation(format!("'{}' is not signed", self.event)))?;
+        if issued_by.idp_id != issuer.identity.id {
+            return Err(IdpError::Reputation(format!("'{}' was issued by {}, not {}", self.event, issued_by.idp_id, issuer.identity.id)));
+        }
+        check_signed(issuer, issued_by, self.timestamp, &reputation_statement(subject, score_name, self), &self.signature)
+    }
+}
+
+impl Identity {
+    /// Signs a reputation event for `subject`, as its issuer.
+    pub fn issue_reputation_event(&self, subject: &IdpId, score_name: &str, event: &str, change: i64, signer: &dyn SigningBackend) -> Result<IssuedReputationEvent, IdpError> {
+        self.issue_reputation_event_at(subject, score_name, event, change, Utc::now(), signer)
+    }
+
+    fn issue_reputation_event_at(&self, subject: &IdpId, score_name: &str, event: &str, change: i64, timestamp: DateTime<Utc>, signer: &dyn SigningBackend) -> Result<IssuedReputationEvent, IdpError> {
+        if score_name.trim().is_empty() || event.trim().is_empty() {
+            return Err(IdpError::Reputation("a reputation event needs a score and a description".to_string()));
+        }
+        let mut issued = IssuedReputationEvent {
+            subject: subject.clone(),
+            score_name: score_name.to_string(),
+            event: ReputationEvent {
+                event: event.to_string(),
+                change,
+                timestamp,
+                issued_by: Some(Signer { idp_id: self.identity.id.clone(), key_id: self.issuing_key(signer)?.key_id.clone() }),
+                signature: vec![],
+                unknown_fields: Default::default(),
+            },
+        };
+        let signature = signer.sign(&reputation_statement(subject, score_name, &issued.event))?;
+        issued.event.signature.push(signature);
+        Ok(issued)
+    }
+
+    /// Signs `entries` for `subject` as a batch, each event and the whole, for the subject to
+    /// import.
+    pub fn export_reputation(&self, subject: &IdpId, entries: &[ReputationEntry], signer: &dyn SigningBackend) -> Result<ReputationBatch, IdpError> {
+        let now = Utc::now();
+        let events = entries.iter().map(|entry| self.issue_reputation_event_at(subject, &entry.score_name, &entry.event, entry.change, entry.timestamp.unwrap_or(now), signer)).collect::<Result<_, _>>()?;
+        let mut batch = ReputationBatch {
+            batch_type: REPUTATION_BATCH.to_string(),
+            subject: subject.clone(),
+            events,
+            signed_by: Signer { idp_id: self.identity.id.clone(), key_id: self.issuing_key(signer)?.key_id.clone() },
+            signed_at: now,
+            signature: vec![],
+        };
+        batch.signature.push(signer.sign(&batch.statement())?);
+        Ok(batch)
+    }
+
+    /// Takes in a batch for this identity once it verifies against its issuer, found through
+    /// `issuers`. Events already in the history are skipped; the rest are added. Returns how
+    /// many were added and how many skipped.
+    pub fn import_reputation(&mut self, batch: ReputationBatch, issuers: &dyn IdentityResolver) -> Result<(usize, usize), IdpError> {
+        // 1. The whole batch checks out, or nothing is taken in.
+        if batch.subject != self.identity.id {
+            return Err(IdpError::Reputation(format!("the batch is for {}, not {}", batch.subject, self.identity.id)));
+        }
+        let issuer = issuers.resolve(batch.signed_by.idp_id.as_str())?.ok_or_else(|| IdpError::Reputation(format!("the issuer {} of the batch is not known", batch.signed_by.idp_id)))?;
+        batch.verify(&issuer)?;
+
+        // 2. Merged into the histories, once each.
+        let (mut added, mut skipped) = (0, 0);
+        for issued in batch.events {
+            match self.reputation.iter().find(|score| score.score_name == issued.score_name) {
+                Some(score) if score.contains(&issued.event) => skipped += 1,
+                _ => {
+                    self.add_reputation_event(issued, &vec![issuer.clone()], true)?;
+                    added += 1;
+                }
+            }
+        }
+        Ok((added, skipped))
+    }
+
+    /// Attests to `subject`'s score, as a credential for them to hold.
+    pub fn attest_reputation(&self, subject: &Identity, attestation: &ReputationAttestation, expires_at: Option<DateTime<Utc>>, signer: &dyn SigningBackend) -> Result<IssuedCredential, IdpError> {
+        self.issue_credential(subject, &attestation.to_claim()?, expires_at, signer)
+    }
+
+    /// The scores others attest to that this identity holds, and have not expired at `now`.
+    pub fn attested_reputation(&self, now: DateTime<Utc>) -> Vec<(&Credential, ReputationAttestation)> {
+        self.credentials
+            .iter()
+            .filter(|credential| !credential.is_expired(now))
+            .filter_map(|credential| credential.parse_claim::<ReputationAttestation>().ok().map(|attestation| (credential, attestation)))
+            .collect()
+    }
+
+    /// Adds an event to one of this identity's scores, starting the score if it is new, and
+    /// moves its value by the event's change. A signed event must be issued to this identity
+    /// and verify against its issuer, found through `issuers`; an unsigned one is refused if
+    /// `strict`. An event already in the history is refused too.
+    pub fn add_reputation_event(&mut self, issued: IssuedReputationEvent, issuers: &dyn IdentityResolver, strict: bool) -> Result<&Reputation, IdpError> {
+        // 1. For this identity, and signed by who it says, or unsigned and allowed to be.
+        let IssuedReputationEvent { subject, score_name, event } = issued;
+        if subject != self.identity.id {
+            return Err(IdpError::Reputation(format!("'{}' is for {}, not {}", event.event, subject, self.identity.id)));
+        }
+        match &event.issued_by {
+            Some(issued_by) => {
+                let issuer = issuers.resolve(issued_by.idp_id.as_str())?.ok_or_else(|| IdpError::Reputation(format!("the issuer {} of '{}' is not known", issued_by.idp_id, event.event)))?;
+                event.verify(&subject, &score_name, &issuer)?;
+            }
+            None if strict => return Err(IdpError::Reputation(format!("'{}' is not signed, and only signed events are accepted", event.event))),
+            None => {}
+        }
+
+        // 2. Into the score's history, once.
+        let index = match self.reputation.iter().position(|score| score.score_name == score_name) {
+            Some(index) => index,
+            None => {
+                self.reputation.push(Reputation { score_name: score_name.clone(), value: 0, history: vec![], unknown_fields: Default::default() });
+                self.reputation.len() - 1
+            }
+        };
+        let score = &mut self.reputation[index];
+        if score.contains(&event) {
+            return Err(IdpError::Reputation(format!("'{}' is already in the history of {}", event.event, score_name)));
+        }
+        score.value += event.change;
+        score.history.push(event);
+        self.touch();
+        Ok(&self.reputation[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_adds_only_reputation_events_that_verify() {
+        let (market, market_key) = Identity::new("Market", "Runs a marketplace.").unwrap();
+        let (mut seller, _) = Identity::new("Seller", "Sells things.").unwrap();
+        let issuers = vec![market.clone()];
+        let subject = seller.identity.id.clone();
+
+        // A signed sale counts; the same event twice does not.
+        let sale = market.issue_reputation_event(&subject, "seller", "sale completed", 5, &market_key).unwrap();
+        assert_eq!(seller.add_reputation_event(sale.clone(), &issuers, true).unwrap().value, 5);
+        assert!(seller.add_reputation_event(sale.clone(), &issuers, true).is_err());
+        sale.event.verify(&subject, "seller", &market).unwrap();
+        assert!(sale.event.verify(&subject, "buyer", &market).is_err());
+
+        // An inflated change breaks the signature; an unknown issuer is refused.
+        let mut inflated = market.issue_reputation_event(&subject, "seller", "sale completed", 5, &market_key).unwrap();
+        inflated.event.change = 50;
+        assert!(seller.add_reputation_event(inflated, &issuers, false).is_err());
+        let other = market.issue_reputation_event(&subject, "seller", "refund", -1, &market_key).unwrap();
+        assert!(seller.add_reputation_event(other, &Vec::new(), false).is_err());
+
+        // Unsigned events are only taken when not strict.
+        let mut unsigned = market.issue_reputation_event(&subject, "seller", "self-reported", 10, &market_key).unwrap();
+        (unsigned.event.issued_by, unsigned.event.signature) = (None, vec![]);
+        assert!(seller.add_reputation_event(unsigned.clone(), &issuers, true).is_err());
+        assert_eq!(seller.add_reputation_event(unsigned, &issuers, false).unwrap().value, 15);
+        assert_eq!(seller.reputation[0].history.iter().filter(|event| event.is_signed()).count(), 1);
+    }
+
+    #[test]
+    fn it_computes_scores_under_a_policy() {
+        let (market, market_key) = Identity::new("Market", "Runs a marketplace.").unwrap();
+        let (mut seller, _) = Identity::new("Seller", "Sells things.").unwrap();
+        let subject = seller.identity.id.clone();
+        for change in [4, 4, 4] {
+            let mut sale = market.issue_reputation_event(&subject, "seller", "sale completed", change, &market_key).unwrap();
+            sale.event.timestamp -= Duration::days(10);
+            sale.event.issued_by = None;
+            seller.add_reputation_event(sale, &Vec::<Identity>::new(), false).unwrap();
+        }
+        let sale = market.issue_reputation_event(&subject, "seller", "sale completed", 8, &market_key).unwrap();
+        seller.add_reputation_event(sale, &vec![market.clone()], true).unwrap();
+        let score = &seller.reputation[0];
+        let now = Utc::now();
+
+        // By default the computed score is the raw one.
+        assert_eq!(score.value, 20);
+        assert_eq!(score.compute(&ReputationPolicy::default(), now), 20.0);
+
+        // Unsigned events can count for less, fade over ten days to half, or be capped.
+        let unsigned = ReputationPolicy { unsigned_weight: 0.5, ..Default::default() };
+        assert_eq!(score.compute(&unsigned, now), 14.0);
+        let fading = ReputationPolicy { half_life: Some(Duration::days(10)), ..Default::default() };
+        assert!((score.compute(&fading, now) - 14.0).abs() < 0.01);
+        let capped = ReputationPolicy { issuer_cap: Some(5.0), weights: [(market.identity.id.to_string(), 2.0)].into(), ..Default::default() };
+        assert_eq!(score.compute(&capped, now), 10.0);
+
+        let policy: ReputationPolicy = serde_json::from_str(r#"{"half_life": 10, "issuer_cap": 5}"#).unwrap();
+        assert_eq!((policy.half_life, policy.default_weight), (Some(Duration::days(10)), 1.0));
+        for days in ["1e300", "0", "-3"] {
+            assert!(serde_json::from_str::<ReputationPolicy>(&format!(r#"{{"half_life": {}}}"#, days)).is_err(), "{}", days);
+        }
+        assert!(half_life(f64::NAN).is_err() && half_life(1e-9).is_err());
+    }
+
+    #[test]
+    fn it_carries_attested_scores_as_credentials() {
+        let (market, market_key) = Identity::new("Market", "Runs a marketplace.").unwrap();
+        let (mut seller, _) = Identity::new("Seller", "Sells things.").unwrap();
+        let rating = ReputationAttestation { score_name: "seller rating".to_string(), value: 4.8, scale: Some(5.0), platform: Some("market.example".to_string()), as_of: Utc::now().date_naive() };
+        let issued = market.attest_reputation(&seller, &rating, None, &market_key).unwrap();
+        seller.add_credential(issued).unwrap();
+
+        // The holder's copy reads back as the score, and verifies against the issuer's keys.
+        let attested = seller.attested_reputation(Utc::now());
+        assert_eq!(attested.len(), 1);
+        assert_eq!((attested[0].0.issued_by.as_str(), &attested[0].1), (market.identity.id.as_str(), &rating));
+        let proof = seller.credential_proof(attested[0].0).unwrap();
+        assert!(seller.verify_credential(attested[0].0, proof, &market).is_valid());
+
+        let off_scale = ReputationAttestation { value: 6.0, ..rating };
+        assert!(market.attest_reputation(&seller, &off_scale, None, &market_key).is_err());
+    }
+
+    #[test]
+    fn it_imports_reputation_batches_without_duplicates() {
+        let (market, market_key) = Identity::new("Market", "Runs a marketplace.").unwrap();
+        let (mut seller, _) = Identity::new("Seller", "Sells things.").unwrap();
+        let issuers = vec![market.clone()];
+        let subject = seller.identity.id.clone();
+        let entry = |event: &str, change, days| ReputationEntry { score_name: "seller".to_string(), event: event.to_string(), change, timestamp: Some(Utc::now() - Duration::days(days)) };
+        let batch = market.export_reputation(&subject, &[entry("sale completed", 5, 30), entry("late delivery", -2, 20)], &market_key).unwrap();
+
+        // The batch merges in once; a second import adds nothing.
+        assert_eq!(seller.import_reputation(batch.clone(), &issuers).unwrap(), (2, 0));
+        assert_eq!(seller.import_reputation(batch.clone(), &issuers).unwrap(), (0, 2));
+        assert_eq!(seller.reputation[0].value, 3);
+
+        // A batch that was changed, or is for someone else, is refused whole.
+        let mut changed = batch.clone();
+        changed.events[1].event.change = 2;
+        assert!(changed.verify(&market).is_err());
+        assert!(market.clone().import_reputation(batch, &issuers).is_err());
+    }
+}
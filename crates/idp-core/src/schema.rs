@@ -0,0 +1,122 @@
+// crates/idp-core/src/schema.rs
+
+// Validating documents against the JSON Schema their `schema_url` names. The schema of every
+// released version ships with the crate; others can be fetched with the `remote-schema` feature.
+
+use std::fmt;
+
+use serde_json::Value;
+
+use crate::{Identity, IdpError};
+
+/// The `schema_url` of documents written by this version.
+pub const SCHEMA_URL: &str = "https://idp.org/schemas/v0.2.1";
+
+/// The schemas bundled with the crate, by URL.
+const BUNDLED_SCHEMAS: [(&str, &str); 1] = [(SCHEMA_URL, include_str!("../schemas/idp-v0.2.1.schema.json"))];
+
+/// One way a document breaks its schema.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SchemaViolation {
+    /// Where in the document, as a dot-path (e.g. `system.public_keys.0.status`); empty for
+    /// the document itself.
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.path.is_empty() {
+            true => write!(f, "(document): {}", self.message),
+            false => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+/// The schema published at `url`: a bundled copy, or with the `remote-schema` feature,
+/// whatever the URL serves.
+pub fn schema_for(url: &str) -> Result<Value, IdpError> {
+    if let Some((_, schema)) = BUNDLED_SCHEMAS.iter().find(|(known, _)| *known == url) {
+        return Ok(serde_json::from_str(schema)?);
+    }
+    fetch_schema(url)
+}
+
+#[cfg(feature = "remote-schema")]
+fn fetch_schema(url: &str) -> Result<Value, IdpError> {
+    let response = ureq::get(url).call().map_err(|e| IdpError::Schema(format!("could not fetch '{}': {}", url, e)))?;
+    Ok(serde_json::from_reader(response.into_reader())?)
+}
+
+#[cfg(not(feature = "remote-schema"))]
+fn fetch_schema(url: &str) -> Result<Value, IdpError> {
+    Err(IdpError::Schema(format!(
+        "no schema is bundled for '{}' and this build cannot fetch remote schemas",
+        url
+    )))
+}
+
+/// Validates a document in its generic JSON form, so that documents too broken to load can
+/// still be checked. A document without a `schema_url` is checked against the current schema.
+pub fn validate_document(document: &Value) -> Result<(), IdpError> {
+    // 1. Compile the schema the document asks for.
+    let url = document.pointer("/identity/schema_url").and_then(Value::as_str).unwrap_or(SCHEMA_URL);
+    let validator = jsonschema::options()
+        .should_validate_formats(true)
+        .build(&schema_for(url)?)
+        .map_err(|e| IdpError::Schema(format!("the schema at '{}' is invalid: {}", url, e)))?;
+
+    // 2. Collect every violation, each with the dot-path of the offending value.
+    let violations: Vec<SchemaViolation> = validator
+        .iter_errors(document)
+        .map(|error| SchemaViolation {
+            path: error.instance_path.into_iter().map(|segment| segment.to_string()).collect::<Vec<_>>().join("."),
+            message: error.to_string(),
+        })
+        .collect();
+    match violations.is_empty() {
+        true => Ok(()),
+        false => Err(IdpError::SchemaViolations(violations)),
+    }
+}
+
+impl Identity {
+    /// Validates the serialized document against the schema its `schema_url` names.
+    pub fn validate_schema(&self) -> Result<(), IdpError> {
+        validate_document(&serde_json::to_value(self)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::KeyStatus;
+
+    #[test]
+    fn it_validates_documents_against_their_schema() {
+        let (mut identity, _) = Identity::new("Schema User", "Well formed.").unwrap();
+        identity.validate_schema().unwrap();
+
+        // Each violation is reported with the path of the offending value.
+        identity.system.public_keys[0].status = KeyStatus::Other("lost".to_string());
+        identity.identity.version = "two".to_string();
+        let Err(IdpError::SchemaViolations(violations)) = identity.validate_schema() else {
+            panic!("the document should not validate");
+        };
+        let mut paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        paths.sort();
+        assert_eq!(paths, ["identity.version", "system.public_keys.0.status"]);
+
+        // Documents too broken to load are still checked.
+        let document = serde_json::json!({ "identity": {}, "system": { "public_keys": "none" }, "core": { "name": "X", "bio": "" } });
+        let Err(IdpError::SchemaViolations(violations)) = validate_document(&document) else {
+            panic!("the document should not validate");
+        };
+        assert!(violations.iter().any(|v| v.path == "system.public_keys"));
+
+        // Unknown schemas are not guessed at.
+        identity.identity.schema_url = "https://example.com/other-schema".to_string();
+        #[cfg(not(feature = "remote-schema"))]
+        assert!(matches!(identity.validate_schema(), Err(IdpError::Schema(_))));
+    }
+}
@@ -0,0 +1,280 @@
+// crates/idp-core/src/sd_jwt.rs
+
+// Selective disclosure with SD-JWT (RFC 9901). The issuer signs only salted hashes of the claim
+// fields; each field travels next to the JWT as a disclosure, and the holder passes on just the
+// ones a verifier needs, e.g. `age_over_18` without `birthdate`. The verifier hashes what it was
+// shown, checks the issuer committed to it, and rebuilds the claims from those fields alone.
+
+use chrono::{DateTime, Utc};
+use data_encoding::{BASE64URL_NOPAD, HEXLOWER};
+use ring::{digest, rand::{self, SecureRandom}};
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::jwt::{self, Jws};
+use crate::signer::SigningBackend;
+use crate::{canonical, Credential, Identity, IdpError, IdpId, Proof, Provenance, PublicKey, SignatureComponent, Signer};
+
+/// The `typ` of an SD-JWT VC, also recorded as the format of the credentials kept from one.
+pub const SD_JWT_FORMAT: &str = "vc+sd-jwt";
+
+/// The `Proof.proof_type` of a credential kept from an SD-JWT.
+pub const SD_JWT_PROOF: &str = "SdJwtCredential";
+
+// The only digest algorithm used for disclosures.
+const SD_ALG: &str = "sha-256";
+
+/// One claim field as the issuer salted it: `[salt, name, value]`, Base64url-encoded.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disclosure {
+    pub name: String,
+    pub value: Value,
+    // The encoding as issued; the digest is over these exact characters.
+    encoded: String,
+}
+
+impl Disclosure {
+    /// Salts a field with 128 random bits, so its digest cannot be guessed from likely values.
+    pub fn new(name: &str, value: Value) -> Result<Disclosure, IdpError> {
+        let mut salt = [0u8; 16];
+        rand::SystemRandom::new().fill(&mut salt).map_err(|e| IdpError::Crypto(e.to_string()))?;
+        let encoded = BASE64URL_NOPAD.encode(json!([BASE64URL_NOPAD.encode(&salt), name, value]).to_string().as_bytes());
+        Ok(Disclosure { name: name.to_string(), value, encoded })
+    }
+
+    /// Decodes a disclosure of an object field.
+    pub fn parse(encoded: &str) -> Result<Disclosure, IdpError> {
+        let invalid = || IdpError::Jwt(format!("'{}' is not a disclosure of a claim field", encoded));
+        let bytes = BASE64URL_NOPAD.decode(encoded.as_bytes()).map_err(|_| invalid())?;
+        let Ok(Value::Array(parts)) = serde_json::from_slice::<Value>(&bytes) else {
+            return Err(invalid());
+        };
+        match &parts[..] {
+            [Value::String(_), Value::String(name), value] if name != "_sd" && name != "..." => {
+                Ok(Disclosure { name: name.clone(), value: value.clone(), encoded: encoded.to_string() })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// The digest the issuer signs in place of the field.
+    pub fn digest(&self) -> String {
+        BASE64URL_NOPAD.encode(digest::digest(&digest::SHA256, self.encoded.as_bytes()).as_ref())
+    }
+}
+
+/// An SD-JWT: the issuer-signed JWT and the disclosures that go with it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SdJwt {
+    pub jwt: String,
+    pub disclosures: Vec<Disclosure>,
+}
+
+/// What a verifier learns from an SD-JWT whose signature and disclosures check out.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Disclosed {
+    pub issuer: String,
+    pub subject: Option<String>,
+    /// The issuer's key, as the JWT names it (`kid`).
+    pub key: Option<String>,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Only the fields that were disclosed.
+    pub claims: Map<String, Value>,
+}
+
+impl SdJwt {
+    /// Reads `jwt~disclosure~...~`. Key binding JWTs after the last `~` are not supported.
+    pub fn parse(text: &str) -> Result<SdJwt, IdpError> {
+        let mut parts: Vec<&str> = text.trim().split('~').collect();
+        if parts.len() < 2 {
+            return Err(IdpError::Jwt("an SD-JWT is a JWT followed by `~`-separated disclosures".to_string()));
+        }
+        if parts.pop() != Some("") {
+            return Err(IdpError::Jwt("SD-JWTs with a key binding JWT are not supported".to_string()));
+        }
+        Ok(SdJwt { jwt: parts[0].to_string(), disclosures: parts[1..].iter().map(|part| Disclosure::parse(part)).collect::<Result<_, _>>()? })
+    }
+
+    /// The same SD-JWT with only the disclosures of the fields in `names`, for the holder to present.
+    pub fn disclose(&self, names: &[&str]) -> Result<SdJwt, IdpError> {
+        if let Some(name) = names.iter().find(|name| !self.disclosures.iter().any(|disclosure| disclosure.name == **name)) {
+            return Err(IdpError::Credential(format!("the credential has no field '{}' to disclose", name)));
+        }
+        let disclosures = self.disclosures.iter().filter(|disclosure| names.contains(&disclosure.name.as_str())).cloned().collect();
+        Ok(SdJwt { jwt: self.jwt.clone(), disclosures })
+    }
+
+    /// Checks the issuer's signature with `issuer_key` and every disclosure against the digests
+    /// it signed, and returns the claims rebuilt from the disclosed fields.
+    pub fn verify(&self, issuer_key: &PublicKey) -> Result<Disclosed, IdpError> {
+        // 1. The issuer's signature over the JWT.
+        let jws = Jws::decode(&self.jwt)?;
+        jws.verify(issuer_key)?;
+        let payload = &jws.payload;
+        if payload["_sd_alg"].as_str().unwrap_or(SD_ALG) != SD_ALG {
+            return Err(IdpError::Jwt(format!("unsupported _sd_alg '{}'", payload["_sd_alg"])));
+        }
+
+        // 2. Every disclosure must be one the issuer committed to, and each field shown once.
+        let digests: Vec<&str> = payload["_sd"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let mut claims = Map::new();
+        for disclosure in &self.disclosures {
+            if !digests.contains(&disclosure.digest().as_str()) {
+                return Err(IdpError::Credential(format!("the disclosure of '{}' was not issued with this credential", disclosure.name)));
+            }
+            if payload.get(&disclosure.name).is_some() || claims.insert(disclosure.name.clone(), disclosure.value.clone()).is_some() {
+                return Err(IdpError::Credential(format!("the field '{}' is disclosed more than once", disclosure.name)));
+            }
+        }
+
+        // 3. The validity period, in seconds since the epoch.
+        let time = |field: &str| match &payload[field] {
+            Value::Null => Ok(None),
+            value => value.as_i64().and_then(|seconds| DateTime::from_timestamp(seconds, 0)).map(Some).ok_or_else(|| IdpError::Jwt(format!("invalid {}", field))),
+        };
+        let issued_at = time("iat")?.ok_or_else(|| IdpError::Jwt("the SD-JWT has no iat".to_string()))?;
+        let expires_at = time("exp")?;
+        if let Some(expires_at) = expires_at.filter(|expires_at| *expires_at <= Utc::now()) {
+            return Err(IdpError::Credential(format!("the SD-JWT expired at {}", expires_at.to_rfc3339())));
+        }
+        Ok(Disclosed {
+            issuer: payload["iss"].as_str().ok_or_else(|| IdpError::Jwt("the SD-JWT has no iss".to_string()))?.to_string(),
+            subject: payload["sub"].as_str().map(str::to_string),
+            key: jws.header["kid"].as_str().map(str::to_string),
+            issued_at,
+            expires_at,
+            claims,
+        })
+    }
+}
+
+impl std::fmt::Display for SdJwt {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}~", self.jwt)?;
+        for disclosure in &self.disclosures {
+            write!(f, "{}~", disclosure.encoded)?;
+        }
+        Ok(())
+    }
+}
+
+impl Identity {
+    /// Issues an SD-JWT about `subject` whose `claims` can each be disclosed on their own, signed
+    /// with `signer`'s key of this identity, which must be a signing key.
+    pub fn issue_sd_jwt(
+        &self,
+        subject: &Identity,
+        claims: &Map<String, Value>,
+        expires_at: Option<DateTime<Utc>>,
+        signer: &dyn SigningBackend,
+    ) -> Result<SdJwt, IdpError> {
+        let key = self.issuing_key(signer)?;
+        let alg = jwt::jws_alg(&key.algorithm).ok_or_else(|| IdpError::Jwt(format!("{} keys cannot sign JWTs", key.algorithm)))?;
+        let issued_at = Utc::now();
+        if expires_at.is_some_and(|expires_at| expires_at <= issued_at) {
+            return Err(IdpError::Credential("the credential would expire before it is issued".to_string()));
+        }
+
+        // 1. Salt every field; sorting the digests hides the order the fields came in.
+        let disclosures = claims.iter().map(|(name, value)| Disclosure::new(name, value.clone())).collect::<Result<Vec<_>, _>>()?;
+        let mut digests: Vec<String> = disclosures.iter().map(Disclosure::digest).collect();
+        digests.sort();
+
+        // 2. Sign the digests, not the fields.
+        let header = json!({ "alg": alg, "typ": SD_JWT_FORMAT, "kid": format!("{}#{}", self.identity.id, key.key_id) });
+        let mut payload = json!({
+            "iss": self.identity.id,
+            "sub": subject.identity.id,
+            "iat": issued_at.timestamp(),
+            "_sd": digests,
+            "_sd_alg": SD_ALG,
+        });
+        if let Some(expires_at) = expires_at {
+            payload["exp"] = json!(expires_at.timestamp());
+        }
+        let signing_input = jwt::signing_input(&header, &payload);
+        let jwt = jwt::assemble(&signing_input, &signer.sign(signing_input.as_bytes())?)?;
+        Ok(SdJwt { jwt, disclosures })
+    }
+
+    /// Verifies an SD-JWT issued to this identity with `issuer_key` and keeps it as a credential
+    /// whose claim is the disclosed fields as canonical JSON, with the SD-JWT in its proof.
+    pub fn add_sd_jwt(&mut self, sd_jwt: &SdJwt, issuer_key: &PublicKey) -> Result<&Credential, IdpError> {
+        // 1. It must check out, and be about this identity.
+        let disclosed = sd_jwt.verify(issuer_key)?;
+        if disclosed.subject.as_deref() != Some(self.identity.id.as_str()) {
+            return Err(IdpError::Credential(format!("the SD-JWT is about '{}', not this identity", disclosed.subject.unwrap_or_default())));
+        }
+
+        // 2. Keep all of it, so any selection of fields can be presented later.
+        let text = sd_jwt.to_string();
+        let proof_id = format!("sd-jwt-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, sd_jwt.jwt.as_bytes()).as_ref()[..8]));
+        if self.proofs.iter().any(|proof| proof.proof_id == proof_id) {
+            return Err(IdpError::Credential(format!("the SD-JWT '{}' is already in the document", proof_id)));
+        }
+        let verification_method = disclosed.key.unwrap_or_else(|| format!("{}#{}", disclosed.issuer, issuer_key.key_id));
+        self.proofs.push(Proof {
+            proof_id: proof_id.clone(),
+            proof_type: SD_JWT_PROOF.to_string(),
+            claim_hash: canonical::hash(text.as_bytes()),
+            signed_by: Signer { idp_id: IdpId::parse(&disclosed.issuer)?, key_id: verification_method.clone() },
+            signature: vec![SignatureComponent { algorithm: SD_JWT_FORMAT.to_string(), value: text }],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        });
+        self.credentials.push(Credential {
+            claim: String::from_utf8(canonical::canonicalize(&Value::Object(disclosed.claims))).expect("canonical JSON is UTF-8"),
+            issued_by: disclosed.issuer,
+            issued_at: disclosed.issued_at,
+            expires_at: disclosed.expires_at,
+            proof: proof_id,
+            provenance: Some(Provenance { format: SD_JWT_FORMAT.to_string(), verification_method, imported_at: Utc::now() }),
+            status: None,
+            unknown_fields: Default::default(),
+        });
+        self.touch();
+        Ok(self.credentials.last().expect("just added"))
+    }
+
+    /// The SD-JWT a credential was kept from, with all its disclosures.
+    pub fn held_sd_jwt(&self, credential: &Credential) -> Option<SdJwt> {
+        let proof = self.credential_proof(credential).filter(|proof| proof.proof_type == SD_JWT_PROOF)?;
+        SdJwt::parse(&proof.signature.first()?.value).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_discloses_only_the_fields_presented() {
+        let (issuer, issuer_key) = Identity::new("Registry", "Knows birthdates.").unwrap();
+        let (mut holder, _) = Identity::new("Holder", "Is over 18.").unwrap();
+        let claims = json!({ "birthdate": "2000-01-01", "age_over_18": true }).as_object().unwrap().clone();
+        let issued = issuer.issue_sd_jwt(&holder, &claims, None, &issuer_key).unwrap();
+        let issuer_public_key = &issuer.system.public_keys[0];
+
+        // The holder keeps every field.
+        let credential = holder.add_sd_jwt(&SdJwt::parse(&issued.to_string()).unwrap(), issuer_public_key).unwrap().clone();
+        assert_eq!(credential.claim, r#"{"age_over_18":true,"birthdate":"2000-01-01"}"#);
+        let held = holder.held_sd_jwt(&credential).unwrap();
+
+        // The verifier sees only what was disclosed, and the JWT itself names no field.
+        let presented = SdJwt::parse(&held.disclose(&["age_over_18"]).unwrap().to_string()).unwrap();
+        let disclosed = presented.verify(issuer_public_key).unwrap();
+        assert_eq!(Value::Object(disclosed.claims), json!({ "age_over_18": true }));
+        assert!(!Jws::decode(&presented.jwt).unwrap().payload.to_string().contains("birthdate"));
+        assert!(held.disclose(&["name"]).is_err());
+
+        // Disclosures the issuer did not commit to, or another issuer's key, are refused.
+        let mut forged = presented.clone();
+        forged.disclosures.push(Disclosure::new("birthdate", json!("1990-01-01")).unwrap());
+        assert!(matches!(forged.verify(issuer_public_key), Err(IdpError::Credential(_))));
+        let (stranger, _) = Identity::new("Stranger", "Not the registry.").unwrap();
+        assert!(presented.verify(&stranger.system.public_keys[0]).is_err());
+        assert!(matches!(SdJwt::parse(&presented.jwt), Err(IdpError::Jwt(_))));
+    }
+}
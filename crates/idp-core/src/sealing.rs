@@ -0,0 +1,237 @@
+// crates/idp-core/src/sealing.rs
+
+// Encrypting single sections of a document (credentials, contracts, reputation, consent and its
+// log) while identity, system and core stay readable. A sealed section is written in place of its
+// contents as `{ encrypted: true, ... }`, encrypted to the identity's own X25519 agreement key.
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::encryption::EncryptedMessage;
+use crate::{canonical, Identity, IdpError};
+
+/// The sections that can be sealed.
+pub const SEALABLE_SECTIONS: [&str; 5] = ["credentials", "contracts", "reputation", "consent", "consent_log"];
+
+/// A section as it is stored while sealed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct SealedSection {
+    /// Always true; marks the block as ciphertext rather than contents.
+    pub encrypted: bool,
+    #[serde(flatten)]
+    pub message: EncryptedMessage,
+
+    // The hash of the contents as last sealed or decrypted, while they are in memory.
+    #[serde(skip)]
+    opened: Option<String>,
+}
+
+impl Identity {
+    /// Encrypts a section to the identity's agreement key. Its contents stay readable in
+    /// memory, but are written only as ciphertext from now on.
+    pub fn seal_section(&mut self, name: &str) -> Result<(), IdpError> {
+        let contents = self.section_contents(name)?;
+        let message = Identity::encrypt_for(self, &contents)?;
+        let sealed = SealedSection {
+            encrypted: true,
+            message,
+            opened: Some(canonical::hash(&contents)),
+        };
+        self.sealed.insert(name.to_string(), sealed);
+        Ok(())
+    }
+
+    /// Decrypts every sealed section so its contents can be read; they stay sealed on disk.
+    pub fn decrypt_sections(&mut self, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let names: Vec<String> = self.sealed.keys().cloned().collect();
+        for name in names {
+            let contents = self.decrypt(&self.sealed[&name].message, root_private_key)?;
+            self.set_section_contents(&name, &contents)?;
+            self.sealed.get_mut(&name).expect("listed above").opened = Some(canonical::hash(&contents));
+        }
+        Ok(())
+    }
+
+    /// Stops sealing a section, writing its contents in the clear again.
+    pub fn unseal_section(&mut self, name: &str, root_private_key: &[u8]) -> Result<(), IdpError> {
+        let sealed = self.sealed.get(name).ok_or_else(|| not_sealable(name, "the section is not sealed"))?;
+        let contents = self.decrypt(&sealed.message, root_private_key)?;
+        self.set_section_contents(name, &contents)?;
+        self.sealed.remove(name);
+        Ok(())
+    }
+
+    /// Re-encrypts sealed sections whose contents were edited since they were sealed or
+    /// decrypted. Call it before saving, or the edits are lost.
+    pub fn reseal(&mut self) -> Result<(), IdpError> {
+        let names: Vec<String> = self.sealed.keys().cloned().collect();
+        for name in names {
+            let contents = self.section_contents(&name)?;
+            match &self.sealed[&name].opened {
+                Some(hash) if *hash == canonical::hash(&contents) => {}
+                Some(_) => self.seal_section(&name)?,
+                // Still locked: anything in memory was added without reading what is sealed.
+                None if contents == b"[]" => {}
+                None => return Err(not_sealable(&name, "the section is sealed; decrypt it before editing")),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_sealed(&self, name: &str) -> bool {
+        self.sealed.contains_key(name)
+    }
+
+    /// Whether a section is sealed and has not been decrypted, so its contents are not in memory.
+    pub fn is_locked(&self, name: &str) -> bool {
+        self.sealed.get(name).is_some_and(|sealed| sealed.opened.is_none())
+    }
+
+    fn section_contents(&self, name: &str) -> Result<Vec<u8>, IdpError> {
+        Ok(match name {
+            "credentials" => serde_json::to_vec(&self.credentials)?,
+            "contracts" => serde_json::to_vec(&self.contracts)?,
+            "reputation" => serde_json::to_vec(&self.reputation)?,
+            "consent" => serde_json::to_vec(&self.consent)?,
+            "consent_log" => serde_json::to_vec(&self.consent_log)?,
+            _ => return Err(not_sealable(name, "only credentials, contracts, reputation, consent and consent_log can be sealed")),
+        })
+    }
+
+    fn set_section_contents(&mut self, name: &str, contents: &[u8]) -> Result<(), IdpError> {
+        match name {
+            "credentials" => self.credentials = serde_json::from_slice(contents)?,
+            "contracts" => self.contracts = serde_json::from_slice(contents)?,
+            "reputation" => self.reputation = serde_json::from_slice(contents)?,
+            "consent" => self.consent = serde_json::from_slice(contents)?,
+            "consent_log" => self.consent_log = serde_json::from_slice(contents)?,
+            _ => return Err(not_sealable(name, "only credentials, contracts, reputation, consent and consent_log can be sealed")),
+        }
+        Ok(())
+    }
+}
+
+fn not_sealable(name: &str, reason: &str) -> IdpError {
+    IdpError::Path {
+        path: name.to_string(),
+        reason: reason.to_string(),
+    }
+}
+
+// Sealed sections found while a document is being read, handed from the section fields to
+// `Identity::deserialize`.
+thread_local! {
+    static FOUND: RefCell<BTreeMap<String, SealedSection>> = const { RefCell::new(BTreeMap::new()) };
+}
+
+impl Serialize for Identity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.sealed.is_empty() {
+            return Identity::serialize(self, serializer);
+        }
+
+        // Write the plain document, then put each sealed section in place of its contents.
+        struct Plain<'a>(&'a Identity);
+        impl Serialize for Plain<'_> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                Identity::serialize(self.0, serializer)
+            }
+        }
+        let mut document = serde_yaml::to_value(Plain(self)).map_err(serde::ser::Error::custom)?;
+        let map = document.as_mapping_mut().expect("a document is a mapping");
+        for (name, sealed) in &self.sealed {
+            map.insert(name.as_str().into(), serde_yaml::to_value(sealed).map_err(serde::ser::Error::custom)?);
+        }
+        document.serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Identity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let outer = FOUND.take();
+        let result = Identity::deserialize(deserializer);
+        let found = FOUND.replace(outer);
+        let mut identity = result?;
+        identity.sealed = found;
+        Ok(identity)
+    }
+}
+
+// A section field: either its contents, or a sealed block that is set aside.
+fn section<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D, name: &str) -> Result<Vec<T>, D::Error> {
+    let value = serde_yaml::Value::deserialize(deserializer)?;
+    if value.get("encrypted").is_some() {
+        let sealed: SealedSection = serde_yaml::from_value(value).map_err(de::Error::custom)?;
+        FOUND.with_borrow_mut(|found| found.insert(name.to_string(), sealed));
+        return Ok(vec![]);
+    }
+    serde_yaml::from_value(value).map_err(de::Error::custom)
+}
+
+pub(crate) fn credentials<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    section(deserializer, "credentials")
+}
+
+pub(crate) fn contracts<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    section(deserializer, "contracts")
+}
+
+pub(crate) fn reputation<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    section(deserializer, "reputation")
+}
+
+pub(crate) fn consent<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    section(deserializer, "consent")
+}
+
+pub(crate) fn consent_log<'de, D: Deserializer<'de>, T: DeserializeOwned>(deserializer: D) -> Result<Vec<T>, D::Error> {
+    section(deserializer, "consent_log")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Consent, ConsentPurpose, Format};
+
+    #[test]
+    fn it_seals_sections_in_place() {
+        let (mut identity, private_key) = Identity::new("Private User", "Public bio.").unwrap();
+        let consent = Consent {
+            granted_to: "clinic".to_string(),
+            fields: vec!["core.name".to_string()],
+            expires_at: "2030-01-01T00:00:00Z".parse().unwrap(),
+            purpose: ConsentPurpose::Custom("appointments".to_string()),
+            granted_at: None,
+            revoked_at: None,
+            unknown_fields: Default::default(),
+        };
+        identity.consent.push(consent.clone());
+        identity.seal_section("consent").unwrap();
+
+        // On disk the section is ciphertext, while the rest stays readable.
+        let yaml = identity.to_string_with_format(Format::Yaml).unwrap();
+        assert!(yaml.contains("consent:\n  encrypted: true") && !yaml.contains("clinic") && yaml.contains("Public bio."));
+        identity.validate_schema().unwrap();
+
+        // Without the key it stays sealed, and saving it again keeps the ciphertext.
+        let mut loaded = Identity::parse(yaml.as_bytes()).unwrap();
+        assert!(loaded.is_sealed("consent") && loaded.consent.is_empty());
+        assert_eq!(loaded.to_string_with_format(Format::Yaml).unwrap(), yaml);
+        loaded.reseal().unwrap();
+
+        // With the key it opens, and edits are re-encrypted.
+        loaded.decrypt_sections(&private_key).unwrap();
+        assert_eq!(loaded.consent, [consent]);
+        loaded.consent[0].purpose = ConsentPurpose::Custom("billing".to_string());
+        loaded.reseal().unwrap();
+        let mut reloaded = Identity::parse(loaded.to_string_with_format(Format::Json).unwrap().as_bytes()).unwrap();
+        reloaded.unseal_section("consent", &private_key).unwrap();
+        assert_eq!(reloaded.consent[0].purpose.to_string(), "billing");
+        assert!(!reloaded.to_string_with_format(Format::Yaml).unwrap().contains("encrypted"));
+
+        assert!(matches!(identity.seal_section("core"), Err(IdpError::Path { .. })));
+    }
+}
@@ -0,0 +1,70 @@
+// crates/idp-core/src/secret.rs
+
+// Private key material. Secrets are wiped from memory when dropped, so a key does not
+// linger in freed heap pages after signing, rotating or exporting it.
+
+use std::fmt;
+use std::ops::Deref;
+
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// Secret bytes (usually a PKCS#8 private key) that are zeroed when dropped.
+/// Derefs to `&[u8]`, so it can be passed wherever key bytes are read.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct SecretBytes(Vec<u8>);
+
+impl SecretBytes {
+    /// Takes ownership of the bytes; the caller's copy is moved, not duplicated.
+    pub fn new(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl From<&[u8]> for SecretBytes {
+    fn from(bytes: &[u8]) -> Self {
+        SecretBytes(bytes.to_vec())
+    }
+}
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl AsRef<[u8]> for SecretBytes {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+// Never print the secret itself, not even in debug output.
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretBytes([REDACTED; {}])", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_wipes_and_hides_secrets() {
+        let mut secret = SecretBytes::from(vec![0x42; 48]);
+        assert_eq!(format!("{:?}", secret), "SecretBytes([REDACTED; 48])");
+        assert_eq!(&secret[..2], &[0x42, 0x42]);
+
+        // Dropping runs the same wipe.
+        secret.zeroize();
+        assert!(secret.is_empty());
+    }
+}
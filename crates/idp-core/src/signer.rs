@@ -0,0 +1,884 @@
+// crates/idp-core/src/signer.rs
+
+// Signing backends: anything that can produce signatures for a key of the identity.
+// Software keys (PKCS#8 bytes, as stored in `my.key`) are the default backend;
+// hardware tokens implement the same trait so the private key never leaves the device.
+
+use crate::{crypto, IdpError, SecretBytes, SignatureComponent};
+
+/// Something that holds a private key and can sign with it.
+pub trait SigningBackend {
+    /// The Base64 public key value (as stored in `PublicKey.value`) matching the private key.
+    fn public_key_value(&self) -> Result<String, IdpError>;
+
+    /// Signs a message with the private key.
+    fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError>;
+
+    /// The signature algorithm of the key, as recorded in `PublicKey.algorithm`.
+    fn algorithm(&self) -> &'static str {
+        "Ed25519"
+    }
+
+    /// The raw private key, for operations that need the key itself (backups, subkeys).
+    /// Keys that never leave their device have none.
+    fn software_key(&self) -> Option<&[u8]> {
+        None
+    }
+}
+
+/// A software Ed25519 key in PKCS#8 form, as written to `my.key`.
+impl SigningBackend for SecretBytes {
+    fn public_key_value(&self) -> Result<String, IdpError> {
+        crypto::public_key_value(self)
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+        crypto::sign(self, message)
+    }
+
+    fn algorithm(&self) -> &'static str {
+        crypto::private_key_algorithm(self).unwrap_or(crypto::ED25519)
+    }
+
+    fn software_key(&self) -> Option<&[u8]> {
+        Some(self)
+    }
+}
+
+/// Ed25519 signing on a YubiKey (or any OpenPGP card) through GnuPG's smart card daemon.
+///
+/// The key must already be on the token's signature slot (e.g. generated with
+/// `gpg --card-edit` or `ykman openpgp`). We talk to `scdaemon` with the Assuan protocol via
+/// `gpg-connect-agent`, so PIN entry goes through the user's usual pinentry and the
+/// private key never leaves the device.
+#[cfg(feature = "yubikey")]
+pub mod yubikey {
+    use data_encoding::{BASE64, HEXUPPER};
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    use super::SigningBackend;
+    use crate::{IdpError, SignatureComponent};
+
+    /// The OpenPGP card key slot used for signing.
+    const SIGNING_KEY_REF: &str = "OPENPGP.1";
+
+    /// The longest line Assuan accepts, including its line feed.
+    const ASSUAN_LINE_MAX: usize = 1000;
+
+    /// How much of a message goes on one `SETDATA` line: two hex digits a byte, after the command.
+    const SETDATA_CHUNK: usize = (ASSUAN_LINE_MAX - "SCD SETDATA --append \n".len()) / 2;
+
+    /// A signer backed by the signature key of a connected hardware token.
+    pub struct YubiKeySigner {
+        public_key: [u8; 32],
+    }
+
+    impl YubiKeySigner {
+        /// Connects to the token and reads its Ed25519 signing key.
+        pub fn connect() -> Result<Self, IdpError> {
+            let sexp = assuan(&[format!("SCD READKEY {}", SIGNING_KEY_REF)])?;
+            Ok(YubiKeySigner {
+                public_key: parse_ed25519_public_key(&sexp)?,
+            })
+        }
+    }
+
+    impl SigningBackend for YubiKeySigner {
+        fn public_key_value(&self) -> Result<String, IdpError> {
+            Ok(BASE64.encode(&self.public_key))
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+            // EdDSA signs the message itself, so it is handed to the card unhashed.
+            let signature = assuan(&sign_commands(message))?;
+            if signature.len() != 64 {
+                return Err(IdpError::Keystore(format!(
+                    "token returned a {}-byte signature; is the signing key Ed25519?",
+                    signature.len()
+                )));
+            }
+            Ok(SignatureComponent {
+                algorithm: "Ed25519".to_string(),
+                value: BASE64.encode(&signature),
+            })
+        }
+    }
+
+    /// The Assuan commands that sign `message`: it goes to the card in `SETDATA` lines short
+    /// enough for Assuan, each after the first appended to the last, then `PKSIGN` signs it.
+    fn sign_commands(message: &[u8]) -> Vec<String> {
+        let mut commands: Vec<String> = message
+            .chunks(SETDATA_CHUNK)
+            .enumerate()
+            .map(|(i, chunk)| format!("SCD SETDATA {}{}", if i == 0 { "" } else { "--append " }, HEXUPPER.encode(chunk)))
+            .collect();
+        if commands.is_empty() {
+            commands.push("SCD SETDATA".to_string());
+        }
+        commands.push(format!("SCD PKSIGN --hash=none {}", SIGNING_KEY_REF));
+        commands
+    }
+
+    /// Runs Assuan commands in one `gpg-connect-agent` session and returns the decoded data lines.
+    fn assuan(commands: &[String]) -> Result<Vec<u8>, IdpError> {
+        let mut child = Command::new("gpg-connect-agent")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .map_err(|e| IdpError::Keystore(format!("cannot run gpg-connect-agent (is GnuPG installed?): {}", e)))?;
+
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        for command in commands {
+            writeln!(stdin, "{}", command)?;
+        }
+        writeln!(stdin, "/bye")?;
+        drop(stdin);
+
+        let output = child.wait_with_output()?;
+        let mut data = Vec::new();
+        for line in String::from_utf8_lossy(&output.stdout).lines() {
+            if let Some(error) = line.strip_prefix("ERR ") {
+                return Err(IdpError::Keystore(format!("hardware token error: {}", error)));
+            }
+            if let Some(chunk) = line.strip_prefix("D ") {
+                data.extend(percent_decode(chunk));
+            }
+        }
+        Ok(data)
+    }
+
+    /// Undoes Assuan's percent-escaping of `%`, CR and LF in data lines.
+    fn percent_decode(chunk: &str) -> Vec<u8> {
+        let bytes = chunk.as_bytes();
+        let mut decoded = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let escaped = bytes.get(i + 1..i + 3).and_then(|hex| HEXUPPER.decode(&hex.to_ascii_uppercase()).ok());
+            match (bytes[i], escaped) {
+                (b'%', Some(byte)) => {
+                    decoded.extend(byte);
+                    i += 3;
+                }
+                (byte, _) => {
+                    decoded.push(byte);
+                    i += 1;
+                }
+            }
+        }
+        decoded
+    }
+
+    /// Extracts the 32-byte Ed25519 point from a GnuPG public key S-expression.
+    /// GnuPG stores it either raw or with a 0x40 "native point" prefix.
+    fn parse_ed25519_public_key(sexp: &[u8]) -> Result<[u8; 32], IdpError> {
+        let find = |pattern: &[u8]| sexp.windows(pattern.len()).position(|w| w == pattern).map(|p| p + pattern.len());
+        let start = find(b"1:q33:\x40")
+            .or_else(|| find(b"1:q32:"))
+            .ok_or_else(|| IdpError::Keystore("the token's signing key is not an Ed25519 key".to_string()))?;
+        sexp.get(start..start + 32)
+            .and_then(|point| point.try_into().ok())
+            .ok_or_else(|| IdpError::Keystore("truncated public key from token".to_string()))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn it_parses_gnupg_responses() {
+            assert_eq!(percent_decode("a%25b%0Ac"), b"a%b\nc".to_vec());
+
+            let mut sexp = b"(10:public-key(3:ecc(5:curve7:Ed25519)(1:q33:\x40".to_vec();
+            sexp.extend([7u8; 32]);
+            sexp.extend(b")))");
+            assert_eq!(parse_ed25519_public_key(&sexp).unwrap(), [7u8; 32]);
+            assert!(parse_ed25519_public_key(b"(10:public-key(3:rsa))").is_err());
+        }
+
+        #[test]
+        fn it_fits_large_messages_on_assuan_lines() {
+            // A freshly initialised document is over a kilobyte of canonical JSON.
+            let message = vec![b'x'; 2000];
+            let commands = sign_commands(&message);
+            assert!(commands.iter().all(|command| command.len() < ASSUAN_LINE_MAX), "every line fits with its line feed");
+            assert_eq!(commands.len(), 6);
+            assert!(commands[1..5].iter().all(|command| command.starts_with("SCD SETDATA --append ")));
+            let hex: String = commands[..5].iter().map(|command| command.rsplit(' ').next().unwrap()).collect();
+            assert_eq!(HEXUPPER.decode(hex.as_bytes()).unwrap(), message);
+            assert_eq!(sign_commands(b"short"), ["SCD SETDATA 73686F7274", "SCD PKSIGN --hash=none OPENPGP.1"]);
+        }
+    }
+}
+
+/// Ed25519 signing through a running ssh-agent, so an SSH key (possibly on a security key
+/// or in a password manager's agent) can act as the identity key without `my.key`.
+#[cfg(all(feature = "ssh-agent", unix))]
+pub mod ssh_agent {
+    use data_encoding::BASE64;
+    use std::io::{Read, Write};
+    use std::os::unix::net::UnixStream;
+    use std::path::{Path, PathBuf};
+
+    use super::SigningBackend;
+    use crate::ssh::{self, WireReader};
+    use crate::{IdpError, SignatureComponent};
+
+    // Message numbers from the ssh-agent protocol (draft-miller-ssh-agent).
+    const SSH_AGENT_FAILURE: u8 = 5;
+    const SSH_AGENTC_REQUEST_IDENTITIES: u8 = 11;
+    const SSH_AGENT_IDENTITIES_ANSWER: u8 = 12;
+    const SSH_AGENTC_SIGN_REQUEST: u8 = 13;
+    const SSH_AGENT_SIGN_RESPONSE: u8 = 14;
+
+    /// A signer backed by an Ed25519 key loaded in ssh-agent.
+    pub struct SshAgentSigner {
+        socket: std::path::PathBuf,
+        public_key: Vec<u8>,
+    }
+
+    impl SshAgentSigner {
+        /// The Base64 public key values of the Ed25519 keys in the agent at `SSH_AUTH_SOCK`.
+        pub fn list() -> Result<Vec<String>, IdpError> {
+            Self::list_at(&default_socket()?)
+        }
+
+        /// Like `list`, for an agent listening on an explicit socket.
+        pub fn list_at(socket: &Path) -> Result<Vec<String>, IdpError> {
+            let answer = request(socket, &[SSH_AGENTC_REQUEST_IDENTITIES], SSH_AGENT_IDENTITIES_ANSWER)?;
+            let mut reader = WireReader(&answer);
+            let mut keys = Vec::new();
+            for _ in 0..reader.u32()? {
+                let blob = reader.string()?;
+                let _comment = reader.string()?;
+
+                let mut key = WireReader(blob);
+                if key.string()? == ssh::SSH_ED25519.as_bytes() {
+                    keys.push(BASE64.encode(key.string()?));
+                }
+            }
+            Ok(keys)
+        }
+
+        /// Connects to the agent at `SSH_AUTH_SOCK` to sign with the key with this public key value.
+        pub fn connect(public_key_value: &str) -> Result<Self, IdpError> {
+            Self::connect_at(&default_socket()?, public_key_value)
+        }
+
+        /// Like `connect`, for an agent listening on an explicit socket.
+        pub fn connect_at(socket: &Path, public_key_value: &str) -> Result<Self, IdpError> {
+            if !Self::list_at(socket)?.iter().any(|value| value == public_key_value) {
+                return Err(IdpError::Keystore("ssh-agent does not hold this key (add it with `ssh-add`)".to_string()));
+            }
+            let public_key = BASE64
+                .decode(public_key_value.as_bytes())
+                .map_err(|_| IdpError::Keystore("malformed public key value".to_string()))?;
+            Ok(SshAgentSigner {
+                socket: socket.to_path_buf(),
+                public_key,
+            })
+        }
+    }
+
+    fn default_socket() -> Result<PathBuf, IdpError> {
+        std::env::var_os("SSH_AUTH_SOCK")
+            .map(PathBuf::from)
+            .ok_or_else(|| IdpError::Keystore("no ssh-agent is running (SSH_AUTH_SOCK is not set)".to_string()))
+    }
+
+    impl SigningBackend for SshAgentSigner {
+        fn public_key_value(&self) -> Result<String, IdpError> {
+            Ok(BASE64.encode(&self.public_key))
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+            let mut body = vec![SSH_AGENTC_SIGN_REQUEST];
+            ssh::put_string(&mut body, &ssh::ed25519_blob(&self.public_key));
+            ssh::put_string(&mut body, message);
+            body.extend(0u32.to_be_bytes()); // no flags
+            let response = request(&self.socket, &body, SSH_AGENT_SIGN_RESPONSE)?;
+
+            // The response wraps the raw signature as `string type, string signature`.
+            let mut reader = WireReader(&response);
+            let mut signature = WireReader(reader.string()?);
+            let signature_type = signature.string()?;
+            let signature = signature.string()?;
+            if signature_type != ssh::SSH_ED25519.as_bytes() || signature.len() != 64 {
+                return Err(IdpError::Keystore("ssh-agent returned an unexpected signature".to_string()));
+            }
+            Ok(SignatureComponent {
+                algorithm: "Ed25519".to_string(),
+                value: BASE64.encode(signature),
+            })
+        }
+    }
+
+    /// Sends one framed request to the agent and returns the body of its reply.
+    fn request(socket: &Path, body: &[u8], expected: u8) -> Result<Vec<u8>, IdpError> {
+        let mut stream = UnixStream::connect(socket)
+            .map_err(|e| IdpError::Keystore(format!("cannot reach ssh-agent at {}: {}", socket.display(), e)))?;
+        let mut frame = Vec::with_capacity(body.len() + 4);
+        ssh::put_string(&mut frame, body);
+        stream.write_all(&frame)?;
+
+        let mut len = [0u8; 4];
+        stream.read_exact(&mut len)?;
+        let mut reply = vec![0u8; u32::from_be_bytes(len) as usize];
+        stream.read_exact(&mut reply)?;
+
+        match reply.split_first() {
+            Some((&kind, body)) if kind == expected => Ok(body.to_vec()),
+            Some((&SSH_AGENT_FAILURE, _)) => Err(IdpError::Keystore("ssh-agent refused the request".to_string())),
+            _ => Err(IdpError::Keystore("unexpected reply from ssh-agent".to_string())),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::crypto;
+        use std::os::unix::net::UnixListener;
+
+        /// Serves `requests` agent requests from a software key, like `ssh-agent` would.
+        fn fake_agent(socket: &Path, private_key: crate::SecretBytes, requests: usize) {
+            let listener = UnixListener::bind(socket).unwrap();
+            std::thread::spawn(move || {
+                let public_key = BASE64.decode(crypto::public_key_value(&private_key).unwrap().as_bytes()).unwrap();
+                for stream in listener.incoming().take(requests) {
+                    let mut stream = stream.unwrap();
+                    let mut len = [0u8; 4];
+                    stream.read_exact(&mut len).unwrap();
+                    let mut request = vec![0u8; u32::from_be_bytes(len) as usize];
+                    stream.read_exact(&mut request).unwrap();
+
+                    let mut reply = Vec::new();
+                    let (&kind, body) = request.split_first().unwrap();
+                    let mut reader = WireReader(body);
+                    match kind {
+                        SSH_AGENTC_REQUEST_IDENTITIES => {
+                            reply.push(SSH_AGENT_IDENTITIES_ANSWER);
+                            reply.extend(1u32.to_be_bytes());
+                            ssh::put_string(&mut reply, &ssh::ed25519_blob(&public_key));
+                            ssh::put_string(&mut reply, b"test key");
+                        }
+                        _ => {
+                            let _blob = reader.string().unwrap();
+                            let message = reader.string().unwrap();
+                            let signature = crypto::sign(&private_key, message).unwrap();
+                            let mut inner = Vec::new();
+                            ssh::put_string(&mut inner, ssh::SSH_ED25519.as_bytes());
+                            ssh::put_string(&mut inner, &BASE64.decode(signature.value.as_bytes()).unwrap());
+                            reply.push(SSH_AGENT_SIGN_RESPONSE);
+                            ssh::put_string(&mut reply, &inner);
+                        }
+                    }
+                    let mut frame = Vec::new();
+                    ssh::put_string(&mut frame, &reply);
+                    stream.write_all(&frame).unwrap();
+                }
+            });
+        }
+
+        #[test]
+        fn it_signs_through_the_agent() {
+            let dir = tempfile::tempdir().unwrap();
+            let socket = dir.path().join("agent.sock");
+            let key_pair = crypto::generate_ed25519_keypair().unwrap();
+            fake_agent(&socket, key_pair.private_key_bytes, 2);
+
+            let signer = SshAgentSigner::connect_at(&socket, &key_pair.public_key.value).unwrap();
+            let signature = signer.sign(b"via the agent").unwrap();
+            crypto::verify(&key_pair.public_key, b"via the agent", &signature).unwrap();
+        }
+
+        #[test]
+        fn it_reports_a_missing_key() {
+            let dir = tempfile::tempdir().unwrap();
+            let socket = dir.path().join("agent.sock");
+            let key_pair = crypto::generate_ed25519_keypair().unwrap();
+            fake_agent(&socket, key_pair.private_key_bytes, 2);
+
+            assert_eq!(SshAgentSigner::list_at(&socket).unwrap(), vec![key_pair.public_key.value]);
+            let other_key = crypto::generate_ed25519_keypair().unwrap().public_key.value;
+            assert!(matches!(SshAgentSigner::connect_at(&socket, &other_key), Err(IdpError::Keystore(_))));
+        }
+    }
+}
+
+/// Ed25519 signing with a key held in an HSM (or any PKCS#11 token).
+///
+/// Only the handful of PKCS#11 calls needed to log in and out, find the key and sign are bound,
+/// loaded at runtime from the vendor's module so no HSM SDK is needed at build time.
+#[cfg(feature = "pkcs11")]
+pub mod pkcs11 {
+    use data_encoding::BASE64;
+    use std::os::raw::{c_uchar, c_ulong, c_void};
+    use std::path::PathBuf;
+    use std::ptr;
+
+    use super::SigningBackend;
+    use crate::{IdpError, SecretBytes, SignatureComponent};
+
+    /// How to reach the signing key in the HSM.
+    #[derive(Debug, Clone)]
+    pub struct Pkcs11Config {
+        /// Path to the vendor's PKCS#11 module (e.g. `/usr/lib/softhsm/libsofthsm2.so`).
+        pub module_path: PathBuf,
+        /// The slot holding the token.
+        pub slot: u64,
+        /// The user PIN for the token, wiped from memory when the configuration is dropped.
+        pub pin: SecretBytes,
+        /// The `CKA_LABEL` of the key pair; the first Ed25519 key is used if unset.
+        pub key_label: Option<String>,
+    }
+
+    /// A signer backed by an Ed25519 key pair inside a PKCS#11 token.
+    pub struct Pkcs11Signer {
+        functions: *const FunctionList,
+        session: c_ulong,
+        private_key: c_ulong,
+        public_key: [u8; 32],
+        // Whether this signer logged in, and so must log out again when dropped.
+        logged_in: bool,
+        // Keeps the module loaded for as long as its function pointers are in use.
+        _library: libloading::Library,
+    }
+
+    impl Pkcs11Signer {
+        /// Loads the module, logs in to the token and locates the key pair.
+        pub fn open(config: &Pkcs11Config) -> Result<Self, IdpError> {
+            // SAFETY: loading a PKCS#11 module runs its initializers; the user chose to trust it.
+            let library = unsafe { libloading::Library::new(&config.module_path) }
+                .map_err(|e| IdpError::Keystore(format!("cannot load PKCS#11 module: {}", e)))?;
+
+            // SAFETY: C_GetFunctionList has this signature in every PKCS#11 version.
+            let functions = unsafe {
+                let get_function_list: libloading::Symbol<unsafe extern "C" fn(*mut *const FunctionList) -> c_ulong> =
+                    library
+                        .get(b"C_GetFunctionList\0")
+                        .map_err(|e| IdpError::Keystore(format!("not a PKCS#11 module: {}", e)))?;
+                let mut functions = ptr::null();
+                check(get_function_list(&mut functions), "C_GetFunctionList")?;
+                functions
+            };
+            // SAFETY: the module returned a valid function list, which lives as long as the library.
+            let f = unsafe { &*functions };
+
+            unsafe {
+                match (f.initialize)(ptr::null_mut()) {
+                    CKR_OK | CKR_CRYPTOKI_ALREADY_INITIALIZED => {}
+                    rv => check(rv, "C_Initialize")?,
+                }
+
+                let mut session = 0;
+                check(
+                    (f.open_session)(config.slot as c_ulong, CKF_SERIAL_SESSION, ptr::null_mut(), ptr::null_mut(), &mut session),
+                    "C_OpenSession",
+                )?;
+                let mut signer = Pkcs11Signer {
+                    functions,
+                    session,
+                    private_key: 0,
+                    public_key: [0; 32],
+                    logged_in: false,
+                    _library: library,
+                };
+
+                // A login is shared by all sessions of the application, so one made elsewhere is
+                // left for its owner to end.
+                match (f.login)(session, CKU_USER, config.pin.as_ptr(), config.pin.len() as c_ulong) {
+                    CKR_OK => signer.logged_in = true,
+                    CKR_USER_ALREADY_LOGGED_IN => {}
+                    rv => check(rv, "C_Login")?,
+                }
+
+                signer.private_key = signer.find_key(CKO_PRIVATE_KEY, config.key_label.as_deref())?;
+                let public_key = signer.find_key(CKO_PUBLIC_KEY, config.key_label.as_deref())?;
+                signer.public_key = parse_ec_point(&signer.read_attribute(public_key, CKA_EC_POINT)?)?;
+                Ok(signer)
+            }
+        }
+
+        fn functions(&self) -> &FunctionList {
+            // SAFETY: set from the module in `open` and valid while `_library` is loaded.
+            unsafe { &*self.functions }
+        }
+
+        /// Finds the first Ed25519 key object of the given class (and label, if given).
+        unsafe fn find_key(&self, class: c_ulong, label: Option<&str>) -> Result<c_ulong, IdpError> {
+            let f = self.functions();
+            let mut class = class;
+            let mut key_type = CKK_EC_EDWARDS;
+            let mut template = vec![attribute(CKA_CLASS, &mut class), attribute(CKA_KEY_TYPE, &mut key_type)];
+            if let Some(label) = label {
+                template.push(Attribute {
+                    kind: CKA_LABEL,
+                    value: label.as_ptr() as *mut c_void,
+                    value_len: label.len() as c_ulong,
+                });
+            }
+
+            unsafe {
+                check((f.find_objects_init)(self.session, template.as_mut_ptr(), template.len() as c_ulong), "C_FindObjectsInit")?;
+                let mut handle = 0;
+                let mut found = 0;
+                let rv = (f.find_objects)(self.session, &mut handle, 1, &mut found);
+                (f.find_objects_final)(self.session);
+                check(rv, "C_FindObjects")?;
+                if found == 0 {
+                    return Err(IdpError::Keystore("no matching Ed25519 key found on the PKCS#11 token".to_string()));
+                }
+                Ok(handle)
+            }
+        }
+
+        /// Reads a variable-length attribute value (first the length, then the bytes).
+        unsafe fn read_attribute(&self, object: c_ulong, kind: c_ulong) -> Result<Vec<u8>, IdpError> {
+            let f = self.functions();
+            let mut template = [Attribute { kind, value: ptr::null_mut(), value_len: 0 }];
+            unsafe {
+                check((f.get_attribute_value)(self.session, object, template.as_mut_ptr(), 1), "C_GetAttributeValue")?;
+                let mut value = vec![0u8; template[0].value_len as usize];
+                template[0].value = value.as_mut_ptr() as *mut c_void;
+                check((f.get_attribute_value)(self.session, object, template.as_mut_ptr(), 1), "C_GetAttributeValue")?;
+                value.truncate(template[0].value_len as usize);
+                Ok(value)
+            }
+        }
+    }
+
+    impl SigningBackend for Pkcs11Signer {
+        fn public_key_value(&self) -> Result<String, IdpError> {
+            Ok(BASE64.encode(&self.public_key))
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+            let f = self.functions();
+            let mut mechanism = Mechanism {
+                mechanism: CKM_EDDSA,
+                parameter: ptr::null_mut(),
+                parameter_len: 0,
+            };
+            let mut signature = [0u8; 64];
+            let mut signature_len = signature.len() as c_ulong;
+
+            // SAFETY: all buffers outlive the calls and their lengths are passed alongside.
+            unsafe {
+                check((f.sign_init)(self.session, &mut mechanism, self.private_key), "C_SignInit")?;
+                check(
+                    (f.sign)(self.session, message.as_ptr(), message.len() as c_ulong, signature.as_mut_ptr(), &mut signature_len),
+                    "C_Sign",
+                )?;
+            }
+            Ok(SignatureComponent {
+                algorithm: "Ed25519".to_string(),
+                value: BASE64.encode(&signature[..signature_len as usize]),
+            })
+        }
+    }
+
+    impl Drop for Pkcs11Signer {
+        fn drop(&mut self) {
+            // SAFETY: the session was opened by us and is logged out of and closed exactly once.
+            unsafe {
+                if self.logged_in {
+                    (self.functions().logout)(self.session);
+                }
+                (self.functions().close_session)(self.session);
+            }
+        }
+    }
+
+    /// Tokens return the Ed25519 point either raw or wrapped in a DER OCTET STRING.
+    fn parse_ec_point(value: &[u8]) -> Result<[u8; 32], IdpError> {
+        let point = match value {
+            [0x04, 0x20, point @ ..] if point.len() == 32 => point,
+            point => point,
+        };
+        point
+            .try_into()
+            .map_err(|_| IdpError::Keystore("the PKCS#11 key is not an Ed25519 key".to_string()))
+    }
+
+    fn check(rv: c_ulong, function: &str) -> Result<(), IdpError> {
+        match rv {
+            CKR_OK => Ok(()),
+            rv => Err(IdpError::Keystore(format!("{} failed with PKCS#11 error 0x{:X}", function, rv))),
+        }
+    }
+
+    fn attribute(kind: c_ulong, value: &mut c_ulong) -> Attribute {
+        Attribute {
+            kind,
+            value: value as *mut c_ulong as *mut c_void,
+            value_len: std::mem::size_of::<c_ulong>() as c_ulong,
+        }
+    }
+
+    // The subset of the PKCS#11 v2.40 ABI we use.
+    const CKR_OK: c_ulong = 0x0;
+    const CKR_USER_ALREADY_LOGGED_IN: c_ulong = 0x100;
+    const CKR_CRYPTOKI_ALREADY_INITIALIZED: c_ulong = 0x191;
+    const CKF_SERIAL_SESSION: c_ulong = 0x4;
+    const CKU_USER: c_ulong = 1;
+    const CKA_CLASS: c_ulong = 0x0;
+    const CKA_LABEL: c_ulong = 0x3;
+    const CKA_KEY_TYPE: c_ulong = 0x100;
+    const CKA_EC_POINT: c_ulong = 0x181;
+    const CKO_PUBLIC_KEY: c_ulong = 2;
+    const CKO_PRIVATE_KEY: c_ulong = 3;
+    const CKK_EC_EDWARDS: c_ulong = 0x40;
+    const CKM_EDDSA: c_ulong = 0x1057;
+
+    #[cfg_attr(windows, repr(C, packed))]
+    #[cfg_attr(not(windows), repr(C))]
+    struct Attribute {
+        kind: c_ulong,
+        value: *mut c_void,
+        value_len: c_ulong,
+    }
+
+    #[cfg_attr(windows, repr(C, packed))]
+    #[cfg_attr(not(windows), repr(C))]
+    struct Mechanism {
+        mechanism: c_ulong,
+        parameter: *mut c_void,
+        parameter_len: c_ulong,
+    }
+
+    type Rv = c_ulong;
+    type Unused = Option<unsafe extern "C" fn()>;
+
+    // CK_FUNCTION_LIST, up to C_Sign; we only ever read it through a pointer from the module.
+    #[cfg_attr(windows, repr(C, packed))]
+    #[cfg_attr(not(windows), repr(C))]
+    struct FunctionList {
+        version: [c_uchar; 2],
+        initialize: unsafe extern "C" fn(*mut c_void) -> Rv,
+        finalize: Unused,
+        get_info: Unused,
+        get_function_list: Unused,
+        get_slot_list: Unused,
+        get_slot_info: Unused,
+        get_token_info: Unused,
+        get_mechanism_list: Unused,
+        get_mechanism_info: Unused,
+        init_token: Unused,
+        init_pin: Unused,
+        set_pin: Unused,
+        open_session: unsafe extern "C" fn(c_ulong, c_ulong, *mut c_void, *mut c_void, *mut c_ulong) -> Rv,
+        close_session: unsafe extern "C" fn(c_ulong) -> Rv,
+        close_all_sessions: Unused,
+        get_session_info: Unused,
+        get_operation_state: Unused,
+        set_operation_state: Unused,
+        login: unsafe extern "C" fn(c_ulong, c_ulong, *const c_uchar, c_ulong) -> Rv,
+        logout: unsafe extern "C" fn(c_ulong) -> Rv,
+        create_object: Unused,
+        copy_object: Unused,
+        destroy_object: Unused,
+        get_object_size: Unused,
+        get_attribute_value: unsafe extern "C" fn(c_ulong, c_ulong, *mut Attribute, c_ulong) -> Rv,
+        set_attribute_value: Unused,
+        find_objects_init: unsafe extern "C" fn(c_ulong, *mut Attribute, c_ulong) -> Rv,
+        find_objects: unsafe extern "C" fn(c_ulong, *mut c_ulong, c_ulong, *mut c_ulong) -> Rv,
+        find_objects_final: unsafe extern "C" fn(c_ulong) -> Rv,
+        encrypt_init: Unused,
+        encrypt: Unused,
+        encrypt_update: Unused,
+        encrypt_final: Unused,
+        decrypt_init: Unused,
+        decrypt: Unused,
+        decrypt_update: Unused,
+        decrypt_final: Unused,
+        digest_init: Unused,
+        digest: Unused,
+        digest_update: Unused,
+        digest_key: Unused,
+        digest_final: Unused,
+        sign_init: unsafe extern "C" fn(c_ulong, *mut Mechanism, c_ulong) -> Rv,
+        sign: unsafe extern "C" fn(c_ulong, *const c_uchar, c_ulong, *mut c_uchar, *mut c_ulong) -> Rv,
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn it_accepts_raw_and_der_wrapped_points() {
+            let mut wrapped = vec![0x04, 0x20];
+            wrapped.extend([9u8; 32]);
+            assert_eq!(parse_ec_point(&wrapped).unwrap(), [9u8; 32]);
+            assert_eq!(parse_ec_point(&[9u8; 32]).unwrap(), [9u8; 32]);
+            assert!(parse_ec_point(&[1, 2, 3]).is_err());
+        }
+    }
+}
+
+/// ECDSA P-256 signing inside a TPM 2.0, so the private key is bound to this machine's
+/// hardware. TPMs cannot do Ed25519, so these keys are recorded with algorithm `P-256`.
+///
+/// The key is a primary key under the owner hierarchy, made persistent at `TPM_KEY_HANDLE`.
+/// We drive the TPM with the `tpm2-tools` commands, which handle device access and sessions.
+#[cfg(feature = "tpm")]
+pub mod tpm {
+    use data_encoding::BASE64;
+    use std::process::Command;
+
+    use super::SigningBackend;
+    use crate::{IdpError, SignatureComponent};
+
+    /// The persistent handle the identity key is kept at.
+    pub const TPM_KEY_HANDLE: &str = "0x81010049";
+
+    /// DER SubjectPublicKeyInfo header of a P-256 key, up to the uncompressed point.
+    const P256_SPKI_PREFIX: &[u8] = &[
+        0x30, 0x59, 0x30, 0x13, 0x06, 0x07, 0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01, 0x06, 0x08, 0x2a, 0x86, 0x48,
+        0xce, 0x3d, 0x03, 0x01, 0x07, 0x03, 0x42, 0x00,
+    ];
+
+    /// A signer backed by the P-256 key at `TPM_KEY_HANDLE`.
+    pub struct TpmSigner {
+        /// The uncompressed SEC1 point (65 bytes).
+        public_key: Vec<u8>,
+    }
+
+    impl TpmSigner {
+        /// Connects to the key already persisted in the TPM.
+        pub fn connect() -> Result<Self, IdpError> {
+            let dir = tempfile::tempdir()?;
+            let der = dir.path().join("key.der");
+            tpm2(Command::new("tpm2_readpublic").args(["-c", TPM_KEY_HANDLE, "-f", "der", "-o"]).arg(&der))?;
+            Ok(TpmSigner {
+                public_key: parse_p256_spki(&std::fs::read(&der)?)?,
+            })
+        }
+
+        /// Creates the key inside the TPM and persists it. Fails if the handle is taken.
+        pub fn generate() -> Result<Self, IdpError> {
+            let dir = tempfile::tempdir()?;
+            let context = dir.path().join("primary.ctx");
+            tpm2(Command::new("tpm2_createprimary").args(["-C", "o", "-G", "ecc256:ecdsa-sha256", "-c"]).arg(&context))?;
+            tpm2(Command::new("tpm2_evictcontrol").args(["-C", "o", "-c"]).arg(&context).arg(TPM_KEY_HANDLE))?;
+            Self::connect()
+        }
+    }
+
+    impl SigningBackend for TpmSigner {
+        fn public_key_value(&self) -> Result<String, IdpError> {
+            Ok(BASE64.encode(&self.public_key))
+        }
+
+        fn sign(&self, message: &[u8]) -> Result<SignatureComponent, IdpError> {
+            // The TPM hashes the message itself and returns a DER signature.
+            let dir = tempfile::tempdir()?;
+            let (input, output) = (dir.path().join("message"), dir.path().join("signature"));
+            std::fs::write(&input, message)?;
+            tpm2(
+                Command::new("tpm2_sign")
+                    .args(["-c", TPM_KEY_HANDLE, "-g", "sha256", "-s", "ecdsa", "-f", "plain", "-o"])
+                    .arg(&output)
+                    .arg(&input),
+            )?;
+            Ok(SignatureComponent {
+                algorithm: "P-256".to_string(),
+                value: BASE64.encode(&der_signature_to_fixed(&std::fs::read(&output)?)?),
+            })
+        }
+
+        fn algorithm(&self) -> &'static str {
+            "P-256"
+        }
+    }
+
+    /// Runs a `tpm2-tools` command, turning failures into key store errors.
+    fn tpm2(command: &mut Command) -> Result<(), IdpError> {
+        let name = command.get_program().to_string_lossy().into_owned();
+        let output = command
+            .output()
+            .map_err(|e| IdpError::Keystore(format!("cannot run {} (are tpm2-tools installed?): {}", name, e)))?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(IdpError::Keystore(format!("{} failed: {}", name, stderr.trim())));
+        }
+        Ok(())
+    }
+
+    /// Extracts the uncompressed point from a P-256 SubjectPublicKeyInfo.
+    fn parse_p256_spki(der: &[u8]) -> Result<Vec<u8>, IdpError> {
+        der.strip_prefix(P256_SPKI_PREFIX)
+            .filter(|point| point.len() == 65 && point[0] == 0x04)
+            .map(<[u8]>::to_vec)
+            .ok_or_else(|| IdpError::Keystore("the TPM key is not a P-256 key".to_string()))
+    }
+
+    /// Converts a DER `SEQUENCE { INTEGER r, INTEGER s }` into the fixed 64-byte r || s form.
+    fn der_signature_to_fixed(der: &[u8]) -> Result<[u8; 64], IdpError> {
+        let malformed = || IdpError::Keystore("the TPM returned a malformed ECDSA signature".to_string());
+        let body = match der {
+            [0x30, len, body @ ..] if *len as usize == body.len() => body,
+            _ => return Err(malformed()),
+        };
+
+        let mut fixed = [0u8; 64];
+        let mut rest = body;
+        for half in fixed.chunks_mut(32) {
+            let (integer, tail) = match rest {
+                [0x02, len, tail @ ..] if tail.len() >= *len as usize => tail.split_at(*len as usize),
+                _ => return Err(malformed()),
+            };
+            // DER integers are minimal and signed: drop a leading zero, left-pad short ones.
+            let integer = integer.strip_prefix(&[0]).unwrap_or(integer);
+            if integer.len() > 32 {
+                return Err(malformed());
+            }
+            half[32 - integer.len()..].copy_from_slice(integer);
+            rest = tail;
+        }
+        if !rest.is_empty() {
+            return Err(malformed());
+        }
+        Ok(fixed)
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::{crypto, KeyFormat, KeyPurpose, KeyStatus, PublicKey};
+        use ring::rand::SystemRandom;
+        use ring::signature::{EcdsaKeyPair, KeyPair, ECDSA_P256_SHA256_ASN1_SIGNING};
+
+        #[test]
+        fn it_converts_tpm_signatures_for_verification() {
+            // A software P-256 key stands in for the TPM; it also signs in DER.
+            let rng = SystemRandom::new();
+            let pkcs8 = EcdsaKeyPair::generate_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, &rng).unwrap();
+            let key_pair = EcdsaKeyPair::from_pkcs8(&ECDSA_P256_SHA256_ASN1_SIGNING, pkcs8.as_ref(), &rng).unwrap();
+            let der = key_pair.sign(&rng, b"bound to hardware").unwrap();
+
+            let mut spki = P256_SPKI_PREFIX.to_vec();
+            spki.extend(key_pair.public_key().as_ref());
+            let public_key = PublicKey {
+                key_id: "root-key-01".to_string(),
+                algorithm: "P-256".to_string(),
+                value: BASE64.encode(&parse_p256_spki(&spki).unwrap()),
+                format: KeyFormat::Base64,
+                status: KeyStatus::Active,
+                parent_key_id: None,
+                purpose: KeyPurpose::Signing,
+                expires_at: None,
+                unknown_fields: Default::default(),
+            };
+            let signature = SignatureComponent {
+                algorithm: "P-256".to_string(),
+                value: BASE64.encode(&der_signature_to_fixed(der.as_ref()).unwrap()),
+            };
+            crypto::verify(&public_key, b"bound to hardware", &signature).unwrap();
+            assert!(crypto::verify(&public_key, b"something else", &signature).is_err());
+
+            assert!(der_signature_to_fixed(&[0x30, 0x02, 0x02, 0x00]).is_err());
+            assert!(parse_p256_spki(&[0x30, 0x00]).is_err());
+        }
+    }
+}
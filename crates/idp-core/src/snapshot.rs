@@ -0,0 +1,99 @@
+// crates/idp-core/src/snapshot.rs
+
+// Point-in-time copies of a document, to go back to after a bad edit. A snapshot is a plain
+// document file with its integrity digest, named by when it was taken; restoring one checks
+// that it is intact, signed as it claims and of the same identity before anything is replaced.
+
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+
+use crate::{Identity, IdpError};
+
+/// The directory snapshots of the document at `document_path` are kept in
+/// (`my.idp.snapshots` for `my.idp`).
+pub fn snapshot_dir(document_path: &Path) -> PathBuf {
+    let mut path = document_path.as_os_str().to_owned();
+    path.push(".snapshots");
+    PathBuf::from(path)
+}
+
+/// The snapshots in `dir`, oldest first.
+pub fn list_snapshots(dir: &Path) -> Result<Vec<PathBuf>, IdpError> {
+    let mut snapshots = Vec::new();
+    match std::fs::read_dir(dir) {
+        Ok(entries) => {
+            for entry in entries {
+                let path = entry?.path();
+                if path.extension().is_some_and(|extension| extension == "idp") {
+                    snapshots.push(path);
+                }
+            }
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+    // Names are timestamps, so they sort by age.
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+impl Identity {
+    /// Writes a copy of the document into `dir`, named by the current time, and returns its path.
+    pub fn snapshot_to<P: AsRef<Path>>(&self, dir: P) -> Result<PathBuf, IdpError> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(format!("{}.idp", Utc::now().format("%Y-%m-%d_%H-%M-%S%.3f")));
+        self.save_to_file(&path)?;
+        Ok(path)
+    }
+
+    /// Replaces this document with the snapshot at `path`, once it has been checked: its
+    /// integrity digest must match, its envelope signature (if any) verify, and it must be a
+    /// snapshot of this identity.
+    pub fn restore_from<P: AsRef<Path>>(&mut self, path: P) -> Result<(), IdpError> {
+        // 1. Loading checks the digest, but only if there is one.
+        let snapshot = Identity::load_from_file(path.as_ref())?;
+        if snapshot.integrity.is_none() {
+            return Err(IdpError::Snapshot("it has no integrity digest, so it cannot be checked".to_string()));
+        }
+        if snapshot.signature.is_some() {
+            snapshot.verify_self()?;
+        }
+
+        // 2. Only a past version of this document can replace it.
+        if snapshot.identity.id != self.identity.id {
+            return Err(IdpError::Snapshot(format!("it is a snapshot of '{}', not of this identity", snapshot.identity.id)));
+        }
+        *self = snapshot;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_restores_checked_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = dir.path().join("my.idp.snapshots");
+        let (mut identity, private_key) = Identity::new("Careful User", "Before the mistake.").unwrap();
+        identity.sign_document(&private_key).unwrap();
+        let snapshot = identity.snapshot_to(&snapshots).unwrap();
+        assert_eq!(list_snapshots(&snapshots).unwrap(), std::slice::from_ref(&snapshot));
+
+        identity.core.bio = "The mistake.".to_string();
+        identity.restore_from(&snapshot).unwrap();
+        assert_eq!(identity.core.bio, "Before the mistake.");
+
+        // Snapshots of someone else, or without a digest, are refused.
+        let (stranger, _) = Identity::new("Stranger", "Not you.").unwrap();
+        let theirs = stranger.snapshot_to(&snapshots).unwrap();
+        assert!(matches!(identity.restore_from(&theirs), Err(IdpError::Snapshot(_))));
+        let undigested = dir.path().join("undigested.idp");
+        let mut copy = identity.clone();
+        copy.integrity = None;
+        std::fs::write(&undigested, copy.to_string_with_format(crate::Format::Yaml).unwrap()).unwrap();
+        assert!(matches!(identity.restore_from(&undigested), Err(IdpError::Snapshot(_))));
+    }
+}
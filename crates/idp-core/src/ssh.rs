@@ -0,0 +1,168 @@
+// crates/idp-core/src/ssh.rs
+
+// OpenSSH form of IDP keys, so the identity key can be used for SSH logins and git signing.
+// Also holds the SSH wire encoding shared with the ssh-agent signer, and SSHSIG signatures
+// (`ssh-keygen -Y sign`), the form git uses for SSH-signed commits.
+
+use data_encoding::BASE64;
+use ring::digest;
+
+use crate::signer::SigningBackend;
+use crate::{IdpError, KeyFormat, KeyPurpose, KeyStatus, PublicKey};
+
+/// The OpenSSH key type name for Ed25519 keys.
+pub const SSH_ED25519: &str = "ssh-ed25519";
+
+const SSHSIG_MAGIC: &[u8] = b"SSHSIG";
+
+impl PublicKey {
+    /// Formats the key as an OpenSSH public key line (`ssh-ed25519 AAAA... comment`),
+    /// ready for `authorized_keys`.
+    pub fn to_openssh(&self, comment: &str) -> Result<String, IdpError> {
+        if self.algorithm != "Ed25519" {
+            return Err(IdpError::Key(format!("'{}' keys cannot be used with SSH", self.algorithm)));
+        }
+        let raw = self.raw_value()?;
+
+        let line = format!("{} {}", SSH_ED25519, BASE64.encode(&ed25519_blob(&raw)));
+        Ok(match comment {
+            "" => line,
+            comment => format!("{} {}", line, comment),
+        })
+    }
+
+    /// Reads an `ssh-ed25519` public key line; the key id is taken from its comment.
+    pub fn from_openssh(line: &str) -> Result<Self, IdpError> {
+        let mut parts = line.split_whitespace();
+        let (Some(SSH_ED25519), Some(encoded)) = (parts.next(), parts.next()) else {
+            return Err(IdpError::Key("expected an `ssh-ed25519 AAAA...` public key line".to_string()));
+        };
+        let blob = BASE64
+            .decode(encoded.as_bytes())
+            .map_err(|_| IdpError::Key("the SSH public key is not valid Base64".to_string()))?;
+
+        // The blob repeats the key type before the 32-byte key.
+        let mut reader = WireReader(&blob);
+        if reader.string()? != SSH_ED25519.as_bytes() {
+            return Err(IdpError::Key("the SSH key blob is not an Ed25519 key".to_string()));
+        }
+        let raw = reader.string()?;
+        if raw.len() != 32 {
+            return Err(IdpError::Key("the SSH key blob is not an Ed25519 key".to_string()));
+        }
+
+        Ok(PublicKey {
+            key_id: parts.collect::<Vec<_>>().join(" "),
+            algorithm: "Ed25519".to_string(),
+            value: BASE64.encode(raw),
+            format: KeyFormat::Base64,
+            status: KeyStatus::Active,
+            parent_key_id: None,
+            purpose: KeyPurpose::Signing,
+            expires_at: None,
+            unknown_fields: Default::default(),
+        })
+    }
+}
+
+/// Signs `message` the way `ssh-keygen -Y sign -n <namespace>` does, returning the armored
+/// signature. Git verifies commit signatures in the `git` namespace.
+pub fn sshsig(signer: &dyn SigningBackend, public_key: &PublicKey, namespace: &str, message: &[u8]) -> Result<String, IdpError> {
+    if public_key.algorithm != "Ed25519" {
+        return Err(IdpError::Key(format!("'{}' keys cannot be used with SSH", public_key.algorithm)));
+    }
+    let public_blob = ed25519_blob(&public_key.raw_value()?);
+
+    // 1. The signature covers the namespace and a SHA-512 hash of the message.
+    let mut signed = SSHSIG_MAGIC.to_vec();
+    put_string(&mut signed, namespace.as_bytes());
+    put_string(&mut signed, b"");
+    put_string(&mut signed, b"sha512");
+    put_string(&mut signed, digest::digest(&digest::SHA512, message).as_ref());
+    let signature = BASE64
+        .decode(signer.sign(&signed)?.value.as_bytes())
+        .map_err(|_| IdpError::Crypto("the signer returned a malformed signature".to_string()))?;
+
+    // 2. The blob repeats all of that, with the key and the signature.
+    let mut blob = SSHSIG_MAGIC.to_vec();
+    blob.extend(1u32.to_be_bytes());
+    put_string(&mut blob, &public_blob);
+    put_string(&mut blob, namespace.as_bytes());
+    put_string(&mut blob, b"");
+    put_string(&mut blob, b"sha512");
+    let mut signature_blob = Vec::new();
+    put_string(&mut signature_blob, SSH_ED25519.as_bytes());
+    put_string(&mut signature_blob, &signature);
+    put_string(&mut blob, &signature_blob);
+
+    // 3. Armored with 70-character lines, like ssh-keygen.
+    let encoded = BASE64.encode(&blob);
+    let lines: Vec<&str> = encoded.as_bytes().chunks(70).map(|line| std::str::from_utf8(line).expect("Base64 is ASCII")).collect();
+    Ok(format!("-----BEGIN SSH SIGNATURE-----\n{}\n-----END SSH SIGNATURE-----\n", lines.join("\n")))
+}
+
+/// The SSH wire encoding of an Ed25519 public key.
+pub(crate) fn ed25519_blob(raw_public_key: &[u8]) -> Vec<u8> {
+    let mut blob = Vec::new();
+    put_string(&mut blob, SSH_ED25519.as_bytes());
+    put_string(&mut blob, raw_public_key);
+    blob
+}
+
+/// Appends an SSH `string`: a big-endian u32 length followed by the bytes.
+pub(crate) fn put_string(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend((bytes.len() as u32).to_be_bytes());
+    buffer.extend(bytes);
+}
+
+/// Reads SSH wire values from the front of a buffer.
+pub(crate) struct WireReader<'a>(pub(crate) &'a [u8]);
+
+impl<'a> WireReader<'a> {
+    pub(crate) fn u32(&mut self) -> Result<u32, IdpError> {
+        let bytes = self.0.get(..4).ok_or_else(truncated)?;
+        let value = u32::from_be_bytes(bytes.try_into().expect("four bytes"));
+        self.0 = &self.0[4..];
+        Ok(value)
+    }
+
+    pub(crate) fn string(&mut self) -> Result<&'a [u8], IdpError> {
+        let len = self.u32()? as usize;
+        let bytes = self.0.get(..len).ok_or_else(truncated)?;
+        self.0 = &self.0[len..];
+        Ok(bytes)
+    }
+}
+
+fn truncated() -> IdpError {
+    IdpError::Crypto("truncated SSH message".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_exports_an_openssh_public_key() {
+        let (identity, _) = Identity::new("SSH User", "Logs in.").unwrap();
+        let key = &identity.system.public_keys[0];
+
+        let line = key.to_openssh("ssh-user").unwrap();
+        let mut parts = line.split(' ');
+        assert_eq!(parts.next(), Some(SSH_ED25519));
+        let blob = BASE64.decode(parts.next().unwrap().as_bytes()).unwrap();
+        assert_eq!(parts.next(), Some("ssh-user"));
+
+        // The blob carries the key type and the raw key.
+        let mut reader = WireReader(&blob);
+        assert_eq!(reader.string().unwrap(), SSH_ED25519.as_bytes());
+        assert_eq!(BASE64.encode(reader.string().unwrap()), key.value);
+        let back = PublicKey::from_openssh(&line).unwrap();
+        assert_eq!((back.key_id.as_str(), back.value.as_str()), ("ssh-user", key.value.as_str()));
+
+        // Agreement keys are not SSH keys.
+        assert!(identity.agreement_key().unwrap().to_openssh("").is_err());
+        assert!(PublicKey::from_openssh("ssh-rsa AAAAB3NzaC1yc2E= rsa").is_err());
+    }
+}
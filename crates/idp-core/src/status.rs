@@ -0,0 +1,157 @@
+// crates/idp-core/src/status.rs
+
+// The recorded statuses of keys and contracts. Unknown values are rejected when a document is
+// read, so a typo like "actve" never passes for a status; reading leniently keeps them as `Other`.
+
+use std::cell::Cell;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::IdpError;
+
+thread_local! {
+    static LENIENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `read` with unknown statuses kept as `Other` instead of rejected.
+pub(crate) fn leniently<T>(read: impl FnOnce() -> T) -> T {
+    LENIENT.set(true);
+    let result = read();
+    LENIENT.set(false);
+    result
+}
+
+/// A key's recorded status. Whether it may sign right now also depends on its expiry and on
+/// signed revocations; see `Identity::key_status_at`.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum KeyStatus {
+    #[default]
+    Active,
+    /// Replaced by its successor in a rotation.
+    Superseded,
+    /// Withdrawn by a signed revocation.
+    Revoked,
+    /// A status this version does not know, kept when reading leniently.
+    Other(String),
+}
+
+impl KeyStatus {
+    const KNOWN: [KeyStatus; 3] = [KeyStatus::Active, KeyStatus::Superseded, KeyStatus::Revoked];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            KeyStatus::Active => "active",
+            KeyStatus::Superseded => "superseded",
+            KeyStatus::Revoked => "revoked",
+            KeyStatus::Other(other) => other,
+        }
+    }
+}
+
+/// A contract's place in its lifecycle.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum ContractStatus {
+    /// Written, but not yet signed by every party.
+    #[default]
+    Draft,
+    /// Signed by every party and in force.
+    Active,
+    /// Settled with the agreed outcome.
+    Fulfilled,
+    /// Settled because a party failed its obligations.
+    Breached,
+    /// Ended early by agreement.
+    Terminated,
+    /// A status this version does not know, kept when reading leniently.
+    Other(String),
+}
+
+impl ContractStatus {
+    const KNOWN: [ContractStatus; 5] = [
+        ContractStatus::Draft,
+        ContractStatus::Active,
+        ContractStatus::Fulfilled,
+        ContractStatus::Breached,
+        ContractStatus::Terminated,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            ContractStatus::Draft => "draft",
+            ContractStatus::Active => "active",
+            ContractStatus::Fulfilled => "fulfilled",
+            ContractStatus::Breached => "breached",
+            ContractStatus::Terminated => "terminated",
+            ContractStatus::Other(other) => other,
+        }
+    }
+}
+
+// Both enums are written as their names and parsed back the same way.
+macro_rules! status_text {
+    ($status:ident, $what:literal) => {
+        impl fmt::Display for $status {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(self.as_str())
+            }
+        }
+
+        impl FromStr for $status {
+            type Err = IdpError;
+
+            /// Parses a known status; `Other` is only ever made by lenient reading.
+            fn from_str(text: &str) -> Result<Self, IdpError> {
+                $status::KNOWN.into_iter().find(|s| s.as_str() == text).ok_or_else(|| IdpError::Status {
+                    kind: $what.to_string(),
+                    value: text.to_string(),
+                    expected: $status::KNOWN.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "),
+                })
+            }
+        }
+
+        impl Serialize for $status {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_str(self.as_str())
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $status {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let text = String::deserialize(deserializer)?;
+                match text.parse() {
+                    Ok(status) => Ok(status),
+                    Err(_) if LENIENT.get() => Ok($status::Other(text)),
+                    Err(e) => Err(de::Error::custom(e)),
+                }
+            }
+        }
+    };
+}
+
+status_text!(KeyStatus, "key status");
+status_text!(ContractStatus, "contract status");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_rejects_unknown_statuses_unless_lenient() {
+        let (identity, _) = Identity::new("Status User", "Typo prone.").unwrap();
+        let yaml = serde_yaml::to_string(&identity).unwrap().replacen("status: active", "status: actve", 1);
+
+        let error = Identity::parse(yaml.as_bytes()).unwrap_err();
+        assert!(error.to_string().contains("unknown key status 'actve', expected one of: active, superseded, revoked"));
+
+        let lenient = Identity::parse_lenient(yaml.as_bytes()).unwrap();
+        assert_eq!(lenient.system.public_keys[0].status, KeyStatus::Other("actve".to_string()));
+        assert!(serde_yaml::to_string(&lenient).unwrap().contains("status: actve"));
+        assert!(Identity::parse(yaml.as_bytes()).is_err());
+
+        assert_eq!("breached".parse::<ContractStatus>().unwrap(), ContractStatus::Breached);
+        assert!("done".parse::<ContractStatus>().is_err());
+    }
+}
@@ -0,0 +1,337 @@
+// crates/idp-core/src/status_list.rs
+
+// Revocation with a Bitstring Status List (W3C). An issuer keeps one list of bits in its document,
+// gives each revocable credential an index in it, and publishes the list as a signed VC at a URL;
+// setting a credential's bit revokes it. Verifiers fetch the list, check the issuer's signature
+// and the bit, and cache the list for a while so not every check goes to the issuer.
+
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::Utc;
+use data_encoding::{BASE64URL_NOPAD, HEXLOWER};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use ring::digest;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::signer::SigningBackend;
+use crate::{crypto, vc, CredentialStatus, Identity, IdpError, PublicKey};
+
+/// The `credentialStatus.type` of an entry in a bitstring status list.
+pub const STATUS_LIST_ENTRY: &str = "BitstringStatusListEntry";
+
+/// The status purpose where a set bit means the credential was revoked.
+pub const REVOCATION: &str = "revocation";
+
+/// The smallest list the spec allows (16 KiB of bits), so that an index says little about who holds it.
+pub const MIN_LENGTH: usize = 131_072;
+
+/// How long a fetched list is trusted when it does not give a `ttl` of its own.
+pub const DEFAULT_TTL: Duration = Duration::from_secs(300);
+
+// The extension namespace the issuer keeps its list in.
+const NAMESPACE: &str = "status-list";
+
+/// A list of status bits; bit 0 is the most significant bit of the first byte.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusList {
+    bits: Vec<u8>,
+}
+
+impl StatusList {
+    /// A list of `length` bits, all clear.
+    pub fn new(length: usize) -> StatusList {
+        StatusList { bits: vec![0; length.div_ceil(8)] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bits.len() * 8
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bits.is_empty()
+    }
+
+    /// The bit at `index`, or `None` past the end of the list.
+    pub fn get(&self, index: usize) -> Option<bool> {
+        self.bits.get(index / 8).map(|byte| byte & (0x80 >> (index % 8)) != 0)
+    }
+
+    pub fn set(&mut self, index: usize, value: bool) -> Result<(), IdpError> {
+        let length = self.len();
+        let byte = self.bits.get_mut(index / 8).ok_or_else(|| IdpError::Credential(format!("index {} is past the end of the {}-bit status list", index, length)))?;
+        match value {
+            true => *byte |= 0x80 >> (index % 8),
+            false => *byte &= !(0x80 >> (index % 8)),
+        }
+        Ok(())
+    }
+
+    /// The `encodedList` form: GZIP-compressed, then multibase Base64url (`u...`).
+    pub fn encode(&self) -> Result<String, IdpError> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::best());
+        encoder.write_all(&self.bits)?;
+        Ok(format!("u{}", BASE64URL_NOPAD.encode(&encoder.finish()?)))
+    }
+
+    pub fn decode(encoded: &str) -> Result<StatusList, IdpError> {
+        let invalid = || IdpError::Credential("the status list's encodedList is not multibase Base64url of GZIP data".to_string());
+        let compressed = encoded.strip_prefix('u').and_then(|encoded| BASE64URL_NOPAD.decode(encoded.as_bytes()).ok()).ok_or_else(invalid)?;
+        let mut bits = Vec::new();
+        GzDecoder::new(compressed.as_slice()).read_to_end(&mut bits).map_err(|_| invalid())?;
+        Ok(StatusList { bits })
+    }
+}
+
+/// The status list an issuer keeps in its document: where it is published, and what is in it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct IssuerStatusList {
+    pub url: String,
+    pub length: usize,
+    /// The index the next revocable credential gets.
+    pub next_index: usize,
+    #[serde(default)]
+    pub revoked: Vec<usize>,
+}
+
+/// The `credentialStatus` a VC carries for a status entry.
+pub fn status_entry(status: &CredentialStatus) -> Value {
+    json!({
+        "id": format!("{}#{}", status.list, status.index),
+        "type": STATUS_LIST_ENTRY,
+        "statusPurpose": status.purpose,
+        "statusListIndex": status.index.to_string(),
+        "statusListCredential": status.list,
+    })
+}
+
+/// Reads a status entry from a VC's `credentialStatus`, if it is a bitstring status list entry.
+pub fn parse_status_entry(entry: &Value) -> Option<CredentialStatus> {
+    if entry["type"] != STATUS_LIST_ENTRY {
+        return None;
+    }
+    Some(CredentialStatus {
+        purpose: entry["statusPurpose"].as_str()?.to_string(),
+        list: entry["statusListCredential"].as_str()?.to_string(),
+        index: entry["statusListIndex"].as_str()?.parse().ok()?,
+    })
+}
+
+impl Identity {
+    /// Starts a revocation list of `length` bits (at least `MIN_LENGTH`), to be published at `url`.
+    pub fn init_status_list(&mut self, url: &str, length: usize) -> Result<(), IdpError> {
+        if self.status_list()?.is_some() {
+            return Err(IdpError::Credential("this identity already has a status list".to_string()));
+        }
+        if length < MIN_LENGTH {
+            return Err(IdpError::Credential(format!("status lists hold at least {} entries", MIN_LENGTH)));
+        }
+        self.set_extension(NAMESPACE, &IssuerStatusList { url: url.to_string(), length, next_index: 0, revoked: Vec::new() })?;
+        self.touch();
+        Ok(())
+    }
+
+    /// The status list this identity keeps as an issuer, if it has one.
+    pub fn status_list(&self) -> Result<Option<IssuerStatusList>, IdpError> {
+        self.extension(NAMESPACE)
+    }
+
+    /// Takes the next free index in the status list, for a credential about to be issued.
+    pub fn allocate_status(&mut self) -> Result<CredentialStatus, IdpError> {
+        let mut list = self.status_list()?.ok_or_else(|| IdpError::Credential("this identity has no status list to issue revocable credentials with".to_string()))?;
+        if list.next_index >= list.length {
+            return Err(IdpError::Credential(format!("the status list at {} is full", list.url)));
+        }
+        let status = CredentialStatus { purpose: REVOCATION.to_string(), list: list.url.clone(), index: list.next_index };
+        list.next_index += 1;
+        self.set_extension(NAMESPACE, &list)?;
+        self.touch();
+        Ok(status)
+    }
+
+    /// Revokes the credential at `index` of the status list, or reinstates it if `revoked` is false.
+    /// Verifiers see the change once the list is published again.
+    pub fn set_status(&mut self, index: usize, revoked: bool) -> Result<(), IdpError> {
+        let mut list = self.status_list()?.ok_or_else(|| IdpError::Credential("this identity has no status list".to_string()))?;
+        if index >= list.next_index {
+            return Err(IdpError::Credential(format!("no credential was given index {} of the status list", index)));
+        }
+        list.revoked.retain(|revoked| *revoked != index);
+        if revoked {
+            list.revoked.push(index);
+            list.revoked.sort();
+        }
+        self.set_extension(NAMESPACE, &list)?;
+        self.touch();
+        Ok(())
+    }
+
+    /// The status list as a `BitstringStatusListCredential`, signed by `signer`'s Ed25519 key of
+    /// this identity with an `eddsa-jcs-2022` Data Integrity proof, ready to publish at its URL.
+    pub fn publish_status_list(&self, signer: &dyn SigningBackend) -> Result<Value, IdpError> {
+        let list = self.status_list()?.ok_or_else(|| IdpError::Credential("this identity has no status list".to_string()))?;
+        let key = self.issuing_key(signer)?;
+        if key.algorithm != crypto::ED25519 {
+            return Err(IdpError::Credential("status lists are signed with eddsa-jcs-2022, which needs an Ed25519 key".to_string()));
+        }
+
+        // 1. The bits, as the list credential's subject.
+        let mut bits = StatusList::new(list.length);
+        for index in &list.revoked {
+            bits.set(*index, true)?;
+        }
+        let now = Utc::now();
+        let mut document = json!({
+            "@context": [vc::VC_CONTEXT],
+            "id": list.url,
+            "type": ["VerifiableCredential", "BitstringStatusListCredential"],
+            "issuer": self.identity.id,
+            "validFrom": now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "credentialSubject": {
+                "id": format!("{}#list", list.url),
+                "type": "BitstringStatusList",
+                "statusPurpose": REVOCATION,
+                "encodedList": bits.encode()?,
+            },
+        });
+
+        // 2. Signed as exported VCs are.
+        let mut options = json!({
+            "type": "DataIntegrityProof",
+            "cryptosuite": vc::EDDSA_JCS_2022,
+            "created": now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+            "verificationMethod": format!("{}#{}", self.identity.id, key.key_id),
+            "proofPurpose": "assertionMethod",
+        });
+        let signature = signer.sign(&vc::data_integrity_payload(&document, &options))?;
+        let signature = data_encoding::BASE64.decode(signature.value.as_bytes()).map_err(|e| IdpError::Crypto(e.to_string()))?;
+        options["proofValue"] = json!(format!("z{}", bs58::encode(signature).into_string()));
+        document["proof"] = options;
+        Ok(document)
+    }
+}
+
+/// Reads whether a credential's bit is set in a published list, once the list's proof has been
+/// checked with the issuer's key. `issuer_keys` are searched for the key the proof names.
+pub fn check_status(status: &CredentialStatus, issuer: &str, list_document: &Value, issuer_keys: &[PublicKey]) -> Result<bool, IdpError> {
+    // 1. The list must be the one named, published and signed by the credential's issuer.
+    if list_document["id"] != status.list.as_str() || list_document["issuer"] != issuer {
+        return Err(IdpError::Credential(format!("the status list at {} is not {}'s list", status.list, issuer)));
+    }
+    let method = list_document["proof"]["verificationMethod"].as_str().unwrap_or_default();
+    let key = match method.split_once('#') {
+        Some((id, key_id)) if id == issuer => issuer_keys.iter().find(|key| key.key_id == key_id).cloned(),
+        _ if method.starts_with("did:key:") && method.starts_with(issuer) => Some(vc::resolve_did_key(method)?),
+        _ => None,
+    };
+    let key = key.ok_or_else(|| IdpError::Credential(format!("the status list is signed by '{}', which is not a key of {}", method, issuer)))?;
+    vc::verify_data_integrity(list_document, &key)?;
+
+    // 2. The bit, for a list of the same purpose.
+    let subject = &list_document["credentialSubject"];
+    if subject["statusPurpose"] != status.purpose.as_str() {
+        return Err(IdpError::Credential(format!("the status list at {} is not for {}", status.list, status.purpose)));
+    }
+    let bits = StatusList::decode(subject["encodedList"].as_str().unwrap_or_default())?;
+    bits.get(status.index).ok_or_else(|| IdpError::Credential(format!("index {} is past the end of the status list at {}", status.index, status.list)))
+}
+
+/// Status lists fetched before, kept as files in a directory.
+#[derive(Debug, Clone)]
+pub struct StatusListCache {
+    dir: PathBuf,
+}
+
+impl StatusListCache {
+    pub fn new(dir: impl Into<PathBuf>) -> StatusListCache {
+        StatusListCache { dir: dir.into() }
+    }
+
+    /// The list at `url`: the cached copy while it is younger than its `ttl` (milliseconds, as the
+    /// list gives it) or `DEFAULT_TTL`, otherwise a fresh one, which replaces it.
+    pub fn get(&self, url: &str) -> Result<Value, IdpError> {
+        let path = self.dir.join(format!("{}.json", HEXLOWER.encode(&digest::digest(&digest::SHA256, url.as_bytes()).as_ref()[..16])));
+        if let Ok(cached) = std::fs::read(&path)
+            && let Ok(document) = serde_json::from_slice::<Value>(&cached)
+        {
+            let ttl = document["credentialSubject"]["ttl"].as_u64().map(Duration::from_millis).unwrap_or(DEFAULT_TTL);
+            let age = std::fs::metadata(&path)?.modified()?.elapsed().unwrap_or(Duration::MAX);
+            if age < ttl {
+                return Ok(document);
+            }
+        }
+        let document = fetch(url)?;
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_json::to_vec(&document)?)?;
+        Ok(document)
+    }
+
+    /// Forgets every cached list.
+    pub fn clear(&self) -> Result<(), IdpError> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+// Reads a list from a local path or `file://` URL, or over HTTP(S) with the `remote-status` feature.
+fn fetch(url: &str) -> Result<Value, IdpError> {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        return fetch_remote(url);
+    }
+    let path = url.strip_prefix("file://").unwrap_or(url);
+    Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+}
+
+#[cfg(feature = "remote-status")]
+fn fetch_remote(url: &str) -> Result<Value, IdpError> {
+    let response = ureq::get(url).call().map_err(|e| IdpError::Credential(format!("could not fetch the status list at '{}': {}", url, e)))?;
+    Ok(serde_json::from_reader(response.into_reader())?)
+}
+
+#[cfg(not(feature = "remote-status"))]
+fn fetch_remote(url: &str) -> Result<Value, IdpError> {
+    Err(IdpError::Credential(format!("this build cannot fetch status lists over HTTP ('{}'); save the list and pass its path", url)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_revokes_credentials_through_a_published_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let url = format!("file://{}", dir.path().join("status.json").display());
+        let (mut issuer, issuer_key) = Identity::new("Issuer", "Revokes things.").unwrap();
+        let (holder, _) = Identity::new("Holder", "Holds things.").unwrap();
+        issuer.init_status_list(&url, MIN_LENGTH).unwrap();
+        let issued = issuer.issue_revocable_credential(&holder, "member", None, &issuer_key).unwrap();
+        let status = issued.credential.status.clone().unwrap();
+        assert_eq!((status.index, issuer.status_list().unwrap().unwrap().next_index), (0, 1));
+
+        // Published and fetched, the list says the credential stands, until it is revoked.
+        let publish = |issuer: &Identity| std::fs::write(dir.path().join("status.json"), issuer.publish_status_list(&issuer_key).unwrap().to_string()).unwrap();
+        publish(&issuer);
+        let cache = StatusListCache::new(dir.path().join("cache"));
+        let keys = &issuer.system.public_keys.clone();
+        let issuer_id = issuer.identity.id.to_string();
+        assert!(!check_status(&status, &issuer_id, &cache.get(&url).unwrap(), keys).unwrap());
+        issuer.set_status(status.index, true).unwrap();
+        publish(&issuer);
+        assert!(!check_status(&status, &issuer_id, &cache.get(&url).unwrap(), keys).unwrap(), "the cached copy is still fresh");
+        cache.clear().unwrap();
+        assert!(check_status(&status, &issuer_id, &cache.get(&url).unwrap(), keys).unwrap());
+
+        // A list altered after signing, or by someone else, is refused.
+        let mut tampered = cache.get(&url).unwrap();
+        tampered["credentialSubject"]["encodedList"] = json!(StatusList::new(MIN_LENGTH).encode().unwrap());
+        assert!(check_status(&status, &issuer_id, &tampered, keys).is_err());
+        assert!(check_status(&status, holder.identity.id.as_str(), &tampered, keys).is_err());
+        assert!(issuer.set_status(5, true).is_err());
+    }
+}
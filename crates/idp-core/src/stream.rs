@@ -0,0 +1,244 @@
+// crates/idp-core/src/stream.rs
+
+// Reading only some sections of a document. Sections that are not asked for are skipped as they
+// are read, without building their entries, so the identity and system blocks of a document
+// with thousands of reputation events or proofs load quickly. JSON is read as a stream.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::marker::PhantomData;
+use std::path::Path;
+
+use serde::de::value::MapAccessDeserializer;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+
+use crate::sealing::SealedSection;
+use crate::{compress, encryption, Consent, ConsentLogEntry, Contract, CoreBlock, Credential, DocumentSignature, Format, Identity, IdentityBlock, IdpError, Proof, Reputation, SystemBlock};
+
+/// The top-level sections of a document, in the order they are written.
+pub const SECTIONS: [&str; 13] = [
+    "identity",
+    "system",
+    "core",
+    "credentials",
+    "archived_credentials",
+    "proofs",
+    "contracts",
+    "reputation",
+    "consent",
+    "archived_consent",
+    "consent_log",
+    "extensions",
+    "signature",
+];
+
+// Sections every document has; asking for one the document lacks is an error.
+const REQUIRED: [&str; 3] = ["identity", "system", "core"];
+
+/// The sections read by `Identity::read_sections`. Each is `None` if it was not asked for or
+/// the document does not have it; sealed sections are in `sealed` instead.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PartialIdentity {
+    pub identity: Option<IdentityBlock>,
+    pub system: Option<SystemBlock>,
+    pub core: Option<CoreBlock>,
+    pub credentials: Option<Vec<Credential>>,
+    pub archived_credentials: Option<Vec<Credential>>,
+    pub proofs: Option<Vec<Proof>>,
+    pub contracts: Option<Vec<Contract>>,
+    pub reputation: Option<Vec<Reputation>>,
+    pub consent: Option<Vec<Consent>>,
+    pub archived_consent: Option<Vec<Consent>>,
+    pub consent_log: Option<Vec<ConsentLogEntry>>,
+    pub extensions: Option<BTreeMap<String, serde_yaml::Value>>,
+    pub signature: Option<DocumentSignature>,
+    pub sealed: BTreeMap<String, SealedSection>,
+}
+
+impl Identity {
+    /// Reads only the named sections (see `SECTIONS`) of a plain document file.
+    pub fn load_sections<P: AsRef<Path>>(path: P, sections: &[&str]) -> Result<PartialIdentity, IdpError> {
+        Self::read_sections(File::open(path)?, sections)
+    }
+
+    /// Reads only the named sections of a plain document, in either format.
+    pub fn read_sections<R: Read>(reader: R, sections: &[&str]) -> Result<PartialIdentity, IdpError> {
+        if let Some(unknown) = sections.iter().find(|section| !SECTIONS.contains(section)) {
+            return Err(IdpError::Path { path: unknown.to_string(), reason: "not a section of the document".to_string() });
+        }
+
+        // Compressed documents are decompressed as they are read.
+        let mut reader = BufReader::new(reader);
+        if compress::is_compressed(reader.fill_buf()?) {
+            reader.consume(compress::MAGIC.len());
+            return read_plain_sections(BufReader::new(zstd::Decoder::with_buffer(reader)?), sections);
+        }
+        read_plain_sections(reader, sections)
+    }
+}
+
+fn read_plain_sections<R: BufRead>(mut reader: R, sections: &[&str]) -> Result<PartialIdentity, IdpError> {
+    // The start of the document tells its format.
+    let start = reader.fill_buf()?;
+    if encryption::is_encrypted_document(start) {
+        return Err(IdpError::Encrypted);
+    }
+    let seed = Sections { wanted: sections };
+
+    // Read the top-level mapping, skipping the sections that were not asked for.
+    match Format::detect(start) {
+        Format::Json => Ok(seed.deserialize(&mut serde_json::Deserializer::from_reader(reader))?),
+        Format::Yaml => Ok(seed.deserialize(serde_yaml::Deserializer::from_reader(reader))?),
+    }
+}
+
+struct Sections<'a> {
+    wanted: &'a [&'a str],
+}
+
+impl<'de> DeserializeSeed<'de> for Sections<'_> {
+    type Value = PartialIdentity;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<PartialIdentity, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for Sections<'_> {
+    type Value = PartialIdentity;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an IDP document")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<PartialIdentity, A::Error> {
+        let mut partial = PartialIdentity::default();
+        while let Some(key) = map.next_key::<String>()? {
+            if !self.wanted.contains(&key.as_str()) {
+                map.next_value::<IgnoredAny>()?;
+                continue;
+            }
+            match key.as_str() {
+                "identity" => partial.identity = Some(map.next_value()?),
+                "system" => partial.system = Some(map.next_value()?),
+                "core" => partial.core = Some(map.next_value()?),
+                "credentials" => partial.credentials = section(&mut map, &key, &mut partial.sealed)?,
+                "archived_credentials" => partial.archived_credentials = Some(map.next_value()?),
+                "proofs" => partial.proofs = Some(map.next_value()?),
+                "contracts" => partial.contracts = section(&mut map, &key, &mut partial.sealed)?,
+                "reputation" => partial.reputation = section(&mut map, &key, &mut partial.sealed)?,
+                "consent" => partial.consent = section(&mut map, &key, &mut partial.sealed)?,
+                "archived_consent" => partial.archived_consent = Some(map.next_value()?),
+                "consent_log" => partial.consent_log = section(&mut map, &key, &mut partial.sealed)?,
+                "extensions" => partial.extensions = Some(map.next_value()?),
+                "signature" => partial.signature = Some(map.next_value()?),
+                _ => unreachable!("only known sections are wanted"),
+            }
+        }
+
+        let present = [partial.identity.is_some(), partial.system.is_some(), partial.core.is_some()];
+        match REQUIRED.iter().zip(present).find(|(name, present)| self.wanted.contains(name) && !present) {
+            Some((name, _)) => Err(de::Error::missing_field(name)),
+            None => Ok(partial),
+        }
+    }
+}
+
+// A sealable section: its entries, or the sealed block written in their place.
+fn section<'de, A: MapAccess<'de>, T: DeserializeOwned>(
+    map: &mut A,
+    name: &str,
+    sealed: &mut BTreeMap<String, SealedSection>,
+) -> Result<Option<Vec<T>>, A::Error> {
+    match map.next_value::<Section<T>>()? {
+        Section::Open(entries) => Ok(Some(entries)),
+        Section::Sealed(section) => {
+            sealed.insert(name.to_string(), section);
+            Ok(None)
+        }
+    }
+}
+
+enum Section<T> {
+    Open(Vec<T>),
+    Sealed(SealedSection),
+}
+
+impl<'de, T: DeserializeOwned> Deserialize<'de> for Section<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct SectionVisitor<T>(PhantomData<T>);
+
+        impl<'de, T: DeserializeOwned> Visitor<'de> for SectionVisitor<T> {
+            type Value = Section<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a list of entries or a sealed section")
+            }
+
+            // Entries are built one at a time, without an intermediate tree.
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Section<T>, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = seq.next_element()? {
+                    entries.push(entry);
+                }
+                Ok(Section::Open(entries))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Section<T>, A::Error> {
+                SealedSection::deserialize(MapAccessDeserializer::new(map)).map(Section::Sealed)
+            }
+        }
+
+        deserializer.deserialize_any(SectionVisitor(PhantomData))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ReputationEvent;
+
+    #[test]
+    fn it_reads_only_the_sections_asked_for() {
+        let dir = tempfile::tempdir().unwrap();
+        let (mut identity, _) = Identity::new("Busy User", "Lots of history.").unwrap();
+        let events = (0..500)
+            .map(|i| ReputationEvent {
+                event: format!("event-{}", i),
+                change: 1,
+                timestamp: "2024-07-06T10:00:00Z".parse().unwrap(),
+                issued_by: None,
+                signature: vec![],
+                unknown_fields: Default::default(),
+            })
+            .collect();
+        identity.reputation.push(Reputation {
+            score_name: "marketplace".to_string(),
+            value: 500,
+            history: events,
+            unknown_fields: Default::default(),
+        });
+        identity.seal_section("reputation").unwrap();
+
+        for format in [Format::Yaml, Format::Json] {
+            let file_path = dir.path().join("busy.idp");
+            identity.save_to_file_with_format(&file_path, format).unwrap();
+
+            let header = Identity::load_sections(&file_path, &["identity", "system"]).unwrap();
+            assert_eq!(header.identity.as_ref(), Some(&identity.identity));
+            assert_eq!(header.system.as_ref(), Some(&identity.system));
+            assert_eq!((header.core, header.proofs, header.reputation), (None, None, None));
+
+            // Sealed sections come back as ciphertext.
+            let history = Identity::load_sections(&file_path, &["reputation", "proofs"]).unwrap();
+            assert_eq!(history.proofs.as_ref(), Some(&identity.proofs));
+            assert_eq!(history.sealed["reputation"].message, identity.sealed["reputation"].message);
+        }
+
+        assert!(matches!(Identity::read_sections(&b"{}"[..], &["core"]), Err(IdpError::Json(_))));
+        assert!(matches!(Identity::read_sections(&b"{}"[..], &["nickname"]), Err(IdpError::Path { .. })));
+    }
+}
@@ -0,0 +1,278 @@
+// crates/idp-core/src/subject_access.rs
+
+// Subject access exports: everything a document holds about its subject, laid out for a person
+// to read rather than a program, as GDPR's right of access asks. The report lists the personal
+// data, whom it was shared with under consent, the credentials held and who issued them, and the
+// full audit trail: the consent log and the event log of saved changes. It is written as JSON,
+// and as a self-contained HTML page rendered from the same fields, in the order they are declared.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use serde_yaml::{Mapping, Value as Node};
+
+use crate::events::Event;
+use crate::{Consent, Identity, IdpError};
+
+/// A subject access report on one identity.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SubjectAccessReport {
+    pub subject: String,
+    pub generated_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// The core block and the application data kept under extensions.
+    pub personal_data: Value,
+    pub keys: Vec<KeyRecord>,
+    /// Every consent given, in force or not.
+    pub shared_with: Vec<SharingRecord>,
+    pub credentials: Vec<CredentialRecord>,
+    pub contracts: Vec<ContractRecord>,
+    pub reputation: Vec<ReputationRecord>,
+    /// The consent log and the saved changes, oldest first.
+    pub audit_trail: Vec<AuditRecord>,
+    /// Sections still encrypted, whose contents the report could not include.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub sealed_sections: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct KeyRecord {
+    pub key_id: String,
+    pub purpose: String,
+    pub status: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct SharingRecord {
+    pub granted_to: String,
+    pub purpose: String,
+    pub fields: Vec<String>,
+    pub granted_at: Option<DateTime<Utc>>,
+    pub expires_at: DateTime<Utc>,
+    /// `active`, `expired`, `revoked` or `archived`.
+    pub state: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct CredentialRecord {
+    pub claim: String,
+    pub issued_by: String,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub archived: bool,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ContractRecord {
+    pub contract_id: String,
+    pub status: String,
+    pub parties: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ReputationRecord {
+    pub score_name: String,
+    pub value: i64,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub at: DateTime<Utc>,
+    /// `consent` for the consent log, `document` for the event log.
+    pub source: String,
+    pub description: String,
+}
+
+impl Identity {
+    /// The subject access report on this identity at `now`, with the saved changes in `events`
+    /// (the document's event log) as part of its audit trail.
+    pub fn subject_access_report(&self, events: &[Event], now: DateTime<Utc>) -> Result<SubjectAccessReport, IdpError> {
+        // 1. The personal data.
+        let mut personal_data = Map::new();
+        personal_data.insert("core".to_string(), serde_json::to_value(&self.core)?);
+        if !self.extensions.is_empty() {
+            personal_data.insert("extensions".to_string(), serde_json::to_value(&self.extensions)?);
+        }
+
+        // 2. Whom it was shared with.
+        let sharing = |consent: &Consent, archived: bool| SharingRecord {
+            granted_to: consent.granted_to.clone(),
+            purpose: consent.purpose.to_string(),
+            fields: consent.fields.clone(),
+            granted_at: consent.granted_at,
+            expires_at: consent.expires_at,
+            state: match (consent.revoked_at, archived, consent.is_expired(now)) {
+                (Some(_), _, _) => "revoked",
+                (None, true, _) => "archived",
+                (None, false, true) => "expired",
+                (None, false, false) => "active",
+            }
+            .to_string(),
+        };
+        let shared_with = self.consent.iter().map(|consent| sharing(consent, false)).chain(self.archived_consent.iter().map(|consent| sharing(consent, true))).collect();
+
+        // 3. The credentials held, and the rest of the document.
+        let credentials = self
+            .credentials
+            .iter()
+            .map(|credential| (credential, false))
+            .chain(self.archived_credentials.iter().map(|credential| (credential, true)))
+            .map(|(credential, archived)| CredentialRecord {
+                claim: credential.claim.clone(),
+                issued_by: credential.issued_by.clone(),
+                issued_at: credential.issued_at,
+                expires_at: credential.expires_at,
+                archived,
+            })
+            .collect();
+        let keys = self.system.public_keys.iter().map(|key| KeyRecord { key_id: key.key_id.clone(), purpose: key.purpose.to_string(), status: key.status.as_str().to_string() }).collect();
+        let contracts = self.contracts.iter().map(|contract| ContractRecord { contract_id: contract.contract_id.clone(), status: contract.status.to_string(), parties: contract.parties.clone() }).collect();
+        let reputation = self.reputation.iter().map(|score| ReputationRecord { score_name: score.score_name.clone(), value: score.value }).collect();
+
+        // 4. The audit trail, from both logs.
+        let mut audit_trail: Vec<AuditRecord> = self
+            .consent_log
+            .iter()
+            .map(|entry| {
+                let fields = if entry.fields.is_empty() { String::new() } else { format!(": {}", entry.fields.join(", ")) };
+                AuditRecord { at: entry.at, source: "consent".to_string(), description: format!("{} {} for '{}'{}", entry.action, entry.granted_to, entry.purpose, fields) }
+            })
+            .chain(events.iter().map(|event| AuditRecord { at: event.at, source: "document".to_string(), description: format!("{} (signed with {})", event.summary(), event.signed_by.key_id) }))
+            .collect();
+        audit_trail.sort_by_key(|record| record.at);
+
+        Ok(SubjectAccessReport {
+            subject: self.identity.id.to_string(),
+            generated_at: now,
+            created_at: self.identity.created_at,
+            updated_at: self.identity.updated_at,
+            personal_data: Value::Object(personal_data),
+            keys,
+            shared_with,
+            credentials,
+            contracts,
+            reputation,
+            audit_trail,
+            sealed_sections: self.sealed.keys().filter(|name| self.is_locked(name)).cloned().collect(),
+        })
+    }
+}
+
+impl SubjectAccessReport {
+    pub fn to_json(&self) -> Result<String, IdpError> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// The report as a self-contained HTML page: a section for each part, lists of records
+    /// as tables.
+    pub fn to_html(&self) -> Result<String, IdpError> {
+        let report = serde_yaml::to_value(self)?;
+        let mut html = String::new();
+        html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+        html.push_str(&format!("<title>Subject access report: {}</title>\n", escape(&self.subject)));
+        html.push_str("<style>body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}th,td{border:1px solid #ccc;padding:.3em .6em;text-align:left;vertical-align:top}</style>\n</head>\n<body>\n");
+        html.push_str("<h1>Subject access report</h1>\n");
+
+        // The single values (the subject, the times) lead; every list gets its own section.
+        let (sections, summary): (Mapping, Mapping) = report.as_mapping().cloned().unwrap_or_default().into_iter().partition(|(_, value)| value.is_mapping() || value.is_sequence());
+        html.push_str(&render(&Node::Mapping(summary)));
+        html.push('\n');
+        for (name, value) in &sections {
+            html.push_str(&format!("<h2>{}</h2>\n", escape(&title(name.as_str().unwrap_or_default()))));
+            html.push_str(&render(value));
+            html.push('\n');
+        }
+        html.push_str("</body>\n</html>\n");
+        Ok(html)
+    }
+}
+
+// `shared_with` becomes `Shared with`.
+fn title(name: &str) -> String {
+    let words = name.replace('_', " ");
+    let mut chars = words.chars();
+    chars.next().map(|first| first.to_uppercase().chain(chars).collect()).unwrap_or_default()
+}
+
+// A value as HTML: lists of records as tables, records as definition lists.
+fn render(value: &Node) -> String {
+    match value {
+        Node::Null => "<p><em>none</em></p>".to_string(),
+        Node::Sequence(items) if items.is_empty() => "<p><em>none</em></p>".to_string(),
+        Node::Sequence(items) if items.iter().all(Node::is_mapping) => {
+            let columns: Vec<&Node> = items[0].as_mapping().into_iter().flat_map(|row| row.keys()).collect();
+            let mut table = String::from("<table>\n<tr>");
+            for column in &columns {
+                table.push_str(&format!("<th>{}</th>", escape(&title(column.as_str().unwrap_or_default()))));
+            }
+            table.push_str("</tr>\n");
+            for item in items {
+                table.push_str("<tr>");
+                for column in &columns {
+                    table.push_str(&format!("<td>{}</td>", item.get(*column).map(inline).unwrap_or_default()));
+                }
+                table.push_str("</tr>\n");
+            }
+            table.push_str("</table>");
+            table
+        }
+        Node::Mapping(fields) => {
+            let mut list = String::from("<dl>\n");
+            for (name, value) in fields {
+                let value = if value.is_mapping() || value.is_sequence() { render(value) } else { inline(value) };
+                list.push_str(&format!("<dt>{}</dt><dd>{}</dd>\n", escape(&title(name.as_str().unwrap_or_default())), value));
+            }
+            list.push_str("</dl>");
+            list
+        }
+        value => format!("<p>{}</p>", inline(value)),
+    }
+}
+
+// A value inside a table cell or after a term.
+fn inline(value: &Node) -> String {
+    match value {
+        Node::Null => String::new(),
+        Node::Bool(flag) => flag.to_string(),
+        Node::Number(number) => number.to_string(),
+        Node::String(text) => escape(text),
+        Node::Sequence(items) => items.iter().map(inline).collect::<Vec<_>>().join(", "),
+        Node::Mapping(_) => render(value),
+        Node::Tagged(tagged) => inline(&tagged.value),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConsentPurpose;
+    use chrono::Duration;
+
+    #[test]
+    fn it_reports_everything_held_about_the_subject() {
+        let (mut identity, _) = Identity::new("Alice <Admin>", "Shops online.").unwrap();
+        let now = Utc::now();
+        identity.grant_consent("shop", &["core.name".to_string()], &ConsentPurpose::ServiceDelivery, now + Duration::days(30)).unwrap();
+        identity.grant_consent("lab", &["core".to_string()], &ConsentPurpose::Research, now + Duration::days(30)).unwrap();
+        identity.revoke_consent("lab", None).unwrap();
+
+        let report = identity.subject_access_report(&[], Utc::now()).unwrap();
+        assert_eq!(report.personal_data["core"]["name"], Value::from("Alice <Admin>"));
+        let shared: Vec<(&str, &str)> = report.shared_with.iter().map(|record| (record.granted_to.as_str(), record.state.as_str())).collect();
+        assert_eq!(shared, [("shop", "active"), ("lab", "revoked")]);
+        assert_eq!(report.audit_trail.len(), 3);
+        assert_eq!(report.keys.len(), identity.system.public_keys.len());
+
+        // Both renderings hold the same report; the HTML escapes what it shows.
+        let json: Value = serde_json::from_str(&report.to_json().unwrap()).unwrap();
+        assert_eq!(json["shared_with"][1]["state"], Value::from("revoked"));
+        let html = report.to_html().unwrap();
+        assert!(html.contains("<h2>Shared with</h2>") && html.contains("<td>lab</td>"));
+        assert!(html.contains("Alice &lt;Admin&gt;") && !html.contains("<Admin>"));
+    }
+}
@@ -0,0 +1,483 @@
+// crates/idp-core/src/templates.rs
+
+// Credential and contract templates. A credential template gives a common kind of credential
+// its claim, as text or as a typed claim's fields, with `{variable}` placeholders the issuer
+// fills in, and a default validity period; a contract template does the same for a contract's
+// parties, terms and obligations. A few of each are built in; users add their own, or override
+// these, as TOML or YAML files in a templates directory.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Duration, Utc};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{claims, Amount, Consequence, Contract, ContractStatus, IdpError, Obligation};
+
+/// A variable of a template; without a default, the issuer must give it.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct TemplateVariable {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+}
+
+/// A template for one kind of credential.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CredentialTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The claim text, for a free-text claim.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub claim: Option<String>,
+    /// The claim type, by schema name or URI, for a typed claim built from `fields`.
+    #[serde(default, rename = "type", skip_serializing_if = "Option::is_none")]
+    pub claim_type: Option<String>,
+    /// The typed claim's fields; values are read as JSON if they are, and left out if empty.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub fields: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<TemplateVariable>,
+    /// How long credentials from this template are valid; without it, they never expire.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub valid_for_days: Option<i64>,
+}
+
+/// An obligation of a contract template; every field may hold placeholders.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ObligationTemplate {
+    pub party: String,
+    pub action: String,
+    /// Days from the contract's creation until it falls due.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due_days: Option<String>,
+    /// The sum owed, as a decimal number; left out if it renders empty.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub amount: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+}
+
+/// A template for one kind of contract.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ContractTemplate {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    /// The parties' ids, usually one variable each, e.g. `{provider}`.
+    pub parties: Vec<String>,
+    pub terms: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub obligations: Vec<ObligationTemplate>,
+    pub on_success: String,
+    pub on_failure: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<TemplateVariable>,
+    /// Days from the contract's creation until its deadline; without it, it has none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_days: Option<String>,
+}
+
+/// The templates that ship with IDP, as YAML an issuer can copy and change.
+pub const BUILT_IN: [(&str, &str); 3] = [
+    (
+        "employment",
+        "name: employment
+description: Employment at an organization, as a typed membership claim.
+type: membership
+fields:
+  organization: '{employer}'
+  role: '{role}'
+  since: '{since}'
+variables:
+  - name: employer
+  - name: role
+    default: employee
+  - name: since
+    description: the start date, e.g. 2024-01-31
+    default: ''
+valid_for_days: 365
+",
+    ),
+    (
+        "qualification",
+        "name: qualification
+description: A degree, certificate or licence, as a typed qualification claim.
+type: qualification
+fields:
+  title: '{title}'
+  field: '{field}'
+  awarded_on: '{awarded_on}'
+variables:
+  - name: title
+  - name: field
+    default: ''
+  - name: awarded_on
+    default: ''
+",
+    ),
+    (
+        "age-over-18",
+        "name: age-over-18
+description: The holder is 18 or older, without saying when they were born.
+claim: age over 18
+",
+    ),
+];
+
+/// The contract templates that ship with IDP, as YAML to copy and change.
+pub const CONTRACT_BUILT_IN: [(&str, &str); 3] = [
+    (
+        "data-sharing",
+        "name: data-sharing
+description: A controller shares data with a recipient, for one purpose and for a limited time.
+parties: ['{controller}', '{recipient}']
+terms: The controller shares {data} with the recipient, who uses it only for {purpose} and deletes it within {days} days.
+obligations:
+  - party: '{controller}'
+    action: share {data}
+  - party: '{recipient}'
+    action: use {data} only for {purpose}, and delete it
+    due_days: '{days}'
+on_success: the data is deleted
+on_failure: the recipient is liable for any misuse
+variables:
+  - name: controller
+  - name: recipient
+  - name: data
+  - name: purpose
+  - name: days
+    default: '365'
+deadline_days: '{days}'
+",
+    ),
+    (
+        "loan",
+        "name: loan
+description: A lender lends a sum of money, which the borrower repays by a date.
+parties: ['{lender}', '{borrower}']
+terms: The lender lends {amount} {currency} to the borrower, who repays it within {days} days.
+obligations:
+  - party: '{lender}'
+    action: pay out the loan
+    amount: '{amount}'
+    currency: '{currency}'
+  - party: '{borrower}'
+    action: repay the loan
+    due_days: '{days}'
+    amount: '{amount}'
+    currency: '{currency}'
+on_success: the loan is repaid
+on_failure: '{on_failure}'
+variables:
+  - name: lender
+  - name: borrower
+  - name: amount
+  - name: currency
+    default: EUR
+  - name: days
+    default: '90'
+  - name: on_failure
+    default: the borrower owes interest on the amount due
+deadline_days: '{days}'
+",
+    ),
+    (
+        "service-agreement",
+        "name: service-agreement
+description: A provider delivers a service to a client, who pays a fee for it.
+parties: ['{provider}', '{client}']
+terms: The provider delivers {service} to the client within {days} days, for {fee} {currency}.
+obligations:
+  - party: '{provider}'
+    action: deliver {service}
+    due_days: '{days}'
+  - party: '{client}'
+    action: pay the fee
+    due_days: '{days}'
+    amount: '{fee}'
+    currency: '{currency}'
+on_success: the service is delivered and paid for
+on_failure: the fee is refunded
+variables:
+  - name: provider
+  - name: client
+  - name: service
+  - name: fee
+  - name: currency
+    default: EUR
+  - name: days
+    default: '30'
+deadline_days: '{days}'
+",
+    ),
+];
+
+// Reads a template as TOML or YAML, by the file's extension (YAML for anything else).
+fn read<T: DeserializeOwned>(text: &str, path: &Path) -> Result<T, IdpError> {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some("toml") => toml::from_str(text).map_err(|e| IdpError::Template(format!("{}: {}", path.display(), e))),
+        _ => serde_yaml::from_str(text).map_err(|e| IdpError::Template(format!("{}: {}", path.display(), e))),
+    }
+}
+
+// The user's template file for `name` in `dir` (`<name>.toml`, `.yaml` or `.yml`), if there is one.
+fn find(name: &str, dir: Option<&Path>) -> Option<PathBuf> {
+    dir.into_iter().flat_map(|dir| ["toml", "yaml", "yml"].map(|extension| dir.join(format!("{}.{}", name, extension)))).find(|path| path.is_file())
+}
+
+// Every template file in `dir`; none if there is no such directory.
+fn files(dir: Option<&Path>) -> Result<Vec<PathBuf>, IdpError> {
+    let mut paths = Vec::new();
+    if let Some(entries) = dir.and_then(|dir| std::fs::read_dir(dir).ok()) {
+        for entry in entries {
+            let path = entry?.path();
+            if matches!(path.extension().and_then(|extension| extension.to_str()), Some("toml" | "yaml" | "yml")) {
+                paths.push(path);
+            }
+        }
+    }
+    Ok(paths)
+}
+
+// Each variable's placeholder and value, given or default: every variable without a default
+// must be given, and no others.
+fn values(template: &str, variables: &[TemplateVariable], vars: &BTreeMap<String, String>) -> Result<Vec<(String, String)>, IdpError> {
+    if let Some(unknown) = vars.keys().find(|name| !variables.iter().any(|variable| variable.name == **name)) {
+        return Err(IdpError::Template(format!("'{}' has no variable '{}'", template, unknown)));
+    }
+    let mut values = Vec::new();
+    for variable in variables {
+        let value = vars
+            .get(&variable.name)
+            .or(variable.default.as_ref())
+            .ok_or_else(|| IdpError::Template(format!("'{}' needs a value for '{}'", template, variable.name)))?;
+        values.push((format!("{{{}}}", variable.name), value.clone()));
+    }
+    Ok(values)
+}
+
+// `days` days after `from`, for template `template`: never before it, and not past the end of time.
+fn days_after(template: &str, from: DateTime<Utc>, days: i64) -> Result<DateTime<Utc>, IdpError> {
+    if days < 0 {
+        return Err(IdpError::Template(format!("'{}' counts {} days, but cannot count back in time", template, days)));
+    }
+    Duration::try_days(days)
+        .and_then(|days| from.checked_add_signed(days))
+        .ok_or_else(|| IdpError::Template(format!("'{}' counts {} days, which is too far ahead", template, days)))
+}
+
+// `text` with every placeholder replaced by its value.
+fn fill(values: &[(String, String)], text: &str) -> String {
+    values.iter().fold(text.to_string(), |text, (placeholder, value)| text.replace(placeholder, value))
+}
+
+impl CredentialTemplate {
+    /// Reads a template as TOML or YAML, by the file's extension (YAML for anything else).
+    pub fn parse(text: &str, path: &Path) -> Result<CredentialTemplate, IdpError> {
+        let template: CredentialTemplate = read(text, path)?;
+        if template.claim.is_some() == template.claim_type.is_some() {
+            return Err(IdpError::Template(format!("'{}' must have either a claim or a type", template.name)));
+        }
+        template.expires_at(Utc::now())?;
+        Ok(template)
+    }
+
+    /// The template `name`: the issuer's own from `dir` (`<name>.toml`, `.yaml` or `.yml`), or else the built-in one.
+    pub fn load(name: &str, dir: Option<&Path>) -> Result<CredentialTemplate, IdpError> {
+        if let Some(path) = find(name, dir) {
+            return Self::parse(&std::fs::read_to_string(&path)?, &path);
+        }
+        match BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            Some((_, yaml)) => Self::parse(yaml, Path::new("built-in.yaml")),
+            None => Err(IdpError::Template(format!("there is no template '{}'", name))),
+        }
+    }
+
+    /// Every template available: the built-in ones, with those in `dir` added or overriding them.
+    pub fn all(dir: Option<&Path>) -> Result<Vec<CredentialTemplate>, IdpError> {
+        let mut templates = BTreeMap::new();
+        for (name, _) in BUILT_IN {
+            templates.insert(name.to_string(), Self::load(name, None)?);
+        }
+        for path in files(dir)? {
+            let template = Self::parse(&std::fs::read_to_string(&path)?, &path)?;
+            templates.insert(template.name.clone(), template);
+        }
+        Ok(templates.into_values().collect())
+    }
+
+    /// The claim for `vars`: every variable without a default must be given, and no others.
+    pub fn render(&self, vars: &BTreeMap<String, String>) -> Result<String, IdpError> {
+        // 1. Each variable's value, given or default.
+        let values = values(&self.name, &self.variables, vars)?;
+        let fill = |text: &str| fill(&values, text);
+
+        // 2. The claim text, or the typed claim checked against its schema.
+        match (&self.claim, &self.claim_type) {
+            (Some(claim), _) => Ok(fill(claim)),
+            (None, Some(claim_type)) => {
+                let schema = claims::schema(claim_type).ok_or_else(|| IdpError::Template(format!("'{}' has the unknown claim type '{}'", self.name, claim_type)))?;
+                let payload = self
+                    .fields
+                    .iter()
+                    .map(|(name, value)| (name.clone(), fill(value)))
+                    .filter(|(_, value)| !value.is_empty())
+                    .map(|(name, value)| (name, serde_json::from_str(&value).unwrap_or(Value::String(value))))
+                    .collect();
+                schema.to_claim(Value::Object(payload))
+            }
+            (None, None) => Err(IdpError::Template(format!("'{}' must have either a claim or a type", self.name))),
+        }
+    }
+
+    /// When a credential issued at `issued_at` from this template expires by default.
+    pub fn expires_at(&self, issued_at: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, IdpError> {
+        self.valid_for_days.map(|days| days_after(&self.name, issued_at, days)).transpose()
+    }
+}
+
+impl ContractTemplate {
+    /// Reads a contract template as TOML or YAML, by the file's extension (YAML for anything else).
+    pub fn parse(text: &str, path: &Path) -> Result<ContractTemplate, IdpError> {
+        read(text, path)
+    }
+
+    /// The contract template `name`: the user's own from `dir`, or else the built-in one.
+    pub fn load(name: &str, dir: Option<&Path>) -> Result<ContractTemplate, IdpError> {
+        if let Some(path) = find(name, dir) {
+            return Self::parse(&std::fs::read_to_string(&path)?, &path);
+        }
+        match CONTRACT_BUILT_IN.iter().find(|(built_in, _)| *built_in == name) {
+            Some((_, yaml)) => Self::parse(yaml, Path::new("built-in.yaml")),
+            None => Err(IdpError::Template(format!("there is no contract template '{}'", name))),
+        }
+    }
+
+    /// Every contract template available: the built-in ones, with those in `dir` added or overriding them.
+    pub fn all(dir: Option<&Path>) -> Result<Vec<ContractTemplate>, IdpError> {
+        let mut templates = BTreeMap::new();
+        for (name, _) in CONTRACT_BUILT_IN {
+            templates.insert(name.to_string(), Self::load(name, None)?);
+        }
+        for path in files(dir)? {
+            let template = Self::parse(&std::fs::read_to_string(&path)?, &path)?;
+            templates.insert(template.name.clone(), template);
+        }
+        Ok(templates.into_values().collect())
+    }
+
+    /// A draft contract `contract_id` for `vars`, created at `now`, ready for its parties to sign.
+    pub fn instantiate(&self, contract_id: &str, vars: &BTreeMap<String, String>, now: DateTime<Utc>) -> Result<Contract, IdpError> {
+        // 1. Each variable's value, and the days that count from now.
+        let values = values(&self.name, &self.variables, vars)?;
+        let fill = |text: &str| fill(&values, text);
+        let days = |text: &str| match fill(text).trim().parse::<i64>() {
+            Ok(days) => days_after(&self.name, now, days),
+            Err(_) => Err(IdpError::Template(format!("'{}' needs a whole number of days, not '{}'", self.name, fill(text)))),
+        };
+
+        // 2. The obligations, with their amounts where they have one.
+        let mut obligations = Vec::new();
+        for obligation in &self.obligations {
+            let amount = match obligation.amount.as_deref().map(fill).filter(|value| !value.is_empty()) {
+                Some(value) => {
+                    let currency = obligation.currency.as_deref().map(fill).filter(|currency| !currency.is_empty());
+                    let currency = currency.ok_or_else(|| IdpError::Template(format!("'{}' gives an amount without a currency", self.name)))?;
+                    Some(Amount { value, currency })
+                }
+                None => None,
+            };
+            obligations.push(Obligation {
+                party: fill(&obligation.party),
+                action: fill(&obligation.action),
+                due: obligation.due_days.as_deref().map(days).transpose()?,
+                amount,
+                unknown_fields: Default::default(),
+            });
+        }
+
+        // 3. The contract, which must be one its parties can sign.
+        let contract = Contract {
+            contract_id: contract_id.to_string(),
+            status: ContractStatus::Draft,
+            parties: self.parties.iter().map(|party| fill(party)).collect(),
+            terms: fill(&self.terms),
+            obligations,
+            consequence: Consequence { on_success: fill(&self.on_success), on_failure: fill(&self.on_failure), unknown_fields: Default::default() },
+            deadline: self.deadline_days.as_deref().map(days).transpose()?,
+            review_at: None,
+            signatures: vec![],
+            annotations: vec![],
+            unknown_fields: Default::default(),
+        };
+        if contract.parties.iter().any(String::is_empty) || contract.parties.iter().enumerate().any(|(i, party)| contract.parties[..i].contains(party)) {
+            return Err(IdpError::Template(format!("'{}' needs a different, non-empty id for each party", self.name)));
+        }
+        if let Some((_, problem)) = contract.obligation_problems().into_iter().next() {
+            return Err(IdpError::Template(format!("'{}': {}", self.name, problem)));
+        }
+        Ok(contract)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_renders_built_in_and_custom_templates() {
+        let vars = |pairs: &[(&str, &str)]| pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<BTreeMap<_, _>>();
+
+        // A built-in typed template fills its fields, leaving out the empty ones.
+        let employment = CredentialTemplate::load("employment", None).unwrap();
+        let claim = employment.render(&vars(&[("employer", "Acme")])).unwrap();
+        assert_eq!(claims::decode(&claim).unwrap().1, serde_json::json!({ "organization": "Acme", "role": "employee" }));
+        assert!(employment.render(&vars(&[])).is_err(), "employer is required");
+        assert!(employment.render(&vars(&[("employer", "Acme"), ("salary", "1")])).is_err(), "no such variable");
+        assert_eq!(employment.expires_at(DateTime::UNIX_EPOCH).unwrap(), Some(DateTime::UNIX_EPOCH + Duration::days(365)));
+
+        // An issuer's TOML template overrides the built-in one of the same name.
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("employment.toml"), "name = \"employment\"\nclaim = \"works at {employer}\"\nvariables = [{ name = \"employer\" }]\n").unwrap();
+        let custom = CredentialTemplate::load("employment", Some(dir.path())).unwrap();
+        assert_eq!(custom.render(&vars(&[("employer", "Acme")])).unwrap(), "works at Acme");
+        assert_eq!(custom.expires_at(Utc::now()).unwrap(), None);
+        assert_eq!(CredentialTemplate::all(Some(dir.path())).unwrap().len(), BUILT_IN.len());
+
+        // One valid for longer than time goes on is refused when it is read.
+        std::fs::write(dir.path().join("forever.yaml"), "name: forever\nclaim: member\nvalid_for_days: 9999999999999\n").unwrap();
+        assert!(matches!(CredentialTemplate::load("forever", Some(dir.path())), Err(IdpError::Template(_))));
+        assert!(CredentialTemplate::load("unknown", Some(dir.path())).is_err());
+    }
+
+    #[test]
+    fn it_instantiates_contract_templates() {
+        let vars = |pairs: &[(&str, &str)]| pairs.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<BTreeMap<_, _>>();
+        let now = Utc::now();
+
+        // The built-in loan, with its defaults: both obligations carry the amount.
+        let loan = ContractTemplate::load("loan", None).unwrap();
+        let contract = loan.instantiate("loan-1", &vars(&[("lender", "idp:alice"), ("borrower", "idp:bob"), ("amount", "500")]), now).unwrap();
+        assert_eq!((contract.parties.as_slice(), contract.status.clone()), (["idp:alice".to_string(), "idp:bob".to_string()].as_slice(), ContractStatus::Draft));
+        assert_eq!(contract.obligations[1].amount.as_ref().unwrap().to_string(), "500 EUR");
+        assert_eq!((contract.deadline, contract.obligations[1].due), (Some(now + Duration::days(90)), Some(now + Duration::days(90))));
+        assert!(contract.obligation_problems().is_empty());
+
+        // Not with the same party twice, a bad number of days, or a missing variable.
+        assert!(loan.instantiate("loan-2", &vars(&[("lender", "idp:alice"), ("borrower", "idp:alice"), ("amount", "500")]), now).is_err());
+        for days in ["soon", "-1", "9999999999999"] {
+            assert!(matches!(loan.instantiate("loan-3", &vars(&[("lender", "idp:alice"), ("borrower", "idp:bob"), ("amount", "500"), ("days", days)]), now), Err(IdpError::Template(_))), "{}", days);
+        }
+        assert!(loan.instantiate("loan-4", &vars(&[("lender", "idp:alice")]), now).is_err());
+        assert_eq!(ContractTemplate::all(None).unwrap().len(), CONTRACT_BUILT_IN.len());
+    }
+}
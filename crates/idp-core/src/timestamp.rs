@@ -0,0 +1,79 @@
+// crates/idp-core/src/timestamp.rs
+
+// Reading the timestamps of credentials, consents and reputation events. Documents written while
+// these were free-form strings still load, as long as they hold an RFC 3339 time, a plain
+// `YYYY-MM-DD[ HH:MM:SS]` in UTC or Unix seconds; they are written back as RFC 3339.
+
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{de, Deserialize, Deserializer};
+
+/// Parses a timestamp in any of the forms older documents used.
+pub fn parse(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Some(time.with_timezone(&Utc));
+    }
+    if let Ok(time) = NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M:%S") {
+        return Some(time.and_utc());
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(text, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc());
+    }
+    text.parse::<i64>().ok().and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+}
+
+// What a timestamp field may hold in an older document.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Written {
+    Seconds(i64),
+    Text(String),
+}
+
+impl Written {
+    fn into_time<E: de::Error>(self) -> Result<DateTime<Utc>, E> {
+        match self {
+            Written::Seconds(seconds) => {
+                DateTime::from_timestamp(seconds, 0).ok_or_else(|| E::custom(format!("timestamp {} is out of range", seconds)))
+            }
+            Written::Text(text) => parse(&text).ok_or_else(|| {
+                E::custom(format!("invalid timestamp '{}': expected RFC 3339, e.g. 2024-07-06T10:00:00Z", text))
+            }),
+        }
+    }
+}
+
+/// For `#[serde(deserialize_with)]` on a `DateTime<Utc>` field.
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+    Written::deserialize(deserializer)?.into_time()
+}
+
+/// For `#[serde(default, deserialize_with)]` on an `Option<DateTime<Utc>>` field.
+pub(crate) fn deserialize_option<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+    Option::<Written>::deserialize(deserializer)?.map(Written::into_time).transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Credential;
+
+    #[test]
+    fn it_reads_the_timestamps_older_documents_wrote() {
+        let expected = "2024-07-06T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        for written in ["2024-07-06T00:00:00Z", "2024-07-06T02:00:00+02:00", "2024-07-06 00:00:00", "2024-07-06", "1720224000"] {
+            assert_eq!(parse(written), Some(expected), "{}", written);
+        }
+        assert_eq!(parse("next tuesday"), None);
+
+        let yaml = "claim: c\nissued_by: i\nissued_at: 2024-07-06\nexpires_at: 1720224000\nproof: p\n";
+        let credential: Credential = serde_yaml::from_str(yaml).unwrap();
+        assert_eq!((credential.issued_at, credential.expires_at), (expected, Some(expected)));
+        assert!(serde_yaml::to_string(&credential).unwrap().contains("issued_at: 2024-07-06T00:00:00Z"));
+        assert!(!credential.is_expired(expected - chrono::Duration::seconds(1)));
+        assert!(credential.is_expired(expected));
+
+        let error = serde_yaml::from_str::<Credential>(&yaml.replace("2024-07-06", "soon")).unwrap_err();
+        assert!(error.to_string().contains("invalid timestamp 'soon'"));
+    }
+}
@@ -0,0 +1,192 @@
+// crates/idp-core/src/trust.rs
+
+// Trust chains. A verifier rarely knows every issuer, but it may trust a few roots: if Alice's
+// credential is signed by Bob, and Bob holds a "notary" credential signed by a root, the chain
+// Alice <- Bob <- root holds. `TrustChain` walks issuers' credentials from a holder's credential
+// to one of its trust anchors, loading intermediate identities through a resolver.
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use crate::{Credential, Identity, IdpError};
+
+/// The longest chain followed when no other limit is set.
+pub const DEFAULT_MAX_DEPTH: usize = 8;
+
+/// Finds identity documents by id, for the issuers along a chain.
+pub trait IdentityResolver {
+    /// The identity with this id, or `None` if it is not known.
+    fn resolve(&self, id: &str) -> Result<Option<Identity>, IdpError>;
+}
+
+impl<F: Fn(&str) -> Result<Option<Identity>, IdpError>> IdentityResolver for F {
+    fn resolve(&self, id: &str) -> Result<Option<Identity>, IdpError> {
+        self(id)
+    }
+}
+
+impl IdentityResolver for Vec<Identity> {
+    fn resolve(&self, id: &str) -> Result<Option<Identity>, IdpError> {
+        Ok(self.iter().find(|identity| identity.identity.id.as_str() == id).cloned())
+    }
+}
+
+/// Resolves identities from the documents in a directory (`*.idp`, `*.yaml`, `*.json`).
+#[derive(Debug, Clone)]
+pub struct DirectoryResolver {
+    dir: PathBuf,
+}
+
+impl DirectoryResolver {
+    pub fn new(dir: impl Into<PathBuf>) -> DirectoryResolver {
+        DirectoryResolver { dir: dir.into() }
+    }
+}
+
+impl IdentityResolver for DirectoryResolver {
+    fn resolve(&self, id: &str) -> Result<Option<Identity>, IdpError> {
+        for entry in std::fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            let document = path.extension().and_then(|extension| extension.to_str()).is_some_and(|extension| ["idp", "yaml", "yml", "json"].contains(&extension));
+            // Files that are not plain identity documents are not what is looked for.
+            if let Some(identity) = document.then(|| Identity::load_from_file(&path).ok()).flatten()
+                && identity.identity.id.as_str() == id
+            {
+                return Ok(Some(identity));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// One step of a chain: `issuer` vouched for `subject` with `claim`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainLink {
+    pub subject: String,
+    pub issuer: String,
+    pub claim: String,
+    pub proof: String,
+}
+
+/// Validates credentials by the path from their issuer to a trust anchor.
+pub struct TrustChain<'a> {
+    resolver: &'a dyn IdentityResolver,
+    anchors: Vec<String>,
+    required_claim: Option<String>,
+    max_depth: usize,
+}
+
+impl<'a> TrustChain<'a> {
+    pub fn new(resolver: &'a dyn IdentityResolver) -> TrustChain<'a> {
+        TrustChain { resolver, anchors: Vec::new(), required_claim: None, max_depth: DEFAULT_MAX_DEPTH }
+    }
+
+    /// Trusts the identity with this id as a root.
+    pub fn anchor(mut self, id: &str) -> Self {
+        self.anchors.push(id.to_string());
+        self
+    }
+
+    /// Only follows intermediate credentials with this claim (or claim type URI), e.g. `notary`.
+    pub fn require_claim(mut self, claim: &str) -> Self {
+        self.required_claim = Some(claim.to_string());
+        self
+    }
+
+    /// The most links a chain may have.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// The chain from `credential`, held by `holder`, to a trust anchor, starting with the
+    /// credential itself. Every link must verify against its issuer's keys and be current.
+    pub fn validate(&self, holder: &Identity, credential: &Credential) -> Result<Vec<ChainLink>, IdpError> {
+        if self.anchors.is_empty() {
+            return Err(IdpError::Trust("no trust anchor is configured".to_string()));
+        }
+        let mut visited = HashSet::from([holder.identity.id.to_string()]);
+        let mut failures = Vec::new();
+        match self.walk(holder, credential, &mut visited, &mut failures)? {
+            Some(chain) => Ok(chain),
+            None => Err(IdpError::Trust(format!("no chain from {} reaches a trust anchor ({})", credential.issued_by, failures.join("; ")))),
+        }
+    }
+
+    // Follows one credential up; `None` if no path from its issuer reaches an anchor.
+    fn walk(&self, subject: &Identity, credential: &Credential, visited: &mut HashSet<String>, failures: &mut Vec<String>) -> Result<Option<Vec<ChainLink>>, IdpError> {
+        // 1. The credential must verify against its issuer, which must be known.
+        let Some(issuer) = self.resolver.resolve(&credential.issued_by)? else {
+            failures.push(format!("the identity of {} could not be found", credential.issued_by));
+            return Ok(None);
+        };
+        let problems = match subject.credential_proof(credential) {
+            Some(proof) => subject.verify_credential(credential, proof, &issuer).problems(),
+            None => vec![format!("its proof '{}' is missing", credential.proof)],
+        };
+        if !problems.is_empty() {
+            failures.push(format!("'{}' held by {}: {}", credential.claim, subject.identity.id, problems.join(", ")));
+            return Ok(None);
+        }
+        let link = ChainLink {
+            subject: subject.identity.id.to_string(),
+            issuer: credential.issued_by.clone(),
+            claim: credential.claim.clone(),
+            proof: credential.proof.clone(),
+        };
+
+        // 2. An anchor ends the chain; otherwise the issuer's own credentials lead on.
+        if self.anchors.contains(&credential.issued_by) {
+            return Ok(Some(vec![link]));
+        }
+        if visited.len() >= self.max_depth {
+            failures.push(format!("the chain is longer than {} links", self.max_depth));
+            return Ok(None);
+        }
+        if !visited.insert(credential.issued_by.clone()) {
+            return Ok(None);
+        }
+        let candidates = issuer.credentials.iter().filter(|candidate| match &self.required_claim {
+            Some(required) => candidate.claim == *required || candidate.claim_type().as_deref() == Some(required.as_str()),
+            None => true,
+        });
+        for candidate in candidates {
+            if let Some(mut chain) = self.walk(&issuer, candidate, visited, failures)? {
+                chain.insert(0, link);
+                return Ok(Some(chain));
+            }
+        }
+        visited.remove(&credential.issued_by);
+        failures.push(format!("{} holds no credential leading to an anchor", credential.issued_by));
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_walks_from_a_credential_to_a_trust_anchor() {
+        let (root, root_key) = Identity::new("Root", "Appoints notaries.").unwrap();
+        let (mut bob, bob_key) = Identity::new("Bob", "A notary.").unwrap();
+        let (mut alice, _) = Identity::new("Alice", "Notarized.").unwrap();
+        bob.add_credential(root.issue_credential(&bob, "notary", None, &root_key).unwrap()).unwrap();
+        alice.add_credential(bob.issue_credential(&alice, "deed of sale", None, &bob_key).unwrap()).unwrap();
+        let known = vec![root.clone(), bob.clone()];
+        let credential = &alice.credentials[0];
+
+        // Alice <- Bob <- Root, with Bob's link a notary credential.
+        let chain = TrustChain::new(&known).anchor(root.identity.id.as_str()).require_claim("notary").validate(&alice, credential).unwrap();
+        let issuers: Vec<&str> = chain.iter().map(|link| link.issuer.as_str()).collect();
+        assert_eq!(issuers, [bob.identity.id.as_str(), root.identity.id.as_str()]);
+        assert_eq!(chain[1].claim, "notary");
+
+        // Not for another anchor, another required claim, a shorter limit, or an unknown issuer.
+        let (stranger, _) = Identity::new("Stranger", "Trusted by no one.").unwrap();
+        assert!(matches!(TrustChain::new(&known).anchor(stranger.identity.id.as_str()).validate(&alice, credential), Err(IdpError::Trust(_))));
+        assert!(TrustChain::new(&known).anchor(root.identity.id.as_str()).require_claim("judge").validate(&alice, credential).is_err());
+        assert!(TrustChain::new(&known).anchor(root.identity.id.as_str()).max_depth(1).validate(&alice, credential).is_err());
+        assert!(TrustChain::new(&vec![root.clone()]).anchor(root.identity.id.as_str()).validate(&alice, credential).is_err());
+    }
+}
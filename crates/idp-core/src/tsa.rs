@@ -0,0 +1,400 @@
+// crates/idp-core/src/tsa.rs
+
+// Trusted timestamps (RFC 3161). A time-stamping authority (TSA) signs the hash of a proof's
+// signature together with the time it saw it, so the signature provably existed by then. The
+// token is kept as one more component of the proof's signature. Only the DER needed to write
+// requests, read responses and check the TSA's CMS signature is handled here.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use data_encoding::{BASE64, HEXLOWER};
+use ring::{digest, signature};
+
+use crate::{Identity, IdpError, Proof, SignatureComponent};
+
+/// The `algorithm` of the signature component that holds a timestamp token.
+pub const RFC3161: &str = "rfc3161";
+
+// DER tags.
+const BOOLEAN: u8 = 0x01;
+const INTEGER: u8 = 0x02;
+const BIT_STRING: u8 = 0x03;
+const OCTET_STRING: u8 = 0x04;
+const NULL: u8 = 0x05;
+const OID: u8 = 0x06;
+const GENERALIZED_TIME: u8 = 0x18;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+const CONTEXT_0: u8 = 0xa0;
+
+// Object identifiers, as their encoded content.
+const SHA256: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+const SHA384: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x02];
+const SHA512: &[u8] = &[0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x03];
+const SIGNED_DATA: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x07, 0x02];
+const TST_INFO: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x10, 0x01, 0x04];
+const MESSAGE_DIGEST: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x09, 0x04];
+const RSA_ENCRYPTION: &[u8] = &[0x2a, 0x86, 0x48, 0x86, 0xf7, 0x0d, 0x01, 0x01, 0x01];
+const EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+const PRIME256V1: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+const SECP384R1: &[u8] = &[0x2b, 0x81, 0x04, 0x00, 0x22];
+const COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+
+/// What a verified token says.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Timestamp {
+    /// When the TSA saw the hash.
+    pub time: DateTime<Utc>,
+    /// The token's serial number, in hex.
+    pub serial: String,
+    /// The common name of the TSA's certificate, or its fingerprint if it has none.
+    pub tsa: String,
+    /// The SHA-256 fingerprint of the TSA's certificate, for pinning the TSAs one trusts.
+    pub certificate: String,
+}
+
+/// The bytes a proof's timestamp covers: its first signature component's value, as written.
+pub fn stamped_data(proof: &Proof) -> Result<Vec<u8>, IdpError> {
+    let signature = proof.signature.first().ok_or_else(|| IdpError::Timestamp(format!("the proof '{}' has no signature to timestamp", proof.proof_id)))?;
+    Ok(signature.value.as_bytes().to_vec())
+}
+
+/// A `TimeStampReq` for `data`'s SHA-256 hash, asking for the TSA's certificate in the token.
+pub fn request(data: &[u8], nonce: u64) -> Vec<u8> {
+    let imprint = digest::digest(&digest::SHA256, data);
+    der(SEQUENCE, &[
+        &der(INTEGER, &[&[1]]),
+        &der(SEQUENCE, &[&der(SEQUENCE, &[&der(OID, &[SHA256]), &der(NULL, &[])]), &der(OCTET_STRING, &[imprint.as_ref()])]),
+        &der(INTEGER, &[&integer(nonce)]),
+        &der(BOOLEAN, &[&[0xff]]),
+    ])
+}
+
+/// Takes the token out of a `TimeStampResp` to a `request(data, nonce)`, once it checks out.
+pub fn accept_response(response: &[u8], data: &[u8], nonce: u64) -> Result<Vec<u8>, IdpError> {
+    // 1. The TSA must have granted the request (status 0, or 1 "with modifications").
+    let parts = children(read(response)?.0.content)?;
+    let status = parts.first().map(|info| children(info.content)).transpose()?.and_then(|info| info.first().map(|status| status.content.to_vec()));
+    if !matches!(status.as_deref(), Some([0] | [1])) {
+        return Err(IdpError::Timestamp(format!("the TSA refused the request (status {})", status.map(|status| HEXLOWER.encode(&status)).unwrap_or_default())));
+    }
+    let token = parts.get(1).ok_or_else(|| IdpError::Timestamp("the TSA's response holds no token".to_string()))?.raw.to_vec();
+
+    // 2. And answered this request, not a replayed one.
+    let (_, token_nonce) = check(&token, data)?;
+    if token_nonce != Some(integer(nonce)) {
+        return Err(IdpError::Timestamp("the token's nonce is not the request's".to_string()));
+    }
+    Ok(token)
+}
+
+/// Checks a `TimeStampToken` over `data`: the hash it records, and the TSA's signature with the
+/// certificate the token carries. Whether to trust that TSA is up to the verifier.
+pub fn verify(token: &[u8], data: &[u8]) -> Result<Timestamp, IdpError> {
+    Ok(check(token, data)?.0)
+}
+
+/// Asks the TSA at `url` for a timestamp over `data`, over HTTP(S) with the `tsa` feature.
+#[cfg(feature = "tsa")]
+pub fn request_timestamp(url: &str, data: &[u8]) -> Result<Vec<u8>, IdpError> {
+    let nonce: u64 = rand::random();
+    let response = ureq::post(url)
+        .set("Content-Type", "application/timestamp-query")
+        .send_bytes(&request(data, nonce))
+        .map_err(|e| IdpError::Timestamp(format!("could not reach the TSA at '{}': {}", url, e)))?;
+    let mut body = Vec::new();
+    std::io::Read::read_to_end(&mut response.into_reader(), &mut body)?;
+    accept_response(&body, data, nonce)
+}
+
+#[cfg(not(feature = "tsa"))]
+pub fn request_timestamp(url: &str, _data: &[u8]) -> Result<Vec<u8>, IdpError> {
+    Err(IdpError::Timestamp(format!("this build cannot reach a TSA ('{}'); rebuild with the `tsa` feature", url)))
+}
+
+/// Checks the timestamp a proof carries, if it has one.
+pub fn proof_timestamp(proof: &Proof) -> Option<Result<Timestamp, IdpError>> {
+    let component = proof.signature.iter().find(|component| component.algorithm == RFC3161)?;
+    Some(BASE64.decode(component.value.as_bytes()).map_err(|_| IdpError::Timestamp("the token is not valid Base64".to_string())).and_then(|token| verify(&token, &stamped_data(proof)?)))
+}
+
+/// Adds `token` to `proof` once it checks out, replacing any timestamp the proof had.
+pub fn add_timestamp(proof: &mut Proof, token: &[u8]) -> Result<Timestamp, IdpError> {
+    let timestamp = verify(token, &stamped_data(proof)?)?;
+    proof.signature.retain(|component| component.algorithm != RFC3161);
+    proof.signature.push(SignatureComponent { algorithm: RFC3161.to_string(), value: BASE64.encode(token) });
+    Ok(timestamp)
+}
+
+impl Identity {
+    /// Adds a timestamp token to the proof `proof_id` of this document.
+    pub fn timestamp_proof(&mut self, proof_id: &str, token: &[u8]) -> Result<Timestamp, IdpError> {
+        let proof = self.proofs.iter_mut().find(|proof| proof.proof_id == proof_id).ok_or_else(|| IdpError::Timestamp(format!("no proof '{}' in the document", proof_id)))?;
+        let timestamp = add_timestamp(proof, token)?;
+        self.touch();
+        Ok(timestamp)
+    }
+}
+
+// Checks a token over `data`, returning what it says and its nonce, as DER integer content.
+fn check(token: &[u8], data: &[u8]) -> Result<(Timestamp, Option<Vec<u8>>), IdpError> {
+    let malformed = |what: &str| IdpError::Timestamp(format!("the token's {} is malformed", what));
+
+    // 1. ContentInfo { signedData, [0] SignedData }, and the TSTInfo it encapsulates.
+    let content_info = children(read(token)?.0.content)?;
+    if content_info.first().map(|oid| oid.content) != Some(SIGNED_DATA) {
+        return Err(IdpError::Timestamp("the token is not CMS signed data".to_string()));
+    }
+    let signed_data = content_info.get(1).map(|wrapper| read(wrapper.content)).transpose()?.ok_or_else(|| malformed("signed data"))?.0;
+    let signed_data = children(signed_data.content)?;
+    let encapsulated = children(signed_data.get(2).ok_or_else(|| malformed("content"))?.content)?;
+    if encapsulated.first().map(|oid| oid.content) != Some(TST_INFO) {
+        return Err(IdpError::Timestamp("the token does not hold a TSTInfo".to_string()));
+    }
+    let octets = encapsulated.get(1).map(|wrapper| read(wrapper.content)).transpose()?.ok_or_else(|| malformed("TSTInfo"))?.0;
+    let tst_info_der = octets.content;
+    let tst_info = children(read(tst_info_der)?.0.content)?;
+
+    // 2. It must timestamp the hash of `data`.
+    let [_, _, imprint, serial, time, rest @ ..] = &tst_info[..] else {
+        return Err(malformed("TSTInfo"));
+    };
+    let imprint = children(imprint.content)?;
+    let (algorithm, hashed) = match &imprint[..] {
+        [algorithm, hashed] => (digest_algorithm(algorithm)?, hashed.content),
+        _ => return Err(malformed("message imprint")),
+    };
+    if digest::digest(algorithm, data).as_ref() != hashed {
+        return Err(IdpError::Timestamp("the token timestamps other data".to_string()));
+    }
+    if time.tag != GENERALIZED_TIME {
+        return Err(malformed("time"));
+    }
+    let text = std::str::from_utf8(time.content).map_err(|_| malformed("time"))?;
+    let seconds = text.trim_end_matches('Z').split('.').next().unwrap_or_default();
+    let time = NaiveDateTime::parse_from_str(seconds, "%Y%m%d%H%M%S").map_err(|_| malformed("time"))?.and_utc();
+    let nonce = rest.iter().find(|field| field.tag == INTEGER).map(|nonce| nonce.content.to_vec());
+
+    // 3. SignerInfo: the signed attributes must hash the TSTInfo, and a certificate in the token
+    //    must verify the signature over them.
+    let signer_infos = signed_data.last().filter(|set| set.tag == SET).ok_or_else(|| malformed("signer infos"))?;
+    let signer_info = children(children(signer_infos.content)?.first().ok_or_else(|| malformed("signer info"))?.content)?;
+    let digest_algorithm = digest_algorithm(signer_info.get(2).ok_or_else(|| malformed("signer info"))?)?;
+    let (signed, signature) = match &signer_info[3..] {
+        [attributes, _, signature, ..] if attributes.tag == CONTEXT_0 => {
+            let expected = digest::digest(digest_algorithm, tst_info_der);
+            let message_digest = children(attributes.content)?
+                .into_iter()
+                .filter_map(|attribute| children(attribute.content).ok())
+                .find(|attribute| attribute.first().map(|oid| oid.content) == Some(MESSAGE_DIGEST))
+                .and_then(|attribute| attribute.get(1).and_then(|values| read(values.content).ok()).map(|(value, _)| value.content.to_vec()));
+            if message_digest.as_deref() != Some(expected.as_ref()) {
+                return Err(IdpError::Timestamp("the TSA's signed attributes do not match the TSTInfo".to_string()));
+            }
+            // Signed as a SET, though stored with an implicit [0] tag.
+            let mut signed = attributes.raw.to_vec();
+            signed[0] = SET;
+            (signed, signature.content)
+        }
+        [_, signature, ..] => (tst_info_der.to_vec(), signature.content),
+        _ => return Err(malformed("signer info")),
+    };
+    let certificates = match signed_data.get(3) {
+        Some(certificates) if certificates.tag == CONTEXT_0 => children(certificates.content)?,
+        _ => Vec::new(),
+    };
+    let certificate = certificates
+        .iter()
+        .find(|certificate| verifies(certificate, digest_algorithm, &signed, signature).unwrap_or(false))
+        .ok_or_else(|| IdpError::Timestamp("no certificate in the token verifies the TSA's signature".to_string()))?;
+
+    let fingerprint = HEXLOWER.encode(digest::digest(&digest::SHA256, certificate.raw).as_ref());
+    let timestamp = Timestamp {
+        time,
+        serial: HEXLOWER.encode(serial.content),
+        tsa: common_name(certificate).unwrap_or_else(|| fingerprint.clone()),
+        certificate: fingerprint,
+    };
+    Ok((timestamp, nonce))
+}
+
+// The hash an AlgorithmIdentifier names.
+fn digest_algorithm(identifier: &Tlv) -> Result<&'static digest::Algorithm, IdpError> {
+    match children(identifier.content)?.first().map(|oid| oid.content) {
+        Some(SHA256) => Ok(&digest::SHA256),
+        Some(SHA384) => Ok(&digest::SHA384),
+        Some(SHA512) => Ok(&digest::SHA512),
+        _ => Err(IdpError::Timestamp("the token uses a hash other than SHA-2".to_string())),
+    }
+}
+
+// Whether the certificate's key verifies `signature` over `signed`, for the keys ring can use.
+fn verifies(certificate: &Tlv, hash: &'static digest::Algorithm, signed: &[u8], signature: &[u8]) -> Result<bool, IdpError> {
+    let tbs = children(children(certificate.content)?.first().ok_or_else(|| IdpError::Timestamp("empty certificate".to_string()))?.content)?;
+    let offset = if tbs.first().is_some_and(|version| version.tag == CONTEXT_0) { 1 } else { 0 };
+    let key_info = children(tbs.get(offset + 5).ok_or_else(|| IdpError::Timestamp("certificate without a key".to_string()))?.content)?;
+    let [algorithm, key] = &key_info[..] else {
+        return Ok(false);
+    };
+    let algorithm = children(algorithm.content)?;
+    let key = key.content.get(1..).filter(|_| key.tag == BIT_STRING).unwrap_or_default();
+    let (sha256, sha384) = (hash == &digest::SHA256, hash == &digest::SHA384);
+    let verifier: &dyn signature::VerificationAlgorithm = match (algorithm.first().map(|oid| oid.content), algorithm.get(1).map(|curve| curve.content)) {
+        (Some(RSA_ENCRYPTION), _) if sha256 => &signature::RSA_PKCS1_2048_8192_SHA256,
+        (Some(RSA_ENCRYPTION), _) if sha384 => &signature::RSA_PKCS1_2048_8192_SHA384,
+        (Some(RSA_ENCRYPTION), _) => &signature::RSA_PKCS1_2048_8192_SHA512,
+        (Some(EC_PUBLIC_KEY), Some(PRIME256V1)) if sha256 => &signature::ECDSA_P256_SHA256_ASN1,
+        (Some(EC_PUBLIC_KEY), Some(PRIME256V1)) if sha384 => &signature::ECDSA_P256_SHA384_ASN1,
+        (Some(EC_PUBLIC_KEY), Some(SECP384R1)) if sha256 => &signature::ECDSA_P384_SHA256_ASN1,
+        (Some(EC_PUBLIC_KEY), Some(SECP384R1)) if sha384 => &signature::ECDSA_P384_SHA384_ASN1,
+        _ => return Ok(false),
+    };
+    Ok(signature::UnparsedPublicKey::new(verifier, key).verify(signed, signature).is_ok())
+}
+
+// The CN of a certificate's subject.
+fn common_name(certificate: &Tlv) -> Option<String> {
+    let tbs = children(children(certificate.content).ok()?.first()?.content).ok()?;
+    let offset = if tbs.first()?.tag == CONTEXT_0 { 1 } else { 0 };
+    let subject = children(tbs.get(offset + 4)?.content).ok()?;
+    subject.iter().filter_map(|set| children(set.content).ok()).flatten().find_map(|attribute| {
+        let attribute = children(attribute.content).ok()?;
+        let value = attribute.get(1).filter(|_| attribute[0].content == COMMON_NAME)?;
+        Some(String::from_utf8_lossy(value.content).into_owned())
+    })
+}
+
+// One DER element: its tag, its content, and all of its bytes.
+#[derive(Debug, Clone, Copy)]
+struct Tlv<'a> {
+    tag: u8,
+    content: &'a [u8],
+    raw: &'a [u8],
+}
+
+// Reads the element at the start of `input`, and what follows it.
+fn read(input: &[u8]) -> Result<(Tlv<'_>, &[u8]), IdpError> {
+    let malformed = || IdpError::Timestamp("malformed DER".to_string());
+    let [tag, first, rest @ ..] = input else {
+        return Err(malformed());
+    };
+    let (length, rest) = match *first {
+        length @ 0..=0x7f => (length as usize, rest),
+        count @ 0x81..=0x84 => {
+            let count = (count & 0x7f) as usize;
+            let bytes = rest.get(..count).ok_or_else(malformed)?;
+            (bytes.iter().fold(0usize, |length, byte| length << 8 | *byte as usize), &rest[count..])
+        }
+        _ => return Err(malformed()),
+    };
+    let content = rest.get(..length).ok_or_else(malformed)?;
+    let header = input.len() - rest.len();
+    Ok((Tlv { tag: *tag, content, raw: &input[..header + length] }, &rest[length..]))
+}
+
+// The elements a constructed element's content holds.
+fn children(mut content: &[u8]) -> Result<Vec<Tlv<'_>>, IdpError> {
+    let mut elements = Vec::new();
+    while !content.is_empty() {
+        let (element, rest) = read(content)?;
+        elements.push(element);
+        content = rest;
+    }
+    Ok(elements)
+}
+
+// Writes an element whose content is `parts`, one after another.
+fn der(tag: u8, parts: &[&[u8]]) -> Vec<u8> {
+    let content = parts.concat();
+    let mut element = vec![tag];
+    match content.len() {
+        length @ 0..=0x7f => element.push(length as u8),
+        length => {
+            let bytes: Vec<u8> = length.to_be_bytes().into_iter().skip_while(|byte| *byte == 0).collect();
+            element.push(0x80 | bytes.len() as u8);
+            element.extend(bytes);
+        }
+    }
+    element.extend(content);
+    element
+}
+
+// The content of a non-negative INTEGER: minimal, with a zero byte if the top bit is set.
+fn integer(value: u64) -> Vec<u8> {
+    let mut bytes: Vec<u8> = value.to_be_bytes().into_iter().skip_while(|byte| *byte == 0).collect();
+    if bytes.first().is_none_or(|byte| byte & 0x80 != 0) {
+        bytes.insert(0, 0);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use p256::ecdsa::signature::Signer;
+    use p256::ecdsa::{Signature, SigningKey};
+
+    const ECDSA_WITH_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+
+    // A TSA with a P-256 key, answering as RFC 3161 says.
+    fn respond(request: &[u8], time: &str) -> Vec<u8> {
+        let key = SigningKey::from_bytes(&[7u8; 32].into()).unwrap();
+        let request = children(read(request).unwrap().0.content).unwrap();
+        let (imprint, nonce) = (request[1].raw, request[2].raw);
+        let name = der(SEQUENCE, &[&der(SET, &[&der(SEQUENCE, &[&der(OID, &[COMMON_NAME]), &der(0x0c, &[b"Test TSA"])])])]);
+        let point = key.verifying_key().to_encoded_point(false);
+        let algorithm = der(SEQUENCE, &[&der(OID, &[ECDSA_WITH_SHA256])]);
+        let tbs = der(SEQUENCE, &[
+            &der(INTEGER, &[&[5]]),
+            &algorithm,
+            &name,
+            &der(SEQUENCE, &[&der(0x17, &[b"260101000000Z"]), &der(0x17, &[b"360101000000Z"])]),
+            &name,
+            &der(SEQUENCE, &[&der(SEQUENCE, &[&der(OID, &[EC_PUBLIC_KEY]), &der(OID, &[PRIME256V1])]), &der(BIT_STRING, &[&[0], point.as_bytes()])]),
+        ]);
+        let certificate = der(SEQUENCE, &[&tbs, &algorithm, &der(BIT_STRING, &[&[0]])]);
+
+        let tst_info = der(SEQUENCE, &[&der(INTEGER, &[&[1]]), &der(OID, &[&[0x2a, 0x03]]), imprint, &der(INTEGER, &[&[0x2a]]), &der(GENERALIZED_TIME, &[time.as_bytes()]), nonce]);
+        let message_digest = digest::digest(&digest::SHA256, &tst_info);
+        let attributes = [der(SEQUENCE, &[&der(OID, &[MESSAGE_DIGEST]), &der(SET, &[&der(OCTET_STRING, &[message_digest.as_ref()])])])].concat();
+        let signature: Signature = key.sign(&der(SET, &[&attributes]));
+        let sha256 = der(SEQUENCE, &[&der(OID, &[SHA256]), &der(NULL, &[])]);
+        let signer_info = der(SEQUENCE, &[
+            &der(INTEGER, &[&[1]]),
+            &der(SEQUENCE, &[&name, &der(INTEGER, &[&[5]])]),
+            &sha256,
+            &der(CONTEXT_0, &[&attributes]),
+            &algorithm,
+            &der(OCTET_STRING, &[signature.to_der().as_bytes()]),
+        ]);
+        let signed_data = der(SEQUENCE, &[
+            &der(INTEGER, &[&[3]]),
+            &der(SET, &[&sha256]),
+            &der(SEQUENCE, &[&der(OID, &[TST_INFO]), &der(CONTEXT_0, &[&der(OCTET_STRING, &[&tst_info])])]),
+            &der(CONTEXT_0, &[&certificate]),
+            &der(SET, &[&signer_info]),
+        ]);
+        let token = der(SEQUENCE, &[&der(OID, &[SIGNED_DATA]), &der(CONTEXT_0, &[&signed_data])]);
+        der(SEQUENCE, &[&der(SEQUENCE, &[&der(INTEGER, &[&[0]])]), &token])
+    }
+
+    #[test]
+    fn it_timestamps_a_proofs_signature() {
+        let (issuer, issuer_key) = Identity::new("Issuer", "Signs in time.").unwrap();
+        let (subject, _) = Identity::new("Subject", "Holds it.").unwrap();
+        let mut proof = issuer.issue_credential(&subject, "member", None, &issuer_key).unwrap().proof;
+        let data = stamped_data(&proof).unwrap();
+
+        // The token answers the request, names its TSA and time, and travels with the proof.
+        let nonce = 0x8000_0000_0000_0001;
+        let token = accept_response(&respond(&request(&data, nonce), "20261016120000.5Z"), &data, nonce).unwrap();
+        let timestamp = add_timestamp(&mut proof, &token).unwrap();
+        assert_eq!((timestamp.tsa.as_str(), timestamp.time.to_rfc3339()), ("Test TSA", "2026-10-16T12:00:00+00:00".to_string()));
+        assert_eq!(proof_timestamp(&proof).unwrap().unwrap(), timestamp);
+
+        // Not for other data, another request, or once the proof's signature changes.
+        assert!(matches!(verify(&token, b"other"), Err(IdpError::Timestamp(_))));
+        assert!(accept_response(&respond(&request(&data, 1), "20261016120000Z"), &data, 2).is_err());
+        proof.signature[0].value = BASE64.encode(b"forged");
+        assert!(proof_timestamp(&proof).unwrap().is_err());
+    }
+}
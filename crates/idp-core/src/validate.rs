@@ -0,0 +1,169 @@
+// crates/idp-core/src/validate.rs
+
+// Semantic checks: what the schema cannot see because it needs more than one value at a time,
+// like duplicate key ids, proofs by keys the document does not list or timestamps out of order.
+
+use std::collections::HashSet;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::keys::EffectiveStatus;
+use crate::{Identity, Signer};
+
+/// How much a finding matters.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The document contradicts itself; tools cannot trust it.
+    Error,
+    /// The document is consistent but something in it no longer holds, e.g. an expired credential.
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// One problem found by `Identity::validate`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Finding {
+    pub severity: Severity,
+    /// Where in the document, as a dot-path (e.g. `proofs.0.signed_by.key_id`).
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}: {}", self.severity, self.path, self.message)
+    }
+}
+
+impl Identity {
+    /// Runs the semantic checks as of now. An empty list means the document is consistent.
+    pub fn validate(&self) -> Vec<Finding> {
+        self.validate_at(Utc::now())
+    }
+
+    /// Runs the semantic checks as of `now`, errors first.
+    pub fn validate_at(&self, now: DateTime<Utc>) -> Vec<Finding> {
+        let mut findings = Vec::new();
+        let mut find = |severity, path: String, message: String| findings.push(Finding { severity, path, message });
+
+        // 1. Timestamps in order.
+        if self.identity.updated_at < self.identity.created_at {
+            find(Severity::Error, "identity.updated_at".to_string(), "the document was updated before it was created".to_string());
+        }
+
+        // 2. Keys: unique ids, and parents that exist.
+        let mut key_ids = HashSet::new();
+        for (i, key) in self.system.public_keys.iter().enumerate() {
+            if !key_ids.insert(key.key_id.as_str()) {
+                find(Severity::Error, format!("system.public_keys.{}.key_id", i), format!("key id '{}' is used more than once", key.key_id));
+            }
+            if let Some(parent) = key.parent_key_id.as_deref().filter(|parent| self.find_key(parent).is_none()) {
+                find(Severity::Error, format!("system.public_keys.{}.parent_key_id", i), format!("parent key '{}' is not in the document", parent));
+            }
+        }
+
+        // 3. Signers: every key this identity signed with is listed, and none is revoked.
+        let signers = self.proofs.iter().enumerate().map(|(i, proof)| (format!("proofs.{}.signed_by.key_id", i), &proof.signed_by));
+        let document_signer = self.signature.iter().map(|signature| ("signature.signed_by.key_id".to_string(), &signature.signed_by));
+        for (path, Signer { idp_id, key_id }) in signers.chain(document_signer) {
+            if *idp_id != self.identity.id {
+                continue;
+            }
+            match self.key_status_at(key_id, now) {
+                Err(_) => find(Severity::Error, path, format!("signed by key '{}', which is not in the document", key_id)),
+                Ok(EffectiveStatus::Revoked) => find(Severity::Warning, path, format!("signed by key '{}', which has been revoked", key_id)),
+                Ok(_) => {}
+            }
+        }
+
+        // 4. Credentials: issued before they expire, and not yet expired.
+        for (i, credential) in self.credentials.iter().enumerate() {
+            let path = format!("credentials.{}.expires_at", i);
+            match credential.expires_at {
+                Some(expires_at) if expires_at < credential.issued_at => {
+                    find(Severity::Error, path, "the credential expires before it was issued".to_string())
+                }
+                Some(expires_at) if credential.is_expired(now) => {
+                    find(Severity::Warning, path, format!("'{}' expired at {}", credential.claim, expires_at.to_rfc3339()))
+                }
+                _ => {}
+            }
+        }
+
+        // 5. Contracts: obligations owed by their parties.
+        for (i, contract) in self.contracts.iter().enumerate() {
+            for (j, problem) in contract.obligation_problems() {
+                find(Severity::Error, format!("contracts.{}.obligations.{}", i, j), problem);
+            }
+        }
+
+        // 6. Reputation: events someone vouches for.
+        for (i, score) in self.reputation.iter().enumerate() {
+            for (j, event) in score.history.iter().enumerate().filter(|(_, event)| !event.is_signed()) {
+                find(Severity::Warning, format!("reputation.{}.history.{}", i, j), format!("'{}' is not signed by an issuer", event.event));
+            }
+        }
+
+        findings.sort_by_key(|finding| finding.severity);
+        findings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Credential, Proof};
+
+    #[test]
+    fn it_reports_semantic_problems() {
+        let (mut identity, _) = Identity::new("Valid User", "Consistent.").unwrap();
+        assert_eq!(identity.validate(), vec![]);
+
+        let now = Utc::now();
+        identity.identity.updated_at = identity.identity.created_at - chrono::Duration::seconds(1);
+        identity.system.public_keys.push(identity.system.public_keys[0].clone());
+        // After the delegation proof of the agreement key.
+        identity.proofs.push(Proof {
+            proof_id: "p-1".to_string(),
+            proof_type: "test".to_string(),
+            claim_hash: String::new(),
+            signed_by: Signer { idp_id: identity.identity.id.clone(), key_id: "missing-key".to_string() },
+            signature: vec![],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        });
+        identity.credentials.push(Credential {
+            claim: "member".to_string(),
+            issued_by: "club".to_string(),
+            issued_at: now - chrono::Duration::days(2),
+            expires_at: Some(now - chrono::Duration::days(1)),
+            proof: String::new(),
+            provenance: None,
+            status: None,
+            unknown_fields: Default::default(),
+        });
+
+        let findings = identity.validate_at(now);
+        assert_eq!(
+            findings.iter().map(|f| (f.severity, f.path.as_str())).collect::<Vec<_>>(),
+            [
+                (Severity::Error, "identity.updated_at"),
+                (Severity::Error, "system.public_keys.2.key_id"),
+                (Severity::Error, "proofs.1.signed_by.key_id"),
+                (Severity::Warning, "credentials.0.expires_at"),
+            ]
+        );
+    }
+}
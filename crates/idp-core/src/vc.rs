@@ -0,0 +1,433 @@
+// crates/idp-core/src/vc.rs
+
+// W3C Verifiable Credentials (VC Data Model 2.0). An issued credential can be written as a VC
+// secured by a Data Integrity proof with the `eddsa-jcs-2022` cryptosuite, or as a JWT with the
+// standard VC claims, so verifiers in the VC ecosystem can check it. The issuer signs those forms
+// too when issuing; they are rebuilt from the credential and those signatures when exported.
+// VCs from other issuers, in that form or as JWTs, are verified and kept as credentials, with
+// the VC itself in their proof so it can be checked again and shown as issued.
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use data_encoding::{BASE64, HEXLOWER};
+use ring::digest;
+use serde_json::{json, Value};
+
+use crate::crypto::{self, VerifyError};
+use crate::jwt::{self, Jws};
+use crate::sd_jwt::{self, SdJwt};
+use crate::status_list;
+use crate::{
+    canonical, multibase, timestamp, Credential, Identity, IdpError, IdpId, KeyFormat, KeyPurpose, KeyStatus, Proof, Provenance, PublicKey,
+    SignatureComponent, Signer,
+};
+
+/// The `@context` every VC 2.0 document starts with.
+pub const VC_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+
+/// The Data Integrity cryptosuite of the VC form, also the `algorithm` of its signature component.
+pub const EDDSA_JCS_2022: &str = "eddsa-jcs-2022";
+
+/// The `Proof.proof_type` of a VC imported from another issuer.
+pub const IMPORTED_VC_PROOF: &str = "W3cVerifiableCredential";
+
+/// `Provenance.format` of an imported VC secured with a Data Integrity proof.
+pub const DATA_INTEGRITY_FORMAT: &str = "vc-data-integrity";
+
+/// `Provenance.format` of an imported VC in JWT form.
+pub const JWT_FORMAT: &str = "vc-jwt";
+
+/// The VC form of a credential held by `subject`, without its proof.
+pub fn unsecured_credential(subject: &IdpId, credential: &Credential) -> Value {
+    let mut document = json!({
+        "@context": [VC_CONTEXT],
+        "id": format!("urn:idp:{}", credential.proof),
+        "type": ["VerifiableCredential"],
+        "issuer": credential.issued_by,
+        "validFrom": xsd_time(&credential.issued_at),
+        "credentialSubject": {
+            "id": subject,
+            "claim": credential.claim,
+        },
+    });
+    if let Some(expires_at) = &credential.expires_at {
+        document["validUntil"] = json!(xsd_time(expires_at));
+    }
+    if let Some(status) = &credential.status {
+        document["credentialStatus"] = status_list::status_entry(status);
+    }
+    document
+}
+
+/// The proof options of the VC form of a credential: everything in its proof but the value.
+pub fn proof_options(credential: &Credential, proof: &Proof) -> Value {
+    json!({
+        "type": "DataIntegrityProof",
+        "cryptosuite": EDDSA_JCS_2022,
+        "created": xsd_time(&credential.issued_at),
+        "verificationMethod": verification_method(proof),
+        "proofPurpose": "assertionMethod",
+    })
+}
+
+/// The header and claims of the JWT form of a credential held by `subject`: the VC in `vc`,
+/// with `iss`, `sub`, `nbf`, `exp` and `jti` standing in for its fields. `None` if the
+/// issuer's key cannot sign JWTs.
+pub fn jwt_parts(subject: &IdpId, credential: &Credential, proof: &Proof) -> Option<(Value, Value)> {
+    let alg = jwt::jws_alg(&proof.signature.first()?.algorithm)?;
+    let header = json!({ "alg": alg, "typ": "JWT", "kid": verification_method(proof) });
+    let mut payload = json!({
+        "iss": credential.issued_by,
+        "sub": subject,
+        "nbf": credential.issued_at.timestamp(),
+        "jti": format!("urn:idp:{}", credential.proof),
+        "vc": {
+            "@context": [VC_CONTEXT],
+            "type": ["VerifiableCredential"],
+            "credentialSubject": { "claim": credential.claim },
+        },
+    });
+    if let Some(expires_at) = credential.expires_at {
+        payload["exp"] = json!(expires_at.timestamp());
+    }
+    if let Some(status) = &credential.status {
+        payload["vc"]["credentialStatus"] = status_list::status_entry(status);
+    }
+    Some((header, payload))
+}
+
+/// Writes a credential held by `subject` as a JWT, from the issuer's `vc-jwt` signature in its proof.
+pub fn to_jwt(subject: &IdpId, credential: &Credential, proof: &Proof) -> Result<String, IdpError> {
+    let not_signed = || IdpError::Credential(format!("'{}' was not signed for export as a JWT; ask the issuer to issue it again", credential.claim));
+    let signature = proof.signature.iter().find(|signature| signature.algorithm == JWT_FORMAT).ok_or_else(not_signed)?;
+    let (header, payload) = jwt_parts(subject, credential, proof).ok_or_else(not_signed)?;
+    jwt::assemble(&jwt::signing_input(&header, &payload), signature)
+}
+
+// The key that signed a credential, as VCs name it.
+fn verification_method(proof: &Proof) -> String {
+    format!("{}#{}", proof.signed_by.idp_id, proof.signed_by.key_id)
+}
+
+/// The bytes an `eddsa-jcs-2022` proof signs: the SHA-256 hash of the canonical proof options
+/// (with the document's `@context`), followed by that of the canonical unsecured document.
+pub fn data_integrity_payload(unsecured: &Value, options: &Value) -> Vec<u8> {
+    let mut options = options.clone();
+    options["@context"] = unsecured["@context"].clone();
+    let mut payload = digest::digest(&digest::SHA256, &canonical::canonicalize(&options)).as_ref().to_vec();
+    payload.extend(digest::digest(&digest::SHA256, &canonical::canonicalize(unsecured)).as_ref());
+    payload
+}
+
+/// Writes a credential held by `subject` as a VC with its Data Integrity proof. The proof must
+/// carry the issuer's `eddsa-jcs-2022` signature, which credentials issued with an Ed25519 key do.
+pub fn to_verifiable_credential(subject: &IdpId, credential: &Credential, proof: &Proof) -> Result<Value, IdpError> {
+    let signature = proof
+        .signature
+        .iter()
+        .find(|signature| signature.algorithm == EDDSA_JCS_2022)
+        .ok_or_else(|| IdpError::Credential(format!("'{}' was not signed for export as a VC; ask the issuer to issue it again", credential.claim)))?;
+    let signature = BASE64
+        .decode(signature.value.as_bytes())
+        .map_err(|_| IdpError::Credential("the VC signature is not valid Base64".to_string()))?;
+
+    let mut document = unsecured_credential(subject, credential);
+    let mut proof = proof_options(credential, proof);
+    proof["proofValue"] = json!(format!("z{}", bs58::encode(signature).into_string()));
+    document["proof"] = proof;
+    Ok(document)
+}
+
+/// Checks the `eddsa-jcs-2022` Data Integrity proof of a VC against the issuer's key.
+pub fn verify_data_integrity(document: &Value, public_key: &PublicKey) -> Result<(), IdpError> {
+    // 1. Split the proof off, and its value off the proof.
+    let mut unsecured = document.clone();
+    let Some(mut options) = unsecured.as_object_mut().and_then(|document| document.remove("proof")) else {
+        return Err(IdpError::Credential("the VC has no proof".to_string()));
+    };
+    if options["type"] != "DataIntegrityProof" || options["cryptosuite"] != EDDSA_JCS_2022 {
+        return Err(IdpError::Credential(format!("only {} Data Integrity proofs are supported", EDDSA_JCS_2022)));
+    }
+    let proof_value = options.as_object_mut().and_then(|options| options.remove("proofValue"));
+    let signature = match proof_value.as_ref().and_then(Value::as_str).and_then(|value| value.strip_prefix('z')) {
+        Some(encoded) => bs58::decode(encoded).into_vec().map_err(|_| VerifyError::MalformedEncoding("proofValue".to_string()))?,
+        None => return Err(VerifyError::MalformedEncoding("proofValue".to_string()).into()),
+    };
+
+    // 2. Verify the signature over the rebuilt payload with the key, as an ordinary Ed25519 signature.
+    let signature = SignatureComponent { algorithm: public_key.algorithm.clone(), value: BASE64.encode(&signature) };
+    Ok(crypto::verify(public_key, &data_integrity_payload(&unsecured, &options), &signature)?)
+}
+
+/// The key a `did:key` DID or verification method (`did:key:z6Mk...#z6Mk...`) stands for.
+pub fn resolve_did_key(verification_method: &str) -> Result<PublicKey, IdpError> {
+    let did = verification_method.split('#').next().unwrap_or_default();
+    let Some(value) = did.strip_prefix("did:key:") else {
+        return Err(IdpError::Credential(format!("the key of '{}' cannot be looked up; give the issuer's key", verification_method)));
+    };
+    let (algorithm, _) = multibase::decode(value)?;
+    Ok(PublicKey {
+        key_id: verification_method.to_string(),
+        algorithm: algorithm.to_string(),
+        value: value.to_string(),
+        format: KeyFormat::Multibase,
+        status: KeyStatus::Active,
+        parent_key_id: None,
+        purpose: KeyPurpose::Signing,
+        expires_at: None,
+        unknown_fields: Default::default(),
+    })
+}
+
+// A VC whose signature has been checked, taken out of its envelope.
+struct OpenedCredential {
+    format: &'static str,
+    verification_method: String,
+    document: Value,
+    // What was signed, as kept in the proof: the canonical JSON of the VC, or the JWT.
+    original: String,
+}
+
+// Checks the signature of a VC in JSON or JWT form, with `issuer_key` or else the did:key it names.
+fn open(vc: &str, issuer_key: Option<&PublicKey>) -> Result<OpenedCredential, IdpError> {
+    let key_for = |method: &str| match issuer_key {
+        Some(key) => Ok(key.clone()),
+        None => resolve_did_key(method),
+    };
+    let missing = |what: &str| IdpError::Credential(format!("the VC has no {}", what));
+
+    // 1. Verify, in whichever form the VC came.
+    let opened = match vc.trim_start().starts_with('{') {
+        true => {
+            let document: Value = serde_json::from_str(vc)?;
+            let method = document["proof"]["verificationMethod"].as_str().ok_or_else(|| missing("proof.verificationMethod"))?.to_string();
+            verify_data_integrity(&document, &key_for(&method)?)?;
+            let original = String::from_utf8(canonical::canonicalize(&document)).expect("canonical JSON is UTF-8");
+            OpenedCredential { format: DATA_INTEGRITY_FORMAT, verification_method: method, document, original }
+        }
+        false => {
+            let jws = Jws::decode(vc)?;
+            let issuer = jws.payload["iss"].as_str().unwrap_or_default();
+            let method = match jws.header["kid"].as_str() {
+                Some(kid) if kid.starts_with('#') => format!("{}{}", issuer, kid),
+                Some(kid) => kid.to_string(),
+                None if !issuer.is_empty() => issuer.to_string(),
+                None => return Err(missing("`kid` or `iss` naming its key")),
+            };
+            jws.verify(&key_for(&method)?)?;
+            OpenedCredential { format: JWT_FORMAT, verification_method: method, document: jwt_document(&jws.payload), original: vc.trim().to_string() }
+        }
+    };
+
+    // 2. The key must be the issuer's own.
+    let issuer = issuer_of(&opened.document).ok_or_else(|| missing("issuer"))?;
+    if opened.verification_method.split('#').next() != Some(issuer) {
+        return Err(IdpError::Credential(format!("it is signed by '{}', which is not a key of its issuer '{}'", opened.verification_method, issuer)));
+    }
+    Ok(opened)
+}
+
+// The VC in a JWT: the payload itself, or in the older form, its `vc` claim with the
+// registered claims standing in for the fields they replace.
+fn jwt_document(payload: &Value) -> Value {
+    let Some(vc) = payload.get("vc") else {
+        return payload.clone();
+    };
+    let mut document = vc.clone();
+    let time = |claim: &str| payload[claim].as_i64().and_then(|seconds| DateTime::from_timestamp(seconds, 0)).map(|time| json!(xsd_time(&time)));
+    if let Some(issuer) = payload.get("iss") {
+        document["issuer"] = issuer.clone();
+    }
+    if let Some(valid_from) = time("nbf").or_else(|| time("iat")) {
+        document["validFrom"] = valid_from;
+    }
+    if let Some(valid_until) = time("exp") {
+        document["validUntil"] = valid_until;
+    }
+    if let (Some(subject), Some(_)) = (payload.get("sub"), document.get("credentialSubject")) {
+        document["credentialSubject"]["id"] = subject.clone();
+    }
+    document
+}
+
+fn issuer_of(document: &Value) -> Option<&str> {
+    document["issuer"].as_str().or_else(|| document["issuer"]["id"].as_str())
+}
+
+impl Identity {
+    /// Verifies a VC another issuer gave this identity, as JSON with an `eddsa-jcs-2022` proof or as
+    /// a JWT, and adds it as a credential. `issuer_key` is needed unless the issuer signs with a
+    /// did:key. The VC must be current, and about this identity if it names a subject.
+    pub fn import_verifiable_credential(&mut self, vc: &str, issuer_key: Option<&PublicKey>) -> Result<&Credential, IdpError> {
+        let opened = open(vc, issuer_key)?;
+        let document = &opened.document;
+        let issuer = issuer_of(document).expect("checked when opened").to_string();
+
+        // 1. One subject, this identity (by its id or the did:key of one of its keys) if named.
+        let subject = match &document["credentialSubject"] {
+            Value::Array(subjects) if subjects.len() == 1 => &subjects[0],
+            subject @ Value::Object(_) => subject,
+            _ => return Err(IdpError::Credential("only VCs about one subject can be imported".to_string())),
+        };
+        if let Some(id) = subject["id"].as_str() {
+            let own_dids = self.system.public_keys.iter().filter_map(|key| key.to_multibase().ok()).map(|value| format!("did:key:{}", value));
+            if id != self.identity.id.as_str() && !own_dids.into_iter().any(|did| did == id) {
+                return Err(IdpError::Credential(format!("the VC is about '{}', not this identity", id)));
+            }
+        }
+
+        // 2. The claim: a plain `claim` as it is, anything else as canonical JSON.
+        let mut claims = subject.as_object().expect("subjects are objects").clone();
+        claims.remove("id");
+        let claim = match claims.get("claim").and_then(Value::as_str) {
+            Some(claim) if claims.len() == 1 => claim.to_string(),
+            _ => String::from_utf8(canonical::canonicalize(&Value::Object(claims))).expect("canonical JSON is UTF-8"),
+        };
+        let time = |fields: [&str; 2]| match fields.iter().find_map(|field| document[field].as_str()) {
+            Some(text) => timestamp::parse(text).map(Some).ok_or_else(|| IdpError::Credential(format!("invalid time '{}'", text))),
+            None => Ok(None),
+        };
+        let issued_at = time(["validFrom", "issuanceDate"])?.ok_or_else(|| IdpError::Credential("the VC has no validFrom".to_string()))?;
+        let expires_at = time(["validUntil", "expirationDate"])?;
+        if expires_at.is_some_and(|expires_at| expires_at <= Utc::now()) {
+            return Err(IdpError::Credential(format!("the VC expired at {}", expires_at.expect("checked").to_rfc3339())));
+        }
+        let status = document.get("credentialStatus").and_then(status_list::parse_status_entry);
+
+        // 3. Keep the VC itself in the proof, so it can be checked again later.
+        let proof_id = format!("vc-{}", HEXLOWER.encode(&digest::digest(&digest::SHA256, opened.original.as_bytes()).as_ref()[..8]));
+        if self.proofs.iter().any(|proof| proof.proof_id == proof_id) {
+            return Err(IdpError::Credential(format!("the VC '{}' is already in the document", proof_id)));
+        }
+        self.proofs.push(Proof {
+            proof_id: proof_id.clone(),
+            proof_type: IMPORTED_VC_PROOF.to_string(),
+            claim_hash: canonical::hash(opened.original.as_bytes()),
+            signed_by: Signer { idp_id: IdpId::parse(&issuer)?, key_id: opened.verification_method.clone() },
+            signature: vec![SignatureComponent { algorithm: opened.format.to_string(), value: opened.original }],
+            domain: None,
+            challenge: None,
+            unknown_fields: Default::default(),
+        });
+        self.credentials.push(Credential {
+            claim,
+            issued_by: issuer,
+            issued_at,
+            expires_at,
+            proof: proof_id,
+            provenance: Some(Provenance { format: opened.format.to_string(), verification_method: opened.verification_method, imported_at: Utc::now() }),
+            status,
+            unknown_fields: Default::default(),
+        });
+        self.touch();
+        Ok(self.credentials.last().expect("just added"))
+    }
+
+    /// Checks the signature of an imported VC (or SD-JWT) again, from the copy kept in its proof.
+    /// SD-JWTs need the issuer's key.
+    pub fn verify_imported_credential(&self, proof: &Proof, issuer_key: Option<&PublicKey>) -> Result<(), IdpError> {
+        match (proof.proof_type.as_str(), proof.signature.first()) {
+            (IMPORTED_VC_PROOF, Some(signature)) => open(&signature.value, issuer_key).map(|_| ()),
+            (sd_jwt::SD_JWT_PROOF, Some(signature)) => {
+                let issuer_key = issuer_key.ok_or_else(|| IdpError::Credential(format!("the key '{}' is needed to check the SD-JWT", proof.signed_by.key_id)))?;
+                SdJwt::parse(&signature.value)?.verify(issuer_key).map(|_| ())
+            }
+            _ => Err(IdpError::Credential(format!("'{}' is not the proof of an imported VC", proof.proof_id))),
+        }
+    }
+}
+
+// VC timestamps are XML Schema dateTimes: RFC 3339 with a `Z`.
+fn xsd_time(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::AutoSi, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Identity;
+
+    #[test]
+    fn it_exports_credentials_as_verifiable_credentials() {
+        let (issuer, issuer_key) = Identity::new("Issuer", "Issues VCs.").unwrap();
+        let (subject, _) = Identity::new("Subject", "Holds VCs.").unwrap();
+        let expires_at = Utc::now() + chrono::Duration::days(30);
+        let issued = issuer.issue_credential(&subject, "licensed pilot", Some(expires_at), &issuer_key).unwrap();
+
+        let vc = to_verifiable_credential(&subject.identity.id, &issued.credential, &issued.proof).unwrap();
+        assert_eq!(vc["credentialSubject"]["claim"], "licensed pilot");
+        assert_eq!(vc["issuer"], issuer.identity.id.as_str());
+        assert!(vc["proof"]["proofValue"].as_str().unwrap().starts_with('z'));
+        verify_data_integrity(&vc, &issuer.system.public_keys[0]).unwrap();
+
+        // Any change to the VC breaks its proof.
+        let mut changed = vc.clone();
+        changed["credentialSubject"]["claim"] = json!("licensed astronaut");
+        assert!(verify_data_integrity(&changed, &issuer.system.public_keys[0]).is_err());
+
+        // Without the issuer's VC signature, there is nothing to export.
+        let mut proof = issued.proof.clone();
+        proof.signature.truncate(1);
+        assert!(matches!(to_verifiable_credential(&subject.identity.id, &issued.credential, &proof), Err(IdpError::Credential(_))));
+    }
+
+    #[test]
+    fn it_exports_credentials_as_jwts() {
+        for algorithm in [crypto::ED25519, crypto::P256] {
+            let (issuer, issuer_key) = Identity::new_with_algorithm("Issuer", "Issues JWTs.", algorithm).unwrap();
+            let (mut holder, _) = Identity::new("Holder", "Presents JWTs.").unwrap();
+            let issued = issuer.issue_credential(&holder, "employee", None, &issuer_key).unwrap();
+
+            // The JWT carries the standard claims, and reads back as the same credential.
+            let token = to_jwt(&holder.identity.id, &issued.credential, &issued.proof).unwrap();
+            let jws = Jws::decode(&token).unwrap();
+            assert_eq!((jws.alg(), jws.payload["sub"].as_str()), (jwt::jws_alg(algorithm).unwrap(), Some(holder.identity.id.as_str())));
+            assert_eq!(jws.payload["vc"]["credentialSubject"]["claim"], "employee");
+            let imported = holder.import_verifiable_credential(&token, Some(&issuer.system.public_keys[0])).unwrap();
+            assert_eq!((imported.claim.as_str(), imported.issued_at.timestamp()), ("employee", issued.credential.issued_at.timestamp()));
+        }
+    }
+
+    #[test]
+    fn it_imports_verifiable_credentials_from_did_key_issuers() {
+        use crate::signer::SigningBackend;
+        use data_encoding::BASE64URL_NOPAD;
+
+        let (issuer, issuer_key) = Identity::new("University", "Issues degrees.").unwrap();
+        let did = format!("did:key:{}", issuer.system.public_keys[0].to_multibase().unwrap());
+        let method = format!("{}#{}", did, did.trim_start_matches("did:key:"));
+        let (mut holder, _) = Identity::new("Graduate", "Holds a degree.").unwrap();
+
+        // 1. A VC with a Data Integrity proof, about a structured claim.
+        let mut vc = json!({
+            "@context": [VC_CONTEXT],
+            "type": ["VerifiableCredential"],
+            "issuer": did,
+            "validFrom": "2024-06-01T00:00:00Z",
+            "credentialSubject": { "id": holder.identity.id, "degree": { "name": "BSc" } },
+        });
+        let options = json!({ "type": "DataIntegrityProof", "cryptosuite": EDDSA_JCS_2022, "verificationMethod": method, "proofPurpose": "assertionMethod" });
+        let signature = BASE64.decode(issuer_key.sign(&data_integrity_payload(&vc, &options)).unwrap().value.as_bytes()).unwrap();
+        vc["proof"] = options;
+        vc["proof"]["proofValue"] = json!(format!("z{}", bs58::encode(signature).into_string()));
+        let credential = holder.import_verifiable_credential(&vc.to_string(), None).unwrap().clone();
+        assert_eq!(credential.claim, r#"{"degree":{"name":"BSc"}}"#);
+        assert_eq!(credential.provenance.as_ref().unwrap().format, DATA_INTEGRITY_FORMAT);
+        assert!(matches!(holder.import_verifiable_credential(&vc.to_string(), None), Err(IdpError::Credential(_))));
+        holder.verify_imported_credential(holder.credential_proof(&credential).unwrap(), None).unwrap();
+
+        // 2. A JWT in the older `vc` claim form, with a plain claim.
+        let encode = |value: Value| BASE64URL_NOPAD.encode(value.to_string().as_bytes());
+        let payload = json!({ "iss": did, "sub": holder.identity.id, "nbf": 1717200000, "vc": { "credentialSubject": { "claim": "over 18" } } });
+        let signing_input = format!("{}.{}", encode(json!({ "alg": "EdDSA", "kid": method })), encode(payload));
+        let signature = BASE64.decode(issuer_key.sign(signing_input.as_bytes()).unwrap().value.as_bytes()).unwrap();
+        let token = format!("{}.{}", signing_input, BASE64URL_NOPAD.encode(&signature));
+        let credential = holder.import_verifiable_credential(&token, None).unwrap();
+        assert_eq!((credential.claim.as_str(), credential.issued_by.as_str()), ("over 18", did.as_str()));
+
+        // VCs about someone else, or altered after signing, are refused.
+        let (mut stranger, _) = Identity::new("Stranger", "Not a graduate.").unwrap();
+        assert!(matches!(stranger.import_verifiable_credential(&token, None), Err(IdpError::Credential(_))));
+        vc["validFrom"] = json!("2020-01-01T00:00:00Z");
+        assert!(matches!(holder.import_verifiable_credential(&vc.to_string(), None), Err(IdpError::Verify(_))));
+    }
+}
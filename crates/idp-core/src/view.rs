@@ -0,0 +1,98 @@
+// crates/idp-core/src/view.rs
+
+// Minimal views of a document for sharing with verifiers: only the requested paths, plus the
+// identity and system blocks needed to check what is shared. Sealed sections stay sealed.
+
+use serde_yaml::{Mapping, Value};
+
+use crate::{path, Identity, IdpError};
+
+// Always part of a view: who the document belongs to and the keys its proofs are made with.
+const ALWAYS_SHARED: [&str; 2] = ["identity", "system"];
+
+impl Identity {
+    /// A new document holding only `fields` (dot-paths, `*` wildcards, or `credentials[0]`
+    /// style indices). The document signature is left out, since it covers the whole document.
+    pub fn export_view(&self, fields: &[&str]) -> Result<Value, IdpError> {
+        let document = serde_yaml::to_value(self)?;
+
+        // 1. Every requested path must point at something.
+        let fields: Vec<String> = fields.iter().map(|field| field.replace('[', ".").replace(']', "")).collect();
+        for field in &fields {
+            if path::query(&document, field)?.is_empty() {
+                return Err(IdpError::Path { path: field.clone(), reason: "no value at this path".to_string() });
+            }
+        }
+
+        // 2. Keep the selected nodes and their parents, dropping everything else.
+        let mut selected: Vec<Vec<&str>> = ALWAYS_SHARED.iter().map(|block| vec![*block]).collect();
+        for field in &fields {
+            selected.push(path::parse(field)?);
+        }
+        Ok(select(&document, &selected).unwrap_or(Value::Null))
+    }
+}
+
+// The parts of `node` the remaining segments of `paths` reach; lists keep their order but close
+// the gaps left by items that were not selected.
+fn select(node: &Value, paths: &[Vec<&str>]) -> Option<Value> {
+    if paths.iter().any(Vec::is_empty) {
+        return Some(node.clone());
+    }
+    let child = |value: &Value, segment: &str| -> Option<Value> {
+        let below: Vec<Vec<&str>> = paths.iter().filter(|path| path[0] == "*" || path[0] == segment).map(|path| path[1..].to_vec()).collect();
+        (!below.is_empty()).then(|| select(value, &below)).flatten()
+    };
+    match node {
+        Value::Mapping(map) => {
+            let mut kept = Mapping::new();
+            for (key, value) in map {
+                if let Some(value) = child(value, key.as_str().unwrap_or_default()) {
+                    kept.insert(key.clone(), value);
+                }
+            }
+            (!kept.is_empty()).then_some(Value::Mapping(kept))
+        }
+        Value::Sequence(items) => {
+            let kept: Vec<Value> = items.iter().enumerate().filter_map(|(i, item)| child(item, &i.to_string())).collect();
+            (!kept.is_empty()).then_some(Value::Sequence(kept))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Credential;
+
+    #[test]
+    fn it_exports_only_the_requested_paths() {
+        let (mut identity, _) = Identity::new("View User", "Private bio.").unwrap();
+        for claim in ["over-18", "member"] {
+            identity.credentials.push(Credential {
+                claim: claim.to_string(),
+                issued_by: "issuer".to_string(),
+                issued_at: "2024-07-06T10:00:00Z".parse().unwrap(),
+                expires_at: None,
+                proof: String::new(),
+                provenance: None,
+                status: None,
+                unknown_fields: Default::default(),
+            });
+        }
+
+        let view = identity.export_view(&["core.name", "credentials[1]"]).unwrap();
+        let mut keys: Vec<&str> = view.as_mapping().unwrap().keys().filter_map(Value::as_str).collect();
+        keys.sort();
+        assert_eq!(keys, ["core", "credentials", "identity", "system"]);
+        assert_eq!(view["core"].as_mapping().unwrap().len(), 1);
+        assert_eq!(view["credentials"].as_sequence().unwrap().len(), 1);
+        assert_eq!(view["credentials"][0]["claim"], Value::from("member"));
+
+        // Wildcards select from every entry; paths that reach nothing are errors.
+        let view = identity.export_view(&["credentials.*.claim"]).unwrap();
+        assert_eq!(view["credentials"][1], serde_yaml::from_str::<Value>("claim: member").unwrap());
+        assert!(matches!(identity.export_view(&["reputation"]), Err(IdpError::Path { .. })));
+    }
+}